@@ -28,9 +28,6 @@ type TVmCallbacks = {
   onScriptErrorCleared: Function,
   onGlobalListChanged: (globals: Map<string, JsBridgeDatum>) => void,
   onDebugMessage: (message: string) => void,
-  onScheduleTimeout: (timeoutName: string, periodMs: number) => void,
-  onClearTimeout: (timeoutName: string) => void,
-  onClearAllTimeouts: () => void,
   onDatumSnapshot: (datumRef: DatumRef, datum: JsBridgeDatum) => void,
   onScriptInstanceSnapshot: (scriptInstanceRef: ScriptInstanceId, scriptInstance: JsBridgeDatum) => void,
   onChannelChanged: (channelNumber: number, channelData: ScoreSpriteSnapshot) => void,
@@ -55,18 +55,6 @@ export function onDebugMessage(message) {
   vmCallbacks.onDebugMessage(message)
 }
 
-export function onScheduleTimeout(name, period) {
-  vmCallbacks.onScheduleTimeout(name, period)
-}
-
-export function onClearTimeout(name) {
-  vmCallbacks.onClearTimeout(name)
-}
-
-export function onClearAllTimeouts() {
-  vmCallbacks.onClearAllTimeouts()
-}
-
 export function onDatumSnapshot(datumRef, snapshot) {
   vmCallbacks.onDatumSnapshot(datumRef, snapshot)
 }
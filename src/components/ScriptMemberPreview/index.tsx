@@ -62,6 +62,7 @@ export default function ScriptMemberPreview({
   const [expandedHandlerNames, setExpandedHandlerNames] = useState<string[]>(
     []
   );
+  const [showBytecode, setShowBytecode] = useState(false);
 
   const onToggleHandler = (handlerName: string) => {
     if (expandedHandlerNames.includes(handlerName)) {
@@ -75,12 +76,34 @@ export default function ScriptMemberPreview({
 
   return (
     <div className={styles.scriptContainer}>
+      <button onClick={() => setShowBytecode(!showBytecode)}>
+        {showBytecode ? "Show Lingo" : "Show bytecode"}
+      </button>
       {snapshot.script.handlers.map((handler) => {
         const isExpanded = expandedHandlerNames.includes(handler.name);
         const isHandlerHighlighted = highlightedHandlerName === handler.name;
         const isHandlerInBg = backgroundScopes.some(
           ([name, _, scriptMemRef]) => name === handler.name && memberId.castNumber === scriptMemRef[0] && memberId.memberNumber === scriptMemRef[1]
         );
+        const handlerBreakpoints = breakpoints.filter(
+          (bp) =>
+            bp.script_name === snapshot.name &&
+            bp.handler_name === handler.name
+        );
+        // The first line is the `on` line, which the handler button already shows.
+        const lingoLines = handler.lingo.slice(1);
+        const onLingoBreakpointClick = (lineIndex: number, bytecodeIndex: number) => {
+          const lineBreakpoints = handlerBreakpoints.filter(
+            (bp) => handler.bytecodeLines[bp.bytecode_index] === lineIndex
+          );
+          if (lineBreakpoints.length > 0) {
+            lineBreakpoints.forEach((bp) =>
+              toggle_breakpoint(snapshot.name, handler.name, bp.bytecode_index)
+            );
+          } else {
+            toggle_breakpoint(snapshot.name, handler.name, bytecodeIndex);
+          }
+        };
         return (
           <div>
             <button
@@ -93,7 +116,32 @@ export default function ScriptMemberPreview({
             >
               on {handler.name} {handler.args.join(", ")}
             </button>
-            {isExpanded &&
+            {isExpanded && !showBytecode &&
+              lingoLines.map((line, i) => {
+                const lineIndex = i + 1;
+                const { bytecodeIndex } = line;
+                return (
+                  <BytecodeLine
+                    hasBreakpoint={handlerBreakpoints.some(
+                      (bp) => handler.bytecodeLines[bp.bytecode_index] === lineIndex
+                    )}
+                    text={line.text}
+                    key={lineIndex}
+                    isHighlighted={
+                      isHandlerHighlighted &&
+                      highlightedBytecodeIndex !== undefined &&
+                      handler.bytecodeLines[highlightedBytecodeIndex] === lineIndex
+                    }
+                    isInBackground={backgroundScopes.some(([name, idx, scriptMemRef]) => name === handler.name && handler.bytecodeLines[idx] === lineIndex && memberId.castNumber === scriptMemRef[0] && memberId.memberNumber === scriptMemRef[1])}
+                    onBreakpointClick={() => {
+                      if (bytecodeIndex !== undefined) {
+                        onLingoBreakpointClick(lineIndex, bytecodeIndex);
+                      }
+                    }}
+                  />
+                );
+              })}
+            {isExpanded && showBytecode &&
               handler.bytecode.map((bytecode, i) => (
                 <BytecodeLine
                   hasBreakpoint={breakpoints.some(
@@ -113,7 +161,7 @@ export default function ScriptMemberPreview({
                   }
                 />
               ))}
-            {isExpanded && <p className={styles.handlerName}>end</p>}
+            {isExpanded && showBytecode && <p className={styles.handlerName}>end</p>}
             {isExpanded && <p className={styles.handlerName}>&nbsp;</p>}
           </div>
         );
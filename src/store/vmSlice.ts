@@ -17,7 +17,6 @@ interface VMSliceState {
   scriptError?: string
   breakpoints: JsBridgeBreakpoint[],
   globals: Record<string, DatumRef>,
-  timeoutHandles: Record<string, NodeJS.Timer>,
   datumSnapshots: Record<DatumRef, JsBridgeDatum>,
   scriptInstanceSnapshots: Record<ScriptInstanceId, JsBridgeDatum>,
   channelSnapshots: Record<number, ScoreSpriteSnapshot>,
@@ -33,7 +32,6 @@ const initialState: VMSliceState = {
   scopes: [],
   breakpoints: [],
   globals: {},
-  timeoutHandles: {},
   datumSnapshots: {},
   scriptInstanceSnapshots: {},
   channelSnapshots: {},
@@ -150,23 +148,6 @@ const vmSlice = createSlice({
         globals: action.payload,
       }
     },
-    setTimeoutHandle: (state, action: PayloadAction<{ name: string, handle: NodeJS.Timer }>) => {
-      return {
-        ...state,
-        timeoutHandles: {
-          ...state.timeoutHandles,
-          [action.payload.name]: action.payload.handle,
-        }
-      }
-    },
-    removeTimeoutHandle: (state, action: PayloadAction<string>) => {
-      const newHandles = { ...state.timeoutHandles }
-      delete newHandles[action.payload]
-      return {
-        ...state,
-        timeoutHandles: newHandles,
-      }
-    },
     datumSnapshot: (state, action: PayloadAction<{ datumRef: DatumRef, datum: JsBridgeDatum }>) => {
       return {
         ...state,
@@ -239,5 +220,5 @@ export const selectBreakpoints = (state: VMSliceState, scriptName?: string) => s
 export const selectGlobals = (state: VMSliceState) => state.globals
 
 // Action creators are generated for each case reducer function
-export const { ready, castListChanged, castLibNameChanged, castMemberListChanged, scoreChanged, frameChanged, scopeListChanged, onScriptError, breakpointListChanged, scriptErrorCleared, globalsChanged, setTimeoutHandle, removeTimeoutHandle, datumSnapshot, scriptInstanceSnapshot, channelChanged, memberSubscribed, memberUnsubscribed, castMemberChanged, channelDisplayNameChanged, movieLoaded } = vmSlice.actions
+export const { ready, castListChanged, castLibNameChanged, castMemberListChanged, scoreChanged, frameChanged, scopeListChanged, onScriptError, breakpointListChanged, scriptErrorCleared, globalsChanged, datumSnapshot, scriptInstanceSnapshot, channelChanged, memberSubscribed, memberUnsubscribed, castMemberChanged, channelDisplayNameChanged, movieLoaded } = vmSlice.actions
 export default vmSlice.reducer
@@ -1,7 +1,7 @@
 import { ICastMemberRef, JsBridgeBreakpoint, OnScriptErrorData, registerVmCallbacks } from "dirplayer-js-api";
 import store from "../store";
-import { breakpointListChanged, castLibNameChanged, castListChanged, castMemberChanged, castMemberListChanged, channelChanged, channelDisplayNameChanged, datumSnapshot, frameChanged, globalsChanged, movieLoaded, onScriptError, removeTimeoutHandle, scopeListChanged, scoreChanged, scriptErrorCleared, scriptInstanceSnapshot, setTimeoutHandle } from "../store/vmSlice";
-import { OnMovieLoadedCallbackData, trigger_timeout } from 'vm-rust'
+import { breakpointListChanged, castLibNameChanged, castListChanged, castMemberChanged, castMemberListChanged, channelChanged, channelDisplayNameChanged, datumSnapshot, frameChanged, globalsChanged, movieLoaded, onScriptError, scopeListChanged, scoreChanged, scriptErrorCleared, scriptInstanceSnapshot } from "../store/vmSlice";
+import { OnMovieLoadedCallbackData } from 'vm-rust'
 import { DatumRef, IVMScope, JsBridgeDatum, MemberSnapshot, ScoreSnapshot, ScoreSpriteSnapshot } from ".";
 import { onMemberSelected } from "../store/uiSlice";
 import { isUIShown } from "../utils/debug";
@@ -58,27 +58,6 @@ export function initVmCallbacks() {
     onDebugMessage: (message: string) => {
       console.log("-- ", message);
     },
-    onScheduleTimeout: (timeoutName: string, periodMs: number) => {
-      const handle = setInterval(() => {
-        trigger_timeout(timeoutName)
-      }, periodMs);
-      store.dispatch(setTimeoutHandle({ name: timeoutName, handle }))
-    },
-    onClearTimeout: (timeoutName: string) => {
-      const handle = store.getState().vm.timeoutHandles[timeoutName];
-      if (handle) {
-        clearInterval(handle as Parameters<typeof clearInterval>[0]);
-        store.dispatch(removeTimeoutHandle(timeoutName))
-      }
-    },
-    onClearAllTimeouts: () => {
-      const handles = store.getState().vm.timeoutHandles;
-      Object.keys(handles).forEach((key) => {
-        clearInterval(handles[key] as Parameters<typeof clearInterval>[0]);
-        store.dispatch(removeTimeoutHandle(key))
-      })
-      console.log("Cleared all timeouts");
-    },
     onDatumSnapshot: (datumRef: DatumRef, datum: JsBridgeDatum) => {
       store.dispatch(datumSnapshot({ datumRef, datum }));
     },
@@ -130,6 +130,8 @@ export interface IHandlerSnapshot {
   name: string
   args: string[],
   bytecode: IBytecodeSnapshot[],
+  lingo: ILingoLineSnapshot[],
+  bytecodeLines: number[],
 }
 
 export interface IBytecodeSnapshot {
@@ -137,6 +139,11 @@ export interface IBytecodeSnapshot {
   text: string
 }
 
+export interface ILingoLineSnapshot {
+  text: string
+  bytecodeIndex?: number
+}
+
 export interface IUnknownMemberSnapshot {
   type: 'unknown'
 }
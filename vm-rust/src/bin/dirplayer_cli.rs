@@ -0,0 +1,50 @@
+use std::{path::PathBuf, process::ExitCode};
+
+use vm_rust::platform::headless::{run_headless, HeadlessOptions};
+
+const USAGE: &str = "Usage: dirplayer-cli <movie.dcr> [--frames N] [--out stage.png] [--system-font charmap-system.png] [--deterministic] [--data-dir DIR]";
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<HeadlessOptions, String> {
+  let mut movie_path = None;
+  let mut frames = 1;
+  let mut output_path = PathBuf::from("stage.png");
+  let mut system_font_path = None;
+  let mut deterministic = false;
+  let mut data_dir = None;
+
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--frames" => {
+        let value = args.next().ok_or("Missing value for --frames")?;
+        frames = value.parse().map_err(|_| format!("Invalid frame count {}", value))?;
+      }
+      "--out" => output_path = args.next().ok_or("Missing value for --out")?.into(),
+      "--system-font" => system_font_path = Some(args.next().ok_or("Missing value for --system-font")?.into()),
+      "--deterministic" => deterministic = true,
+      "--data-dir" => data_dir = Some(args.next().ok_or("Missing value for --data-dir")?.into()),
+      "-h" | "--help" => return Err(USAGE.to_string()),
+      _ if movie_path.is_none() && !arg.starts_with("--") => movie_path = Some(PathBuf::from(arg)),
+      _ => return Err(format!("Unexpected argument {}\n{}", arg, USAGE)),
+    }
+  }
+
+  Ok(HeadlessOptions {
+    movie_path: movie_path.ok_or(USAGE)?,
+    frames,
+    output_path,
+    system_font_path,
+    deterministic,
+    data_dir,
+  })
+}
+
+fn main() -> ExitCode {
+  let result = parse_args(std::env::args().skip(1)).and_then(run_headless);
+  match result {
+    Ok(()) => ExitCode::SUCCESS,
+    Err(err) => {
+      eprintln!("{}", err);
+      ExitCode::FAILURE
+    }
+  }
+}
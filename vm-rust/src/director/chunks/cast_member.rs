@@ -1,6 +1,7 @@
 use binary_reader::{BinaryReader, Endian};
 
-use crate::director::{chunks::cast_member_info::CastMemberInfoChunk, enums::{BitmapInfo, MemberType, ScriptType, ShapeInfo}};
+use crate::director::{chunks::cast_member_info::CastMemberInfoChunk, enums::{BitmapInfo, FieldInfo, FilmLoopInfo, MemberType, ScriptType, ShapeInfo}};
+use crate::io::reader::DirectorExt;
 
 use super::Chunk;
 
@@ -91,6 +92,23 @@ impl CastMemberChunk {
           ShapeInfo::from(specific_data.as_slice())
         );
       }
+      MemberType::Text => {
+        specific_data_parsed = CastMemberSpecificData::Field(
+          FieldInfo::from(specific_data.as_slice())
+        );
+      }
+      MemberType::FilmLoop => {
+        specific_data_parsed = CastMemberSpecificData::FilmLoop(
+          FilmLoopInfo::from(specific_data.as_slice())
+        );
+      }
+      MemberType::Xtra => {
+        // The data starts with the length and name of the Xtra the member belongs to, such as `text`
+        let name_len = specific_reader.read_u32().unwrap_or(0) as usize;
+        specific_data_parsed = CastMemberSpecificData::Xtra(
+          specific_reader.read_string(name_len).unwrap_or_default()
+        );
+      }
       _ => {
         specific_data_parsed = CastMemberSpecificData::None;
       }
@@ -108,10 +126,21 @@ pub enum CastMemberSpecificData {
   Script(ScriptType),
   Bitmap(BitmapInfo),
   Shape(ShapeInfo),
+  FilmLoop(FilmLoopInfo),
+  Field(FieldInfo),
+  Xtra(String),
   None
 }
 
 impl CastMemberSpecificData {
+  pub fn xtra_name(&self) -> Option<&str> {
+    if let CastMemberSpecificData::Xtra(name) = self {
+      Some(name)
+    } else {
+      None
+    }
+  }
+
   pub fn script_type(&self) -> Option<ScriptType> {
     if let CastMemberSpecificData::Script(script_type) = self {
       Some(*script_type)
@@ -135,4 +164,20 @@ impl CastMemberSpecificData {
       None
     }
   }
+
+  pub fn field_info(&self) -> Option<&FieldInfo> {
+    if let CastMemberSpecificData::Field(field_info) = self {
+      Some(field_info)
+    } else {
+      None
+    }
+  }
+
+  pub fn film_loop_info(&self) -> Option<&FilmLoopInfo> {
+    if let CastMemberSpecificData::FilmLoop(film_loop_info) = self {
+      Some(film_loop_info)
+    } else {
+      None
+    }
+  }
 }
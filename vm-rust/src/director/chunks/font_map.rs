@@ -0,0 +1,190 @@
+use binary_reader::BinaryReader;
+
+#[derive(Clone)]
+pub struct FontMapEntry {
+  pub id: u16,
+  pub name: String,
+}
+
+/// Maps the font ids used by text style runs to font names.
+///
+/// Director 4 to 6 store this in `VWFM`, later versions in `Fmap`.
+pub struct FontMapChunk {
+  pub entries: Vec<FontMapEntry>,
+}
+
+impl FontMapChunk {
+  pub fn from_vwfm_reader(reader: &mut BinaryReader, _dir_version: u16) -> Result<FontMapChunk, String> {
+    reader.set_endian(binary_reader::Endian::Big);
+
+    // A table of ids followed by the Pascal string names, in the same order
+    let count = reader.read_u16().map_err(|e| e.to_string())? as usize;
+    let ids = (0..count)
+      .map(|_| reader.read_u16().map_err(|e| e.to_string()))
+      .collect::<Result<Vec<u16>, String>>()?;
+
+    let mut entries = Vec::with_capacity(count);
+    for id in ids {
+      let length = reader.read_u8().map_err(|e| e.to_string())? as usize;
+      let bytes = reader.read_bytes(length).map_err(|e| e.to_string())?;
+      entries.push(FontMapEntry {
+        id,
+        name: String::from_utf8_lossy(bytes).to_string(),
+      });
+    }
+    Ok(FontMapChunk { entries })
+  }
+
+  pub fn from_fmap_reader(reader: &mut BinaryReader, _dir_version: u16) -> Result<FontMapChunk, String> {
+    reader.set_endian(binary_reader::Endian::Big);
+
+    let map_length = reader.read_u32().map_err(|e| e.to_string())? as usize;
+    let _names_length = reader.read_u32().map_err(|e| e.to_string())?;
+    let body_start = reader.pos;
+    let names_start = body_start + map_length;
+    let _ = reader.read_u32().map_err(|e| e.to_string())?;
+    let _ = reader.read_u32().map_err(|e| e.to_string())?;
+    let entries_used = reader.read_u32().map_err(|e| e.to_string())? as usize;
+    let _entries_total = reader.read_u32().map_err(|e| e.to_string())?;
+    let _ = reader.read_u32().map_err(|e| e.to_string())?;
+    let _ = reader.read_u32().map_err(|e| e.to_string())?;
+    let _ = reader.read_u32().map_err(|e| e.to_string())?;
+
+    let mut entries = Vec::with_capacity(entries_used);
+    for _ in 0..entries_used {
+      let name_offset = reader.read_u32().map_err(|e| e.to_string())? as usize;
+      let _platform = reader.read_u16().map_err(|e| e.to_string())?;
+      let id = reader.read_u16().map_err(|e| e.to_string())?;
+      let entry_end = reader.pos;
+
+      reader.jmp(names_start + name_offset);
+      let name_length = reader.read_u32().map_err(|e| e.to_string())? as usize;
+      let bytes = reader.read_bytes(name_length).map_err(|e| e.to_string())?;
+      entries.push(FontMapEntry {
+        id,
+        name: String::from_utf8_lossy(bytes).to_string(),
+      });
+      reader.jmp(entry_end);
+    }
+    Ok(FontMapChunk { entries })
+  }
+}
+
+#[derive(Clone)]
+pub struct FontSubstitution {
+  pub from_platform: String,
+  pub from_name: String,
+  pub to_platform: String,
+  pub to_name: String,
+}
+
+/// The `FXmp` chunk, a copy of the authoring tool's `fontmap.txt` describing which font
+/// replaces a font from another platform, e.g. `Mac:Geneva => Win:"MS Sans Serif"`.
+pub struct FontXPlatformMapChunk {
+  pub substitutions: Vec<FontSubstitution>,
+}
+
+impl FontXPlatformMapChunk {
+  pub fn from_reader(reader: &mut BinaryReader, _dir_version: u16) -> Result<FontXPlatformMapChunk, String> {
+    let bytes = reader.read_bytes(reader.length).map_err(|e| e.to_string())?;
+    let text = String::from_utf8_lossy(bytes);
+    let substitutions = text
+      .split(['\r', '\n'])
+      .filter_map(Self::parse_line)
+      .collect();
+    Ok(FontXPlatformMapChunk { substitutions })
+  }
+
+  /// Reads `Platform:Name` or `Platform:"Quoted Name"` from the start of `text`, ignoring anything after it.
+  fn parse_font(text: &str) -> Option<(String, String)> {
+    let (platform, rest) = text.trim_start().split_once(':')?;
+    let name = match rest.strip_prefix('"') {
+      Some(quoted) => quoted.split_once('"')?.0,
+      None => rest.split(char::is_whitespace).next().unwrap_or(rest),
+    };
+    Some((platform.trim().to_string(), name.to_string()))
+  }
+
+  fn parse_line(line: &str) -> Option<FontSubstitution> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with(';') {
+      return None;
+    }
+    let (from, to) = line.split_once("=>")?;
+    let (from_platform, from_name) = Self::parse_font(from)?;
+    let (to_platform, to_name) = Self::parse_font(to)?;
+    Some(FontSubstitution {
+      from_platform,
+      from_name,
+      to_platform,
+      to_name,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn entries(chunk: &FontMapChunk) -> Vec<(u16, &str)> {
+    chunk.entries.iter().map(|x| (x.id, x.name.as_str())).collect()
+  }
+
+  #[test]
+  fn reads_vwfm_ids_and_names() {
+    let mut data = vec![0, 2, 0, 3, 0, 200];
+    data.extend_from_slice(b"\x06Geneva\x09Helvetica");
+    let mut reader = BinaryReader::from_vec(&data);
+    let chunk = FontMapChunk::from_vwfm_reader(&mut reader, 500).unwrap();
+    assert_eq!(entries(&chunk), vec![(3, "Geneva"), (200, "Helvetica")]);
+
+    // The names are cut short
+    let mut reader = BinaryReader::from_u8(&data[..12]);
+    assert!(FontMapChunk::from_vwfm_reader(&mut reader, 500).is_err());
+  }
+
+  #[test]
+  fn reads_fmap_entries_from_the_name_table() {
+    let names: [(&[u8], u16); 2] = [(b"Arial", 1), (b"Times New Roman", 1025)];
+    let mut map = vec![];
+    for value in [0u32, 0, names.len() as u32, names.len() as u32, 0, 0, 0] {
+      map.extend_from_slice(&value.to_be_bytes());
+    }
+    let mut name_table = vec![];
+    // The entries list the names in reverse order, to check that the offsets are followed
+    let mut name_offsets = vec![];
+    for (name, _) in names.iter().rev() {
+      name_offsets.push(name_table.len() as u32);
+      name_table.extend_from_slice(&(name.len() as u32).to_be_bytes());
+      name_table.extend_from_slice(name);
+    }
+    for ((_, id), offset) in names.iter().zip(name_offsets.iter().rev()) {
+      map.extend_from_slice(&offset.to_be_bytes());
+      map.extend_from_slice(&0u16.to_be_bytes());
+      map.extend_from_slice(&id.to_be_bytes());
+    }
+    let mut data = vec![];
+    data.extend_from_slice(&(map.len() as u32).to_be_bytes());
+    data.extend_from_slice(&(name_table.len() as u32).to_be_bytes());
+    data.extend_from_slice(&map);
+    data.extend_from_slice(&name_table);
+
+    let mut reader = BinaryReader::from_vec(&data);
+    let chunk = FontMapChunk::from_fmap_reader(&mut reader, 1100).unwrap();
+    assert_eq!(entries(&chunk), vec![(1, "Arial"), (1025, "Times New Roman")]);
+  }
+
+  #[test]
+  fn reads_fxmp_substitutions() {
+    let text = "; Font mapping\r\rMac:Geneva => Win:\"MS Sans Serif\" Map None\r\nWin:Arial=>Mac:Helvetica\rnot a mapping\r";
+    let mut reader = BinaryReader::from_u8(text.as_bytes());
+    let chunk = FontXPlatformMapChunk::from_reader(&mut reader, 1100).unwrap();
+    let substitutions: Vec<_> = chunk.substitutions.iter()
+      .map(|x| (x.from_platform.as_str(), x.from_name.as_str(), x.to_platform.as_str(), x.to_name.as_str()))
+      .collect();
+    assert_eq!(substitutions, vec![
+      ("Mac", "Geneva", "Win", "MS Sans Serif"),
+      ("Win", "Arial", "Mac", "Helvetica"),
+    ]);
+  }
+}
@@ -0,0 +1,76 @@
+use binary_reader::BinaryReader;
+
+#[derive(Clone)]
+pub struct FrameLabel {
+  pub frame_num: u32,
+  pub label: String,
+}
+
+pub struct FrameLabelsChunk {
+  pub labels: Vec<FrameLabel>,
+}
+
+impl FrameLabelsChunk {
+  pub fn from_reader(reader: &mut BinaryReader, _dir_version: u16) -> Result<FrameLabelsChunk, String> {
+    reader.set_endian(binary_reader::Endian::Big);
+
+    // The offset table has one extra entry marking the end of the last label
+    let label_count = reader.read_u16().unwrap() as usize;
+    let offsets: Vec<(u16, usize)> = (0..label_count + 1)
+      .map(|_| (reader.read_u16().unwrap(), reader.read_u16().unwrap() as usize))
+      .collect();
+    let strings_offset = 2 + (label_count + 1) * 4;
+
+    let mut labels = Vec::with_capacity(label_count);
+    for i in 0..label_count {
+      let (frame_num, start) = offsets[i];
+      let (_, end) = offsets[i + 1];
+      reader.jmp(strings_offset + start);
+      let bytes = reader.read_bytes(end.saturating_sub(start)).unwrap();
+      // Anything after a carriage return is the label comment
+      let label_bytes = bytes.split(|x| *x == 0x0D).next().unwrap_or(&[]);
+      labels.push(FrameLabel {
+        frame_num: frame_num as u32,
+        label: String::from_utf8_lossy(label_bytes).to_string(),
+      });
+    }
+
+    return Ok(FrameLabelsChunk { labels })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use binary_reader::BinaryReader;
+
+  use super::*;
+
+  fn labels_chunk(labels: &[(u16, &str)]) -> Vec<u8> {
+    let mut data = (labels.len() as u16).to_be_bytes().to_vec();
+    let mut strings = vec![];
+    for (frame_num, label) in labels {
+      data.extend_from_slice(&frame_num.to_be_bytes());
+      data.extend_from_slice(&(strings.len() as u16).to_be_bytes());
+      strings.extend_from_slice(label.as_bytes());
+    }
+    data.extend_from_slice(&[0, 0]);
+    data.extend_from_slice(&(strings.len() as u16).to_be_bytes());
+    data.extend_from_slice(&strings);
+    data
+  }
+
+  #[test]
+  fn reads_labels_and_drops_comments() {
+    let data = labels_chunk(&[(1, "intro"), (10, "menu\rThe main menu"), (25, "")]);
+    let chunk = FrameLabelsChunk::from_reader(&mut BinaryReader::from_vec(&data), 1100).unwrap();
+    let labels = chunk.labels.iter().map(|x| (x.frame_num, x.label.as_str())).collect::<Vec<_>>();
+    assert_eq!(labels, vec![(1, "intro"), (10, "menu"), (25, "")]);
+  }
+
+  #[test]
+  fn reads_an_empty_chunk() {
+    let data = labels_chunk(&[]);
+    let chunk = FrameLabelsChunk::from_reader(&mut BinaryReader::from_vec(&data), 1100).unwrap();
+    assert!(chunk.labels.is_empty());
+  }
+}
@@ -33,6 +33,15 @@ pub struct Bytecode {
 }
 
 impl Bytecode {
+  pub fn new(opcode: OpCode, obj: i64, pos: usize) -> Bytecode {
+    Bytecode {
+      opcode,
+      obj,
+      pos,
+      owner_loop: u32::MAX,
+    }
+  }
+
   pub fn pos_to_str(pos: usize) -> String {
     format_args!("[{}]", pos).to_string()
   }
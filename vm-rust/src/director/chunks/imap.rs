@@ -2,16 +2,23 @@ use binary_reader::BinaryReader;
 
 #[allow(dead_code)]
 pub struct InitialMapChunk {
-  version: u32,
-  mmap_offset: usize,
-  director_version: u32,
+  pub version: u32,
+  pub mmap_offset: usize,
+  pub director_version: u32,
   unused1: u32,
   unused2: u32,
   unused3: u32,
 }
 
 impl InitialMapChunk {
-  pub fn from_reader(_: &mut BinaryReader, _: u16) -> Result<InitialMapChunk, String> {
-    return Err("TODO".to_owned());
+  pub fn from_reader(reader: &mut BinaryReader, _dir_version: u16) -> Result<InitialMapChunk, String> {
+    return Ok(InitialMapChunk {
+      version: reader.read_u32().unwrap(),
+      mmap_offset: reader.read_u32().unwrap() as usize,
+      director_version: reader.read_u32().unwrap(),
+      unused1: reader.read_u32().unwrap(),
+      unused2: reader.read_u32().unwrap(),
+      unused3: reader.read_u32().unwrap(),
+    })
   }
 }
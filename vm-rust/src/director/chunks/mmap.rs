@@ -0,0 +1,68 @@
+use binary_reader::BinaryReader;
+
+#[allow(dead_code)]
+pub struct MemoryMapEntry {
+  pub fourcc: u32,
+  pub len: u32,
+  pub offset: u32,
+  pub flags: u16,
+  pub unknown0: u16,
+  pub next: i32,
+}
+
+impl MemoryMapEntry {
+  pub fn from_reader(reader: &mut BinaryReader, _dir_version: u16) -> Result<MemoryMapEntry, String> {
+    return Ok(MemoryMapEntry {
+      fourcc: reader.read_u32().unwrap(),
+      len: reader.read_u32().unwrap(),
+      offset: reader.read_u32().unwrap(),
+      flags: reader.read_u16().unwrap(),
+      unknown0: reader.read_u16().unwrap(),
+      next: reader.read_i32().unwrap(),
+    })
+  }
+}
+
+#[allow(dead_code)]
+pub struct MemoryMapChunk {
+  pub header_length: u16,
+  pub entry_length: u16,
+  pub chunk_count_max: u32,
+  pub chunk_count_used: u32,
+  pub junk_head: i32,
+  pub junk_head2: i32,
+  pub free_head: i32,
+  pub map_array: Vec<MemoryMapEntry>,
+}
+
+impl MemoryMapChunk {
+  pub fn from_reader(reader: &mut BinaryReader, dir_version: u16) -> Result<MemoryMapChunk, String> {
+    let header_length = reader.read_u16().unwrap();
+    let entry_length = reader.read_u16().unwrap();
+    let chunk_count_max = reader.read_u32().unwrap();
+    let chunk_count_used = reader.read_u32().unwrap();
+    let junk_head = reader.read_i32().unwrap();
+    let junk_head2 = reader.read_i32().unwrap();
+    let free_head = reader.read_i32().unwrap();
+
+    let map_array = (0..chunk_count_used)
+      .map(|i| {
+        // Entries are laid out at fixed strides, honour the declared sizes in case
+        // a future version pads either the header or the entries.
+        reader.jmp(header_length as usize + (i as usize) * entry_length as usize);
+        MemoryMapEntry::from_reader(reader, dir_version)
+      })
+      .collect::<Result<Vec<_>, _>>()?;
+
+    return Ok(MemoryMapChunk {
+      header_length,
+      entry_length,
+      chunk_count_max,
+      chunk_count_used,
+      junk_head,
+      junk_head2,
+      free_head,
+      map_array,
+    })
+  }
+}
@@ -1,5 +1,6 @@
 pub mod config;
 pub mod imap;
+pub mod mmap;
 pub mod key_table;
 pub mod cast_list;
 pub mod list;
@@ -15,6 +16,10 @@ pub mod score;
 pub mod text;
 pub mod bitmap;
 pub mod palette;
+pub mod frame_labels;
+pub mod sound;
+pub mod rich_text;
+pub mod font_map;
 
 use std::collections::HashMap;
 
@@ -22,16 +27,14 @@ use binary_reader::{Endian, BinaryReader};
 use config::ConfigChunk;
 use imap::InitialMapChunk;
 use key_table::KeyTableChunk;
+use mmap::MemoryMapChunk;
 
-use self::{bitmap::BitmapChunk, cast::CastChunk, cast_list::CastListChunk, cast_member::CastMemberChunk, lctx::ScriptContextChunk, palette::PaletteChunk, score::ScoreChunk, script::ScriptChunk, script_names::ScriptNamesChunk, text::TextChunk};
+use self::{bitmap::BitmapChunk, font_map::{FontMapChunk, FontXPlatformMapChunk}, rich_text::{RichTextBitmapChunk, RichTextChunk, XMediaChunk}, sound::{MediaChunk, SoundChunk}, frame_labels::FrameLabelsChunk, cast::CastChunk, cast_list::CastListChunk, cast_member::CastMemberChunk, lctx::ScriptContextChunk, palette::PaletteChunk, score::{ScoreChunk, ScoreFrameData}, script::ScriptChunk, script_names::ScriptNamesChunk, text::TextChunk};
 use super::{guid::MoaID, utils::{fourcc_to_string, FOURCC}, rifx::RIFXReaderContext};
 
 pub struct CastInfoChunkProps {
 }
 
-pub struct MemoryMapChunkProps {
-}
-
 #[allow(dead_code)]
 pub enum Chunk {
 	Cast(CastChunk),
@@ -41,14 +44,23 @@ pub enum Chunk {
 	Config(ConfigChunk),
 	InitialMap(InitialMapChunk),
 	KeyTable(KeyTableChunk),
-	MemoryMap(MemoryMapChunkProps),
+	MemoryMap(MemoryMapChunk),
 	Script(ScriptChunk),
 	ScriptContext(ScriptContextChunk),
 	ScriptNames(ScriptNamesChunk),
   Score(ScoreChunk),
+  FilmLoopScore(ScoreFrameData),
   Text(TextChunk),
   Bitmap(BitmapChunk),
   Palette(PaletteChunk),
+  FrameLabels(FrameLabelsChunk),
+  Sound(SoundChunk),
+  Media(MediaChunk),
+  RichText(RichTextChunk),
+  RichTextBitmap(RichTextBitmapChunk),
+  XMedia(XMediaChunk),
+  FontMap(FontMapChunk),
+  FontXPlatformMap(FontXPlatformMapChunk),
 }
 
 impl Chunk {
@@ -72,6 +84,55 @@ impl Chunk {
       _ => { None }
     }
   }
+
+  pub fn as_sound(&self) -> Option<&SoundChunk> {
+    match self {
+      Self::Sound(data) => { Some(data) }
+      _ => { None }
+    }
+  }
+
+  pub fn as_media(&self) -> Option<&MediaChunk> {
+    match self {
+      Self::Media(data) => { Some(data) }
+      _ => { None }
+    }
+  }
+
+  pub fn as_rich_text(&self) -> Option<&RichTextChunk> {
+    match self {
+      Self::RichText(data) => { Some(data) }
+      _ => { None }
+    }
+  }
+
+  pub fn as_rich_text_bitmap(&self) -> Option<&RichTextBitmapChunk> {
+    match self {
+      Self::RichTextBitmap(data) => { Some(data) }
+      _ => { None }
+    }
+  }
+
+  pub fn as_xmedia(&self) -> Option<&XMediaChunk> {
+    match self {
+      Self::XMedia(data) => { Some(data) }
+      _ => { None }
+    }
+  }
+
+  pub fn as_score(&self) -> Option<&ScoreChunk> {
+    match self {
+      Self::Score(data) => { Some(data) }
+      _ => { None }
+    }
+  }
+
+  pub fn as_film_loop_score(&self) -> Option<&ScoreFrameData> {
+    match self {
+      Self::FilmLoopScore(data) => { Some(data) }
+      _ => { None }
+    }
+  }
 }
 
 pub struct ChunkInfo {
@@ -118,9 +179,13 @@ pub fn make_chunk(
         )
       );
     }
-    // "mmap" => {
-    //   //res = MemoryMapChunk(dir: this);
-    // }
+    "mmap" => {
+      return Ok(
+        Chunk::MemoryMap(
+          MemoryMapChunk::from_reader(&mut chunk_reader, version).unwrap()
+        )
+      );
+    }
     "CAS*" => {
       return Ok(
         Chunk::Cast(
@@ -164,10 +229,7 @@ pub fn make_chunk(
         )
       );
     }
-    // "VWCF" => {
-    //   //res = ConfigChunk(dir: this);
-    // }
-    "DRCF" => {
+    "VWCF" | "DRCF" => {
       return Ok(
         Chunk::Config(
           ConfigChunk::from_reader(&mut chunk_reader, version, endian).unwrap()
@@ -203,7 +265,17 @@ pub fn make_chunk(
         )
       )
     }
+    "SCVW" => Ok(Chunk::FilmLoopScore(ScoreFrameData::read_film_loop(&mut chunk_reader)?)),
+    "VWLB" => Ok(Chunk::FrameLabels(FrameLabelsChunk::from_reader(&mut chunk_reader, version).unwrap())),
     "CLUT" => Ok(Chunk::Palette(palette::PaletteChunk::from_reader(&mut chunk_reader, version).unwrap())),
+    "snd " => Ok(Chunk::Sound(SoundChunk::from_reader(&mut chunk_reader, version)?)),
+    "ediM" => Ok(Chunk::Media(MediaChunk::from_reader(&mut chunk_reader, version)?)),
+    "RTE1" => Ok(Chunk::RichText(RichTextChunk::from_reader(&mut chunk_reader, version)?)),
+    "RTE2" => Ok(Chunk::RichTextBitmap(RichTextBitmapChunk::from_reader(&mut chunk_reader, version)?)),
+    "XMED" | "RTE0" => Ok(Chunk::XMedia(XMediaChunk::from_reader(&mut chunk_reader, version)?)),
+    "VWFM" => Ok(Chunk::FontMap(FontMapChunk::from_vwfm_reader(&mut chunk_reader, version)?)),
+    "Fmap" => Ok(Chunk::FontMap(FontMapChunk::from_fmap_reader(&mut chunk_reader, version)?)),
+    "FXmp" => Ok(Chunk::FontXPlatformMap(FontXPlatformMapChunk::from_reader(&mut chunk_reader, version)?)),
     _ => {
       return Err(format_args!("Could not deserialize '{}' chunk", fourcc_to_string(fourcc)).to_string());
     }
@@ -0,0 +1,341 @@
+use binary_reader::{BinaryReader, Endian};
+
+use crate::io::reader::DirectorExt;
+
+/// The `RTE1` chunk of a rich text member, holding its plain text.
+pub struct RichTextChunk {
+  pub text: String,
+}
+
+impl RichTextChunk {
+  pub fn from_reader(reader: &mut BinaryReader, _dir_version: u16) -> Result<RichTextChunk, String> {
+    let text = reader.read_string(reader.length).map_err(|e| e.to_string())?;
+    Ok(RichTextChunk {
+      text: text.trim_end_matches('\0').to_string(),
+    })
+  }
+}
+
+/// The `RTE2` chunk of a rich text member: the text as rendered by the authoring tool, kept
+/// alongside the text so that players without the fonts can show it.
+pub struct RichTextBitmapChunk {
+  pub width: u16,
+  pub height: u16,
+  pub bit_depth: u16,
+  pub data: Vec<u8>,
+}
+
+impl RichTextBitmapChunk {
+  pub fn from_reader(reader: &mut BinaryReader, _dir_version: u16) -> Result<RichTextBitmapChunk, String> {
+    reader.set_endian(Endian::Big);
+    let width = reader.read_u16().map_err(|e| e.to_string())?;
+    let height = reader.read_u16().map_err(|e| e.to_string())?;
+    let bit_depth = reader.read_u16().map_err(|e| e.to_string())?;
+    let data = reader.read_bytes(reader.length - reader.pos).map_err(|e| e.to_string())?.to_vec();
+    Ok(RichTextBitmapChunk { width, height, bit_depth, data })
+  }
+}
+
+/// A run of character formatting in an `XMED` chunk, from `start` until the next run.
+pub struct XMediaStyleRun {
+  /// Byte offset in the text.
+  pub start: usize,
+  pub font_name: Option<String>,
+  pub font_size: u16,
+  pub style: u8,
+  pub color: (u8, u8, u8),
+}
+
+/// A run of paragraph formatting in an `XMED` chunk, from `start` until the next run.
+pub struct XMediaParagraphRun {
+  /// Byte offset in the text.
+  pub start: usize,
+  /// 0 for left, 1 for center, 2 for right and 3 for justified.
+  pub alignment: u8,
+}
+
+pub struct XMediaStyledText {
+  pub text: String,
+  pub style_runs: Vec<XMediaStyleRun>,
+  pub paragraph_runs: Vec<XMediaParagraphRun>,
+}
+
+/// The `XMED` chunk of a text member, or the `RTE0` chunk of a rich text member, which uses the same layout.
+///
+/// The format is undocumented. Its sections store numbers as ASCII hex followed by a comma, and strings
+/// as a hex length, a comma and the characters. The text is followed by its formatting tables:
+///
+/// - character runs: a count, then the byte offset and style index of each run
+/// - styles: a count, then the font index, size, style bits and 16-bit red, green and blue of each style
+/// - paragraph runs: a count, then the byte offset and alignment of each run
+/// - fonts: a count, then the name of each font as a string
+///
+/// This layout was worked out without chunks from real movies at hand, and is only tested against
+/// hand-built chunks. Text whose formatting tables do not match it keeps its text without runs.
+pub struct XMediaChunk {
+  pub data: Vec<u8>,
+}
+
+/// Reads the comma separated fields of an `XMED` chunk.
+struct XMediaFieldReader<'a> {
+  data: &'a [u8],
+  pos: usize,
+}
+
+impl XMediaFieldReader<'_> {
+  fn read_number(&mut self) -> Option<u32> {
+    let digits_end = (self.pos..self.data.len()).find(|x| !self.data[*x].is_ascii_hexdigit())?;
+    if digits_end == self.pos || digits_end - self.pos > 8 || self.data[digits_end] != b',' {
+      return None;
+    }
+    let number = std::str::from_utf8(&self.data[self.pos..digits_end]).ok()?;
+    let number = u32::from_str_radix(number, 16).ok()?;
+    self.pos = digits_end + 1;
+    Some(number)
+  }
+
+  fn read_string(&mut self) -> Option<String> {
+    let length = self.read_number()? as usize;
+    let bytes = self.data.get(self.pos..self.pos + length)?;
+    self.pos += length;
+    let mut reader = BinaryReader::from_u8(bytes);
+    reader.read_string(length).ok()
+  }
+
+  /// Reads a table of `count` rows of `row_len` numbers, rejecting counts that cannot fit in the chunk.
+  fn read_table(&mut self, row_len: usize) -> Option<Vec<Vec<u32>>> {
+    let count = self.read_number()? as usize;
+    if count * row_len * 2 > self.data.len() - self.pos {
+      return None;
+    }
+    (0..count)
+      .map(|_| (0..row_len).map(|_| self.read_number()).collect())
+      .collect()
+  }
+}
+
+impl XMediaChunk {
+  pub fn from_reader(reader: &mut BinaryReader, _dir_version: u16) -> Result<XMediaChunk, String> {
+    let data = reader.read_bytes(reader.length).map_err(|e| e.to_string())?.to_vec();
+    Ok(XMediaChunk { data })
+  }
+
+  fn is_text_byte(byte: u8) -> bool {
+    byte >= 0x20 || byte == b'\r' || byte == b'\n' || byte == b'\t'
+  }
+
+  /// Returns the offset and length of the length-prefixed strings in the chunk, longest first.
+  fn text_candidates(&self) -> Vec<(usize, usize)> {
+    let data = &self.data;
+    let mut candidates = vec![];
+    let mut i = 0;
+    while i < data.len() {
+      if !data[i].is_ascii_hexdigit() || (i > 0 && data[i - 1].is_ascii_hexdigit()) {
+        i += 1;
+        continue;
+      }
+      let digits_end = (i..data.len()).find(|x| !data[*x].is_ascii_hexdigit()).unwrap_or(data.len());
+      if digits_end - i > 8 || data.get(digits_end) != Some(&b',') {
+        i = digits_end;
+        continue;
+      }
+      let length = std::str::from_utf8(&data[i..digits_end]).ok().and_then(|x| usize::from_str_radix(x, 16).ok());
+      let text_start = digits_end + 1;
+      if let Some(length) = length {
+        let text_end = text_start + length;
+        let is_text = text_end <= data.len()
+          && data[text_start..text_end].iter().all(|x| Self::is_text_byte(*x))
+          // Skip runs of numeric fields, which the formatting tables are made of
+          && !data[text_start..text_end].iter().all(|x| x.is_ascii_hexdigit() || *x == b',');
+        if length > 0 && is_text {
+          candidates.push((text_start, length));
+        }
+      }
+      i = text_start;
+    }
+    candidates.sort_by_key(|x| std::cmp::Reverse(x.1));
+    candidates
+  }
+
+  fn read_text(&self, (start, length): (usize, usize)) -> Option<String> {
+    let mut reader = BinaryReader::from_u8(&self.data[start..start + length]);
+    reader.read_string(length).ok()
+  }
+
+  /// Returns the text of the member, taken to be the longest string in the chunk.
+  pub fn text(&self) -> Option<String> {
+    self.read_text(*self.text_candidates().first()?)
+  }
+
+  /// Returns the text of the member and its formatting. The text is the longest string followed by
+  /// valid formatting tables. If there is none, the runs are empty and the text is the longest string.
+  pub fn styled_text(&self) -> Option<XMediaStyledText> {
+    let candidates = self.text_candidates();
+    for candidate in &candidates {
+      let Some(text) = self.read_text(*candidate) else { continue };
+      if let Some((style_runs, paragraph_runs)) = self.read_formatting(candidate.0 + candidate.1, &text) {
+        return Some(XMediaStyledText { text, style_runs, paragraph_runs });
+      }
+    }
+    let text = self.read_text(*candidates.first()?)?;
+    Some(XMediaStyledText { text, style_runs: vec![], paragraph_runs: vec![] })
+  }
+
+  fn read_formatting(&self, pos: usize, text: &str) -> Option<(Vec<XMediaStyleRun>, Vec<XMediaParagraphRun>)> {
+    let mut reader = XMediaFieldReader { data: &self.data, pos };
+    let char_runs = reader.read_table(2)?;
+    let styles = reader.read_table(6)?;
+    let paragraph_runs = reader.read_table(2)?;
+    let font_count = reader.read_number()? as usize;
+    let fonts = (0..font_count).map(|_| reader.read_string()).collect::<Option<Vec<_>>>()?;
+
+    let is_valid_offset = |runs: &Vec<Vec<u32>>| {
+      runs.iter().all(|x| x[0] as usize <= text.len()) && runs.windows(2).all(|x| x[0][0] <= x[1][0])
+    };
+    if !is_valid_offset(&char_runs) || !is_valid_offset(&paragraph_runs) {
+      return None;
+    }
+    let style_runs = char_runs.iter().map(|run| {
+      let style = styles.get(run[1] as usize)?;
+      Some(XMediaStyleRun {
+        start: run[0] as usize,
+        font_name: fonts.get(style[0] as usize).cloned(),
+        font_size: style[1] as u16,
+        style: style[2] as u8,
+        color: ((style[3] >> 8) as u8, (style[4] >> 8) as u8, (style[5] >> 8) as u8),
+      })
+    }).collect::<Option<Vec<_>>>()?;
+    let paragraph_runs = paragraph_runs.iter()
+      .map(|run| XMediaParagraphRun { start: run[0] as usize, alignment: run[1] as u8 })
+      .collect();
+    Some((style_runs, paragraph_runs))
+  }
+
+  /// Whether the chunk holds Portable Font Resource outlines, which Director uses for fonts
+  /// embedded on the Mac and by most versions of the authoring tool.
+  pub fn is_pfr_data(&self) -> bool {
+    self.data.windows(4).any(|x| x == b"PFR0")
+  }
+
+  /// Font members embed their outlines in an `XMED` chunk, after a header of their own. Returns
+  /// them if they are TrueType or OpenType data, as opposed to the PFR format used by most authoring
+  /// tools. Decoding PFR is out of scope for now: its outlines are compressed in a format no crate
+  /// we use can read, so text using such fonts falls back to the system font.
+  pub fn truetype_data(&self) -> Option<&[u8]> {
+    (0..self.data.len())
+      .find(|offset| Self::is_sfnt_header(&self.data[*offset..]))
+      .map(|offset| &self.data[offset..])
+  }
+
+  /// Whether `data` starts with the header of a font file. The magic alone could appear anywhere in
+  /// the chunk, so the table count and search range following it must agree as well.
+  fn is_sfnt_header(data: &[u8]) -> bool {
+    let read_u16 = |offset: usize| data.get(offset..offset + 2).map(|x| u16::from_be_bytes([x[0], x[1]]));
+    match data.get(..4) {
+      Some(b"ttcf") => matches!(read_u16(4), Some(1 | 2)) && read_u16(6) == Some(0),
+      Some([0, 1, 0, 0] | b"OTTO" | b"true") => {
+        let (Some(num_tables), Some(search_range)) = (read_u16(4), read_u16(6)) else { return false };
+        num_tables > 0 && search_range as u32 == 16 << num_tables.ilog2()
+      }
+      _ => false,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn sfnt_header(magic: &[u8], num_tables: u16) -> Vec<u8> {
+    let mut data = magic.to_vec();
+    data.extend_from_slice(&num_tables.to_be_bytes());
+    data.extend_from_slice(&((16 << num_tables.ilog2()) as u16).to_be_bytes());
+    data.extend_from_slice(&[0; 4]);
+    data.extend_from_slice(b"cmap");
+    data
+  }
+
+  #[test]
+  fn finds_truetype_data_at_the_start_of_the_chunk() {
+    let data = sfnt_header(&[0, 1, 0, 0], 11);
+    let chunk = XMediaChunk { data: data.clone() };
+    assert_eq!(chunk.truetype_data(), Some(data.as_slice()));
+    assert!(!chunk.is_pfr_data());
+
+    let chunk = XMediaChunk { data: sfnt_header(b"OTTO", 9) };
+    assert!(chunk.truetype_data().is_some_and(|x| x.starts_with(b"OTTO")));
+  }
+
+  #[test]
+  fn finds_truetype_data_after_the_member_header() {
+    let font = sfnt_header(b"true", 4);
+    // The font name mentions the magic, but isn't followed by a table count
+    let mut data = b"FONT\0\0\0\x02\x0ctrue Sans ok".to_vec();
+    data.extend_from_slice(&font);
+    let chunk = XMediaChunk { data };
+    assert_eq!(chunk.truetype_data(), Some(font.as_slice()));
+
+    let mut collection = b"FONT\0\0\0\x02".to_vec();
+    collection.extend_from_slice(b"ttcf\0\x01\0\0\0\0\0\x02");
+    let chunk = XMediaChunk { data: collection };
+    assert!(chunk.truetype_data().is_some_and(|x| x.starts_with(b"ttcf")));
+  }
+
+  #[test]
+  fn has_no_truetype_data_for_pfr_fonts() {
+    let chunk = XMediaChunk { data: b"FONT\0\0\0\x02PFR0\0\x01\0\0true".to_vec() };
+    assert!(chunk.is_pfr_data());
+    assert!(chunk.truetype_data().is_none());
+  }
+
+  fn xmedia_fixture(formatting: &str) -> XMediaChunk {
+    let mut data = b"DEMX\0\0\0\x01".to_vec();
+    data.extend_from_slice(b"B,Hello\rWorld");
+    data.extend_from_slice(formatting.as_bytes());
+    XMediaChunk { data }
+  }
+
+  #[test]
+  fn reads_style_and_paragraph_runs() {
+    let chunk = xmedia_fixture("2,0,0,6,1,2,0,C,1,0,0,0,1,18,0,FFFF,0,0,2,0,1,6,2,2,5,Arial7,Verdana");
+    let styled_text = chunk.styled_text().unwrap();
+    assert_eq!(styled_text.text, "Hello\rWorld");
+
+    let runs = &styled_text.style_runs;
+    assert_eq!(runs.len(), 2);
+    assert_eq!((runs[0].start, runs[0].font_name.as_deref(), runs[0].font_size, runs[0].style), (0, Some("Arial"), 12, 1));
+    assert_eq!(runs[0].color, (0, 0, 0));
+    assert_eq!((runs[1].start, runs[1].font_name.as_deref(), runs[1].font_size, runs[1].style), (6, Some("Verdana"), 24, 0));
+    assert_eq!(runs[1].color, (255, 0, 0));
+
+    let paragraphs = &styled_text.paragraph_runs;
+    assert_eq!(paragraphs.iter().map(|x| (x.start, x.alignment)).collect::<Vec<_>>(), vec![(0, 1), (6, 2)]);
+  }
+
+  #[test]
+  fn keeps_text_without_runs_when_the_tables_are_invalid() {
+    // The second run starts past the end of the text
+    let chunk = xmedia_fixture("2,0,0,F,0,1,0,C,0,0,0,0,1,0,0,1,5,Arial");
+    let styled_text = chunk.styled_text().unwrap();
+    assert_eq!(styled_text.text, "Hello\rWorld");
+    assert!(styled_text.style_runs.is_empty());
+    assert!(styled_text.paragraph_runs.is_empty());
+
+    // The run refers to a style that does not exist
+    let styled_text = xmedia_fixture("1,0,3,1,0,C,0,0,0,0,0,0,0,").styled_text().unwrap();
+    assert!(styled_text.style_runs.is_empty());
+
+    let styled_text = xmedia_fixture("").styled_text().unwrap();
+    assert_eq!(styled_text.text, "Hello\rWorld");
+    assert!(styled_text.style_runs.is_empty());
+  }
+
+  #[test]
+  fn reads_rich_text_bitmap_size() {
+    let bytes = [0, 120, 0, 40, 0, 8, 1, 2, 3];
+    let mut reader = BinaryReader::from_u8(&bytes);
+    let chunk = RichTextBitmapChunk::from_reader(&mut reader, 0).unwrap();
+    assert_eq!((chunk.width, chunk.height, chunk.bit_depth), (120, 40, 8));
+    assert_eq!(chunk.data, vec![1, 2, 3]);
+  }
+}
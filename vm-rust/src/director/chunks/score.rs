@@ -21,37 +21,102 @@ impl ScoreFrameDelta {
 const K_CHANNEL_DATA_SIZE: usize = 38664; // (25 * 50);
 
 #[allow(dead_code)]
+#[derive(Clone, Default)]
 pub struct ScoreFrameChannelData {
-  flags: u16,
-  unk0: u16,
-  cast_lib: u16,
-  cast_member: u16,
-  unk1: u16,
-  pos_y: u16,
-  pos_x: u16,
-  height: u16,
-  width: u16,
+  pub sprite_type: u8,
+  pub ink: u8,
+  pub fore_color: u8,
+  pub back_color: u8,
+  pub cast_lib: u16,
+  pub cast_member: u16,
+  pub script_cast_lib: u16,
+  pub script_cast_member: u16,
+  pub pos_y: i16,
+  pub pos_x: i16,
+  pub height: i16,
+  pub width: i16,
+  pub color_flag: u8,
+  pub blend: u8,
+  pub thickness: u8,
+  // Director 7 and above
+  pub fore_color_g: u8,
+  pub back_color_g: u8,
+  pub fore_color_b: u8,
+  pub back_color_b: u8,
+  pub rotation: f32,
+  pub skew: f32,
 }
 
 impl ScoreFrameChannelData {
-  pub fn read(reader: &mut BinaryReader) -> ScoreFrameChannelData {
-    let flags = reader.read_u16().unwrap();
-    let unk0 = reader.read_u16().unwrap();
+  /// Reads one sprite record of `record_size` bytes. Fields past the end of shorter records are left at zero.
+  pub fn read(reader: &mut BinaryReader, record_size: u16) -> ScoreFrameChannelData {
+    let record_len = (record_size as usize).min(reader.length - reader.pos);
+    let mut record = reader.read_bytes(record_len).unwrap().to_vec();
+    record.resize(record_len.max(48), 0);
+    let mut reader = BinaryReader::from_vec(&record);
+    reader.set_endian(Endian::Big);
+
+    let sprite_type = reader.read_u8().unwrap();
+    let ink = reader.read_u8().unwrap();
+    let fore_color = reader.read_u8().unwrap();
+    let back_color = reader.read_u8().unwrap();
     let cast_lib = reader.read_u16().unwrap();
     let cast_member = reader.read_u16().unwrap();
-    let unk1 = reader.read_u16().unwrap();
-    let pos_y = reader.read_u16().unwrap();
-    let pos_x = reader.read_u16().unwrap();
-    let height = reader.read_u16().unwrap();
-    let width = reader.read_u16().unwrap();
+    let script_cast_lib = reader.read_u16().unwrap();
+    let script_cast_member = reader.read_u16().unwrap();
+    let pos_y = reader.read_i16().unwrap();
+    let pos_x = reader.read_i16().unwrap();
+    let height = reader.read_i16().unwrap();
+    let width = reader.read_i16().unwrap();
+    let color_flag = reader.read_u8().unwrap();
+    let blend = reader.read_u8().unwrap();
+    let thickness = reader.read_u8().unwrap();
+    let _ = reader.read_u8().unwrap();
+
+    let mut result = ScoreFrameChannelData {
+      sprite_type, ink, fore_color, back_color, cast_lib, cast_member, script_cast_lib, script_cast_member,
+      pos_y, pos_x, height, width, color_flag, blend, thickness,
+      ..Default::default()
+    };
+    if record_size >= 48 {
+      result.fore_color_g = reader.read_u8().unwrap();
+      result.back_color_g = reader.read_u8().unwrap();
+      result.fore_color_b = reader.read_u8().unwrap();
+      result.back_color_b = reader.read_u8().unwrap();
+      result.rotation = reader.read_i32().unwrap() as f32 / 100.0;
+      result.skew = reader.read_i32().unwrap() as f32 / 100.0;
+    }
+    result
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.sprite_type == 0 && self.cast_member == 0
+  }
 
-    ScoreFrameChannelData { flags, unk0, cast_lib, cast_member, unk1, pos_y, pos_x, height, width }
+  /// The ink number, without the trails and stretch flags.
+  pub fn ink_number(&self) -> u8 {
+    self.ink & 0x3F
+  }
+
+  pub fn stretch(&self) -> bool {
+    self.ink & 0x80 != 0
+  }
+
+  pub fn editable(&self) -> bool {
+    self.color_flag & 0x40 != 0
+  }
+
+  /// The blend percentage. The score stores the inverse of the amount in the 0..255 range.
+  pub fn blend_percent(&self) -> u8 {
+    ((255 - self.blend as u32) * 100 / 255) as u8
   }
 }
 
 pub struct ScoreFrameData {
   pub header: ScoreFrameDataHeader,
   pub uncompressed_data: Vec<u8>,
+  /// Non-empty sprite channels of every frame as (frame index, channel index, data), sorted by frame.
+  pub frame_channel_data: Vec<(u32, u16, ScoreFrameChannelData)>,
 }
 
 pub struct ScoreFrameDataHeader {
@@ -60,16 +125,22 @@ pub struct ScoreFrameDataHeader {
   pub num_channels: u16,
 }
 
+/// The number of channels preceding the sprite channels (script, tempo, transition, 2 sounds and palette).
+pub const NUM_MAIN_CHANNELS: u16 = 6;
+
 impl ScoreFrameData {
   #[allow(unused_variables)]
   pub fn read(reader: &mut BinaryReader) -> ScoreFrameData {
     let header = Self::read_header(reader);
     log_i(format_args!("ScoreFrameData {} {} {}", header.frame_count, header.num_channels, header.sprite_record_size).to_string().as_str());
 
-    let mut channel_data = vec![0u8; (header.frame_count as usize) * (header.num_channels as usize) * (header.sprite_record_size as usize)];
-    
-    while !reader.eof() {
-      let fd_start = reader.pos as usize;
+    let frame_size = (header.num_channels as usize) * (header.sprite_record_size as usize);
+    let mut channel_data = vec![0u8; (header.frame_count as usize) * frame_size];
+    // Each frame is stored as a delta against the previous one
+    let mut frame_buffer = vec![0u8; frame_size];
+    let mut frame_index = 0;
+
+    while !reader.eof() && frame_index < header.frame_count as usize {
       let length = reader.read_u16().unwrap();
 
       if length == 0 {
@@ -87,30 +158,97 @@ impl ScoreFrameData {
           let channel_offset = frame_chunk_reader.read_u16().unwrap() as usize;
           let channel_delta = frame_chunk_reader.read_bytes(channel_size).unwrap();
 
-          channel_data[channel_offset..channel_offset + channel_size].copy_from_slice(channel_delta);
+          if channel_offset + channel_size > frame_size {
+            error!("Score frame {frame_index} delta at {channel_offset} overflows frame size {frame_size}");
+            continue;
+          }
+          frame_buffer[channel_offset..channel_offset + channel_size].copy_from_slice(channel_delta);
         }
       }
+
+      channel_data[frame_index * frame_size..(frame_index + 1) * frame_size].copy_from_slice(&frame_buffer);
+      frame_index += 1;
     }
 
     let uncompressed_data = channel_data;
+    let frame_channel_data = Self::read_sprite_channels(&uncompressed_data, frame_index as u32, header.num_channels, header.sprite_record_size);
 
-    let mut channel_reader = BinaryReader::from_vec(&uncompressed_data);
-    channel_reader.set_endian(Endian::Big);
-    for i in 0..header.frame_count {
-      for j in 0..header.num_channels {
-        let pos = channel_reader.pos;
-        let channel_frame_data = ScoreFrameChannelData::read(&mut channel_reader);
-        channel_reader.jmp(pos + header.sprite_record_size as usize);
-        if channel_frame_data.flags != 0 {
-          log_i(format_args!("frame {i} channel {j} flags={}", channel_frame_data.flags).to_string().as_str());
+    ScoreFrameData {
+      header,
+      uncompressed_data,
+      frame_channel_data,
+    }
+  }
+
+  /// Reads the score of a film loop member (SCVW chunk). It holds the same delta encoded frames as the
+  /// movie score, after a header of the total length, the offset of the frames and, at offset 14, the
+  /// sprite record size. The frame and channel counts are not stored, so frames run to the end of the data.
+  pub fn read_film_loop(reader: &mut BinaryReader) -> Result<ScoreFrameData, String> {
+    let total_length = reader.read_u32().map_err(|e| e.to_string())? as usize;
+    let frames_offset = reader.read_u32().map_err(|e| e.to_string())? as usize;
+    reader.jmp(14);
+    let sprite_record_size = reader.read_u16().map_err(|e| e.to_string())?;
+    if sprite_record_size == 0 {
+      return Err("Film loop score has no sprite record size".to_string());
+    }
+    reader.jmp(frames_offset);
+
+    let end = total_length.min(reader.length);
+    let mut frames: Vec<Vec<u8>> = vec![];
+    let mut frame_buffer = vec![];
+    while reader.pos + 2 <= end {
+      let length = reader.read_u16().map_err(|e| e.to_string())? as usize;
+      if length < 2 || reader.pos + length - 2 > end {
+        break;
+      }
+      let chunk_data = reader.read_bytes(length - 2).map_err(|e| e.to_string())?;
+      let mut frame_chunk_reader = BinaryReader::from_u8(chunk_data);
+      frame_chunk_reader.set_endian(Endian::Big);
+      while frame_chunk_reader.pos + 4 <= frame_chunk_reader.length {
+        let channel_size = frame_chunk_reader.read_u16().unwrap() as usize;
+        let channel_offset = frame_chunk_reader.read_u16().unwrap() as usize;
+        let channel_delta = frame_chunk_reader.read_bytes(channel_size).map_err(|e| e.to_string())?;
+        // Loops only store the channels they use, so the frame grows to fit the highest one
+        if channel_offset + channel_size > frame_buffer.len() {
+          frame_buffer.resize(channel_offset + channel_size, 0);
         }
+        frame_buffer[channel_offset..channel_offset + channel_size].copy_from_slice(channel_delta);
       }
+      frames.push(frame_buffer.clone());
     }
 
-    ScoreFrameData {
-      header,
-      uncompressed_data
+    let num_channels = frame_buffer.len().div_ceil(sprite_record_size as usize) as u16;
+    let frame_size = num_channels as usize * sprite_record_size as usize;
+    let uncompressed_data = frames.into_iter().flat_map(|mut frame| {
+      frame.resize(frame_size, 0);
+      frame
+    }).collect_vec();
+    let frame_count = (uncompressed_data.len() / frame_size.max(1)) as u32;
+    let frame_channel_data = Self::read_sprite_channels(&uncompressed_data, frame_count, num_channels, sprite_record_size);
+
+    Ok(ScoreFrameData {
+      header: ScoreFrameDataHeader { frame_count, sprite_record_size, num_channels },
+      uncompressed_data,
+      frame_channel_data,
+    })
+  }
+
+  /// Reads the non-empty sprite channels of the uncompressed frames, skipping the main channels.
+  fn read_sprite_channels(data: &[u8], frame_count: u32, num_channels: u16, record_size: u16) -> Vec<(u32, u16, ScoreFrameChannelData)> {
+    let frame_size = num_channels as usize * record_size as usize;
+    let mut frame_channel_data = vec![];
+    let mut channel_reader = BinaryReader::from_u8(data);
+    channel_reader.set_endian(Endian::Big);
+    for i in 0..frame_count {
+      for j in NUM_MAIN_CHANNELS..num_channels {
+        channel_reader.jmp(i as usize * frame_size + j as usize * record_size as usize);
+        let channel_frame_data = ScoreFrameChannelData::read(&mut channel_reader, record_size);
+        if !channel_frame_data.is_empty() {
+          frame_channel_data.push((i, j, channel_frame_data));
+        }
+      }
     }
+    frame_channel_data
   }
 
   #[allow(unused_variables)]
@@ -147,7 +285,9 @@ pub struct FrameIntervalPrimary {
   pub end_frame: u32,
   pub unk0: u32,
   pub unk1: u32,
-  pub sprite_number: u32,
+  /// Index into the frame channels, where 0 is the frame script channel and
+  /// sprite channels start after the `NUM_MAIN_CHANNELS` main channels.
+  pub channel_index: u32,
   pub unk2: u16,
   pub unk3: u32,
   pub unk4: u16,
@@ -164,7 +304,7 @@ impl FrameIntervalPrimary {
       end_frame: reader.read_u32().map_err(|_| ())?,
       unk0: reader.read_u32().map_err(|_| ())?,
       unk1: reader.read_u32().map_err(|_| ())?,
-      sprite_number: reader.read_u32().map_err(|_| ())?,
+      channel_index: reader.read_u32().map_err(|_| ())?,
       unk2: reader.read_u16().map_err(|_| ())?,
       unk3: reader.read_u32().map_err(|_| ())?,
       unk4: reader.read_u16().map_err(|_| ())?,
@@ -190,6 +330,20 @@ impl FrameIntervalSecondary {
       unk0: reader.read_u32().unwrap(),
     }
   }
+
+  /// Reads every behavior attached to an interval.
+  pub fn read_all(reader: &mut BinaryReader) -> Vec<Self> {
+    let mut result = vec![];
+    while reader.length - reader.pos >= 8 {
+      result.push(Self::read(reader));
+    }
+    result
+  }
+}
+
+pub struct FrameInterval {
+  pub primary: FrameIntervalPrimary,
+  pub secondaries: Vec<FrameIntervalSecondary>,
 }
 
 pub struct ScoreChunkHeader {
@@ -204,8 +358,7 @@ pub struct ScoreChunkHeader {
 pub struct ScoreChunk {
   pub header: ScoreChunkHeader,
   pub entries: Vec<Vec<u8>>,
-  pub frame_interval_primaries: Vec<FrameIntervalPrimary>,
-  pub frame_interval_secondaries: Vec<FrameIntervalSecondary>,
+  pub frame_intervals: Vec<FrameInterval>,
   pub frame_data: ScoreFrameData,
 }
 
@@ -231,37 +384,41 @@ impl ScoreChunk {
     let frame_data = ScoreFrameData::read(&mut delta_reader);
 
     let frame_interval_entries = entries.split_off(3);
-    let mut frame_interval_primaries = vec![];
-    let mut frame_interval_secondaries = vec![];
+    let mut frame_intervals = vec![];
 
-    for (i, entry) in frame_interval_entries.iter().enumerate() {
-      if entry.is_empty() {
+    // Intervals are stored as (primary, secondary, tertiary) triplets
+    for (i, triplet) in frame_interval_entries.chunks(3).enumerate() {
+      let primary_entry = &triplet[0];
+      if primary_entry.is_empty() {
         continue;
       }
-      let is_primary = i % 3 == 0;
-      let is_secondary = i % 3 == 1;
-      let is_tertiary = i % 3 == 2;
-
-      let mut frame_interval_reader = BinaryReader::from_u8(entry);
-      frame_interval_reader.set_endian(Endian::Big);
-      if is_primary {
-        if let Ok(item) = FrameIntervalPrimary::read(&mut frame_interval_reader) {
-          frame_interval_primaries.push(item);
-        } else {
-          error!("Failed to read FrameIntervalPrimary at index {}", i);
-          break;
+
+      let mut primary_reader = BinaryReader::from_u8(primary_entry);
+      primary_reader.set_endian(Endian::Big);
+      let primary = if let Ok(item) = FrameIntervalPrimary::read(&mut primary_reader) {
+        item
+      } else {
+        error!("Failed to read FrameIntervalPrimary at index {}", i * 3);
+        break;
+      };
+
+      let secondaries = match triplet.get(1) {
+        Some(secondary_entry) => {
+          let mut secondary_reader = BinaryReader::from_u8(secondary_entry);
+          secondary_reader.set_endian(Endian::Big);
+          FrameIntervalSecondary::read_all(&mut secondary_reader)
         }
-      } else if is_secondary {
-        frame_interval_secondaries.push(FrameIntervalSecondary::read(&mut frame_interval_reader));
-      }
+        None => vec![],
+      };
       // TODO tertiary
+
+      frame_intervals.push(FrameInterval { primary, secondaries });
     }
 
     Ok(ScoreChunk {
       header,
       entries,
-      frame_interval_primaries,
-      frame_interval_secondaries,
+      frame_intervals,
       frame_data,
     })
   }
@@ -277,4 +434,77 @@ impl ScoreChunk {
       entry_size_sum: reader.read_u32().unwrap(),
     }
   }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn read_records(data: &[u8], record_size: u16) -> Vec<ScoreFrameChannelData> {
+    let mut reader = BinaryReader::from_u8(data);
+    reader.set_endian(Endian::Big);
+    (0..data.len() / record_size as usize).map(|_| ScoreFrameChannelData::read(&mut reader, record_size)).collect()
+  }
+
+  #[test]
+  fn reads_director_7_records() {
+    let mut data = vec![1, 8, 2, 3, 0, 1, 0, 5, 0, 0, 0, 0, 0, 20, 0, 10, 0, 30, 0, 40, 0x40, 0, 1, 0];
+    data.extend_from_slice(&[4, 5, 6, 7]);
+    data.extend_from_slice(&4500i32.to_be_bytes());
+    data.extend_from_slice(&(-1000i32).to_be_bytes());
+    data.resize(48, 0);
+    let record = &read_records(&data, 48)[0];
+    assert_eq!((record.sprite_type, record.ink, record.cast_lib, record.cast_member), (1, 8, 1, 5));
+    assert_eq!((record.pos_x, record.pos_y, record.width, record.height), (10, 20, 40, 30));
+    assert!(record.editable());
+    assert_eq!((record.fore_color_g, record.back_color_b), (4, 7));
+    assert_eq!((record.rotation, record.skew), (45.0, -10.0));
+  }
+
+  #[test]
+  fn keeps_short_records_apart() {
+    // Two records of 20 bytes, shorter than the fields of a full record
+    let data = [
+      [1, 0, 0, 0, 0, 1, 0, 2, 0, 0, 0, 0, 0, 5, 0, 6, 0, 7, 0, 8],
+      [1, 0, 0, 0, 0, 1, 0, 3, 0, 0, 0, 0, 0, 9, 0, 10, 0, 11, 0, 12],
+    ]
+    .concat();
+    let records = read_records(&data, 20);
+    assert_eq!(records.iter().map(|x| x.cast_member).collect::<Vec<_>>(), vec![2, 3]);
+    assert_eq!((records[1].pos_y, records[1].pos_x, records[1].height, records[1].width), (9, 10, 11, 12));
+    assert_eq!((records[1].color_flag, records[1].blend), (0, 0));
+  }
+
+  #[test]
+  fn skips_the_rest_of_long_records() {
+    let mut data = vec![0u8; 100];
+    data[7] = 2;
+    data[57] = 3;
+    let records = read_records(&data, 50);
+    assert_eq!(records.iter().map(|x| x.cast_member).collect::<Vec<_>>(), vec![2, 3]);
+  }
+
+  #[test]
+  fn reads_film_loop_frames() {
+    let data = crate::player::testing::film_loop_score_data(&[
+      &[(1, 2, 10, 20, 30, 40), (3, 4, 0, 0, 5, 5)],
+      &[(1, 2, 15, 20, 30, 40)],
+    ]);
+    let mut reader = BinaryReader::from_vec(&data);
+    reader.set_endian(Endian::Big);
+    let frame_data = ScoreFrameData::read_film_loop(&mut reader).unwrap();
+    assert_eq!((frame_data.header.frame_count, frame_data.header.num_channels), (2, NUM_MAIN_CHANNELS + 3));
+    let channels = frame_data.frame_channel_data.iter()
+      .map(|(frame, channel, data)| (*frame, *channel, data.cast_member, data.pos_x))
+      .collect_vec();
+    // The second frame keeps channel 3 from the first
+    assert_eq!(channels, vec![(0, 6, 2, 10), (0, 8, 4, 0), (1, 6, 2, 15), (1, 8, 4, 0)]);
+  }
+
+  #[test]
+  fn rejects_film_loops_without_record_size() {
+    let mut reader = BinaryReader::from_u8(&[0; 20]);
+    reader.set_endian(Endian::Big);
+    assert!(ScoreFrameData::read_film_loop(&mut reader).is_err());
+  }
+}
@@ -5,7 +5,7 @@ use crate::director::{chunks::literal::LiteralStore, lingo::datum::Datum};
 
 use super::handler::{HandlerDef, HandlerRecord};
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct ScriptChunk {
   pub literals: Vec<Datum>,
   pub handlers: Vec<HandlerDef>,
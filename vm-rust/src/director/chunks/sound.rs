@@ -0,0 +1,140 @@
+use binary_reader::{BinaryReader, Endian};
+
+use crate::director::utils::FOURCC;
+
+const SND_CMD_SOUND: u16 = 0x8050;
+const SND_CMD_BUFFER: u16 = 0x8051;
+
+const SND_HEADER_STANDARD: u8 = 0x00;
+const SND_HEADER_EXTENDED: u8 = 0xFF;
+const SND_HEADER_COMPRESSED: u8 = 0xFE;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SoundCodec {
+  /// Uncompressed PCM. 8-bit samples are unsigned, 16-bit samples are signed big endian.
+  Raw,
+  /// Apple IMA4 ADPCM: 34 byte packets holding 64 samples per channel.
+  ImaAdpcm,
+}
+
+/// A Mac `snd ` resource, as stored in the `snd ` chunk of a sound cast member.
+pub struct SoundChunk {
+  pub codec: SoundCodec,
+  pub sample_rate: u32,
+  pub channel_count: u16,
+  pub sample_size: u16,
+  /// Number of sample frames, or packets for compressed sounds.
+  pub frame_count: u32,
+  pub loop_start: u32,
+  pub loop_end: u32,
+  pub data: Vec<u8>,
+}
+
+impl SoundChunk {
+  pub fn from_reader(reader: &mut BinaryReader, _dir_version: u16) -> Result<SoundChunk, String> {
+    reader.set_endian(Endian::Big);
+
+    let format = reader.read_u16().map_err(|e| e.to_string())?;
+    match format {
+      1 => {
+        let data_format_count = reader.read_u16().map_err(|e| e.to_string())?;
+        // Each data format is a synthesizer id followed by its init options
+        reader.adv(data_format_count as usize * 6);
+      }
+      2 => {
+        reader.read_u16().map_err(|e| e.to_string())?; // ref count
+      }
+      _ => return Err(format!("Unsupported snd format {}", format)),
+    }
+
+    let command_count = reader.read_u16().map_err(|e| e.to_string())?;
+    let mut header_offset = None;
+    for _ in 0..command_count {
+      let cmd = reader.read_u16().map_err(|e| e.to_string())?;
+      let _param1 = reader.read_u16().map_err(|e| e.to_string())?;
+      let param2 = reader.read_u32().map_err(|e| e.to_string())?;
+      // The high bit of the command marks param2 as an offset into the resource
+      if cmd == SND_CMD_SOUND || cmd == SND_CMD_BUFFER {
+        header_offset = Some(param2 as usize);
+      }
+    }
+    let header_offset = header_offset.ok_or("snd resource has no sound or buffer command")?;
+
+    reader.jmp(header_offset);
+    let _sample_ptr = reader.read_u32().map_err(|e| e.to_string())?;
+    let length_or_channels = reader.read_u32().map_err(|e| e.to_string())?;
+    let sample_rate_fixed = reader.read_u32().map_err(|e| e.to_string())?;
+    let loop_start = reader.read_u32().map_err(|e| e.to_string())?;
+    let loop_end = reader.read_u32().map_err(|e| e.to_string())?;
+    let encode = reader.read_u8().map_err(|e| e.to_string())?;
+    let _base_frequency = reader.read_u8().map_err(|e| e.to_string())?;
+    let sample_rate = sample_rate_fixed >> 16;
+
+    let (codec, channel_count, sample_size, frame_count) = match encode {
+      SND_HEADER_STANDARD => (SoundCodec::Raw, 1, 8, length_or_channels),
+      SND_HEADER_EXTENDED => {
+        let frame_count = reader.read_u32().map_err(|e| e.to_string())?;
+        reader.read_bytes(10).map_err(|e| e.to_string())?; // AIFF sample rate
+        reader.read_u32().map_err(|e| e.to_string())?; // marker chunk
+        reader.read_u32().map_err(|e| e.to_string())?; // instrument chunks
+        reader.read_u32().map_err(|e| e.to_string())?; // AES recording
+        let sample_size = reader.read_u16().map_err(|e| e.to_string())?;
+        reader.read_bytes(14).map_err(|e| e.to_string())?; // future use
+        (SoundCodec::Raw, length_or_channels as u16, sample_size, frame_count)
+      }
+      SND_HEADER_COMPRESSED => {
+        let frame_count = reader.read_u32().map_err(|e| e.to_string())?;
+        reader.read_bytes(10).map_err(|e| e.to_string())?; // AIFF sample rate
+        reader.read_u32().map_err(|e| e.to_string())?; // marker chunk
+        let compression_format = reader.read_u32().map_err(|e| e.to_string())?;
+        reader.read_u32().map_err(|e| e.to_string())?; // future use
+        reader.read_u32().map_err(|e| e.to_string())?; // state vars
+        reader.read_u32().map_err(|e| e.to_string())?; // left over samples
+        reader.read_u16().map_err(|e| e.to_string())?; // compression id
+        reader.read_u16().map_err(|e| e.to_string())?; // packet size
+        reader.read_u16().map_err(|e| e.to_string())?; // synth id
+        let sample_size = reader.read_u16().map_err(|e| e.to_string())?;
+        if compression_format != FOURCC("ima4") {
+          return Err(format!("Unsupported snd compression format {:08X}", compression_format));
+        }
+        (SoundCodec::ImaAdpcm, length_or_channels as u16, sample_size, frame_count)
+      }
+      _ => return Err(format!("Unsupported snd header encoding {:02X}", encode)),
+    };
+
+    let data = reader.read_bytes(reader.length - reader.pos).map_err(|e| e.to_string())?.to_vec();
+    return Ok(SoundChunk {
+      codec,
+      sample_rate,
+      channel_count: channel_count.max(1),
+      sample_size,
+      frame_count,
+      loop_start,
+      loop_end,
+      data,
+    });
+  }
+}
+
+/// The `ediM` media chunk. For Shockwave Audio members this is an SWA header followed by MPEG audio frames.
+pub struct MediaChunk {
+  pub data: Vec<u8>,
+}
+
+impl MediaChunk {
+  pub fn from_reader(reader: &mut BinaryReader, _dir_version: u16) -> Result<MediaChunk, String> {
+    let data = reader.read_bytes(reader.length).map_err(|e| e.to_string())?.to_vec();
+    return Ok(MediaChunk { data });
+  }
+
+  /// Returns the MPEG audio stream, skipping any header before the first frame sync.
+  pub fn mpeg_audio_data(&self) -> Option<&[u8]> {
+    let data = &self.data;
+    for i in 0..data.len().saturating_sub(1) {
+      if data[i] == 0xFF && (data[i + 1] & 0xE0) == 0xE0 && (data[i + 1] & 0x06) != 0 {
+        return Some(&data[i..]);
+      }
+    }
+    None
+  }
+}
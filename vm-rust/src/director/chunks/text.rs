@@ -2,12 +2,46 @@ use binary_reader::BinaryReader;
 
 use crate::io::reader::DirectorExt;
 
+pub const TEXT_STYLE_BOLD: u8 = 0x01;
+pub const TEXT_STYLE_ITALIC: u8 = 0x02;
+pub const TEXT_STYLE_UNDERLINE: u8 = 0x04;
+
+/// A style run of an `STXT` chunk. The run applies from `start_offset` until the next run.
+#[derive(Clone)]
+pub struct TextChunkStyle {
+  pub start_offset: u32,
+  pub height: u16,
+  pub ascent: u16,
+  pub font_id: u16,
+  pub style: u8,
+  pub font_size: u16,
+  /// 16-bit per component QuickDraw colour.
+  pub color: (u16, u16, u16),
+}
+
+impl TextChunkStyle {
+  fn read(reader: &mut BinaryReader) -> Result<TextChunkStyle, String> {
+    let start_offset = reader.read_u32().map_err(|e| e.to_string())?;
+    let height = reader.read_u16().map_err(|e| e.to_string())?;
+    let ascent = reader.read_u16().map_err(|e| e.to_string())?;
+    let font_id = reader.read_u16().map_err(|e| e.to_string())?;
+    let style = reader.read_u8().map_err(|e| e.to_string())?;
+    reader.read_u8().map_err(|e| e.to_string())?;
+    let font_size = reader.read_u16().map_err(|e| e.to_string())?;
+    let red = reader.read_u16().map_err(|e| e.to_string())?;
+    let green = reader.read_u16().map_err(|e| e.to_string())?;
+    let blue = reader.read_u16().map_err(|e| e.to_string())?;
+    Ok(TextChunkStyle { start_offset, height, ascent, font_id, style, font_size, color: (red, green, blue) })
+  }
+}
+
 pub struct TextChunk {
   pub offset: usize,
   pub text_length: usize,
   pub data_length: usize,
   pub text: String,
   pub data: Vec<u8>,
+  pub styles: Vec<TextChunkStyle>,
 }
 
 impl TextChunk {
@@ -21,13 +55,29 @@ impl TextChunk {
 
     let text_length = reader.read_u32().unwrap() as usize;
     let data_length = reader.read_u32().unwrap() as usize;
+    let text = reader.read_string(text_length).unwrap();
+    let data = reader.read_bytes(data_length).unwrap().to_vec();
+
+    let mut styles = vec![];
+    if data.len() >= 2 {
+      let mut style_reader = BinaryReader::from_vec(&data);
+      style_reader.set_endian(binary_reader::Endian::Big);
+      let style_count = style_reader.read_u16().unwrap();
+      for _ in 0..style_count {
+        match TextChunkStyle::read(&mut style_reader) {
+          Ok(style) => styles.push(style),
+          Err(_) => break,
+        }
+      }
+    }
 
     Ok(TextChunk {
       offset,
       text_length,
       data_length,
-      text: reader.read_string(text_length).unwrap(),
-      data: reader.read_bytes(data_length).unwrap().to_vec(),
+      text,
+      data,
+      styles,
     })
   }
 }
@@ -20,7 +20,8 @@ pub enum MemberType {
 	DigitalVideo = (10),
 	Script = (11),
 	RTE = (12),
-  Font = (15)
+  Font = (15),
+	Xtra = (16)
 }
 
 impl MemberType {
@@ -48,7 +49,82 @@ pub struct BitmapInfo {
 }
 
 #[derive(Clone)]
-#[allow(dead_code)]
+pub struct FieldInfo {
+	pub border: u8,
+	pub margin: u8,
+	pub box_drop_shadow: u8,
+	/// 0: adjust to fit, 1: scrolling, 2: fixed, 3: limit to field size.
+	pub box_type: u8,
+	/// 0: left, 1: center, -1: right.
+	pub alignment: i16,
+	pub bg_color: (u16, u16, u16),
+	pub scroll: u16,
+	/// Initial rect of the field, as (left, top, right, bottom).
+	pub rect: (i16, i16, i16, i16),
+	pub max_height: u16,
+	pub text_shadow: u8,
+	/// 0x1: editable, 0x2: auto tab, 0x4: don't wrap.
+	pub flags: u8,
+	pub text_height: u16,
+}
+
+impl FieldInfo {
+	pub fn alignment_name(&self) -> &'static str {
+		match self.alignment {
+			1 => "center",
+			-1 => "right",
+			_ => "left",
+		}
+	}
+
+	pub fn box_type_name(&self) -> &'static str {
+		match self.box_type {
+			1 => "scroll",
+			2 => "fixed",
+			3 => "limit",
+			_ => "adjust",
+		}
+	}
+
+	pub fn editable(&self) -> bool {
+		self.flags & 0x1 != 0
+	}
+
+	pub fn auto_tab(&self) -> bool {
+		self.flags & 0x2 != 0
+	}
+
+	pub fn word_wrap(&self) -> bool {
+		self.flags & 0x4 == 0
+	}
+}
+
+#[derive(Clone)]
+pub struct FilmLoopInfo {
+	/// Bounding rect of the loop contents on the stage it was recorded from, as (left, top, right, bottom).
+	pub rect: (i16, i16, i16, i16),
+	pub looping: bool,
+	pub crop: bool,
+	pub center: bool,
+	pub enable_sound: bool,
+}
+
+impl FilmLoopInfo {
+	pub fn width(&self) -> i32 {
+		self.rect.2 as i32 - self.rect.0 as i32
+	}
+
+	pub fn height(&self) -> i32 {
+		self.rect.3 as i32 - self.rect.1 as i32
+	}
+
+	/// Film loops are registered at the center of their rect.
+	pub fn reg_point(&self) -> (i16, i16) {
+		((self.width() / 2) as i16, (self.height() / 2) as i16)
+	}
+}
+
+#[derive(Clone, Copy, PartialEq)]
 pub enum ShapeType {
 	Rect,
 	Oval,
@@ -57,13 +133,42 @@ pub enum ShapeType {
 	Unknown,
 }
 
+impl ShapeType {
+	pub fn from_symbol(name: &str) -> Option<ShapeType> {
+		match name.to_lowercase().as_str() {
+			"rect" => Some(ShapeType::Rect),
+			"oval" => Some(ShapeType::Oval),
+			"roundrect" => Some(ShapeType::OvalRect),
+			"line" => Some(ShapeType::Line),
+			_ => None,
+		}
+	}
+
+	pub fn symbol(&self) -> &'static str {
+		match self {
+			ShapeType::Rect => "rect",
+			ShapeType::Oval => "oval",
+			ShapeType::OvalRect => "roundRect",
+			ShapeType::Line => "line",
+			ShapeType::Unknown => "unknown",
+		}
+	}
+}
+
 #[derive(Clone)]
 pub struct ShapeInfo {
 	pub shape_type: ShapeType,
 	pub reg_point: (i16, i16),
 	pub width: u16,
 	pub height: u16,
-	pub color: u8,
+	/// Number of the 8x8 fill pattern in the tool palette, 0 and 1 being solid.
+	pub pattern: u16,
+	pub fore_color: u8,
+	pub back_color: u8,
+	pub filled: bool,
+	pub line_size: u8,
+	/// 0 for a line from the top left to the bottom right corner, 1 from the bottom left to the top right.
+	pub line_direction: u8,
 }
 
 impl ScriptType {
@@ -123,27 +228,151 @@ impl From<&[u8]> for ShapeInfo {
 		reader.set_endian(binary_reader::Endian::Big);
 
 		let shape_type = reader.read_u16().unwrap(); // 00 01
-		let reg_y = reader.read_u16().unwrap(); // 00 00
-		let reg_x = reader.read_u16().unwrap(); // 00 00
-		let height = reader.read_u16().unwrap(); // 00 36
-		let width = reader.read_u16().unwrap(); // 02 d0
-		let _ = reader.read_u16().unwrap();
-		let color = reader.read_u8().unwrap();
-		let _ = reader.read_u16().unwrap();
-		let _ = reader.read_u16().unwrap();
-		
+		let top = reader.read_i16().unwrap(); // 00 00
+		let left = reader.read_i16().unwrap(); // 00 00
+		let bottom = reader.read_i16().unwrap(); // 00 36
+		let right = reader.read_i16().unwrap(); // 02 d0
+		let pattern = reader.read_u16().unwrap_or(1);
+		let fore_color = reader.read_u8().unwrap_or(255);
+		let back_color = reader.read_u8().unwrap_or(0);
+		let fill_type = reader.read_u8().unwrap_or(1);
+		let line_size = reader.read_u8().unwrap_or(1);
+		// Stored as 5 for top left to bottom right and 6 for bottom left to top right
+		let line_direction = reader.read_u8().unwrap_or(5);
+
 		return ShapeInfo {
 			shape_type: match shape_type {
 				0x0001 => ShapeType::Rect,
+				0x0002 => ShapeType::OvalRect,
+				0x0003 => ShapeType::Oval,
+				0x0004 => ShapeType::Line,
 				_ => {
 					warn!("Unknown shape type: {:x}", shape_type);
 					ShapeType::Unknown
 				}
 			},
-			reg_point: (reg_x as i16, reg_y as i16),
-			width,
-			height,
-			color,
+			reg_point: (left, top),
+			width: (right as i32 - left as i32).max(0) as u16,
+			height: (bottom as i32 - top as i32).max(0) as u16,
+			pattern,
+			fore_color,
+			back_color,
+			filled: fill_type != 0,
+			line_size,
+			line_direction: if line_direction == 6 { 1 } else { 0 },
+		};
+	}
+}
+
+impl From<&[u8]> for FilmLoopInfo {
+	fn from(bytes: &[u8]) -> FilmLoopInfo {
+		let mut reader = BinaryReader::from_u8(bytes);
+		reader.set_endian(binary_reader::Endian::Big);
+
+		let top = reader.read_i16().unwrap_or(0);
+		let left = reader.read_i16().unwrap_or(0);
+		let bottom = reader.read_i16().unwrap_or(0);
+		let right = reader.read_i16().unwrap_or(0);
+		let flags = reader.read_u32().unwrap_or(0);
+
+		return FilmLoopInfo {
+			rect: (left, top, right, bottom),
+			looping: flags & 0x40 == 0,
+			crop: flags & 0x02 == 0,
+			center: flags & 0x01 != 0,
+			enable_sound: flags & 0x08 != 0,
+		};
+	}
+}
+
+impl From<&[u8]> for FieldInfo {
+	fn from(bytes: &[u8]) -> FieldInfo {
+		let mut reader = BinaryReader::from_u8(bytes);
+		reader.set_endian(binary_reader::Endian::Big);
+
+		let border = reader.read_u8().unwrap_or(0);
+		let margin = reader.read_u8().unwrap_or(0);
+		let box_drop_shadow = reader.read_u8().unwrap_or(0);
+		let box_type = reader.read_u8().unwrap_or(0);
+		let alignment = reader.read_i16().unwrap_or(0);
+		let bg_red = reader.read_u16().unwrap_or(0xFFFF);
+		let bg_green = reader.read_u16().unwrap_or(0xFFFF);
+		let bg_blue = reader.read_u16().unwrap_or(0xFFFF);
+		let scroll = reader.read_u16().unwrap_or(0);
+		let top = reader.read_i16().unwrap_or(0);
+		let left = reader.read_i16().unwrap_or(0);
+		let bottom = reader.read_i16().unwrap_or(0);
+		let right = reader.read_i16().unwrap_or(0);
+		let max_height = reader.read_u16().unwrap_or(0);
+		let text_shadow = reader.read_u8().unwrap_or(0);
+		let flags = reader.read_u8().unwrap_or(0);
+		let text_height = reader.read_u16().unwrap_or(0);
+
+		return FieldInfo {
+			border,
+			margin,
+			box_drop_shadow,
+			box_type,
+			alignment,
+			bg_color: (bg_red, bg_green, bg_blue),
+			scroll,
+			rect: (left, top, right, bottom),
+			max_height,
+			text_shadow,
+			flags,
+			text_height,
 		};
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn shape_bytes(shape_type: u16, rect: (i16, i16, i16, i16), pattern: u16, fill_type: u8, line_size: u8, line_direction: u8) -> Vec<u8> {
+		let (top, left, bottom, right) = rect;
+		let mut bytes = vec![];
+		for value in [shape_type as i16, top, left, bottom, right, pattern as i16] {
+			bytes.extend_from_slice(&value.to_be_bytes());
+		}
+		bytes.extend_from_slice(&[0x22, 0x05, fill_type, line_size, line_direction]);
+		bytes
+	}
+
+	#[test]
+	fn reads_shape_info() {
+		let info = ShapeInfo::from(shape_bytes(3, (10, 20, 60, 120), 4, 1, 2, 6).as_slice());
+		assert!(info.shape_type == ShapeType::Oval);
+		assert_eq!(info.reg_point, (20, 10));
+		assert_eq!((info.width, info.height), (100, 50));
+		assert_eq!(info.pattern, 4);
+		assert_eq!((info.fore_color, info.back_color), (0x22, 0x05));
+		assert!(info.filled);
+		assert_eq!(info.line_size, 2);
+		assert_eq!(info.line_direction, 1);
+
+		let info = ShapeInfo::from(shape_bytes(4, (0, 0, 10, 10), 1, 0, 0, 5).as_slice());
+		assert!(info.shape_type == ShapeType::Line);
+		assert!(!info.filled);
+		assert_eq!(info.line_direction, 0);
+	}
+
+	#[test]
+	fn measures_shapes_wider_than_an_i16() {
+		let info = ShapeInfo::from(shape_bytes(1, (-20000, -20000, 20000, 20000), 1, 1, 1, 5).as_slice());
+		assert_eq!((info.width, info.height), (40000, 40000));
+
+		let inverted = ShapeInfo::from(shape_bytes(1, (10, 10, 0, 0), 1, 1, 1, 5).as_slice());
+		assert_eq!((inverted.width, inverted.height), (0, 0));
+	}
+
+	#[test]
+	fn falls_back_to_defaults_for_short_shape_data() {
+		let bytes = shape_bytes(2, (0, 0, 10, 10), 1, 1, 1, 5);
+		let info = ShapeInfo::from(&bytes[..10]);
+		assert!(info.shape_type == ShapeType::OvalRect);
+		assert_eq!(info.pattern, 1);
+		assert!(info.filled);
+		assert_eq!(info.line_size, 1);
+	}
+}
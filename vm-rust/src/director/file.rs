@@ -25,6 +25,9 @@ use super::chunks::cast_member::CastMemberChunk;
 use super::chunks::key_table::KeyTableEntry;
 use super::chunks::lctx::ScriptContextChunk;
 use super::chunks::make_chunk;
+use super::chunks::frame_labels::FrameLabelsChunk;
+use super::chunks::font_map::FontMapChunk;
+use super::chunks::font_map::FontXPlatformMapChunk;
 use super::chunks::score::ScoreChunk;
 use super::chunks::script::ScriptChunk;
 use super::chunks::script_names::ScriptNamesChunk;
@@ -34,10 +37,14 @@ pub struct DirectorFile {
   pub file_name: String,
   //pub endian: Endian,
   pub version: u16,
+  pub after_burned: bool,
   pub cast_entries: Vec<CastListEntry>,
   pub casts: Vec<CastDef>,
   pub config: ConfigChunk,
   pub score: Option<ScoreChunk>,
+  pub frame_labels: Option<FrameLabelsChunk>,
+  pub font_map: Option<FontMapChunk>,
+  pub font_xplatform_map: Option<FontXPlatformMapChunk>,
 }
 
 // macro_rules! console_log {
@@ -68,8 +75,10 @@ impl DirectorFile {
     let mut ils_body_offset: usize = 0;
 
     if codec == FOURCC("MV93") || codec == FOURCC("MC95") {
-      // read memory map
-      return Err("read mmap not implemented".to_owned());
+      read_memory_map(
+        reader,
+        &mut chunk_container.chunk_info
+      )?;
     } else if codec == FOURCC("FGDM") || codec == FOURCC("FGDC") {
       after_burned = true;
       ils_body_offset = read_after_burner_map(
@@ -121,15 +130,22 @@ impl DirectorFile {
     // }
 
     let score = get_score_chunk(reader, chunk_container, &mut rifx);
+    let frame_labels = get_frame_labels_chunk(reader, chunk_container, &mut rifx);
+    let font_map = get_font_map_chunk(reader, chunk_container, &mut rifx);
+    let font_xplatform_map = get_font_xplatform_map_chunk(reader, chunk_container, &mut rifx);
     
     return Ok(DirectorFile { 
       base_path, 
       file_name, 
       version: rifx.dir_version,
+      after_burned: rifx.after_burned,
       casts,
       cast_entries,
       config,
-      score
+      score,
+      frame_labels,
+      font_map,
+      font_xplatform_map,
     });
   }
 }
@@ -337,6 +353,53 @@ pub fn get_score_chunk(
   }
 }
 
+pub fn get_frame_labels_chunk( 
+  reader: &mut BinaryReader, 
+  chunk_container: &mut ChunkContainer,
+  rifx: &mut RIFXReaderContext,
+) -> Option<FrameLabelsChunk> {
+  let chunk = get_first_chunk(
+    reader, 
+    chunk_container,
+    rifx,
+    FOURCC("VWLB"),
+  );
+  if chunk.is_none() {
+    return None;
+  } else if let Chunk::FrameLabels(chunk_data) = chunk.unwrap() {
+    return Some(chunk_data);
+  } else {
+    panic!("Not a frame labels chunk");
+  }
+}
+
+pub fn get_font_map_chunk( 
+  reader: &mut BinaryReader, 
+  chunk_container: &mut ChunkContainer,
+  rifx: &mut RIFXReaderContext,
+) -> Option<FontMapChunk> {
+  let chunk = try_get_first_chunk(reader, chunk_container, rifx, FOURCC("Fmap"))
+    .or_else(|| try_get_first_chunk(reader, chunk_container, rifx, FOURCC("VWFM")));
+  match chunk {
+    Some(Chunk::FontMap(chunk_data)) => Some(chunk_data),
+    Some(_) => panic!("Not a font map chunk"),
+    None => None,
+  }
+}
+
+pub fn get_font_xplatform_map_chunk( 
+  reader: &mut BinaryReader, 
+  chunk_container: &mut ChunkContainer,
+  rifx: &mut RIFXReaderContext,
+) -> Option<FontXPlatformMapChunk> {
+  let chunk = try_get_first_chunk(reader, chunk_container, rifx, FOURCC("FXmp"));
+  match chunk {
+    Some(Chunk::FontXPlatformMap(chunk_data)) => Some(chunk_data),
+    Some(_) => panic!("Not a font cross-platform map chunk"),
+    None => None,
+  }
+}
+
 pub fn get_script_context_key_entry_for_cast<'a>(
   _reader: &mut BinaryReader, 
   _chunk_container: &mut ChunkContainer,
@@ -444,6 +507,49 @@ fn read_config(
   }
 }
 
+fn read_memory_map(
+  reader: &mut BinaryReader,
+  chunk_info: &mut HashMap<u32, ChunkInfo>
+) -> Result<(), String> {
+  let mut rifx = RIFXReaderContext {
+    after_burned: false,
+    ils_body_offset: 0,
+    dir_version: 0,
+    lctx_capital_x: false,
+  };
+
+  // Initial map
+  let imap_data = read_chunk_data(reader, FOURCC("imap"), u32::MAX)?;
+  let imap = match make_chunk(reader.endian, &mut rifx, FOURCC("imap"), &imap_data)? {
+    Chunk::InitialMap(imap) => imap,
+    _ => return Err("readMemoryMap(): Not an imap chunk".to_owned()),
+  };
+
+  // Codec-dependent map
+  reader.jmp(imap.mmap_offset);
+  let mmap_data = read_chunk_data(reader, FOURCC("mmap"), u32::MAX)?;
+  let mmap = match make_chunk(reader.endian, &mut rifx, FOURCC("mmap"), &mmap_data)? {
+    Chunk::MemoryMap(mmap) => mmap,
+    _ => return Err("readMemoryMap(): Not an mmap chunk".to_owned()),
+  };
+
+  for (i, entry) in mmap.map_array.iter().enumerate() {
+    if entry.fourcc == FOURCC("free") || entry.fourcc == FOURCC("junk") {
+      continue;
+    }
+    let info = ChunkInfo {
+      id: i as u32,
+      fourcc: entry.fourcc,
+      len: entry.len as usize,
+      uncompressed_len: entry.len as usize,
+      offset: entry.offset as usize,
+      compression_id: NULL_COMPRESSION_GUID,
+    };
+    chunk_info.insert(i as u32, info);
+  }
+  return Ok(());
+}
+
 fn read_after_burner_map(
   reader: &mut BinaryReader,
   cached_chunk_views: &mut HashMap<u32, Vec<u8>>,
@@ -653,6 +759,25 @@ fn get_first_chunk(
   }
 }
 
+/// Like `get_first_chunk`, but logs chunks that fail to load instead of panicking.
+/// Used for chunks the movie can play without, such as font maps stored with the built-in map compression.
+fn try_get_first_chunk(
+  reader: &mut BinaryReader, 
+  chunk_container: &mut ChunkContainer,
+  rifx: &mut RIFXReaderContext,
+  fourcc: u32,
+) -> Option<Chunk> {
+  let info = get_first_chunk_info(&chunk_container.chunk_info, fourcc)?;
+  let (fourcc, id) = (info.fourcc, info.id);
+  match get_chunk(reader, chunk_container, rifx, fourcc, id) {
+    Ok(chunk) => Some(chunk),
+    Err(err) => {
+      warn!("Could not load '{}' chunk: {}", fourcc_to_string(fourcc), err);
+      None
+    }
+  }
+}
+
 fn read_chunk_data(reader: &mut BinaryReader, fourcc: u32, len: u32) -> Result<Vec<u8>, String> {
   let offset = reader.pos;
 
@@ -752,7 +877,7 @@ fn get_chunk_data(
         }
       } else {
         reader.jmp(info.offset);
-        chunk_container.cached_chunk_views.insert(id, read_chunk_data(reader, fourcc, id).unwrap());
+        chunk_container.cached_chunk_views.insert(id, read_chunk_data(reader, fourcc, info.len as u32)?);
       }
 
       return Ok(chunk_container.cached_chunk_views.get(&id).unwrap().to_vec());
@@ -804,3 +929,112 @@ pub fn get_children_of_chunk<'a>(chunk_id: &u32, key_table: &'a KeyTableChunk) -
 fn compression_implemented(compression_id: &MoaID) -> bool {
   return *compression_id == ZLIB_COMPRESSION_GUID || *compression_id == ZLIB_COMPRESSION_GUID2 || *compression_id == SND_COMPRESSION_GUID;
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn chunk(fourcc: &str, data: &[u8]) -> Vec<u8> {
+    let mut bytes = FOURCC(fourcc).to_be_bytes().to_vec();
+    bytes.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(data);
+    bytes
+  }
+
+  /// Lays out an uncompressed movie with the given chunks after its imap and mmap.
+  fn memory_mapped_movie(chunks: &[(&str, Vec<u8>)]) -> Vec<u8> {
+    let header_len = 12;
+    let imap_len = 8 + 24;
+    let entries = ["RIFX", "imap", "mmap", "free"].iter().map(|x| x.to_string())
+      .chain(chunks.iter().map(|(fourcc, _)| fourcc.to_string()))
+      .collect_vec();
+    let mmap_len = 8 + 24 + 20 * entries.len();
+
+    let mut offsets = vec![0, header_len, header_len + imap_len, 0];
+    let mut offset = header_len + imap_len + mmap_len;
+    for (_, data) in chunks {
+      offsets.push(offset);
+      offset += 8 + data.len();
+    }
+
+    let mut imap = vec![];
+    for value in [1, (header_len + imap_len) as u32, 0x4C7, 0, 0, 0] {
+      imap.extend_from_slice(&value.to_be_bytes());
+    }
+
+    let mut mmap = vec![];
+    mmap.extend_from_slice(&24u16.to_be_bytes());
+    mmap.extend_from_slice(&20u16.to_be_bytes());
+    mmap.extend_from_slice(&(entries.len() as u32).to_be_bytes());
+    mmap.extend_from_slice(&(entries.len() as u32).to_be_bytes());
+    mmap.extend_from_slice(&(-1i32).to_be_bytes());
+    mmap.extend_from_slice(&(-1i32).to_be_bytes());
+    mmap.extend_from_slice(&3i32.to_be_bytes());
+    for (i, fourcc) in entries.iter().enumerate() {
+      let len = if i < 4 { 0 } else { chunks[i - 4].1.len() as u32 };
+      mmap.extend_from_slice(&FOURCC(fourcc).to_be_bytes());
+      mmap.extend_from_slice(&len.to_be_bytes());
+      mmap.extend_from_slice(&(offsets[i] as u32).to_be_bytes());
+      mmap.extend_from_slice(&0u16.to_be_bytes());
+      mmap.extend_from_slice(&0u16.to_be_bytes());
+      mmap.extend_from_slice(&(-1i32).to_be_bytes());
+    }
+
+    let mut bytes = chunk("RIFX", &FOURCC("MV93").to_be_bytes());
+    bytes.extend_from_slice(&chunk("imap", &imap));
+    bytes.extend_from_slice(&chunk("mmap", &mmap));
+    for (fourcc, data) in chunks {
+      bytes.extend_from_slice(&chunk(fourcc, data));
+    }
+    bytes
+  }
+
+  fn read_test_memory_map(bytes: &Vec<u8>) -> (BinaryReader, ChunkContainer) {
+    let mut reader = BinaryReader::from_vec(bytes);
+    reader.set_endian(binary_reader::Endian::Big);
+    reader.jmp(12);
+    let mut chunk_container = ChunkContainer {
+      cached_chunk_views: HashMap::new(),
+      chunk_info: HashMap::new(),
+      deserialized_chunks: HashMap::new(),
+    };
+    read_memory_map(&mut reader, &mut chunk_container.chunk_info).unwrap();
+    (reader, chunk_container)
+  }
+
+  #[test]
+  fn reads_the_memory_map_of_uncompressed_movies() {
+    let bytes = memory_mapped_movie(&[("KEY*", vec![1, 2, 3, 4]), ("STXT", vec![5, 6])]);
+    let (_, chunk_container) = read_test_memory_map(&bytes);
+
+    let mut chunks = chunk_container.chunk_info.values()
+      .map(|x| (x.id, fourcc_to_string(x.fourcc), x.len))
+      .collect_vec();
+    chunks.sort();
+    assert_eq!(chunks, vec![
+      (0, "RIFX".to_string(), 0),
+      (1, "imap".to_string(), 0),
+      (2, "mmap".to_string(), 0),
+      (4, "KEY*".to_string(), 4),
+      (5, "STXT".to_string(), 2),
+    ]);
+  }
+
+  #[test]
+  fn reads_chunk_data_at_memory_mapped_offsets() {
+    let bytes = memory_mapped_movie(&[("KEY*", vec![1, 2, 3, 4]), ("STXT", vec![5, 6])]);
+    let (mut reader, mut chunk_container) = read_test_memory_map(&bytes);
+    let rifx = RIFXReaderContext {
+      after_burned: false,
+      ils_body_offset: 0,
+      dir_version: 0,
+      lctx_capital_x: false,
+    };
+
+    let data = get_chunk_data(&mut reader, &mut chunk_container, &rifx, FOURCC("STXT"), 5).unwrap();
+    assert_eq!(data, vec![5, 6]);
+    let data = get_chunk_data(&mut reader, &mut chunk_container, &rifx, FOURCC("KEY*"), 4).unwrap();
+    assert_eq!(data, vec![1, 2, 3, 4]);
+    assert!(get_chunk_data(&mut reader, &mut chunk_container, &rifx, FOURCC("STXT"), 4).is_err());
+  }
+}
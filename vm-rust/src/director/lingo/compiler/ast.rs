@@ -0,0 +1,38 @@
+use crate::director::lingo::decompiler::ast::Expr;
+
+/// A parsed script. Expressions share the decompiler's `Expr` so both sides agree on
+/// what each construct compiles to.
+pub struct ScriptNode {
+  pub properties: Vec<String>,
+  pub globals: Vec<String>,
+  pub handlers: Vec<HandlerNode>,
+}
+
+pub struct HandlerNode {
+  pub name: String,
+  pub args: Vec<String>,
+  pub body: Vec<Stmt>,
+}
+
+pub struct CaseLabelNode {
+  pub values: Vec<Expr>,
+  pub body: Vec<Stmt>,
+}
+
+pub enum Stmt {
+  Expr(Expr),
+  Global(Vec<String>),
+  Assign(Expr, Expr),
+  Put(u8, Expr, Expr),
+  Call(String, Vec<Expr>),
+  Return(Option<Expr>),
+  Exit,
+  ExitRepeat,
+  NextRepeat,
+  If(Expr, Vec<Stmt>, Vec<Stmt>),
+  RepeatWhile(Expr, Vec<Stmt>),
+  RepeatWithIn(String, Expr, Vec<Stmt>),
+  RepeatWithTo(String, Expr, Expr, bool, Vec<Stmt>), // bool is true for `to` and false for `down to`
+  Case(Expr, Vec<CaseLabelNode>, Option<Vec<Stmt>>),
+  Tell(Expr, Vec<Stmt>),
+}
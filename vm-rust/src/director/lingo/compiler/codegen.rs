@@ -0,0 +1,722 @@
+use std::convert::TryFrom;
+
+use fxhash::FxHashMap;
+
+use crate::director::{
+  chunks::{handler::{Bytecode, HandlerDef}, script::ScriptChunk},
+  file::get_variable_multiplier,
+  lingo::{datum::{Datum, StringChunkType}, decompiler::ast::Expr, opcode::OpCode},
+};
+
+use super::ast::{HandlerNode, ScriptNode, Stmt};
+
+type CodegenResult<T> = Result<T, String>;
+
+fn chunk_type_id(chunk_type: &StringChunkType) -> i64 {
+  match chunk_type {
+    StringChunkType::Item => 0x01,
+    StringChunkType::Word => 0x02,
+    StringChunkType::Char => 0x03,
+    StringChunkType::Line => 0x04,
+  }
+}
+
+/// The argument of a bytecode, or a label for jumps whose offset is only known once the handler is complete.
+enum Operand {
+  Value(i64),
+  Label(usize),
+}
+
+fn operand_size(opcode: OpCode, operand: &Operand) -> usize {
+  if num::ToPrimitive::to_u16(&opcode).unwrap() < 0x40 {
+    return 0;
+  }
+  match operand {
+    Operand::Label(_) => 2,
+    Operand::Value(value) => match opcode {
+      OpCode::PushInt8 => 1,
+      OpCode::PushInt16 => 2,
+      OpCode::PushInt32 => 4,
+      _ if (0..=0xff).contains(value) => 1,
+      _ if (0..=0xffff).contains(value) => 2,
+      _ => 4,
+    },
+  }
+}
+
+enum Var {
+  Param(usize),
+  Local(usize),
+  Global(String),
+  Prop(String),
+}
+
+/// Generates the bytecode of a script. Bytecode refers to names by index, so new names are
+/// added to the names of the script context the script is compiled into.
+pub struct ScriptCodegen<'a> {
+  names: &'a mut Vec<String>,
+  multiplier: u32,
+  dir_version: u16,
+  literals: Vec<Datum>,
+  properties: Vec<String>,
+  globals: Vec<String>,
+  handler_names: Vec<String>,
+  /// Whether undeclared variables are globals rather than locals, as in `do` and `value()`.
+  implicit_globals: bool,
+}
+
+impl<'a> ScriptCodegen<'a> {
+  pub fn new(names: &'a mut Vec<String>, dir_version: u16, capital_x: bool) -> ScriptCodegen<'a> {
+    ScriptCodegen {
+      names,
+      multiplier: get_variable_multiplier(capital_x, dir_version),
+      dir_version,
+      literals: vec![],
+      properties: vec![],
+      globals: vec![],
+      handler_names: vec![],
+      implicit_globals: false,
+    }
+  }
+
+  pub fn generate_script(mut self, script: &ScriptNode) -> CodegenResult<ScriptChunk> {
+    self.properties = script.properties.clone();
+    self.globals = script.globals.clone();
+    self.handler_names = script.handlers.iter().map(|x| x.name.to_owned()).collect();
+    let handlers = script
+      .handlers
+      .iter()
+      .map(|handler| self.generate_handler(handler))
+      .collect::<CodegenResult<Vec<_>>>()?;
+    let property_name_ids = script.properties.iter().map(|x| self.name_id(x)).collect();
+    Ok(ScriptChunk {
+      literals: self.literals,
+      handlers,
+      property_name_ids,
+    })
+  }
+
+  /// Generates a script with a single handler for the statements of `do` or the expression of `value()`.
+  pub fn generate_eval_script(mut self, handler_name: &str, body: Vec<Stmt>) -> CodegenResult<ScriptChunk> {
+    self.implicit_globals = true;
+    let handler = HandlerNode {
+      name: handler_name.to_owned(),
+      args: vec![],
+      body,
+    };
+    let handler = self.generate_handler(&handler)?;
+    Ok(ScriptChunk {
+      literals: self.literals,
+      handlers: vec![handler],
+      property_name_ids: vec![],
+    })
+  }
+
+  fn generate_handler(&mut self, handler: &HandlerNode) -> CodegenResult<HandlerDef> {
+    let name_id = self.name_id(&handler.name);
+    let mut codegen = HandlerCodegen {
+      script: self,
+      args: handler.args.clone(),
+      locals: vec![],
+      globals: vec![],
+      ops: vec![],
+      labels: vec![],
+      loops: vec![],
+      tell_depth: 0,
+    };
+    codegen.block(&handler.body)?;
+    Ok(codegen.finish(name_id))
+  }
+
+  /// Names are shared by all scripts of a cast and looked up case-insensitively, except for
+  /// built-in handlers whose exact spelling is preferred.
+  fn name_id(&mut self, name: &str) -> u16 {
+    let index = self
+      .names
+      .iter()
+      .position(|x| x == name)
+      .or_else(|| self.names.iter().position(|x| x.eq_ignore_ascii_case(name)));
+    match index {
+      Some(index) => index as u16,
+      None => {
+        self.names.push(name.to_owned());
+        (self.names.len() - 1) as u16
+      }
+    }
+  }
+
+  fn literal_index(&mut self, literal: Datum) -> usize {
+    let existing = self.literals.iter().position(|x| match (x, &literal) {
+      (Datum::String(a), Datum::String(b)) => a == b,
+      (Datum::Float(a), Datum::Float(b)) => a.to_bits() == b.to_bits(),
+      _ => false,
+    });
+    existing.unwrap_or_else(|| {
+      self.literals.push(literal);
+      self.literals.len() - 1
+    })
+  }
+}
+
+struct HandlerCodegen<'s, 'a> {
+  script: &'s mut ScriptCodegen<'a>,
+  args: Vec<String>,
+  locals: Vec<String>,
+  globals: Vec<String>,
+  ops: Vec<(OpCode, Operand)>,
+  /// The index of the bytecode each label points to.
+  labels: Vec<usize>,
+  /// The `next repeat` and `exit repeat` labels of the enclosing loops.
+  loops: Vec<(usize, usize)>,
+  /// The number of enclosing `tell` blocks, whose calls go to the told object first.
+  tell_depth: usize,
+}
+
+impl<'s, 'a> HandlerCodegen<'s, 'a> {
+  fn emit(&mut self, opcode: OpCode, obj: i64) {
+    self.ops.push((opcode, Operand::Value(obj)));
+  }
+
+  fn emit_jump(&mut self, opcode: OpCode, label: usize) {
+    self.ops.push((opcode, Operand::Label(label)));
+  }
+
+  fn new_label(&mut self) -> usize {
+    self.labels.push(usize::MAX);
+    self.labels.len() - 1
+  }
+
+  fn place_label(&mut self, label: usize) {
+    self.labels[label] = self.ops.len();
+  }
+
+  fn emit_name(&mut self, opcode: OpCode, name: &str) {
+    let name_id = self.script.name_id(name);
+    self.emit(opcode, name_id as i64);
+  }
+
+  fn emit_int(&mut self, value: i64) {
+    if value == 0 {
+      self.emit(OpCode::PushZero, 0);
+    } else if i8::try_from(value).is_ok() {
+      self.emit(OpCode::PushInt8, value);
+    } else if i16::try_from(value).is_ok() {
+      self.emit(OpCode::PushInt16, value);
+    } else {
+      self.emit(OpCode::PushInt32, value);
+    }
+  }
+
+  fn emit_literal(&mut self, literal: Datum) {
+    let index = self.script.literal_index(literal);
+    self.emit(OpCode::PushCons, (index * self.script.multiplier as usize) as i64);
+  }
+
+  fn finish(mut self, name_id: u16) -> HandlerDef {
+    self.emit(OpCode::Ret, 0);
+
+    let mut positions = Vec::with_capacity(self.ops.len());
+    let mut pos = 0;
+    for (opcode, operand) in &self.ops {
+      positions.push(pos);
+      pos += 1 + operand_size(*opcode, operand);
+    }
+
+    let mut bytecode_array = Vec::with_capacity(self.ops.len());
+    let mut bytecode_index_map = FxHashMap::default();
+    for (index, (opcode, operand)) in self.ops.iter().enumerate() {
+      let obj = match operand {
+        Operand::Value(value) => *value,
+        Operand::Label(label) => {
+          let target = positions[self.labels[*label]] as i64;
+          if *opcode == OpCode::EndRepeat {
+            positions[index] as i64 - target
+          } else {
+            target - positions[index] as i64
+          }
+        }
+      };
+      bytecode_array.push(Bytecode::new(*opcode, obj, positions[index]));
+      bytecode_index_map.insert(positions[index], index);
+    }
+
+    let script = &mut self.script;
+    let argument_name_ids = self.args.iter().map(|x| script.name_id(x)).collect();
+    let local_name_ids = self.locals.iter().map(|x| script.name_id(x)).collect();
+    let global_name_ids = self.globals.iter().map(|x| script.name_id(x)).collect();
+    HandlerDef {
+      name_id,
+      bytecode_array,
+      bytecode_index_map,
+      argument_name_ids,
+      local_name_ids,
+      global_name_ids,
+    }
+  }
+
+  // Variables
+
+  fn resolve_var(&mut self, name: &str) -> Var {
+    if let Some(index) = self.args.iter().position(|x| x.eq_ignore_ascii_case(name)) {
+      return Var::Param(index);
+    }
+    if let Some(index) = self.locals.iter().position(|x| x.eq_ignore_ascii_case(name)) {
+      return Var::Local(index);
+    }
+    if let Some(global) = self.globals.iter().find(|x| x.eq_ignore_ascii_case(name)) {
+      return Var::Global(global.to_owned());
+    }
+    if let Some(global) = self.script.globals.iter().find(|x| x.eq_ignore_ascii_case(name)).cloned() {
+      self.globals.push(global.to_owned());
+      return Var::Global(global);
+    }
+    if let Some(prop) = self.script.properties.iter().find(|x| x.eq_ignore_ascii_case(name)) {
+      return Var::Prop(prop.to_owned());
+    }
+    if self.script.implicit_globals {
+      self.globals.push(name.to_owned());
+      return Var::Global(name.to_owned());
+    }
+    self.locals.push(name.to_owned());
+    Var::Local(self.locals.len() - 1)
+  }
+
+  fn get_var(&mut self, name: &str) {
+    match self.resolve_var(name) {
+      Var::Param(index) => self.emit(OpCode::GetParam, (index * self.script.multiplier as usize) as i64),
+      Var::Local(index) => self.emit(OpCode::GetLocal, (index * self.script.multiplier as usize) as i64),
+      Var::Global(name) => self.emit_name(OpCode::GetGlobal, &name),
+      Var::Prop(name) => self.emit_name(OpCode::GetProp, &name),
+    }
+  }
+
+  fn set_var(&mut self, name: &str) {
+    match self.resolve_var(name) {
+      Var::Param(index) => self.emit(OpCode::SetParam, (index * self.script.multiplier as usize) as i64),
+      Var::Local(index) => self.emit(OpCode::SetLocal, (index * self.script.multiplier as usize) as i64),
+      Var::Global(name) => self.emit_name(OpCode::SetGlobal, &name),
+      Var::Prop(name) => self.emit_name(OpCode::SetProp, &name),
+    }
+  }
+
+  // Statements
+
+  fn block(&mut self, stmts: &[Stmt]) -> CodegenResult<()> {
+    for stmt in stmts {
+      self.stmt(stmt)?;
+    }
+    Ok(())
+  }
+
+  fn stmt(&mut self, stmt: &Stmt) -> CodegenResult<()> {
+    match stmt {
+      Stmt::Expr(Expr::Call(name, args)) => self.call(name, args, false)?,
+      Stmt::Expr(Expr::ObjCall(name, args)) => self.obj_call(name, args, false)?,
+      Stmt::Expr(expr) => {
+        self.expr(expr)?;
+        self.emit(OpCode::Pop, 1);
+      }
+      Stmt::Global(names) => {
+        for name in names {
+          if !self.globals.iter().any(|x| x.eq_ignore_ascii_case(name)) {
+            self.globals.push(name.to_owned());
+          }
+        }
+      }
+      Stmt::Assign(target, value) => self.assign(target, value)?,
+      Stmt::Put(put_type, target, value) => {
+        let value = match put_type {
+          0x02 => Expr::Binary(OpCode::JoinStr, Box::new(target.clone()), Box::new(value.clone())),
+          0x03 => Expr::Binary(OpCode::JoinStr, Box::new(value.clone()), Box::new(target.clone())),
+          _ => value.clone(),
+        };
+        self.assign(target, &value)?;
+      }
+      Stmt::Call(name, args) => self.call(name, args, false)?,
+      Stmt::Return(Some(value)) => {
+        self.expr(value)?;
+        self.emit(OpCode::PushArgListNoRet, 1);
+        self.emit_name(OpCode::ExtCall, "return");
+      }
+      Stmt::Return(None) | Stmt::Exit => self.emit(OpCode::Ret, 0),
+      Stmt::ExitRepeat | Stmt::NextRepeat => {
+        let (next_label, exit_label) = *self.loops.last().ok_or_else(|| "exit repeat and next repeat must be inside a repeat".to_owned())?;
+        let label = if let Stmt::ExitRepeat = stmt { exit_label } else { next_label };
+        self.emit_jump(OpCode::Jmp, label);
+      }
+      Stmt::If(condition, then_body, else_body) => {
+        let else_label = self.new_label();
+        self.expr(condition)?;
+        self.emit_jump(OpCode::JmpIfZ, else_label);
+        self.block(then_body)?;
+        if else_body.is_empty() {
+          self.place_label(else_label);
+        } else {
+          let end_label = self.new_label();
+          self.emit_jump(OpCode::Jmp, end_label);
+          self.place_label(else_label);
+          self.block(else_body)?;
+          self.place_label(end_label);
+        }
+      }
+      Stmt::RepeatWhile(condition, body) => {
+        let (start_label, next_label, exit_label) = (self.new_label(), self.new_label(), self.new_label());
+        self.place_label(start_label);
+        self.expr(condition)?;
+        self.emit_jump(OpCode::JmpIfZ, exit_label);
+        self.loop_body(body, next_label, exit_label)?;
+        self.place_label(next_label);
+        self.emit_jump(OpCode::EndRepeat, start_label);
+        self.place_label(exit_label);
+      }
+      Stmt::RepeatWithTo(var, start, end, up, body) => {
+        let (start_label, next_label, exit_label) = (self.new_label(), self.new_label(), self.new_label());
+        self.expr(start)?;
+        self.set_var(var);
+        self.place_label(start_label);
+        self.get_var(var);
+        self.expr(end)?;
+        self.emit(if *up { OpCode::LtEq } else { OpCode::GtEq }, 0);
+        self.emit_jump(OpCode::JmpIfZ, exit_label);
+        self.loop_body(body, next_label, exit_label)?;
+        self.place_label(next_label);
+        self.emit_int(if *up { 1 } else { -1 });
+        self.get_var(var);
+        self.emit(OpCode::Add, 0);
+        self.set_var(var);
+        self.emit_jump(OpCode::EndRepeat, start_label);
+        self.place_label(exit_label);
+      }
+      Stmt::RepeatWithIn(var, list, body) => {
+        // The list, its count and the current index stay on the stack during the loop
+        let (start_label, next_label, exit_label) = (self.new_label(), self.new_label(), self.new_label());
+        self.expr(list)?;
+        self.emit(OpCode::Peek, 0);
+        self.emit(OpCode::PushArgList, 1);
+        self.emit_name(OpCode::ExtCall, "count");
+        self.emit_int(1);
+        self.place_label(start_label);
+        self.emit(OpCode::Peek, 0);
+        self.emit(OpCode::Peek, 2);
+        self.emit(OpCode::LtEq, 0);
+        self.emit_jump(OpCode::JmpIfZ, exit_label);
+        self.emit(OpCode::Peek, 2);
+        self.emit(OpCode::Peek, 1);
+        self.emit(OpCode::PushArgList, 2);
+        self.emit_name(OpCode::ExtCall, "getAt");
+        self.set_var(var);
+        self.loop_body(body, next_label, exit_label)?;
+        self.place_label(next_label);
+        self.emit_int(1);
+        self.emit(OpCode::Add, 0);
+        self.emit_jump(OpCode::EndRepeat, start_label);
+        self.place_label(exit_label);
+        self.emit(OpCode::Pop, 3);
+      }
+      Stmt::Case(value, labels, otherwise) => {
+        let end_label = self.new_label();
+        self.expr(value)?;
+        for (label_index, label) in labels.iter().enumerate() {
+          let (block_label, next_label) = (self.new_label(), self.new_label());
+          for (value_index, label_value) in label.values.iter().enumerate() {
+            self.emit(OpCode::Peek, 0);
+            self.expr(label_value)?;
+            if value_index + 1 < label.values.len() {
+              self.emit(OpCode::NtEq, 0);
+              self.emit_jump(OpCode::JmpIfZ, block_label);
+            } else {
+              self.emit(OpCode::Eq, 0);
+              self.emit_jump(OpCode::JmpIfZ, next_label);
+            }
+          }
+          self.place_label(block_label);
+          self.block(&label.body)?;
+          if label_index + 1 < labels.len() || otherwise.is_some() {
+            self.emit_jump(OpCode::Jmp, end_label);
+          }
+          self.place_label(next_label);
+        }
+        if let Some(otherwise) = otherwise {
+          self.block(otherwise)?;
+        }
+        self.place_label(end_label);
+        self.emit(OpCode::Pop, 1);
+      }
+      Stmt::Tell(target, body) => {
+        self.expr(target)?;
+        self.emit(OpCode::StartTell, 0);
+        self.tell_depth += 1;
+        let result = self.block(body);
+        self.tell_depth -= 1;
+        result?;
+        self.emit(OpCode::EndTell, 0);
+      }
+    }
+    Ok(())
+  }
+
+  fn loop_body(&mut self, body: &[Stmt], next_label: usize, exit_label: usize) -> CodegenResult<()> {
+    self.loops.push((next_label, exit_label));
+    let result = self.block(body);
+    self.loops.pop();
+    result
+  }
+
+  fn assign(&mut self, target: &Expr, value: &Expr) -> CodegenResult<()> {
+    match target {
+      Expr::Var(name) => {
+        self.expr(value)?;
+        self.set_var(name);
+      }
+      Expr::The(prop) => {
+        self.expr(value)?;
+        self.emit_name(OpCode::SetMovieProp, prop);
+      }
+      Expr::TheProp(obj, prop) | Expr::ObjProp(obj, prop) => {
+        self.obj_expr(obj)?;
+        self.expr(value)?;
+        self.emit_name(OpCode::SetObjProp, prop);
+      }
+      Expr::ObjBracket(obj, index) => {
+        self.expr(obj)?;
+        self.expr(index)?;
+        self.expr(value)?;
+        self.emit(OpCode::PushArgListNoRet, 3);
+        self.emit_name(OpCode::ObjCall, "setAt");
+      }
+      Expr::Call(name, _) if name.eq_ignore_ascii_case("field") => {
+        // Putting into a field sets the text of its member
+        self.obj_expr(target)?;
+        self.expr(value)?;
+        self.emit_name(OpCode::SetObjProp, "text");
+      }
+      _ => return Err(format!("Cannot assign to {}", target.to_lingo(true))),
+    }
+    Ok(())
+  }
+
+  // Expressions
+
+  /// Generates the object of a property access, where `field x` refers to the member rather than its text.
+  fn obj_expr(&mut self, expr: &Expr) -> CodegenResult<()> {
+    match expr {
+      Expr::Call(name, args) if name.eq_ignore_ascii_case("field") => self.call("member", args, true),
+      _ => self.expr(expr),
+    }
+  }
+
+  fn expr(&mut self, expr: &Expr) -> CodegenResult<()> {
+    match expr {
+      Expr::Int(value) => self.emit_int(*value as i64),
+      Expr::Float(value) => self.emit_literal(Datum::Float(*value)),
+      Expr::String(value) => self.emit_literal(Datum::String(value.to_owned())),
+      Expr::Symbol(name) => self.emit_name(OpCode::PushSymb, name),
+      Expr::Var(name) => self.get_var(name),
+      Expr::List(items) => {
+        for item in items {
+          self.expr(item)?;
+        }
+        self.emit(OpCode::PushArgList, items.len() as i64);
+        self.emit(OpCode::PushList, 0);
+      }
+      Expr::PropList(pairs) => {
+        for (key, value) in pairs {
+          self.expr(key)?;
+          self.expr(value)?;
+        }
+        self.emit(OpCode::PushArgList, (pairs.len() * 2) as i64);
+        self.emit(OpCode::PushPropList, 0);
+      }
+      Expr::The(prop) => {
+        if prop.eq_ignore_ascii_case("paramCount") || prop.eq_ignore_ascii_case("result") {
+          let name = if prop.eq_ignore_ascii_case("result") { "result" } else { "paramCount" };
+          self.emit(OpCode::PushArgList, 0);
+          self.emit_name(OpCode::TheBuiltin, name);
+        } else {
+          self.emit_name(OpCode::GetMovieProp, prop);
+        }
+      }
+      Expr::TheProp(obj, prop) | Expr::ObjProp(obj, prop) => {
+        self.obj_expr(obj)?;
+        self.emit_name(OpCode::GetObjProp, prop);
+      }
+      Expr::Chunk(chunk_type, first, last, string) => {
+        // GetChunk takes the first and last of each chunk type, from char to line
+        for slot_type in [StringChunkType::Char, StringChunkType::Word, StringChunkType::Item, StringChunkType::Line] {
+          if chunk_type_id(&slot_type) == chunk_type_id(chunk_type) {
+            self.expr(first)?;
+            self.expr(last)?;
+          } else {
+            self.emit_int(0);
+            self.emit_int(0);
+          }
+        }
+        self.expr(string)?;
+        self.emit(OpCode::GetChunk, 0);
+      }
+      Expr::ChunkCount(chunk_type, string) => {
+        self.expr(string)?;
+        self.emit_int(chunk_type_id(chunk_type));
+        self.emit(OpCode::Get, 0x01);
+      }
+      Expr::LastChunk(chunk_type, string) => {
+        self.expr(string)?;
+        self.emit_int(0x0b + chunk_type_id(chunk_type));
+        self.emit(OpCode::Get, 0x00);
+      }
+      Expr::Binary(opcode, left, right) => {
+        self.expr(left)?;
+        self.expr(right)?;
+        self.emit(*opcode, 0);
+      }
+      Expr::Not(operand) => {
+        self.expr(operand)?;
+        self.emit(OpCode::Not, 0);
+      }
+      Expr::Negate(operand) => {
+        self.expr(operand)?;
+        self.emit(OpCode::Inv, 0);
+      }
+      Expr::SpriteIntersects(a, b) | Expr::SpriteWithin(a, b) => {
+        self.expr(a)?;
+        self.expr(b)?;
+        self.emit(if let Expr::SpriteIntersects(..) = expr { OpCode::OntoSpr } else { OpCode::IntoSpr }, 0);
+      }
+      Expr::Call(name, args) => self.call(name, args, true)?,
+      Expr::ObjCall(name, args) => self.obj_call(name, args, true)?,
+      Expr::ObjBracket(obj, index) => {
+        self.expr(obj)?;
+        self.expr(index)?;
+        self.emit(OpCode::PushArgList, 2);
+        self.emit_name(OpCode::ObjCall, "getAt");
+      }
+      _ => return Err(format!("Unsupported expression {}", expr.to_lingo(true))),
+    }
+    Ok(())
+  }
+
+  fn call(&mut self, name: &str, args: &[Expr], use_result: bool) -> CodegenResult<()> {
+    if use_result && name.eq_ignore_ascii_case("field") && !args.is_empty() && args.len() <= 2 {
+      self.expr(&args[0])?;
+      if self.script.dir_version >= 500 {
+        match args.get(1) {
+          Some(cast) => self.expr(cast)?,
+          None => self.emit_int(0),
+        }
+      }
+      self.emit(OpCode::GetField, 0);
+      return Ok(());
+    }
+    for arg in args {
+      self.expr(arg)?;
+    }
+    self.emit(if use_result { OpCode::PushArgList } else { OpCode::PushArgListNoRet }, args.len() as i64);
+    match self.script.handler_names.iter().position(|x| x.eq_ignore_ascii_case(name)) {
+      Some(index) if self.tell_depth == 0 => self.emit(OpCode::LocalCall, index as i64),
+      _ if self.tell_depth > 0 => self.emit_name(OpCode::TellCall, name),
+      _ => self.emit_name(OpCode::ExtCall, name),
+    }
+    Ok(())
+  }
+
+  fn obj_call(&mut self, name: &str, args: &[Expr], use_result: bool) -> CodegenResult<()> {
+    for arg in args {
+      self.expr(arg)?;
+    }
+    self.emit(if use_result { OpCode::PushArgList } else { OpCode::PushArgListNoRet }, args.len() as i64);
+    self.emit_name(OpCode::ObjCall, name);
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::director::lingo::constants::get_opcode_name;
+
+  use super::super::{compile_expression, compile_script, compile_statements};
+  use super::*;
+
+  /// Writes each bytecode of a handler as its opcode name and operand, with names resolved.
+  fn disassemble(handler: &HandlerDef, names: &[String]) -> Vec<String> {
+    handler.bytecode_array.iter().map(|bytecode| {
+      let name = get_opcode_name(&bytecode.opcode);
+      match bytecode.opcode {
+        OpCode::ExtCall | OpCode::ObjCall | OpCode::TellCall | OpCode::TheBuiltin | OpCode::PushSymb
+        | OpCode::GetGlobal | OpCode::SetGlobal | OpCode::GetProp | OpCode::SetProp
+        | OpCode::GetMovieProp | OpCode::SetMovieProp | OpCode::GetObjProp | OpCode::SetObjProp => {
+          format!("{} {}", name, names[bytecode.obj as usize])
+        }
+        opcode if num::ToPrimitive::to_u16(&opcode).unwrap() < 0x40 => name,
+        _ => format!("{} {}", name, bytecode.obj),
+      }
+    }).collect()
+  }
+
+  fn compile_eval(compile: fn(&str, &mut Vec<String>, u16, bool) -> Result<ScriptChunk, String>, source: &str) -> Vec<String> {
+    let mut names = vec![];
+    let script = compile(source, &mut names, 1100, true).unwrap();
+    disassemble(&script.handlers[0], &names)
+  }
+
+  #[test]
+  fn generates_operators_in_precedence_order() {
+    assert_eq!(compile_eval(compile_expression, "1 + 2 * 3"), vec![
+      "pushint8 1", "pushint8 2", "pushint8 3", "mul", "add", "pusharglistnoret 1", "extcall return", "ret",
+    ]);
+    assert_eq!(compile_eval(compile_expression, "not x = 0"), vec![
+      "getglobal x", "pushzero", "eq", "not", "pusharglistnoret 1", "extcall return", "ret",
+    ]);
+  }
+
+  #[test]
+  fn generates_chunk_expressions_with_a_range_per_chunk_type() {
+    assert_eq!(compile_eval(compile_expression, "word 2 to 3 of s"), vec![
+      "pushzero", "pushzero", "pushint8 2", "pushint8 3", "pushzero", "pushzero", "pushzero", "pushzero",
+      "getglobal s", "getchunk", "pusharglistnoret 1", "extcall return", "ret",
+    ]);
+    assert_eq!(compile_eval(compile_expression, "the number of lines in s"), vec![
+      "getglobal s", "pushint8 4", "get 1", "pusharglistnoret 1", "extcall return", "ret",
+    ]);
+  }
+
+  #[test]
+  fn generates_the_properties() {
+    assert_eq!(compile_eval(compile_statements, "set the locH of sprite 1 to the mouseH"), vec![
+      "pushint8 1", "pusharglist 1", "extcall sprite", "getmovieprop mouseH", "setobjprop locH", "ret",
+    ]);
+    assert_eq!(compile_eval(compile_statements, "the stageColor = the paramCount"), vec![
+      "pusharglist 0", "thebuiltin paramCount", "setmovieprop stageColor", "ret",
+    ]);
+  }
+
+  #[test]
+  fn resolves_jumps_to_byte_offsets() {
+    // getglobal takes 2 bytes, jumps 3 and pushint8 2
+    assert_eq!(compile_eval(compile_statements, "if x then\r  y = 1\relse\r  y = 2\rend if"), vec![
+      "getglobal x", "jmpifz 10", "pushint8 1", "setglobal y", "jmp 7", "pushint8 2", "setglobal y", "ret",
+    ]);
+  }
+
+  #[test]
+  fn scales_variable_indices_by_the_multiplier() {
+    let source = "on f a, b\r  c = b\r  return c\rend";
+    for (dir_version, capital_x, index) in [(700, false, 8), (1100, true, 1)] {
+      let mut names = vec![];
+      let script = compile_script(source, &mut names, dir_version, capital_x).unwrap();
+      assert_eq!(disassemble(&script.handlers[0], &names), vec![
+        format!("getparam {}", index), "setlocal 0".to_owned(), "getlocal 0".to_owned(),
+        "pusharglistnoret 1".to_owned(), "extcall return".to_owned(), "ret".to_owned(),
+      ]);
+    }
+  }
+
+  #[test]
+  fn reports_statements_that_cannot_be_generated() {
+    let mut names = vec![];
+    assert_eq!(compile_statements("1 = 2", &mut names, 1100, true).err().unwrap(), "Cannot assign to 1");
+    assert_eq!(
+      compile_statements("exit repeat", &mut names, 1100, true).err().unwrap(),
+      "exit repeat and next repeat must be inside a repeat",
+    );
+  }
+}
@@ -0,0 +1,272 @@
+#[derive(Clone, PartialEq, Debug)]
+pub enum Token {
+  Int(i32),
+  Float(f32),
+  String(String),
+  Symbol(String),
+  Ident(String),
+  Plus,
+  Minus,
+  Star,
+  Slash,
+  Amp,
+  AmpAmp,
+  Eq,
+  NtEq,
+  Lt,
+  LtEq,
+  Gt,
+  GtEq,
+  LParen,
+  RParen,
+  LBracket,
+  RBracket,
+  Comma,
+  Colon,
+  Dot,
+  Newline,
+  Eof,
+}
+
+impl Token {
+  pub fn is_keyword(&self, keyword: &str) -> bool {
+    match self {
+      Token::Ident(name) => name.eq_ignore_ascii_case(keyword),
+      _ => false,
+    }
+  }
+
+  pub fn describe(&self) -> String {
+    match self {
+      Token::Int(value) => value.to_string(),
+      Token::Float(value) => value.to_string(),
+      Token::String(value) => format!("\"{}\"", value),
+      Token::Symbol(name) => format!("#{}", name),
+      Token::Ident(name) => name.to_owned(),
+      Token::Newline => "end of line".to_owned(),
+      Token::Eof => "end of script".to_owned(),
+      Token::Plus => "+".to_owned(),
+      Token::Minus => "-".to_owned(),
+      Token::Star => "*".to_owned(),
+      Token::Slash => "/".to_owned(),
+      Token::Amp => "&".to_owned(),
+      Token::AmpAmp => "&&".to_owned(),
+      Token::Eq => "=".to_owned(),
+      Token::NtEq => "<>".to_owned(),
+      Token::Lt => "<".to_owned(),
+      Token::LtEq => "<=".to_owned(),
+      Token::Gt => ">".to_owned(),
+      Token::GtEq => ">=".to_owned(),
+      Token::LParen => "(".to_owned(),
+      Token::RParen => ")".to_owned(),
+      Token::LBracket => "[".to_owned(),
+      Token::RBracket => "]".to_owned(),
+      Token::Comma => ",".to_owned(),
+      Token::Colon => ":".to_owned(),
+      Token::Dot => ".".to_owned(),
+    }
+  }
+}
+
+pub struct LexedToken {
+  pub token: Token,
+  pub line: usize,
+}
+
+fn is_ident_start(c: char) -> bool {
+  c.is_ascii_alphabetic() || c == '_'
+}
+
+fn is_ident_char(c: char) -> bool {
+  c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Splits Lingo source into tokens. Line breaks are significant in Lingo, so consecutive
+/// line breaks are kept as a single `Newline` token, and `¬` or `\` continue a line.
+pub fn tokenize(source: &str) -> Result<Vec<LexedToken>, String> {
+  let chars: Vec<char> = source.chars().collect();
+  let mut tokens: Vec<LexedToken> = vec![];
+  let mut line = 1;
+  let mut i = 0;
+
+  let push = |tokens: &mut Vec<LexedToken>, token: Token, line: usize| {
+    if token == Token::Newline && tokens.last().is_none_or(|x| x.token == Token::Newline) {
+      return;
+    }
+    tokens.push(LexedToken { token, line });
+  };
+
+  while i < chars.len() {
+    let c = chars[i];
+    let next = chars.get(i + 1).copied();
+    match c {
+      ' ' | '\t' => i += 1,
+      '\r' | '\n' => {
+        push(&mut tokens, Token::Newline, line);
+        if c == '\r' && next == Some('\n') {
+          i += 1;
+        }
+        line += 1;
+        i += 1;
+      }
+      '¬' | '\\' => {
+        // Line continuation, skip up to and including the line break
+        i += 1;
+        while i < chars.len() && (chars[i] == ' ' || chars[i] == '\t') {
+          i += 1;
+        }
+        match chars.get(i) {
+          Some('\r') if chars.get(i + 1) == Some(&'\n') => i += 2,
+          Some('\r') | Some('\n') => i += 1,
+          _ => return Err(format!("Line {}: Unexpected character {}", line, c)),
+        }
+        line += 1;
+      }
+      '-' if next == Some('-') => {
+        while i < chars.len() && chars[i] != '\r' && chars[i] != '\n' {
+          i += 1;
+        }
+      }
+      '"' => {
+        let start = i + 1;
+        let mut end = start;
+        while end < chars.len() && chars[end] != '"' && chars[end] != '\r' && chars[end] != '\n' {
+          end += 1;
+        }
+        if chars.get(end) != Some(&'"') {
+          return Err(format!("Line {}: Unterminated string", line));
+        }
+        push(&mut tokens, Token::String(chars[start..end].iter().collect()), line);
+        i = end + 1;
+      }
+      '#' => {
+        let start = i + 1;
+        let mut end = start;
+        while end < chars.len() && is_ident_char(chars[end]) {
+          end += 1;
+        }
+        if end == start {
+          return Err(format!("Line {}: Expected a symbol name after #", line));
+        }
+        push(&mut tokens, Token::Symbol(chars[start..end].iter().collect()), line);
+        i = end;
+      }
+      _ if c.is_ascii_digit() || (c == '.' && next.is_some_and(|x| x.is_ascii_digit())) => {
+        let start = i;
+        while i < chars.len() && chars[i].is_ascii_digit() {
+          i += 1;
+        }
+        let mut is_float = false;
+        if chars.get(i) == Some(&'.') && chars.get(i + 1).is_some_and(|x| x.is_ascii_digit()) {
+          is_float = true;
+          i += 1;
+          while i < chars.len() && chars[i].is_ascii_digit() {
+            i += 1;
+          }
+        }
+        if matches!(chars.get(i), Some('e') | Some('E')) {
+          let mut exponent_end = i + 1;
+          if matches!(chars.get(exponent_end), Some('+') | Some('-')) {
+            exponent_end += 1;
+          }
+          if chars.get(exponent_end).is_some_and(|x| x.is_ascii_digit()) {
+            is_float = true;
+            i = exponent_end;
+            while i < chars.len() && chars[i].is_ascii_digit() {
+              i += 1;
+            }
+          }
+        }
+        let text: String = chars[start..i].iter().collect();
+        let token = if is_float {
+          Token::Float(text.parse().map_err(|_| format!("Line {}: Invalid number {}", line, text))?)
+        } else {
+          match text.parse::<i32>() {
+            Ok(value) => Token::Int(value),
+            Err(_) => Token::Float(text.parse().map_err(|_| format!("Line {}: Invalid number {}", line, text))?),
+          }
+        };
+        push(&mut tokens, token, line);
+      }
+      _ if is_ident_start(c) => {
+        let start = i;
+        while i < chars.len() && is_ident_char(chars[i]) {
+          i += 1;
+        }
+        push(&mut tokens, Token::Ident(chars[start..i].iter().collect()), line);
+      }
+      _ => {
+        let (token, len) = match (c, next) {
+          ('&', Some('&')) => (Token::AmpAmp, 2),
+          ('<', Some('>')) => (Token::NtEq, 2),
+          ('<', Some('=')) => (Token::LtEq, 2),
+          ('>', Some('=')) => (Token::GtEq, 2),
+          ('+', _) => (Token::Plus, 1),
+          ('-', _) => (Token::Minus, 1),
+          ('*', _) => (Token::Star, 1),
+          ('/', _) => (Token::Slash, 1),
+          ('&', _) => (Token::Amp, 1),
+          ('=', _) => (Token::Eq, 1),
+          ('<', _) => (Token::Lt, 1),
+          ('>', _) => (Token::Gt, 1),
+          ('(', _) => (Token::LParen, 1),
+          (')', _) => (Token::RParen, 1),
+          ('[', _) => (Token::LBracket, 1),
+          (']', _) => (Token::RBracket, 1),
+          (',', _) => (Token::Comma, 1),
+          (':', _) => (Token::Colon, 1),
+          ('.', _) => (Token::Dot, 1),
+          _ => return Err(format!("Line {}: Unexpected character {}", line, c)),
+        };
+        push(&mut tokens, token, line);
+        i += len;
+      }
+    }
+  }
+  push(&mut tokens, Token::Newline, line);
+  tokens.push(LexedToken { token: Token::Eof, line });
+  Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn tokens(source: &str) -> Vec<Token> {
+    tokenize(source).unwrap().into_iter().map(|x| x.token).collect()
+  }
+
+  fn ident(name: &str) -> Token {
+    Token::Ident(name.to_owned())
+  }
+
+  #[test]
+  fn tokenizes_literals_and_operators() {
+    assert_eq!(tokens("x <= 1.5e2 && \"a b\" <> #sym"), vec![
+      ident("x"), Token::LtEq, Token::Float(150.0), Token::AmpAmp, Token::String("a b".to_owned()), Token::NtEq,
+      Token::Symbol("sym".to_owned()), Token::Newline, Token::Eof,
+    ]);
+    assert_eq!(tokens("a.b[2]-.5"), vec![
+      ident("a"), Token::Dot, ident("b"), Token::LBracket, Token::Int(2), Token::RBracket, Token::Minus,
+      Token::Float(0.5), Token::Newline, Token::Eof,
+    ]);
+  }
+
+  #[test]
+  fn skips_comments_and_joins_continued_lines() {
+    let lexed = tokenize("x = 1 -- one\r\r\ny = 2 ¬\r  + 3\rz").unwrap();
+    let summary: Vec<(Token, usize)> = lexed.into_iter().map(|x| (x.token, x.line)).collect();
+    assert_eq!(summary, vec![
+      (ident("x"), 1), (Token::Eq, 1), (Token::Int(1), 1), (Token::Newline, 1),
+      (ident("y"), 3), (Token::Eq, 3), (Token::Int(2), 3), (Token::Plus, 4), (Token::Int(3), 4), (Token::Newline, 4),
+      (ident("z"), 5), (Token::Newline, 5), (Token::Eof, 5),
+    ]);
+  }
+
+  #[test]
+  fn reports_the_line_of_malformed_tokens() {
+    assert_eq!(tokenize("x = 1\ry = \"abc").err().unwrap(), "Line 2: Unterminated string");
+    assert_eq!(tokenize("x = 1 \\\r  + 2\ry = ?").err().unwrap(), "Line 3: Unexpected character ?");
+    assert_eq!(tokenize("\r\rx = # 1").err().unwrap(), "Line 3: Expected a symbol name after #");
+  }
+}
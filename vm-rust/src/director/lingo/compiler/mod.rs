@@ -0,0 +1,28 @@
+pub mod ast;
+pub mod codegen;
+pub mod lexer;
+pub mod parser;
+
+use crate::director::chunks::script::ScriptChunk;
+
+use self::{ast::Stmt, codegen::ScriptCodegen, lexer::tokenize, parser::Parser};
+
+/// Compiles the source of a script member. New names are appended to `names`, which must be
+/// the names of the script context the script is stored in.
+pub fn compile_script(source: &str, names: &mut Vec<String>, dir_version: u16, capital_x: bool) -> Result<ScriptChunk, String> {
+  let script = Parser::new(tokenize(source)?).parse_script()?;
+  ScriptCodegen::new(names, dir_version, capital_x).generate_script(&script)
+}
+
+/// Compiles the statements of a `do` command into a script with a single `do` handler.
+pub fn compile_statements(source: &str, names: &mut Vec<String>, dir_version: u16, capital_x: bool) -> Result<ScriptChunk, String> {
+  let body = Parser::new(tokenize(source)?).parse_statements()?;
+  ScriptCodegen::new(names, dir_version, capital_x).generate_eval_script("do", body)
+}
+
+/// Compiles an expression into a script with a single `value` handler that returns it.
+pub fn compile_expression(source: &str, names: &mut Vec<String>, dir_version: u16, capital_x: bool) -> Result<ScriptChunk, String> {
+  let expr = Parser::new(tokenize(source)?).parse_single_expr()?;
+  ScriptCodegen::new(names, dir_version, capital_x).generate_eval_script("value", vec![Stmt::Return(Some(expr))])
+}
+
@@ -0,0 +1,956 @@
+use crate::director::lingo::{datum::StringChunkType, decompiler::ast::Expr, opcode::OpCode};
+
+use super::{
+  ast::{CaseLabelNode, HandlerNode, ScriptNode, Stmt},
+  lexer::{LexedToken, Token},
+};
+
+type ParseResult<T> = Result<T, String>;
+
+/// Words that end an operand, so that verbose forms like `sprite 1` and `put x into y` stop before them.
+const STOP_WORDS: [&str; 18] = [
+  "of", "to", "then", "and", "or", "mod", "into", "after", "before", "else", "in", "contains", "starts", "intersects",
+  "within", "down", "end", "not",
+];
+
+fn constant_expr(name: &str) -> Option<Expr> {
+  let value = match name.to_lowercase().as_str() {
+    "true" => return Some(Expr::Int(1)),
+    "false" => return Some(Expr::Int(0)),
+    "empty" => "",
+    "return" => "\r",
+    "tab" => "\t",
+    "quote" => "\"",
+    "space" => " ",
+    "enter" => "\x03",
+    "backspace" => "\x08",
+    _ => return None,
+  };
+  Some(Expr::String(value.to_owned()))
+}
+
+fn chunk_type_from_word(token: &Token, plural: bool) -> Option<StringChunkType> {
+  let name = match token {
+    Token::Ident(name) => name.to_lowercase(),
+    _ => return None,
+  };
+  let name = if plural { name.strip_suffix('s')?.to_owned() } else { name };
+  match name.as_str() {
+    "char" | "word" | "item" | "line" => Some(StringChunkType::from(&name)),
+    _ => None,
+  }
+}
+
+/// Bare names are symbols when used as property list keys, as in `[name: "x"]`.
+fn prop_list_key(key: Expr) -> Expr {
+  match key {
+    Expr::Var(name) => Expr::Symbol(name),
+    other => other,
+  }
+}
+
+/// Canonical spelling of the functions that also have a verbose form, such as `member "x" of castLib 2`.
+fn reference_function_name(name: &str) -> Option<&'static str> {
+  match name.to_lowercase().as_str() {
+    "sprite" => Some("sprite"),
+    "member" => Some("member"),
+    "castlib" => Some("castLib"),
+    "field" => Some("field"),
+    "script" => Some("script"),
+    "window" => Some("window"),
+    _ => None,
+  }
+}
+
+pub struct Parser {
+  tokens: Vec<LexedToken>,
+  pos: usize,
+}
+
+impl Parser {
+  pub fn new(tokens: Vec<LexedToken>) -> Parser {
+    Parser { tokens, pos: 0 }
+  }
+
+  // Token helpers
+
+  fn peek(&self) -> &Token {
+    self.peek_at(0)
+  }
+
+  fn peek_at(&self, offset: usize) -> &Token {
+    let index = (self.pos + offset).min(self.tokens.len() - 1);
+    &self.tokens[index].token
+  }
+
+  fn advance(&mut self) -> Token {
+    let token = self.peek().clone();
+    if self.pos < self.tokens.len() - 1 {
+      self.pos += 1;
+    }
+    token
+  }
+
+  fn error<T>(&self, message: String) -> ParseResult<T> {
+    Err(format!("Line {}: {}", self.tokens[self.pos.min(self.tokens.len() - 1)].line, message))
+  }
+
+  fn unexpected<T>(&self) -> ParseResult<T> {
+    self.error(format!("Unexpected {}", self.peek().describe()))
+  }
+
+  fn at_keyword(&self, keyword: &str) -> bool {
+    self.peek().is_keyword(keyword)
+  }
+
+  fn accept_keyword(&mut self, keyword: &str) -> bool {
+    if self.at_keyword(keyword) {
+      self.advance();
+      true
+    } else {
+      false
+    }
+  }
+
+  fn expect_keyword(&mut self, keyword: &str) -> ParseResult<()> {
+    if self.accept_keyword(keyword) {
+      Ok(())
+    } else {
+      self.error(format!("Expected {}, found {}", keyword, self.peek().describe()))
+    }
+  }
+
+  fn accept(&mut self, token: &Token) -> bool {
+    if self.peek() == token {
+      self.advance();
+      true
+    } else {
+      false
+    }
+  }
+
+  fn expect(&mut self, token: Token) -> ParseResult<()> {
+    if self.accept(&token) {
+      Ok(())
+    } else {
+      self.error(format!("Expected {}, found {}", token.describe(), self.peek().describe()))
+    }
+  }
+
+  fn expect_ident(&mut self) -> ParseResult<String> {
+    match self.peek() {
+      Token::Ident(name) => {
+        let name = name.to_owned();
+        self.advance();
+        Ok(name)
+      }
+      _ => self.error(format!("Expected a name, found {}", self.peek().describe())),
+    }
+  }
+
+  fn at_line_end(&self) -> bool {
+    matches!(self.peek(), Token::Newline | Token::Eof)
+  }
+
+  /// Whether a statement ends here, which includes the `else` of a single-line `if`.
+  fn at_stmt_end(&self) -> bool {
+    self.at_line_end() || self.at_keyword("else")
+  }
+
+  fn expect_line_end(&mut self) -> ParseResult<()> {
+    match self.peek() {
+      Token::Newline => {
+        self.advance();
+        Ok(())
+      }
+      Token::Eof => Ok(()),
+      _ => self.error(format!("Expected end of line, found {}", self.peek().describe())),
+    }
+  }
+
+  fn skip_newlines(&mut self) {
+    while *self.peek() == Token::Newline {
+      self.advance();
+    }
+  }
+
+  fn at_operand_start(&self) -> bool {
+    match self.peek() {
+      Token::Int(_) | Token::Float(_) | Token::String(_) | Token::Symbol(_) => true,
+      Token::LParen | Token::LBracket | Token::Minus => true,
+      Token::Ident(name) => !STOP_WORDS.iter().any(|x| name.eq_ignore_ascii_case(x)),
+      _ => false,
+    }
+  }
+
+  fn parse_ident_list(&mut self) -> ParseResult<Vec<String>> {
+    let mut names = vec![self.expect_ident()?];
+    while self.accept(&Token::Comma) {
+      names.push(self.expect_ident()?);
+    }
+    Ok(names)
+  }
+
+  // Sources
+
+  /// Parses the text of a script member: handlers and `property` / `global` declarations.
+  pub fn parse_script(&mut self) -> ParseResult<ScriptNode> {
+    let mut script = ScriptNode {
+      properties: vec![],
+      globals: vec![],
+      handlers: vec![],
+    };
+    loop {
+      self.skip_newlines();
+      if *self.peek() == Token::Eof {
+        break;
+      }
+      if self.accept_keyword("property") {
+        script.properties.extend(self.parse_ident_list()?);
+      } else if self.accept_keyword("global") {
+        script.globals.extend(self.parse_ident_list()?);
+      } else if self.at_keyword("on") {
+        script.handlers.push(self.parse_handler()?);
+      } else {
+        return self.error(format!("Expected a handler, found {}", self.peek().describe()));
+      }
+      self.expect_line_end()?;
+    }
+    Ok(script)
+  }
+
+  /// Parses the statements of a `do` command.
+  pub fn parse_statements(&mut self) -> ParseResult<Vec<Stmt>> {
+    let body = self.parse_block(&[])?;
+    if *self.peek() != Token::Eof {
+      return self.unexpected();
+    }
+    Ok(body)
+  }
+
+  /// Parses the expression of a `value()` call.
+  pub fn parse_single_expr(&mut self) -> ParseResult<Expr> {
+    self.skip_newlines();
+    let expr = self.parse_expr()?;
+    self.skip_newlines();
+    if *self.peek() != Token::Eof {
+      return self.unexpected();
+    }
+    Ok(expr)
+  }
+
+  fn parse_handler(&mut self) -> ParseResult<HandlerNode> {
+    self.expect_keyword("on")?;
+    let name = self.expect_ident()?;
+    let mut args = vec![];
+    if self.accept(&Token::LParen) {
+      if !self.accept(&Token::RParen) {
+        args = self.parse_ident_list()?;
+        self.expect(Token::RParen)?;
+      }
+    } else if !self.at_line_end() {
+      args = self.parse_ident_list()?;
+    }
+    self.expect_line_end()?;
+    let body = self.parse_block(&["end"])?;
+    self.expect_keyword("end")?;
+    if !self.at_line_end() {
+      self.expect_ident()?;
+    }
+    Ok(HandlerNode { name, args, body })
+  }
+
+  /// Parses statements until one of the `terminators` keywords starts a line.
+  fn parse_block(&mut self, terminators: &[&str]) -> ParseResult<Vec<Stmt>> {
+    let mut stmts = vec![];
+    loop {
+      self.skip_newlines();
+      if *self.peek() == Token::Eof || terminators.iter().any(|x| self.at_keyword(x)) {
+        break;
+      }
+      stmts.push(self.parse_stmt()?);
+      if !terminators.iter().any(|x| self.at_keyword(x)) {
+        self.expect_line_end()?;
+      }
+    }
+    Ok(stmts)
+  }
+
+  // Statements
+
+  fn parse_stmt(&mut self) -> ParseResult<Stmt> {
+    let keyword = match self.peek() {
+      Token::Ident(name) => name.to_lowercase(),
+      _ => String::new(),
+    };
+    match keyword.as_str() {
+      "if" => {
+        self.advance();
+        self.parse_if(false)
+      }
+      "repeat" => self.parse_repeat(),
+      "case" => self.parse_case(),
+      "tell" => self.parse_tell(),
+      "exit" => {
+        self.advance();
+        if self.accept_keyword("repeat") {
+          Ok(Stmt::ExitRepeat)
+        } else {
+          Ok(Stmt::Exit)
+        }
+      }
+      "next" if self.peek_at(1).is_keyword("repeat") => {
+        self.advance();
+        self.advance();
+        Ok(Stmt::NextRepeat)
+      }
+      "return" => {
+        self.advance();
+        if self.at_stmt_end() {
+          Ok(Stmt::Return(None))
+        } else {
+          Ok(Stmt::Return(Some(self.parse_expr()?)))
+        }
+      }
+      "global" => {
+        self.advance();
+        Ok(Stmt::Global(self.parse_ident_list()?))
+      }
+      "put" => self.parse_put(),
+      "set" => {
+        self.advance();
+        let target = self.parse_operand()?;
+        if !self.accept_keyword("to") {
+          self.expect(Token::Eq)?;
+        }
+        Ok(Stmt::Assign(target, self.parse_expr()?))
+      }
+      "go" => self.parse_go(),
+      _ => self.parse_simple_stmt(),
+    }
+  }
+
+  /// Parses an `if` after its keyword. `chained` is set for the `if` of an `else if`, which shares
+  /// the `end if` of the statement it continues.
+  fn parse_if(&mut self, chained: bool) -> ParseResult<Stmt> {
+    let condition = self.parse_expr()?;
+    if *self.peek() == Token::Newline && self.tokens[self.pos..].iter().find(|x| x.token != Token::Newline).is_some_and(|x| x.token.is_keyword("then")) {
+      self.skip_newlines();
+    }
+    self.expect_keyword("then")?;
+
+    let mut then_body = vec![];
+    if !self.at_line_end() {
+      then_body.push(self.parse_stmt()?);
+      if !chained {
+        return self.parse_single_line_else(condition, then_body);
+      }
+    }
+    then_body.extend(self.parse_block(&["else", "end"])?);
+
+    let mut else_body = vec![];
+    if self.accept_keyword("else") {
+      if self.accept_keyword("if") {
+        else_body.push(self.parse_if(true)?);
+        return Ok(Stmt::If(condition, then_body, else_body));
+      }
+      else_body = self.parse_block(&["end"])?;
+    }
+    self.expect_keyword("end")?;
+    self.expect_keyword("if")?;
+    Ok(Stmt::If(condition, then_body, else_body))
+  }
+
+  /// Finishes `if c then x`, which may continue with `else y` on the same or the next line.
+  fn parse_single_line_else(&mut self, condition: Expr, then_body: Vec<Stmt>) -> ParseResult<Stmt> {
+    let saved_pos = self.pos;
+    self.skip_newlines();
+    if !self.accept_keyword("else") {
+      self.pos = saved_pos;
+      return Ok(Stmt::If(condition, then_body, vec![]));
+    }
+    if self.at_line_end() {
+      let else_body = self.parse_block(&["end"])?;
+      self.expect_keyword("end")?;
+      self.expect_keyword("if")?;
+      return Ok(Stmt::If(condition, then_body, else_body));
+    }
+    let else_stmt = self.parse_stmt()?;
+    Ok(Stmt::If(condition, then_body, vec![else_stmt]))
+  }
+
+  fn parse_repeat(&mut self) -> ParseResult<Stmt> {
+    self.expect_keyword("repeat")?;
+    let stmt = if self.accept_keyword("while") {
+      let condition = self.parse_expr()?;
+      self.expect_line_end()?;
+      Stmt::RepeatWhile(condition, self.parse_block(&["end"])?)
+    } else if self.accept_keyword("with") {
+      let var = self.expect_ident()?;
+      if self.accept(&Token::Eq) {
+        let start = self.parse_expr()?;
+        let up = !self.accept_keyword("down");
+        self.expect_keyword("to")?;
+        let end = self.parse_expr()?;
+        self.expect_line_end()?;
+        Stmt::RepeatWithTo(var, start, end, up, self.parse_block(&["end"])?)
+      } else {
+        self.expect_keyword("in")?;
+        let list = self.parse_expr()?;
+        self.expect_line_end()?;
+        Stmt::RepeatWithIn(var, list, self.parse_block(&["end"])?)
+      }
+    } else {
+      return self.error(format!("Expected while or with, found {}", self.peek().describe()));
+    };
+    self.expect_keyword("end")?;
+    self.expect_keyword("repeat")?;
+    Ok(stmt)
+  }
+
+  fn parse_case(&mut self) -> ParseResult<Stmt> {
+    self.expect_keyword("case")?;
+    let value = self.parse_expr()?;
+    self.expect_keyword("of")?;
+    self.expect_line_end()?;
+
+    let mut labels = vec![];
+    let mut otherwise = None;
+    loop {
+      self.skip_newlines();
+      if self.accept_keyword("end") {
+        self.expect_keyword("case")?;
+        break;
+      }
+      if *self.peek() == Token::Eof {
+        return self.error("Expected end case".to_owned());
+      }
+      if self.accept_keyword("otherwise") {
+        self.accept(&Token::Colon);
+        otherwise = Some(self.parse_block(&["end"])?);
+        continue;
+      }
+      match self.parse_case_label() {
+        Some(values) => {
+          let body = self.parse_case_body()?;
+          labels.push(CaseLabelNode { values, body });
+        }
+        None => return self.error(format!("Expected a case label, found {}", self.peek().describe())),
+      }
+    }
+    Ok(Stmt::Case(value, labels, otherwise))
+  }
+
+  /// Parses `value1, value2:` if the line starts with a case label, otherwise leaves the position unchanged.
+  fn parse_case_label(&mut self) -> Option<Vec<Expr>> {
+    let saved_pos = self.pos;
+    let mut values = vec![];
+    while let Ok(value) = self.parse_expr() {
+      values.push(value);
+      if !self.accept(&Token::Comma) && !self.accept_keyword("or") {
+        break;
+      }
+    }
+    if !values.is_empty() && self.accept(&Token::Colon) {
+      Some(values)
+    } else {
+      self.pos = saved_pos;
+      None
+    }
+  }
+
+  fn parse_case_body(&mut self) -> ParseResult<Vec<Stmt>> {
+    let mut stmts = vec![];
+    loop {
+      self.skip_newlines();
+      if *self.peek() == Token::Eof || self.at_keyword("end") || self.at_keyword("otherwise") {
+        break;
+      }
+      let saved_pos = self.pos;
+      if self.parse_case_label().is_some() {
+        self.pos = saved_pos;
+        break;
+      }
+      stmts.push(self.parse_stmt()?);
+      self.expect_line_end()?;
+    }
+    Ok(stmts)
+  }
+
+  /// Parses a `tell` block, or `tell target to statement` on a single line.
+  fn parse_tell(&mut self) -> ParseResult<Stmt> {
+    self.expect_keyword("tell")?;
+    let target = self.parse_expr()?;
+    if self.accept_keyword("to") {
+      return Ok(Stmt::Tell(target, vec![self.parse_stmt()?]));
+    }
+    self.expect_line_end()?;
+    let body = self.parse_block(&["end"])?;
+    self.expect_keyword("end")?;
+    self.expect_keyword("tell")?;
+    Ok(Stmt::Tell(target, body))
+  }
+
+  fn parse_put(&mut self) -> ParseResult<Stmt> {
+    self.expect_keyword("put")?;
+    let value = self.parse_expr()?;
+    let put_type = if self.accept_keyword("into") {
+      0x01
+    } else if self.accept_keyword("after") {
+      0x02
+    } else if self.accept_keyword("before") {
+      0x03
+    } else {
+      return Ok(Stmt::Call("put".to_owned(), vec![value]));
+    };
+    Ok(Stmt::Put(put_type, self.parse_operand()?, value))
+  }
+
+  fn parse_go(&mut self) -> ParseResult<Stmt> {
+    self.expect_keyword("go")?;
+    self.accept_keyword("to");
+    for (keyword, handler_name) in [("loop", "goLoop"), ("next", "goNext"), ("previous", "goPrevious")] {
+      if self.at_keyword(keyword) && self.peek_at(1) != &Token::LParen {
+        self.advance();
+        return Ok(Stmt::Call(handler_name.to_owned(), vec![]));
+      }
+    }
+    if self.accept_keyword("movie") {
+      let movie = self.parse_expr()?;
+      return Ok(Stmt::Call("go".to_owned(), vec![Expr::Int(1), movie]));
+    }
+    self.accept_keyword("frame");
+    let frame = self.parse_expr()?;
+    if self.accept_keyword("of") {
+      self.expect_keyword("movie")?;
+      let movie = self.parse_expr()?;
+      return Ok(Stmt::Call("go".to_owned(), vec![frame, movie]));
+    }
+    Ok(Stmt::Call("go".to_owned(), vec![frame]))
+  }
+
+  /// Parses assignments, calls like `foo(1)` or `obj.foo()`, and commands like `foo 1, 2`.
+  fn parse_simple_stmt(&mut self) -> ParseResult<Stmt> {
+    let target = self.parse_operand()?;
+    if self.accept(&Token::Eq) {
+      return Ok(Stmt::Assign(target, self.parse_expr()?));
+    }
+    if self.at_stmt_end() {
+      return Ok(match target {
+        Expr::Var(name) => Stmt::Call(name, vec![]),
+        other => Stmt::Expr(other),
+      });
+    }
+    match target {
+      Expr::Var(name) => {
+        let mut args = vec![self.parse_expr()?];
+        while self.accept(&Token::Comma) {
+          args.push(self.parse_expr()?);
+        }
+        Ok(Stmt::Call(name, args))
+      }
+      _ => self.unexpected(),
+    }
+  }
+
+  // Expressions
+
+  pub fn parse_expr(&mut self) -> ParseResult<Expr> {
+    let mut left = self.parse_not()?;
+    loop {
+      let opcode = if self.at_keyword("and") {
+        OpCode::And
+      } else if self.at_keyword("or") {
+        OpCode::Or
+      } else {
+        break;
+      };
+      self.advance();
+      let right = self.parse_not()?;
+      left = Expr::Binary(opcode, Box::new(left), Box::new(right));
+    }
+    Ok(left)
+  }
+
+  fn parse_not(&mut self) -> ParseResult<Expr> {
+    if self.accept_keyword("not") {
+      Ok(Expr::Not(Box::new(self.parse_not()?)))
+    } else {
+      self.parse_comparison()
+    }
+  }
+
+  fn parse_comparison(&mut self) -> ParseResult<Expr> {
+    let mut left = self.parse_concat()?;
+    loop {
+      let opcode = match self.peek() {
+        Token::Eq => OpCode::Eq,
+        Token::NtEq => OpCode::NtEq,
+        Token::Lt => OpCode::Lt,
+        Token::LtEq => OpCode::LtEq,
+        Token::Gt => OpCode::Gt,
+        Token::GtEq => OpCode::GtEq,
+        token if token.is_keyword("contains") => OpCode::ContainsStr,
+        token if token.is_keyword("starts") => OpCode::Contains0Str,
+        _ => break,
+      };
+      self.advance();
+      let right = self.parse_concat()?;
+      left = Expr::Binary(opcode, Box::new(left), Box::new(right));
+    }
+    Ok(left)
+  }
+
+  fn parse_concat(&mut self) -> ParseResult<Expr> {
+    let mut left = self.parse_additive()?;
+    loop {
+      let opcode = match self.peek() {
+        Token::Amp => OpCode::JoinStr,
+        Token::AmpAmp => OpCode::JoinPadStr,
+        _ => break,
+      };
+      self.advance();
+      let right = self.parse_additive()?;
+      left = Expr::Binary(opcode, Box::new(left), Box::new(right));
+    }
+    Ok(left)
+  }
+
+  fn parse_additive(&mut self) -> ParseResult<Expr> {
+    let mut left = self.parse_multiplicative()?;
+    loop {
+      let opcode = match self.peek() {
+        Token::Plus => OpCode::Add,
+        Token::Minus => OpCode::Sub,
+        _ => break,
+      };
+      self.advance();
+      let right = self.parse_multiplicative()?;
+      left = Expr::Binary(opcode, Box::new(left), Box::new(right));
+    }
+    Ok(left)
+  }
+
+  fn parse_multiplicative(&mut self) -> ParseResult<Expr> {
+    let mut left = self.parse_unary()?;
+    loop {
+      let opcode = match self.peek() {
+        Token::Star => OpCode::Mul,
+        Token::Slash => OpCode::Div,
+        token if token.is_keyword("mod") => OpCode::Mod,
+        _ => break,
+      };
+      self.advance();
+      let right = self.parse_unary()?;
+      left = Expr::Binary(opcode, Box::new(left), Box::new(right));
+    }
+    Ok(left)
+  }
+
+  fn parse_unary(&mut self) -> ParseResult<Expr> {
+    if self.accept(&Token::Minus) {
+      return Ok(match self.parse_unary()? {
+        Expr::Int(value) => Expr::Int(-value),
+        Expr::Float(value) => Expr::Float(-value),
+        other => Expr::Negate(Box::new(other)),
+      });
+    }
+    if self.accept(&Token::Plus) {
+      return self.parse_unary();
+    }
+    if self.accept_keyword("not") {
+      return Ok(Expr::Not(Box::new(self.parse_unary()?)));
+    }
+    self.parse_operand()
+  }
+
+  /// Parses a primary expression followed by any `.prop`, `.method()` and `[index]` accessors.
+  fn parse_operand(&mut self) -> ParseResult<Expr> {
+    let mut expr = self.parse_primary()?;
+    loop {
+      if self.accept(&Token::Dot) {
+        let name = self.expect_ident()?;
+        if *self.peek() == Token::LParen {
+          let mut args = vec![expr];
+          args.extend(self.parse_call_args()?);
+          expr = Expr::ObjCall(name, args);
+        } else {
+          expr = Expr::ObjProp(Box::new(expr), name);
+        }
+      } else if self.accept(&Token::LBracket) {
+        let index = self.parse_expr()?;
+        self.expect(Token::RBracket)?;
+        expr = Expr::ObjBracket(Box::new(expr), Box::new(index));
+      } else {
+        break;
+      }
+    }
+    Ok(expr)
+  }
+
+  fn parse_call_args(&mut self) -> ParseResult<Vec<Expr>> {
+    self.expect(Token::LParen)?;
+    let mut args = vec![];
+    if self.accept(&Token::RParen) {
+      return Ok(args);
+    }
+    loop {
+      args.push(self.parse_expr()?);
+      if !self.accept(&Token::Comma) {
+        break;
+      }
+    }
+    self.expect(Token::RParen)?;
+    Ok(args)
+  }
+
+  fn parse_primary(&mut self) -> ParseResult<Expr> {
+    match self.peek().clone() {
+      Token::Int(value) => {
+        self.advance();
+        Ok(Expr::Int(value))
+      }
+      Token::Float(value) => {
+        self.advance();
+        Ok(Expr::Float(value))
+      }
+      Token::String(value) => {
+        self.advance();
+        Ok(Expr::String(value))
+      }
+      Token::Symbol(name) => {
+        self.advance();
+        Ok(Expr::Symbol(name))
+      }
+      Token::LParen => {
+        self.advance();
+        let expr = self.parse_expr()?;
+        self.expect(Token::RParen)?;
+        Ok(expr)
+      }
+      Token::LBracket => {
+        self.advance();
+        self.parse_list()
+      }
+      Token::Ident(name) => {
+        self.advance();
+        self.parse_ident_expr(name)
+      }
+      _ => self.unexpected(),
+    }
+  }
+
+  fn parse_list(&mut self) -> ParseResult<Expr> {
+    if self.accept(&Token::RBracket) {
+      return Ok(Expr::List(vec![]));
+    }
+    if self.accept(&Token::Colon) {
+      self.expect(Token::RBracket)?;
+      return Ok(Expr::PropList(vec![]));
+    }
+    let first = self.parse_expr()?;
+    if self.accept(&Token::Colon) {
+      let mut pairs = vec![(prop_list_key(first), self.parse_expr()?)];
+      while self.accept(&Token::Comma) {
+        let key = prop_list_key(self.parse_expr()?);
+        self.expect(Token::Colon)?;
+        pairs.push((key, self.parse_expr()?));
+      }
+      self.expect(Token::RBracket)?;
+      Ok(Expr::PropList(pairs))
+    } else {
+      let mut items = vec![first];
+      while self.accept(&Token::Comma) {
+        items.push(self.parse_expr()?);
+      }
+      self.expect(Token::RBracket)?;
+      Ok(Expr::List(items))
+    }
+  }
+
+  fn parse_ident_expr(&mut self, name: String) -> ParseResult<Expr> {
+    let is_call = *self.peek() == Token::LParen;
+    if let Some(constant) = constant_expr(&name) {
+      return Ok(constant);
+    }
+    if name.eq_ignore_ascii_case("the") {
+      return self.parse_the();
+    }
+    if !is_call && (name.eq_ignore_ascii_case("void") || name.eq_ignore_ascii_case("pi")) {
+      return Ok(Expr::Call(name.to_lowercase(), vec![]));
+    }
+    if !is_call && self.at_operand_start() {
+      if let Some(function_name) = reference_function_name(&name) {
+        return self.parse_reference(function_name);
+      }
+      if let Some(chunk_type) = chunk_type_from_word(&Token::Ident(name.to_owned()), false) {
+        if let Some(chunk) = self.parse_chunk(chunk_type) {
+          return Ok(chunk);
+        }
+      }
+    }
+    if is_call {
+      let args = self.parse_call_args()?;
+      return Ok(Expr::Call(name, args));
+    }
+    Ok(Expr::Var(name))
+  }
+
+  /// Parses the verbose form of a reference, such as `sprite 1` or `member "x" of castLib 2`.
+  fn parse_reference(&mut self, function_name: &str) -> ParseResult<Expr> {
+    let id = self.parse_unary()?;
+    if function_name == "sprite" {
+      if self.accept_keyword("intersects") {
+        return Ok(Expr::SpriteIntersects(Box::new(id), Box::new(self.parse_unary()?)));
+      }
+      if self.accept_keyword("within") {
+        return Ok(Expr::SpriteWithin(Box::new(id), Box::new(self.parse_unary()?)));
+      }
+    }
+    let has_cast = matches!(function_name, "member" | "field" | "script")
+      && self.at_keyword("of")
+      && (self.peek_at(1).is_keyword("castLib") || self.peek_at(1).is_keyword("cast"));
+    if has_cast {
+      self.advance();
+      self.advance();
+      let cast = self.parse_unary()?;
+      return Ok(Expr::Call(function_name.to_owned(), vec![id, cast]));
+    }
+    Ok(Expr::Call(function_name.to_owned(), vec![id]))
+  }
+
+  /// Parses `char 1 to 3 of s` after the chunk keyword, or returns `None` with the position unchanged
+  /// if the keyword is used as a variable name instead.
+  fn parse_chunk(&mut self, chunk_type: StringChunkType) -> Option<Expr> {
+    let saved_pos = self.pos;
+    let result = (|| {
+      let first = self.parse_unary().ok()?;
+      let last = if self.accept_keyword("to") { self.parse_unary().ok()? } else { Expr::Int(0) };
+      if !self.accept_keyword("of") {
+        return None;
+      }
+      let string = self.parse_unary().ok()?;
+      Some(Expr::Chunk(chunk_type, Box::new(first), Box::new(last), Box::new(string)))
+    })();
+    if result.is_none() {
+      self.pos = saved_pos;
+    }
+    result
+  }
+
+  /// Parses the expression after `the`.
+  fn parse_the(&mut self) -> ParseResult<Expr> {
+    if self.at_keyword("number") && self.peek_at(1).is_keyword("of") {
+      if let Some(chunk_type) = chunk_type_from_word(self.peek_at(2), true) {
+        self.advance();
+        self.advance();
+        self.advance();
+        if !self.accept_keyword("in") {
+          self.expect_keyword("of")?;
+        }
+        let string = self.parse_unary()?;
+        return Ok(Expr::ChunkCount(chunk_type, Box::new(string)));
+      }
+    }
+    if self.at_keyword("last") {
+      if let Some(chunk_type) = chunk_type_from_word(self.peek_at(1), false) {
+        self.advance();
+        self.advance();
+        if !self.accept_keyword("in") {
+          self.expect_keyword("of")?;
+        }
+        let string = self.parse_unary()?;
+        return Ok(Expr::LastChunk(chunk_type, Box::new(string)));
+      }
+    }
+    let prop = self.expect_ident()?;
+    if self.accept_keyword("of") {
+      let obj = self.parse_unary()?;
+      return Ok(Expr::TheProp(Box::new(obj), prop));
+    }
+    Ok(Expr::The(prop))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::super::lexer::tokenize;
+  use super::*;
+
+  fn parse_expr(source: &str) -> Expr {
+    Parser::new(tokenize(source).unwrap()).parse_single_expr().unwrap()
+  }
+
+  /// Writes an expression with every binary and `not` operation in parentheses, so the tree's shape shows.
+  fn grouped(expr: &Expr) -> String {
+    match expr {
+      Expr::Binary(opcode, left, right) => {
+        // Operands written as variables are printed verbatim
+        let left = Box::new(Expr::Var(grouped(left)));
+        let right = Box::new(Expr::Var(grouped(right)));
+        format!("({})", Expr::Binary(*opcode, left, right).to_lingo(false))
+      }
+      Expr::Not(operand) => format!("(not {})", grouped(operand)),
+      other => other.to_lingo(false),
+    }
+  }
+
+  fn parse_error(source: &str) -> String {
+    Parser::new(tokenize(source).unwrap()).parse_script().err().unwrap()
+  }
+
+  #[test]
+  fn parses_operators_by_precedence() {
+    let cases = [
+      ("1 + 2 * 3", "(1 + (2 * 3))"),
+      ("(1 + 2) * 3", "((1 + 2) * 3)"),
+      ("10 - 2 - 3", "((10 - 2) - 3)"),
+      ("a mod b / c", "((a mod b) / c)"),
+      ("a & b + c", "(a & (b + c))"),
+      ("a & b = c", "((a & b) = c)"),
+      ("a = b and c < d", "((a = b) and (c < d))"),
+      ("a or b and c", "((a or b) and c)"),
+      ("not a = b", "(not (a = b))"),
+      ("-x * 2", "(-x * 2)"),
+      ("-3 - -2", "(-3 - -2)"),
+    ];
+    for (source, expected) in cases {
+      assert_eq!(grouped(&parse_expr(source)), expected, "{}", source);
+    }
+  }
+
+  #[test]
+  fn parses_chunk_expressions() {
+    let cases = [
+      ("char 2 to 4 of s", "char 2 to 4 of s"),
+      ("word 1 of line 2 of s", "word 1 of line 2 of s"),
+      ("char 1 of s & x", "(char 1 of s & x)"),
+      ("the number of items in s", "the number of items in s"),
+      ("the number of words of s", "the number of words in s"),
+      ("the last word in s", "the last word in s"),
+      // Without a chunk after them, chunk keywords are plain variables
+      ("char + 1", "(char + 1)"),
+    ];
+    for (source, expected) in cases {
+      assert_eq!(grouped(&parse_expr(source)), expected, "{}", source);
+    }
+  }
+
+  #[test]
+  fn parses_the_properties() {
+    assert!(matches!(parse_expr("the mouseH"), Expr::The(prop) if prop == "mouseH"));
+    assert_eq!(parse_expr("the name of member 1").to_lingo(false), "the name of member(1)");
+    assert_eq!(grouped(&parse_expr("the locH of sprite 2 + 1")), "(the locH of sprite(2) + 1)");
+    assert!(matches!(parse_expr("sprite(1).locH"), Expr::ObjProp(_, prop) if prop == "locH"));
+    assert!(matches!(parse_expr("the paramCount"), Expr::The(prop) if prop == "paramCount"));
+  }
+
+  #[test]
+  fn reports_the_line_of_malformed_scripts() {
+    assert_eq!(parse_error("on test\r  x = (1 + 2\rend"), "Line 2: Expected ), found end of line");
+    assert_eq!(parse_error("on test\r  if x then\r    y = 1\r  end\rend"), "Line 4: Expected if, found end of line");
+    assert_eq!(parse_error("on test\r  repeat with i = 1 until 3\r  end repeat\rend"), "Line 2: Expected to, found until");
+    assert_eq!(parse_error("on a\rend\r\rfoo"), "Line 4: Expected a handler, found foo");
+    assert_eq!(Parser::new(tokenize("1 +").unwrap()).parse_single_expr().err().unwrap(), "Line 1: Unexpected end of line");
+  }
+}
@@ -153,6 +153,89 @@ lazy_static! {
     (0x0a, "abbr date".to_owned()),
     (0x0b, "long date".to_owned()),
   ]);
+
+  pub static ref SPRITE_PROP_NAMES: HashMap<u16, String> = HashMap::from([
+    (0x01, "type".to_owned()),
+    (0x02, "backColor".to_owned()),
+    (0x03, "bottom".to_owned()),
+    (0x04, "castNum".to_owned()),
+    (0x05, "constraint".to_owned()),
+    (0x06, "cursor".to_owned()),
+    (0x07, "foreColor".to_owned()),
+    (0x08, "height".to_owned()),
+    (0x09, "immediate".to_owned()),
+    (0x0a, "ink".to_owned()),
+    (0x0b, "left".to_owned()),
+    (0x0c, "lineSize".to_owned()),
+    (0x0d, "locH".to_owned()),
+    (0x0e, "locV".to_owned()),
+    (0x0f, "movieRate".to_owned()),
+    (0x10, "movieTime".to_owned()),
+    (0x11, "pattern".to_owned()),
+    (0x12, "puppet".to_owned()),
+    (0x13, "right".to_owned()),
+    (0x14, "startTime".to_owned()),
+    (0x15, "stopTime".to_owned()),
+    (0x16, "stretch".to_owned()),
+    (0x17, "top".to_owned()),
+    (0x18, "trails".to_owned()),
+    (0x19, "visible".to_owned()),
+    (0x1a, "volume".to_owned()),
+    (0x1b, "width".to_owned()),
+    (0x1c, "blend".to_owned()),
+    (0x1d, "scriptNum".to_owned()),
+    (0x1e, "moveableSprite".to_owned()),
+    (0x1f, "editableText".to_owned()),
+    (0x20, "scoreColor".to_owned()),
+    (0x21, "loc".to_owned()),
+    (0x22, "rect".to_owned()),
+    (0x23, "memberNum".to_owned()),
+    (0x24, "castLibNum".to_owned()),
+    (0x25, "member".to_owned()),
+    (0x26, "scriptInstanceList".to_owned()),
+    (0x27, "currentTime".to_owned()),
+    (0x28, "mostRecentCuePoint".to_owned()),
+    (0x29, "tweened".to_owned()),
+    (0x2a, "name".to_owned()),
+  ]);
+
+  pub static ref MEMBER_PROP_NAMES: HashMap<u16, String> = HashMap::from([
+    (0x01, "name".to_owned()),
+    (0x02, "text".to_owned()),
+    (0x03, "textStyle".to_owned()),
+    (0x04, "textFont".to_owned()),
+    (0x05, "textHeight".to_owned()),
+    (0x06, "textAlign".to_owned()),
+    (0x07, "textSize".to_owned()),
+    (0x08, "picture".to_owned()),
+    (0x09, "hilite".to_owned()),
+    (0x0a, "number".to_owned()),
+    (0x0b, "size".to_owned()),
+    (0x0c, "loop".to_owned()),
+    (0x0d, "duration".to_owned()),
+    (0x0e, "controller".to_owned()),
+    (0x0f, "directToStage".to_owned()),
+    (0x10, "sound".to_owned()),
+    (0x11, "foreColor".to_owned()),
+    (0x12, "backColor".to_owned()),
+    (0x13, "type".to_owned()),
+  ]);
+
+  pub static ref MENU_PROP_NAMES: HashMap<u16, String> = HashMap::from([
+    (0x01, "name".to_owned()),
+    (0x02, "number of menuItems".to_owned()),
+  ]);
+
+  pub static ref MENU_ITEM_PROP_NAMES: HashMap<u16, String> = HashMap::from([
+    (0x01, "name".to_owned()),
+    (0x02, "checkMark".to_owned()),
+    (0x03, "enabled".to_owned()),
+    (0x04, "script".to_owned()),
+  ]);
+
+  pub static ref SOUND_PROP_NAMES: HashMap<u16, String> = HashMap::from([
+    (0x01, "volume".to_owned()),
+  ]);
 }
 
 pub fn get_opcode_name(opcode: &OpCode) -> String {
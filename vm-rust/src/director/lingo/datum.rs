@@ -16,6 +16,7 @@ pub enum DatumType {
   CastLibRef,
   CastMemberRef,
   StageRef,
+  WindowRef,
   SpriteRef,
   StringChunk,
 	String,
@@ -39,6 +40,7 @@ pub enum DatumType {
   PlayerRef,
   MovieRef,
   SoundRef,
+  Media,
 }
 
 #[derive(Clone, FromPrimitive)]
@@ -116,6 +118,8 @@ pub enum Datum {
   Symbol(String),
   CastLib(u32),
   Stage,
+  /// A movie in a window, by the name of the window.
+  Window(String),
   ScriptRef(CastMemberRef),
   ScriptInstanceRef(ScriptInstanceRef),
   CastMember(CastMemberRef),
@@ -133,6 +137,8 @@ pub enum Datum {
   PlayerRef,
   MovieRef,
   SoundRef(u16),
+  /// The raw media data of a cast member, such as the content of a Multiuser message.
+  Media(Vec<u8>),
   Null,
 }
 
@@ -150,6 +156,7 @@ impl DatumType {
       DatumType::Symbol => "symbol".to_string(),
       DatumType::CastLibRef => "cast_lib".to_string(),
       DatumType::StageRef => "stage".to_string(),
+      DatumType::WindowRef => "window".to_string(),
       DatumType::ScriptRef => "script_ref".to_string(),
       DatumType::ScriptInstanceRef => "script_instance".to_string(),
       DatumType::CastMemberRef => "cast_member".to_string(),
@@ -171,6 +178,7 @@ impl DatumType {
       DatumType::PlayerRef => "player_ref".to_string(),
       DatumType::MovieRef => "movie_ref".to_string(),
       DatumType::SoundRef => "sound_ref".to_string(),
+      DatumType::Media => "media".to_string(),
     }
   }
 }
@@ -189,6 +197,7 @@ impl Datum {
       Datum::Symbol(_) => DatumType::Symbol,
       Datum::CastLib(_) => DatumType::CastLibRef,
       Datum::Stage => DatumType::StageRef,
+      Datum::Window(_) => DatumType::WindowRef,
       Datum::ScriptRef(_) => DatumType::ScriptRef,
       Datum::ScriptInstanceRef(_) => DatumType::ScriptInstanceRef,
       Datum::CastMember(_) => DatumType::CastMemberRef,
@@ -206,6 +215,7 @@ impl Datum {
       Datum::PlayerRef => DatumType::PlayerRef,
       Datum::MovieRef => DatumType::MovieRef,
       Datum::SoundRef(_) => DatumType::SoundRef,
+      Datum::Media(_) => DatumType::Media,
       Datum::Null => DatumType::Null,
     }
   }
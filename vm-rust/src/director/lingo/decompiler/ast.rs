@@ -0,0 +1,387 @@
+use itertools::Itertools;
+
+use crate::director::lingo::{datum::StringChunkType, opcode::OpCode};
+
+pub type StmtId = usize;
+pub type BlockId = usize;
+
+#[derive(Clone)]
+pub enum Expr {
+  Error(String),
+  Int(i32),
+  Float(f32),
+  String(String),
+  Symbol(String),
+  Var(String),
+  List(Vec<Expr>),
+  PropList(Vec<(Expr, Expr)>),
+  ArgList(Vec<Expr>, bool), // bool is for whether the call result is used
+  The(String),
+  /// `the prop of obj`, or `obj.prop` with dot syntax when `obj` is a member-like reference.
+  TheProp(Box<Expr>, String),
+  /// A reference such as `member 1 of castLib 2`, `sprite 3` or `field "x"`.
+  Member(String, Box<Expr>, Option<Box<Expr>>),
+  MenuItem(Box<Expr>, Box<Expr>),
+  Chunk(StringChunkType, Box<Expr>, Box<Expr>, Box<Expr>),
+  ChunkCount(StringChunkType, Box<Expr>),
+  LastChunk(StringChunkType, Box<Expr>),
+  Binary(OpCode, Box<Expr>, Box<Expr>),
+  Not(Box<Expr>),
+  Negate(Box<Expr>),
+  SpriteIntersects(Box<Expr>, Box<Expr>),
+  SpriteWithin(Box<Expr>, Box<Expr>),
+  Call(String, Vec<Expr>),
+  ObjCall(String, Vec<Expr>),
+  ObjCallV4(Box<Expr>, Vec<Expr>),
+  ObjProp(Box<Expr>, String),
+  ObjBracket(Box<Expr>, Box<Expr>),
+  ObjPropIndex(Box<Expr>, String, Box<Expr>, Option<Box<Expr>>),
+  NewObj(String, Vec<Expr>),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum CaseExpect {
+  End,
+  Or,
+  Next,
+  Otherwise,
+}
+
+pub struct CaseLabel {
+  pub values: Vec<Expr>,
+  pub expect: CaseExpect,
+  pub block: Option<BlockId>,
+}
+
+pub enum Stmt {
+  Expr(Expr),
+  Comment(String),
+  Assign(Expr, Expr, bool), // bool forces the verbose `set ... to ...` form
+  Put(u8, Expr, Expr),
+  Call(String, Vec<Expr>),
+  SoundCmd(String, Vec<Expr>),
+  When(String, String),
+  ChunkHilite(Expr),
+  ChunkDelete(Expr),
+  Return(Option<Expr>),
+  Exit,
+  ExitRepeat,
+  NextRepeat,
+  If(Expr, BlockId, Option<BlockId>),
+  RepeatWhile(Expr, BlockId),
+  RepeatWithIn(String, Expr, BlockId),
+  RepeatWithTo(String, Expr, Expr, bool, BlockId), // bool is true for `to` and false for `down to`
+  Case(Expr, Vec<CaseLabel>, Option<BlockId>),
+  Tell(Expr, BlockId),
+}
+
+pub struct StmtNode {
+  pub stmt: Stmt,
+  /// The block the statement belongs to.
+  pub parent: BlockId,
+  /// Index of the bytecode the statement was translated from.
+  pub index: usize,
+  /// For case statements, the position the labels jump to when they are done.
+  pub end_pos: Option<usize>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum BlockKind {
+  Root,
+  Then,
+  Else,
+  Loop,
+  CaseLabel,
+  Otherwise,
+  Tell,
+}
+
+pub struct Block {
+  pub kind: BlockKind,
+  pub stmts: Vec<StmtId>,
+  /// The statement owning the block, `None` for the handler body.
+  pub parent: Option<StmtId>,
+  /// Bytecode position at which the block ends, if it is known.
+  pub end_pos: Option<usize>,
+  /// The case statement of this block that is still expecting labels.
+  pub current_case: Option<StmtId>,
+}
+
+pub struct Ast {
+  pub stmts: Vec<StmtNode>,
+  pub blocks: Vec<Block>,
+}
+
+impl Ast {
+  pub fn new() -> Ast {
+    Ast {
+      stmts: vec![],
+      blocks: vec![Block {
+        kind: BlockKind::Root,
+        stmts: vec![],
+        parent: None,
+        end_pos: None,
+        current_case: None,
+      }],
+    }
+  }
+
+  pub fn add_block(&mut self, kind: BlockKind, parent: StmtId, end_pos: Option<usize>) -> BlockId {
+    self.blocks.push(Block {
+      kind,
+      stmts: vec![],
+      parent: Some(parent),
+      end_pos,
+      current_case: None,
+    });
+    self.blocks.len() - 1
+  }
+
+  pub fn add_stmt(&mut self, block: BlockId, stmt: Stmt, index: usize) -> StmtId {
+    self.stmts.push(StmtNode {
+      stmt,
+      parent: block,
+      index,
+      end_pos: None,
+    });
+    let id = self.stmts.len() - 1;
+    self.blocks[block].stmts.push(id);
+    id
+  }
+
+  /// Returns the innermost loop statement enclosing the block.
+  pub fn ancestor_loop(&self, block: BlockId) -> Option<StmtId> {
+    let mut block = block;
+    while let Some(stmt_id) = self.blocks[block].parent {
+      match self.stmts[stmt_id].stmt {
+        Stmt::RepeatWhile(..) | Stmt::RepeatWithIn(..) | Stmt::RepeatWithTo(..) => return Some(stmt_id),
+        _ => block = self.stmts[stmt_id].parent,
+      }
+    }
+    None
+  }
+}
+
+pub fn chunk_type_name(chunk_type: &StringChunkType) -> String {
+  chunk_type.clone().into()
+}
+
+fn binary_op_text(opcode: OpCode) -> &'static str {
+  match opcode {
+    OpCode::Mul => "*",
+    OpCode::Add => "+",
+    OpCode::Sub => "-",
+    OpCode::Div => "/",
+    OpCode::Mod => "mod",
+    OpCode::JoinStr => "&",
+    OpCode::JoinPadStr => "&&",
+    OpCode::Lt => "<",
+    OpCode::LtEq => "<=",
+    OpCode::NtEq => "<>",
+    OpCode::Eq => "=",
+    OpCode::Gt => ">",
+    OpCode::GtEq => ">=",
+    OpCode::And => "and",
+    OpCode::Or => "or",
+    OpCode::ContainsStr => "contains",
+    OpCode::Contains0Str => "starts",
+    _ => "?",
+  }
+}
+
+/// Lower values bind tighter. `and` and `or` share a level since Lingo evaluates them left to right.
+/// Precedence 0 means the operator is always parenthesized when mixed with others.
+fn binary_op_precedence(opcode: OpCode) -> u8 {
+  match opcode {
+    OpCode::Mul | OpCode::Div | OpCode::Mod => 1,
+    OpCode::Add | OpCode::Sub => 2,
+    OpCode::Lt | OpCode::LtEq | OpCode::NtEq | OpCode::Eq | OpCode::Gt | OpCode::GtEq => 3,
+    OpCode::And | OpCode::Or => 4,
+    _ => 0,
+  }
+}
+
+fn is_associative(opcode: OpCode) -> bool {
+  matches!(opcode, OpCode::Add | OpCode::Mul | OpCode::And | OpCode::Or | OpCode::JoinStr | OpCode::JoinPadStr)
+}
+
+/// Whether an operand of `parent` that is itself an operation with `child` needs parentheses.
+fn needs_parens(parent: OpCode, child: OpCode, is_right: bool) -> bool {
+  let parent_precedence = binary_op_precedence(parent);
+  let child_precedence = binary_op_precedence(child);
+  if parent_precedence == 0 || child_precedence == 0 {
+    return child != parent || (is_right && !is_associative(parent));
+  }
+  if child_precedence != parent_precedence {
+    return child_precedence > parent_precedence;
+  }
+  is_right && (child != parent || !is_associative(parent))
+}
+
+fn format_float(value: f32) -> String {
+  let text = value.to_string();
+  if text.contains('.') || text.contains('e') || !value.is_finite() {
+    text
+  } else {
+    format!("{}.0", text)
+  }
+}
+
+fn format_string(value: &str) -> String {
+  match value {
+    "" => return "EMPTY".to_owned(),
+    "\x03" => return "ENTER".to_owned(),
+    "\x08" => return "BACKSPACE".to_owned(),
+    "\t" => return "TAB".to_owned(),
+    "\r" => return "RETURN".to_owned(),
+    "\"" => return "QUOTE".to_owned(),
+    _ => {}
+  }
+  // Lingo string literals have no escapes, so quotes and line breaks are joined in as constants.
+  let mut parts = vec![];
+  let mut current = String::new();
+  for c in value.chars() {
+    let constant = match c {
+      '"' => "QUOTE",
+      '\r' => "RETURN",
+      _ => {
+        current.push(c);
+        continue;
+      }
+    };
+    if !current.is_empty() {
+      parts.push(format!("\"{}\"", current));
+      current.clear();
+    }
+    parts.push(constant.to_owned());
+  }
+  if !current.is_empty() {
+    parts.push(format!("\"{}\"", current));
+  }
+  if parts.len() == 1 {
+    parts.remove(0)
+  } else {
+    format!("({})", parts.join(" & "))
+  }
+}
+
+fn join_exprs(exprs: &[Expr], dot: bool) -> String {
+  exprs.iter().map(|x| x.to_lingo(dot)).join(", ")
+}
+
+impl Expr {
+  pub fn int_value(&self) -> Option<i32> {
+    match self {
+      Expr::Int(value) => Some(*value),
+      _ => None,
+    }
+  }
+
+  fn is_operation(&self) -> bool {
+    matches!(self, Expr::Binary(..) | Expr::Not(..) | Expr::Negate(..) | Expr::SpriteIntersects(..) | Expr::SpriteWithin(..))
+  }
+
+  /// Writes the expression, parenthesized if it is an operation.
+  fn to_lingo_operand(&self, dot: bool) -> String {
+    if self.is_operation() {
+      format!("({})", self.to_lingo(dot))
+    } else {
+      self.to_lingo(dot)
+    }
+  }
+
+  pub fn to_lingo(&self, dot: bool) -> String {
+    match self {
+      Expr::Error(message) => format!("ERROR({})", message),
+      Expr::Int(value) => value.to_string(),
+      Expr::Float(value) => format_float(*value),
+      Expr::String(value) => format_string(value),
+      Expr::Symbol(name) => format!("#{}", name),
+      Expr::Var(name) => name.to_owned(),
+      Expr::List(items) | Expr::ArgList(items, _) => format!("[{}]", join_exprs(items, dot)),
+      Expr::PropList(pairs) => {
+        if pairs.is_empty() {
+          "[:]".to_owned()
+        } else {
+          let mut pairs = pairs.iter().map(|(key, value)| format!("{}: {}", key.to_lingo(dot), value.to_lingo(dot)));
+          format!("[{}]", pairs.join(", "))
+        }
+      }
+      Expr::The(name) => format!("the {}", name),
+      Expr::TheProp(obj, prop) => {
+        if dot && matches!(**obj, Expr::Member(..)) {
+          format!("{}.{}", obj.to_lingo(dot), prop)
+        } else {
+          format!("the {} of {}", prop, obj.to_lingo_operand(false))
+        }
+      }
+      Expr::Member(kind, id, cast) => {
+        let cast = cast.as_ref().filter(|cast| cast.int_value() != Some(0));
+        if dot {
+          match cast {
+            Some(cast) => format!("{}({}, {})", kind, id.to_lingo(dot), cast.to_lingo(dot)),
+            None => format!("{}({})", kind, id.to_lingo(dot)),
+          }
+        } else {
+          match cast {
+            Some(cast) => format!("{} {} of castLib {}", kind, id.to_lingo_operand(dot), cast.to_lingo_operand(dot)),
+            None => format!("{} {}", kind, id.to_lingo_operand(dot)),
+          }
+        }
+      }
+      Expr::MenuItem(item, menu) => format!("menuItem {} of menu {}", item.to_lingo_operand(dot), menu.to_lingo_operand(dot)),
+      Expr::Chunk(chunk_type, first, last, string) => {
+        let mut result = format!("{} {}", chunk_type_name(chunk_type), first.to_lingo_operand(dot));
+        if last.int_value() != Some(0) {
+          result.push_str(&format!(" to {}", last.to_lingo_operand(dot)));
+        }
+        result.push_str(&format!(" of {}", string.to_lingo_operand(dot)));
+        result
+      }
+      Expr::ChunkCount(chunk_type, string) => format!("the number of {}s in {}", chunk_type_name(chunk_type), string.to_lingo_operand(dot)),
+      Expr::LastChunk(chunk_type, string) => format!("the last {} in {}", chunk_type_name(chunk_type), string.to_lingo_operand(dot)),
+      Expr::Binary(opcode, left, right) => {
+        let paren_left = match &**left {
+          Expr::Binary(left_op, ..) => needs_parens(*opcode, *left_op, false),
+          other => other.is_operation(),
+        };
+        let paren_right = match &**right {
+          Expr::Binary(right_op, ..) => needs_parens(*opcode, *right_op, true),
+          other => other.is_operation(),
+        };
+        let left = if paren_left { format!("({})", left.to_lingo(dot)) } else { left.to_lingo(dot) };
+        let right = if paren_right { format!("({})", right.to_lingo(dot)) } else { right.to_lingo(dot) };
+        format!("{} {} {}", left, binary_op_text(*opcode), right)
+      }
+      Expr::Not(operand) => format!("not {}", operand.to_lingo_operand(dot)),
+      Expr::Negate(operand) => format!("-{}", operand.to_lingo_operand(dot)),
+      Expr::SpriteIntersects(first, second) => format!("sprite {} intersects {}", first.to_lingo_operand(dot), second.to_lingo_operand(dot)),
+      Expr::SpriteWithin(first, second) => format!("sprite {} within {}", first.to_lingo_operand(dot), second.to_lingo_operand(dot)),
+      Expr::Call(name, args) => format!("{}({})", name, join_exprs(args, dot)),
+      Expr::ObjCall(method, args) => {
+        if dot && !args.is_empty() {
+          format!("{}.{}({})", args[0].to_lingo_operand(dot), method, join_exprs(&args[1..], dot))
+        } else {
+          format!("{}({})", method, join_exprs(args, dot))
+        }
+      }
+      Expr::ObjCallV4(obj, args) => format!("{}({})", obj.to_lingo_operand(dot), join_exprs(args, dot)),
+      Expr::ObjProp(obj, prop) => {
+        if dot {
+          format!("{}.{}", obj.to_lingo_operand(dot), prop)
+        } else {
+          format!("the {} of {}", prop, obj.to_lingo_operand(dot))
+        }
+      }
+      Expr::ObjBracket(obj, index) => format!("{}[{}]", obj.to_lingo_operand(dot), index.to_lingo(dot)),
+      Expr::ObjPropIndex(obj, prop, index, index2) => {
+        let range = match index2 {
+          Some(index2) => format!("{}..{}", index.to_lingo(dot), index2.to_lingo(dot)),
+          None => index.to_lingo(dot),
+        };
+        format!("{}.{}[{}]", obj.to_lingo_operand(dot), prop, range)
+      }
+      Expr::NewObj(obj_type, args) => format!("new {}({})", obj_type, join_exprs(args, dot)),
+    }
+  }
+}
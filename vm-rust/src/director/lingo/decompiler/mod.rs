@@ -0,0 +1,429 @@
+pub mod ast;
+pub mod translate;
+
+use std::collections::HashMap;
+
+use itertools::Itertools;
+
+use crate::director::{chunks::{handler::HandlerDef, script::ScriptChunk}, lingo::script::ScriptContext};
+
+use self::{
+  ast::{Ast, BlockId, Stmt, StmtId},
+  translate::{BytecodeOwner, StmtPart, Translator},
+};
+
+/// What the decompiler needs to know about the script a handler belongs to.
+pub struct DecompilerContext<'a> {
+  pub names: &'a [String],
+  pub script: &'a ScriptChunk,
+  pub dir_version: u16,
+  pub capital_x: bool,
+}
+
+impl<'a> DecompilerContext<'a> {
+  pub fn new(lctx: &'a ScriptContext, script: &'a ScriptChunk, dir_version: u16, capital_x: bool) -> DecompilerContext<'a> {
+    DecompilerContext {
+      names: &lctx.names,
+      script,
+      dir_version,
+      capital_x,
+    }
+  }
+
+  /// Dot syntax was introduced in Director 7.
+  pub fn dot_syntax(&self) -> bool {
+    self.dir_version >= 700
+  }
+
+  fn name(&self, id: u16) -> String {
+    self.names.get(id as usize).cloned().unwrap_or_else(|| format!("UNKNOWN_NAME_{}", id))
+  }
+}
+
+pub struct DecompiledLine {
+  pub text: String,
+  /// The first bytecode of the line, where a breakpoint on the line is set.
+  pub bytecode_index: Option<usize>,
+}
+
+pub struct DecompiledHandler {
+  /// The handler source, from `on` to `end`.
+  pub lines: Vec<DecompiledLine>,
+  /// The line each bytecode of the handler was decompiled to.
+  pub bytecode_lines: Vec<usize>,
+}
+
+/// Decompiles a handler to Lingo source.
+pub fn decompile_handler(ctx: &DecompilerContext, handler: &HandlerDef) -> DecompiledHandler {
+  let mut translator = Translator::new(ctx, handler);
+  translator.translate();
+
+  let mut writer = CodeWriter {
+    ast: &translator.ast,
+    dot: ctx.dot_syntax(),
+    indent: 0,
+    lines: vec![],
+  };
+  let args = handler.argument_name_ids.iter().map(|x| ctx.name(*x)).join(", ");
+  if args.is_empty() {
+    writer.line(format!("on {}", ctx.name(handler.name_id)), vec![]);
+  } else {
+    writer.line(format!("on {} {}", ctx.name(handler.name_id), args), vec![]);
+  }
+  writer.indent += 1;
+  if !handler.global_name_ids.is_empty() {
+    let globals = handler.global_name_ids.iter().map(|x| ctx.name(*x)).join(", ");
+    writer.line(format!("global {}", globals), vec![]);
+  }
+  writer.write_block(0);
+  writer.indent -= 1;
+  writer.line("end".to_owned(), vec![]);
+
+  let mut owner_lines: HashMap<BytecodeOwner, usize> = HashMap::new();
+  for (line_index, (_, owners)) in writer.lines.iter().enumerate() {
+    for owner in owners {
+      owner_lines.entry(*owner).or_insert(line_index);
+    }
+  }
+  let end_line = writer.lines.len() - 1;
+  let bytecode_lines = translator
+    .owners
+    .iter()
+    .map(|owner| match owner {
+      Some((stmt_id, part)) => owner_lines
+        .get(&(*stmt_id, *part))
+        .or_else(|| owner_lines.get(&(*stmt_id, StmtPart::Head)))
+        .copied()
+        .unwrap_or(end_line),
+      None => end_line,
+    })
+    .collect_vec();
+
+  let mut lines = writer.lines.into_iter().map(|(text, _)| DecompiledLine { text, bytecode_index: None }).collect_vec();
+  for (bytecode_index, line_index) in bytecode_lines.iter().enumerate() {
+    let line = &mut lines[*line_index];
+    if line.bytecode_index.is_none() {
+      line.bytecode_index = Some(bytecode_index);
+    }
+  }
+  DecompiledHandler { lines, bytecode_lines }
+}
+
+/// Decompiles all handlers of a script, as shown in the script window.
+pub fn decompile_script(ctx: &DecompilerContext) -> String {
+  let mut lines = vec![];
+  if !ctx.script.property_name_ids.is_empty() {
+    let properties = ctx.script.property_name_ids.iter().map(|x| ctx.name(*x)).join(", ");
+    lines.push(format!("property {}", properties));
+  }
+  for handler in &ctx.script.handlers {
+    if !lines.is_empty() {
+      lines.push(String::new());
+    }
+    lines.extend(decompile_handler(ctx, handler).lines.into_iter().map(|x| x.text));
+  }
+  lines.join("\r")
+}
+
+fn put_type_name(put_type: u8) -> &'static str {
+  match put_type {
+    0x02 => "after",
+    0x03 => "before",
+    _ => "into",
+  }
+}
+
+struct CodeWriter<'a> {
+  ast: &'a Ast,
+  dot: bool,
+  indent: usize,
+  lines: Vec<(String, Vec<BytecodeOwner>)>,
+}
+
+impl<'a> CodeWriter<'a> {
+  fn line(&mut self, text: String, owners: Vec<BytecodeOwner>) {
+    self.lines.push((format!("{}{}", "  ".repeat(self.indent), text), owners));
+  }
+
+  fn write_block(&mut self, block: BlockId) {
+    for stmt_id in &self.ast.blocks[block].stmts {
+      self.write_stmt(*stmt_id);
+    }
+  }
+
+  fn write_indented_block(&mut self, block: BlockId) {
+    self.indent += 1;
+    self.write_block(block);
+    self.indent -= 1;
+  }
+
+  fn write_stmt(&mut self, id: StmtId) {
+    let dot = self.dot;
+    let head = vec![(id, StmtPart::Head)];
+    let end = vec![(id, StmtPart::End)];
+    match &self.ast.stmts[id].stmt {
+      Stmt::Expr(expr) => self.line(expr.to_lingo(dot), head),
+      Stmt::Comment(text) => self.line(format!("-- {}", text), head),
+      Stmt::Assign(target, value, force_verbose) => {
+        let text = if !dot || *force_verbose {
+          format!("set {} to {}", target.to_lingo(false), value.to_lingo(dot))
+        } else {
+          format!("{} = {}", target.to_lingo(dot), value.to_lingo(dot))
+        };
+        self.line(text, head);
+      }
+      Stmt::Put(put_type, target, value) => {
+        self.line(format!("put {} {} {}", value.to_lingo(dot), put_type_name(*put_type), target.to_lingo(dot)), head);
+      }
+      Stmt::Call(name, args) => {
+        if args.is_empty() {
+          self.line(name.to_owned(), head);
+        } else {
+          self.line(format!("{} {}", name, args.iter().map(|x| x.to_lingo(dot)).join(", ")), head);
+        }
+      }
+      Stmt::SoundCmd(command, args) => {
+        if args.is_empty() {
+          self.line(format!("sound {}", command), head);
+        } else {
+          self.line(format!("sound {} {}", command, args.iter().map(|x| x.to_lingo(dot)).join(", ")), head);
+        }
+      }
+      Stmt::When(event, script) => self.line(format!("when {} then {}", event, script), head),
+      Stmt::ChunkHilite(chunk) => self.line(format!("hilite {}", chunk.to_lingo(dot)), head),
+      Stmt::ChunkDelete(chunk) => self.line(format!("delete {}", chunk.to_lingo(dot)), head),
+      Stmt::Return(None) => self.line("return".to_owned(), head),
+      Stmt::Return(Some(value)) => self.line(format!("return {}", value.to_lingo(dot)), head),
+      Stmt::Exit => self.line("exit".to_owned(), head),
+      Stmt::ExitRepeat => self.line("exit repeat".to_owned(), head),
+      Stmt::NextRepeat => self.line("next repeat".to_owned(), head),
+      Stmt::If(..) => {
+        let mut end_owners = self.write_if(id, "if", vec![]);
+        end_owners.push((id, StmtPart::End));
+        self.line("end if".to_owned(), end_owners);
+      }
+      Stmt::RepeatWhile(condition, block) => {
+        self.line(format!("repeat while {}", condition.to_lingo(dot)), head);
+        self.write_indented_block(*block);
+        self.line("end repeat".to_owned(), end);
+      }
+      Stmt::RepeatWithIn(var, list, block) => {
+        self.line(format!("repeat with {} in {}", var, list.to_lingo(dot)), head);
+        self.write_indented_block(*block);
+        self.line("end repeat".to_owned(), end);
+      }
+      Stmt::RepeatWithTo(var, start, end_value, up, block) => {
+        let direction = if *up { "to" } else { "down to" };
+        self.line(format!("repeat with {} = {} {} {}", var, start.to_lingo(dot), direction, end_value.to_lingo(dot)), head);
+        self.write_indented_block(*block);
+        self.line("end repeat".to_owned(), end);
+      }
+      Stmt::Case(value, labels, otherwise) => {
+        self.line(format!("case {} of", value.to_lingo(dot)), head);
+        self.indent += 1;
+        for (label_index, label) in labels.iter().enumerate() {
+          let values = label.values.iter().map(|x| x.to_lingo(dot)).join(", ");
+          self.line(format!("{}:", values), vec![(id, StmtPart::Label(label_index))]);
+          if let Some(block) = label.block {
+            self.write_indented_block(block);
+          }
+        }
+        if let Some(otherwise) = otherwise {
+          self.line("otherwise:".to_owned(), vec![]);
+          self.write_indented_block(*otherwise);
+        }
+        self.indent -= 1;
+        self.line("end case".to_owned(), end);
+      }
+      Stmt::Tell(window, block) => {
+        self.line(format!("tell {}", window.to_lingo(dot)), head);
+        self.write_indented_block(*block);
+        self.line("end tell".to_owned(), end);
+      }
+    }
+  }
+
+  /// Writes an if statement without its `end if`, continuing `else if` chains on the same level.
+  /// Returns the nested ifs that share the `end if`.
+  fn write_if(&mut self, id: StmtId, keyword: &str, owners: Vec<BytecodeOwner>) -> Vec<BytecodeOwner> {
+    let (condition, then_block, else_block) = match &self.ast.stmts[id].stmt {
+      Stmt::If(condition, then_block, else_block) => (condition, *then_block, *else_block),
+      _ => return vec![],
+    };
+    let mut owners = owners;
+    owners.push((id, StmtPart::Head));
+    self.line(format!("{} {} then", keyword, condition.to_lingo(self.dot)), owners);
+    self.write_indented_block(then_block);
+
+    if let Some(else_block) = else_block {
+      let else_stmts = &self.ast.blocks[else_block].stmts;
+      match else_stmts.as_slice() {
+        [nested_id] if matches!(self.ast.stmts[*nested_id].stmt, Stmt::If(..)) => {
+          let nested_id = *nested_id;
+          let mut end_owners = self.write_if(nested_id, "else if", vec![(id, StmtPart::Else)]);
+          end_owners.push((nested_id, StmtPart::End));
+          return end_owners;
+        }
+        _ => {
+          self.line("else".to_owned(), vec![(id, StmtPart::Else)]);
+          self.write_indented_block(else_block);
+        }
+      }
+    }
+    vec![]
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use fxhash::FxHashMap;
+
+  use super::*;
+  use crate::director::{chunks::handler::Bytecode, lingo::opcode::OpCode};
+
+  const NAMES: [&str; 8] = ["test", "x", "alert", "i", "s", "y", "width", "foo"];
+  const X: i64 = 1;
+  const ALERT: i64 = 2;
+
+  /// Builds a handler `test` from opcodes and operands. Jump operands are given as the index of the
+  /// target bytecode and converted to offsets.
+  fn handler(args: &[u16], locals: &[u16], ops: &[(OpCode, i64)]) -> HandlerDef {
+    let pos = |index: usize| index * 2;
+    let bytecode_array = ops.iter().enumerate().map(|(index, (opcode, obj))| {
+      let obj = match opcode {
+        OpCode::Jmp | OpCode::JmpIfZ => (pos(*obj as usize) - pos(index)) as i64,
+        OpCode::EndRepeat => (pos(index) - pos(*obj as usize)) as i64,
+        _ => *obj,
+      };
+      Bytecode::new(*opcode, obj, pos(index))
+    }).collect_vec();
+    let bytecode_index_map: FxHashMap<usize, usize> = (0..ops.len()).map(|index| (pos(index), index)).collect();
+    HandlerDef {
+      name_id: 0,
+      bytecode_array,
+      bytecode_index_map,
+      argument_name_ids: args.to_vec(),
+      local_name_ids: locals.to_vec(),
+      global_name_ids: vec![],
+    }
+  }
+
+  fn decompile(handler: HandlerDef, dir_version: u16) -> (String, Vec<usize>) {
+    let names = NAMES.iter().map(|x| x.to_string()).collect_vec();
+    let script = ScriptChunk { literals: vec![], handlers: vec![handler], property_name_ids: vec![] };
+    let ctx = DecompilerContext { names: &names, script: &script, dir_version, capital_x: true };
+    let decompiled = decompile_handler(&ctx, &script.handlers[0]);
+    let text = decompiled.lines.iter().map(|x| x.text.as_str()).join("\n");
+    (text, decompiled.bytecode_lines)
+  }
+
+  fn alert(value: (OpCode, i64)) -> [(OpCode, i64); 3] {
+    [value, (OpCode::PushArgListNoRet, 1), (OpCode::ExtCall, ALERT)]
+  }
+
+  #[test]
+  fn decompiles_if_else() {
+    let ops = [
+      vec![(OpCode::GetParam, 0), (OpCode::PushInt8, 1), (OpCode::Gt, 0), (OpCode::JmpIfZ, 8)],
+      alert((OpCode::PushInt8, 1)).to_vec(),
+      vec![(OpCode::Jmp, 11)],
+      alert((OpCode::PushInt8, 2)).to_vec(),
+      vec![(OpCode::Ret, 0)],
+    ].concat();
+    let (text, lines) = decompile(handler(&[1], &[], &ops), 700);
+    assert_eq!(text, [
+      "on test x",
+      "  if x > 1 then",
+      "    alert 1",
+      "  else",
+      "    alert 2",
+      "  end if",
+      "end",
+    ].join("\n"));
+    assert_eq!(lines, vec![1, 1, 1, 1, 2, 2, 2, 3, 4, 4, 4, 6]);
+  }
+
+  #[test]
+  fn decompiles_repeat_loops() {
+    let ops = [
+      // repeat with i = 1 to 3
+      vec![(OpCode::PushInt8, 1), (OpCode::SetLocal, 0), (OpCode::GetLocal, 0), (OpCode::PushInt8, 3), (OpCode::LtEq, 0), (OpCode::JmpIfZ, 14)],
+      alert((OpCode::GetLocal, 0)).to_vec(),
+      vec![(OpCode::PushInt8, 1), (OpCode::GetLocal, 0), (OpCode::Add, 0), (OpCode::SetLocal, 0), (OpCode::EndRepeat, 2)],
+      // repeat while x < 3
+      vec![(OpCode::GetParam, 0), (OpCode::PushInt8, 3), (OpCode::Lt, 0), (OpCode::JmpIfZ, 22)],
+      alert((OpCode::GetParam, 0)).to_vec(),
+      vec![(OpCode::EndRepeat, 14), (OpCode::Ret, 0)],
+    ].concat();
+    let (text, lines) = decompile(handler(&[1], &[3], &ops), 700);
+    assert_eq!(text, [
+      "on test x",
+      "  repeat with i = 1 to 3",
+      "    alert i",
+      "  end repeat",
+      "  repeat while x < 3",
+      "    alert x",
+      "  end repeat",
+      "end",
+    ].join("\n"));
+    assert_eq!(lines, vec![1, 1, 1, 1, 1, 1, 2, 2, 2, 3, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 6, 7]);
+  }
+
+  #[test]
+  fn decompiles_case() {
+    let ops = [
+      vec![(OpCode::GetParam, 0), (OpCode::Peek, 0), (OpCode::PushInt8, 1), (OpCode::Eq, 0), (OpCode::JmpIfZ, 9)],
+      alert((OpCode::PushInt8, 1)).to_vec(),
+      vec![(OpCode::Jmp, 24)],
+      vec![(OpCode::Peek, 0), (OpCode::PushInt8, 2), (OpCode::NtEq, 0), (OpCode::JmpIfZ, 17)],
+      vec![(OpCode::Peek, 0), (OpCode::PushInt8, 3), (OpCode::Eq, 0), (OpCode::JmpIfZ, 21)],
+      alert((OpCode::PushInt8, 2)).to_vec(),
+      vec![(OpCode::Jmp, 24)],
+      alert((OpCode::PushInt8, 4)).to_vec(),
+      vec![(OpCode::Pop, 1), (OpCode::Ret, 0)],
+    ].concat();
+    let (text, lines) = decompile(handler(&[1], &[], &ops), 700);
+    assert_eq!(text, [
+      "on test x",
+      "  case x of",
+      "    1:",
+      "      alert 1",
+      "    2, 3:",
+      "      alert 2",
+      "    otherwise:",
+      "      alert 4",
+      "  end case",
+      "end",
+    ].join("\n"));
+    // The jumps out of the labels belong to the end of the case
+    assert_eq!(lines, vec![1, 2, 2, 2, 2, 3, 3, 3, 8, 4, 4, 4, 4, 4, 4, 4, 4, 5, 5, 5, 8, 7, 7, 7, 9, 9]);
+  }
+
+  #[test]
+  fn decompiles_chunk_expressions() {
+    let ops = [
+      vec![(OpCode::PushInt8, 1), (OpCode::PushInt8, 3), (OpCode::PushInt8, 2)],
+      vec![(OpCode::PushZero, 0); 5],
+      vec![(OpCode::GetParam, 0), (OpCode::GetChunk, 0), (OpCode::PushArgListNoRet, 1), (OpCode::ExtCall, ALERT), (OpCode::Ret, 0)],
+    ].concat();
+    let (text, lines) = decompile(handler(&[4], &[], &ops), 700);
+    assert_eq!(text, "on test s\n  alert char 1 to 3 of word 2 of s\nend");
+    assert_eq!(lines, vec![1; 12].into_iter().chain([2]).collect_vec());
+  }
+
+  #[test]
+  fn decompiles_dot_syntax_from_director_7() {
+    let ops = vec![
+      (OpCode::GetParam, 0), (OpCode::PushInt8, 5), (OpCode::SetObjProp, 5),
+      (OpCode::GetParam, 0), (OpCode::GetObjProp, 6), (OpCode::PushArgListNoRet, 1), (OpCode::ExtCall, ALERT),
+      (OpCode::GetParam, 0), (OpCode::PushInt8, 1), (OpCode::PushArgListNoRet, 2), (OpCode::ObjCall, 7),
+      (OpCode::Ret, 0),
+    ];
+    let (text, lines) = decompile(handler(&[X as u16], &[], &ops), 700);
+    assert_eq!(text, "on test x\n  x.y = 5\n  alert x.width\n  x.foo(1)\nend");
+    assert_eq!(lines, vec![1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4]);
+
+    let (text, _) = decompile(handler(&[X as u16], &[], &ops), 600);
+    assert_eq!(text, "on test x\n  set the y of x to 5\n  alert the width of x\n  foo(x, 1)\nend");
+  }
+}
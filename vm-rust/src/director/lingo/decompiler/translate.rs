@@ -0,0 +1,963 @@
+use std::{collections::HashMap, convert::TryFrom};
+
+use crate::director::{
+  chunks::handler::HandlerDef,
+  file::get_variable_multiplier,
+  lingo::{
+    constants::{
+      ANIM2_PROP_NAMES, ANIM_PROP_NAMES, MEMBER_PROP_NAMES, MENU_ITEM_PROP_NAMES, MENU_PROP_NAMES, MOVIE_PROP_NAMES,
+      SOUND_PROP_NAMES, SPRITE_PROP_NAMES,
+    },
+    datum::{Datum, StringChunkType},
+    opcode::OpCode,
+  },
+};
+
+use super::{
+  ast::{Ast, BlockId, BlockKind, CaseExpect, CaseLabel, Expr, Stmt, StmtId},
+  DecompilerContext,
+};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Tag {
+  None,
+  Skip,
+  RepeatWhile,
+  RepeatWithIn,
+  RepeatWithTo,
+  RepeatWithDownTo,
+  NextRepeatTarget,
+  EndCase,
+}
+
+/// Which line of a statement a bytecode belongs to.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StmtPart {
+  Head,
+  Label(usize),
+  Else,
+  End,
+}
+
+pub type BytecodeOwner = (StmtId, StmtPart);
+
+/// Rebuilds the statements of a handler from its bytecode by simulating the stack.
+pub struct Translator<'a> {
+  ctx: &'a DecompilerContext<'a>,
+  handler: &'a HandlerDef,
+  multiplier: u32,
+  tags: Vec<Tag>,
+  owner_loops: Vec<Option<usize>>,
+  stack: Vec<Expr>,
+  pub ast: Ast,
+  current_block: BlockId,
+  /// First bytecode index that is not yet attributed to a statement.
+  pending_index: usize,
+  pub owners: Vec<Option<BytecodeOwner>>,
+}
+
+impl<'a> Translator<'a> {
+  pub fn new(ctx: &'a DecompilerContext<'a>, handler: &'a HandlerDef) -> Translator<'a> {
+    let len = handler.bytecode_array.len();
+    Translator {
+      ctx,
+      handler,
+      multiplier: get_variable_multiplier(ctx.capital_x, ctx.dir_version),
+      tags: vec![Tag::None; len],
+      owner_loops: vec![None; len],
+      stack: vec![],
+      ast: Ast::new(),
+      current_block: 0,
+      pending_index: 0,
+      owners: vec![None; len],
+    }
+  }
+
+  pub fn translate(&mut self) {
+    self.tag_loops();
+
+    let len = self.handler.bytecode_array.len();
+    let mut index = 0;
+    while index < len {
+      let pos = self.handler.bytecode_array[index].pos;
+      while self.current_block != 0 && self.ast.blocks[self.current_block].end_pos == Some(pos) {
+        self.exit_block(index);
+      }
+      index += self.translate_bytecode(index);
+    }
+    while self.current_block != 0 {
+      self.exit_block(len);
+    }
+  }
+
+  fn bytecode_index(&self, pos: usize) -> Option<usize> {
+    self.handler.bytecode_index_map.get(&pos).copied()
+  }
+
+  fn opcode_at(&self, index: usize) -> Option<OpCode> {
+    self.handler.bytecode_array.get(index).map(|x| x.opcode)
+  }
+
+  fn is_op(&self, index: usize, opcode: OpCode, obj: Option<i64>) -> bool {
+    match self.handler.bytecode_array.get(index) {
+      Some(bytecode) => bytecode.opcode == opcode && obj.is_none_or(|obj| bytecode.obj == obj),
+      None => false,
+    }
+  }
+
+  fn name(&self, id: i64) -> String {
+    usize::try_from(id)
+      .ok()
+      .and_then(|id| self.ctx.names.get(id))
+      .cloned()
+      .unwrap_or_else(|| format!("UNKNOWN_NAME_{}", id))
+  }
+
+  fn var_name(&self, name_ids: &[u16], obj: i64, kind: &str) -> String {
+    let index = obj as usize / self.multiplier as usize;
+    match name_ids.get(index) {
+      Some(name_id) => self.name(*name_id as i64),
+      None => format!("UNKNOWN_{}_{}", kind, index),
+    }
+  }
+
+  fn local_name(&self, obj: i64) -> String {
+    self.var_name(&self.handler.local_name_ids, obj, "LOCAL")
+  }
+
+  fn arg_name(&self, obj: i64) -> String {
+    self.var_name(&self.handler.argument_name_ids, obj, "PARAM")
+  }
+
+  fn var_name_from_set(&self, index: usize) -> String {
+    match self.handler.bytecode_array.get(index) {
+      Some(bytecode) => match bytecode.opcode {
+        OpCode::SetGlobal | OpCode::SetGlobal2 | OpCode::SetProp => self.name(bytecode.obj),
+        OpCode::SetParam => self.arg_name(bytecode.obj),
+        OpCode::SetLocal => self.local_name(bytecode.obj),
+        _ => "ERROR".to_owned(),
+      },
+      None => "ERROR".to_owned(),
+    }
+  }
+
+  fn pop(&mut self) -> Expr {
+    self.stack.pop().unwrap_or_else(|| Expr::Error("stack underflow".to_owned()))
+  }
+
+  fn pop_args(&mut self) -> (Vec<Expr>, bool) {
+    match self.pop() {
+      Expr::ArgList(args, returns) => (args, returns),
+      other => (vec![other], true),
+    }
+  }
+
+  // Loop recognition
+
+  fn tag_loops(&mut self) {
+    let bytecodes = &self.handler.bytecode_array;
+    for start_index in 0..bytecodes.len() {
+      // All loops begin with jmpifz and end with an endrepeat jumping back before it.
+      let jmpifz = &bytecodes[start_index];
+      if jmpifz.opcode != OpCode::JmpIfZ {
+        continue;
+      }
+      let end_index = match self.bytecode_index(jmpifz.pos + jmpifz.obj as usize) {
+        Some(end_index) if end_index > 0 => end_index,
+        _ => continue,
+      };
+      let end_repeat = &bytecodes[end_index - 1];
+      if end_repeat.opcode != OpCode::EndRepeat || end_repeat.pos.checked_sub(end_repeat.obj as usize).is_none_or(|x| x > jmpifz.pos) {
+        continue;
+      }
+
+      let loop_type = self.identify_loop(start_index, end_index);
+      self.tags[start_index] = loop_type;
+      match loop_type {
+        Tag::RepeatWithIn => {
+          for i in (start_index - 7)..start_index {
+            self.tags[i] = Tag::Skip;
+          }
+          for i in (start_index + 1)..=(start_index + 5) {
+            self.tags[i] = Tag::Skip;
+          }
+          self.tags[end_index - 3] = Tag::NextRepeatTarget; // pushint8 1
+          self.owner_loops[end_index - 3] = Some(start_index);
+          self.tags[end_index - 2] = Tag::Skip; // add
+          self.tags[end_index - 1] = Tag::Skip; // endrepeat
+          self.owner_loops[end_index - 1] = Some(start_index);
+          self.tags[end_index] = Tag::Skip; // pop 3
+        }
+        Tag::RepeatWithTo | Tag::RepeatWithDownTo => {
+          // identify_loop only reports these loops when the condition start is known
+          let Some(condition_start_index) = self.loop_condition_start(end_index) else {
+            self.tags[start_index] = Tag::RepeatWhile;
+            continue;
+          };
+          self.tags[condition_start_index - 1] = Tag::Skip; // set
+          self.tags[condition_start_index] = Tag::Skip; // get
+          self.tags[start_index - 1] = Tag::Skip; // lteq / gteq
+          self.tags[end_index - 5] = Tag::NextRepeatTarget; // pushint8 1 / pushint8 -1
+          self.owner_loops[end_index - 5] = Some(start_index);
+          self.tags[end_index - 4] = Tag::Skip; // get
+          self.tags[end_index - 3] = Tag::Skip; // add
+          self.tags[end_index - 2] = Tag::Skip; // set
+          self.tags[end_index - 1] = Tag::Skip; // endrepeat
+          self.owner_loops[end_index - 1] = Some(start_index);
+        }
+        _ => {
+          self.tags[end_index - 1] = Tag::NextRepeatTarget; // endrepeat
+          self.owner_loops[end_index - 1] = Some(start_index);
+        }
+      }
+    }
+  }
+
+  /// Index of the first bytecode the endrepeat before `end_index` jumps back to.
+  fn loop_condition_start(&self, end_index: usize) -> Option<usize> {
+    let end_repeat = &self.handler.bytecode_array[end_index - 1];
+    self.bytecode_index(end_repeat.pos.checked_sub(end_repeat.obj as usize)?)
+  }
+
+  fn is_repeat_with_in(&self, start_index: usize, end_index: usize) -> bool {
+    if start_index < 7 || end_index < 3 {
+      return false;
+    }
+    let is_call = |index: usize, name: &str| self.is_op(index, OpCode::ExtCall, None) && self.name(self.handler.bytecode_array[index].obj) == name;
+    let is_set = matches!(
+      self.opcode_at(start_index + 5),
+      Some(OpCode::SetGlobal | OpCode::SetProp | OpCode::SetParam | OpCode::SetLocal)
+    );
+    self.is_op(start_index - 7, OpCode::Peek, Some(0))
+      && self.is_op(start_index - 6, OpCode::PushArgList, Some(1))
+      && is_call(start_index - 5, "count")
+      && self.is_op(start_index - 4, OpCode::PushInt8, Some(1))
+      && self.is_op(start_index - 3, OpCode::Peek, Some(0))
+      && self.is_op(start_index - 2, OpCode::Peek, Some(2))
+      && self.is_op(start_index - 1, OpCode::LtEq, None)
+      && self.is_op(start_index + 1, OpCode::Peek, Some(2))
+      && self.is_op(start_index + 2, OpCode::Peek, Some(1))
+      && self.is_op(start_index + 3, OpCode::PushArgList, Some(2))
+      && is_call(start_index + 4, "getAt")
+      && is_set
+      && self.is_op(end_index - 3, OpCode::PushInt8, Some(1))
+      && self.is_op(end_index - 2, OpCode::Add, None)
+      && self.is_op(end_index, OpCode::Pop, Some(3))
+  }
+
+  fn identify_loop(&self, start_index: usize, end_index: usize) -> Tag {
+    if self.is_repeat_with_in(start_index, end_index) {
+      return Tag::RepeatWithIn;
+    }
+    if start_index < 1 || end_index < 5 {
+      return Tag::RepeatWhile;
+    }
+    let up = match self.opcode_at(start_index - 1) {
+      Some(OpCode::LtEq) => true,
+      Some(OpCode::GtEq) => false,
+      _ => return Tag::RepeatWhile,
+    };
+    let condition_start_index = match self.loop_condition_start(end_index) {
+      Some(index) if index >= 1 => index,
+      _ => return Tag::RepeatWhile,
+    };
+    let set = &self.handler.bytecode_array[condition_start_index - 1];
+    let get_op = match set.opcode {
+      OpCode::SetGlobal => OpCode::GetGlobal,
+      OpCode::SetGlobal2 => OpCode::GetGlobal2,
+      OpCode::SetProp => OpCode::GetProp,
+      OpCode::SetParam => OpCode::GetParam,
+      OpCode::SetLocal => OpCode::GetLocal,
+      _ => return Tag::RepeatWhile,
+    };
+    let step = if up { 1 } else { -1 };
+    let is_loop = self.is_op(condition_start_index, get_op, Some(set.obj))
+      && self.is_op(end_index - 5, OpCode::PushInt8, Some(step))
+      && self.is_op(end_index - 4, get_op, Some(set.obj))
+      && self.is_op(end_index - 3, OpCode::Add, None)
+      && self.is_op(end_index - 2, set.opcode, Some(set.obj));
+    match (is_loop, up) {
+      (true, true) => Tag::RepeatWithTo,
+      (true, false) => Tag::RepeatWithDownTo,
+      _ => Tag::RepeatWhile,
+    }
+  }
+
+  // Blocks and statements
+
+  /// Attributes the bytecodes from the pending index up to `last_index` to `owner`.
+  fn claim(&mut self, owner: BytecodeOwner, last_index: usize) {
+    let end = (last_index + 1).min(self.owners.len());
+    for index in self.pending_index..end {
+      self.owners[index] = Some(owner);
+    }
+    self.pending_index = self.pending_index.max(end);
+  }
+
+  fn add_stmt(&mut self, stmt: Stmt, index: usize, last_index: usize) -> StmtId {
+    let id = self.ast.add_stmt(self.current_block, stmt, index);
+    self.claim((id, StmtPart::Head), last_index);
+    id
+  }
+
+  fn exit_block(&mut self, next_index: usize) {
+    let block_id = self.current_block;
+    let kind = self.ast.blocks[block_id].kind;
+    let stmt_id = self.ast.blocks[block_id].parent.unwrap();
+    let else_block = match self.ast.stmts[stmt_id].stmt {
+      Stmt::If(_, _, else_block) if kind == BlockKind::Then => else_block,
+      _ => None,
+    };
+    if next_index > 0 {
+      let part = if else_block.is_some() { StmtPart::Else } else { StmtPart::End };
+      self.claim((stmt_id, part), next_index - 1);
+    }
+    self.current_block = self.ast.stmts[stmt_id].parent;
+
+    if let Some(else_block) = else_block {
+      self.current_block = else_block;
+    } else if kind == BlockKind::CaseLabel {
+      let expect = match &self.ast.stmts[stmt_id].stmt {
+        Stmt::Case(_, labels, _) => labels.last().map(|x| x.expect),
+        _ => None,
+      };
+      match expect {
+        Some(CaseExpect::Otherwise) => {
+          self.ast.blocks[self.current_block].current_case = None;
+          let end_pos = self.ast.stmts[stmt_id].end_pos;
+          let otherwise = self.ast.add_block(BlockKind::Otherwise, stmt_id, end_pos);
+          if let Stmt::Case(_, _, otherwise_block) = &mut self.ast.stmts[stmt_id].stmt {
+            *otherwise_block = Some(otherwise);
+          }
+          self.current_block = otherwise;
+        }
+        Some(CaseExpect::End) => {
+          self.ast.blocks[self.current_block].current_case = None;
+        }
+        _ => {}
+      }
+    }
+  }
+
+  /// Translates the bytecode at `index` and returns the number of bytecodes consumed.
+  fn translate_bytecode(&mut self, index: usize) -> usize {
+    if matches!(self.tags[index], Tag::Skip | Tag::NextRepeatTarget) {
+      return 1;
+    }
+    let bytecode = self.handler.bytecode_array[index].clone();
+    let dir_version = self.ctx.dir_version;
+    match bytecode.opcode {
+      OpCode::Ret | OpCode::RetFactory => {
+        if index != self.handler.bytecode_array.len() - 1 {
+          self.add_stmt(Stmt::Exit, index, index);
+        }
+      }
+      OpCode::PushZero => self.stack.push(Expr::Int(0)),
+      OpCode::Mul
+      | OpCode::Add
+      | OpCode::Sub
+      | OpCode::Div
+      | OpCode::Mod
+      | OpCode::JoinStr
+      | OpCode::JoinPadStr
+      | OpCode::Lt
+      | OpCode::LtEq
+      | OpCode::NtEq
+      | OpCode::Eq
+      | OpCode::Gt
+      | OpCode::GtEq
+      | OpCode::And
+      | OpCode::Or
+      | OpCode::ContainsStr
+      | OpCode::Contains0Str => {
+        let right = self.pop();
+        let left = self.pop();
+        self.stack.push(Expr::Binary(bytecode.opcode, Box::new(left), Box::new(right)));
+      }
+      OpCode::Inv => {
+        let value = self.pop();
+        self.stack.push(Expr::Negate(Box::new(value)));
+      }
+      OpCode::Not => {
+        let value = self.pop();
+        self.stack.push(Expr::Not(Box::new(value)));
+      }
+      OpCode::GetChunk => {
+        let string = self.pop();
+        let chunk = self.read_chunk_ref(string);
+        self.stack.push(chunk);
+      }
+      OpCode::HiliteChunk => {
+        let cast_id = if dir_version >= 500 { Some(Box::new(self.pop())) } else { None };
+        let field_id = self.pop();
+        let field = Expr::Member("field".to_owned(), Box::new(field_id), cast_id);
+        let chunk = self.read_chunk_ref(field);
+        self.add_stmt(Stmt::ChunkHilite(chunk), index, index);
+      }
+      OpCode::OntoSpr | OpCode::IntoSpr => {
+        let second = Box::new(self.pop());
+        let first = Box::new(self.pop());
+        self.stack.push(if bytecode.opcode == OpCode::OntoSpr {
+          Expr::SpriteIntersects(first, second)
+        } else {
+          Expr::SpriteWithin(first, second)
+        });
+      }
+      OpCode::GetField => {
+        let cast_id = if dir_version >= 500 { Some(Box::new(self.pop())) } else { None };
+        let field_id = self.pop();
+        self.stack.push(Expr::Member("field".to_owned(), Box::new(field_id), cast_id));
+      }
+      OpCode::StartTell => {
+        let window = self.pop();
+        let stmt_id = self.add_stmt(Stmt::Comment(String::new()), index, index);
+        let block = self.ast.add_block(BlockKind::Tell, stmt_id, None);
+        self.ast.stmts[stmt_id].stmt = Stmt::Tell(window, block);
+        self.current_block = block;
+      }
+      OpCode::EndTell => {
+        if self.ast.blocks[self.current_block].kind == BlockKind::Tell {
+          self.exit_block(index + 1);
+        }
+      }
+      OpCode::PushList => {
+        let (items, _) = self.pop_args();
+        self.stack.push(Expr::List(items));
+      }
+      OpCode::PushPropList => {
+        let (items, _) = self.pop_args();
+        let pairs = items.chunks(2).map(|pair| (pair[0].clone(), pair.get(1).cloned().unwrap_or(Expr::Int(0)))).collect();
+        self.stack.push(Expr::PropList(pairs));
+      }
+      OpCode::Swap => {
+        let len = self.stack.len();
+        if len >= 2 {
+          self.stack.swap(len - 1, len - 2);
+        }
+      }
+      OpCode::PushInt8 | OpCode::PushInt16 | OpCode::PushInt32 => self.stack.push(Expr::Int(bytecode.obj as i32)),
+      OpCode::PushFloat32 => self.stack.push(Expr::Float(f32::from_be_bytes((bytecode.obj as i32).to_be_bytes()))),
+      OpCode::PushArgListNoRet | OpCode::PushArgList => {
+        let count = (bytecode.obj as usize).min(self.stack.len());
+        let args = self.stack.split_off(self.stack.len() - count);
+        self.stack.push(Expr::ArgList(args, bytecode.opcode == OpCode::PushArgList));
+      }
+      OpCode::PushCons => {
+        let literal_id = bytecode.obj as usize / self.multiplier as usize;
+        let literal = match self.ctx.script.literals.get(literal_id) {
+          Some(Datum::Int(value)) => Expr::Int(*value),
+          Some(Datum::Float(value)) => Expr::Float(*value),
+          Some(Datum::String(value)) => Expr::String(value.to_owned()),
+          _ => Expr::Error(format!("literal {}", literal_id)),
+        };
+        self.stack.push(literal);
+      }
+      OpCode::PushSymb => self.stack.push(Expr::Symbol(self.name(bytecode.obj))),
+      OpCode::PushVarRef | OpCode::GetGlobal | OpCode::GetGlobal2 | OpCode::GetProp | OpCode::GetTopLevelProp => {
+        self.stack.push(Expr::Var(self.name(bytecode.obj)))
+      }
+      OpCode::GetParam => self.stack.push(Expr::Var(self.arg_name(bytecode.obj))),
+      OpCode::GetLocal => self.stack.push(Expr::Var(self.local_name(bytecode.obj))),
+      OpCode::SetGlobal | OpCode::SetGlobal2 | OpCode::SetProp | OpCode::SetParam | OpCode::SetLocal => {
+        let name = self.var_name_from_set(index);
+        let value = self.pop();
+        self.add_stmt(Stmt::Assign(Expr::Var(name), value, false), index, index);
+      }
+      OpCode::Jmp => return self.translate_jmp(index),
+      OpCode::EndRepeat => {
+        self.add_stmt(Stmt::Comment("ERROR: Stray endrepeat".to_owned()), index, index);
+      }
+      OpCode::JmpIfZ => self.translate_jmp_if_zero(index),
+      OpCode::LocalCall => {
+        let (args, returns) = self.pop_args();
+        let name = match self.ctx.script.handlers.get(bytecode.obj as usize) {
+          Some(handler) => self.name(handler.name_id as i64),
+          None => format!("UNKNOWN_HANDLER_{}", bytecode.obj),
+        };
+        self.push_call(Expr::Call(name, args), returns, index);
+      }
+      OpCode::ExtCall | OpCode::TellCall => {
+        let name = self.name(bytecode.obj);
+        let (mut args, returns) = self.pop_args();
+        if !returns && name == "return" && args.len() <= 1 {
+          // The `ret` following a return statement is part of it.
+          let last_index = if self.is_op(index + 1, OpCode::Ret, None) { index + 1 } else { index };
+          self.add_stmt(Stmt::Return(args.pop()), index, last_index);
+          return last_index - index + 1;
+        }
+        if !returns && name == "sound" && matches!(args.first(), Some(Expr::Symbol(_))) {
+          let command = match args.remove(0) {
+            Expr::Symbol(command) => command,
+            _ => unreachable!(),
+          };
+          self.add_stmt(Stmt::SoundCmd(command, args), index, index);
+        } else if !returns {
+          self.add_stmt(Stmt::Call(name, args), index, index);
+        } else {
+          self.stack.push(Expr::Call(name, args));
+        }
+      }
+      OpCode::ObjCallV4 => {
+        let obj = self.read_var(bytecode.obj);
+        let (mut args, returns) = self.pop_args();
+        if let Some(Expr::Symbol(name)) = args.first() {
+          args[0] = Expr::Var(name.to_owned());
+        }
+        self.push_call(Expr::ObjCallV4(Box::new(obj), args), returns, index);
+      }
+      OpCode::Put => {
+        let put_type = ((bytecode.obj >> 4) & 0xf) as u8;
+        let var = self.read_var(bytecode.obj & 0xf);
+        let value = self.pop();
+        self.add_stmt(Stmt::Put(put_type, var, value), index, index);
+      }
+      OpCode::PutChunk => {
+        let put_type = ((bytecode.obj >> 4) & 0xf) as u8;
+        let var = self.read_var(bytecode.obj & 0xf);
+        let chunk = self.read_chunk_ref(var);
+        let value = self.pop();
+        self.add_stmt(Stmt::Put(put_type, chunk, value), index, index);
+      }
+      OpCode::DeleteChunk => {
+        let var = self.read_var(bytecode.obj);
+        let chunk = self.read_chunk_ref(var);
+        self.add_stmt(Stmt::ChunkDelete(chunk), index, index);
+      }
+      OpCode::Get => {
+        let prop_id = self.pop();
+        let prop = match prop_id.int_value() {
+          Some(prop_id) => self.read_v4_property(bytecode.obj, prop_id),
+          None => Expr::Error("property id".to_owned()),
+        };
+        self.stack.push(prop);
+      }
+      OpCode::Set => {
+        let prop_id = self.pop();
+        let value = self.pop();
+        let prop_id = prop_id.int_value();
+        let when = match (&value, prop_id) {
+          // Setting an event script to a string that looks like code is a `when ... then` statement.
+          (Expr::String(script), Some(prop_id @ 1..=5)) if bytecode.obj == 0 && (script.starts_with(' ') || script.contains('\r')) => {
+            let event = ["mouseDown", "mouseUp", "keyDown", "keyUp", "timeOut"][prop_id as usize - 1];
+            Some(Stmt::When(event.to_owned(), script.trim().to_owned()))
+          }
+          _ => None,
+        };
+        let stmt = match (when, prop_id) {
+          (Some(when), _) => when,
+          (None, Some(prop_id)) => Stmt::Assign(self.read_v4_property(bytecode.obj, prop_id), value, true),
+          (None, None) => Stmt::Comment("ERROR: Unknown property id".to_owned()),
+        };
+        self.add_stmt(stmt, index, index);
+      }
+      OpCode::GetMovieProp => self.stack.push(Expr::The(self.name(bytecode.obj))),
+      OpCode::SetMovieProp => {
+        let value = self.pop();
+        self.add_stmt(Stmt::Assign(Expr::The(self.name(bytecode.obj)), value, false), index, index);
+      }
+      OpCode::GetObjProp | OpCode::GetChainedProp => {
+        let obj = self.pop();
+        self.stack.push(Expr::ObjProp(Box::new(obj), self.name(bytecode.obj)));
+      }
+      OpCode::SetObjProp => {
+        let value = self.pop();
+        let obj = self.pop();
+        self.add_stmt(Stmt::Assign(Expr::ObjProp(Box::new(obj), self.name(bytecode.obj)), value, false), index, index);
+      }
+      OpCode::Peek => return self.translate_case_label(index),
+      OpCode::Pop => {
+        if self.tags[index] != Tag::EndCase && bytecode.obj == 1 && self.stack.len() == 1 {
+          // An unused value left on the stack is a case statement without labels.
+          let value = self.pop();
+          self.add_stmt(Stmt::Case(value, vec![], None), index, index);
+        } else {
+          // Otherwise this pops the value of a case statement before a return.
+          let count = (bytecode.obj as usize).min(self.stack.len());
+          self.stack.truncate(self.stack.len() - count);
+        }
+      }
+      OpCode::TheBuiltin => {
+        self.pop_args();
+        self.stack.push(Expr::The(self.name(bytecode.obj)));
+      }
+      OpCode::ObjCall => self.translate_obj_call(index),
+      OpCode::PushChunkVarRef => {
+        let var = self.read_var(bytecode.obj);
+        self.stack.push(var);
+      }
+      OpCode::NewObj => {
+        let (args, _) = self.pop_args();
+        self.stack.push(Expr::NewObj(self.name(bytecode.obj), args));
+      }
+      OpCode::CallJavaScript | OpCode::Invalid => {
+        self.add_stmt(Stmt::Comment("ERROR: Unsupported bytecode".to_owned()), index, index);
+      }
+    }
+    1
+  }
+
+  fn push_call(&mut self, call: Expr, returns: bool, index: usize) {
+    if returns {
+      self.stack.push(call);
+    } else {
+      self.add_stmt(Stmt::Expr(call), index, index);
+    }
+  }
+
+  fn translate_jmp_if_zero(&mut self, index: usize) {
+    let bytecode = &self.handler.bytecode_array[index];
+    let end_pos = bytecode.pos + bytecode.obj as usize;
+    let tag = self.tags[index];
+    let stmt_id = self.add_stmt(Stmt::Comment(String::new()), index, index);
+    let kind = if tag == Tag::None { BlockKind::Then } else { BlockKind::Loop };
+    let block = self.ast.add_block(kind, stmt_id, Some(end_pos));
+    let stmt = match tag {
+      Tag::RepeatWithIn => {
+        let list = self.pop();
+        Stmt::RepeatWithIn(self.var_name_from_set(index + 5), list, block)
+      }
+      Tag::RepeatWithTo | Tag::RepeatWithDownTo => {
+        let end = self.pop();
+        let start = self.pop();
+        let condition_start_index = self.bytecode_index(end_pos).and_then(|end_index| self.loop_condition_start(end_index));
+        let name = match condition_start_index {
+          Some(condition_start_index) if condition_start_index > 0 => self.var_name_from_set(condition_start_index - 1),
+          _ => Expr::Error("loop variable".to_owned()).to_lingo(false),
+        };
+        Stmt::RepeatWithTo(name, start, end, tag == Tag::RepeatWithTo, block)
+      }
+      Tag::RepeatWhile => Stmt::RepeatWhile(self.pop(), block),
+      _ => Stmt::If(self.pop(), block, None),
+    };
+    self.ast.stmts[stmt_id].stmt = stmt;
+    self.current_block = block;
+  }
+
+  fn translate_jmp(&mut self, index: usize) -> usize {
+    let bytecode = &self.handler.bytecode_array[index];
+    let target_pos = bytecode.pos + bytecode.obj as usize;
+    let target_index = match self.bytecode_index(target_pos) {
+      Some(target_index) => target_index,
+      None => {
+        self.add_stmt(Stmt::Comment("ERROR: Could not identify jmp".to_owned()), index, index);
+        return 1;
+      }
+    };
+
+    if let Some(loop_id) = self.ast.ancestor_loop(self.current_block) {
+      let loop_index = Some(self.ast.stmts[loop_id].index);
+      if target_index > 0 && self.is_op(target_index - 1, OpCode::EndRepeat, None) && self.owner_loops[target_index - 1] == loop_index {
+        self.add_stmt(Stmt::ExitRepeat, index, index);
+        return 1;
+      } else if self.tags[target_index] == Tag::NextRepeatTarget && self.owner_loops[target_index] == loop_index {
+        self.add_stmt(Stmt::NextRepeat, index, index);
+        return 1;
+      }
+    }
+
+    let next_pos = self.handler.bytecode_array.get(index + 1).map(|x| x.pos);
+    let block = &self.ast.blocks[self.current_block];
+    let block_kind = block.kind;
+    if let Some(stmt_id) = block.parent.filter(|_| next_pos.is_some() && next_pos == block.end_pos) {
+      match self.ast.stmts[stmt_id].stmt {
+        Stmt::If(_, _, None) if block_kind == BlockKind::Then => {
+          let else_block = self.ast.add_block(BlockKind::Else, stmt_id, Some(target_pos));
+          if let Stmt::If(_, _, else_slot) = &mut self.ast.stmts[stmt_id].stmt {
+            *else_slot = Some(else_block);
+          }
+          return 1;
+        }
+        Stmt::Case(..) => {
+          self.ast.stmts[stmt_id].end_pos = Some(target_pos);
+          self.tags[target_index] = Tag::EndCase;
+          return 1;
+        }
+        _ => {}
+      }
+    }
+
+    if self.is_op(target_index, OpCode::Pop, Some(1)) {
+      // A case statement starting with `otherwise`.
+      let value = self.pop();
+      let stmt_id = self.add_stmt(Stmt::Case(value, vec![], None), index, index);
+      self.ast.stmts[stmt_id].end_pos = Some(target_pos);
+      self.tags[target_index] = Tag::EndCase;
+      let otherwise = self.ast.add_block(BlockKind::Otherwise, stmt_id, Some(target_pos));
+      if let Stmt::Case(_, _, otherwise_block) = &mut self.ast.stmts[stmt_id].stmt {
+        *otherwise_block = Some(otherwise);
+      }
+      self.current_block = otherwise;
+      return 1;
+    }
+
+    self.add_stmt(Stmt::Comment("ERROR: Could not identify jmp".to_owned()), index, index);
+    1
+  }
+
+  /// A peek starts a label of a case statement: the switch value is compared against the label value.
+  fn translate_case_label(&mut self, index: usize) -> usize {
+    let prev_case = self.ast.blocks[self.current_block].current_case;
+    let original_stack_len = self.stack.len();
+    let len = self.handler.bytecode_array.len();
+
+    let mut current_index = index + 1;
+    while current_index < len {
+      let opcode = self.handler.bytecode_array[current_index].opcode;
+      if self.stack.len() == original_stack_len + 1 && (opcode == OpCode::Eq || opcode == OpCode::NtEq) {
+        break;
+      }
+      current_index += self.translate_bytecode(current_index);
+    }
+    if current_index + 1 >= len || !self.is_op(current_index + 1, OpCode::JmpIfZ, None) {
+      let last_index = current_index.min(len - 1);
+      self.add_stmt(Stmt::Comment("ERROR: Could not identify case label".to_owned()), index, last_index);
+      return last_index - index + 1;
+    }
+
+    let not_eq = self.is_op(current_index, OpCode::NtEq, None);
+    let value = self.pop();
+    let jmpifz_index = current_index + 1;
+    let jmpifz = &self.handler.bytecode_array[jmpifz_index];
+    let jmp_pos = jmpifz.pos + jmpifz.obj as usize;
+    let target_index = self.bytecode_index(jmp_pos).unwrap_or(len);
+    let expect = if not_eq {
+      CaseExpect::Or
+    } else if self.is_op(target_index, OpCode::Peek, None) {
+      CaseExpect::Next
+    } else if self.is_op(target_index, OpCode::Pop, Some(1))
+      && self.handler.bytecode_array.get(target_index.wrapping_sub(1)).is_none_or(|prev| {
+        prev.opcode != OpCode::Jmp || prev.pos + prev.obj as usize == jmp_pos
+      })
+    {
+      CaseExpect::End
+    } else {
+      CaseExpect::Otherwise
+    };
+    if expect == CaseExpect::End {
+      self.tags[target_index] = Tag::EndCase;
+    }
+
+    let stmt_id = match prev_case {
+      None => {
+        let switch_value = self.pop();
+        let label = CaseLabel { values: vec![value], expect, block: None };
+        let stmt_id = self.add_stmt(Stmt::Case(switch_value, vec![label], None), index, index.saturating_sub(1));
+        self.ast.blocks[self.current_block].current_case = Some(stmt_id);
+        stmt_id
+      }
+      Some(stmt_id) => {
+        if let Stmt::Case(_, labels, _) = &mut self.ast.stmts[stmt_id].stmt {
+          match labels.last_mut() {
+            Some(label) if label.expect == CaseExpect::Or => {
+              label.values.push(value);
+              label.expect = expect;
+            }
+            _ => labels.push(CaseLabel { values: vec![value], expect, block: None }),
+          }
+        }
+        stmt_id
+      }
+    };
+    let label_index = match &self.ast.stmts[stmt_id].stmt {
+      Stmt::Case(_, labels, _) => labels.len() - 1,
+      _ => 0,
+    };
+    self.claim((stmt_id, StmtPart::Label(label_index)), jmpifz_index);
+
+    // The block of the label starts after the last equivalent value.
+    if expect != CaseExpect::Or {
+      let block = self.ast.add_block(BlockKind::CaseLabel, stmt_id, Some(jmp_pos));
+      if let Stmt::Case(_, labels, _) = &mut self.ast.stmts[stmt_id].stmt {
+        labels[label_index].block = Some(block);
+      }
+      self.current_block = block;
+    }
+    jmpifz_index - index + 1
+  }
+
+  fn translate_obj_call(&mut self, index: usize) {
+    let method = self.name(self.handler.bytecode_array[index].obj);
+    let (mut args, returns) = self.pop_args();
+    let second_symbol = match args.get(1) {
+      Some(Expr::Symbol(name)) => Some(name.to_owned()),
+      _ => None,
+    };
+    let nargs = args.len();
+    match (method.as_str(), nargs) {
+      ("getAt", 2) => {
+        let index_expr = args.pop().unwrap();
+        let obj = args.pop().unwrap();
+        self.push_call(Expr::ObjBracket(Box::new(obj), Box::new(index_expr)), true, index);
+      }
+      ("setAt", 3) => {
+        let value = args.pop().unwrap();
+        let index_expr = args.pop().unwrap();
+        let obj = args.pop().unwrap();
+        self.add_stmt(Stmt::Assign(Expr::ObjBracket(Box::new(obj), Box::new(index_expr)), value, false), index, index);
+      }
+      ("getProp" | "getPropRef", 3 | 4) if second_symbol.is_some() => {
+        let index2 = if nargs == 4 { Some(Box::new(args.pop().unwrap())) } else { None };
+        let index_expr = args.pop().unwrap();
+        let obj = args.remove(0);
+        let expr = Expr::ObjPropIndex(Box::new(obj), second_symbol.unwrap(), Box::new(index_expr), index2);
+        self.push_call(expr, true, index);
+      }
+      ("setProp", 4 | 5) if second_symbol.is_some() => {
+        let value = args.pop().unwrap();
+        let index2 = if nargs == 5 { Some(Box::new(args.pop().unwrap())) } else { None };
+        let index_expr = args.pop().unwrap();
+        let obj = args.remove(0);
+        let target = Expr::ObjPropIndex(Box::new(obj), second_symbol.unwrap(), Box::new(index_expr), index2);
+        self.add_stmt(Stmt::Assign(target, value, false), index, index);
+      }
+      ("count", 2) if second_symbol.is_some() => {
+        let obj = args.remove(0);
+        let prop = Expr::ObjProp(Box::new(obj), second_symbol.unwrap());
+        self.push_call(Expr::ObjProp(Box::new(prop), "count".to_owned()), true, index);
+      }
+      ("setContents" | "setContentsAfter" | "setContentsBefore", 2) => {
+        let put_type = match method.as_str() {
+          "setContents" => 1,
+          "setContentsAfter" => 2,
+          _ => 3,
+        };
+        let value = args.pop().unwrap();
+        let var = args.pop().unwrap();
+        self.add_stmt(Stmt::Put(put_type, var, value), index, index);
+      }
+      ("hilite", 1) => {
+        self.add_stmt(Stmt::ChunkHilite(args.remove(0)), index, index);
+      }
+      ("delete", 1) => {
+        self.add_stmt(Stmt::ChunkDelete(args.remove(0)), index, index);
+      }
+      _ => self.push_call(Expr::ObjCall(method, args), returns, index),
+    }
+  }
+
+  // Operand readers
+
+  /// Reads a chunk expression of `string`. Only the chunk types with a non-zero first index are used.
+  fn read_chunk_ref(&mut self, string: Expr) -> Expr {
+    let last_line = self.pop();
+    let first_line = self.pop();
+    let last_item = self.pop();
+    let first_item = self.pop();
+    let last_word = self.pop();
+    let first_word = self.pop();
+    let last_char = self.pop();
+    let first_char = self.pop();
+
+    let mut result = string;
+    for (chunk_type, first, last) in [
+      (StringChunkType::Line, first_line, last_line),
+      (StringChunkType::Item, first_item, last_item),
+      (StringChunkType::Word, first_word, last_word),
+      (StringChunkType::Char, first_char, last_char),
+    ] {
+      if first.int_value() != Some(0) {
+        result = Expr::Chunk(chunk_type, Box::new(first), Box::new(last), Box::new(result));
+      }
+    }
+    result
+  }
+
+  /// Reads the operand of put and chunk statements, following `read_context_var_args`.
+  fn read_var(&mut self, var_type: i64) -> Expr {
+    let cast_id = if var_type == 0x6 && self.ctx.dir_version >= 500 {
+      Some(Box::new(self.pop()))
+    } else {
+      None
+    };
+    let id = self.pop();
+    match (var_type, id.int_value()) {
+      // global | global | property/instance
+      (0x1..=0x3, Some(name_id)) => Expr::Var(self.name(name_id as i64)),
+      (0x1..=0x3, None) => id,
+      (0x4, Some(arg_id)) => Expr::Var(self.arg_name(arg_id as i64)),
+      (0x5, Some(local_id)) => Expr::Var(self.local_name(local_id as i64)),
+      (0x6, _) => Expr::Member("field".to_owned(), Box::new(id), cast_id),
+      _ => Expr::Error(format!("var type {}", var_type)),
+    }
+  }
+
+  fn prop_name(names: &HashMap<u16, String>, prop_id: i32) -> String {
+    u16::try_from(prop_id)
+      .ok()
+      .and_then(|prop_id| names.get(&prop_id))
+      .cloned()
+      .unwrap_or_else(|| format!("UNKNOWN_PROP_{}", prop_id))
+  }
+
+  /// Reads the property of a Director 4 style get or set, popping the objects it applies to.
+  fn read_v4_property(&mut self, prop_type: i64, prop_id: i32) -> Expr {
+    let max_movie_prop_id = *MOVIE_PROP_NAMES.keys().max().unwrap() as i32;
+    let chunk_type = |id: i32| if (0x01..=0x04).contains(&id) { Some(StringChunkType::from(&id)) } else { None };
+    match prop_type {
+      0x00 if prop_id <= max_movie_prop_id => Expr::The(Self::prop_name(&MOVIE_PROP_NAMES, prop_id)),
+      0x00 => {
+        let string = self.pop();
+        match chunk_type(prop_id - max_movie_prop_id) {
+          Some(chunk_type) => Expr::LastChunk(chunk_type, Box::new(string)),
+          None => Expr::Error(format!("chunk type {}", prop_id)),
+        }
+      }
+      0x01 => {
+        let string = self.pop();
+        match chunk_type(prop_id) {
+          Some(chunk_type) => Expr::ChunkCount(chunk_type, Box::new(string)),
+          None => Expr::Error(format!("chunk type {}", prop_id)),
+        }
+      }
+      0x02 => {
+        let menu_id = self.pop();
+        let menu = Expr::Member("menu".to_owned(), Box::new(menu_id), None);
+        Expr::TheProp(Box::new(menu), Self::prop_name(&MENU_PROP_NAMES, prop_id))
+      }
+      0x03 => {
+        let menu_id = self.pop();
+        let item_id = self.pop();
+        let item = Expr::MenuItem(Box::new(item_id), Box::new(menu_id));
+        Expr::TheProp(Box::new(item), Self::prop_name(&MENU_ITEM_PROP_NAMES, prop_id))
+      }
+      0x04 => {
+        let sound_id = self.pop();
+        let sound = Expr::Member("sound".to_owned(), Box::new(sound_id), None);
+        Expr::TheProp(Box::new(sound), Self::prop_name(&SOUND_PROP_NAMES, prop_id))
+      }
+      0x06 => {
+        let sprite_id = self.pop();
+        let sprite = Expr::Member("sprite".to_owned(), Box::new(sprite_id), None);
+        Expr::TheProp(Box::new(sprite), Self::prop_name(&SPRITE_PROP_NAMES, prop_id))
+      }
+      0x07 => Expr::The(Self::prop_name(&ANIM_PROP_NAMES, prop_id)),
+      0x08 => {
+        let name = Self::prop_name(&ANIM2_PROP_NAMES, prop_id);
+        if prop_id == 0x02 && self.ctx.dir_version >= 500 {
+          // the number of castMembers supports castLib selection from Director 5.0
+          let cast_lib = self.pop();
+          if cast_lib.int_value() != Some(0) {
+            let cast_lib = Expr::Member("castLib".to_owned(), Box::new(cast_lib), None);
+            return Expr::TheProp(Box::new(cast_lib), name);
+          }
+        }
+        Expr::The(name)
+      }
+      0x09..=0x15 => {
+        let prop_name = Self::prop_name(&MEMBER_PROP_NAMES, prop_id);
+        let cast_id = if self.ctx.dir_version >= 500 { Some(Box::new(self.pop())) } else { None };
+        let member_id = self.pop();
+        let kind = match prop_type {
+          0x0b | 0x0c => "field",
+          0x14 | 0x15 => "script",
+          _ if self.ctx.dir_version >= 500 => "member",
+          _ => "cast",
+        };
+        let member = Expr::Member(kind.to_owned(), Box::new(member_id), cast_id);
+        let entity = if matches!(prop_type, 0x0a | 0x0c | 0x15) { self.read_chunk_ref(member) } else { member };
+        Expr::TheProp(Box::new(entity), prop_name)
+      }
+      _ => Expr::Error(format!("property type {}", prop_type)),
+    }
+  }
+}
@@ -2,3 +2,5 @@ pub mod opcode;
 pub mod datum;
 pub mod script;
 pub mod constants;
+pub mod compiler;
+pub mod decompiler;
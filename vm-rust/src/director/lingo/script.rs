@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 
 use crate::director::chunks::script::ScriptChunk;
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct ScriptContext {
   pub names: Vec<String>,
   pub scripts: HashMap<u32, ScriptChunk>,
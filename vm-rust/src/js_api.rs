@@ -2,6 +2,7 @@ use std::{collections::HashMap, iter::FromIterator, sync::Arc};
 
 use itertools::Itertools;
 use js_sys::Array;
+use log::{error, info};
 use wasm_bindgen::prelude::*;
 
 use crate::{
@@ -9,10 +10,10 @@ use crate::{
         chunks::script::ScriptChunk,
         enums::ScriptType,
         file::DirectorFile,
-        lingo::{datum::Datum, script::ScriptContext},
+        lingo::{datum::Datum, decompiler::{decompile_handler, DecompilerContext}, script::ScriptContext},
     }, player::{
-        allocator::ScriptInstanceAllocatorTrait, bitmap::bitmap::PaletteRef, cast_lib::CastMemberRef, cast_member::{CastMember, CastMemberType, ScriptMember}, datum_formatting::{format_concrete_datum, format_datum}, datum_ref::{DatumId, DatumRef}, handlers::datum_handlers::cast_member_ref::CastMemberRefHandlers, reserve_player_ref, score::Score, script::ScriptInstanceId, script_ref::ScriptInstanceRef, DirPlayer, ScriptError, PLAYER_OPT
-    }, rendering::RENDERER_LOCK
+        allocator::ScriptInstanceAllocatorTrait, bitmap::bitmap::PaletteRef, cast_lib::{CastLib, CastMemberRef}, cast_member::{CastMember, CastMemberType, ScriptMember}, datum_formatting::{format_concrete_datum, format_datum}, datum_ref::{DatumId, DatumRef}, handlers::datum_handlers::cast_member_ref::CastMemberRefHandlers, reserve_player_ref, score::Score, script::ScriptInstanceId, script_ref::ScriptInstanceRef, DirPlayer, ScriptError, PLAYER_OPT
+    }, platform::has_js_host, rendering::RENDERER_LOCK
 };
 
 pub fn ascii_safe(string: &str) -> String {
@@ -164,165 +165,195 @@ extern "C" {
   pub fn onChannelChanged(channel: i16, snapshot: js_sys::Object);
   pub fn onChannelDisplayNameChanged(channel: i16, display_name: &str);
   pub fn onFrameChanged(frame: u32);
+  pub fn onCursorChanged(cursor: i32);
   pub fn onScriptError(data: js_sys::Object);
   pub fn onScopeListChanged(scopes: Vec<js_sys::Object>);
   pub fn onBreakpointListChanged(data: Vec<js_sys::Object>);
   pub fn onGlobalListChanged(data: js_sys::Object);
   pub fn onScriptErrorCleared();
   pub fn onDebugMessage(message: &str);
-  pub fn onScheduleTimeout(timeout_name: &str, interval: u32);
-  pub fn onClearTimeout(timeout_name: &str);
-  pub fn onClearTimeouts();
   pub fn onDatumSnapshot(datum_id: DatumId, data: js_sys::Object);
   pub fn onScriptInstanceSnapshot(script_ref: ScriptInstanceId, data: js_sys::Object);
 }
 
 pub struct JsApi {}
 
+/// Runs `dispatch` if there is a JS host to receive its callbacks. Without one, as in the headless
+/// runner and in tests, calling into JS would panic, so the update is dropped.
+fn dispatch_to_js_host(dispatch: impl FnOnce()) {
+  if has_js_host() {
+    dispatch();
+  }
+}
+
 impl JsApi {
   pub fn dispatch_datum_snapshot(datum_ref: &DatumRef, player: &DirPlayer) {
-    let snapshot = datum_to_js_bridge(datum_ref, player, 0);
-    onDatumSnapshot(datum_ref.unwrap(), snapshot);
+    dispatch_to_js_host(|| {
+      let snapshot = datum_to_js_bridge(datum_ref, player, 0);
+      onDatumSnapshot(datum_ref.unwrap(), snapshot);
+    });
   }
   pub fn dispatch_script_instance_snapshot(script_ref: Option<ScriptInstanceRef>, player: &DirPlayer) {
-    let datum = if script_ref.is_none() {
-      Datum::Void
-    } else {
-      Datum::ScriptInstanceRef(script_ref.clone().unwrap())
-    };
-    let snapshot = concrete_datum_to_js_bridge(&datum, player, 0);
-    onScriptInstanceSnapshot(*script_ref.unwrap(), snapshot);
-  }
-  pub fn dispatch_schedule_timeout(timeout_name: &str, interval: u32) {
-    onScheduleTimeout(timeout_name, interval);
-  }
-  pub fn dispatch_clear_timeout(timeout_name: &str) {
-    onClearTimeout(timeout_name);
-  }
-  #[allow(dead_code)]
-  pub fn dispatch_clear_timeouts() {
-    onClearTimeouts();
+    dispatch_to_js_host(|| {
+      let datum = if script_ref.is_none() {
+        Datum::Void
+      } else {
+        Datum::ScriptInstanceRef(script_ref.clone().unwrap())
+      };
+      let snapshot = concrete_datum_to_js_bridge(&datum, player, 0);
+      onScriptInstanceSnapshot(*script_ref.unwrap(), snapshot);
+    });
   }
   pub fn dispatch_movie_loaded(dir_file: &DirectorFile) {
-    let test = dir_file
-      .cast_entries
-      .iter()
-      .map(|cast| cast.name.to_owned())
-      .collect_vec()
-      .join(", ");
-
-    onMovieLoaded(OnMovieLoadedCallbackData {
-      version: dir_file.version,
-      test_val: test,
+    dispatch_to_js_host(|| {
+      let test = dir_file
+        .cast_entries
+        .iter()
+        .map(|cast| cast.name.to_owned())
+        .collect_vec()
+        .join(", ");
+
+      onMovieLoaded(OnMovieLoadedCallbackData {
+        version: dir_file.version,
+        test_val: test,
+      });
     });
   }
 
   pub fn dispatch_cast_name_changed(cast_number: u32) {
-    async_std::task::spawn_local(async move {
-      let player = unsafe { PLAYER_OPT.as_ref().unwrap() };
-      let cast = player.movie.cast_manager.get_cast(cast_number).unwrap();
-      onCastLibNameChanged(
-        cast_number,
-        &cast.name,
-      );
+    dispatch_to_js_host(|| {
+      async_std::task::spawn_local(async move {
+        let player = unsafe { PLAYER_OPT.as_ref().unwrap() };
+        let cast = player.movie.cast_manager.get_cast(cast_number).unwrap();
+        onCastLibNameChanged(
+          cast_number,
+          &cast.name,
+        );
+      });
     });
   }
 
   pub fn dispatch_cast_list_changed() {
-    async_std::task::spawn_local(async move {
-      let player = unsafe { PLAYER_OPT.as_ref().unwrap() };
-      let names = player
-        .movie
-        .cast_manager
-        .casts
-        .iter()
-        .map(|x| x.name.to_owned())
-        .collect_vec();
-
-      onCastListChanged(
-        names
-            .into_iter()
-            .map(|x| JsValue::from_str(&x))
-            .collect::<Array>(),
-      );
+    dispatch_to_js_host(|| {
+      async_std::task::spawn_local(async move {
+        let player = unsafe { PLAYER_OPT.as_ref().unwrap() };
+        let names = player
+          .movie
+          .cast_manager
+          .casts
+          .iter()
+          .map(|x| x.name.to_owned())
+          .collect_vec();
+
+        onCastListChanged(
+          names
+              .into_iter()
+              .map(|x| JsValue::from_str(&x))
+              .collect::<Array>(),
+        );
+      });
     });
   }
 
   pub fn dispatch_cast_member_list_changed(cast_number: u32) {
-    async_std::task::spawn_local(async move {
-      let player = unsafe { PLAYER_OPT.as_ref().unwrap() };
-      let cast = player.movie.cast_manager.get_cast(cast_number).unwrap();
-      let members_iter = cast.members.values().into_iter();
-
-      let member_list = js_sys::Map::new();
-      for member in members_iter {
-        let member_map = Self::get_mini_member_snapshot(member);
-        member_list.set(&JsValue::from(member.number), &member_map.to_js_object());
-      }
+    dispatch_to_js_host(|| {
+      async_std::task::spawn_local(async move {
+        let player = unsafe { PLAYER_OPT.as_ref().unwrap() };
+        let cast = player.movie.cast_manager.get_cast(cast_number).unwrap();
+        let members_iter = cast.members.values().into_iter();
+
+        let member_list = js_sys::Map::new();
+        for member in members_iter {
+          let member_map = Self::get_mini_member_snapshot(member);
+          member_list.set(&JsValue::from(member.number), &member_map.to_js_object());
+        }
 
-      onCastMemberListChanged(cast_number, member_list.to_js_object());
+        onCastMemberListChanged(cast_number, member_list.to_js_object());
+      });
     });
   }
 
   pub fn dispatch_cast_member_changed(member_ref: CastMemberRef) {
-    async_std::task::spawn_local(async move {
-      let player = unsafe { PLAYER_OPT.as_ref().unwrap() };
-      let subscribed_members = &player.subscribed_member_refs;
-      if !subscribed_members.contains(&member_ref) {
-        return;
-      }
+    dispatch_to_js_host(|| {
+      async_std::task::spawn_local(async move {
+        let player = unsafe { PLAYER_OPT.as_ref().unwrap() };
+        let subscribed_members = &player.subscribed_member_refs;
+        if !subscribed_members.contains(&member_ref) {
+          return;
+        }
 
-      let cast = player.movie.cast_manager.get_cast(member_ref.cast_lib as u32).unwrap();
-      let member = cast.members.get(&(member_ref.cast_member as u32)).unwrap();
-      let member_map = Self::get_member_snapshot(member, cast.lctx.as_ref(), player);
+        let cast = player.movie.cast_manager.get_cast(member_ref.cast_lib as u32).unwrap();
+        let member = cast.members.get(&(member_ref.cast_member as u32)).unwrap();
+        let member_map = Self::get_member_snapshot(member, cast, player);
 
-      onCastMemberChanged(member_ref.to_js().to_js_value(), member_map.to_js_object());
+        onCastMemberChanged(member_ref.to_js().to_js_value(), member_map.to_js_object());
+      });
     });
   }
 
   pub fn on_cast_member_name_changed(slot_number: u32) {
-    async_std::task::spawn_local(async move {
-      let player = unsafe { PLAYER_OPT.as_ref().unwrap() };
+    dispatch_to_js_host(|| {
+      async_std::task::spawn_local(async move {
+        let player = unsafe { PLAYER_OPT.as_ref().unwrap() };
 
-      if player.is_subscribed_to_channel_names {
-        for channel in player.movie.score.channels.iter() {
-          if channel.sprite.member.as_ref().map(|x| CastMemberRefHandlers::get_cast_slot_number(x.cast_lib as u32, x.cast_member as u32)) == Some(slot_number) {
-            Self::dispatch_channel_name_changed(channel.number as i16);
+        if player.is_subscribed_to_channel_names {
+          for channel in player.movie.score.channels.iter() {
+            if channel.sprite.member.as_ref().map(|x| CastMemberRefHandlers::get_cast_slot_number(x.cast_lib as u32, x.cast_member as u32)) == Some(slot_number) {
+              Self::dispatch_channel_name_changed(channel.number as i16);
+            }
           }
         }
-      }
+      });
     });
   }
 
   pub fn on_sprite_member_changed(sprite_num: i16) {
-    Self::dispatch_channel_name_changed(sprite_num)
+    dispatch_to_js_host(|| {
+      Self::dispatch_channel_name_changed(sprite_num)
+    });
   }
 
   pub fn dispatch_score_changed() {
-    async_std::task::spawn_local(async move {
-      let player = unsafe { PLAYER_OPT.as_ref().unwrap() };
+    dispatch_to_js_host(|| {
+      async_std::task::spawn_local(async move {
+        let player = unsafe { PLAYER_OPT.as_ref().unwrap() };
 
-      let snapshot = Self::get_score_snapshot(player, &player.movie.score);
-      onScoreChanged(snapshot.to_js_object());
+        let snapshot = Self::get_score_snapshot(player, &player.movie.score);
+        onScoreChanged(snapshot.to_js_object());
+      });
     });
   }
 
   pub fn dispatch_channel_changed(channel: i16) {
-    async_std::task::spawn_local(async move {
-      let selected_channel = RENDERER_LOCK.with(|x| x.borrow().as_ref().and_then(|y| y.debug_selected_channel_num));
-      if selected_channel.is_some() && selected_channel.unwrap() == channel {
-        let player = unsafe { PLAYER_OPT.as_ref().unwrap() };
-        let snapshot = Self::get_channel_snapshot(player, &channel);
-        onChannelChanged(channel, snapshot.to_js_object());
-      }
+    dispatch_to_js_host(|| {
+      async_std::task::spawn_local(async move {
+        let selected_channel = RENDERER_LOCK.with(|x| x.borrow().as_ref().and_then(|y| y.debug_selected_channel_num));
+        if selected_channel.is_some() && selected_channel.unwrap() == channel {
+          let player = unsafe { PLAYER_OPT.as_ref().unwrap() };
+          let snapshot = Self::get_channel_snapshot(player, &channel);
+          onChannelChanged(channel, snapshot.to_js_object());
+        }
+      });
     });
   }
 
   pub fn dispatch_frame_changed(frame: u32) {
-    onFrameChanged(frame);
+    dispatch_to_js_host(|| {
+      onFrameChanged(frame);
+    });
+  }
+
+  pub fn dispatch_cursor_changed(cursor: i32) {
+    dispatch_to_js_host(|| {
+      onCursorChanged(cursor);
+    });
   }
 
   pub fn dispatch_debug_message(message: &str) {
+    if !has_js_host() {
+      info!("{}", message);
+      return;
+    }
     onDebugMessage(message);
   }
 
@@ -336,7 +367,7 @@ impl JsApi {
     return member_map;
   }
 
-  pub fn get_member_snapshot(member: &CastMember, lctx: Option<&ScriptContext>, player: &DirPlayer) -> js_sys::Map {
+  pub fn get_member_snapshot(member: &CastMember, cast: &CastLib, player: &DirPlayer) -> js_sys::Map {
     let member_map = js_sys::Map::new();
     member_map.str_set("number", &JsValue::from(member.number));
     member_map.str_set("name", &JsValue::from_str(&member.name));
@@ -353,11 +384,12 @@ impl JsApi {
         member_map.str_set("text", &ascii_safe(&text_data.text).to_js_value());
       }
       CastMemberType::Script(script_data) => {
-        let lctx = lctx.unwrap();
+        let lctx = cast.lctx.as_ref().unwrap();
         let script = &lctx.scripts[&script_data.script_id];
+        let decompiler_ctx = DecompilerContext::new(lctx, script, cast.dir_version, cast.capital_x);
         member_map.str_set(
             "script",
-            &Self::get_script_snapshot(&script_data, &script, &lctx).to_js_object(),
+            &Self::get_script_snapshot(script_data, script, lctx, &decompiler_ctx).to_js_object(),
         );
       }
       CastMemberType::Bitmap(bitmap_data) => {
@@ -406,13 +438,15 @@ impl JsApi {
   }
 
   pub fn dispatch_channel_name_changed(channel: i16) {
-    async_std::task::spawn_local(async move {
-      let player = unsafe { PLAYER_OPT.as_ref().unwrap() };
+    dispatch_to_js_host(|| {
+      async_std::task::spawn_local(async move {
+        let player = unsafe { PLAYER_OPT.as_ref().unwrap() };
       
-      if player.is_subscribed_to_channel_names {
-        let display_name = Self::get_channel_display_name(&channel, player).unwrap_or("".to_owned());
-        onChannelDisplayNameChanged(channel, &display_name);
-      }
+        if player.is_subscribed_to_channel_names {
+          let display_name = Self::get_channel_display_name(&channel, player).unwrap_or("".to_owned());
+          onChannelDisplayNameChanged(channel, &display_name);
+        }
+      });
     });
   }
 
@@ -480,6 +514,7 @@ impl JsApi {
     member: &ScriptMember,
     chunk: &ScriptChunk,
     lctx: &ScriptContext,
+    decompiler_ctx: &DecompilerContext,
   ) -> js_sys::Map {
     let member_map = js_sys::Map::new();
     member_map.str_set("name", &member.name.to_js_value());
@@ -513,9 +548,26 @@ impl JsApi {
         args_array.push(&lctx.names[*arg as usize].to_js_value());
       }
 
+      let decompiled = decompile_handler(decompiler_ctx, handler);
+      let lingo_array = js_sys::Array::new();
+      for line in &decompiled.lines {
+        let line_map = js_sys::Map::new();
+        line_map.str_set("text", &line.text.to_js_value());
+        if let Some(bytecode_index) = line.bytecode_index {
+          line_map.str_set("bytecodeIndex", &JsValue::from(bytecode_index));
+        }
+        lingo_array.push(&line_map.to_js_object());
+      }
+      let bytecode_lines_array = js_sys::Array::new();
+      for line_index in &decompiled.bytecode_lines {
+        bytecode_lines_array.push(&JsValue::from(*line_index));
+      }
+
       handler_map.str_set("name", &name.to_js_value());
       handler_map.str_set("args", &args_array);
       handler_map.str_set("bytecode", &bytecode_array);
+      handler_map.str_set("lingo", &lingo_array);
+      handler_map.str_set("bytecodeLines", &bytecode_lines_array);
       handlers_array.push(&handler_map.to_js_object());
     }
     member_map.str_set("handlers", &handlers_array);
@@ -524,47 +576,57 @@ impl JsApi {
   }
 
   pub fn dispatch_scope_list(player: &DirPlayer) {
-    onScopeListChanged(
-      player
-        .scopes
-        .iter()
-        .enumerate()
-        .filter(|(i, _)| player.scope_count > *i as u32)
-        .map(|(_, scope)| {
-          let cast_lib = player.movie.cast_manager.get_cast(scope.script_ref.cast_lib as u32).unwrap();
-          let handler_name = cast_lib.lctx.as_ref().unwrap().names.get(scope.handler_name_id as usize).unwrap();
-          let scope = JsBridgeScope {
-            script_member_ref: scope.script_ref.to_js(),
-            bytecode_index: scope.bytecode_index as u32,
-            handler_name: handler_name.to_owned(),
-            locals: scope.locals.clone().into_iter().map(|(k, v)| (k.to_owned(), v)).collect(),
-            stack: scope.stack.clone(),
-            args: scope.args.clone()
-          };
-          let scope_js: js_sys::Map = scope.into();
-          scope_js.to_js_object()
-        })
-        .collect(),
-    );
+    dispatch_to_js_host(|| {
+      onScopeListChanged(
+        player
+          .scopes
+          .iter()
+          .enumerate()
+          .filter(|(i, _)| player.scope_count > *i as u32)
+          .map(|(_, scope)| {
+            let cast_lib = player.movie.cast_manager.get_cast(scope.script_ref.cast_lib as u32).unwrap();
+            let handler_name = cast_lib.lctx.as_ref().unwrap().names.get(scope.handler_name_id as usize).unwrap();
+            let scope = JsBridgeScope {
+              script_member_ref: scope.script_ref.to_js(),
+              bytecode_index: scope.bytecode_index as u32,
+              handler_name: handler_name.to_owned(),
+              locals: scope.locals.clone().into_iter().map(|(k, v)| (k.to_owned(), v)).collect(),
+              stack: scope.stack.clone(),
+              args: scope.args.clone()
+            };
+            let scope_js: js_sys::Map = scope.into();
+            scope_js.to_js_object()
+          })
+          .collect(),
+      );
+    });
   }
 
   pub fn dispatch_global_list(player: &DirPlayer) {
-    let globals = js_sys::Map::new();
-    for (k, v) in player.globals.iter() {
-      globals.set(
-        &JsValue::from_str(&k.to_string()),
-        &v.unwrap().to_js_value(),
-      );
-    }
-    onGlobalListChanged(globals.to_js_object());
+    dispatch_to_js_host(|| {
+      let globals = js_sys::Map::new();
+      for (k, v) in player.globals.iter() {
+        globals.set(
+          &JsValue::from_str(&k.to_string()),
+          &v.unwrap().to_js_value(),
+        );
+      }
+      onGlobalListChanged(globals.to_js_object());
+    });
   }
 
   pub fn dispatch_debug_update(player: &DirPlayer) {
-    Self::dispatch_scope_list(player);
-    Self::dispatch_global_list(player);
+    dispatch_to_js_host(|| {
+      Self::dispatch_scope_list(player);
+      Self::dispatch_global_list(player);
+    });
   }
 
   pub fn dispatch_script_error(player: &DirPlayer, err: &ScriptError) {
+    if !has_js_host() {
+      error!("Script error: {}", err.message);
+      return;
+    }
     let data: js_sys::Map = if let Some(current_scope) = player.scopes.get(player.current_scope_ref()) {
       let cast_lib = player.movie.cast_manager.get_cast(current_scope.script_ref.cast_lib as u32).unwrap();
       let current_handler_name = cast_lib.lctx.as_ref().unwrap().names.get(current_scope.handler_name_id as usize).unwrap();
@@ -587,28 +649,32 @@ impl JsApi {
   }
 
   pub fn dispatch_breakpoint_list_changed() {
-    async_std::task::spawn_local(async move {
-      let player = unsafe { PLAYER_OPT.as_ref().unwrap() };
-      let breakpoints = player
-        .breakpoint_manager
-        .breakpoints
-        .iter()
-        .map(|x| {
-          let breakpoint = JsBridgeBreakpoint {
-            script_name: x.script_name.to_owned(),
-            handler_name: x.handler_name.to_owned(),
-            bytecode_index: x.bytecode_index,
-          };
-          let breakpoint_js: js_sys::Map = breakpoint.into();
-          breakpoint_js.to_js_object()
-        })
-        .collect();
-      onBreakpointListChanged(breakpoints);
+    dispatch_to_js_host(|| {
+      async_std::task::spawn_local(async move {
+        let player = unsafe { PLAYER_OPT.as_ref().unwrap() };
+        let breakpoints = player
+          .breakpoint_manager
+          .breakpoints
+          .iter()
+          .map(|x| {
+            let breakpoint = JsBridgeBreakpoint {
+              script_name: x.script_name.to_owned(),
+              handler_name: x.handler_name.to_owned(),
+              bytecode_index: x.bytecode_index,
+            };
+            let breakpoint_js: js_sys::Map = breakpoint.into();
+            breakpoint_js.to_js_object()
+          })
+          .collect();
+        onBreakpointListChanged(breakpoints);
+      });
     });
   }
 
   pub fn dispatch_script_error_cleared() {
-    onScriptErrorCleared();
+    dispatch_to_js_host(|| {
+      onScriptErrorCleared();
+    });
   }
 }
 
@@ -680,6 +746,10 @@ fn concrete_datum_to_js_bridge(datum: &Datum, player: &DirPlayer, depth: u8) ->
     Datum::Stage => {
       map.str_set("type", &JsValue::from_str("stage"));
     }
+    Datum::Window(name) => {
+      map.str_set("type", &JsValue::from_str("window"));
+      map.str_set("value", &JsValue::from_str(name));
+    }
     Datum::PropList(properties, sorted) => {
       map.str_set("type", &JsValue::from_str("propList"));
       let props_map = js_sys::Map::new();
@@ -761,6 +831,9 @@ fn concrete_datum_to_js_bridge(datum: &Datum, player: &DirPlayer, depth: u8) ->
     Datum::SoundRef(_) => {
       map.str_set("type", &JsValue::from_str("soundRef"));
     }
+    Datum::Media(_) => {
+      map.str_set("type", &JsValue::from_str("media"));
+    }
   }
   return map.to_js_object();
 }
@@ -3,9 +3,10 @@ mod player;
 mod io;
 mod js_api;
 mod rendering;
+pub mod platform;
 
 use async_std::task::spawn_local;
-use js_api::JsApi;
+use js_api::{JsApi, JsSerializable, JsUtils};
 use num::ToPrimitive;
 use utils::set_panic_hook;
 use wasm_bindgen::prelude::*;
@@ -15,6 +16,17 @@ extern crate pest_derive;
 
 mod director;
 
+/// Everything an embedder needs to add its own Xtras with `register_xtra`.
+pub mod xtra {
+  pub use crate::director::lingo::datum::{Datum, XtraInstanceId};
+  pub use crate::player::{
+    datum_ref::DatumRef,
+    reserve_player_mut, reserve_player_ref,
+    xtra::manager::{register_xtra, unregister_xtra, with_xtra_registry, Xtra, XtraRegistry},
+    DirPlayer, ScriptError,
+  };
+}
+
 use player::{cast_lib::{cast_member_ref, CastMemberRef}, commands::{player_dispatch, PlayerVMCommand}, datum_ref::DatumId, init_player, PLAYER_OPT};
 
 #[wasm_bindgen]
@@ -90,6 +102,18 @@ pub fn set_stage_size(width: u32, height: u32) {
   player_dispatch(PlayerVMCommand::SetStageSize(width, height));
 }
 
+/// Runs the player on a virtual clock starting at `start_millis`, so that time only passes as the movie
+/// plays or when stepped with `step_clock`.
+#[wasm_bindgen]
+pub fn use_virtual_clock(start_millis: f64) {
+  platform::virtual_clock::use_virtual_clock(start_millis as i64);
+}
+
+#[wasm_bindgen]
+pub fn step_clock(millis: f64) -> Result<(), JsValue> {
+  platform::virtual_clock::step_virtual_clock(millis.max(0.0) as u64).map_err(|err| JsValue::from_str(&err))
+}
+
 #[wasm_bindgen]
 pub fn trigger_timeout(name: &str) {
   player_dispatch(PlayerVMCommand::TimeoutTriggered(name.to_string()));
@@ -115,6 +139,25 @@ pub fn mouse_move(x: f64, y: f64) {
   player_dispatch(PlayerVMCommand::MouseMove((x.to_i32().unwrap(), y.to_i32().unwrap())));
 }
 
+#[wasm_bindgen]
+pub fn right_mouse_down(x: f64, y: f64) {
+  player_dispatch(PlayerVMCommand::RightMouseDown((x.to_i32().unwrap(), y.to_i32().unwrap())));
+}
+
+#[wasm_bindgen]
+pub fn right_mouse_up(x: f64, y: f64) {
+  player_dispatch(PlayerVMCommand::RightMouseUp((x.to_i32().unwrap(), y.to_i32().unwrap())));
+}
+
+/// Scrolls by `delta` lines, positive when scrolling down. Deltas that are not a number are dropped.
+#[wasm_bindgen]
+pub fn mouse_wheel(delta: f64) {
+  if delta.is_nan() {
+    return;
+  }
+  player_dispatch(PlayerVMCommand::MouseWheel(delta.round().clamp(i32::MIN as f64, i32::MAX as f64) as i32));
+}
+
 #[wasm_bindgen]
 pub fn key_down(key: String, code: u16) {
   player_dispatch(PlayerVMCommand::KeyDown(key, code));
@@ -150,6 +193,22 @@ pub fn trigger_alert_hook() {
   player_dispatch(PlayerVMCommand::TriggerAlertHook);
 }
 
+#[wasm_bindgen]
+pub fn get_allocator_stats() -> js_sys::Object {
+  let player = unsafe { PLAYER_OPT.as_ref().unwrap() };
+  let allocator = &player.allocator;
+  let stats = &allocator.cycle_collector_stats;
+  let map = js_sys::Map::new();
+  map.str_set("datumCount", &JsValue::from(allocator.datum_count()));
+  map.str_set("scriptInstanceCount", &JsValue::from(allocator.script_instance_count()));
+  map.str_set("collections", &JsValue::from(stats.collections));
+  map.str_set("datumsCollected", &JsValue::from(stats.datums_collected));
+  map.str_set("scriptInstancesCollected", &JsValue::from(stats.script_instances_collected));
+  map.str_set("lastDatumsCollected", &JsValue::from(stats.last_datums_collected));
+  map.str_set("lastScriptInstancesCollected", &JsValue::from(stats.last_script_instances_collected));
+  map.to_js_object()
+}
+
 #[wasm_bindgen]
 pub fn subscribe_to_channel_names() {
   spawn_local(async {
@@ -0,0 +1,102 @@
+use std::{
+  cell::RefCell,
+  io::ErrorKind,
+  path::{Component, Path, PathBuf},
+  rc::Rc,
+};
+
+use fxhash::FxHashMap;
+
+#[derive(Debug, PartialEq)]
+pub enum FileStorageError {
+  NotFound,
+  InvalidPath,
+  Io(String),
+}
+
+/// Stores the files that movies write, such as FileIO saves and downloaded net things.
+/// Paths are `/` separated and relative to the storage root. The player confines them
+/// to the sandbox of the movie before they get here.
+pub trait FileStorage {
+  fn read(&self, path: &str) -> Result<Vec<u8>, FileStorageError>;
+  fn write(&self, path: &str, data: &[u8]) -> Result<(), FileStorageError>;
+  fn delete(&self, path: &str) -> Result<(), FileStorageError>;
+
+  fn exists(&self, path: &str) -> bool {
+    self.read(path).is_ok()
+  }
+}
+
+/// Keeps files in memory, for tests and for hosts without persistent storage.
+/// Clones share the same files.
+#[derive(Clone, Default)]
+pub struct MemoryFileStorage {
+  files: Rc<RefCell<FxHashMap<String, Vec<u8>>>>,
+}
+
+impl FileStorage for MemoryFileStorage {
+  fn read(&self, path: &str) -> Result<Vec<u8>, FileStorageError> {
+    self.files.borrow().get(path).cloned().ok_or(FileStorageError::NotFound)
+  }
+
+  fn write(&self, path: &str, data: &[u8]) -> Result<(), FileStorageError> {
+    self.files.borrow_mut().insert(path.to_owned(), data.to_vec());
+    Ok(())
+  }
+
+  fn delete(&self, path: &str) -> Result<(), FileStorageError> {
+    self.files.borrow_mut().remove(path).map(|_| ()).ok_or(FileStorageError::NotFound)
+  }
+
+  fn exists(&self, path: &str) -> bool {
+    self.files.borrow().contains_key(path)
+  }
+}
+
+/// Keeps files in a directory of the host.
+pub struct DirectoryFileStorage {
+  root: PathBuf,
+}
+
+impl DirectoryFileStorage {
+  pub fn new(root: impl Into<PathBuf>) -> DirectoryFileStorage {
+    DirectoryFileStorage { root: root.into() }
+  }
+
+  fn host_path(&self, path: &str) -> Result<PathBuf, FileStorageError> {
+    let relative = Path::new(path);
+    if !relative.components().all(|x| matches!(x, Component::Normal(_))) {
+      return Err(FileStorageError::InvalidPath);
+    }
+    Ok(self.root.join(relative))
+  }
+}
+
+fn to_storage_error(err: std::io::Error) -> FileStorageError {
+  match err.kind() {
+    ErrorKind::NotFound => FileStorageError::NotFound,
+    _ => FileStorageError::Io(err.to_string()),
+  }
+}
+
+impl FileStorage for DirectoryFileStorage {
+  fn read(&self, path: &str) -> Result<Vec<u8>, FileStorageError> {
+    std::fs::read(self.host_path(path)?).map_err(to_storage_error)
+  }
+
+  fn write(&self, path: &str, data: &[u8]) -> Result<(), FileStorageError> {
+    let host_path = self.host_path(path)?;
+    if let Some(parent) = host_path.parent() {
+      std::fs::create_dir_all(parent).map_err(to_storage_error)?;
+    }
+    std::fs::write(host_path, data).map_err(to_storage_error)
+  }
+
+  fn delete(&self, path: &str) -> Result<(), FileStorageError> {
+    std::fs::remove_file(self.host_path(path)?).map_err(to_storage_error)
+  }
+
+  fn exists(&self, path: &str) -> bool {
+    self.host_path(path).is_ok_and(|x| x.is_file())
+  }
+}
@@ -0,0 +1,95 @@
+use std::{path::{Path, PathBuf}, rc::Rc};
+
+use url::Url;
+
+use crate::{
+  player::{
+    bitmap::bitmap::{get_system_default_palette, Bitmap, PaletteRef},
+    commands::{run_player_command, PlayerVMCommand},
+    font::player_load_system_font,
+    init_player_with_platform, player_run_movie, reserve_player_mut,
+    sound::NullSoundBackend,
+  },
+  rendering::render_stage_to_bitmap,
+};
+
+use super::{
+  native::{init_native_logger, native_platform_with_data_dir, DEFAULT_DATA_DIR},
+  png::encode_png,
+  virtual_clock::VirtualClock,
+  FramePresenter,
+};
+
+pub struct HeadlessOptions {
+  pub movie_path: PathBuf,
+  pub frames: u32,
+  pub output_path: PathBuf,
+  pub system_font_path: Option<PathBuf>,
+  /// Runs on a virtual clock that starts at the Unix epoch, so that runs are reproducible.
+  pub deterministic: bool,
+  /// Where movies keep their files and prefs. Defaults to `dirplayer-data` in the working directory.
+  pub data_dir: Option<PathBuf>,
+}
+
+/// Writes each presented frame to a PNG file, overwriting the previous one.
+pub struct PngFramePresenter {
+  pub output_path: PathBuf,
+  pub error: Option<String>,
+}
+
+impl FramePresenter for PngFramePresenter {
+  fn present_frame(&mut self, stage: &Bitmap) {
+    let png = encode_png(stage.width as u32, stage.height as u32, &stage.data);
+    if let Err(err) = std::fs::write(&self.output_path, png) {
+      self.error = Some(format!("Could not write {}: {}", self.output_path.display(), err));
+    }
+  }
+}
+
+fn to_file_url(path: &Path) -> Result<Url, String> {
+  let path = std::fs::canonicalize(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+  Url::from_file_path(&path).map_err(|_| format!("Invalid path {}", path.display()))
+}
+
+/// Loads a movie from disk, plays it for a number of frames and writes the stage to a PNG.
+pub fn run_headless(options: HeadlessOptions) -> Result<(), String> {
+  init_native_logger(log::LevelFilter::Warn);
+
+  let movie_url = to_file_url(&options.movie_path)?;
+  let font_url = options.system_font_path.as_deref().map(to_file_url).transpose()?;
+  let base_url = movie_url.join(".").map_err(|err| err.to_string())?;
+  let file_name = movie_url.path_segments().and_then(|mut x| x.next_back()).unwrap_or_default().to_owned();
+
+  async_std::task::block_on(async move {
+    let data_dir = options.data_dir.unwrap_or_else(|| PathBuf::from(DEFAULT_DATA_DIR));
+    let mut platform = native_platform_with_data_dir(&data_dir);
+    if options.deterministic {
+      platform.clock = Rc::new(VirtualClock::new(0));
+    }
+    init_player_with_platform(platform, Box::new(NullSoundBackend {}));
+    reserve_player_mut(|player| player.net_manager.set_base_path(base_url));
+    if let Some(font_url) = font_url {
+      player_load_system_font(font_url.as_str()).await;
+    }
+
+    run_player_command(PlayerVMCommand::LoadMovieFromFile(file_name)).await.map_err(|err| err.message)?;
+    reserve_player_mut(|player| {
+      player.is_playing = true;
+      player.is_script_paused = false;
+    });
+    player_run_movie(Some(options.frames)).await;
+
+    let mut presenter = PngFramePresenter { output_path: options.output_path, error: None };
+    reserve_player_mut(|player| {
+      let mut bitmap = Bitmap::new(
+        player.movie.rect.width() as u16,
+        player.movie.rect.height() as u16,
+        32,
+        PaletteRef::BuiltIn(get_system_default_palette()),
+      );
+      render_stage_to_bitmap(player, &mut bitmap, None);
+      presenter.present_frame(&bitmap);
+    });
+    presenter.error.map_or(Ok(()), Err)
+  })
+}
@@ -0,0 +1,62 @@
+use std::{cell::RefCell, rc::Rc};
+
+use async_std::task::spawn_local;
+use futures::{future::{self, LocalBoxFuture}, FutureExt};
+
+use super::{NetLoader, NetProgressCallback, NetRequest, NetResponse, SocketConnection, SocketConnector, SocketEvent};
+
+pub type NetRequestHandler = Box<dyn Fn(&NetRequest) -> Result<NetResponse, i32>>;
+
+/// Answers requests with a handler function instead of the network, so net Lingo can run against
+/// a fake server. Every request is recorded, and clones share the same record.
+#[derive(Clone)]
+pub struct InProcessNetLoader {
+  handler: Rc<NetRequestHandler>,
+  requests: Rc<RefCell<Vec<NetRequest>>>,
+}
+
+impl InProcessNetLoader {
+  pub fn new(handler: NetRequestHandler) -> InProcessNetLoader {
+    InProcessNetLoader {
+      handler: Rc::new(handler),
+      requests: Rc::new(RefCell::new(vec![])),
+    }
+  }
+
+  pub fn requests(&self) -> Vec<NetRequest> {
+    self.requests.borrow().clone()
+  }
+}
+
+impl NetLoader for InProcessNetLoader {
+  fn fetch(&self, request: NetRequest, on_progress: NetProgressCallback) -> LocalBoxFuture<'static, Result<NetResponse, i32>> {
+    let result = (self.handler)(&request);
+    self.requests.borrow_mut().push(request);
+    if let Ok(response) = &result {
+      let size = response.data.len() as u64;
+      on_progress(size, Some(size));
+    }
+    future::ready(result).boxed_local()
+  }
+}
+
+/// A local echo server: every connection succeeds and sends back whatever is written to it.
+/// Lets the socket Xtras be exercised without a network.
+pub struct EchoSocketConnector {}
+
+impl SocketConnector for EchoSocketConnector {
+  fn connect(&self, _host: &str, _port: u16) -> SocketConnection {
+    let (outgoing_tx, outgoing_rx) = async_std::channel::unbounded::<Vec<u8>>();
+    let (event_tx, event_rx) = async_std::channel::unbounded();
+    let _ = event_tx.try_send(SocketEvent::Connected);
+    spawn_local(async move {
+      while let Ok(data) = outgoing_rx.recv().await {
+        if event_tx.send(SocketEvent::Data(data)).await.is_err() {
+          return;
+        }
+      }
+      let _ = event_tx.send(SocketEvent::Closed).await;
+    });
+    SocketConnection { outgoing: outgoing_tx, events: event_rx }
+  }
+}
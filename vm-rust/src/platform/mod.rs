@@ -0,0 +1,146 @@
+pub mod file_storage;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod headless;
+pub mod in_process_net;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod native;
+pub mod png;
+pub mod pref_storage;
+pub mod virtual_clock;
+pub mod web;
+
+use async_std::channel::{Receiver, Sender};
+use futures::{future::LocalBoxFuture, FutureExt};
+use std::{cell::RefCell, rc::Rc};
+use url::Url;
+
+use crate::player::{bitmap::bitmap::Bitmap, net_task::NetResult};
+
+use self::{file_storage::FileStorage, pref_storage::PrefStorage};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum NetMethod {
+  Get,
+  Post,
+}
+
+impl NetMethod {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      NetMethod::Get => "GET",
+      NetMethod::Post => "POST",
+    }
+  }
+}
+
+#[derive(Clone)]
+pub struct NetRequest {
+  pub url: Url,
+  pub method: NetMethod,
+  pub body: Option<Vec<u8>>,
+  pub content_type: Option<String>,
+  /// The host and port set with `proxyServer` for the scheme of the URL.
+  pub proxy: Option<(String, u16)>,
+}
+
+impl NetRequest {
+  pub fn get(url: Url) -> NetRequest {
+    NetRequest { url, method: NetMethod::Get, body: None, content_type: None, proxy: None }
+  }
+}
+
+pub struct NetResponse {
+  pub data: Vec<u8>,
+  pub mime_type: Option<String>,
+  /// The `Last-Modified` date of the resource, as sent by the server.
+  pub last_modified: Option<String>,
+}
+
+/// Called with the number of bytes received so far and the total size, when it is known.
+pub type NetProgressCallback = Box<dyn Fn(u64, Option<u64>)>;
+
+/// Fetches the bytes behind a resolved URL, for movies, casts and any other net thing.
+/// Failures are reported with the error codes of `netError`.
+pub trait NetLoader {
+  fn fetch(&self, request: NetRequest, on_progress: NetProgressCallback) -> LocalBoxFuture<'static, Result<NetResponse, i32>>;
+
+  fn load(&self, url: &Url) -> LocalBoxFuture<'static, NetResult> {
+    let response = self.fetch(NetRequest::get(url.clone()), Box::new(|_, _| {}));
+    async move { response.await.map(|response| response.data) }.boxed_local()
+  }
+}
+
+/// The source of time for the player. Frame pacing and timeout objects only ever wait through `sleep`,
+/// so a clock that is stepped manually makes a movie run reproducible.
+pub trait Clock {
+  /// Milliseconds since the Unix epoch.
+  fn now_millis(&self) -> i64;
+  /// Resolves once the given number of milliseconds have passed.
+  fn sleep(&self, millis: u64) -> LocalBoxFuture<'static, ()>;
+  /// Whether time only passes when the player sleeps or the clock is stepped, instead of following
+  /// the system time. Virtual clocks have no timezone, so local time is UTC.
+  fn is_virtual(&self) -> bool {
+    false
+  }
+  /// Moves a virtual clock forward. Returns false for clocks that follow the system time.
+  fn step(&self, _millis: u64) -> bool {
+    false
+  }
+}
+
+/// Shows a rendered stage. Implemented by the canvas renderer and by headless runs.
+pub trait FramePresenter {
+  fn present_frame(&mut self, stage: &Bitmap);
+}
+
+pub enum SocketEvent {
+  Connected,
+  Data(Vec<u8>),
+  Error(String),
+  Closed,
+}
+
+/// The two ends of an open socket. Dropping `outgoing` closes the connection.
+pub struct SocketConnection {
+  pub outgoing: Sender<Vec<u8>>,
+  pub events: Receiver<SocketEvent>,
+}
+
+/// Opens the socket connections used by networking Xtras. Raw TCP where the host allows it,
+/// otherwise bridged over some other transport such as a WebSocket.
+pub trait SocketConnector {
+  fn connect(&self, host: &str, port: u16) -> SocketConnection;
+
+  /// Accepts incoming connections on `port`, for movies that host peer-to-peer sessions.
+  fn listen(&self, port: u16) -> Result<Receiver<SocketConnection>, String> {
+    Err(format!("Cannot listen on port {} on this platform", port))
+  }
+}
+
+#[derive(Clone)]
+pub struct Platform {
+  pub net_loader: Rc<dyn NetLoader>,
+  pub clock: Rc<dyn Clock>,
+  pub sockets: Rc<dyn SocketConnector>,
+  pub files: Rc<dyn FileStorage>,
+  pub prefs: Rc<dyn PrefStorage>,
+}
+
+thread_local! {
+  static PLATFORM: RefCell<Option<Rc<Platform>>> = const { RefCell::new(None) };
+}
+
+/// Installs the platform services. Anything still holding the previous services keeps using them.
+pub fn set_platform(platform: Platform) {
+  PLATFORM.with(|x| x.replace(Some(Rc::new(platform))));
+}
+
+/// Returns the platform services installed by `init_player`.
+pub fn platform() -> Rc<Platform> {
+  PLATFORM.with(|x| x.borrow().clone().expect("Platform services are not initialized"))
+}
+
+/// Whether the player runs inside a browser, with the JS API and console available.
+pub const fn has_js_host() -> bool {
+  cfg!(target_arch = "wasm32")
+}
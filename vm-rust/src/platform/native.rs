@@ -0,0 +1,210 @@
+use std::{io::{ErrorKind, Read, Write}, net::{IpAddr, Ipv4Addr, TcpListener, TcpStream}, path::Path, rc::Rc, time::Duration};
+
+use async_std::channel::{Receiver, Sender};
+use chrono::{DateTime, Local, Utc};
+use futures::{future::LocalBoxFuture, FutureExt};
+use log::{warn, Log, Metadata, Record};
+use crate::player::net_task::{NET_ERROR_CONNECTION_FAILED, NET_ERROR_INTERNAL, NET_ERROR_INVALID_URL, NET_ERROR_NOT_AUTHENTICATED, NET_ERROR_NOT_FOUND};
+
+use super::{file_storage::DirectoryFileStorage, pref_storage::DirectoryPrefStorage, Clock, NetLoader, NetProgressCallback, NetRequest, NetResponse, Platform, SocketConnection, SocketConnector, SocketEvent};
+
+/// Loads `file://` URLs from disk. There is no HTTP client, so other schemes fail, with or without
+/// a proxy. Files are read the same way for any request method.
+pub struct FileNetLoader {}
+
+fn guess_mime_type(path: &Path) -> Option<String> {
+  let extension = path.extension()?.to_str()?.to_lowercase();
+  let mime_type = match extension.as_str() {
+    "txt" => "text/plain",
+    "htm" | "html" => "text/html",
+    "xml" => "text/xml",
+    "png" => "image/png",
+    "gif" => "image/gif",
+    "jpg" | "jpeg" => "image/jpeg",
+    "dcr" | "dir" | "dxr" | "cct" | "cst" | "cxt" => "application/x-director",
+    _ => return None,
+  };
+  Some(mime_type.to_owned())
+}
+
+fn read_file(path: &Path) -> Result<NetResponse, i32> {
+  let data = std::fs::read(path).map_err(|err| {
+    warn!("Could not read {}: {}", path.display(), err);
+    match err.kind() {
+      ErrorKind::NotFound => NET_ERROR_NOT_FOUND,
+      ErrorKind::PermissionDenied => NET_ERROR_NOT_AUTHENTICATED,
+      _ => NET_ERROR_INTERNAL,
+    }
+  })?;
+  let last_modified = std::fs::metadata(path)
+    .and_then(|x| x.modified())
+    .ok()
+    .map(|x| DateTime::<Utc>::from(x).format("%a, %d %b %Y %H:%M:%S GMT").to_string());
+  Ok(NetResponse { data, mime_type: guess_mime_type(path), last_modified })
+}
+
+impl NetLoader for FileNetLoader {
+  fn fetch(&self, request: NetRequest, on_progress: NetProgressCallback) -> LocalBoxFuture<'static, Result<NetResponse, i32>> {
+    let result = if request.url.scheme() != "file" {
+      match &request.proxy {
+        Some((host, port)) => warn!("Cannot load {} through the proxy {}:{}, proxies are not supported", request.url, host, port),
+        None => warn!("Cannot load {} without a network connection", request.url),
+      }
+      Err(NET_ERROR_CONNECTION_FAILED)
+    } else {
+      match request.url.to_file_path() {
+        Ok(path) => read_file(&path),
+        Err(_) => Err(NET_ERROR_INVALID_URL),
+      }
+    };
+    if let Ok(response) = &result {
+      let size = response.data.len() as u64;
+      on_progress(size, Some(size));
+    }
+    async move { result }.boxed_local()
+  }
+}
+
+/// Wall clock time.
+pub struct SystemClock {}
+
+impl Clock for SystemClock {
+  fn now_millis(&self) -> i64 {
+    Local::now().timestamp_millis()
+  }
+
+  fn sleep(&self, millis: u64) -> LocalBoxFuture<'static, ()> {
+    async_std::task::sleep(Duration::from_millis(millis)).boxed_local()
+  }
+}
+
+/// Connects with plain TCP, reading and writing on background threads.
+pub struct TcpSocketConnector {
+  /// The address hosted servers listen on. Only local connections are accepted by default.
+  pub bind_address: IpAddr,
+}
+
+impl Default for TcpSocketConnector {
+  fn default() -> TcpSocketConnector {
+    TcpSocketConnector { bind_address: IpAddr::V4(Ipv4Addr::LOCALHOST) }
+  }
+}
+
+/// Runs an open stream until either side closes it. Blocks the calling thread on the reads.
+fn run_tcp_stream(mut stream: TcpStream, outgoing_rx: Receiver<Vec<u8>>, event_tx: Sender<SocketEvent>) {
+  let _ = event_tx.try_send(SocketEvent::Connected);
+
+  if let Ok(mut write_stream) = stream.try_clone() {
+    std::thread::spawn(move || {
+      while let Ok(message) = async_std::task::block_on(outgoing_rx.recv()) {
+        if write_stream.write_all(&message).is_err() {
+          break;
+        }
+      }
+      let _ = write_stream.shutdown(std::net::Shutdown::Both);
+    });
+  }
+
+  let mut buffer = [0u8; 4096];
+  loop {
+    match stream.read(&mut buffer) {
+      Ok(0) => break,
+      Ok(length) => {
+        let _ = event_tx.try_send(SocketEvent::Data(buffer[..length].to_vec()));
+      }
+      Err(err) => {
+        let _ = event_tx.try_send(SocketEvent::Error(err.to_string()));
+        break;
+      }
+    }
+  }
+  let _ = event_tx.try_send(SocketEvent::Closed);
+}
+
+impl SocketConnector for TcpSocketConnector {
+  fn connect(&self, host: &str, port: u16) -> SocketConnection {
+    let (outgoing_tx, outgoing_rx) = async_std::channel::unbounded::<Vec<u8>>();
+    let (event_tx, event_rx) = async_std::channel::unbounded();
+    let address = format!("{}:{}", host, port);
+
+    std::thread::spawn(move || {
+      match TcpStream::connect(&address) {
+        Ok(stream) => run_tcp_stream(stream, outgoing_rx, event_tx),
+        Err(err) => {
+          let _ = event_tx.try_send(SocketEvent::Error(err.to_string()));
+        }
+      }
+    });
+
+    SocketConnection { outgoing: outgoing_tx, events: event_rx }
+  }
+
+  fn listen(&self, port: u16) -> Result<Receiver<SocketConnection>, String> {
+    let listener = TcpListener::bind((self.bind_address, port)).map_err(|err| err.to_string())?;
+    let (connection_tx, connection_rx) = async_std::channel::unbounded();
+
+    std::thread::spawn(move || {
+      for stream in listener.incoming() {
+        let stream = match stream {
+          Ok(stream) => stream,
+          Err(err) => {
+            warn!("Failed to accept a connection: {}", err);
+            continue;
+          }
+        };
+        let (outgoing_tx, outgoing_rx) = async_std::channel::unbounded::<Vec<u8>>();
+        let (event_tx, event_rx) = async_std::channel::unbounded();
+        if connection_tx.try_send(SocketConnection { outgoing: outgoing_tx, events: event_rx }).is_err() {
+          // Nobody is listening anymore.
+          break;
+        }
+        std::thread::spawn(move || run_tcp_stream(stream, outgoing_rx, event_tx));
+      }
+    });
+
+    Ok(connection_rx)
+  }
+}
+
+/// Writes log records to stderr, in place of the browser console.
+pub struct StderrLogger {}
+
+impl Log for StderrLogger {
+  fn enabled(&self, _metadata: &Metadata) -> bool {
+    true
+  }
+
+  fn log(&self, record: &Record) {
+    if self.enabled(record.metadata()) {
+      eprintln!("[{}] {}", record.level(), record.args());
+    }
+  }
+
+  fn flush(&self) {}
+}
+
+static STDERR_LOGGER: StderrLogger = StderrLogger {};
+
+pub fn init_native_logger(level: log::LevelFilter) {
+  if log::set_logger(&STDERR_LOGGER).is_ok() {
+    log::set_max_level(level);
+  }
+}
+
+/// The directory, relative to the working directory, that holds the files and prefs movies write.
+pub const DEFAULT_DATA_DIR: &str = "dirplayer-data";
+
+pub fn native_platform() -> Platform {
+  native_platform_with_data_dir(Path::new(DEFAULT_DATA_DIR))
+}
+
+/// Keeps the files of movies in `files` and their prefs in `prefs` below `data_dir`.
+pub fn native_platform_with_data_dir(data_dir: &Path) -> Platform {
+  Platform {
+    net_loader: Rc::new(FileNetLoader {}),
+    clock: Rc::new(SystemClock {}),
+    sockets: Rc::new(TcpSocketConnector::default()),
+    files: Rc::new(DirectoryFileStorage::new(data_dir.join("files"))),
+    prefs: Rc::new(DirectoryPrefStorage::new(data_dir.join("prefs"))),
+  }
+}
@@ -0,0 +1,185 @@
+use std::{convert::TryInto, io::{Read, Write}};
+
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression, Crc};
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// An 8-bit RGBA image, the layout used by 32-bit bitmaps.
+pub struct RgbaImage {
+  pub width: u32,
+  pub height: u32,
+  pub data: Vec<u8>,
+}
+
+fn write_chunk(out: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+  out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+  out.extend_from_slice(chunk_type);
+  out.extend_from_slice(data);
+  let mut crc = Crc::new();
+  crc.update(chunk_type);
+  crc.update(data);
+  out.extend_from_slice(&crc.sum().to_be_bytes());
+}
+
+/// Encodes RGBA pixels as a PNG without filtering.
+pub fn encode_png(width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+  let mut out = PNG_SIGNATURE.to_vec();
+
+  let mut header = vec![];
+  header.extend_from_slice(&width.to_be_bytes());
+  header.extend_from_slice(&height.to_be_bytes());
+  // Bit depth 8, color type RGBA, default compression, filtering and no interlacing
+  header.extend_from_slice(&[8, 6, 0, 0, 0]);
+  write_chunk(&mut out, b"IHDR", &header);
+
+  let stride = width as usize * 4;
+  let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+  for row in rgba.chunks(stride).take(height as usize) {
+    encoder.write_all(&[0]).unwrap();
+    encoder.write_all(row).unwrap();
+  }
+  write_chunk(&mut out, b"IDAT", &encoder.finish().unwrap());
+  write_chunk(&mut out, b"IEND", &[]);
+  out
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+  let p = a as i16 + b as i16 - c as i16;
+  let pa = (p - a as i16).abs();
+  let pb = (p - b as i16).abs();
+  let pc = (p - c as i16).abs();
+  if pa <= pb && pa <= pc {
+    a
+  } else if pb <= pc {
+    b
+  } else {
+    c
+  }
+}
+
+/// Decodes a non-interlaced PNG with 8 bits per channel into RGBA pixels.
+pub fn decode_png(bytes: &[u8]) -> Result<RgbaImage, String> {
+  if bytes.len() < 8 || bytes[0..8] != PNG_SIGNATURE {
+    return Err("Not a PNG file".to_string());
+  }
+  let mut pos = 8;
+  let mut header = None;
+  let mut palette: &[u8] = &[];
+  let mut compressed = vec![];
+  while pos + 8 <= bytes.len() {
+    let length = u32::from_be_bytes(bytes[pos..pos + 4].try_into().unwrap()) as usize;
+    let chunk_type = &bytes[pos + 4..pos + 8];
+    let data = bytes.get(pos + 8..pos + 8 + length).ok_or("Truncated PNG chunk")?;
+    match chunk_type {
+      b"IHDR" => header = Some(data),
+      b"PLTE" => palette = data,
+      b"IDAT" => compressed.extend_from_slice(data),
+      b"IEND" => break,
+      _ => {}
+    }
+    pos += 12 + length;
+  }
+
+  let header = header.filter(|x| x.len() >= 13).ok_or("Missing PNG header")?;
+  let width = u32::from_be_bytes(header[0..4].try_into().unwrap());
+  let height = u32::from_be_bytes(header[4..8].try_into().unwrap());
+  let (bit_depth, color_type, interlace) = (header[8], header[9], header[12]);
+  if bit_depth != 8 || interlace != 0 {
+    return Err(format!("Unsupported PNG format: bit depth {}, interlace {}", bit_depth, interlace));
+  }
+  let channels = match color_type {
+    0 | 3 => 1,
+    4 => 2,
+    2 => 3,
+    6 => 4,
+    _ => return Err(format!("Unsupported PNG color type {}", color_type)),
+  };
+
+  let mut filtered = vec![];
+  ZlibDecoder::new(compressed.as_slice()).read_to_end(&mut filtered).map_err(|e| e.to_string())?;
+  let stride = width as usize * channels;
+  if filtered.len() < (stride + 1) * height as usize {
+    return Err("Truncated PNG image data".to_string());
+  }
+
+  let mut pixels = vec![0u8; stride * height as usize];
+  for y in 0..height as usize {
+    let filter = filtered[y * (stride + 1)];
+    let src = &filtered[y * (stride + 1) + 1..(y + 1) * (stride + 1)];
+    let (previous_rows, current_rows) = pixels.split_at_mut(y * stride);
+    let prior = if y > 0 { &previous_rows[(y - 1) * stride..] } else { &[][..] };
+    let row = &mut current_rows[..stride];
+    for x in 0..stride {
+      let a = if x >= channels { row[x - channels] } else { 0 };
+      let b = prior.get(x).copied().unwrap_or(0);
+      let c = if x >= channels { prior.get(x - channels).copied().unwrap_or(0) } else { 0 };
+      row[x] = match filter {
+        0 => src[x],
+        1 => src[x].wrapping_add(a),
+        2 => src[x].wrapping_add(b),
+        3 => src[x].wrapping_add(((a as u16 + b as u16) / 2) as u8),
+        4 => src[x].wrapping_add(paeth(a, b, c)),
+        _ => return Err(format!("Invalid PNG filter {}", filter)),
+      };
+    }
+  }
+
+  let mut data = Vec::with_capacity(width as usize * height as usize * 4);
+  for pixel in pixels.chunks(channels) {
+    let rgba = match color_type {
+      0 => [pixel[0], pixel[0], pixel[0], 255],
+      3 => {
+        let index = pixel[0] as usize * 3;
+        let color = palette.get(index..index + 3).unwrap_or(&[0, 0, 0]);
+        [color[0], color[1], color[2], 255]
+      }
+      4 => [pixel[0], pixel[0], pixel[0], pixel[1]],
+      2 => [pixel[0], pixel[1], pixel[2], 255],
+      _ => [pixel[0], pixel[1], pixel[2], pixel[3]],
+    };
+    data.extend_from_slice(&rgba);
+  }
+  Ok(RgbaImage { width, height, data })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn encode_rows(header: &[u8], rows: &[&[u8]]) -> Vec<u8> {
+    let mut out = PNG_SIGNATURE.to_vec();
+    write_chunk(&mut out, b"IHDR", header);
+    let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+    for row in rows {
+      encoder.write_all(row).unwrap();
+    }
+    write_chunk(&mut out, b"IDAT", &encoder.finish().unwrap());
+    write_chunk(&mut out, b"IEND", &[]);
+    out
+  }
+
+  #[test]
+  fn decodes_what_it_encodes() {
+    let rgba: Vec<u8> = (0..3 * 2 * 4).map(|x| (x * 10) as u8).collect();
+    let image = decode_png(&encode_png(3, 2, &rgba)).unwrap();
+    assert_eq!((image.width, image.height), (3, 2));
+    assert_eq!(image.data, rgba);
+  }
+
+  #[test]
+  fn undoes_row_filters() {
+    // 2x2 grayscale, the first row with the sub filter and the second with the up filter
+    let header = [0, 0, 0, 2, 0, 0, 0, 2, 8, 0, 0, 0, 0];
+    let png = encode_rows(&header, &[&[1, 10, 5], &[2, 1, 2]]);
+    let image = decode_png(&png).unwrap();
+    let gray: Vec<u8> = image.data.chunks(4).map(|pixel| pixel[0]).collect();
+    assert_eq!(gray, vec![10, 15, 11, 17]);
+  }
+
+  #[test]
+  fn rejects_other_files() {
+    assert!(decode_png(b"GIF89a").is_err());
+    let header = [0, 0, 0, 1, 0, 0, 0, 1, 16, 6, 0, 0, 0];
+    assert!(decode_png(&encode_rows(&header, &[&[0; 9]])).is_err());
+  }
+}
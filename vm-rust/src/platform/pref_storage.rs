@@ -0,0 +1,118 @@
+use std::{cell::RefCell, path::PathBuf, rc::Rc};
+
+use fxhash::FxHashMap;
+
+use super::file_storage::{DirectoryFileStorage, FileStorage, FileStorageError};
+
+/// Stores the prefs written with `setPref`. Keys are the origin of the movie followed by
+/// the pref name, both already validated by the player, for example `example.com/scores.txt`.
+pub trait PrefStorage {
+  fn get(&self, key: &str) -> Option<String>;
+  fn set(&self, key: &str, value: &str) -> Result<(), String>;
+}
+
+/// Keeps prefs in memory, for tests and for hosts without persistent storage.
+/// Clones share the same prefs.
+#[derive(Clone, Default)]
+pub struct MemoryPrefStorage {
+  prefs: Rc<RefCell<FxHashMap<String, String>>>,
+}
+
+impl PrefStorage for MemoryPrefStorage {
+  fn get(&self, key: &str) -> Option<String> {
+    self.prefs.borrow().get(key).cloned()
+  }
+
+  fn set(&self, key: &str, value: &str) -> Result<(), String> {
+    self.prefs.borrow_mut().insert(key.to_owned(), value.to_owned());
+    Ok(())
+  }
+}
+
+/// Keeps each pref in a text file of a host directory, with a subdirectory per origin.
+pub struct DirectoryPrefStorage {
+  files: DirectoryFileStorage,
+}
+
+impl DirectoryPrefStorage {
+  pub fn new(root: impl Into<PathBuf>) -> DirectoryPrefStorage {
+    DirectoryPrefStorage { files: DirectoryFileStorage::new(root) }
+  }
+}
+
+impl PrefStorage for DirectoryPrefStorage {
+  fn get(&self, key: &str) -> Option<String> {
+    // Prefs are written as strings, but the file may have been edited by hand.
+    self.files.read(key).ok().map(|data| String::from_utf8_lossy(&data).to_string())
+  }
+
+  fn set(&self, key: &str, value: &str) -> Result<(), String> {
+    self.files.write(key, value.as_bytes()).map_err(|err| match err {
+      FileStorageError::Io(message) => message,
+      err => format!("{:?}", err),
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// A directory of its own below the system temp directory, removed again when dropped.
+  struct TempDir(PathBuf);
+
+  impl TempDir {
+    fn new(name: &str) -> TempDir {
+      let path = std::env::temp_dir().join(format!("dirplayer-{}-{}", name, std::process::id()));
+      let _ = std::fs::remove_dir_all(&path);
+      TempDir(path)
+    }
+  }
+
+  impl Drop for TempDir {
+    fn drop(&mut self) {
+      let _ = std::fs::remove_dir_all(&self.0);
+    }
+  }
+
+  fn assert_round_trips(prefs: &dyn PrefStorage) {
+    assert_eq!(prefs.get("example.com/scores"), None);
+    prefs.set("example.com/scores", "alice 10").unwrap();
+    assert_eq!(prefs.get("example.com/scores").as_deref(), Some("alice 10"));
+    prefs.set("example.com/scores", "bob 20").unwrap();
+    assert_eq!(prefs.get("example.com/scores").as_deref(), Some("bob 20"));
+    // Prefs of other movies are kept apart
+    assert_eq!(prefs.get("example.org/scores"), None);
+    prefs.set("example.com/empty", "").unwrap();
+    assert_eq!(prefs.get("example.com/empty").as_deref(), Some(""));
+  }
+
+  #[test]
+  fn memory_prefs_round_trip() {
+    let prefs = MemoryPrefStorage::default();
+    assert_round_trips(&prefs);
+    // Clones share the prefs
+    prefs.clone().set("example.com/shared", "yes").unwrap();
+    assert_eq!(prefs.get("example.com/shared").as_deref(), Some("yes"));
+  }
+
+  #[test]
+  fn directory_prefs_round_trip() {
+    let dir = TempDir::new("prefs-round-trip");
+    assert_round_trips(&DirectoryPrefStorage::new(&dir.0));
+    // Another storage on the same directory sees the prefs written before
+    let prefs = DirectoryPrefStorage::new(&dir.0);
+    assert_eq!(prefs.get("example.com/scores").as_deref(), Some("bob 20"));
+    assert!(prefs.set("../outside", "value").is_err());
+  }
+
+  #[test]
+  fn directory_prefs_keep_line_endings_and_non_ascii_text() {
+    let dir = TempDir::new("prefs-text");
+    let prefs = DirectoryPrefStorage::new(&dir.0);
+    let value = "first line\rsecond line\r\nthird line\nGrüße, 你好 ✓\r";
+    prefs.set("example.com/text", value).unwrap();
+    assert_eq!(prefs.get("example.com/text").as_deref(), Some(value));
+    assert_eq!(std::fs::read(dir.0.join("example.com/text")).unwrap(), value.as_bytes());
+  }
+}
@@ -0,0 +1,93 @@
+use std::{cell::Cell, rc::Rc};
+
+use futures::{future::{self, LocalBoxFuture}, FutureExt};
+
+use super::{platform, set_platform, Clock, Platform};
+
+/// A deterministic clock. Time never passes on its own: it moves forward when the player sleeps,
+/// by exactly the requested amount, or when it is stepped with `advance`.
+/// Clones share the same time, so a clone kept outside the platform can step the installed clock.
+#[derive(Clone)]
+pub struct VirtualClock {
+  now: Rc<Cell<i64>>,
+}
+
+impl VirtualClock {
+  pub fn new(start_millis: i64) -> VirtualClock {
+    VirtualClock {
+      now: Rc::new(Cell::new(start_millis)),
+    }
+  }
+
+  pub fn advance(&self, millis: u64) {
+    self.now.set(self.now.get() + millis as i64);
+  }
+}
+
+impl Clock for VirtualClock {
+  fn now_millis(&self) -> i64 {
+    self.now.get()
+  }
+
+  fn sleep(&self, millis: u64) -> LocalBoxFuture<'static, ()> {
+    self.advance(millis);
+    future::ready(()).boxed_local()
+  }
+
+  fn is_virtual(&self) -> bool {
+    true
+  }
+
+  fn step(&self, millis: u64) -> bool {
+    self.advance(millis);
+    true
+  }
+}
+
+/// Switches the running player to a virtual clock starting at `start_millis`, like `--deterministic`
+/// does for headless runs. Timers already waiting on the previous clock still fire on it.
+pub fn use_virtual_clock(start_millis: i64) {
+  set_platform(Platform {
+    clock: Rc::new(VirtualClock::new(start_millis)),
+    ..platform().as_ref().clone()
+  });
+}
+
+/// Steps the player's clock, for embedders driving a deterministic run frame by frame.
+pub fn step_virtual_clock(millis: u64) -> Result<(), String> {
+  if platform().clock.step(millis) {
+    Ok(())
+  } else {
+    Err("The player is not running on a virtual clock".to_string())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{player::testing::with_test_player, utils::{get_local_time, get_ticks}};
+
+  #[test]
+  fn steps_the_installed_clock() {
+    with_test_player(|test_platform| {
+      use_virtual_clock(1000);
+      assert_eq!(platform().clock.now_millis(), 1000);
+      step_virtual_clock(500).unwrap();
+      assert_eq!(platform().clock.now_millis(), 1500);
+      assert_eq!(get_ticks(), 90);
+      // The test platform's own clock was replaced and no longer drives the player
+      test_platform.clock.advance(10_000);
+      assert_eq!(platform().clock.now_millis(), 1500);
+    });
+  }
+
+  #[test]
+  fn virtual_local_time_is_utc() {
+    with_test_player(|test_platform| {
+      test_platform.clock.advance(((12 * 60 + 34) * 60 + 56) * 1000);
+      let time = get_local_time();
+      assert_eq!(time.offset().local_minus_utc(), 0);
+      assert_eq!(time.format("%m/%d/%Y %H:%M:%S").to_string(), "01/01/1970 12:34:56");
+    });
+  }
+}
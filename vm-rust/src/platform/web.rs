@@ -0,0 +1,211 @@
+use std::{rc::Rc, time::Duration};
+
+use async_std::{future::{self, timeout}, task::spawn_local};
+use chrono::Local;
+use futures::{future::LocalBoxFuture, FutureExt};
+use js_sys::Uint8Array;
+use log::warn;
+use wasm_bindgen::{closure::Closure, JsCast};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{ErrorEvent, Event, MessageEvent, Request, RequestInit, Response, Storage, WebSocket};
+
+use crate::player::net_task::{net_error_for_http_status, NET_ERROR_CONNECTION_FAILED, NET_ERROR_INVALID_URL, NET_ERROR_UNEXPECTED_CLOSE};
+
+use super::{file_storage::{FileStorage, FileStorageError}, pref_storage::PrefStorage, Clock, NetLoader, NetProgressCallback, NetRequest, NetResponse, Platform, SocketConnection, SocketConnector, SocketEvent};
+
+/// Loads net things with the browser's `fetch`.
+pub struct WebNetLoader {}
+
+/// Browsers choose the proxy themselves, so the `proxy` of a request is not used.
+impl NetLoader for WebNetLoader {
+  fn fetch(&self, request: NetRequest, on_progress: NetProgressCallback) -> LocalBoxFuture<'static, Result<NetResponse, i32>> {
+    async move {
+      let init = RequestInit::new();
+      init.set_method(request.method.as_str());
+      if let Some(body) = &request.body {
+        init.set_body(&Uint8Array::from(body.as_slice()));
+      }
+      let js_request = Request::new_with_str_and_init(request.url.as_str(), &init).map_err(|_| NET_ERROR_INVALID_URL)?;
+      if let Some(content_type) = &request.content_type {
+        js_request.headers().set("Content-Type", content_type).map_err(|_| NET_ERROR_INVALID_URL)?;
+      }
+
+      let window = web_sys::window().unwrap();
+      let resp_value = JsFuture::from(window.fetch_with_request(&js_request)).await.map_err(|_| NET_ERROR_CONNECTION_FAILED)?;
+      let resp: Response = resp_value.dyn_into().unwrap();
+      if !resp.ok() {
+        return Err(net_error_for_http_status(resp.status()));
+      }
+      let headers = resp.headers();
+      let get_header = |name: &str| headers.get(name).ok().flatten();
+      let bytes_total = get_header("Content-Length").and_then(|x| x.parse().ok());
+      on_progress(0, bytes_total);
+
+      let buffer = resp.array_buffer().map_err(|_| NET_ERROR_UNEXPECTED_CLOSE)?;
+      let buffer = JsFuture::from(buffer).await.map_err(|_| NET_ERROR_UNEXPECTED_CLOSE)?;
+      let data = Uint8Array::new(&buffer).to_vec();
+      on_progress(data.len() as u64, Some(data.len() as u64));
+      Ok(NetResponse {
+        data,
+        mime_type: get_header("Content-Type"),
+        last_modified: get_header("Last-Modified"),
+      })
+    }.boxed_local()
+  }
+}
+
+/// Wall clock time, sleeping on the browser event loop.
+pub struct WebClock {}
+
+impl Clock for WebClock {
+  fn now_millis(&self) -> i64 {
+    Local::now().timestamp_millis()
+  }
+
+  fn sleep(&self, millis: u64) -> LocalBoxFuture<'static, ()> {
+    async move {
+      timeout(Duration::from_millis(millis), future::pending::<()>()).await.unwrap_err();
+    }.boxed_local()
+  }
+}
+
+/// Connects through a `WebSocket`, as browsers can't open raw TCP sockets.
+pub struct WebSocketConnector {}
+
+impl SocketConnector for WebSocketConnector {
+  fn connect(&self, host: &str, port: u16) -> SocketConnection {
+    let (outgoing_tx, outgoing_rx) = async_std::channel::unbounded::<Vec<u8>>();
+    let (event_tx, event_rx) = async_std::channel::unbounded();
+    let connection = SocketConnection { outgoing: outgoing_tx, events: event_rx };
+
+    let socket = match WebSocket::new(&format!("ws://{}:{}", host, port)) {
+      Ok(socket) => socket,
+      Err(err) => {
+        let _ = event_tx.try_send(SocketEvent::Error(format!("{:?}", err)));
+        return connection;
+      }
+    };
+    socket.set_binary_type(web_sys::BinaryType::Arraybuffer);
+
+    let message_tx = event_tx.clone();
+    let onmessage_callback = Closure::<dyn FnMut(_)>::new(move |e: MessageEvent| {
+      // The protocols spoken over sockets are binary, so a text frame means the server is not one we can talk to
+      let event = match e.data().dyn_into::<js_sys::ArrayBuffer>() {
+        Ok(data) => SocketEvent::Data(Uint8Array::new(&data).to_vec()),
+        Err(_) => SocketEvent::Error("WebSocket received a text frame instead of binary data".to_string()),
+      };
+      let _ = message_tx.try_send(event);
+    });
+    let error_tx = event_tx.clone();
+    let onerror_callback = Closure::<dyn FnMut(_)>::new(move |e: ErrorEvent| {
+      let _ = error_tx.try_send(SocketEvent::Error(e.message()));
+    });
+    let open_tx = event_tx.clone();
+    let onopen_callback = Closure::<dyn FnMut(_)>::new(move |_: Event| {
+      let _ = open_tx.try_send(SocketEvent::Connected);
+    });
+    let onclose_callback = Closure::<dyn FnMut(_)>::new(move |_: Event| {
+      let _ = event_tx.try_send(SocketEvent::Closed);
+    });
+    socket.set_onmessage(Some(onmessage_callback.as_ref().unchecked_ref()));
+    socket.set_onerror(Some(onerror_callback.as_ref().unchecked_ref()));
+    socket.set_onopen(Some(onopen_callback.as_ref().unchecked_ref()));
+    socket.set_onclose(Some(onclose_callback.as_ref().unchecked_ref()));
+
+    spawn_local(async move {
+      while let Ok(message) = outgoing_rx.recv().await {
+        if let Err(err) = socket.send_with_u8_array(&message) {
+          warn!("WebSocket send failed: {:?}", err);
+        }
+      }
+      let _ = socket.close();
+    });
+
+    // Forget the callbacks to keep them alive
+    onmessage_callback.forget();
+    onerror_callback.forget();
+    onopen_callback.forget();
+    onclose_callback.forget();
+
+    connection
+  }
+}
+
+fn local_storage() -> Result<Storage, String> {
+  web_sys::window()
+    .and_then(|window| window.local_storage().ok().flatten())
+    .ok_or_else(|| "localStorage is not available".to_string())
+}
+
+/// Keeps prefs in the `localStorage` of the page, so they outlive the session like
+/// the prefs of the Shockwave plugin.
+pub struct LocalStoragePrefStorage {}
+
+impl LocalStoragePrefStorage {
+  const KEY_PREFIX: &'static str = "dirplayer.pref:";
+}
+
+impl PrefStorage for LocalStoragePrefStorage {
+  fn get(&self, key: &str) -> Option<String> {
+    let storage = local_storage().ok()?;
+    storage.get_item(&format!("{}{}", Self::KEY_PREFIX, key)).ok().flatten()
+  }
+
+  fn set(&self, key: &str, value: &str) -> Result<(), String> {
+    // Fails when the quota of the origin is exceeded.
+    local_storage()?
+      .set_item(&format!("{}{}", Self::KEY_PREFIX, key), value)
+      .map_err(|err| format!("Could not store pref: {:?}", err))
+  }
+}
+
+/// Keeps the files of movies in the `localStorage` of the page, next to the prefs, so that
+/// FileIO saves outlive the session. Each byte is stored as the character with the same code.
+pub struct LocalStorageFileStorage {}
+
+impl LocalStorageFileStorage {
+  const KEY_PREFIX: &'static str = "dirplayer.file:";
+
+  fn get_item(path: &str) -> Result<Option<String>, FileStorageError> {
+    let storage = local_storage().map_err(FileStorageError::Io)?;
+    storage.get_item(&format!("{}{}", Self::KEY_PREFIX, path)).map_err(|err| FileStorageError::Io(format!("{:?}", err)))
+  }
+}
+
+impl FileStorage for LocalStorageFileStorage {
+  fn read(&self, path: &str) -> Result<Vec<u8>, FileStorageError> {
+    let item = Self::get_item(path)?.ok_or(FileStorageError::NotFound)?;
+    Ok(item.chars().map(|c| c as u32 as u8).collect())
+  }
+
+  fn write(&self, path: &str, data: &[u8]) -> Result<(), FileStorageError> {
+    let item: String = data.iter().map(|&byte| byte as char).collect();
+    // Fails when the quota of the origin is exceeded.
+    local_storage()
+      .map_err(FileStorageError::Io)?
+      .set_item(&format!("{}{}", Self::KEY_PREFIX, path), &item)
+      .map_err(|err| FileStorageError::Io(format!("Could not store file: {:?}", err)))
+  }
+
+  fn delete(&self, path: &str) -> Result<(), FileStorageError> {
+    Self::get_item(path)?.ok_or(FileStorageError::NotFound)?;
+    local_storage()
+      .map_err(FileStorageError::Io)?
+      .remove_item(&format!("{}{}", Self::KEY_PREFIX, path))
+      .map_err(|err| FileStorageError::Io(format!("{:?}", err)))
+  }
+
+  fn exists(&self, path: &str) -> bool {
+    Self::get_item(path).is_ok_and(|item| item.is_some())
+  }
+}
+
+pub fn web_platform() -> Platform {
+  Platform {
+    net_loader: Rc::new(WebNetLoader {}),
+    clock: Rc::new(WebClock {}),
+    sockets: Rc::new(WebSocketConnector {}),
+    files: Rc::new(LocalStorageFileStorage {}),
+    prefs: Rc::new(LocalStoragePrefStorage {}),
+  }
+}
@@ -4,7 +4,7 @@ use async_std::channel::{Receiver, Sender};
 use fxhash::FxHashMap;
 use log::warn;
 
-use crate::{console_warn, director::lingo::datum::Datum};
+use crate::{console_warn, director::lingo::datum::{Datum, StringChunkSource, VarRef}};
 
 use super::{datum_ref::{DatumId, DatumRef}, reserve_player_mut, reserve_player_ref, script::{ScriptInstance, ScriptInstanceId}, script_ref::ScriptInstanceRef, ScriptError};
 
@@ -40,9 +40,69 @@ pub trait ScriptInstanceAllocatorTrait {
   fn on_script_instance_ref_dropped(&mut self, id: ScriptInstanceId);
 }
 
+/// Number of frames after which the cycle collector runs even if the heap hasn't grown.
+const CYCLE_COLLECTION_FRAME_INTERVAL: u32 = 60;
+/// Heap size below which growth alone never triggers a collection.
+const MIN_CYCLE_COLLECTION_HEAP_SIZE: usize = 1024;
+
+/// Totals kept by the cycle collector since the allocator was last reset.
+#[derive(Clone, Copy, Default)]
+pub struct CycleCollectorStats {
+  pub collections: u32,
+  pub frames_since_collection: u32,
+  /// Number of datums and script instances left alive by the last collection.
+  pub heap_size_after_collection: usize,
+  pub datums_collected: usize,
+  pub script_instances_collected: usize,
+  pub last_datums_collected: usize,
+  pub last_script_instances_collected: usize,
+}
+
+#[derive(Clone, Copy)]
+enum HeapNode {
+  Datum(DatumId),
+  ScriptInstance(ScriptInstanceId),
+}
+
+/// Calls `f` with every datum and script instance directly referenced by a datum.
+fn for_each_datum_child(datum: &Datum, f: &mut impl FnMut(HeapNode)) {
+  let mut visit_ref = |datum_ref: &DatumRef| {
+    if let DatumRef::Ref(id, ..) = datum_ref {
+      f(HeapNode::Datum(*id));
+    }
+  };
+  match datum {
+    Datum::List(_, items, _) => items.iter().for_each(visit_ref),
+    Datum::PropList(pairs, _) => {
+      for (key, value) in pairs {
+        visit_ref(key);
+        visit_ref(value);
+      }
+    }
+    Datum::StringChunk(StringChunkSource::Datum(datum_ref), ..) => visit_ref(datum_ref),
+    Datum::ScriptInstanceRef(instance_ref) | Datum::VarRef(VarRef::ScriptInstance(instance_ref)) => {
+      f(HeapNode::ScriptInstance(**instance_ref))
+    }
+    _ => {}
+  }
+}
+
+/// Calls `f` with the ancestor and every property value of a script instance.
+fn for_each_script_instance_child(script_instance: &ScriptInstance, f: &mut impl FnMut(HeapNode)) {
+  if let Some(ancestor) = &script_instance.ancestor {
+    f(HeapNode::ScriptInstance(**ancestor));
+  }
+  for value in script_instance.properties.values() {
+    if let DatumRef::Ref(id, ..) = value {
+      f(HeapNode::Datum(*id));
+    }
+  }
+}
+
 pub struct DatumAllocator {
   pub datums: FxHashMap<DatumId, DatumRefEntry>,
   pub script_instances: FxHashMap<ScriptInstanceId, ScriptInstanceRefEntry>,
+  pub cycle_collector_stats: CycleCollectorStats,
   datum_id_counter: DatumId,
   script_instance_counter: ScriptInstanceId,
   void_datum: Datum,
@@ -56,6 +116,7 @@ impl DatumAllocator {
     DatumAllocator {
       datums: FxHashMap::default(),
       script_instances: FxHashMap::default(),
+      cycle_collector_stats: CycleCollectorStats::default(),
       datum_id_counter: 1,
       script_instance_counter: 1,
       void_datum: Datum::Void,
@@ -139,6 +200,119 @@ impl DatumAllocator {
   }
 }
 
+impl DatumAllocator {
+  /// Called once per frame, runs the cycle collector every `CYCLE_COLLECTION_FRAME_INTERVAL` frames
+  /// or sooner when the heap has doubled in size since the last collection.
+  pub fn on_frame(&mut self) {
+    let heap_size = self.datum_count() + self.script_instance_count();
+    let stats = &mut self.cycle_collector_stats;
+    stats.frames_since_collection += 1;
+    let grown = heap_size >= MIN_CYCLE_COLLECTION_HEAP_SIZE.max(stats.heap_size_after_collection * 2);
+    if grown || stats.frames_since_collection >= CYCLE_COLLECTION_FRAME_INTERVAL {
+      self.collect_cycles();
+    }
+  }
+
+  /// Frees datums and script instances that are only kept alive by reference cycles,
+  /// such as child objects pointing back at their parent or a list containing itself.
+  ///
+  /// Uses trial deletion: the references held between heap objects are subtracted from
+  /// their ref counts, so anything with a count left over is referenced from outside the
+  /// heap (globals, scopes, sprite script instances, timeouts, Xtra instances or Rust code)
+  /// and acts as a root. Everything not reachable from a root is garbage.
+  pub fn collect_cycles(&mut self) {
+    let mut internal_datum_refs: FxHashMap<DatumId, u32> = FxHashMap::default();
+    let mut internal_instance_refs: FxHashMap<ScriptInstanceId, u32> = FxHashMap::default();
+    {
+      let mut count_ref = |node: HeapNode| match node {
+        HeapNode::Datum(id) => *internal_datum_refs.entry(id).or_default() += 1,
+        HeapNode::ScriptInstance(id) => *internal_instance_refs.entry(id).or_default() += 1,
+      };
+      for entry in self.datums.values() {
+        for_each_datum_child(&entry.datum, &mut count_ref);
+      }
+      for entry in self.script_instances.values() {
+        for_each_script_instance_child(&entry.script_instance, &mut count_ref);
+      }
+    }
+
+    let mut stack: Vec<HeapNode> = vec![];
+    for (id, entry) in &self.datums {
+      let ref_count = unsafe { *entry.ref_count.get() };
+      if ref_count > internal_datum_refs.get(id).copied().unwrap_or(0) {
+        stack.push(HeapNode::Datum(*id));
+      }
+    }
+    for (id, entry) in &self.script_instances {
+      let ref_count = unsafe { *entry.ref_count.get() };
+      if ref_count > internal_instance_refs.get(id).copied().unwrap_or(0) {
+        stack.push(HeapNode::ScriptInstance(*id));
+      }
+    }
+
+    let mut live_datums: FxHashMap<DatumId, ()> = FxHashMap::default();
+    let mut live_instances: FxHashMap<ScriptInstanceId, ()> = FxHashMap::default();
+    while let Some(node) = stack.pop() {
+      match node {
+        HeapNode::Datum(id) => {
+          if live_datums.insert(id, ()).is_some() {
+            continue;
+          }
+          if let Some(entry) = self.datums.get(&id) {
+            for_each_datum_child(&entry.datum, &mut |child| stack.push(child));
+          }
+        }
+        HeapNode::ScriptInstance(id) => {
+          if live_instances.insert(id, ()).is_some() {
+            continue;
+          }
+          if let Some(entry) = self.script_instances.get(&id) {
+            for_each_script_instance_child(&entry.script_instance, &mut |child| stack.push(child));
+          }
+        }
+      }
+    }
+
+    let garbage_datum_ids: Vec<DatumId> = self.datums.keys()
+      .filter(|id| !live_datums.contains_key(id))
+      .copied()
+      .collect();
+    let garbage_instance_ids: Vec<ScriptInstanceId> = self.script_instances.keys()
+      .filter(|id| !live_instances.contains_key(id))
+      .copied()
+      .collect();
+
+    let stats = &mut self.cycle_collector_stats;
+    stats.collections += 1;
+    stats.frames_since_collection = 0;
+    stats.heap_size_after_collection = self.datums.len() + self.script_instances.len()
+      - garbage_datum_ids.len() - garbage_instance_ids.len();
+    stats.last_datums_collected = garbage_datum_ids.len();
+    stats.last_script_instances_collected = garbage_instance_ids.len();
+    stats.datums_collected += garbage_datum_ids.len();
+    stats.script_instances_collected += garbage_instance_ids.len();
+    if garbage_datum_ids.is_empty() && garbage_instance_ids.is_empty() {
+      return;
+    }
+
+    // Unlink the garbage before dropping it so the references it holds to other garbage
+    // don't free anything twice. The ref count cells are kept alive until every reference
+    // pointing at them has been dropped.
+    let garbage_datums: Vec<DatumRefEntry> = garbage_datum_ids.iter()
+      .filter_map(|id| self.datums.remove(id))
+      .collect();
+    let garbage_instances: Vec<ScriptInstanceRefEntry> = garbage_instance_ids.iter()
+      .filter_map(|id| self.script_instances.remove(id))
+      .collect();
+    let ref_counts: Vec<Rc<UnsafeCell<u32>>> = garbage_datums.iter().map(|x| x.ref_count.clone())
+      .chain(garbage_instances.iter().map(|x| x.ref_count.clone()))
+      .collect();
+    drop(garbage_datums);
+    drop(garbage_instances);
+    drop(ref_counts);
+  }
+}
+
 impl DatumAllocatorTrait for DatumAllocator {
   fn alloc_datum(&mut self, datum: Datum) -> Result<DatumRef, ScriptError> {
     if datum.is_void() {
@@ -234,5 +408,127 @@ impl ResetableAllocator for DatumAllocator {
     self.datum_id_counter = 1;
     self.script_instances.clear();
     self.script_instance_counter = 1;
+    self.cycle_collector_stats = CycleCollectorStats::default();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{director::lingo::datum::DatumType, player::{cast_lib::INVALID_CAST_MEMBER_REF, testing::with_test_player}};
+
+  fn datum_id(datum_ref: &DatumRef) -> DatumId {
+    match datum_ref {
+      DatumRef::Ref(id, ..) => *id,
+      DatumRef::Void => panic!("Expected a datum ref"),
+    }
+  }
+
+  fn alloc_self_containing_list(allocator: &mut DatumAllocator) -> DatumRef {
+    let list = allocator.alloc_datum(Datum::List(DatumType::List, vec![], false)).unwrap();
+    let item = list.clone();
+    match allocator.get_datum_mut(&list) {
+      Datum::List(_, items, _) => items.push(item),
+      _ => unreachable!(),
+    }
+    list
+  }
+
+  fn alloc_instance(allocator: &mut DatumAllocator, ancestor: Option<ScriptInstanceRef>) -> ScriptInstanceRef {
+    allocator.alloc_script_instance(ScriptInstance {
+      instance_id: 0,
+      script: INVALID_CAST_MEMBER_REF,
+      ancestor,
+      properties: FxHashMap::default(),
+    })
+  }
+
+  #[test]
+  fn frees_a_list_containing_itself() {
+    with_test_player(|_| {
+      reserve_player_mut(|player| {
+        let allocator = &mut player.allocator;
+        let list = alloc_self_containing_list(allocator);
+        let id = datum_id(&list);
+        drop(list);
+        assert!(allocator.get_datum_ref(id).is_some());
+
+        allocator.collect_cycles();
+        assert!(allocator.get_datum_ref(id).is_none());
+        assert_eq!(allocator.cycle_collector_stats.last_datums_collected, 1);
+      });
+    });
+  }
+
+  #[test]
+  fn frees_a_child_object_pointing_back_at_its_ancestor() {
+    with_test_player(|_| {
+      reserve_player_mut(|player| {
+        let allocator = &mut player.allocator;
+        let parent = alloc_instance(allocator, None);
+        let child = alloc_instance(allocator, Some(parent.clone()));
+        let child_datum = allocator.alloc_datum(Datum::ScriptInstanceRef(child.clone())).unwrap();
+        allocator.get_script_instance_mut(&parent).properties.insert("child".to_string(), child_datum);
+        let (parent_id, child_id) = (*parent, *child);
+        drop(parent);
+        drop(child);
+        assert!(allocator.get_script_instance_ref(parent_id).is_some());
+
+        allocator.collect_cycles();
+        assert!(allocator.get_script_instance_ref(parent_id).is_none());
+        assert!(allocator.get_script_instance_ref(child_id).is_none());
+        assert_eq!(allocator.cycle_collector_stats.last_script_instances_collected, 2);
+        assert_eq!(allocator.cycle_collector_stats.last_datums_collected, 1);
+      });
+    });
+  }
+
+  #[test]
+  fn keeps_cycles_that_are_still_reachable() {
+    with_test_player(|_| {
+      reserve_player_mut(|player| {
+        let allocator = &mut player.allocator;
+        let list = alloc_self_containing_list(allocator);
+        let instance = alloc_instance(allocator, None);
+        let nested = alloc_self_containing_list(allocator);
+        let nested_id = datum_id(&nested);
+        allocator.get_script_instance_mut(&instance).properties.insert("items".to_string(), nested);
+
+        allocator.collect_cycles();
+        assert!(allocator.get_datum_ref(datum_id(&list)).is_some());
+        assert!(allocator.get_script_instance_ref(*instance).is_some());
+        assert!(allocator.get_datum_ref(nested_id).is_some());
+        match allocator.get_datum(&list) {
+          Datum::List(_, items, _) => assert_eq!(datum_id(&items[0]), datum_id(&list)),
+          _ => unreachable!(),
+        }
+        assert_eq!(allocator.cycle_collector_stats.last_datums_collected, 0);
+      });
+    });
+  }
+
+  #[test]
+  fn collects_every_interval_or_when_the_heap_grows() {
+    with_test_player(|_| {
+      reserve_player_mut(|player| {
+        let allocator = &mut player.allocator;
+        allocator.collect_cycles();
+        for _ in 1..CYCLE_COLLECTION_FRAME_INTERVAL {
+          allocator.on_frame();
+        }
+        assert_eq!(allocator.cycle_collector_stats.collections, 1);
+        allocator.on_frame();
+        assert_eq!(allocator.cycle_collector_stats.collections, 2);
+
+        let datums: Vec<DatumRef> = (0..MIN_CYCLE_COLLECTION_HEAP_SIZE)
+          .map(|i| allocator.alloc_datum(Datum::Int(i as i32)).unwrap())
+          .collect();
+        allocator.on_frame();
+        assert_eq!(allocator.cycle_collector_stats.collections, 3);
+        allocator.on_frame();
+        assert_eq!(allocator.cycle_collector_stats.collections, 3);
+        drop(datums);
+      });
+    });
   }
 }
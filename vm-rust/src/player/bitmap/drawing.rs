@@ -3,7 +3,7 @@ use std::collections::HashMap;
 use nohash_hasher::IntMap;
 use rgb565::Rgb565;
 
-use crate::{director::lingo::datum::Datum, player::{font::{bitmap_font_copy_char, BitmapFont}, geometry::IntRect, sprite::ColorRef}};
+use crate::{director::{enums::{ShapeInfo, ShapeType}, lingo::datum::Datum}, player::{ScriptError, font::{bitmap_font_copy_char, BitmapFont}, geometry::{IntRect, Transform2D}, sprite::ColorRef}};
 
 use super::{bitmap::{resolve_color_ref, Bitmap}, mask::BitmapMask, palette_map::PaletteMap};
 
@@ -13,6 +13,10 @@ pub struct CopyPixelsParams<'a> {
     pub color: ColorRef,
     pub bg_color: ColorRef,
     pub mask_image: Option<&'a BitmapMask>,
+    /// Applied to the destination rect, for rotated, skewed and quad-mapped copies.
+    pub transform: Option<Transform2D>,
+    /// Samples transformed copies with bilinear filtering instead of nearest-neighbour.
+    pub bilinear: bool,
 }
 
 impl CopyPixelsParams<'_> {
@@ -23,6 +27,8 @@ impl CopyPixelsParams<'_> {
             color: bitmap.get_fg_color_ref(),
             bg_color: bitmap.get_bg_color_ref(),
             mask_image: None,
+            transform: None,
+            bilinear: false,
         }
     }
 }
@@ -47,69 +53,244 @@ pub fn should_matte_sprite(ink: u32) -> bool {
     ink == 36 || ink == 33 || ink == 41 || ink == 8 || ink == 7
 }
 
+/// Whether the ink draws the sprite through the mask taken from the next cast member.
+pub fn should_mask_sprite(ink: u32) -> bool {
+    ink == 9
+}
+
+/// The inputs of `copy_pixels_transformed`, resolved from the `CopyPixelsParams` of the copy.
+struct TransformedCopy<'a> {
+    src: &'a Bitmap,
+    dst_rect: IntRect,
+    src_rect: IntRect,
+    transform: Transform2D,
+    mask_image: Option<&'a BitmapMask>,
+    colors: InkColors,
+    ink: u32,
+    alpha: f32,
+    bilinear: bool,
+}
+
+/// The colors an ink is drawn with, resolved against the destination palette.
+struct InkColors {
+    fg: (u8, u8, u8),
+    bg: (u8, u8, u8),
+    /// Whether a foreground or background color other than black and white tints the source.
+    colorize: bool,
+}
+
+fn map_channels(a: (u8, u8, u8), b: (u8, u8, u8), f: impl Fn(u8, u8) -> u8) -> (u8, u8, u8) {
+    (f(a.0, b.0), f(a.1, b.1), f(a.2, b.2))
+}
+
+fn invert_color(color: (u8, u8, u8)) -> (u8, u8, u8) {
+    (!color.0, !color.1, !color.2)
+}
+
+/// Maps black source pixels to the foreground color and white ones to the background color,
+/// interpolating the shades in between.
+fn colorize_pixel(src: (u8, u8, u8), colors: &InkColors) -> (u8, u8, u8) {
+    if !colors.colorize {
+        return src;
+    }
+    let lerp = |fg: u8, bg: u8, value: u8| {
+        ((fg as u32 * (255 - value as u32) + bg as u32 * value as u32) / 255) as u8
+    };
+    (
+        lerp(colors.fg.0, colors.bg.0, src.0),
+        lerp(colors.fg.1, colors.bg.1, src.1),
+        lerp(colors.fg.2, colors.bg.2, src.2),
+    )
+}
+
 fn blend_pixel(
     dst: (u8, u8, u8), 
     src: (u8, u8, u8), 
     ink: u32,
-    bg_color: (u8, u8, u8),
+    colors: &InkColors,
     alpha: f32,
 ) -> (u8, u8, u8) {
+    let color = colorize_pixel(src, colors);
     match ink {
-        0 => {
-            // Copy
-            blend_color_alpha(dst, src, alpha)
+        0 | 8 | 9 | 32 => {
+            // Copy, Matte, Mask and Blend. Matte and Mask pixels are masked out before reaching here.
+            blend_color_alpha(dst, color, alpha)
         }
-        7 => {
-            // Not Ghost
-            // TODO
-            blend_color_alpha(dst, src, alpha)
+        1 => {
+            // Transparent: white source pixels let the destination show through
+            blend_color_alpha(dst, map_channels(dst, color, |d, s| d & s), alpha)
+        }
+        2 => {
+            // Reverse: black source pixels invert the destination
+            blend_color_alpha(dst, map_channels(dst, color, |d, s| d ^ !s), alpha)
+        }
+        3 => {
+            // Ghost: black source pixels erase the destination to the background color
+            blend_color_alpha(dst, map_channels(dst, color, |d, s| d | !s), alpha)
+        }
+        4 => {
+            // Not Copy
+            blend_color_alpha(dst, invert_color(color), alpha)
         }
-        8 => {
-            // Matte
-            // TODO
-            blend_color_alpha(dst, src, alpha)
+        5 => {
+            // Not Transparent
+            blend_color_alpha(dst, map_channels(dst, color, |d, s| d & !s), alpha)
         }
-        9 => {
-            // Mask
-            // TODO
-            blend_color_alpha(dst, src, alpha)
+        6 => {
+            // Not Reverse
+            blend_color_alpha(dst, map_channels(dst, color, |d, s| d ^ s), alpha)
+        }
+        7 => {
+            // Not Ghost
+            blend_color_alpha(dst, map_channels(dst, color, |d, s| d | s), alpha)
         }
         33 => {
             // Add pin
-            if src == bg_color {
+            if color == colors.bg {
                 dst
             } else {
-                let r = dst.0 as i32 + src.0 as i32;
-                let g = dst.1 as i32 + src.1 as i32;
-                let b = dst.2 as i32 + src.2 as i32;
-                let r = r.min(255).max(0) as u8;
-                let g = g.min(255).max(0) as u8;
-                let b = b.min(255).max(0) as u8;
-                (r, g, b)
+                let sum = map_channels(dst, color, |d, s| d.saturating_add(s));
+                blend_color_alpha(dst, sum, alpha)
             }
         }
+        34 => {
+            // Add: channels wrap around
+            blend_color_alpha(dst, map_channels(dst, color, |d, s| d.wrapping_add(s)), alpha)
+        }
+        35 => {
+            // Subtract pin
+            blend_color_alpha(dst, map_channels(dst, color, |d, s| d.saturating_sub(s)), alpha)
+        }
         36 => {
             // Background transparent
-            if src == bg_color {
+            if color == colors.bg {
                 dst
             } else {
-                blend_color_alpha(dst, src, alpha)
+                blend_color_alpha(dst, color, alpha)
             }
         }
+        37 => {
+            // Lightest
+            blend_color_alpha(dst, map_channels(dst, color, |d, s| d.max(s)), alpha)
+        }
+        38 => {
+            // Subtract: channels wrap around
+            blend_color_alpha(dst, map_channels(dst, color, |d, s| d.wrapping_sub(s)), alpha)
+        }
+        39 => {
+            // Darkest
+            blend_color_alpha(dst, map_channels(dst, color, |d, s| d.min(s)), alpha)
+        }
+        40 => {
+            // Lighten: the source is screened with the foreground color, then multiplied by the background color
+            let screened = map_channels(src, colors.fg, |s, fg| 255 - ((255 - s as u32) * (255 - fg as u32) / 255) as u8);
+            let color = map_channels(screened, colors.bg, |s, bg| (s as u32 * bg as u32 / 255) as u8);
+            blend_color_alpha(dst, color, alpha)
+        }
         41 => {
-            // Darken
-            // TODO
-            // bg_color
-            let r = (src.0 as f32 / 255.0) * (bg_color.0 as f32 / 255.0) * 255.0;
-            let g = (src.1 as f32 / 255.0) * (bg_color.1 as f32 / 255.0) * 255.0;
-            let b = (src.2 as f32 / 255.0) * (bg_color.2 as f32 / 255.0) * 255.0;
-            let color = (r as u8, g as u8, b as u8);
+            // Darken: the source is multiplied by the background color, then the foreground color is added
+            let multiplied = map_channels(src, colors.bg, |s, bg| (s as u32 * bg as u32 / 255) as u8);
+            let color = map_channels(multiplied, colors.fg, |s, fg| s.saturating_add(fg));
             blend_color_alpha(dst, color, alpha)
         }
-        _ => blend_color_alpha(dst, src, alpha),
+        _ => blend_color_alpha(dst, color, alpha),
+    }
+}
+
+/// The 8x8 fill patterns numbered 1 to 38 in the tool palette, which are the standard Macintosh system
+/// patterns. One byte per row with the leftmost pixel in the high bit, set bits are drawn with the
+/// foreground color and clear bits with the background color.
+const SHAPE_PATTERNS: [[u8; 8]; 38] = [
+    [0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+    [0xDD, 0xFF, 0x77, 0xFF, 0xDD, 0xFF, 0x77, 0xFF],
+    [0xDD, 0x77, 0xDD, 0x77, 0xDD, 0x77, 0xDD, 0x77],
+    [0xAA, 0xFF, 0xAA, 0xFF, 0xAA, 0xFF, 0xAA, 0xFF],
+    [0x55, 0xFF, 0x55, 0xFF, 0x55, 0xFF, 0x55, 0xFF],
+    [0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA],
+    [0xEE, 0xDD, 0xBB, 0x77, 0xEE, 0xDD, 0xBB, 0x77],
+    [0x88, 0x88, 0x88, 0x88, 0x88, 0x88, 0x88, 0x88],
+    [0xB1, 0x30, 0x03, 0x1B, 0xD8, 0xC0, 0x0C, 0x8D],
+    [0x80, 0x10, 0x02, 0x20, 0x01, 0x08, 0x40, 0x04],
+    [0xFF, 0x88, 0x88, 0x88, 0xFF, 0x88, 0x88, 0x88],
+    [0xFF, 0x80, 0x80, 0x80, 0xFF, 0x08, 0x08, 0x08],
+    [0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x80, 0x40, 0x20, 0x00, 0x02, 0x04, 0x08, 0x00],
+    [0x82, 0x44, 0x39, 0x44, 0x82, 0x01, 0x01, 0x01],
+    [0xF8, 0x74, 0x22, 0x47, 0x8F, 0x17, 0x22, 0x71],
+    [0x55, 0xA0, 0x40, 0x40, 0x55, 0x0A, 0x04, 0x04],
+    [0x20, 0x50, 0x88, 0x88, 0x88, 0x88, 0x05, 0x02],
+    [0xBF, 0x00, 0xBF, 0xBF, 0xB0, 0xB0, 0xB0, 0xB0],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x80, 0x00, 0x08, 0x00, 0x80, 0x00, 0x08, 0x00],
+    [0x88, 0x00, 0x22, 0x00, 0x88, 0x00, 0x22, 0x00],
+    [0x88, 0x22, 0x88, 0x22, 0x88, 0x22, 0x88, 0x22],
+    [0xAA, 0x00, 0xAA, 0x00, 0xAA, 0x00, 0xAA, 0x00],
+    [0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00],
+    [0x11, 0x22, 0x44, 0x88, 0x11, 0x22, 0x44, 0x88],
+    [0xFF, 0x00, 0x00, 0x00, 0xFF, 0x00, 0x00, 0x00],
+    [0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80],
+    [0xAA, 0x00, 0x80, 0x00, 0x88, 0x00, 0x80, 0x00],
+    [0xFF, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80],
+    [0x08, 0x1C, 0x22, 0xC1, 0x80, 0x01, 0x02, 0x04],
+    [0x88, 0x14, 0x22, 0x41, 0x88, 0x00, 0xAA, 0x00],
+    [0x40, 0xA0, 0x00, 0x00, 0x04, 0x0A, 0x00, 0x00],
+    [0x03, 0x84, 0x48, 0x30, 0x0C, 0x02, 0x01, 0x01],
+    [0x80, 0x80, 0x41, 0x3E, 0x08, 0x08, 0x14, 0xE3],
+    [0x10, 0x20, 0x54, 0xAA, 0xFF, 0x02, 0x04, 0x08],
+    [0x77, 0x89, 0x8F, 0x8F, 0x77, 0x98, 0xF8, 0xF8],
+    [0x00, 0x08, 0x14, 0x2A, 0x55, 0x2A, 0x14, 0x08],
+];
+
+/// Returns the rows of a shape pattern number, 0 being solid like 1. Director's own patterns past the
+/// system ones and the tiles made from cast members aren't known and return `None`.
+pub fn shape_pattern(pattern: u16) -> Option<&'static [u8; 8]> {
+    SHAPE_PATTERNS.get((pattern as usize).max(1) - 1)
+}
+
+/// Whether the pattern has its bit set at the given destination position.
+/// Patterns are aligned to the destination so that adjacent shapes tile seamlessly.
+fn pattern_bit(rows: &[u8; 8], x: i32, y: i32) -> bool {
+    rows[y.rem_euclid(8) as usize] & (0x80 >> x.rem_euclid(8)) != 0
+}
+
+/// Whether a point lies inside a rect, round rect or oval spanning the given left, top, right and bottom edges.
+fn shape_contains(shape_type: ShapeType, edges: (f64, f64, f64, f64), corner_radius: f64, x: f64, y: f64) -> bool {
+    let (left, top, right, bottom) = edges;
+    if x < left || x >= right || y < top || y >= bottom {
+        return false;
+    }
+    match shape_type {
+        ShapeType::Oval => {
+            let rx = (right - left) / 2.0;
+            let ry = (bottom - top) / 2.0;
+            let dx = (x - left - rx) / rx;
+            let dy = (y - top - ry) / ry;
+            dx * dx + dy * dy <= 1.0
+        }
+        ShapeType::OvalRect => {
+            // Only the corners are rounded, test against the circle of the nearest corner.
+            let radius = corner_radius.min((right - left) / 2.0).min((bottom - top) / 2.0).max(0.0);
+            let cx = x.clamp(left + radius, right - radius);
+            let cy = y.clamp(top + radius, bottom - radius);
+            (x - cx) * (x - cx) + (y - cy) * (y - cy) <= radius * radius
+        }
+        _ => true,
     }
 }
 
+/// Distance from a point to the line segment between two points.
+fn distance_to_segment(x: f64, y: f64, from: (f64, f64), to: (f64, f64)) -> f64 {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let length_squared = dx * dx + dy * dy;
+    let t = if length_squared == 0.0 {
+        0.0
+    } else {
+        (((x - from.0) * dx + (y - from.1) * dy) / length_squared).clamp(0.0, 1.0)
+    };
+    let (px, py) = (from.0 + t * dx, from.1 + t * dy);
+    ((x - px) * (x - px) + (y - py) * (y - py)).sqrt()
+}
+
 impl Bitmap {
     pub fn set_pixel(&mut self, x: i32, y: i32, color: (u8, u8, u8), palettes: &PaletteMap) {
         if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
@@ -404,40 +585,51 @@ impl Bitmap {
         dst_rect: IntRect,
         src_rect: IntRect,
         param_list: &HashMap<String, Datum>,
-    ) {
+        transform: Option<Transform2D>,
+    ) -> Result<(), ScriptError> {
         let blend = param_list.get("blend")
-            .map(|x| x.int_value().unwrap())
+            .map(|x| x.int_value())
+            .transpose()?
             .unwrap_or(100);
         let ink = param_list.get("ink");
         let ink = if let Some(ink) = ink {
-            ink.int_value().unwrap() as u32
+            ink.int_value()? as u32
         } else {
             0
         };
         let bg_color = param_list.get("bgColor");
         let bg_color = if let Some(bg_color) = bg_color {
-            bg_color.to_color_ref().unwrap().to_owned()
+            bg_color.to_color_ref()?.to_owned()
         } else {
             ColorRef::PaletteIndex(0)
         };
         let color = param_list.get("color");
         let color = if let Some(color) = color {
-            color.to_color_ref().unwrap().to_owned()
+            color.to_color_ref()?.to_owned()
         } else {
             ColorRef::PaletteIndex(255)
         };
 
         let mask_image = param_list.get("maskImage");
-        let mask_image = mask_image.map(|x| x.to_mask().unwrap());
+        let mask_image = mask_image.map(|x| x.to_mask()).transpose()?;
+        // Matte ink without a mask image hides the white around the source, so it needs the source's matte.
+        let src_matte = if ink == 8 && mask_image.is_none() && src.matte.is_none() {
+            Some(src.build_matte(palettes))
+        } else {
+            None
+        };
         
         let params = CopyPixelsParams {
             blend,
             ink,
             bg_color,
-            mask_image,
+            mask_image: mask_image.or(src_matte.as_ref()),
             color,
+            transform,
+            bilinear: false,
         };
         self.copy_pixels_with_params(palettes, src, dst_rect, src_rect, &params);
+        Ok(())
     }
 
     pub fn copy_pixels_with_params(
@@ -450,9 +642,30 @@ impl Bitmap {
     ) {
         let ink = params.ink;
         let alpha = params.blend as f32 / 100.0;
-        let mask_image = params.mask_image;
-        let bg_color = &params.bg_color;
-        let bg_color = resolve_color_ref(palettes, &bg_color, &self.palette_ref);
+        // Matte ink hides the white pixels surrounding the image, using the source matte if none is given.
+        let mask_image = params.mask_image.or(if ink == 8 { src.matte.as_deref() } else { None });
+        let fg_color = resolve_color_ref(palettes, &params.color, &self.palette_ref);
+        let bg_color = resolve_color_ref(palettes, &params.bg_color, &self.palette_ref);
+        let colors = InkColors {
+            fg: fg_color,
+            bg: bg_color,
+            colorize: fg_color != (0, 0, 0) || bg_color != (255, 255, 255),
+        };
+        if let Some(transform) = params.transform {
+            let copy = TransformedCopy {
+                src,
+                dst_rect,
+                src_rect,
+                transform,
+                mask_image,
+                colors,
+                ink,
+                alpha,
+                bilinear: params.bilinear,
+            };
+            self.copy_pixels_transformed(palettes, &copy);
+            return;
+        }
 
         let mut src_y = if dst_rect.height() < 0 { src_rect.bottom } else { src_rect.top } as f32;
         let step_x = src_rect.width() as f32 / dst_rect.width() as f32;
@@ -484,7 +697,7 @@ impl Bitmap {
                 }
                 let src_color = src.get_pixel_color(palettes, src_x.floor() as u16, src_y.floor() as u16);
                 let dst_color = self.get_pixel_color(palettes, dst_x as u16, dst_y as u16);
-                let blended_color = blend_pixel(dst_color, src_color, ink, bg_color, alpha);
+                let blended_color = blend_pixel(dst_color, src_color, ink, &colors, alpha);
 
                 self.set_pixel(dst_x, dst_y, blended_color, palettes);
                 src_x += step_x;
@@ -495,6 +708,151 @@ impl Bitmap {
         // self.stroke_rect(min_dst_x, min_dst_y, max_dst_x, max_dst_y, (0, 255, 0), palettes, 1.0);
     }
 
+    /// Copies `src_rect` onto `dst_rect` mapped through `transform`, walking the destination pixels
+    /// covered by the transformed rect and sampling the source at their inverse-mapped position.
+    fn copy_pixels_transformed(&mut self, palettes: &PaletteMap, copy: &TransformedCopy) {
+        let TransformedCopy { src, dst_rect, src_rect, transform, mask_image, colors, ink, alpha, bilinear } = copy;
+        let inverse = match transform.inverse() {
+            Some(inverse) => inverse,
+            None => return,
+        };
+        if dst_rect.width() == 0 || dst_rect.height() == 0 {
+            return;
+        }
+        let bounds = transform
+            .bounding_rect(dst_rect)
+            .intersect(&IntRect::from(0, 0, self.width as i32, self.height as i32));
+
+        for dst_y in bounds.top..bounds.bottom {
+            for dst_x in bounds.left..bounds.right {
+                let (x, y) = inverse.apply(dst_x as f64 + 0.5, dst_y as f64 + 0.5);
+                // Relative position inside the destination rect, which is reversed when flipped.
+                let u = (x - dst_rect.left as f64) / dst_rect.width() as f64;
+                let v = (y - dst_rect.top as f64) / dst_rect.height() as f64;
+                if !(0.0..1.0).contains(&u) || !(0.0..1.0).contains(&v) {
+                    continue;
+                }
+                let src_x = src_rect.left as f64 + u * src_rect.width() as f64;
+                let src_y = src_rect.top as f64 + v * src_rect.height() as f64;
+                if let Some(mask_image) = mask_image {
+                    if !mask_image.get_bit(src_x as u16, src_y as u16) {
+                        continue;
+                    }
+                }
+                let src_color = if *bilinear {
+                    src.sample_bilinear(palettes, src_rect, src_x, src_y)
+                } else {
+                    src.get_pixel_color(palettes, src_x.floor() as u16, src_y.floor() as u16)
+                };
+                let dst_color = self.get_pixel_color(palettes, dst_x as u16, dst_y as u16);
+                let blended_color = blend_pixel(dst_color, src_color, *ink, colors, *alpha);
+                self.set_pixel(dst_x, dst_y, blended_color, palettes);
+            }
+        }
+    }
+
+    /// Rasterizes a shape member into `dst_rect`. The border is `line_size` pixels wide and drawn
+    /// with the foreground color, the inside is filled with the shape's pattern when filled.
+    /// Patterns that aren't known are filled solid, a warning is logged when the member gets one.
+    pub fn draw_shape(
+        &mut self,
+        palettes: &PaletteMap,
+        shape_info: &ShapeInfo,
+        dst_rect: IntRect,
+        params: &CopyPixelsParams,
+    ) {
+        let alpha = params.blend as f32 / 100.0;
+        let colors = InkColors {
+            fg: resolve_color_ref(palettes, &params.color, &self.palette_ref),
+            bg: resolve_color_ref(palettes, &params.bg_color, &self.palette_ref),
+            colorize: false,
+        };
+        let inverse = match params.transform {
+            Some(transform) => match transform.inverse() {
+                Some(inverse) => Some(inverse),
+                None => return,
+            },
+            None => None,
+        };
+        let bitmap_rect = IntRect::from(0, 0, self.width as i32, self.height as i32);
+        let bounds = match &params.transform {
+            Some(transform) => transform.bounding_rect(&dst_rect).intersect(&bitmap_rect),
+            None => dst_rect.intersect(&bitmap_rect),
+        };
+
+        let (left, top) = (dst_rect.left as f64, dst_rect.top as f64);
+        let (right, bottom) = (dst_rect.right as f64, dst_rect.bottom as f64);
+        let line_size = shape_info.line_size as f64;
+        if shape_info.shape_type == ShapeType::Line && line_size == 0.0 {
+            return;
+        }
+        let pattern = shape_pattern(shape_info.pattern).unwrap_or(&SHAPE_PATTERNS[0]);
+        let corner_radius = (dst_rect.width().min(dst_rect.height()) / 4) as f64;
+        let (line_from, line_to) = if shape_info.line_direction == 1 {
+            ((left + 0.5, bottom - 0.5), (right - 0.5, top + 0.5))
+        } else {
+            ((left + 0.5, top + 0.5), (right - 0.5, bottom - 0.5))
+        };
+
+        for dst_y in bounds.top..bounds.bottom {
+            for dst_x in bounds.left..bounds.right {
+                let (x, y) = match &inverse {
+                    Some(inverse) => inverse.apply(dst_x as f64 + 0.5, dst_y as f64 + 0.5),
+                    None => (dst_x as f64 + 0.5, dst_y as f64 + 0.5),
+                };
+                let color = if shape_info.shape_type == ShapeType::Line {
+                    if distance_to_segment(x, y, line_from, line_to) > line_size / 2.0 {
+                        continue;
+                    }
+                    colors.fg
+                } else {
+                    if !shape_contains(shape_info.shape_type, (left, top, right, bottom), corner_radius, x, y) {
+                        continue;
+                    }
+                    let inside = shape_contains(
+                        shape_info.shape_type,
+                        (left + line_size, top + line_size, right - line_size, bottom - line_size),
+                        corner_radius - line_size,
+                        x,
+                        y,
+                    );
+                    if !inside {
+                        colors.fg
+                    } else if !shape_info.filled {
+                        continue;
+                    } else if pattern_bit(pattern, dst_x, dst_y) {
+                        colors.fg
+                    } else {
+                        colors.bg
+                    }
+                };
+                let dst_color = self.get_pixel_color(palettes, dst_x as u16, dst_y as u16);
+                let blended_color = blend_pixel(dst_color, color, params.ink, &colors, alpha);
+                self.set_pixel(dst_x, dst_y, blended_color, palettes);
+            }
+        }
+    }
+
+    /// Interpolates the four pixels around a position, clamping to the given rect.
+    fn sample_bilinear(&self, palettes: &PaletteMap, rect: &IntRect, x: f64, y: f64) -> (u8, u8, u8) {
+        let (min_x, max_x) = (rect.left.min(rect.right), rect.left.max(rect.right) - 1);
+        let (min_y, max_y) = (rect.top.min(rect.bottom), rect.top.max(rect.bottom) - 1);
+        let x = x - 0.5;
+        let y = y - 0.5;
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let fx = (x - x0) as f32;
+        let fy = (y - y0) as f32;
+        let pixel = |px: f64, py: f64| {
+            let px = (px as i32).clamp(min_x, max_x.max(min_x));
+            let py = (py as i32).clamp(min_y, max_y.max(min_y));
+            self.get_pixel_color(palettes, px as u16, py as u16)
+        };
+        let top = blend_color_alpha(pixel(x0, y0), pixel(x0 + 1.0, y0), fx);
+        let bottom = blend_color_alpha(pixel(x0, y0 + 1.0), pixel(x0 + 1.0, y0 + 1.0), fx);
+        blend_color_alpha(top, bottom, fy)
+    }
+
     pub fn _draw_bitmap(
         &mut self,
         palettes: &PaletteMap,
@@ -506,7 +864,7 @@ impl Bitmap {
         ink: u32, 
         bg_color: (u8, u8, u8),
         alpha: f32,
-    ) {
+    ) -> Result<(), ScriptError> {
         let mut params = HashMap::new();
         params.insert("blend".to_owned(), Datum::Int((alpha * 100.0) as i32));
         params.insert("ink".to_owned(), Datum::Int(ink as i32));
@@ -514,7 +872,7 @@ impl Bitmap {
 
         let src_rect = IntRect::from_tuple((0, 0, bitmap.width as i32, bitmap.height as i32));
         let dst_rect = IntRect::from_tuple((loc_h, loc_v, loc_h + width as i32, loc_v + height as i32));
-        self.copy_pixels(palettes, bitmap, dst_rect, src_rect, &params);
+        self.copy_pixels(palettes, bitmap, dst_rect, src_rect, &params, None)
     }
 
     pub fn draw_text(
@@ -642,3 +1000,276 @@ impl Bitmap {
         mask
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::bitmap::bitmap::{get_system_default_palette, PaletteRef};
+
+    const BLACK: (u8, u8, u8) = (0, 0, 0);
+    const WHITE: (u8, u8, u8) = (255, 255, 255);
+    const GRAY: (u8, u8, u8) = (100, 150, 200);
+    const DST: (u8, u8, u8) = (200, 100, 50);
+
+    fn bitmap_with_pixels(pixels: &[(u8, u8, u8)], palettes: &PaletteMap) -> Bitmap {
+        let mut bitmap = Bitmap::new(pixels.len() as u16, 1, 32, PaletteRef::BuiltIn(get_system_default_palette()));
+        for (x, color) in pixels.iter().enumerate() {
+            bitmap.set_pixel(x as i32, 0, *color, palettes);
+        }
+        bitmap
+    }
+
+    /// Copies black, white and gray pixels onto a colored destination and returns the result.
+    fn copy_with(ink: u32, blend: i32, color: (u8, u8, u8), bg_color: (u8, u8, u8)) -> Vec<(u8, u8, u8)> {
+        let palettes = PaletteMap::new();
+        let src = bitmap_with_pixels(&[BLACK, WHITE, GRAY], &palettes);
+        let mut dst = bitmap_with_pixels(&[DST, DST, DST], &palettes);
+        let params = CopyPixelsParams {
+            blend,
+            ink,
+            color: ColorRef::Rgb(color.0, color.1, color.2),
+            bg_color: ColorRef::Rgb(bg_color.0, bg_color.1, bg_color.2),
+            mask_image: None,
+            transform: None,
+            bilinear: false,
+        };
+        dst.copy_pixels_with_params(&palettes, &src, IntRect::from(0, 0, 3, 1), IntRect::from(0, 0, 3, 1), &params);
+        (0..3).map(|x| dst.get_pixel_color(&palettes, x, 0)).collect()
+    }
+
+    fn copy_with_ink(ink: u32) -> Vec<(u8, u8, u8)> {
+        copy_with(ink, 100, BLACK, WHITE)
+    }
+
+    #[test]
+    fn copy_inks() {
+        assert_eq!(copy_with_ink(0), vec![BLACK, WHITE, GRAY]);
+        assert_eq!(copy_with_ink(8), vec![BLACK, WHITE, GRAY]);
+        assert_eq!(copy_with_ink(32), vec![BLACK, WHITE, GRAY]);
+        assert_eq!(copy_with_ink(4), vec![WHITE, BLACK, (155, 105, 55)]);
+    }
+
+    #[test]
+    fn logical_inks() {
+        assert_eq!(copy_with_ink(1), vec![BLACK, DST, (64, 4, 0)]);
+        assert_eq!(copy_with_ink(2), vec![(55, 155, 205), DST, (83, 13, 5)]);
+        assert_eq!(copy_with_ink(3), vec![WHITE, DST, (219, 109, 55)]);
+        assert_eq!(copy_with_ink(5), vec![DST, BLACK, (136, 96, 50)]);
+        assert_eq!(copy_with_ink(6), vec![DST, (55, 155, 205), (172, 242, 250)]);
+        assert_eq!(copy_with_ink(7), vec![DST, WHITE, (236, 246, 250)]);
+    }
+
+    #[test]
+    fn arithmetic_inks() {
+        assert_eq!(copy_with_ink(33), vec![DST, DST, (255, 250, 250)]);
+        assert_eq!(copy_with_ink(34), vec![DST, (199, 99, 49), (44, 250, 250)]);
+        assert_eq!(copy_with_ink(35), vec![DST, BLACK, (100, 0, 0)]);
+        assert_eq!(copy_with_ink(36), vec![BLACK, DST, GRAY]);
+        assert_eq!(copy_with_ink(37), vec![DST, WHITE, (200, 150, 200)]);
+        assert_eq!(copy_with_ink(38), vec![DST, (201, 101, 51), (100, 206, 106)]);
+        assert_eq!(copy_with_ink(39), vec![BLACK, DST, (100, 100, 50)]);
+        // With the default colors, lighten and darken leave the source unchanged
+        assert_eq!(copy_with_ink(40), vec![BLACK, WHITE, GRAY]);
+        assert_eq!(copy_with_ink(41), vec![BLACK, WHITE, GRAY]);
+    }
+
+    #[test]
+    fn foreground_and_background_colors_colorize_the_source() {
+        let red = (255, 0, 0);
+        let blue = (0, 0, 255);
+        // Black maps to the foreground color, white to the background color and grays in between
+        assert_eq!(copy_with(0, 100, red, blue), vec![red, blue, (155, 0, 200)]);
+        // Background transparent drops the pixels that end up in the background color
+        assert_eq!(copy_with(36, 100, red, blue), vec![red, DST, (155, 0, 200)]);
+        assert_eq!(copy_with(40, 100, red, blue), vec![BLACK, blue, (0, 0, 200)]);
+        assert_eq!(copy_with(41, 100, red, blue), vec![red, (255, 0, 255), (255, 0, 200)]);
+    }
+
+    #[test]
+    fn blend_mixes_with_the_destination() {
+        assert_eq!(copy_with(0, 50, BLACK, WHITE), vec![(100, 50, 25), (227, 177, 152), (150, 125, 125)]);
+        assert_eq!(copy_with(32, 25, BLACK, WHITE), vec![(150, 75, 37), (213, 138, 101), (175, 112, 87)]);
+        assert_eq!(copy_with(0, 0, BLACK, WHITE), vec![DST, DST, DST]);
+        // Pixels an ink leaves untouched stay untouched whatever the blend
+        assert_eq!(copy_with(36, 50, BLACK, WHITE), vec![(100, 50, 25), DST, (150, 125, 125)]);
+    }
+
+    fn bitmap_with_rows(rows: &[&[(u8, u8, u8)]], palettes: &PaletteMap) -> Bitmap {
+        let mut bitmap = Bitmap::new(rows[0].len() as u16, rows.len() as u16, 32, PaletteRef::BuiltIn(get_system_default_palette()));
+        for (y, row) in rows.iter().enumerate() {
+            for (x, color) in row.iter().enumerate() {
+                bitmap.set_pixel(x as i32, y as i32, *color, palettes);
+            }
+        }
+        bitmap
+    }
+
+    /// Copies `src` onto a destination filled with `DST` and returns its rows.
+    fn copy_masked(src: &Bitmap, ink: u32, mask_image: Option<&BitmapMask>, palettes: &PaletteMap) -> Vec<Vec<(u8, u8, u8)>> {
+        let rows = vec![vec![DST; src.width as usize]; src.height as usize];
+        let rows = rows.iter().map(|x| x.as_slice()).collect::<Vec<_>>();
+        let mut dst = bitmap_with_rows(&rows, palettes);
+        let params = CopyPixelsParams {
+            blend: 100,
+            ink,
+            color: ColorRef::Rgb(0, 0, 0),
+            bg_color: ColorRef::Rgb(255, 255, 255),
+            mask_image,
+            transform: None,
+            bilinear: false,
+        };
+        let (width, height) = (src.width as i32, src.height as i32);
+        dst.copy_pixels_with_params(palettes, src, IntRect::from(0, 0, width, height), IntRect::from(0, 0, width, height), &params);
+        (0..src.height).map(|y| (0..src.width).map(|x| dst.get_pixel_color(palettes, x, y)).collect()).collect()
+    }
+
+    /// A black ring around a white hole, surrounded by white.
+    fn ring_bitmap(palettes: &PaletteMap) -> Bitmap {
+        const W: (u8, u8, u8) = WHITE;
+        const B: (u8, u8, u8) = BLACK;
+        bitmap_with_rows(&[
+            &[W, W, W, W, W],
+            &[W, B, B, B, W],
+            &[W, B, W, B, W],
+            &[W, B, B, B, W],
+            &[W, W, W, W, W],
+        ], palettes)
+    }
+
+    #[test]
+    fn matte_ink_hides_the_white_around_the_image() {
+        let palettes = PaletteMap::new();
+        let mut src = ring_bitmap(&palettes);
+        src.create_matte(&palettes);
+        let result = copy_masked(&src, 8, None, &palettes);
+        assert_eq!(result[0], vec![DST; 5]);
+        assert_eq!(result[1], vec![DST, BLACK, BLACK, BLACK, DST]);
+        // White enclosed by the image is part of it
+        assert_eq!(result[2], vec![DST, BLACK, WHITE, BLACK, DST]);
+        // Other inks ignore the matte
+        assert_eq!(copy_masked(&src, 0, None, &palettes)[0], vec![WHITE; 5]);
+    }
+
+    #[test]
+    fn copy_pixels_builds_the_matte_of_sources_without_one() {
+        let palettes = PaletteMap::new();
+        let src = ring_bitmap(&palettes);
+        let mut dst = bitmap_with_rows(&[&[DST; 5][..]; 5], &palettes);
+        let params = HashMap::from([("ink".to_string(), Datum::Int(8))]);
+        let rect = IntRect::from(0, 0, 5, 5);
+        dst.copy_pixels(&palettes, &src, rect, rect, &params, None).unwrap();
+        assert_eq!(dst.get_pixel_color(&palettes, 0, 0), DST);
+        assert_eq!(dst.get_pixel_color(&palettes, 1, 1), BLACK);
+        assert_eq!(dst.get_pixel_color(&palettes, 2, 2), WHITE);
+        assert!(src.matte.is_none());
+    }
+
+    #[test]
+    fn mask_ink_draws_through_the_mask_image() {
+        let palettes = PaletteMap::new();
+        let src = bitmap_with_pixels(&[GRAY, GRAY, GRAY], &palettes);
+        let mask = bitmap_with_pixels(&[BLACK, WHITE, BLACK], &palettes).to_mask();
+        assert_eq!(copy_masked(&src, 9, Some(&mask), &palettes), vec![vec![GRAY, DST, GRAY]]);
+        // Without a mask the whole image is drawn
+        assert_eq!(copy_masked(&src, 9, None, &palettes), vec![vec![GRAY; 3]]);
+    }
+
+    #[test]
+    fn a_given_mask_image_takes_the_place_of_the_matte() {
+        let palettes = PaletteMap::new();
+        let mut src = ring_bitmap(&palettes);
+        src.create_matte(&palettes);
+        let mut mask = BitmapMask::new(5, 5, false);
+        mask.set_bit(0, 0, true);
+        mask.set_bit(1, 1, true);
+        let result = copy_masked(&src, 8, Some(&mask), &palettes);
+        assert_eq!(result[0], vec![WHITE, DST, DST, DST, DST]);
+        assert_eq!(result[1], vec![DST, BLACK, DST, DST, DST]);
+        assert_eq!(result[2], vec![DST; 5]);
+    }
+
+    fn shape(shape_type: ShapeType, pattern: u16, filled: bool, line_size: u8) -> ShapeInfo {
+        ShapeInfo {
+            shape_type,
+            reg_point: (0, 0),
+            width: 4,
+            height: 4,
+            pattern,
+            fore_color: 255,
+            back_color: 0,
+            filled,
+            line_size,
+            line_direction: 0,
+        }
+    }
+
+    /// Draws a shape in black on white over a 4x4 destination filled with `DST` and returns its rows.
+    fn draw(shape_info: &ShapeInfo) -> Vec<Vec<(u8, u8, u8)>> {
+        let palettes = PaletteMap::new();
+        let rows = vec![vec![DST; 4]; 4];
+        let rows: Vec<&[(u8, u8, u8)]> = rows.iter().map(|row| row.as_slice()).collect();
+        let mut dst = bitmap_with_rows(&rows, &palettes);
+        let mut params = CopyPixelsParams::default(&dst);
+        params.color = ColorRef::Rgb(0, 0, 0);
+        params.bg_color = ColorRef::Rgb(255, 255, 255);
+        dst.draw_shape(&palettes, shape_info, IntRect::from(0, 0, 4, 4), &params);
+        (0..4).map(|y| (0..4).map(|x| dst.get_pixel_color(&palettes, x, y)).collect()).collect()
+    }
+
+    #[test]
+    fn fills_shapes_solid_with_the_first_patterns() {
+        assert_eq!(draw(&shape(ShapeType::Rect, 1, true, 1)), vec![vec![BLACK; 4]; 4]);
+        assert_eq!(draw(&shape(ShapeType::Rect, 0, true, 0)), vec![vec![BLACK; 4]; 4]);
+    }
+
+    #[test]
+    fn fills_shapes_with_the_pattern_in_the_foreground_and_background_colors() {
+        // Pattern 20 is white, pattern 23 is the light gray 0x88 0x22
+        assert_eq!(draw(&shape(ShapeType::Rect, 20, true, 0)), vec![vec![WHITE; 4]; 4]);
+        let gray = draw(&shape(ShapeType::Rect, 23, true, 0));
+        assert_eq!(gray[0], vec![BLACK, WHITE, WHITE, WHITE]);
+        assert_eq!(gray[1], vec![WHITE, WHITE, BLACK, WHITE]);
+        assert_eq!(gray[2], vec![BLACK, WHITE, WHITE, WHITE]);
+    }
+
+    #[test]
+    fn fills_unknown_patterns_solid() {
+        assert!(shape_pattern(38).is_some());
+        assert!(shape_pattern(39).is_none());
+        assert_eq!(draw(&shape(ShapeType::Rect, 60, true, 0)), vec![vec![BLACK; 4]; 4]);
+    }
+
+    #[test]
+    fn draws_only_the_border_of_unfilled_shapes() {
+        let result = draw(&shape(ShapeType::Rect, 1, false, 1));
+        assert_eq!(result[0], vec![BLACK; 4]);
+        assert_eq!(result[1], vec![BLACK, DST, DST, BLACK]);
+        assert_eq!(result[2], vec![BLACK, DST, DST, BLACK]);
+        assert_eq!(result[3], vec![BLACK; 4]);
+    }
+
+    #[test]
+    fn rounds_the_corners_of_ovals() {
+        let result = draw(&shape(ShapeType::Oval, 1, true, 1));
+        assert_eq!(result[0], vec![DST, BLACK, BLACK, DST]);
+        assert_eq!(result[1], vec![BLACK; 4]);
+        assert_eq!(result[3], vec![DST, BLACK, BLACK, DST]);
+    }
+
+    #[test]
+    fn draws_lines_only_with_a_line_size() {
+        let result = draw(&shape(ShapeType::Line, 1, false, 1));
+        for (y, row) in result.iter().enumerate() {
+            assert_eq!(row[y], BLACK);
+        }
+        assert_eq!((result[0][3], result[3][0]), (DST, DST));
+        assert_eq!(draw(&shape(ShapeType::Line, 1, false, 0)), vec![vec![DST; 4]; 4]);
+
+        let mut reversed = shape(ShapeType::Line, 1, false, 1);
+        reversed.line_direction = 1;
+        let result = draw(&reversed);
+        assert_eq!(result[0][3], BLACK);
+        assert_eq!(result[3][0], BLACK);
+        assert_eq!(result[0][0], DST);
+    }
+}
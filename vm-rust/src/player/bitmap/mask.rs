@@ -78,6 +78,11 @@ impl Bitmap {
     }
 
     pub fn create_matte(&mut self, palettes: &PaletteMap) {
+      self.matte = Some(Arc::new(self.build_matte(palettes)));
+    }
+
+    /// Returns the pixels of the image, leaving out the background color around it.
+    pub fn build_matte(&self, palettes: &PaletteMap) -> BitmapMask {
       let bg_color = &self.get_bg_color_ref();
       let mut mask = self.get_mask(palettes, bg_color);
       let mut outside_pixels = vec![];
@@ -103,7 +108,6 @@ impl Bitmap {
             outside_pixels.push((x, self.height - 1));
           }
       }
-      let matte = mask.flood_matte(outside_pixels, false, true);
-      self.matte = Some(Arc::new(matte));
+      mask.flood_matte(outside_pixels, false, true)
     }
 }
\ No newline at end of file
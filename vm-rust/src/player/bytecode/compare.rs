@@ -1,6 +1,6 @@
 use num::ToPrimitive;
 
-use crate::{director::lingo::datum::{datum_bool, Datum}, player::{compare::{datum_equals, datum_greater_than, datum_less_than}, reserve_player_mut, HandlerExecutionResult, HandlerExecutionResultContext, ScriptError}};
+use crate::{director::lingo::datum::{datum_bool, Datum}, player::{compare::{datum_equals, datum_greater_than, datum_less_than}, reserve_player_mut, score::{sprite_intersects, sprite_within}, HandlerExecutionResult, HandlerExecutionResultContext, ScriptError}};
 
 use super::handler_manager::BytecodeHandlerContext;
 
@@ -190,4 +190,40 @@ impl CompareBytecodeHandler {
       Ok(HandlerExecutionResult::Advance)
     })
   }
+
+  fn read_sprite_pair(ctx: &BytecodeHandlerContext) -> Result<(i16, i16), ScriptError> {
+    reserve_player_mut(|player| {
+      let (first, second) = {
+        let scope = player.scopes.get_mut(ctx.scope_ref).unwrap();
+        let second = scope.stack.pop().unwrap();
+        let first = scope.stack.pop().unwrap();
+        (first, second)
+      };
+      let first = player.get_datum(&first).int_value()? as i16;
+      let second = player.get_datum(&second).int_value()? as i16;
+      Ok((first, second))
+    })
+  }
+
+  pub fn onto_spr(ctx: &BytecodeHandlerContext) -> Result<HandlerExecutionResult, ScriptError> {
+    let (first, second) = Self::read_sprite_pair(ctx)?;
+    reserve_player_mut(|player| {
+      let result = sprite_intersects(player, first, second);
+      let result_id = player.alloc_datum(datum_bool(result));
+      let scope = player.scopes.get_mut(ctx.scope_ref).unwrap();
+      scope.stack.push(result_id);
+      Ok(HandlerExecutionResult::Advance)
+    })
+  }
+
+  pub fn into_spr(ctx: &BytecodeHandlerContext) -> Result<HandlerExecutionResult, ScriptError> {
+    let (first, second) = Self::read_sprite_pair(ctx)?;
+    reserve_player_mut(|player| {
+      let result = sprite_within(player, first, second);
+      let result_id = player.alloc_datum(datum_bool(result));
+      let scope = player.scopes.get_mut(ctx.scope_ref).unwrap();
+      scope.stack.push(result_id);
+      Ok(HandlerExecutionResult::Advance)
+    })
+  }
 }
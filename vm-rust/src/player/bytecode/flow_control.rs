@@ -1,4 +1,4 @@
-use crate::{director::lingo::datum::{Datum, DatumType}, player::{compare::datum_is_zero, handlers::datum_handlers::player_call_datum_handler, player_call_script_handler_raw_args, player_ext_call, player_handle_scope_return, reserve_player_mut, reserve_player_ref, script::{get_current_handler_def, get_current_script, get_name}, HandlerExecutionResult, HandlerExecutionResultContext, ScriptError, PLAYER_OPT}};
+use crate::{director::lingo::datum::{Datum, DatumType}, player::{compare::datum_is_zero, datum_formatting::format_datum, handlers::datum_handlers::{player_call_datum_handler, script_instance::ScriptInstanceUtils}, player_call_script_handler, player_call_script_handler_raw_args, script_ref::ScriptInstanceRef, DirPlayer, DatumRef, player_ext_call, player_handle_scope_return, reserve_player_mut, reserve_player_ref, script::{get_current_handler_def, get_current_script, get_name}, HandlerExecutionResult, HandlerExecutionResultContext, ScriptError, PLAYER_OPT}};
 
 use super::handler_manager::BytecodeHandlerContext;
 
@@ -157,6 +157,93 @@ impl FlowControlBytecodeHandler {
     Ok(HandlerExecutionResult::Advance)
   }
 
+  pub fn start_tell(ctx: &BytecodeHandlerContext) -> Result<HandlerExecutionResult, ScriptError> {
+    reserve_player_mut(|player| {
+      let scope = player.scopes.get_mut(ctx.scope_ref).unwrap();
+      let target = scope.stack.pop().unwrap();
+      scope.tell_targets.push(target);
+    });
+    Ok(HandlerExecutionResult::Advance)
+  }
+
+  pub fn end_tell(ctx: &BytecodeHandlerContext) -> Result<HandlerExecutionResult, ScriptError> {
+    reserve_player_mut(|player| {
+      let scope = player.scopes.get_mut(ctx.scope_ref).unwrap();
+      scope.tell_targets.pop();
+    });
+    Ok(HandlerExecutionResult::Advance)
+  }
+
+  /// Returns the script instances a call inside a `tell` block is sent to before falling back to global handlers.
+  fn get_tell_target_instances(player: &DirPlayer, target_ref: &DatumRef) -> Result<Vec<ScriptInstanceRef>, ScriptError> {
+    match player.get_datum(target_ref) {
+      Datum::SpriteRef(sprite_num) => Ok(player.movie.score
+        .get_sprite(*sprite_num)
+        .map(|sprite| sprite.script_instance_list.clone())
+        .unwrap_or_default()),
+      Datum::ScriptInstanceRef(instance_ref) => Ok(vec![instance_ref.clone()]),
+      // The stage plays the current movie, so its calls go to the movie scripts
+      Datum::Stage => Ok(vec![]),
+      // Movies in a window are not played, so no window exists to take the call
+      Datum::Window(name) => Err(ScriptError::new(format!("Cannot tell window \"{}\": the window does not exist", name))),
+      _ => Err(ScriptError::new(format!("Cannot tell {}: only the stage, sprites and script instances can be told", format_datum(target_ref, player)))),
+    }
+  }
+
+  pub async fn tell_call(ctx: &BytecodeHandlerContext) -> Result<HandlerExecutionResult, ScriptError> {
+    let (name, args, is_no_ret, target_instances) = reserve_player_mut(|player| {
+      let name = get_name(&player, &ctx, player.get_ctx_current_bytecode(&ctx).obj as u16).unwrap().to_owned();
+      let scope = player.scopes.get_mut(ctx.scope_ref).unwrap();
+      let arg_list_id = scope.stack.pop().unwrap();
+      let target_ref = scope.tell_targets.last().cloned();
+      let arg_list_datum = player.get_datum(&arg_list_id);
+      let is_no_ret = match arg_list_datum {
+        Datum::List(DatumType::ArgListNoRet, _, _) => true,
+        _ => false,
+      };
+      let args = arg_list_datum.to_list()?.clone();
+      let target_instances = match target_ref {
+        Some(target_ref) => Self::get_tell_target_instances(player, &target_ref)?,
+        None => vec![],
+      };
+      Ok((name, args, is_no_ret, target_instances))
+    })?;
+
+    // Like a message sent to a sprite, the call reaches every behavior that handles it. It only
+    // goes on to the movie scripts when none did, or when all of them passed it.
+    let mut handled_result = None;
+    for instance_ref in target_instances {
+      let handler_ref = reserve_player_ref(|player| {
+        ScriptInstanceUtils::get_script_instance_handler(&name, &instance_ref, player)
+      })?;
+      if let Some(handler_ref) = handler_ref {
+        let scope = player_call_script_handler(Some(instance_ref), handler_ref, &args).await?;
+        if !scope.passed {
+          handled_result = Some(scope.return_value);
+        }
+      }
+    }
+    if let Some(result) = handled_result {
+      reserve_player_mut(|player| {
+        player.last_handler_result = result.clone();
+        if !is_no_ret {
+          let scope = player.scopes.get_mut(ctx.scope_ref).unwrap();
+          scope.stack.push(result);
+        }
+      });
+      return Ok(HandlerExecutionResult::Advance);
+    }
+
+    let result_ctx = player_ext_call(name, &args, ctx.scope_ref).await;
+    if !is_no_ret {
+      reserve_player_mut(|player| {
+        let scope = player.scopes.get_mut(ctx.scope_ref).unwrap();
+        scope.stack.push(scope.return_value.clone());
+      });
+    }
+    Ok(result_ctx)
+  }
+
   pub fn end_repeat(ctx: &BytecodeHandlerContext) -> Result<HandlerExecutionResult, ScriptError> {
     reserve_player_mut(|player| {
       let new_index = {
@@ -171,3 +258,95 @@ impl FlowControlBytecodeHandler {
     })
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use async_std::task::block_on;
+
+  use crate::{
+    director::enums::ScriptType,
+    player::{
+      events::player_invoke_global_event,
+      testing::{add_test_scripts, start_test_log, take_test_log, with_test_player},
+    },
+  };
+
+  use super::*;
+
+  const GREETER: &str = "property pName\r\
+    on new me, name\r  pName = name\r  return me\rend\r\
+    on greet me, greeting\r  global gLog\r  gLog = gLog & pName & \":\" & greeting & \" \"\rend\r";
+
+  fn run(handler: &str) -> Result<DatumRef, ScriptError> {
+    block_on(player_invoke_global_event(&handler.to_string(), &vec![]))
+  }
+
+  /// Adds a movie script with the given handlers, along with the `greeter` parent script.
+  fn add_scripts(main: &str) {
+    let main = main.to_string() + "on greet greeting\r  global gLog\r  gLog = gLog & \"movie:\" & greeting & \" \"\rend\r";
+    add_test_scripts(vec![
+      (1, "main", ScriptType::Movie, &main),
+      (2, "greeter", ScriptType::Parent, GREETER),
+    ]);
+    start_test_log();
+  }
+
+  #[test]
+  fn tell_calls_the_handlers_of_the_told_object() {
+    with_test_player(|_| {
+      add_scripts("on test\r  tell new(script \"greeter\", \"a\")\r    greet(\"hi\")\r  end tell\r  greet(\"bye\")\rend\r");
+      run("test").unwrap();
+      assert_eq!(take_test_log(), "a:hi movie:bye ");
+    });
+  }
+
+  #[test]
+  fn tell_falls_back_to_the_movie_scripts() {
+    with_test_player(|_| {
+      add_scripts("on test\r  tell the stage to greet(\"hi\")\r  tell new(script \"greeter\", \"a\") to shout()\rend\r\
+        on shout\r  global gLog\r  gLog = gLog & \"shout \"\rend\r");
+      run("test").unwrap();
+      assert_eq!(take_test_log(), "movie:hi shout ");
+    });
+  }
+
+  #[test]
+  fn nested_tell_blocks_restore_the_outer_target() {
+    with_test_player(|_| {
+      add_scripts("on test\r  inner = new(script \"greeter\", \"inner\")\r  tell new(script \"greeter\", \"outer\")\r    tell inner\r      greet(1)\r    end tell\r    greet(2)\r  end tell\r  greet(3)\rend\r");
+      run("test").unwrap();
+      assert_eq!(take_test_log(), "inner:1 outer:2 movie:3 ");
+    });
+  }
+
+  #[test]
+  fn tell_sprite_calls_its_behaviors() {
+    with_test_player(|_| {
+      add_scripts("on setUp\r  global gFirst, gSecond\r  gFirst = new(script \"greeter\", \"first\")\r  gSecond = new(script \"greeter\", \"second\")\rend\r\
+        on test\r  tell sprite(1) to greet(\"hi\")\rend\r");
+      run("setUp").unwrap();
+      reserve_player_mut(|player| {
+        let instances = ["gFirst", "gSecond"].iter()
+          .map(|name| match player.get_datum(&player.globals[*name]) {
+            Datum::ScriptInstanceRef(instance_ref) => instance_ref.clone(),
+            _ => panic!("Not a script instance"),
+          })
+          .collect::<Vec<_>>();
+        player.movie.score.set_channel_count(1);
+        player.movie.score.get_sprite_mut(1).script_instance_list = instances;
+      });
+      run("test").unwrap();
+      assert_eq!(take_test_log(), "first:hi second:hi ");
+    });
+  }
+
+  #[test]
+  fn telling_a_window_fails() {
+    with_test_player(|_| {
+      add_scripts("on test\r  tell window(\"Help\") to greet(\"hi\")\rend\r");
+      let err = run("test").unwrap_err();
+      assert!(err.message.contains("window \"Help\""), "{}", err.message);
+      assert_eq!(take_test_log(), "");
+    });
+  }
+}
@@ -15,7 +15,7 @@ impl GetSetUtils {
       match prop_name.as_str() {
         "paramCount" => Ok(player.alloc_datum(Datum::Int(player.scopes.get(ctx.scope_ref).unwrap().args.len() as i32))),
         "result" => Ok(player.last_handler_result.clone()),
-        _ => Ok(player.alloc_datum(player.get_movie_prop(prop_name)?))
+        _ => player.get_movie_prop_ref(prop_name)
       }
   }
 
@@ -101,9 +101,8 @@ impl GetSetBytecodeHandler {
 
   pub fn get_movie_prop(ctx: &BytecodeHandlerContext) -> Result<HandlerExecutionResult, ScriptError> {
     reserve_player_mut(|player| {
-      let prop_name = get_name(&player, &ctx, player.get_ctx_current_bytecode(ctx).obj as u16).unwrap();
-      let value = player.get_movie_prop(&prop_name)?;
-      let result_id = player.alloc_datum(value);
+      let prop_name = get_name(&player, &ctx, player.get_ctx_current_bytecode(ctx).obj as u16).unwrap().to_owned();
+      let result_id = player.get_movie_prop_ref(&prop_name)?;
       let scope = player.scopes.get_mut(ctx.scope_ref).unwrap();
       scope.stack.push(result_id);
       Ok(HandlerExecutionResult::Advance)
@@ -231,7 +230,7 @@ impl GetSetBytecodeHandler {
 
   pub fn get_param(ctx: &BytecodeHandlerContext) -> Result<HandlerExecutionResult, ScriptError> {
     reserve_player_mut(|player| {
-      let param_number = player.get_ctx_current_bytecode(ctx).obj as usize;
+      let param_number = (player.get_ctx_current_bytecode(ctx).obj as u32 / get_current_variable_multiplier(player, ctx)) as usize;
       let scope = player.scopes.get_mut(ctx.scope_ref).unwrap();
       let result = scope.args.get(param_number).unwrap_or(&DatumRef::Void).clone();
       scope.stack.push(result);
@@ -241,10 +240,9 @@ impl GetSetBytecodeHandler {
 
   pub fn set_param(ctx: &BytecodeHandlerContext) -> Result<HandlerExecutionResult, ScriptError> {
     reserve_player_mut(|player| {
-      let bytecode_obj = player.get_ctx_current_bytecode(ctx).obj as usize;
+      let arg_index = (player.get_ctx_current_bytecode(ctx).obj as u32 / get_current_variable_multiplier(player, ctx)) as usize;
       let scope = player.scopes.get_mut(ctx.scope_ref).unwrap();
       let arg_count = scope.args.len();
-      let arg_index = bytecode_obj;
       let value_ref = scope.stack.pop().unwrap();
 
       if arg_index < scope.args.len() {
@@ -379,4 +377,90 @@ impl GetSetBytecodeHandler {
       Ok(HandlerExecutionResult::Advance)
     })
   }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+  use async_std::task::block_on;
+  use binary_reader::BinaryReader;
+
+  use crate::{
+    director::{chunks::script::ScriptChunk, enums::ScriptType, lingo::script::ScriptContext},
+    player::{
+      cast_lib::cast_member_ref,
+      cast_member::{CastMemberType, ScriptMember},
+      player_call_script_handler,
+      reserve_player_ref,
+      testing::{add_test_cast, with_test_player},
+    },
+  };
+
+  use super::*;
+
+  /// An `Lscr` chunk as Director 7 writes it, holding `on double a, x` with the body
+  /// `x = x * 2` and `return x`. Director scales the param indices by the variable multiplier,
+  /// which is 8 for this version, as it does for locals, so `x` is param 8.
+  fn double_script_chunk() -> Vec<u8> {
+    const HEADER_LENGTH: usize = 92;
+    const RECORD_LENGTH: usize = 42;
+    let bytecode: [u8; 14] = [
+      0x4b, 0x08, // getparam x
+      0x41, 0x02, // pushint8 2
+      0x04,       // mul
+      0x51, 0x08, // setparam x
+      0x4b, 0x08, // getparam x
+      0x42, 0x01, // pusharglistnoret 1
+      0x57, 0x03, // extcall return
+      0x01,       // ret
+    ];
+    let compiled_offset = HEADER_LENGTH + RECORD_LENGTH;
+    let argument_offset = compiled_offset + bytecode.len() + 1;
+    let argument_name_ids: [u16; 2] = [1, 2]; // "a" and "x"
+
+    let mut data = vec![0; HEADER_LENGTH];
+    data[16..18].copy_from_slice(&(HEADER_LENGTH as u16).to_be_bytes());
+    data[72..74].copy_from_slice(&1u16.to_be_bytes());
+    data[74..78].copy_from_slice(&(HEADER_LENGTH as u32).to_be_bytes());
+
+    data.extend_from_slice(&0u16.to_be_bytes()); // name id of "double"
+    data.extend_from_slice(&0u16.to_be_bytes());
+    data.extend_from_slice(&(bytecode.len() as u32).to_be_bytes());
+    data.extend_from_slice(&(compiled_offset as u32).to_be_bytes());
+    data.extend_from_slice(&(argument_name_ids.len() as u16).to_be_bytes());
+    data.extend_from_slice(&(argument_offset as u32).to_be_bytes());
+    data.resize(compiled_offset, 0);
+
+    data.extend_from_slice(&bytecode);
+    data.push(0);
+    for name_id in argument_name_ids {
+      data.extend_from_slice(&name_id.to_be_bytes());
+    }
+    data
+  }
+
+  #[test]
+  fn reads_and_writes_params_scaled_by_the_variable_multiplier() {
+    with_test_player(|_| {
+      let script = ScriptMember { script_id: 1, script_type: ScriptType::Movie, name: "double".to_string() };
+      let cast_lib = add_test_cast(vec![(1, "double", CastMemberType::Script(script))]);
+      reserve_player_mut(|player| {
+        let chunk_data = double_script_chunk();
+        let mut reader = BinaryReader::from_vec(&chunk_data);
+        let chunk = ScriptChunk::from_reader(&mut reader, 700, false).unwrap();
+        let cast = player.movie.cast_manager.get_cast_mut(cast_lib);
+        cast.dir_version = 700;
+        cast.lctx = Some(ScriptContext {
+          names: ["double", "a", "x", "return"].iter().map(|name| name.to_string()).collect(),
+          scripts: [(1, chunk)].into(),
+        });
+        cast.set_script_type(1, ScriptType::Movie).unwrap();
+      });
+
+      let args = reserve_player_mut(|player| vec![player.alloc_datum(Datum::Int(1)), player.alloc_datum(Datum::Int(21))]);
+      let handler_ref = (cast_member_ref(cast_lib as i32, 1), "double".to_string());
+      let result = block_on(player_call_script_handler(None, handler_ref, &args)).unwrap();
+      reserve_player_ref(|player| {
+        assert_eq!(player.get_datum(&result.return_value).int_value().unwrap(), 42);
+      });
+    });
+  }
+}
@@ -58,6 +58,8 @@ impl StaticBytecodeHandlerManager {
             OpCode::PushList => StackBytecodeHandler::push_list(ctx),
             OpCode::Not => CompareBytecodeHandler::not(ctx),
             OpCode::NtEq => CompareBytecodeHandler::nt_eq(ctx),
+            OpCode::OntoSpr => CompareBytecodeHandler::onto_spr(ctx),
+            OpCode::IntoSpr => CompareBytecodeHandler::into_spr(ctx),
             OpCode::TheBuiltin => GetSetBytecodeHandler::the_built_in(ctx),
             OpCode::Peek => StackBytecodeHandler::peek(ctx),
             OpCode::Pop => StackBytecodeHandler::pop(ctx),
@@ -80,7 +82,11 @@ impl StaticBytecodeHandlerManager {
             OpCode::Mul => ArithmeticsBytecodeHandler::mul(ctx),
             OpCode::PushChunkVarRef => StackBytecodeHandler::push_chunk_var_ref(ctx),
             OpCode::DeleteChunk => StringBytecodeHandler::delete_chunk(ctx),
+            OpCode::PutChunk => StringBytecodeHandler::put_chunk(ctx),
+            OpCode::HiliteChunk => StringBytecodeHandler::hilite_chunk(ctx),
             OpCode::GetTopLevelProp => GetSetBytecodeHandler::get_top_level_prop(ctx),
+            OpCode::StartTell => FlowControlBytecodeHandler::start_tell(ctx),
+            OpCode::EndTell => FlowControlBytecodeHandler::end_tell(ctx),
             _ => Err(ScriptError::new(
                 format_args!(
                     "No handler for opcode {} ({:#04x})",
@@ -100,6 +106,7 @@ impl StaticBytecodeHandlerManager {
             OpCode::ObjCall => true,
             OpCode::LocalCall => true,
             OpCode::SetObjProp => true,
+            OpCode::TellCall => true,
             _ => false,
         }
     }
@@ -112,6 +119,7 @@ impl StaticBytecodeHandlerManager {
             OpCode::ObjCall => FlowControlBytecodeHandler::obj_call(&ctx).await,
             OpCode::LocalCall => FlowControlBytecodeHandler::local_call(&ctx).await,
             OpCode::SetObjProp => GetSetBytecodeHandler::set_obj_prop(&ctx).await,
+            OpCode::TellCall => FlowControlBytecodeHandler::tell_call(&ctx).await,
             _ => Err(ScriptError::new(
                 format_args!(
                     "No handler for opcode {} ({:#04x})",
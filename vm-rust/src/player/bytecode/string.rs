@@ -1,4 +1,4 @@
-use crate::{director::{chunks::handler::Bytecode, lingo::datum::{datum_bool, Datum, StringChunkExpr, StringChunkSource, StringChunkType}}, player::{context_vars::{player_get_context_var, player_set_context_var, read_context_var_args}, datum_formatting::format_concrete_datum, handlers::datum_handlers::string_chunk::StringChunkUtils, reserve_player_mut, DirPlayer, HandlerExecutionResult, HandlerExecutionResultContext, ScriptError}};
+use crate::{director::{chunks::handler::Bytecode, lingo::datum::{datum_bool, Datum, StringChunkExpr, StringChunkType}}, player::{cast_member::CastMemberType, context_vars::{player_get_context_var, player_set_context_var, read_context_var_args}, datum_formatting::format_concrete_datum, handlers::datum_handlers::string_chunk::StringChunkUtils, reserve_player_mut, DirPlayer, HandlerExecutionResult, HandlerExecutionResultContext, ScriptError}};
 
 use super::handler_manager::BytecodeHandlerContext;
 
@@ -248,13 +248,87 @@ impl StringBytecodeHandler {
 
   pub fn delete_chunk(ctx: &BytecodeHandlerContext) -> Result<HandlerExecutionResult, ScriptError> {
     reserve_player_mut(|player| {
-      let bytecode_obj = player.get_ctx_current_bytecode(ctx).obj;
-      let (id_ref, cast_id_ref) = read_context_var_args(player, bytecode_obj as u32, ctx.scope_ref);
-      let string_ref = player_get_context_var(player, &id_ref, cast_id_ref.as_ref(), bytecode_obj as u32, ctx)?;
-      // let string = player.get_datum(string_ref);
+      let var_type = player.get_ctx_current_bytecode(ctx).obj as u32;
+      let (id_ref, cast_id_ref) = read_context_var_args(player, var_type, ctx.scope_ref);
       let chunk_expr = Self::read_chunk_ref(player, ctx)?;
 
-      StringChunkUtils::delete(player, &StringChunkSource::Datum(string_ref), &chunk_expr)?;
+      let curr_string_ref = player_get_context_var(player, &id_ref, cast_id_ref.as_ref(), var_type, ctx)?;
+      let curr_string = player.get_datum(&curr_string_ref).string_value()?;
+      let new_string = StringChunkUtils::string_by_deleting_chunk(&curr_string, &chunk_expr)?;
+      let new_string_ref = player.alloc_datum(Datum::String(new_string));
+      player_set_context_var(player, &id_ref, cast_id_ref.as_ref(), var_type, &new_string_ref, ctx)?;
+
+      Ok(HandlerExecutionResult::Advance)
+    })
+  }
+
+  pub fn put_chunk(ctx: &BytecodeHandlerContext) -> Result<HandlerExecutionResult, ScriptError> {
+    reserve_player_mut(|player| {
+      let bytecode = player.get_ctx_current_bytecode(ctx);
+      let put_type = PutType::from(((bytecode.obj >> 4) & 0xF) as u8);
+      let var_type = (bytecode.obj & 0xF) as u32;
+      let (id_ref, cast_id_ref) = read_context_var_args(player, var_type, ctx.scope_ref);
+      let chunk_expr = Self::read_chunk_ref(player, ctx)?;
+      let value_ref = {
+        let scope = player.scopes.get_mut(ctx.scope_ref).unwrap();
+        scope.stack.pop().unwrap()
+      };
+      let value = Self::get_datum_concat_value(player.get_datum(&value_ref), player)?;
+
+      let curr_string_ref = player_get_context_var(player, &id_ref, cast_id_ref.as_ref(), var_type, ctx)?;
+      let curr_string = player.get_datum(&curr_string_ref);
+      let curr_string = if curr_string.is_void() { "".to_string() } else { curr_string.string_value()? };
+      let new_string = match put_type {
+        PutType::Into => StringChunkUtils::string_by_setting_chunk(&curr_string, &chunk_expr, &value)?,
+        PutType::Before => StringChunkUtils::string_by_putting_before_chunk(&curr_string, &chunk_expr, &value)?,
+        PutType::After => StringChunkUtils::string_by_putting_after_chunk(&curr_string, &chunk_expr, &value)?,
+      };
+      let new_string_ref = player.alloc_datum(Datum::String(new_string));
+      player_set_context_var(player, &id_ref, cast_id_ref.as_ref(), var_type, &new_string_ref, ctx)?;
+
+      Ok(HandlerExecutionResult::Advance)
+    })
+  }
+
+  pub fn hilite_chunk(ctx: &BytecodeHandlerContext) -> Result<HandlerExecutionResult, ScriptError> {
+    reserve_player_mut(|player| {
+      let cast_id_ref = if player.movie.dir_version >= 500 {
+        let scope = player.scopes.get_mut(ctx.scope_ref).unwrap();
+        Some(scope.stack.pop().unwrap())
+      } else {
+        None
+      };
+      let field_ref = {
+        let scope = player.scopes.get_mut(ctx.scope_ref).unwrap();
+        scope.stack.pop().unwrap()
+      };
+      let chunk_expr = Self::read_chunk_ref(player, ctx)?;
+
+      let member_ref = {
+        let field = player.get_datum(&field_ref);
+        let cast_id = cast_id_ref.as_ref().map(|x| player.get_datum(x));
+        match field {
+          Datum::CastMember(member_ref) => Some(member_ref.to_owned()),
+          _ => player.movie.cast_manager.find_member_ref_by_identifiers(field, cast_id, &player.allocator)?,
+        }
+      }.ok_or_else(|| ScriptError::new("hilite: field not found".to_string()))?;
+      let text = match player.movie.cast_manager.find_member_by_ref(&member_ref).map(|x| &x.member_type) {
+        Some(CastMemberType::Field(field)) => field.text.clone(),
+        Some(CastMemberType::Text(text)) => text.text.clone(),
+        _ => return Err(ScriptError::new("hilite: member is not a field".to_string())),
+      };
+
+      let (start, end) = StringChunkUtils::resolve_chunk_char_range(&text, &chunk_expr);
+      player.text_selection_start = start as u32;
+      player.text_selection_end = end as u32;
+
+      let sprite_num = player.movie.score.get_sorted_channels()
+        .iter()
+        .find(|channel| channel.sprite.member.as_ref() == Some(&member_ref))
+        .map(|channel| channel.number as i16);
+      if let Some(sprite_num) = sprite_num {
+        player.keyboard_focus_sprite = sprite_num;
+      }
 
       Ok(HandlerExecutionResult::Advance)
     })
@@ -283,3 +357,173 @@ impl StringBytecodeHandler {
     })
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use async_std::task::block_on;
+
+  use crate::{
+    director::{
+      chunks::{handler::HandlerDef, script::ScriptChunk},
+      enums::ScriptType,
+      file::get_variable_multiplier,
+      lingo::{opcode::OpCode, script::ScriptContext},
+    },
+    player::{
+      cast_lib::cast_member_ref,
+      cast_member::{FieldMember, ScriptMember, TextMember},
+      player_call_script_handler,
+      reserve_player_ref,
+      testing::{add_test_cast, with_test_player},
+    },
+  };
+
+  use super::*;
+
+  const PUT_INTO: i64 = 0x1;
+  const PUT_AFTER: i64 = 0x2;
+  const PUT_BEFORE: i64 = 0x3;
+  const VAR_GLOBAL: i64 = 0x2;
+  const VAR_FIELD: i64 = 0x6;
+
+  /// Pushes a literal of the test script, whose index is scaled like variables.
+  fn push_literal(index: i64) -> (OpCode, i64) {
+    (OpCode::PushCons, index * get_variable_multiplier(false, 1100) as i64)
+  }
+
+  /// Pushes the chunk range operands in the order `read_chunk_ref` pops them.
+  fn push_chunk(chunk_type: StringChunkType, start: i64, end: i64) -> Vec<(OpCode, i64)> {
+    let index = match chunk_type {
+      StringChunkType::Char => 0,
+      StringChunkType::Word => 1,
+      StringChunkType::Item => 2,
+      StringChunkType::Line => 3,
+    };
+    let mut ranges = [0; 8];
+    ranges[index * 2] = start;
+    ranges[index * 2 + 1] = end;
+    ranges.iter().map(|x| (OpCode::PushInt32, *x)).collect()
+  }
+
+  /// Runs a handler made of `ops`, in a Director 11 movie whose names are `gText` and the handler's.
+  fn run_ops(ops: Vec<(OpCode, i64)>, literals: Vec<Datum>) {
+    let script = ScriptMember { script_id: 1, script_type: ScriptType::Movie, name: "test".to_string() };
+    let cast_lib = add_test_cast(vec![(1, "test", CastMemberType::Script(script))]);
+    reserve_player_mut(|player| {
+      player.movie.dir_version = 1100;
+      let bytecode_array: Vec<Bytecode> = ops.into_iter()
+        .chain([(OpCode::Ret, 0)])
+        .enumerate()
+        .map(|(pos, (opcode, obj))| Bytecode::new(opcode, obj, pos))
+        .collect();
+      let handler = HandlerDef {
+        name_id: 0,
+        bytecode_index_map: (0..bytecode_array.len()).map(|x| (x, x)).collect(),
+        bytecode_array,
+        argument_name_ids: vec![],
+        local_name_ids: vec![],
+        global_name_ids: vec![],
+      };
+      let chunk = ScriptChunk { literals, handlers: vec![handler], property_name_ids: vec![] };
+      let cast = player.movie.cast_manager.get_cast_mut(cast_lib);
+      cast.dir_version = 1100;
+      cast.lctx = Some(ScriptContext {
+        names: vec!["test".to_string(), "gText".to_string()],
+        scripts: [(1, chunk)].into(),
+      });
+      cast.set_script_type(1, ScriptType::Movie).unwrap();
+    });
+    let handler_ref = (cast_member_ref(cast_lib as i32, 1), "test".to_string());
+    block_on(player_call_script_handler(None, handler_ref, &vec![])).unwrap();
+  }
+
+  fn put_into_global(put_type: i64, chunk: Vec<(OpCode, i64)>, value: &str, text: &str) -> String {
+    reserve_player_mut(|player| {
+      let text_ref = player.alloc_datum(Datum::String(text.to_string()));
+      player.globals.insert("gText".to_string(), text_ref);
+    });
+    let mut ops = vec![push_literal(0)];
+    ops.extend(chunk);
+    ops.extend([(OpCode::PushInt8, 1), (OpCode::PutChunk, put_type << 4 | VAR_GLOBAL)]);
+    run_ops(ops, vec![Datum::String(value.to_string())]);
+    reserve_player_ref(|player| player.get_datum(&player.globals["gText"]).string_value().unwrap())
+  }
+
+  #[test]
+  fn puts_into_chunks_of_variables() {
+    with_test_player(|_| {
+      let text = "one two three";
+      assert_eq!(put_into_global(PUT_INTO, push_chunk(StringChunkType::Word, 2, 2), "X", text), "one X three");
+      assert_eq!(put_into_global(PUT_AFTER, push_chunk(StringChunkType::Word, 2, 2), "!", text), "one two! three");
+      assert_eq!(put_into_global(PUT_BEFORE, push_chunk(StringChunkType::Char, 1, 3), "a", text), "aone two three");
+      assert_eq!(put_into_global(PUT_INTO, push_chunk(StringChunkType::Line, 2, 2), "b", "a"), "a\rb");
+    });
+  }
+
+  fn field_ops(member: i64, cast_lib: i64) -> [(OpCode, i64); 2] {
+    [(OpCode::PushInt8, member), (OpCode::PushInt8, cast_lib)]
+  }
+
+  #[test]
+  fn puts_into_chunks_of_field_and_text_members() {
+    with_test_player(|_| {
+      let mut field = FieldMember::new();
+      field.text = "red.green.blue".to_string();
+      let mut text = TextMember::new();
+      text.text = "Hello world".to_string();
+      let members_cast = add_test_cast(vec![
+        (1, "field", CastMemberType::Field(field)),
+        (2, "text", CastMemberType::Text(text)),
+      ]) as i64;
+
+      let mut ops = vec![push_literal(0)];
+      ops.extend(push_chunk(StringChunkType::Item, 2, 2));
+      ops.extend(field_ops(1, members_cast));
+      ops.push((OpCode::PutChunk, PUT_INTO << 4 | VAR_FIELD));
+      ops.push(push_literal(1));
+      ops.extend(push_chunk(StringChunkType::Word, 1, 1));
+      ops.extend(field_ops(2, members_cast));
+      ops.push((OpCode::PutChunk, PUT_AFTER << 4 | VAR_FIELD));
+      run_ops(ops, vec![Datum::String("yellow".to_string()), Datum::String(",".to_string())]);
+
+      reserve_player_ref(|player| {
+        let member_ref = |number| cast_member_ref(members_cast as i32, number);
+        let member_text = |number| match &player.movie.cast_manager.find_member_by_ref(&member_ref(number)).unwrap().member_type {
+          CastMemberType::Field(field) => field.text.clone(),
+          CastMemberType::Text(text) => text.text.clone(),
+          _ => panic!("not a text member"),
+        };
+        assert_eq!(member_text(1), "red.yellow.blue");
+        assert_eq!(member_text(2), "Hello, world");
+      });
+    });
+  }
+
+  fn hilite(chunk: Vec<(OpCode, i64)>, member: i64, cast_lib: i64) -> (u32, u32) {
+    let mut ops = chunk;
+    ops.extend(field_ops(member, cast_lib));
+    ops.push((OpCode::HiliteChunk, 0));
+    run_ops(ops, vec![]);
+    reserve_player_ref(|player| (player.text_selection_start, player.text_selection_end))
+  }
+
+  #[test]
+  fn hilites_chunks_of_fields() {
+    with_test_player(|_| {
+      let mut field = FieldMember::new();
+      field.text = "héllo big world".to_string();
+      let mut long_field = FieldMember::new();
+      long_field.text = "x".repeat(70_000) + " end";
+      let members_cast = add_test_cast(vec![
+        (1, "field", CastMemberType::Field(field)),
+        (2, "long", CastMemberType::Field(long_field)),
+      ]) as i64;
+
+      // The selection counts characters, not bytes
+      assert_eq!(hilite(push_chunk(StringChunkType::Word, 2, 3), 1, members_cast), (6, 15));
+      assert_eq!(hilite(push_chunk(StringChunkType::Char, 2, 2), 1, members_cast), (1, 2));
+      // Offsets past 65535 are kept
+      assert_eq!(hilite(push_chunk(StringChunkType::Word, 2, 2), 2, members_cast), (70_001, 70_004));
+    });
+  }
+}
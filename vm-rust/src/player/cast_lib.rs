@@ -3,9 +3,9 @@ use std::{collections::HashMap, rc::Rc};
 use fxhash::FxHashMap;
 use url::Url;
 
-use crate::{director::{cast::CastDef, file::{read_director_file_bytes, DirectorFile}, lingo::{datum::Datum, script::ScriptContext}}, js_api::{self, JsApi}, utils::{get_base_url, get_basename_no_extension, log_i}};
+use crate::{director::{cast::CastDef, chunks::script::ScriptChunk, enums::ScriptType, file::{read_director_file_bytes, DirectorFile}, lingo::{compiler::compile_script, datum::Datum, script::ScriptContext}}, js_api::{self, JsApi}, utils::{get_base_url, get_basename_no_extension, log_i}};
 
-use super::{allocator::DatumAllocator, bitmap::{bitmap::{Bitmap, BuiltInPalette, PaletteRef}, manager::BitmapManager}, cast_member::{BitmapMember, CastMember, CastMemberType, FieldMember, PaletteMember, TextMember}, handlers::datum_handlers::cast_member_ref::CastMemberRefHandlers, net_manager::NetManager, net_task::NetResult, reserve_player_mut, script::Script, ScriptError, PLAYER_OPT};
+use super::{allocator::DatumAllocator, bitmap::{bitmap::{Bitmap, BuiltInPalette, PaletteRef}, manager::BitmapManager}, cast_member::{BitmapMember, CastMember, CastMemberType, FieldMember, PaletteMember, ScriptMember, TextMember}, handlers::datum_handlers::cast_member_ref::CastMemberRefHandlers, net_manager::NetManager, net_task::NetResult, reserve_player_mut, script::Script, ScriptError, PLAYER_OPT};
 
 #[repr(u8)]
 #[derive(PartialEq)]
@@ -208,25 +208,7 @@ impl CastLib {
 
   pub fn insert_member(&mut self, number: u32, member: CastMember) {
     if let CastMemberType::Script(script_member) = &member.member_type {
-      let script_def = self.lctx.as_ref().unwrap().scripts.get(&script_member.script_id).unwrap();
-
-      let mut handler_names = Vec::new();
-      let mut handler_name_map = FxHashMap::default();
-      for handler in &script_def.handlers {
-        let handler_name = &self.lctx.as_ref().unwrap().names[handler.name_id as usize];
-        handler_name_map.insert(handler_name.to_lowercase(), Rc::new(handler.clone()));
-        handler_names.push(handler_name.to_owned());
-      }
-
-      let script = Script { 
-        member_ref: cast_member_ref(self.number as i32, number as i32),
-        name: (&member.name).to_owned(),
-        chunk: script_def.clone(),
-        script_type: script_member.script_type,
-        handlers: handler_name_map,
-        handler_names,
-      };
-      self.scripts.insert(number, Rc::new(script));
+      self.load_script(number, script_member, &member.name);
     } else if let CastMemberType::Palette(_) = &member.member_type {
       reserve_player_mut(|player| {
         player.movie.cast_manager.invalidate_palette_cache();
@@ -236,6 +218,50 @@ impl CastLib {
     self.members.insert(number, member);
   }
 
+  fn load_script(&mut self, number: u32, script_member: &ScriptMember, name: &str) {
+    let lctx = self.lctx.as_ref().unwrap();
+    let script_def = lctx.scripts.get(&script_member.script_id).unwrap();
+    let script = Script::new(
+      cast_member_ref(self.number as i32, number as i32),
+      name.to_owned(),
+      script_def.clone(),
+      script_member.script_type,
+      &lctx.names,
+    );
+    self.scripts.insert(number, Rc::new(script));
+  }
+
+  /// Replaces the script of a script member with one compiled from Lingo source.
+  /// Scripts that are running keep their old bytecode until they return.
+  pub fn set_script_text(&mut self, number: u32, source: &str) -> Result<(), ScriptError> {
+    let script_id = match self.members.get(&number).map(|x| &x.member_type) {
+      Some(CastMemberType::Script(script_member)) => script_member.script_id,
+      _ => return Err(ScriptError::new(format!("Member {} is not a script", number))),
+    };
+    let lctx = self.lctx.get_or_insert_with(ScriptContext::default);
+    let chunk = compile_script(source, &mut lctx.names, self.dir_version, self.capital_x).map_err(ScriptError::new)?;
+    lctx.scripts.insert(script_id, chunk);
+    self.reload_script(number);
+    Ok(())
+  }
+
+  pub fn set_script_type(&mut self, number: u32, script_type: ScriptType) -> Result<(), ScriptError> {
+    match self.members.get_mut(&number).map(|x| &mut x.member_type) {
+      Some(CastMemberType::Script(script_member)) => script_member.script_type = script_type,
+      _ => return Err(ScriptError::new(format!("Member {} is not a script", number))),
+    };
+    self.reload_script(number);
+    Ok(())
+  }
+
+  fn reload_script(&mut self, number: u32) {
+    let member = self.members.get(&number).unwrap();
+    if let CastMemberType::Script(script_member) = &member.member_type {
+      let (script_member, name) = (script_member.clone(), member.name.clone());
+      self.load_script(number, &script_member, &name);
+    }
+  }
+
   pub fn create_member_at(&mut self, number: u32, member_type: &str, bitmap_manager: &mut BitmapManager) -> Result<CastMemberRef, ScriptError> {
     let member = match member_type {
       "field" => Ok(CastMember::new(number, CastMemberType::Field(FieldMember::new()))),
@@ -251,6 +277,16 @@ impl CastLib {
         )))
       },
       "palette" => Ok(CastMember::new(number, CastMemberType::Palette(PaletteMember::new()))),
+      "script" => {
+        let lctx = self.lctx.get_or_insert_with(ScriptContext::default);
+        let script_id = lctx.scripts.keys().max().map_or(1, |x| x + 1);
+        lctx.scripts.insert(script_id, ScriptChunk::default());
+        Ok(CastMember::new(number, CastMemberType::Script(ScriptMember {
+          script_id,
+          script_type: ScriptType::Score,
+          name: "".to_owned(),
+        })))
+      }
       _ => Err(ScriptError::new(format!("Cannot create member of type {}", member_type)))
     }?;
     self.insert_member(number, member);
@@ -294,3 +330,4 @@ pub async fn player_cast_lib_set_prop(cast_lib: u32, prop_name: &String, value:
   // TODO handle preload error
   Ok(())
 }
+
@@ -48,7 +48,7 @@ impl CastManager {
       let cast_def = dir.casts.iter().find(|cast| cast.id == cast_entry.id);
       let mut cast = CastLib {
         name: cast_entry.name.to_owned(),
-        file_name: normalize_cast_lib_path(&net_manager.base_path, &cast_entry.file_path).map_or("".to_string(), |it| it.to_string()),
+        file_name: normalize_cast_lib_path(&net_manager.base_path, &cast_entry.file_path, dir.after_burned).map_or("".to_string(), |it| it.to_string()),
         number: (index + 1) as u32,
         is_external: cast_def.is_none(),
         state: if cast_def.is_some() { CastLibState::Loaded } else { CastLibState::None },
@@ -269,12 +269,10 @@ impl CastManager {
   pub fn get_field_value_by_identifiers(&self, member_name_or_num: &Datum, cast_name_or_num: Option<&Datum>, datums: &DatumAllocator) -> Result<String, ScriptError> {
     let member = self.find_member_by_identifiers(member_name_or_num, cast_name_or_num, datums)?;
     match member {
-      Some(member) => {
-        if let CastMemberType::Field(field) = &member.member_type {
-          Ok(field.text.to_owned())
-        } else {
-          Err(ScriptError::new(format!("Cast member is not a field")))
-        }
+      Some(member) => match &member.member_type {
+        CastMemberType::Field(field) => Ok(field.text.to_owned()),
+        CastMemberType::Text(text) => Ok(text.text.to_owned()),
+        _ => Err(ScriptError::new(format!("Cast member is not a field"))),
       },
       None => {
         Err(ScriptError::new(format!("Cast member not found")))
@@ -313,15 +311,21 @@ impl CastManager {
   }
 }
 
-fn normalize_cast_lib_path(base_path: &Option<Url>, file_path: &String, ) -> Option<String> {
+fn normalize_cast_lib_path(base_path: &Option<Url>, file_path: &String, after_burned: bool) -> Option<String> {
   if file_path.is_empty() {
     return None;
   }
   let slash_normalized = file_path.replace("\\", "/");
   let file_base_name = slash_normalized.split("/").last().unwrap();
-  let file_base_name_without_ext_split = file_base_name.split(".").collect_vec();
-  let file_base_name_without_ext = &file_base_name_without_ext_split[0..(file_base_name_without_ext_split.len() - 1)].join(".");
-  let cast_file_name = format!("{file_base_name_without_ext}.cct");
+  // Shockwave movies reference their casts by the authoring name, but the published
+  // files are always compressed. Unprotected movies ship their casts as-is.
+  let cast_file_name = if after_burned {
+    let file_base_name_without_ext_split = file_base_name.split(".").collect_vec();
+    let file_base_name_without_ext = &file_base_name_without_ext_split[0..(file_base_name_without_ext_split.len() - 1)].join(".");
+    format!("{file_base_name_without_ext}.cct")
+  } else {
+    file_base_name.to_string()
+  };
 
   match base_path {
     Some(base_path) => { Some(base_path.join(&cast_file_name).unwrap().to_string()) }
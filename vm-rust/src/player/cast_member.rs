@@ -1,9 +1,11 @@
 use core::fmt;
-use std::fmt::Formatter;
+use std::{fmt::Formatter, rc::Rc};
 
-use crate::director::{chunks::cast_member::CastMemberDef, enums::{MemberType, ScriptType, ShapeInfo}, lingo::script::ScriptContext};
+use log::warn;
 
-use super::{bitmap::{bitmap::{decompress_bitmap, Bitmap, BuiltInPalette, PaletteRef}, manager::{BitmapManager, BitmapRef}}, sprite::ColorRef, ScriptError};
+use crate::director::{chunks::{cast_member::CastMemberDef, rich_text::{XMediaStyleRun, XMediaStyledText}, score::ScoreFrameChannelData, text::{TextChunkStyle, TEXT_STYLE_BOLD, TEXT_STYLE_ITALIC, TEXT_STYLE_UNDERLINE}}, enums::{FilmLoopInfo, MemberType, ScriptType, ShapeInfo}, lingo::script::ScriptContext};
+
+use super::{bitmap::{bitmap::{decompress_bitmap, Bitmap, BuiltInPalette, PaletteRef}, drawing::shape_pattern, manager::{BitmapManager, BitmapRef}}, font::{font_style_bits, layout::{StyledText, TextAlignment, TextLayout, TextLayoutCache}, FontManager}, sound::decoder::{decode_mpeg_audio, decode_snd_chunk}, sprite::ColorRef, ScriptError};
 
 #[derive(Clone)]
 pub struct CastMember {
@@ -30,6 +32,67 @@ pub struct FieldMember {
   pub auto_tab: bool, // Tabbing order depends on sprite number order, not position on the Stage.
   pub editable: bool,
   pub border: u16,
+  pub height: u16,
+  pub style_runs: Vec<TextStyleRun>,
+  pub layout_cache: TextLayoutCache,
+}
+
+/// Formatting applied to the text of a field or text member, from `start` until the next run.
+#[derive(Clone, PartialEq)]
+pub struct TextStyleRun {
+  /// Index of the first character the run applies to.
+  pub start: usize,
+  pub font_id: u16,
+  pub font_name: Option<String>,
+  pub font_size: u16,
+  pub font_style: Vec<String>,
+  pub color: ColorRef,
+}
+
+impl TextStyleRun {
+  pub fn from_style_bits(style: u8) -> Vec<String> {
+    let mut result = vec![];
+    if style & TEXT_STYLE_BOLD != 0 {
+      result.push("bold".to_string());
+    }
+    if style & TEXT_STYLE_ITALIC != 0 {
+      result.push("italic".to_string());
+    }
+    if style & TEXT_STYLE_UNDERLINE != 0 {
+      result.push("underline".to_string());
+    }
+    if result.is_empty() {
+      result.push("plain".to_string());
+    }
+    result
+  }
+
+  fn from_xmedia_styles(text: &str, runs: &[XMediaStyleRun]) -> Vec<TextStyleRun> {
+    runs.iter().map(|run| {
+      TextStyleRun {
+        start: text.get(..run.start).map_or(run.start, |x| x.chars().count()),
+        font_id: 0,
+        font_name: run.font_name.clone(),
+        font_size: run.font_size,
+        font_style: Self::from_style_bits(run.style),
+        color: ColorRef::Rgb(run.color.0, run.color.1, run.color.2),
+      }
+    }).collect()
+  }
+
+  fn from_stxt_styles(text: &str, styles: &Vec<TextChunkStyle>) -> Vec<TextStyleRun> {
+    styles.iter().map(|style| {
+      let start_offset = style.start_offset as usize;
+      TextStyleRun {
+        start: text.get(..start_offset).map_or(start_offset, |x| x.chars().count()),
+        font_id: style.font_id,
+        font_name: None,
+        font_size: style.font_size,
+        font_style: Self::from_style_bits(style.style),
+        color: ColorRef::Rgb((style.color.0 >> 8) as u8, (style.color.1 >> 8) as u8, (style.color.2 >> 8) as u8),
+      }
+    }).collect()
+  }
 }
 
 #[derive(Clone)]
@@ -45,6 +108,9 @@ pub struct TextMember {
   pub fixed_line_space: u16,
   pub top_spacing: i16,
   pub width: u16,
+  pub height: u16,
+  pub style_runs: Vec<TextStyleRun>,
+  pub layout_cache: TextLayoutCache,
 }
 
 impl CastMember {
@@ -76,8 +142,30 @@ impl FieldMember {
       auto_tab: false,
       editable: false,
       border: 0,
+      height: 0,
+      style_runs: vec![],
+      layout_cache: TextLayoutCache::default(),
+    }
+  }
+
+  pub fn styled_text(&self) -> StyledText<'_> {
+    StyledText {
+      text: &self.text,
+      style_runs: &self.style_runs,
+      font: &self.font,
+      font_size: self.font_size,
+      font_style: font_style_bits(&self.font_style.split(',').collect::<Vec<_>>()),
+      alignment: TextAlignment::from_name(&self.alignment),
+      word_wrap: self.word_wrap,
+      fixed_line_space: self.fixed_line_space,
+      top_spacing: self.top_spacing,
+      width: self.width as i32,
     }
   }
+
+  pub fn layout(&self, font_manager: &FontManager) -> Rc<TextLayout> {
+    self.layout_cache.layout(font_manager, &self.styled_text())
+  }
 }
 
 impl TextMember {
@@ -94,8 +182,60 @@ impl TextMember {
       box_type: "adjust".to_string(),
       anti_alias: false,
       width: 100,
+      height: 0,
+      style_runs: vec![],
+      layout_cache: TextLayoutCache::default(),
+    }
+  }
+
+  /// Takes the formatting of the text member from the runs of its `XMED` chunk. The member-wide
+  /// font and alignment are those at the start of the text.
+  fn apply_xmedia_styles(&mut self, styled_text: &XMediaStyledText) {
+    self.style_runs = TextStyleRun::from_xmedia_styles(&styled_text.text, &styled_text.style_runs);
+    if let Some(first_run) = self.style_runs.first() {
+      if let Some(font_name) = &first_run.font_name {
+        self.font = font_name.clone();
+      }
+      self.font_size = first_run.font_size;
+      self.font_style = first_run.font_style.clone();
+    }
+    if let Some(first_paragraph) = styled_text.paragraph_runs.first() {
+      self.alignment = match first_paragraph.alignment {
+        1 => "center",
+        2 => "right",
+        3 => "justify",
+        _ => "left",
+      }.to_string();
+    }
+  }
+
+  pub fn styled_text(&self) -> StyledText<'_> {
+    StyledText {
+      text: &self.text,
+      style_runs: &self.style_runs,
+      font: &self.font,
+      font_size: self.font_size,
+      font_style: font_style_bits(&self.font_style),
+      alignment: TextAlignment::from_name(&self.alignment),
+      word_wrap: self.word_wrap,
+      fixed_line_space: self.fixed_line_space,
+      top_spacing: self.top_spacing,
+      width: self.width as i32,
     }
   }
+
+  pub fn layout(&self, font_manager: &FontManager) -> Rc<TextLayout> {
+    self.layout_cache.layout(font_manager, &self.styled_text())
+  }
+}
+
+/// A font embedded in the movie. Only TrueType and OpenType outlines can be rasterized: fonts
+/// embedded as PFR (Portable Font Resource) outlines have no `font_data`, and text using them
+/// is drawn with the system font.
+#[derive(Clone)]
+pub struct FontMember {
+  pub font_name: String,
+  pub font_data: Option<Rc<Vec<u8>>>,
 }
 
 #[derive(Clone)]
@@ -121,6 +261,66 @@ pub struct ShapeMember {
   pub shape_info: ShapeInfo
 }
 
+#[derive(Clone)]
+pub struct SoundMember {
+  pub sample_rate: u32,
+  pub channel_count: u16,
+  pub sample_size: u16,
+  /// Interleaved 16-bit PCM, shared with any sound channel playing it.
+  pub samples: Rc<Vec<i16>>,
+  pub looped: bool,
+  /// Loop start and end stored in the sound data, in sample frames.
+  pub loop_points: Option<(u32, u32)>,
+}
+
+impl SoundMember {
+  pub fn empty() -> SoundMember {
+    SoundMember {
+      sample_rate: 22050,
+      channel_count: 1,
+      sample_size: 16,
+      samples: Rc::new(vec![]),
+      looped: false,
+      loop_points: None,
+    }
+  }
+
+  pub fn frame_count(&self) -> usize {
+    self.samples.len() / self.channel_count.max(1) as usize
+  }
+
+  pub fn duration_ms(&self) -> u32 {
+    (self.frame_count() as u64 * 1000 / self.sample_rate.max(1) as u64) as u32
+  }
+
+  /// Returns the (left, right) sample at a fractional frame position, linearly interpolated.
+  pub fn sample_at(&self, position: f64) -> (f32, f32) {
+    let frame_count = self.frame_count();
+    if frame_count == 0 || position < 0.0 {
+      return (0.0, 0.0);
+    }
+    let index = position as usize;
+    if index >= frame_count {
+      return (0.0, 0.0);
+    }
+    let next_index = (index + 1).min(frame_count - 1);
+    let t = (position - index as f64) as f32;
+    let channels = self.channel_count.max(1) as usize;
+    let read = |frame: usize, channel: usize| self.samples[frame * channels + channel.min(channels - 1)] as f32 / 32768.0;
+    let left = read(index, 0) * (1.0 - t) + read(next_index, 0) * t;
+    let right = read(index, 1) * (1.0 - t) + read(next_index, 1) * t;
+    (left, right)
+  }
+}
+
+#[derive(Clone)]
+pub struct FilmLoopMember {
+  pub info: FilmLoopInfo,
+  pub frame_count: u32,
+  /// Non-empty sprite channels of every frame of the embedded score, as (frame index, channel index, data).
+  pub channel_data: Rc<Vec<(u32, u16, ScoreFrameChannelData)>>,
+}
+
 impl PaletteMember {
   pub fn new() -> PaletteMember {
     PaletteMember {
@@ -138,6 +338,9 @@ pub enum CastMemberType {
   Bitmap(BitmapMember),
   Palette(PaletteMember),
   Shape(ShapeMember),
+  Sound(SoundMember),
+  FilmLoop(FilmLoopMember),
+  Font(FontMember),
   Unknown
 }
 
@@ -149,6 +352,9 @@ pub enum CastMemberTypeId {
   Bitmap,
   Palette,
   Shape,
+  Sound,
+  FilmLoop,
+  Font,
   Unknown
 }
 
@@ -161,6 +367,9 @@ impl fmt::Debug for CastMemberType {
       Self::Bitmap(_) => { write!(f, "Bitmap") }
       Self::Palette(_) => { write!(f, "Palette") }
       Self::Shape(_) => { write!(f, "Shape") }
+      Self::Sound(_) => { write!(f, "Sound") }
+      Self::FilmLoop(_) => { write!(f, "FilmLoop") }
+      Self::Font(_) => { write!(f, "Font") }
       Self::Unknown => { write!(f, "Unknown") }
     }
   }
@@ -175,6 +384,9 @@ impl CastMemberTypeId {
       Self::Bitmap => { Ok("bitmap") }
       Self::Palette => { Ok("palette") }
       Self::Shape => { Ok("shape") }
+      Self::Sound => { Ok("sound") }
+      Self::FilmLoop => { Ok("filmLoop") }
+      Self::Font => { Ok("font") }
       _ => { Err(ScriptError::new("Unknown cast member type".to_string())) }
     }
   }
@@ -189,6 +401,9 @@ impl CastMemberType {
       Self::Bitmap(_) => { CastMemberTypeId::Bitmap }
       Self::Palette(_) => { CastMemberTypeId::Palette }
       Self::Shape(_) => { CastMemberTypeId::Shape }
+      Self::Sound(_) => { CastMemberTypeId::Sound }
+      Self::FilmLoop(_) => { CastMemberTypeId::FilmLoop }
+      Self::Font(_) => { CastMemberTypeId::Font }
       Self::Unknown => { CastMemberTypeId::Unknown }
     }
   }
@@ -201,6 +416,9 @@ impl CastMemberType {
       Self::Bitmap(_) => { "bitmap" }
       Self::Palette(_) => { "palette" }
       Self::Shape(_) => { "shape" }
+      Self::Sound(_) => { "sound" }
+      Self::FilmLoop(_) => { "filmLoop" }
+      Self::Font(_) => { "font" }
       _ => { "unknown" }
     }
   }
@@ -261,9 +479,71 @@ impl CastMemberType {
       _ => { None }
     }
   }
+
+  pub fn as_sound(&self) -> Option<&SoundMember> {
+    return match self {
+      Self::Sound(data) => { Some(data) }
+      _ => { None }
+    }
+  }
+
+  pub fn as_sound_mut(&mut self) -> Option<&mut SoundMember> {
+    return match self {
+      Self::Sound(data) => { Some(data) }
+      _ => { None }
+    }
+  }
+
+  pub fn as_shape(&self) -> Option<&ShapeMember> {
+    return match self {
+      Self::Shape(data) => { Some(data) }
+      _ => { None }
+    }
+  }
+
+  pub fn as_shape_mut(&mut self) -> Option<&mut ShapeMember> {
+    return match self {
+      Self::Shape(data) => { Some(data) }
+      _ => { None }
+    }
+  }
+
+  pub fn as_film_loop(&self) -> Option<&FilmLoopMember> {
+    return match self {
+      Self::FilmLoop(data) => { Some(data) }
+      _ => { None }
+    }
+  }
+
+  pub fn as_film_loop_mut(&mut self) -> Option<&mut FilmLoopMember> {
+    return match self {
+      Self::FilmLoop(data) => { Some(data) }
+      _ => { None }
+    }
+  }
+
+  pub fn as_font(&self) -> Option<&FontMember> {
+    return match self {
+      Self::Font(data) => { Some(data) }
+      _ => { None }
+    }
+  }
 }
 
 impl CastMember {
+  /// Whether the member is rich text, or from Director 7 on a member of the text Xtra. Both keep
+  /// their text in `RTE1` and their formatting in `XMED` or `RTE0`.
+  fn is_text_def(member_def: &CastMemberDef) -> bool {
+    let chunk = &member_def.chunk;
+    let is_text_type = match chunk.member_type {
+      MemberType::RTE => true,
+      // Other Xtras, such as Flash, keep their own data in `XMED` chunks
+      MemberType::Xtra => chunk.specific_data.xtra_name().is_some_and(|x| x.eq_ignore_ascii_case("text")),
+      _ => false,
+    };
+    is_text_type && member_def.children.iter().flatten().any(|x| x.as_xmedia().is_some() || x.as_rich_text().is_some())
+  }
+
   pub fn from(
     cast_lib: u32,
     number: u32, 
@@ -278,8 +558,41 @@ impl CastMember {
         let text_chunk = member_def.children[0].as_ref().unwrap().as_text().expect("Not a text chunk");
         let mut field_member = FieldMember::new();
         field_member.text = text_chunk.text.clone();
+        if let Some(field_info) = chunk.specific_data.field_info() {
+          field_member.alignment = field_info.alignment_name().to_string();
+          field_member.box_type = field_info.box_type_name().to_string();
+          field_member.word_wrap = field_info.word_wrap();
+          field_member.editable = field_info.editable();
+          field_member.auto_tab = field_info.auto_tab();
+          field_member.border = field_info.border as u16;
+          if field_info.rect.2 > field_info.rect.0 {
+            field_member.width = (field_info.rect.2 - field_info.rect.0) as u16;
+            field_member.height = (field_info.rect.3 - field_info.rect.1).max(0) as u16;
+          }
+        }
+        field_member.style_runs = TextStyleRun::from_stxt_styles(&text_chunk.text, &text_chunk.styles);
+        if let Some(first_run) = field_member.style_runs.first() {
+          field_member.font_size = first_run.font_size;
+          field_member.font_style = first_run.font_style.join(", ");
+        }
         CastMemberType::Field(field_member)
       }
+      MemberType::RTE | MemberType::Xtra if Self::is_text_def(member_def) => {
+        // Plain text from RTE1 is preferred, as extracting text from the styled data is best effort
+        let rich_text = member_def.children.iter().flatten().find_map(|x| x.as_rich_text().map(|x| x.text.clone()));
+        let styled_text = member_def.children.iter().flatten().find_map(|x| x.as_xmedia().and_then(|x| x.styled_text()));
+        let mut text_member = TextMember::new();
+        text_member.text = rich_text.clone().or_else(|| styled_text.as_ref().map(|x| x.text.clone())).unwrap_or_default();
+        // The runs index the styled text, so they only apply if the plain text matches it
+        if let Some(styled_text) = styled_text.filter(|x| rich_text.is_none() || rich_text.as_ref() == Some(&x.text)) {
+          text_member.apply_xmedia_styles(&styled_text);
+        }
+        if let Some(bitmap) = member_def.children.iter().flatten().find_map(|x| x.as_rich_text_bitmap()) {
+          text_member.width = bitmap.width;
+          text_member.height = bitmap.height;
+        }
+        CastMemberType::Text(text_member)
+      }
       MemberType::Script => {
         let member_info = chunk.member_info.as_ref().unwrap();
         let script_id = member_info.header.script_id;
@@ -332,20 +645,164 @@ impl CastMember {
         CastMemberType::Palette(PaletteMember { colors: palette_chunk.colors.clone() })
       }
       MemberType::Shape => {
-        CastMemberType::Shape(ShapeMember {
-          shape_info: chunk.specific_data.shape_info().unwrap().clone()
+        let shape_info = chunk.specific_data.shape_info().unwrap().clone();
+        if shape_pattern(shape_info.pattern).is_none() {
+          warn!("Shape member {} uses pattern {}, which is not supported, it will be filled solid", number, shape_info.pattern);
+        }
+        CastMemberType::Shape(ShapeMember { shape_info })
+      }
+      MemberType::Sound => {
+        let decoded = member_def.children.iter()
+          .flatten()
+          .find_map(|child| {
+            if let Some(snd_chunk) = child.as_sound() {
+              Some(decode_snd_chunk(snd_chunk))
+            } else if let Some(media_chunk) = child.as_media() {
+              media_chunk.mpeg_audio_data().map(decode_mpeg_audio)
+            } else {
+              None
+            }
+          });
+        let mut sound_member = SoundMember::empty();
+        match decoded {
+          Some(Ok(decoded)) => {
+            sound_member.sample_rate = decoded.sample_rate;
+            sound_member.channel_count = decoded.channel_count;
+            sound_member.sample_size = decoded.sample_size;
+            sound_member.loop_points = decoded.loop_points;
+            sound_member.samples = Rc::new(decoded.samples);
+          }
+          Some(Err(err)) => warn!("Could not decode sound member {}: {}", number, err),
+          None => {}
+        }
+        // Flag 0x10 in the member info marks a sound that should not loop
+        sound_member.looped = chunk.member_info.as_ref().is_some_and(|x| x.header.flags & 0x10 == 0);
+        CastMemberType::Sound(sound_member)
+      }
+      MemberType::FilmLoop => {
+        let frame_data = member_def.children.iter()
+          .flatten()
+          .find_map(|child| child.as_film_loop_score());
+        let (frame_count, channel_data) = match frame_data {
+          Some(frame_data) => (frame_data.header.frame_count, frame_data.frame_channel_data.clone()),
+          None => {
+            warn!("Film loop member {} has no score", number);
+            (0, vec![])
+          }
+        };
+        CastMemberType::FilmLoop(FilmLoopMember {
+          info: chunk.specific_data.film_loop_info().unwrap().clone(),
+          frame_count,
+          channel_data: Rc::new(channel_data),
+        })
+      }
+      MemberType::Font => {
+        let font_data = member_def.children.iter()
+          .flatten()
+          .find_map(|child| child.as_xmedia().and_then(|x| x.truetype_data()))
+          .map(|data| Rc::new(data.to_vec()));
+        let is_pfr = member_def.children.iter().flatten().any(|child| child.as_xmedia().is_some_and(|x| x.is_pfr_data()));
+        if font_data.is_none() && is_pfr {
+          warn!("Font member {} has PFR outlines, which are not supported, text using it will be drawn with the system font", number);
+        } else if font_data.is_none() {
+          warn!("Font member {} has no TrueType data, text using it will be drawn with the system font", number);
+        }
+        // Font members are named after their font, with a trailing asterisk
+        let name = chunk.member_info.as_ref().map(|x| x.name.as_str()).unwrap_or_default();
+        CastMemberType::Font(FontMember {
+          font_name: name.trim_end_matches(" *").to_owned(),
+          font_data,
         })
       }
       _ => { 
         CastMemberType::Unknown
       }
     };
+    // Shapes store their own colors, other members default to black on white
+    let (color, bg_color) = match &member_type {
+      CastMemberType::Shape(shape) => (ColorRef::PaletteIndex(shape.shape_info.fore_color), ColorRef::PaletteIndex(shape.shape_info.back_color)),
+      _ => (ColorRef::PaletteIndex(255), ColorRef::PaletteIndex(0)),
+    };
     CastMember {
       number,
       name: chunk.member_info.as_ref().map(|x| x.name.to_owned()).unwrap_or_default(),
       member_type: member_type,
-      color: ColorRef::PaletteIndex(255),
-      bg_color: ColorRef::PaletteIndex(0),
+      color,
+      bg_color,
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use binary_reader::BinaryReader;
+
+  use super::*;
+  use crate::director::chunks::{cast_member::CastMemberChunk, rich_text::{RichTextChunk, XMediaChunk, XMediaParagraphRun}, Chunk};
+
+  /// A Director 7 `CASt` chunk of an Xtra member without info.
+  fn xtra_member_chunk(xtra_name: &str) -> CastMemberChunk {
+    let mut specific_data = (xtra_name.len() as u32).to_be_bytes().to_vec();
+    specific_data.extend_from_slice(xtra_name.as_bytes());
+    let mut data = (MemberType::Xtra as u32).to_be_bytes().to_vec();
+    data.extend_from_slice(&0u32.to_be_bytes());
+    data.extend_from_slice(&(specific_data.len() as u32).to_be_bytes());
+    data.extend_from_slice(&specific_data);
+    CastMemberChunk::from_reader(&mut BinaryReader::from_vec(&data), 700).unwrap()
+  }
+
+  fn xtra_member(xtra_name: &str, xmedia: &[u8], rich_text: &str) -> CastMemberType {
+    let member_def = CastMemberDef {
+      chunk: xtra_member_chunk(xtra_name),
+      children: vec![
+        Some(Chunk::XMedia(XMediaChunk { data: xmedia.to_vec() })),
+        Some(Chunk::RichText(RichTextChunk { text: rich_text.to_string() })),
+      ],
+    };
+    CastMember::from(1, 1, &member_def, &None, &mut BitmapManager::new()).member_type
+  }
+
+  #[test]
+  fn text_xtra_members_load_as_styled_text() {
+    let xmedia = b"DEMX\0\0\0\x01B,Hello\rWorld2,0,0,6,1,2,0,C,1,0,0,0,1,18,0,FFFF,0,0,1,0,2,1,5,Arial7,Verdana";
+    let CastMemberType::Text(text_member) = xtra_member("text", xmedia, "Hello\rWorld") else {
+      panic!("not a text member")
+    };
+    assert_eq!(text_member.text, "Hello\rWorld");
+    assert_eq!((text_member.font.as_str(), text_member.font_size), ("Arial", 12));
+    assert_eq!(text_member.alignment, "right");
+    assert_eq!(text_member.style_runs.iter().map(|x| x.start).collect::<Vec<_>>(), vec![0, 6]);
+    assert_eq!(text_member.style_runs[1].font_size, 24);
+
+    // Other Xtras keep data of their own in XMED
+    assert!(!matches!(xtra_member("flash", xmedia, ""), CastMemberType::Text(_)));
+  }
+
+  #[test]
+  fn text_member_takes_xmedia_styles() {
+    let run = |start, font_name: &str, font_size, style| XMediaStyleRun {
+      start,
+      font_name: Some(font_name.to_string()),
+      font_size,
+      style,
+      color: (255, 0, 0),
+    };
+    let styled_text = XMediaStyledText {
+      text: "Café\rBar".to_string(),
+      style_runs: vec![run(0, "Arial", 12, 1), run(6, "Verdana", 24, 0)],
+      paragraph_runs: vec![XMediaParagraphRun { start: 0, alignment: 1 }],
+    };
+    let mut text_member = TextMember::new();
+    text_member.text = styled_text.text.clone();
+    text_member.apply_xmedia_styles(&styled_text);
+
+    assert_eq!((text_member.font.as_str(), text_member.font_size), ("Arial", 12));
+    assert_eq!(text_member.font_style, vec!["bold".to_string()]);
+    assert_eq!(text_member.alignment, "center");
+    // Byte offsets are converted to character offsets
+    let starts: Vec<usize> = text_member.style_runs.iter().map(|x| x.start).collect();
+    assert_eq!(starts, vec![0, 5]);
+    assert_eq!(text_member.style_runs[1].font_name.as_deref(), Some("Verdana"));
+    assert!(matches!(text_member.style_runs[1].color, ColorRef::Rgb(255, 0, 0)));
+  }
+}
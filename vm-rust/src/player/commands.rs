@@ -1,7 +1,6 @@
 use std::collections::HashMap;
 
 use async_std::channel::Receiver;
-use chrono::Local;
 use log::warn;
 use manual_future::ManualFuture;
 use url::Url;
@@ -11,7 +10,7 @@ use crate::{
 };
 
 use super::{
-    allocator::ScriptInstanceAllocatorTrait, cast_lib::CastMemberRef, cast_member::CastMemberType, datum_ref::{DatumId, DatumRef}, events::{player_dispatch_callback_event, player_dispatch_event_to_sprite, player_dispatch_targeted_event, player_wait_available}, font::player_load_system_font, keyboard_events::{player_key_down, player_key_up}, player_alloc_datum, player_call_script_handler, player_dispatch_global_event, player_is_playing, reserve_player_mut, reserve_player_ref, score::{concrete_sprite_hit_test, get_sprite_at}, script::ScriptInstanceId, script_ref::ScriptInstanceRef, PlayerVMExecutionItem, ScriptError, ScriptReceiver, PLAYER_TX
+    allocator::ScriptInstanceAllocatorTrait, cast_lib::CastMemberRef, datum_ref::{DatumId, DatumRef}, events::player_wait_available, font::player_load_system_font, keyboard_events::{player_key_down, player_key_up}, mouse_events::{player_mouse_down, player_mouse_move, player_mouse_up, player_mouse_wheel, player_right_mouse_down, player_right_mouse_up}, player_call_script_handler, player_dispatch_global_event, player_stop_movie, reserve_player_mut, reserve_player_ref, script::ScriptInstanceId, timeout::player_trigger_timeout, PlayerVMExecutionItem, ScriptError, ScriptReceiver, PLAYER_TX
 };
 
 #[allow(dead_code)]
@@ -34,6 +33,9 @@ pub enum PlayerVMCommand {
     MouseDown((i32, i32)),
     MouseUp((i32, i32)),
     MouseMove((i32, i32)),
+    RightMouseDown((i32, i32)),
+    RightMouseUp((i32, i32)),
+    MouseWheel(i32),
     KeyDown(String, u16),
     KeyUp(String, u16),
     RequestDatum(DatumId),
@@ -78,6 +80,9 @@ pub fn _format_player_cmd(command: &PlayerVMCommand) -> String {
         PlayerVMCommand::MouseDown((x, y)) => format!("MouseDown({}, {})", x, y),
         PlayerVMCommand::MouseUp((x, y)) => format!("MouseUp({}, {})", x, y),
         PlayerVMCommand::MouseMove((x, y)) => format!("MouseMove({}, {})", x, y),
+        PlayerVMCommand::RightMouseDown((x, y)) => format!("RightMouseDown({}, {})", x, y),
+        PlayerVMCommand::RightMouseUp((x, y)) => format!("RightMouseUp({}, {})", x, y),
+        PlayerVMCommand::MouseWheel(delta) => format!("MouseWheel({})", delta),
         PlayerVMCommand::KeyDown(key, ..) => format!("KeyDown({})", key),
         PlayerVMCommand::KeyUp(key, ..) => format!("KeyUp({})", key),
         PlayerVMCommand::RequestDatum(datum_ref) => format!("RequestDatum({})", datum_ref),
@@ -161,14 +166,14 @@ pub async fn run_player_command(command: PlayerVMCommand) -> Result<DatumRef, Sc
             });
         }
         PlayerVMCommand::Stop => {
-            reserve_player_mut(|player| {
-                player.stop();
-            });
+            player_stop_movie().await?;
         }
         PlayerVMCommand::Reset => {
+            let result = player_stop_movie().await;
             reserve_player_mut(|player| {
                 player.reset();
             });
+            result?;
         }
         PlayerVMCommand::LoadMovieFromFile(file_path) => {
             let player = unsafe { PLAYER_OPT.as_mut().unwrap() };
@@ -213,46 +218,7 @@ pub async fn run_player_command(command: PlayerVMCommand) -> Result<DatumRef, Sc
             });
         }
         PlayerVMCommand::TimeoutTriggered(timeout_ref) => {
-            let (is_found, is_playing, is_script_paused, target_ref, handler_name, timeout_name) =
-                reserve_player_mut(|player| {
-                    if let Some(timeout) = player.timeout_manager.get_timeout(&timeout_ref) {
-                        let is_playing = player.is_playing;
-                        let is_script_paused = player.is_script_paused;
-                        (
-                            true,
-                            is_playing,
-                            is_script_paused,
-                            timeout.target_ref.clone(),
-                            timeout.handler.to_owned(),
-                            timeout.name.to_owned(),
-                        )
-                    } else {
-                        (
-                            false,
-                            false,
-                            false,
-                            DatumRef::Void,
-                            "".to_string(),
-                            "".to_string(),
-                        )
-                    }
-                });
-            if !is_found {
-                warn!("Timeout triggered but not found: {}", timeout_ref);
-                return Ok(DatumRef::Void);
-            }
-            if !is_playing || is_script_paused {
-                // TODO how to handle is_script_paused?
-                warn!("Timeout triggered but not playing");
-                return Ok(DatumRef::Void);
-            }
-            let ref_datum = player_alloc_datum(Datum::TimeoutRef(timeout_name));
-            let args = vec![ref_datum];
-            if target_ref != DatumRef::Void {
-                player_dispatch_callback_event(target_ref, &handler_name, &args);
-            } else {
-                player_dispatch_global_event(&handler_name, &args);
-            }
+            player_trigger_timeout(&timeout_ref).await;
         }
         PlayerVMCommand::PrintMemberBitmapHex(member_ref) => {
             reserve_player_ref(|player| {
@@ -268,98 +234,22 @@ pub async fn run_player_command(command: PlayerVMCommand) -> Result<DatumRef, Sc
             });
         }
         PlayerVMCommand::MouseDown((x, y)) => {
-            if !player_is_playing().await {
-                return Ok(DatumRef::Void);
-            }
-            let instance_ids = reserve_player_mut(|player| {
-                let now = Local::now().timestamp_millis().abs();
-                let is_double_click = (now - player.last_mouse_down_time) < 500;
-                player.mouse_loc = (x, y);
-                player.is_double_click = is_double_click;
-                player.last_mouse_down_time = now;
-                let sprite = get_sprite_at(player, x, y, true);
-                if let Some(sprite_number) = sprite {
-                    let sprite = player.movie.score.get_sprite(sprite_number as i16);
-                    let sprite_member = sprite
-                        .and_then(|x| x.member.as_ref())
-                        .and_then(|x| player.movie.cast_manager.find_member_by_ref(&x));
-                    if let Some(sprite_member) = sprite_member {
-                        match &sprite_member.member_type {
-                            CastMemberType::Field(field_member) => {
-                                if field_member.editable {
-                                    player.keyboard_focus_sprite = sprite_number as i16;
-                                }
-                            }
-                            _ => {}
-                        }
-                    }
-
-                    player.mouse_down_sprite = sprite_number as i16;
-                    sprite.map(|x| x.script_instance_list.clone())
-                } else {
-                    None
-                }
-            });
-            player_dispatch_targeted_event(
-                &"mouseDown".to_string(),
-                &vec![],
-                instance_ids.as_ref(),
-            );
-            return Ok(DatumRef::Void);
+            return player_mouse_down(x, y).await;
         }
         PlayerVMCommand::MouseUp((x, y)) => {
-            if !player_is_playing().await {
-                return Ok(DatumRef::Void);
-            }
-            let result = reserve_player_mut(|player| {
-                player.mouse_loc = (x, y);
-                let sprite = if player.mouse_down_sprite > 0 {
-                    player.movie.score.get_sprite(player.mouse_down_sprite)
-                } else {
-                    None
-                };
-                player.mouse_down_sprite = -1;
-                if let Some(sprite) = sprite {
-                    let is_inside = concrete_sprite_hit_test(player, sprite, x, y);
-                    Some((sprite.script_instance_list.clone(), is_inside))
-                } else {
-                    None
-                }
-            });
-            let is_inside = result.as_ref().map(|x| x.1).unwrap_or(true);
-            let instance_ids = result.as_ref().map(|x| &x.0);
-            let event_name = if is_inside { "mouseUp" } else { "mouseUpOutSide" };
-            player_dispatch_targeted_event(&event_name.to_string(), &vec![], instance_ids);
-            reserve_player_mut(|player| {
-                player.is_double_click = false;
-            });
-            return Ok(DatumRef::Void);
+            return player_mouse_up(x, y).await;
+        }
+        PlayerVMCommand::RightMouseDown((x, y)) => {
+            return player_right_mouse_down(x, y).await;
+        }
+        PlayerVMCommand::RightMouseUp((x, y)) => {
+            return player_right_mouse_up(x, y).await;
         }
         PlayerVMCommand::MouseMove((x, y)) => {
-            if !player_is_playing().await {
-                return Ok(DatumRef::Void);
-            }
-            let (sprite_num, hovered_sprite) = reserve_player_mut(|player| {
-                player.mouse_loc = (x, y);
-                
-                let hovered_sprite = player.hovered_sprite;
-                let sprite_num = get_sprite_at(player, x, y, false);
-                if let Some(sprite_num) = sprite_num {
-                    player.hovered_sprite = Some(sprite_num as i16);
-                }
-                (sprite_num, hovered_sprite)
-            });
-            if let Some(sprite_num) = sprite_num {
-                let hovered_sprite = hovered_sprite.unwrap_or(-1);
-                if hovered_sprite != sprite_num as i16 {
-                    if hovered_sprite != -1 {
-                        player_dispatch_event_to_sprite(&"mouseLeave".to_string(), &vec![], hovered_sprite as u16)
-                    }
-                    player_dispatch_event_to_sprite(&"mouseEnter".to_string(), &vec![], sprite_num as u16);
-                } else {
-                    player_dispatch_event_to_sprite(&"mouseWithin".to_string(), &vec![], sprite_num as u16);
-                }
-            }
+            return player_mouse_move(x, y).await;
+        }
+        PlayerVMCommand::MouseWheel(delta) => {
+            return player_mouse_wheel(delta).await;
         }
         PlayerVMCommand::KeyDown(key, code) => {
             return player_key_down(key, code).await;
@@ -1,4 +1,6 @@
-use super::{bytecode::handler_manager::BytecodeHandlerContext, scope::ScopeRef, script::{get_current_handler_def, get_current_variable_multiplier, get_name}, DatumRef, DirPlayer, ScriptError};
+use crate::{director::lingo::datum::Datum, js_api::JsApi};
+
+use super::{bytecode::handler_manager::BytecodeHandlerContext, cast_member::CastMemberType, scope::ScopeRef, script::{get_current_handler_def, get_current_variable_multiplier, get_name, script_get_prop, script_set_prop}, DatumRef, DirPlayer, ScriptError};
 
 pub fn read_context_var_args(player: &mut DirPlayer, var_type: u32, scope_ref: ScopeRef) -> (DatumRef, Option<DatumRef>) {
   let scope = player.scopes.get_mut(scope_ref).unwrap();
@@ -15,7 +17,7 @@ pub fn read_context_var_args(player: &mut DirPlayer, var_type: u32, scope_ref: S
 pub fn player_get_context_var(
   player: &mut DirPlayer, 
   id_ref: &DatumRef,
-  _cast_id_ref: Option<&DatumRef>,
+  cast_id_ref: Option<&DatumRef>,
   var_type: u32, 
   ctx: &BytecodeHandlerContext,
 ) -> Result<DatumRef, ScriptError> {
@@ -25,7 +27,18 @@ pub fn player_get_context_var(
   
   match var_type {
     // global | global | property/instance
-    0x1 | 0x2 | 0x3 => Err(ScriptError::new("readVar global/prop/instance not implemented".to_string())),
+    0x1 | 0x2 => {
+      let global_name = get_name(player, ctx, id.int_value()? as u16).unwrap();
+      Ok(player.globals.get(global_name).unwrap_or(&DatumRef::Void).clone())
+    }
+    0x3 => {
+      let prop_name = get_name(player, ctx, id.int_value()? as u16).unwrap().to_owned();
+      let receiver = player.scopes.get(ctx.scope_ref).unwrap().receiver.clone();
+      match receiver {
+        Some(instance_ref) => script_get_prop(player, &instance_ref, &prop_name),
+        None => Err(ScriptError::new(format!("No receiver to get prop {}", prop_name))),
+      }
+    }
     0x4 => {
       // arg
       let arg_index = (id.int_value()? / variable_multiplier as i32) as usize;
@@ -45,7 +58,9 @@ pub fn player_get_context_var(
     }
     0x6 => {
       // field
-      Err(ScriptError::new("readVar field not implemented".to_string()))
+      let cast_id = cast_id_ref.map(|x| player.get_datum(x));
+      let field_value = player.movie.cast_manager.get_field_value_by_identifiers(id, cast_id, &player.allocator)?;
+      Ok(player.alloc_datum(Datum::String(field_value)))
     }
     _ => Err(ScriptError::new(format!("Invalid context var type: {}", var_type)))
   }
@@ -54,7 +69,7 @@ pub fn player_get_context_var(
 pub fn player_set_context_var(
   player: &mut DirPlayer, 
   id_ref: &DatumRef,
-  _cast_id_ref: Option<&DatumRef>,
+  cast_id_ref: Option<&DatumRef>,
   var_type: u32, 
   value_ref: &DatumRef, 
   ctx: &BytecodeHandlerContext, 
@@ -65,7 +80,19 @@ pub fn player_set_context_var(
   
   match var_type {
     // global | global | property/instance
-    0x1 | 0x2 | 0x3 => Err(ScriptError::new("set readVar global/prop/instance not implemented".to_string())),
+    0x1 | 0x2 => {
+      let global_name = get_name(player, ctx, id.int_value()? as u16).unwrap().to_owned();
+      player.globals.insert(global_name, value_ref.clone());
+      Ok(())
+    }
+    0x3 => {
+      let prop_name = get_name(player, ctx, id.int_value()? as u16).unwrap().to_owned();
+      let receiver = player.scopes.get(ctx.scope_ref).unwrap().receiver.clone();
+      match receiver {
+        Some(instance_ref) => script_set_prop(player, &instance_ref, &prop_name, value_ref, false),
+        None => Err(ScriptError::new(format!("No receiver to set prop {}", prop_name))),
+      }
+    }
     0x4 => {
       // arg
       let arg_index = (id.int_value()? / variable_multiplier as i32) as usize;
@@ -84,7 +111,19 @@ pub fn player_set_context_var(
     }
     0x6 => {
       // field
-      Err(ScriptError::new("set readVar field not implemented".to_string()))
+      let cast_id = cast_id_ref.map(|x| player.get_datum(x));
+      let member_ref = player.movie.cast_manager
+        .find_member_ref_by_identifiers(id, cast_id, &player.allocator)?
+        .ok_or_else(|| ScriptError::new("Cast member not found".to_string()))?;
+      let value = player.get_datum(value_ref).string_value()?;
+      let member = player.movie.cast_manager.find_mut_member_by_ref(&member_ref).unwrap();
+      match &mut member.member_type {
+        CastMemberType::Field(field) => field.text = value,
+        CastMemberType::Text(text) => text.text = value,
+        _ => return Err(ScriptError::new("Cast member is not a field".to_string())),
+      }
+      JsApi::dispatch_cast_member_changed(member_ref);
+      Ok(())
     }
     _ => Err(ScriptError::new(format!("set Invalid context var type: {}", var_type)))
   }
@@ -26,6 +26,7 @@ pub fn format_concrete_datum(datum: &Datum, player: &DirPlayer) -> String {
     Datum::Symbol(s) => format!("#{s}"),
     Datum::CastLib(n) => format!("castLib({n})"),
     Datum::Stage => "the stage".to_string(),
+    Datum::Window(name) => format!("window \"{name}\""),
     Datum::PropList(entries, ..) => {
       if entries.is_empty() {
         return "[:]".to_string();
@@ -101,6 +102,9 @@ pub fn format_concrete_datum(datum: &Datum, player: &DirPlayer) -> String {
     Datum::SoundRef(_) => {
       format!("<_sound>")
     }
+    Datum::Media(_) => {
+      "<media>".to_string()
+    }
   }
 }
 
@@ -1,18 +1,16 @@
+use std::rc::Rc;
+
 use log::error;
 use pest::{iterators::Pair, Parser};
 
-use crate::{console_error, director::lingo::datum::{datum_bool, Datum, DatumType}, js_api::ascii_safe};
+use crate::{console_error, director::{enums::ScriptType, lingo::{compiler::{compile_expression, compile_statements}, datum::{datum_bool, Datum, DatumType}, script::ScriptContext}}, js_api::ascii_safe};
 
-use super::{sprite::ColorRef, DatumRef, DirPlayer, ScriptError};
+use super::{cast_lib::cast_member_ref, player_call_loaded_script_handler, reserve_player_mut, script::Script, sprite::ColorRef, DatumRef, DirPlayer, ScriptError};
 
 #[derive(Parser)]
 #[grammar = "lingo.pest"]
 struct LingoParser;
 
-fn tokenize_lingo(_expr: &String) -> Vec<String> {
-  [].to_vec()
-}
-
 pub fn eval_lingo_pair(pair: Pair<Rule>, player: &mut DirPlayer) -> Result<DatumRef, ScriptError> {
   // warn!("eval_lingo_expr: {:?}", pair);
 
@@ -119,19 +117,167 @@ pub fn eval_lingo_pair(pair: Pair<Rule>, player: &mut DirPlayer) -> Result<Datum
   }
 }
 
-pub fn eval_lingo(expr: String, player: &mut DirPlayer) -> Result<DatumRef, ScriptError>{
-  let _tokens = tokenize_lingo(&expr);
-  match LingoParser::parse(
-    Rule::eval_expr, 
-    expr.as_str()
-  ) {
-    Ok(parse_result) => {
-      let expr_pair = &parse_result.enumerate().next().unwrap();
-      eval_lingo_pair(expr_pair.1.clone(), player)
+/// Evaluates literals like `[#a: 1]` or `rect(0, 0, 1, 1)` without compiling them, which covers
+/// most uses of `value()`.
+fn eval_lingo_literal(expr: &str, player: &mut DirPlayer) -> Option<Result<DatumRef, ScriptError>> {
+  let parse_result = LingoParser::parse(Rule::eval_expr, expr).ok()?;
+  let expr_pair = parse_result.enumerate().next().unwrap();
+  Some(eval_lingo_pair(expr_pair.1, player))
+}
+
+/// Compiles source for `do` or `value()` into a script with a single handler. The script uses
+/// the names of the cast of the running handler, as bytecode is always run against the names
+/// of the cast its script belongs to.
+fn compile_eval_script(source: &str, is_expression: bool) -> Result<Rc<Script>, ScriptError> {
+  reserve_player_mut(|player| {
+    let cast_lib = if player.scope_count > 0 {
+      player.scopes.get(player.current_scope_ref()).unwrap().script_ref.cast_lib.max(1) as u32
+    } else {
+      1
+    };
+    if player.movie.cast_manager.get_cast_or_null(cast_lib).is_none() {
+      return Err(ScriptError::new(format!("Cannot compile Lingo without cast {}", cast_lib)));
     }
-    Err(e) => {
-      error!("Lingo parse error: {}", ascii_safe(&e.to_string()));
-      Ok(DatumRef::Void)
+    let cast = player.movie.cast_manager.get_cast_mut(cast_lib);
+    let (dir_version, capital_x) = (cast.dir_version, cast.capital_x);
+    let lctx = cast.lctx.get_or_insert_with(ScriptContext::default);
+    let (chunk, handler_name) = if is_expression {
+      (compile_expression(source, &mut lctx.names, dir_version, capital_x), "value")
+    } else {
+      (compile_statements(source, &mut lctx.names, dir_version, capital_x), "do")
+    };
+    let chunk = chunk.map_err(ScriptError::new)?;
+    let script = Script::new(
+      cast_member_ref(cast_lib as i32, 0),
+      handler_name.to_owned(),
+      chunk,
+      ScriptType::Movie,
+      &lctx.names,
+    );
+    Ok(Rc::new(script))
+  })
+}
+
+/// Evaluates the expression of a `value()` call. Expressions that do not compile evaluate to VOID.
+pub async fn eval_lingo(expr: String) -> Result<DatumRef, ScriptError> {
+  if let Some(result) = reserve_player_mut(|player| eval_lingo_literal(&expr, player)) {
+    return result;
+  }
+  let script = match compile_eval_script(&expr, true) {
+    Ok(script) => script,
+    Err(err) => {
+      error!("Lingo parse error: {}", ascii_safe(&err.message));
+      return Ok(DatumRef::Void);
     }
-  } 
+  };
+  let handler_name = script.name.to_owned();
+  let scope = player_call_loaded_script_handler(None, script, &handler_name, &vec![], true).await?;
+  Ok(scope.return_value)
+}
+
+/// Runs the statements of a `do` command.
+pub async fn eval_lingo_command(source: String) -> Result<(), ScriptError> {
+  let script = compile_eval_script(&source, false)?;
+  let handler_name = script.name.to_owned();
+  player_call_loaded_script_handler(None, script, &handler_name, &vec![], true).await?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use async_std::task::block_on;
+
+  use crate::{
+    director::lingo::datum::Datum,
+    player::{
+      cast_lib::cast_member_ref,
+      cast_member::{CastMemberType, ScriptMember},
+      handlers::{datum_handlers::cast_member::script::ScriptMemberHandlers, types::TypeHandlers},
+      reserve_player_mut,
+      testing::{add_test_cast, with_test_player},
+    },
+  };
+
+  use super::*;
+
+  /// Adds the cast that `value()` and `do` compile against, as a Director 11 cast.
+  fn add_eval_cast(members: Vec<(u32, &str, CastMemberType)>) {
+    let cast_lib = add_test_cast(members);
+    reserve_player_mut(|player| player.movie.cast_manager.get_cast_mut(cast_lib).dir_version = 1100);
+  }
+
+  fn value(expr: &str) -> Datum {
+    let arg = reserve_player_mut(|player| player.alloc_datum(Datum::String(expr.to_string())));
+    let result = block_on(TypeHandlers::value(&vec![arg])).unwrap();
+    reserve_player_mut(|player| player.get_datum(&result).clone())
+  }
+
+  fn do_lingo(source: &str) {
+    let arg = reserve_player_mut(|player| player.alloc_datum(Datum::String(source.to_string())));
+    block_on(TypeHandlers::do_lingo(&vec![arg])).unwrap();
+  }
+
+  #[test]
+  fn value_compiles_expressions() {
+    with_test_player(|_| {
+      add_eval_cast(vec![]);
+      assert!(matches!(value("1+2"), Datum::Int(3)));
+      assert!(matches!(value("(1 + 2) * 3 - 1"), Datum::Int(8)));
+      assert!(matches!(value("\"a\" & \"b\""), Datum::String(s) if s == "ab"));
+    });
+  }
+
+  #[test]
+  fn value_reads_sprite_properties() {
+    with_test_player(|_| {
+      add_eval_cast(vec![]);
+      reserve_player_mut(|player| {
+        player.movie.score.set_channel_count(5);
+        let sprite = player.movie.score.get_sprite_mut(3);
+        sprite.loc_h = 10;
+        sprite.loc_v = 20;
+      });
+      assert!(matches!(value("sprite(3).loc"), Datum::IntPoint((10, 20))));
+      assert!(matches!(value("the locH of sprite 3"), Datum::Int(10)));
+    });
+  }
+
+  #[test]
+  fn do_runs_statements() {
+    with_test_player(|_| {
+      add_eval_cast(vec![]);
+      do_lingo("go to frame 5");
+      assert_eq!(reserve_player_mut(|player| player.next_frame), Some(5));
+    });
+  }
+
+  #[test]
+  fn script_text_round_trips() {
+    with_test_player(|_| {
+      let script = ScriptMember { script_id: 1, script_type: ScriptType::Score, name: "doubler".to_string() };
+      add_eval_cast(vec![(1, "doubler", CastMemberType::Script(script))]);
+      let member_ref = cast_member_ref(1, 1);
+      let source = "on double x\r  return x * 2\rend";
+
+      ScriptMemberHandlers::set_prop(&member_ref, &"scriptText".to_string(), Datum::String(source.to_string())).unwrap();
+      let text = reserve_player_mut(|player| ScriptMemberHandlers::get_prop(player, &member_ref, &"scriptText".to_string())).unwrap();
+      assert!(matches!(text, Datum::String(text) if text == source));
+
+      // Once a movie script, its handlers can be called from other Lingo
+      ScriptMemberHandlers::set_prop(&member_ref, &"scriptType".to_string(), Datum::Symbol("movie".to_string())).unwrap();
+      assert!(matches!(value("double(21)"), Datum::Int(42)));
+    });
+  }
+
+  #[test]
+  fn script_props_of_uncompiled_members_are_script_errors() {
+    with_test_player(|_| {
+      let script = ScriptMember { script_id: 1, script_type: ScriptType::Score, name: "empty".to_string() };
+      add_eval_cast(vec![(1, "empty", CastMemberType::Script(script))]);
+      let member_ref = cast_member_ref(1, 1);
+
+      let result = reserve_player_mut(|player| ScriptMemberHandlers::get_prop(player, &member_ref, &"scriptText".to_string()));
+      assert!(matches!(result, Err(err) if err.message == "Script member 1 has no compiled script"));
+    });
+  }
 }
@@ -50,33 +50,6 @@ pub fn player_dispatch_targeted_event(
     .unwrap();
 }
 
-pub fn player_dispatch_event_to_sprite(
-    handler_name: &String,
-    args: &Vec<DatumRef>,
-    sprite_num: u16,
-) {
-    let instance_ids = reserve_player_ref(|player| {
-        let sprite = player.movie.score.get_sprite(sprite_num as i16);
-        if let Some(sprite) = sprite {
-            let instance_ids = sprite.script_instance_list.clone();
-            Some(instance_ids)
-        } else {
-            None
-        }
-    });
-    if instance_ids.is_none() {
-        return;
-    }
-    let instance_ids = instance_ids.unwrap();
-    let tx = unsafe { PLAYER_EVENT_TX.clone() }.unwrap();
-    tx.try_send(PlayerVMEvent::Targeted(
-        handler_name.to_owned(),
-        args.to_owned(),
-        Some(instance_ids),
-    ))
-    .unwrap();
-}
-
 pub async fn player_invoke_event_to_instances(
     handler_name: &String,
     args: &Vec<DatumRef>,
@@ -96,7 +69,7 @@ pub async fn player_invoke_event_to_instances(
     let mut handled = false;
     for (script_instance_ref, handler_ref) in recv_instance_handlers {
         let scope = player_call_script_handler(Some(script_instance_ref), handler_ref, args).await?;
-        if !scope.passed {
+        if !scope.passed || player_is_event_stopped() {
             handled = true;
             break;
         }
@@ -105,7 +78,50 @@ pub async fn player_invoke_event_to_instances(
     Ok(handled)
 }
 
-async fn player_invoke_static_event(
+/// Sends an event to every instance that handles it, regardless of whether a previous one passed it.
+/// Returns true if one of them called `stopEvent`.
+pub async fn player_invoke_event_to_each_instance(
+    handler_name: &String,
+    args: &Vec<DatumRef>,
+    instance_refs: &Vec<ScriptInstanceRef>,
+) -> Result<bool, ScriptError> {
+    for instance_ref in instance_refs {
+        let handler_ref = reserve_player_ref(|player| {
+            ScriptInstanceUtils::get_script_instance_handler(handler_name, instance_ref, player)
+        })?;
+        if let Some(handler_ref) = handler_ref {
+            player_call_script_handler(Some(instance_ref.clone()), handler_ref, args).await?;
+            if player_is_event_stopped() {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+pub async fn player_invoke_static_script_event(
+    script_member_ref: &CastMemberRef,
+    handler_name: &String,
+    args: &Vec<DatumRef>,
+) -> Result<bool, ScriptError> {
+    let has_handler = reserve_player_ref(|player| {
+        let script = player.movie.cast_manager.get_script_by_ref(script_member_ref);
+        let handler = script.and_then(|x| x.get_handler(handler_name));
+        handler.is_some()
+    });
+    if !has_handler {
+        return Ok(false);
+    }
+    let result = player_call_script_handler(
+        None,
+        (script_member_ref.to_owned(), handler_name.to_owned()),
+        args
+    ).await?;
+    Ok(!result.passed)
+}
+
+/// Sends an event to the frame script and then the movie scripts, until one handles it without passing it.
+pub async fn player_invoke_static_event(
     handler_name: &String,
     args: &Vec<DatumRef>,
 ) -> Result<bool, ScriptError> {
@@ -133,20 +149,7 @@ async fn player_invoke_static_event(
 
     let mut handled = false;
     for script_member_ref in active_static_scripts {
-        let has_handler = reserve_player_ref(|player| {
-            let script = player.movie.cast_manager.get_script_by_ref(&script_member_ref);
-            let handler = script.and_then(|x| x.get_handler(handler_name));
-            handler.is_some()
-        });
-        if !has_handler {
-            continue;
-        }
-        let result = player_call_script_handler(
-            None, 
-            (script_member_ref, handler_name.to_owned()), 
-            args
-        ).await?;
-        if !result.passed {
+        if player_invoke_static_script_event(&script_member_ref, handler_name, args).await? || player_is_event_stopped() {
             handled = true;
             break;
         }
@@ -159,16 +162,21 @@ async fn player_invoke_targeted_event(
     args: &Vec<DatumRef>,
     instance_refs: Option<&Vec<ScriptInstanceRef>>,
 ) -> Result<DatumRef, ScriptError> {
-    let handled = match instance_refs {
-        Some(instance_refs) => {
-            player_invoke_event_to_instances(handler_name, args, instance_refs).await?
+    let was_stopped = player_begin_event();
+    let result = async {
+        let handled = match instance_refs {
+            Some(instance_refs) => {
+                player_invoke_event_to_instances(handler_name, args, instance_refs).await?
+            }
+            None => false,
+        };
+        if !handled {
+            player_invoke_static_event(handler_name, args).await?;
         }
-        None => false,
-    };
-    if !handled {
-        player_invoke_static_event(handler_name, args).await?;
-    }
-    Ok(DatumRef::Void)
+        Ok(DatumRef::Void)
+    }.await;
+    player_end_event(was_stopped);
+    result
 }
 
 pub async fn player_invoke_global_event(
@@ -199,14 +207,90 @@ pub async fn player_invoke_global_event(
         active_instance_scripts.to_owned()
     });
 
-    let handled =
-        player_invoke_event_to_instances(handler_name, args, &active_instance_scripts).await?;
-    if handled {
-        return Ok(DatumRef::Void);
+    let was_stopped = player_begin_event();
+    let result = async {
+        let handled =
+            player_invoke_event_to_instances(handler_name, args, &active_instance_scripts).await?;
+        if !handled {
+            player_invoke_static_event(handler_name, args).await?;
+        }
+        Ok(DatumRef::Void)
+    }.await;
+    player_end_event(was_stopped);
+    result
+}
+
+/// Sends a frame event such as `prepareFrame` or `enterFrame`. Every sprite behavior receives it,
+/// then the frame script and the movie scripts until one of them handles it without passing it.
+/// A handler calling `stopEvent` keeps it from going any further.
+pub async fn player_invoke_frame_event(
+    handler_name: &String,
+    args: &Vec<DatumRef>,
+) -> Result<DatumRef, ScriptError> {
+    let sprite_instances = reserve_player_ref(|player| {
+        player.movie.score.get_active_script_instance_list()
+    });
+
+    let was_stopped = player_begin_event();
+    let result = async {
+        let stopped = player_invoke_event_to_each_instance(handler_name, args, &sprite_instances).await?;
+        if !stopped {
+            player_invoke_static_event(handler_name, args).await?;
+        }
+        Ok(DatumRef::Void)
+    }.await;
+    player_end_event(was_stopped);
+    result
+}
+
+/// Sends `stepFrame` to every object in the actorList.
+pub async fn player_step_actors() -> Result<(), ScriptError> {
+    let actors = reserve_player_mut(|player| {
+        let actor_list = player.get_actor_list();
+        match player.get_datum(&actor_list) {
+            Datum::List(_, items, _) => items
+                .iter()
+                .filter_map(|item| match player.get_datum(item) {
+                    Datum::ScriptInstanceRef(instance_ref) => Some(instance_ref.clone()),
+                    _ => None,
+                })
+                .collect(),
+            _ => vec![],
+        }
+    });
+    let was_stopped = player_begin_event();
+    let result = player_invoke_event_to_each_instance(&"stepFrame".to_string(), &vec![], &actors).await;
+    player_end_event(was_stopped);
+    result.map(|_| ())
+}
+
+/// Steps the actorList and sends `prepareFrame`. Calling `updateStage` from these handlers redraws
+/// the stage without preparing the frame again, which would otherwise recurse without end.
+pub async fn player_prepare_frame() -> Result<(), ScriptError> {
+    let was_preparing = reserve_player_mut(|player| std::mem::replace(&mut player.is_preparing_frame, true));
+    if was_preparing {
+        return Ok(());
     }
-    player_invoke_static_event(handler_name, args).await?;
+    let result = async {
+        player_step_actors().await?;
+        player_invoke_frame_event(&"prepareFrame".to_string(), &vec![]).await?;
+        Ok(())
+    }.await;
+    reserve_player_mut(|player| player.is_preparing_frame = false);
+    result
+}
+
+/// Clears the `stopEvent` flag for a new event, returning the flag of the event it interrupts.
+pub fn player_begin_event() -> bool {
+    reserve_player_mut(|player| std::mem::replace(&mut player.is_event_stopped, false))
+}
+
+pub fn player_end_event(was_stopped: bool) {
+    reserve_player_mut(|player| player.is_event_stopped = was_stopped);
+}
 
-    Ok(DatumRef::Void)
+fn player_is_event_stopped() -> bool {
+    reserve_player_ref(|player| player.is_event_stopped)
 }
 
 pub async fn run_event_loop(rx: Receiver<PlayerVMEvent>) {
@@ -251,3 +335,137 @@ pub fn player_unwrap_result(result: Result<DatumRef, ScriptError>) -> DatumRef {
 pub async fn player_wait_available() {
     PLAYER_SEMAPHONE.lock().await;
 }
+
+#[cfg(test)]
+mod tests {
+    use async_std::task::block_on;
+
+    use crate::{
+        director::enums::ScriptType,
+        player::{
+            cast_lib::cast_member_ref,
+            player_run_movie,
+            player_stop_movie,
+            score::ScoreSpriteSpan,
+            testing::{add_test_scripts, log_events, logging_handler, start_test_log, take_test_log, with_test_player},
+        },
+    };
+
+    use super::*;
+
+    /// Runs the movie for the given number of frames and stops it, returning the log of events.
+    fn run_movie(frames: u32) -> String {
+        start_test_log();
+        reserve_player_mut(|player| player.is_playing = true);
+        block_on(async {
+            player_run_movie(Some(frames)).await;
+            player_stop_movie().await.unwrap();
+        });
+        take_test_log()
+    }
+
+    /// Places the given behaviors on sprite 1 for the first ten frames.
+    fn add_sprite_behaviors(cast_lib: u32, behaviors: &[u32]) {
+        reserve_player_mut(|player| {
+            player.movie.score.set_channel_count(1);
+            player.movie.score.sprite_spans.push(ScoreSpriteSpan {
+                sprite_number: 1,
+                start_frame: 1,
+                end_frame: 10,
+                behaviors: behaviors.iter().map(|x| cast_member_ref(cast_lib as i32, *x as i32)).collect(),
+            });
+        });
+    }
+
+    #[test]
+    fn sends_movie_and_frame_events_in_order() {
+        with_test_player(|_| {
+            let events = ["prepareMovie", "startMovie", "prepareFrame", "enterFrame", "exitFrame", "stopMovie"];
+            add_test_scripts(vec![(1, "main", ScriptType::Movie, &log_events(&events, ""))]);
+            assert_eq!(
+                run_movie(2),
+                "prepareMovie prepareFrame startMovie enterFrame exitFrame prepareFrame enterFrame exitFrame stopMovie "
+            );
+        });
+    }
+
+    #[test]
+    fn ends_sprites_before_stopping_the_movie() {
+        with_test_player(|_| {
+            let cast_lib = add_test_scripts(vec![
+                (1, "main", ScriptType::Movie, &log_events(&["startMovie", "stopMovie"], "")),
+                (2, "behavior", ScriptType::Score, &log_events(&["beginSprite", "endSprite"], "me")),
+            ]);
+            add_sprite_behaviors(cast_lib, &[2]);
+            assert_eq!(run_movie(1), "beginSprite startMovie endSprite stopMovie ");
+        });
+    }
+
+    #[test]
+    fn steps_the_actor_list_before_each_frame() {
+        with_test_player(|_| {
+            let main = log_events(&["prepareFrame"], "")
+                + "on prepareMovie\r  add the actorList, new(script \"actor\")\rend\r";
+            add_test_scripts(vec![
+                (1, "main", ScriptType::Movie, &main),
+                (2, "actor", ScriptType::Parent, &log_events(&["stepFrame"], "me")),
+            ]);
+            assert_eq!(run_movie(2), "stepFrame prepareFrame stepFrame prepareFrame ");
+        });
+    }
+
+    #[test]
+    fn update_stage_does_not_prepare_the_frame_it_is_preparing() {
+        with_test_player(|_| {
+            let main = logging_handler("prepareFrame", "", "\"prepareFrame\"", "  updateStage\r")
+                + &logging_handler("exitFrame", "", "\"exitFrame\"", "  updateStage\r")
+                + "on prepareMovie\r  add the actorList, new(script \"actor\")\rend\r";
+            add_test_scripts(vec![
+                (1, "main", ScriptType::Movie, &main),
+                (2, "actor", ScriptType::Parent, &logging_handler("stepFrame", "me", "\"stepFrame\"", "  updateStage\r")),
+            ]);
+            // Outside of prepareFrame, updateStage steps the actors and prepares the frame
+            assert_eq!(run_movie(1), "stepFrame prepareFrame exitFrame stepFrame prepareFrame ");
+            assert!(!reserve_player_ref(|player| player.is_preparing_frame));
+        });
+    }
+
+    #[test]
+    fn stop_event_keeps_frame_events_from_later_handlers() {
+        with_test_player(|_| {
+            let cast_lib = add_test_scripts(vec![
+                (1, "main", ScriptType::Movie, &logging_handler("enterFrame", "", "\"movie\"", "")),
+                (2, "first", ScriptType::Score, &logging_handler("enterFrame", "me", "\"first\"", "")),
+                (3, "second", ScriptType::Score, &logging_handler("enterFrame", "me", "\"second\"", "  stopEvent\r")),
+                (4, "third", ScriptType::Score, &logging_handler("enterFrame", "me", "\"third\"", "")),
+            ]);
+            add_sprite_behaviors(cast_lib, &[2, 3, 4]);
+            assert_eq!(run_movie(1), "first second ");
+        });
+    }
+
+    #[test]
+    fn frame_events_reach_movie_scripts_after_every_behavior() {
+        with_test_player(|_| {
+            let cast_lib = add_test_scripts(vec![
+                (1, "main", ScriptType::Movie, &logging_handler("enterFrame", "", "\"movie\"", "")),
+                (2, "first", ScriptType::Score, &logging_handler("enterFrame", "me", "\"first\"", "")),
+                (3, "second", ScriptType::Score, &logging_handler("enterFrame", "me", "\"second\"", "")),
+            ]);
+            add_sprite_behaviors(cast_lib, &[2, 3]);
+            assert_eq!(run_movie(1), "first second movie ");
+        });
+    }
+
+    #[test]
+    fn pass_sends_events_on_to_the_next_script() {
+        with_test_player(|_| {
+            add_test_scripts(vec![
+                (1, "first", ScriptType::Movie, &logging_handler("startMovie", "", "\"first\"", "  pass\r")),
+                (2, "second", ScriptType::Movie, &logging_handler("startMovie", "", "\"second\"", "")),
+                (3, "third", ScriptType::Movie, &logging_handler("startMovie", "", "\"third\"", "")),
+            ]);
+            assert_eq!(run_movie(1), "first second ");
+        });
+    }
+}
@@ -0,0 +1,115 @@
+use url::Url;
+
+use super::DirPlayer;
+
+/// Makes a path segment safe to use as a file name on any host.
+fn sanitize_segment(segment: &str) -> String {
+  segment
+    .chars()
+    .map(|c| match c {
+      '<' | '>' | ':' | '"' | '|' | '?' | '*' | '\\' | '/' => '_',
+      c if c.is_control() => '_',
+      c => c,
+    })
+    .collect()
+}
+
+/// Returns the directory in file storage that holds the files of the current movie.
+/// Movies loaded from the same directory share it, as they would share files on disk.
+pub fn get_movie_sandbox(player: &DirPlayer) -> String {
+  let base_path = &player.movie.base_path;
+  let mut segments = vec![];
+  match Url::parse(base_path) {
+    Ok(url) => {
+      segments.push(match (url.host_str(), url.port()) {
+        (Some(host), Some(port)) => format!("{}_{}", host, port),
+        (Some(host), None) => host.to_owned(),
+        (None, _) => "local".to_owned(),
+      });
+      segments.extend(url.path_segments().into_iter().flatten().map(|x| x.to_owned()));
+    }
+    Err(_) => segments.extend(base_path.split(['/', '\\']).map(|x| x.to_owned())),
+  }
+  let sandbox = segments
+    .iter()
+    .filter(|x| !x.is_empty() && *x != "." && *x != "..")
+    .map(|x| sanitize_segment(x))
+    .collect::<Vec<_>>()
+    .join("/");
+  if sandbox.is_empty() {
+    "local".to_owned()
+  } else {
+    sandbox
+  }
+}
+
+/// Maps a path given by a movie to a path in its sandbox. Paths relative to the movie, or
+/// starting with `the moviePath` or `@`, stay relative to it. Absolute paths are kept whole
+/// below the sandbox, with the drive letter as a directory. Returns `None` for empty paths and
+/// for paths that would leave the sandbox.
+pub fn resolve_sandbox_path(player: &DirPlayer, path: &str) -> Option<String> {
+  let movie_path = player.movie.base_path.trim_end_matches('/');
+  // Only whole segments match, so `/games` is not the movie path of `/gamesaves/save.txt`.
+  let in_movie_path = path
+    .strip_prefix(movie_path)
+    .filter(|rest| !movie_path.is_empty() && (rest.is_empty() || rest.starts_with(['/', '\\', ':'])));
+  let relative_path = if let Some(stripped) = in_movie_path {
+    stripped
+  } else if let Some(stripped) = path.strip_prefix('@') {
+    stripped
+  } else {
+    path
+  };
+
+  let mut segments: Vec<String> = vec![];
+  // Windows, Mac and URL separators all separate directories.
+  for segment in relative_path.split(['/', '\\', ':']) {
+    match segment {
+      "" | "." => {}
+      ".." => {
+        segments.pop()?;
+      }
+      segment => segments.push(sanitize_segment(segment)),
+    }
+  }
+  if segments.is_empty() {
+    return None;
+  }
+  Some(format!("{}/{}", get_movie_sandbox(player), segments.join("/")))
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::player::{reserve_player_mut, testing::with_test_player};
+
+  use super::*;
+
+  fn resolve(path: &str) -> Option<String> {
+    reserve_player_mut(|player| resolve_sandbox_path(player, path))
+  }
+
+  #[test]
+  fn resolves_paths_in_the_movie_sandbox() {
+    with_test_player(|_| {
+      reserve_player_mut(|player| player.movie.base_path = "http://example.test:8080/games/".to_string());
+      assert_eq!(resolve("save.txt").as_deref(), Some("example.test_8080/games/save.txt"));
+      assert_eq!(resolve("@\\data\\save.txt").as_deref(), Some("example.test_8080/games/data/save.txt"));
+      let movie_path = "http://example.test:8080/games/save.txt";
+      assert_eq!(resolve(movie_path).as_deref(), Some("example.test_8080/games/save.txt"));
+      let other_path = "http://example.test:8080/gamesaves/save.txt";
+      assert_eq!(resolve(other_path).as_deref(), Some("example.test_8080/games/http/example.test/8080/gamesaves/save.txt"));
+      assert_eq!(resolve("C:\\Temp\\save.txt").as_deref(), Some("example.test_8080/games/C/Temp/save.txt"));
+      assert_eq!(resolve("a/./b/../save.txt").as_deref(), Some("example.test_8080/games/a/save.txt"));
+    });
+  }
+
+  #[test]
+  fn rejects_paths_leaving_the_sandbox() {
+    with_test_player(|_| {
+      reserve_player_mut(|player| player.movie.base_path = "http://example.test/games/".to_string());
+      for path in ["", "@", "..", "../save.txt", "a/../../save.txt", "..\\..\\save.txt"] {
+        assert_eq!(resolve(path), None, "{}", path);
+      }
+    });
+  }
+}
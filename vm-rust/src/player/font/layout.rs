@@ -0,0 +1,367 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::player::{
+    bitmap::{
+        bitmap::Bitmap,
+        drawing::CopyPixelsParams,
+        manager::BitmapManager,
+        palette_map::PaletteMap,
+    },
+    cast_member::TextStyleRun,
+    sprite::ColorRef,
+};
+
+use super::{bitmap_font_copy_char, font_style_bits, BitmapFont, FontManager};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum TextAlignment {
+    Left,
+    Center,
+    Right,
+}
+
+impl TextAlignment {
+    pub fn from_name(name: &str) -> TextAlignment {
+        match name.to_lowercase().as_str() {
+            "center" => TextAlignment::Center,
+            "right" => TextAlignment::Right,
+            _ => TextAlignment::Left,
+        }
+    }
+}
+
+/// A borrowed view over the text and formatting shared by field and text members.
+pub struct StyledText<'a> {
+    pub text: &'a str,
+    pub style_runs: &'a [TextStyleRun],
+    pub font: &'a str,
+    pub font_size: u16,
+    pub font_style: u8,
+    pub alignment: TextAlignment,
+    pub word_wrap: bool,
+    pub fixed_line_space: u16,
+    pub top_spacing: i16,
+    pub width: i32,
+}
+
+pub struct TextLayoutLine {
+    /// Index of the first character on the line.
+    pub start: usize,
+    /// Index one past the last character on the line, excluding the line break.
+    pub end: usize,
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+pub struct TextLayout {
+    pub lines: Vec<TextLayoutLine>,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// Everything a layout depends on, to tell whether a cached layout still applies.
+struct TextLayoutKey {
+    text: String,
+    style_runs: Vec<TextStyleRun>,
+    font: String,
+    font_size: u16,
+    font_style: u8,
+    alignment: TextAlignment,
+    word_wrap: bool,
+    fixed_line_space: u16,
+    top_spacing: i16,
+    width: i32,
+    font_generation: u32,
+}
+
+impl TextLayoutKey {
+    fn new(font_manager: &FontManager, text: &StyledText) -> TextLayoutKey {
+        TextLayoutKey {
+            text: text.text.to_owned(),
+            style_runs: text.style_runs.to_vec(),
+            font: text.font.to_owned(),
+            font_size: text.font_size,
+            font_style: text.font_style,
+            alignment: text.alignment,
+            word_wrap: text.word_wrap,
+            fixed_line_space: text.fixed_line_space,
+            top_spacing: text.top_spacing,
+            width: text.width,
+            font_generation: font_manager.generation,
+        }
+    }
+
+    fn matches(&self, font_manager: &FontManager, text: &StyledText) -> bool {
+        self.font_generation == font_manager.generation
+            && self.text == text.text
+            && self.style_runs == text.style_runs
+            && self.font == text.font
+            && self.font_size == text.font_size
+            && self.font_style == text.font_style
+            && self.alignment == text.alignment
+            && self.word_wrap == text.word_wrap
+            && self.fixed_line_space == text.fixed_line_space
+            && self.top_spacing == text.top_spacing
+            && self.width == text.width
+    }
+}
+
+/// The last layout of a member's text, reused until its text, its formatting or the loaded fonts change.
+#[derive(Clone, Default)]
+pub struct TextLayoutCache {
+    entry: RefCell<Option<(Rc<TextLayoutKey>, Rc<TextLayout>)>>,
+}
+
+impl TextLayoutCache {
+    pub fn layout(&self, font_manager: &FontManager, text: &StyledText) -> Rc<TextLayout> {
+        let mut entry = self.entry.borrow_mut();
+        if let Some((key, layout)) = entry.as_ref() {
+            if key.matches(font_manager, text) {
+                return layout.clone();
+            }
+        }
+        let layout = Rc::new(layout_styled_text(font_manager, text));
+        *entry = Some((Rc::new(TextLayoutKey::new(font_manager, text)), layout.clone()));
+        layout
+    }
+}
+
+impl StyledText<'_> {
+    pub fn style_run_at(&self, char_index: usize) -> Option<&TextStyleRun> {
+        self.style_runs.iter().rev().find(|run| run.start <= char_index)
+    }
+
+    /// Returns the font name, size and style bits used to draw the given character.
+    pub fn font_spec_at<'a>(&'a self, font_manager: &'a FontManager, char_index: usize) -> (&'a str, u16, u8) {
+        match self.style_run_at(char_index) {
+            Some(run) => {
+                let name = run.font_name.as_deref()
+                    .or_else(|| font_manager.get_font_name(run.font_id))
+                    .unwrap_or(self.font);
+                (name, run.font_size, font_style_bits(&run.font_style))
+            }
+            None => (self.font, self.font_size, self.font_style),
+        }
+    }
+
+    pub fn font_at<'a>(&self, font_manager: &'a FontManager, char_index: usize) -> Option<&'a BitmapFont> {
+        let (name, size, style) = self.font_spec_at(font_manager, char_index);
+        font_manager.get_font_for(name, size, style)
+    }
+
+    /// Returns every font name, size and style the text is drawn with, to be loaded with `player_ensure_font`.
+    pub fn font_specs(&self, font_manager: &FontManager) -> Vec<(String, u16, u8)> {
+        let mut result = vec![(self.font.to_owned(), self.font_size, self.font_style)];
+        for run in self.style_runs {
+            let (name, size, style) = self.font_spec_at(font_manager, run.start);
+            let spec = (name.to_owned(), size, style);
+            if !result.contains(&spec) {
+                result.push(spec);
+            }
+        }
+        result
+    }
+
+    fn advance_at(&self, font_manager: &FontManager, chars: &[char], char_index: usize) -> i32 {
+        self.font_at(font_manager, char_index)
+            .map_or(0, |font| font.char_advance(chars[char_index] as u8) as i32)
+    }
+}
+
+pub fn layout_styled_text(font_manager: &FontManager, text: &StyledText) -> TextLayout {
+    let chars: Vec<char> = text.text.chars().collect();
+    let default_font = font_manager.get_font_for(text.font, text.font_size, text.font_style);
+    let advance_at = |index: usize| text.advance_at(font_manager, &chars, index);
+    let line_height_at = |start: usize, end: usize| {
+        if text.fixed_line_space > 0 {
+            return text.fixed_line_space as i32;
+        }
+        let fonts = (start..end.max(start + 1)).filter_map(|i| text.font_at(font_manager, i));
+        fonts
+            .chain(default_font)
+            .map(|font| font.char_height as i32 + 1)
+            .max()
+            .unwrap_or(0)
+    };
+    let wrap_width = if text.word_wrap && text.width > 0 { Some(text.width) } else { None };
+
+    let mut lines = vec![];
+    let mut y = text.top_spacing as i32;
+    let mut index = 0;
+    loop {
+        let paragraph_start = index;
+        let mut paragraph_end = paragraph_start;
+        while paragraph_end < chars.len() && chars[paragraph_end] != '\r' && chars[paragraph_end] != '\n' {
+            paragraph_end += 1;
+        }
+
+        let mut line_start = paragraph_start;
+        loop {
+            let mut line_width = 0;
+            let mut end = line_start;
+            let mut last_break = None;
+            while end < paragraph_end {
+                let advance = advance_at(end);
+                if let Some(wrap_width) = wrap_width {
+                    if line_width + advance > wrap_width && end > line_start && chars[end] != ' ' {
+                        break;
+                    }
+                }
+                line_width += advance;
+                if chars[end] == ' ' {
+                    last_break = Some(end + 1);
+                }
+                end += 1;
+            }
+            let line_end = if end < paragraph_end { last_break.unwrap_or(end) } else { end };
+
+            // Trailing spaces hang past the edge and don't affect alignment.
+            let mut visible_end = line_end;
+            while visible_end > line_start && chars[visible_end - 1] == ' ' {
+                visible_end -= 1;
+            }
+            let visible_width: i32 = (line_start..visible_end).map(advance_at).sum();
+            let x = match text.alignment {
+                TextAlignment::Left => 0,
+                TextAlignment::Center => (text.width - visible_width) / 2,
+                TextAlignment::Right => text.width - visible_width,
+            };
+            let height = line_height_at(line_start, line_end);
+            lines.push(TextLayoutLine {
+                start: line_start,
+                end: line_end,
+                x,
+                y,
+                width: visible_width,
+                height,
+            });
+            y += height;
+
+            line_start = line_end;
+            if line_start >= paragraph_end {
+                break;
+            }
+        }
+
+        if paragraph_end >= chars.len() {
+            break;
+        }
+        index = paragraph_end + 1;
+        if chars[paragraph_end] == '\r' && chars.get(index) == Some(&'\n') {
+            index += 1;
+        }
+    }
+
+    let width = lines.iter().map(|line| line.x + line.width).max().unwrap_or(0);
+    TextLayout {
+        lines,
+        width,
+        height: y,
+    }
+}
+
+impl TextLayout {
+    /// Returns the index of the character at the given position relative to the text origin.
+    pub fn char_index_at(&self, font_manager: &FontManager, text: &StyledText, x: i32, y: i32) -> usize {
+        let line = self
+            .lines
+            .iter()
+            .find(|line| y < line.y + line.height)
+            .or(self.lines.last());
+        let Some(line) = line else {
+            return 0;
+        };
+        let chars: Vec<char> = text.text.chars().collect();
+        let mut char_x = line.x;
+        for index in line.start..line.end {
+            let advance = text.advance_at(font_manager, &chars, index);
+            if x < char_x + advance / 2 {
+                return index;
+            }
+            char_x += advance;
+        }
+        line.end
+    }
+
+    /// Returns the position of the given character relative to the text origin.
+    pub fn char_pos(&self, font_manager: &FontManager, text: &StyledText, char_index: usize) -> (i32, i32) {
+        let line = self
+            .lines
+            .iter()
+            .find(|line| char_index <= line.end)
+            .or(self.lines.last());
+        let Some(line) = line else {
+            return (0, text.top_spacing as i32);
+        };
+        let chars: Vec<char> = text.text.chars().collect();
+        let x: i32 = (line.start..char_index.min(line.end))
+            .map(|index| text.advance_at(font_manager, &chars, index))
+            .sum();
+        (line.x + x, line.y)
+    }
+}
+
+pub fn draw_styled_text(
+    dest: &mut Bitmap,
+    font_manager: &FontManager,
+    bitmap_manager: &BitmapManager,
+    text: &StyledText,
+    layout: &TextLayout,
+    loc: (i32, i32),
+    ink: u32,
+    bg_color: ColorRef,
+    palettes: &PaletteMap,
+) {
+    let chars: Vec<char> = text.text.chars().collect();
+    let mut params = CopyPixelsParams::default(dest);
+    params.ink = ink;
+    params.bg_color = bg_color;
+
+    for line in &layout.lines {
+        let mut x = loc.0 + line.x;
+        for (index, c) in chars.iter().enumerate().take(line.end).skip(line.start) {
+            let Some(font) = text.font_at(font_manager, index) else {
+                continue;
+            };
+            let Some(font_bitmap) = bitmap_manager.get_bitmap(font.bitmap_ref) else {
+                continue;
+            };
+            if let Some(run) = text.style_run_at(index) {
+                params.color = run.color.clone();
+            }
+            // Glyphs sit on the bottom of the line so taller fixed line spaces add leading above.
+            let y = loc.1 + line.y + (line.height - font.char_height as i32 - 1).max(0);
+            bitmap_font_copy_char(font, font_bitmap, *c as u8, dest, x, y, palettes, &params);
+            x += font.char_advance(*c as u8) as i32;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::player::cast_member::TextMember;
+
+    use super::*;
+
+    #[test]
+    fn reuses_the_layout_until_the_text_or_fonts_change() {
+        let mut font_manager = FontManager::new();
+        let mut member = TextMember::new();
+        member.text = "Hello".to_string();
+        let layout = member.layout(&font_manager);
+        assert!(Rc::ptr_eq(&layout, &member.layout(&font_manager)));
+
+        member.text = "Hello world".to_string();
+        let changed_text = member.layout(&font_manager);
+        assert!(!Rc::ptr_eq(&layout, &changed_text));
+
+        member.width = 40;
+        let changed_width = member.layout(&font_manager);
+        assert!(!Rc::ptr_eq(&changed_text, &changed_width));
+
+        font_manager.generation += 1;
+        assert!(!Rc::ptr_eq(&changed_width, &member.layout(&font_manager)));
+    }
+}
@@ -1,13 +1,21 @@
 use fxhash::FxHashMap;
 use log::warn;
-use wasm_bindgen::JsCast;
-use wasm_bindgen_futures::JsFuture;
+use url::Url;
 
-use crate::player::{
-    bitmap::bitmap::{get_system_default_palette, Bitmap, PaletteRef},
-    reserve_player_mut,
+use crate::{
+    director::{chunks::text::{TEXT_STYLE_BOLD, TEXT_STYLE_ITALIC, TEXT_STYLE_UNDERLINE}, file::DirectorFile},
+    platform::{platform, png::decode_png},
+    player::{
+        bitmap::bitmap::{get_system_default_palette, Bitmap, PaletteRef},
+        cast_lib::CastMemberRef,
+        cast_member::CastMemberType,
+        reserve_player_mut, DirPlayer,
+    },
 };
 
+pub mod layout;
+pub mod truetype;
+
 use super::{
     bitmap::{drawing::CopyPixelsParams, manager::BitmapRef, palette_map::PaletteMap},
     geometry::IntRect,
@@ -18,6 +26,22 @@ pub struct FontManager {
     pub fonts: FxHashMap<FontRef, BitmapFont>,
     pub system_font: Option<FontRef>,
     pub font_counter: FontRef,
+    /// Fonts rasterized from font members. `None` marks fonts that could not be loaded.
+    pub font_cache: FxHashMap<FontKey, Option<FontRef>>,
+    /// Font names by the ids used in text style runs, from the movie's font map.
+    pub font_names: FxHashMap<u16, String>,
+    /// Replacement font names from the movie's cross-platform font map, keyed by lowercase name.
+    pub font_substitutions: FxHashMap<String, String>,
+    /// Changes whenever fonts are added or the font maps are reloaded, so cached text layouts can tell they are stale.
+    pub generation: u32,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct FontKey {
+    /// Lowercase font name.
+    pub name: String,
+    pub size: u16,
+    pub style: u8,
 }
 
 pub struct BitmapFont {
@@ -31,13 +55,42 @@ pub struct BitmapFont {
     pub char_offset_x: u16,
     pub char_offset_y: u16,
     pub first_char_num: u8,
+    /// Advance of each character from `first_char_num` for proportional fonts.
+    pub char_widths: Option<Vec<u16>>,
+}
+
+impl BitmapFont {
+    /// Width of the glyph drawn for the given character.
+    pub fn glyph_width(&self, char_num: u8) -> u16 {
+        match &self.char_widths {
+            Some(char_widths) => char_num
+                .checked_sub(self.first_char_num)
+                .and_then(|index| char_widths.get(index as usize))
+                .copied()
+                .unwrap_or(0),
+            None => self.char_width,
+        }
+    }
+
+    /// Horizontal distance from the given character to the next one.
+    pub fn char_advance(&self, char_num: u8) -> u16 {
+        match &self.char_widths {
+            Some(_) => self.glyph_width(char_num),
+            None => self.char_width + 1,
+        }
+    }
 }
 
-pub struct DrawTextParams<'a> {
-    pub font: &'a BitmapFont,
-    pub line_height: Option<u16>,
-    pub line_spacing: u16,
-    pub top_spacing: i16,
+/// Converts Lingo font style names such as `bold` to text chunk style bits.
+pub fn font_style_bits<T: AsRef<str>>(font_style: &[T]) -> u8 {
+    font_style.iter().fold(0, |bits, style| {
+        bits | match style.as_ref().trim() {
+            "bold" => TEXT_STYLE_BOLD,
+            "italic" => TEXT_STYLE_ITALIC,
+            "underline" => TEXT_STYLE_UNDERLINE,
+            _ => 0,
+        }
+    })
 }
 
 impl FontManager {
@@ -46,67 +99,142 @@ impl FontManager {
             system_font: None,
             fonts: FxHashMap::default(),
             font_counter: 0,
+            font_cache: FxHashMap::default(),
+            font_names: FxHashMap::default(),
+            font_substitutions: FxHashMap::default(),
+            generation: 0,
         };
     }
 
+    pub fn add_font(&mut self, font: BitmapFont) -> FontRef {
+        let font_ref = self.font_counter;
+        self.font_counter += 1;
+        self.generation += 1;
+        self.fonts.insert(font_ref, font);
+        font_ref
+    }
+
+    pub fn load_font_maps(&mut self, dir: &DirectorFile) {
+        self.generation += 1;
+        self.font_names.clear();
+        self.font_substitutions.clear();
+        if let Some(font_map) = &dir.font_map {
+            for entry in &font_map.entries {
+                self.font_names.insert(entry.id, entry.name.to_owned());
+            }
+        }
+        if let Some(font_xplatform_map) = &dir.font_xplatform_map {
+            // Text is drawn like the Windows projector does, so only mappings to Windows fonts apply
+            for substitution in &font_xplatform_map.substitutions {
+                if substitution.to_platform.eq_ignore_ascii_case("win") {
+                    self.font_substitutions.insert(substitution.from_name.to_lowercase(), substitution.to_name.to_owned());
+                }
+            }
+        }
+    }
+
+    pub fn get_font_name(&self, font_id: u16) -> Option<&str> {
+        self.font_names.get(&font_id).map(|x| x.as_str())
+    }
+
+    pub fn font_key(&self, name: &str, size: u16, style: u8) -> FontKey {
+        let name = name.trim_end_matches(" *").to_lowercase();
+        let name = self.font_substitutions.get(&name).map_or(name, |x| x.to_lowercase());
+        FontKey { name, size, style }
+    }
+
     pub fn get_system_font(&self) -> Option<&BitmapFont> {
         match self.system_font {
             Some(font_ref) => self.fonts.get(&font_ref),
             None => None,
         }
     }
+
+    /// Resolves the font to draw text in the given face, size and style, falling back to the plain
+    /// face and then to the system font. Fonts must have been loaded with `player_ensure_font` first.
+    pub fn get_font_for(&self, name: &str, size: u16, style: u8) -> Option<&BitmapFont> {
+        let key = self.font_key(name, size, style);
+        let plain_key = FontKey { style: 0, ..key.clone() };
+        [key, plain_key]
+            .iter()
+            .find_map(|key| self.font_cache.get(key).copied().flatten())
+            .and_then(|font_ref| self.fonts.get(&font_ref))
+            .or_else(|| self.get_system_font())
+    }
+}
+
+/// Rasterizes the font member matching the given face for the given size and style, unless this was already attempted.
+pub fn player_ensure_font(player: &mut DirPlayer, name: &str, size: u16, style: u8) {
+    let key = player.font_manager.font_key(name, size, style);
+    if player.font_manager.font_cache.contains_key(&key) {
+        return;
+    }
+    let font_data = player.movie.cast_manager.casts.iter()
+        .flat_map(|cast| cast.members.values())
+        .find_map(|member| match &member.member_type {
+            CastMemberType::Font(font_member) if font_member.font_name.eq_ignore_ascii_case(&key.name) => font_member.font_data.clone(),
+            _ => None,
+        });
+    let font = font_data.and_then(|font_data| truetype::rasterize_truetype_font(&font_data, size, style, &mut player.bitmap_manager));
+    let font_ref = font.map(|font| player.font_manager.add_font(font));
+    player.font_manager.font_cache.insert(key, font_ref);
+    player.font_manager.generation += 1;
+}
+
+/// Loads the fonts used by the text or field member, if any.
+pub fn player_ensure_member_fonts(player: &mut DirPlayer, member_ref: &CastMemberRef) {
+    let member = player.movie.cast_manager.find_member_by_ref(member_ref);
+    let font_specs = match member.map(|x| &x.member_type) {
+        Some(CastMemberType::Field(field_member)) => field_member.styled_text().font_specs(&player.font_manager),
+        Some(CastMemberType::Text(text_member)) => text_member.styled_text().font_specs(&player.font_manager),
+        _ => return,
+    };
+    for (name, size, style) in font_specs {
+        player_ensure_font(player, &name, size, style);
+    }
+}
+
+/// Resolves the system font path against the page location, or the working directory natively.
+fn resolve_system_font_url(path: &str) -> Option<Url> {
+    if let Ok(url) = Url::parse(path) {
+        return Some(url);
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        let location = web_sys::window()?.document()?.url().ok()?;
+        Url::parse(&location).ok()?.join(path).ok()
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        Url::from_file_path(std::env::current_dir().ok()?.join(path)).ok()
+    }
 }
 
 pub async fn player_load_system_font(path: &str) {
-    let window = web_sys::window().unwrap();
-    let result = JsFuture::from(window.fetch_with_str(path)).await;
-
-    match result {
-        Ok(result) => {
-            let result = result.dyn_into::<web_sys::Response>().unwrap();
-            let blob = JsFuture::from(result.blob().unwrap()).await.unwrap();
-            let blob = blob.dyn_into::<web_sys::Blob>().unwrap();
-            let image_data = window.create_image_bitmap_with_blob(&blob).unwrap();
-            let image_data = JsFuture::from(image_data).await.unwrap();
-            let image_bitmap = image_data.dyn_into::<web_sys::ImageBitmap>().unwrap();
-
-            let canvas = web_sys::window()
-                .unwrap()
-                .document()
-                .unwrap()
-                .create_element("canvas")
-                .unwrap();
-            let canvas = canvas.dyn_into::<web_sys::HtmlCanvasElement>().unwrap();
-            canvas.set_width(image_bitmap.width());
-            canvas.set_height(image_bitmap.height());
-            let context = canvas
-                .get_context("2d")
-                .unwrap()
-                .unwrap()
-                .dyn_into::<web_sys::CanvasRenderingContext2d>()
-                .unwrap();
-
-            context
-                .draw_image_with_image_bitmap(&image_bitmap, 0.0, 0.0)
-                .unwrap();
-
-            let image_data = context
-                .get_image_data(
-                    0.0,
-                    0.0,
-                    image_bitmap.width() as f64,
-                    image_bitmap.height() as f64,
-                )
-                .unwrap();
+    let url = match resolve_system_font_url(path) {
+        Some(url) => url,
+        None => {
+            warn!("Invalid system font path: {}", path);
+            return;
+        }
+    };
+    let net_loader = platform().net_loader.clone();
+    let image = match net_loader.load(&url).await {
+        Ok(bytes) => decode_png(&bytes),
+        Err(err) => Err(format!("error code {}", err)),
+    };
 
+    match image {
+        Ok(image) => {
             let bitmap = Bitmap {
-                width: image_data.width() as u16,
-                height: image_data.height() as u16,
-                data: image_data.data().0,
+                width: image.width as u16,
+                height: image.height as u16,
+                data: image.data,
                 bit_depth: 32, // TODO use a smaller bit depth
                 palette_ref: PaletteRef::BuiltIn(get_system_default_palette()),
                 matte: None,
             };
+            warn!("Loaded system font image: {}x{}", bitmap.width, bitmap.height);
             reserve_player_mut(|player| {
                 let grid_columns = 18;
                 let grid_rows = 7;
@@ -124,19 +252,15 @@ pub async fn player_load_system_font(path: &str) {
                     grid_cell_height,
                     first_char_num: 32,
                     char_offset_x: 1,
-                    char_offset_y: 1
+                    char_offset_y: 1,
+                    char_widths: None,
                 };
-                let font_ref = player.font_manager.font_counter;
-                player.font_manager.font_counter += 1;
-                player.font_manager.fonts.insert(font_ref, font);
+                let font_ref = player.font_manager.add_font(font);
                 player.font_manager.system_font = Some(font_ref);
             });
-
-            warn!("Loaded system font image data: {:?}", image_data);
         }
         Err(err) => {
-            warn!("Error fetching system font: {:?}", err);
-            return;
+            warn!("Error loading system font: {}", err);
         }
     };
 }
@@ -154,6 +278,7 @@ pub fn bitmap_font_copy_char(
     if char_num < font.first_char_num {
         return;
     }
+    let glyph_width = font.glyph_width(char_num) as i32;
     let char_num = char_num - font.first_char_num;
     let char_x = char_num % font.grid_columns;
     let char_y = char_num / font.grid_columns;
@@ -167,98 +292,79 @@ pub fn bitmap_font_copy_char(
         IntRect::from(
             dest_x,
             dest_y,
-            dest_x + font.char_width as i32,
+            dest_x + glyph_width,
             dest_y + font.char_height as i32,
         ),
         IntRect::from(
             src_x,
             src_y,
-            src_x + font.char_width as i32,
+            src_x + glyph_width,
             src_y + font.char_height as i32,
         ),
         &draw_params,
     )
 }
 
-pub fn measure_text(text: &str, font: &BitmapFont, line_height: Option<u16>, line_spacing: u16, top_spacing: i16) -> (u16, u16) {
-    let mut width = 0;
-    let mut line_width = 0;
-    let line_height = line_height.unwrap_or(font.char_height);
-    let mut height = (top_spacing + line_height as i16) as u16;
-    let mut index = 0;
-    for c in text.chars() {
-        if c == '\r' || c == '\n' {
-            if line_width > width {
-                width = line_width;
-            }
-            line_width = 0;
-        } else {
-            if line_width == 0 && index > 0 {
-                height += (line_height as i16 + line_spacing as i16 + 1) as u16;
-            }
-            line_width += font.char_width + 1;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_font(bitmap_ref: BitmapRef) -> BitmapFont {
+        BitmapFont {
+            bitmap_ref,
+            char_width: 6,
+            char_height: 12,
+            grid_columns: 16,
+            grid_rows: 14,
+            grid_cell_width: 6,
+            grid_cell_height: 12,
+            char_offset_x: 0,
+            char_offset_y: 0,
+            first_char_num: 32,
+            char_widths: None,
         }
-        index += 1;
     }
-    if line_width > width {
-        width = line_width;
+
+    fn cache_font(font_manager: &mut FontManager, name: &str, size: u16, style: u8, bitmap_ref: BitmapRef) {
+        let font_ref = font_manager.add_font(test_font(bitmap_ref));
+        let key = font_manager.font_key(name, size, style);
+        font_manager.font_cache.insert(key, Some(font_ref));
     }
-    return (width, height);
-}
 
-pub fn _get_text_char_pos(text: &str, params: &DrawTextParams, char_index: usize) -> (i16, i16) {
-    let mut x = 0;
-    let mut y = params.top_spacing;
-    let mut line_width = 0;
-    let mut line_index = 0;
-    for c in text.chars() {
-        if c == '\r' || c == '\n' {
-            if line_index == char_index {
-                return (x, y);
-            }
-            if line_width > x {
-                x = line_width;
-            }
-            line_width = 0;
-            y += params.line_height.unwrap_or(params.font.char_height) as i16 + params.line_spacing as i16 + 1;
-        } else {
-            if line_index == char_index {
-                return (x, y);
-            }
-            line_width += params.font.char_width as i16 + 1;
-        }
-        line_index += 1;
+    fn lookup(font_manager: &FontManager, name: &str, size: u16, style: u8) -> Option<BitmapRef> {
+        font_manager.get_font_for(name, size, style).map(|font| font.bitmap_ref)
     }
-    if line_width > x {
-        x = line_width;
+
+    #[test]
+    fn looks_up_fonts_by_name_size_and_style() {
+        let mut font_manager = FontManager::new();
+        cache_font(&mut font_manager, "Arial", 12, 0, 1);
+        cache_font(&mut font_manager, "Arial", 12, TEXT_STYLE_BOLD, 2);
+        cache_font(&mut font_manager, "Arial", 24, 0, 3);
+
+        assert_eq!(lookup(&font_manager, "Arial", 12, 0), Some(1));
+        assert_eq!(lookup(&font_manager, "Arial", 12, TEXT_STYLE_BOLD), Some(2));
+        assert_eq!(lookup(&font_manager, "Arial", 24, 0), Some(3));
+        // Names are matched without case and without the font member suffix
+        assert_eq!(lookup(&font_manager, "ARIAL *", 12, 0), Some(1));
+        // Styles without a font of their own use the plain font
+        assert_eq!(lookup(&font_manager, "Arial", 24, TEXT_STYLE_ITALIC), Some(3));
+        assert_eq!(lookup(&font_manager, "Arial", 18, 0), None);
+        assert_eq!(lookup(&font_manager, "Verdana", 12, 0), None);
     }
-    return (x, y);
-}
 
-pub fn get_text_index_at_pos(text: &str, params: &DrawTextParams, x: i32, y: i32) -> usize {
-    let mut index = 0;
-    let mut line_width = 0;
-    let mut line_y = params.top_spacing as i32;
-    for c in text.chars() {
-        if c == '\r' || c == '\n' {
-            if y >= line_y && y < line_y + params.line_height.unwrap_or(params.font.char_height) as i32 {
-                if x < line_width {
-                    return index;
-                }
-            }
-            if line_width > x {
-                line_width = 0;
-            }
-            line_y += params.line_height.unwrap_or(params.font.char_height) as i32 + params.line_spacing as i32 + 1;
-        } else {
-            if y >= line_y && y < line_y + params.line_height.unwrap_or(params.font.char_height) as i32 {
-                if x < line_width {
-                    return index;
-                }
-            }
-            line_width += params.font.char_width as i32 + 1;
-        }
-        index += 1;
+    #[test]
+    fn falls_back_to_substitutions_and_the_system_font() {
+        let mut font_manager = FontManager::new();
+        cache_font(&mut font_manager, "Arial", 12, 0, 1);
+        font_manager.font_substitutions.insert("helvetica".to_string(), "Arial".to_string());
+        font_manager.system_font = Some(font_manager.add_font(test_font(9)));
+
+        assert_eq!(lookup(&font_manager, "Helvetica", 12, 0), Some(1));
+        assert_eq!(lookup(&font_manager, "Verdana", 12, 0), Some(9));
+        // Fonts that failed to load also use the system font
+        let key = font_manager.font_key("Geneva", 12, 0);
+        font_manager.font_cache.insert(key, None);
+        assert_eq!(lookup(&font_manager, "Geneva", 12, 0), Some(9));
     }
-    return index;
 }
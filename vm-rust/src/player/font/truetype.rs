@@ -0,0 +1,215 @@
+use ab_glyph::{point, Font, PxScale, ScaleFont};
+
+use crate::{
+    director::chunks::text::{TEXT_STYLE_BOLD, TEXT_STYLE_ITALIC, TEXT_STYLE_UNDERLINE},
+    player::bitmap::{
+        bitmap::{get_system_default_palette, Bitmap, PaletteRef},
+        manager::BitmapManager,
+    },
+};
+
+use super::BitmapFont;
+
+const FIRST_CHAR_NUM: u8 = 32;
+const GRID_COLUMNS: u16 = 16;
+const GRID_ROWS: u16 = 14;
+/// Horizontal shift per pixel of height used to slant glyphs when no italic face is available.
+const ITALIC_SLANT: f32 = 0.2;
+
+/// Rasterizes the Latin-1 range of a TrueType or OpenType font into a glyph grid so it can be
+/// drawn like the system font. Styles the font doesn't have a face for are synthesized.
+///
+/// This is the only outline format supported. PFR fonts, which most authoring tools embed, are
+/// not decoded and fall back to the system font.
+pub fn rasterize_truetype_font(
+    data: &[u8],
+    size: u16,
+    style: u8,
+    bitmap_manager: &mut BitmapManager,
+) -> Option<BitmapFont> {
+    let font = ab_glyph::FontRef::try_from_slice(data).ok()?;
+    // Director sizes are points at 72 dpi, so one point is one pixel per em.
+    let units_per_em = font.units_per_em()?;
+    let scale = PxScale::from(size as f32 * font.height_unscaled() / units_per_em);
+    let scaled_font = font.as_scaled(scale);
+
+    let bold = style & TEXT_STYLE_BOLD != 0;
+    let italic = style & TEXT_STYLE_ITALIC != 0;
+    let underline = style & TEXT_STYLE_UNDERLINE != 0;
+
+    let ascent = scaled_font.ascent().ceil() as i32;
+    let descent = (-scaled_font.descent()).ceil() as i32;
+    let cell_height = (ascent + descent).max(1);
+    let slant_width = if italic { (ascent as f32 * ITALIC_SLANT).ceil() as i32 } else { 0 };
+
+    let char_count = (GRID_COLUMNS * GRID_ROWS) as usize;
+    let char_widths: Vec<u16> = (0..char_count)
+        .map(|index| {
+            let c = char::from(FIRST_CHAR_NUM + index as u8);
+            let advance = scaled_font.h_advance(font.glyph_id(c)).round() as i32;
+            (advance + bold as i32).max(0) as u16
+        })
+        .collect();
+    let cell_width = char_widths.iter().map(|x| *x as i32).max().unwrap_or(0) + slant_width + 1;
+
+    let mut bitmap = Bitmap::new(
+        (cell_width * GRID_COLUMNS as i32) as u16,
+        (cell_height * GRID_ROWS as i32) as u16,
+        32,
+        PaletteRef::BuiltIn(get_system_default_palette()),
+    );
+    let mut plot = |x: i32, y: i32, coverage: f32| {
+        if x < 0 || y < 0 || x >= bitmap.width as i32 || y >= bitmap.height as i32 {
+            return;
+        }
+        let index = (y as usize * bitmap.width as usize + x as usize) * 4;
+        let value = 255 - (coverage.clamp(0.0, 1.0) * 255.0) as u8;
+        let value = value.min(bitmap.data[index]);
+        bitmap.data[index] = value;
+        bitmap.data[index + 1] = value;
+        bitmap.data[index + 2] = value;
+    };
+
+    for (index, char_width) in char_widths.iter().enumerate() {
+        let cell_x = (index as i32 % GRID_COLUMNS as i32) * cell_width;
+        let cell_y = (index as i32 / GRID_COLUMNS as i32) * cell_height;
+        let baseline = cell_y + ascent;
+        let c = char::from(FIRST_CHAR_NUM + index as u8);
+        let glyph = font
+            .glyph_id(c)
+            .with_scale_and_position(scale, point(cell_x as f32, baseline as f32));
+
+        if let Some(outline) = font.outline_glyph(glyph) {
+            let bounds = outline.px_bounds();
+            outline.draw(|x, y, coverage| {
+                let px = bounds.min.x as i32 + x as i32;
+                let py = bounds.min.y as i32 + y as i32;
+                let px = px + ((baseline - py) as f32 * if italic { ITALIC_SLANT } else { 0.0 }).round() as i32;
+                plot(px, py, coverage);
+                if bold {
+                    plot(px + 1, py, coverage);
+                }
+            });
+        }
+        if underline {
+            for x in 0..*char_width as i32 {
+                plot(cell_x + x, baseline + 1, 1.0);
+            }
+        }
+    }
+
+    Some(BitmapFont {
+        bitmap_ref: bitmap_manager.add_bitmap(bitmap),
+        char_width: cell_width as u16,
+        char_height: cell_height as u16,
+        grid_columns: GRID_COLUMNS as u8,
+        grid_rows: GRID_ROWS as u8,
+        grid_cell_width: cell_width as u16,
+        grid_cell_height: cell_height as u16,
+        char_offset_x: 0,
+        char_offset_y: 0,
+        first_char_num: FIRST_CHAR_NUM,
+        char_widths: Some(char_widths),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u16s(values: &[u16]) -> Vec<u8> {
+        values.iter().flat_map(|x| x.to_be_bytes()).collect()
+    }
+
+    /// Builds a TrueType font of 1000 units per em with an empty `.notdef` glyph 500 units wide,
+    /// and an `A` that is a 400 by 700 unit square, 600 units wide.
+    fn square_font() -> Vec<u8> {
+        let mut head = u16s(&[1, 0, 1, 0, 0, 0, 0x5f0f, 0x3cf5, 0, 1000]);
+        head.extend_from_slice(&[0; 16]); // created and modified
+        head.extend(u16s(&[100, 0, 500, 700, 0, 8, 2, 0, 0]));
+        let hhea = u16s(&[1, 0, 800, (-200i16) as u16, 0, 600, 0, 0, 500, 1, 0, 0, 0, 0, 0, 0, 0, 2]);
+        let maxp = u16s(&[0, 0x5000, 2]);
+        let hmtx = u16s(&[500, 0, 600, 100]);
+        // One contour of four points on the curve, with coordinates as 16-bit deltas
+        let mut glyf = u16s(&[1, 100, 0, 500, 700, 3, 0]);
+        glyf.extend_from_slice(&[1, 1, 1, 1]);
+        glyf.extend(u16s(&[100, 400, 0, (-400i16) as u16]));
+        glyf.extend(u16s(&[0, 0, 700, 0]));
+        let loca = u16s(&[0, 0, glyf.len() as u16 / 2]);
+        // A Unicode format 4 subtable mapping 'A' to glyph 1
+        let mut cmap = u16s(&[0, 1, 0, 3, 0, 12]);
+        cmap.extend(u16s(&[4, 32, 0, 4, 4, 1, 0, 0x41, 0xffff, 0, 0x41, 0xffff, 1u16.wrapping_sub(0x41), 1, 0, 0]));
+
+        let tables: [(&[u8; 4], Vec<u8>); 7] = [
+            (b"cmap", cmap),
+            (b"glyf", glyf),
+            (b"head", head),
+            (b"hhea", hhea),
+            (b"hmtx", hmtx),
+            (b"loca", loca),
+            (b"maxp", maxp),
+        ];
+        let mut font = u16s(&[1, 0, tables.len() as u16, 64, 2, tables.len() as u16 * 16 - 64]);
+        let mut offset = font.len() + tables.len() * 16;
+        let mut table_data = vec![];
+        for (tag, data) in &tables {
+            font.extend_from_slice(*tag);
+            font.extend_from_slice(&[0; 4]);
+            font.extend_from_slice(&(offset as u32).to_be_bytes());
+            font.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            let padded_len = data.len().next_multiple_of(4);
+            table_data.extend_from_slice(data);
+            table_data.resize(table_data.len() + padded_len - data.len(), 0);
+            offset += padded_len;
+        }
+        font.extend(table_data);
+        font
+    }
+
+    fn dark_pixels_in_cell(bitmap: &Bitmap, font: &BitmapFont, c: char) -> usize {
+        let index = c as u16 - FIRST_CHAR_NUM as u16;
+        let cell_x = (index % GRID_COLUMNS) * font.grid_cell_width;
+        let cell_y = (index / GRID_COLUMNS) * font.grid_cell_height;
+        let mut count = 0;
+        for y in cell_y..cell_y + font.grid_cell_height {
+            for x in cell_x..cell_x + font.grid_cell_width {
+                if bitmap.data[(y as usize * bitmap.width as usize + x as usize) * 4] < 128 {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+
+    #[test]
+    fn rasterizes_glyphs_with_their_advances() {
+        let data = square_font();
+        let mut bitmap_manager = BitmapManager::new();
+        let font = rasterize_truetype_font(&data, 20, 0, &mut bitmap_manager).unwrap();
+        let char_widths = font.char_widths.as_ref().unwrap();
+        // Advances are scaled from 1000 units per em to 20 pixels, unmapped characters use `.notdef`
+        assert_eq!(char_widths[(b'A' - FIRST_CHAR_NUM) as usize], 12);
+        assert_eq!(char_widths[(b' ' - FIRST_CHAR_NUM) as usize], 10);
+        assert_eq!((font.char_height, font.grid_cell_height), (20, 20));
+
+        let bitmap = bitmap_manager.get_bitmap(font.bitmap_ref).unwrap();
+        // The square is 8 by 14 pixels
+        let dark_pixels = dark_pixels_in_cell(bitmap, &font, 'A');
+        assert!((100..=130).contains(&dark_pixels), "{} dark pixels", dark_pixels);
+        assert_eq!(dark_pixels_in_cell(bitmap, &font, 'B'), 0);
+
+        let bold_font = rasterize_truetype_font(&data, 20, TEXT_STYLE_BOLD, &mut bitmap_manager).unwrap();
+        assert_eq!(bold_font.char_widths.as_ref().unwrap()[(b'A' - FIRST_CHAR_NUM) as usize], 13);
+        let bitmap = bitmap_manager.get_bitmap(bold_font.bitmap_ref).unwrap();
+        assert!(dark_pixels_in_cell(bitmap, &bold_font, 'A') > dark_pixels);
+    }
+
+    #[test]
+    fn rejects_data_that_is_not_a_font() {
+        let mut bitmap_manager = BitmapManager::new();
+        assert!(rasterize_truetype_font(b"PFR0 outlines", 12, 0, &mut bitmap_manager).is_none());
+        let mut truncated = square_font();
+        truncated.truncate(40);
+        assert!(rasterize_truetype_font(&truncated, 12, 0, &mut bitmap_manager).is_none());
+    }
+}
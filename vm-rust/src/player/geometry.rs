@@ -2,6 +2,7 @@ use log::warn;
 
 use crate::console_warn;
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct IntRect {
   pub left: i32,
   pub top: i32,
@@ -65,3 +66,169 @@ impl IntRect {
     return IntRect::from(left, top, right, bottom);
   }
 }
+
+/// A 2D affine transform mapping `(x, y)` to `(a * x + c * y + tx, b * x + d * y + ty)`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform2D {
+  pub a: f64,
+  pub b: f64,
+  pub c: f64,
+  pub d: f64,
+  pub tx: f64,
+  pub ty: f64,
+}
+
+impl Transform2D {
+  pub const IDENTITY: Transform2D = Transform2D { a: 1.0, b: 0.0, c: 0.0, d: 1.0, tx: 0.0, ty: 0.0 };
+
+  /// Skews by `skew` degrees, moving the points above `origin` to the right, then rotates clockwise
+  /// by `rotation` degrees around `origin`. This is how Director applies a sprite's rotation and skew
+  /// around its reg point.
+  pub fn rotate_skew_around(origin: (f64, f64), rotation: f64, skew: f64) -> Transform2D {
+    let (sin, cos) = rotation.to_radians().sin_cos();
+    let shear = -skew.to_radians().tan();
+    let (a, b) = (cos, sin);
+    let (c, d) = (cos * shear - sin, sin * shear + cos);
+    Transform2D {
+      a,
+      b,
+      c,
+      d,
+      tx: origin.0 - a * origin.0 - c * origin.1,
+      ty: origin.1 - b * origin.0 - d * origin.1,
+    }
+  }
+
+  /// Maps the corners of `rect` onto the given corners of a parallelogram.
+  pub fn from_rect_to_quad(rect: &IntRect, top_left: (f64, f64), top_right: (f64, f64), bottom_left: (f64, f64)) -> Option<Transform2D> {
+    if rect.width() == 0 || rect.height() == 0 {
+      return None;
+    }
+    let a = (top_right.0 - top_left.0) / rect.width() as f64;
+    let b = (top_right.1 - top_left.1) / rect.width() as f64;
+    let c = (bottom_left.0 - top_left.0) / rect.height() as f64;
+    let d = (bottom_left.1 - top_left.1) / rect.height() as f64;
+    Some(Transform2D {
+      a,
+      b,
+      c,
+      d,
+      tx: top_left.0 - a * rect.left as f64 - c * rect.top as f64,
+      ty: top_left.1 - b * rect.left as f64 - d * rect.top as f64,
+    })
+  }
+
+  pub fn apply(&self, x: f64, y: f64) -> (f64, f64) {
+    (self.a * x + self.c * y + self.tx, self.b * x + self.d * y + self.ty)
+  }
+
+  pub fn inverse(&self) -> Option<Transform2D> {
+    let det = self.a * self.d - self.b * self.c;
+    if det.abs() < f64::EPSILON {
+      return None;
+    }
+    let a = self.d / det;
+    let b = -self.b / det;
+    let c = -self.c / det;
+    let d = self.a / det;
+    Some(Transform2D {
+      a,
+      b,
+      c,
+      d,
+      tx: -(a * self.tx + c * self.ty),
+      ty: -(b * self.tx + d * self.ty),
+    })
+  }
+
+  /// Returns the top left, top right, bottom right and bottom left corners of the transformed rect.
+  pub fn quad(&self, rect: &IntRect) -> [(f64, f64); 4] {
+    let (left, top, right, bottom) = (rect.left as f64, rect.top as f64, rect.right as f64, rect.bottom as f64);
+    [
+      self.apply(left, top),
+      self.apply(right, top),
+      self.apply(right, bottom),
+      self.apply(left, bottom),
+    ]
+  }
+
+  /// Returns the smallest rect containing the transformed rect.
+  pub fn bounding_rect(&self, rect: &IntRect) -> IntRect {
+    let quad = self.quad(rect);
+    let left = quad.iter().map(|p| p.0).fold(f64::INFINITY, f64::min);
+    let top = quad.iter().map(|p| p.1).fold(f64::INFINITY, f64::min);
+    let right = quad.iter().map(|p| p.0).fold(f64::NEG_INFINITY, f64::max);
+    let bottom = quad.iter().map(|p| p.1).fold(f64::NEG_INFINITY, f64::max);
+    // Rounding errors from the trigonometry shouldn't grow the rect by a whole pixel.
+    const EPSILON: f64 = 1e-6;
+    IntRect::from(
+      (left + EPSILON).floor() as i32,
+      (top + EPSILON).floor() as i32,
+      (right - EPSILON).ceil() as i32,
+      (bottom - EPSILON).ceil() as i32,
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn assert_point(actual: (f64, f64), expected: (f64, f64)) {
+    assert!((actual.0 - expected.0).abs() < 1e-9 && (actual.1 - expected.1).abs() < 1e-9, "{:?} != {:?}", actual, expected);
+  }
+
+  fn rect_tuple(rect: &IntRect) -> (i32, i32, i32, i32) {
+    (rect.left, rect.top, rect.right, rect.bottom)
+  }
+
+  #[test]
+  fn rotates_clockwise_around_the_origin() {
+    let transform = Transform2D::rotate_skew_around((10.0, 10.0), 90.0, 0.0);
+    assert_point(transform.apply(10.0, 10.0), (10.0, 10.0));
+    assert_point(transform.apply(20.0, 10.0), (10.0, 20.0));
+    assert_point(transform.apply(10.0, 20.0), (0.0, 10.0));
+  }
+
+  #[test]
+  fn skews_points_above_the_origin_to_the_right() {
+    let transform = Transform2D::rotate_skew_around((0.0, 0.0), 0.0, 45.0);
+    assert_point(transform.apply(0.0, -10.0), (10.0, -10.0));
+    assert_point(transform.apply(0.0, 10.0), (-10.0, 10.0));
+    assert_point(transform.apply(10.0, 0.0), (10.0, 0.0));
+  }
+
+  #[test]
+  fn inverse_undoes_the_transform() {
+    let transform = Transform2D::rotate_skew_around((5.0, -3.0), 30.0, 15.0);
+    let inverse = transform.inverse().unwrap();
+    for point in [(0.0, 0.0), (12.5, -7.0), (-100.0, 40.0)] {
+      let (x, y) = transform.apply(point.0, point.1);
+      assert_point(inverse.apply(x, y), point);
+    }
+    let flattened = Transform2D { a: 1.0, b: 2.0, c: 2.0, d: 4.0, tx: 0.0, ty: 0.0 };
+    assert!(flattened.inverse().is_none());
+  }
+
+  #[test]
+  fn bounding_rect_contains_the_transformed_corners() {
+    let rect = IntRect::from(0, 0, 10, 20);
+    // Exact right angles don't grow the rect through rounding errors
+    let quarter_turn = Transform2D::rotate_skew_around((0.0, 0.0), 90.0, 0.0);
+    assert_eq!(rect_tuple(&quarter_turn.bounding_rect(&rect)), (-20, 0, 0, 10));
+    let eighth_turn = Transform2D::rotate_skew_around((0.0, 0.0), 45.0, 0.0);
+    assert_eq!(rect_tuple(&eighth_turn.bounding_rect(&IntRect::from(0, 0, 10, 10))), (-8, 0, 8, 15));
+    assert_eq!(rect_tuple(&Transform2D::IDENTITY.bounding_rect(&rect)), (0, 0, 10, 20));
+  }
+
+  #[test]
+  fn maps_rects_onto_parallelograms() {
+    let rect = IntRect::from(0, 0, 10, 10);
+    let transform = Transform2D::from_rect_to_quad(&rect, (5.0, 5.0), (25.0, 5.0), (10.0, 15.0)).unwrap();
+    let quad = transform.quad(&rect);
+    for (actual, expected) in quad.iter().zip([(5.0, 5.0), (25.0, 5.0), (30.0, 15.0), (10.0, 15.0)].iter()) {
+      assert_point(*actual, *expected);
+    }
+    assert!(Transform2D::from_rect_to_quad(&IntRect::from(0, 0, 0, 10), (0.0, 0.0), (1.0, 0.0), (0.0, 1.0)).is_none());
+  }
+}
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use crate::{director::lingo::datum::{datum_bool, Datum}, player::{bitmap::{bitmap::{resolve_color_ref, BuiltInPalette, PaletteRef}, manager::BitmapRef}, geometry::IntRect, player_duplicate_datum, reserve_player_mut, DatumRef, DirPlayer, ScriptError}};
+use crate::{director::lingo::datum::{datum_bool, Datum}, player::{bitmap::{bitmap::{resolve_color_ref, BuiltInPalette, PaletteRef}, manager::BitmapRef}, geometry::{IntRect, Transform2D}, player_duplicate_datum, reserve_player_mut, DatumRef, DirPlayer, ScriptError}};
 
 use super::prop_list::PropListUtils;
 
@@ -171,12 +171,12 @@ impl BitmapDatumHandlers {
 
   pub fn copy_pixels(datum: &DatumRef, args: &Vec<DatumRef>) -> Result<DatumRef, ScriptError> {
     reserve_player_mut(|player| {
-      let dst_bitmap_ref = player.get_datum(datum).to_bitmap_ref()?;
+      let dst_bitmap_ref = *player.get_datum(datum).to_bitmap_ref()?;
       let src_bitmap_ref = player.get_datum(&args[0]);
       let src_bitmap_ref = if src_bitmap_ref.is_void() || (src_bitmap_ref.is_number() && src_bitmap_ref.int_value()? == 0) {
         return Ok(datum.clone());
       } else {
-        src_bitmap_ref.to_bitmap_ref()?
+        *src_bitmap_ref.to_bitmap_ref()?
       };
       let dest_rect_or_quad = player.get_datum(&args[1]);
       let src_rect = player.get_datum(&args[2]).to_int_rect()?;
@@ -192,22 +192,42 @@ impl BitmapDatumHandlers {
         }
       }
 
-      let dest_rect = match dest_rect_or_quad {
-        Datum::IntRect(rect) => IntRect::from_tuple(*rect),
+      let (dest_rect, transform) = match dest_rect_or_quad {
+        Datum::IntRect(rect) => (IntRect::from_tuple(*rect), None),
         Datum::List(_, list_val, _) => {
-          IntRect::from_quad(
-            player.get_datum(&list_val[0]).to_int_point()?,
-            player.get_datum(&list_val[1]).to_int_point()?,
-            player.get_datum(&list_val[2]).to_int_point()?,
-            player.get_datum(&list_val[3]).to_int_point()?,
-          )
+          let top_left = player.get_datum(&list_val[0]).to_int_point()?;
+          let top_right = player.get_datum(&list_val[1]).to_int_point()?;
+          let bottom_right = player.get_datum(&list_val[2]).to_int_point()?;
+          let bottom_left = player.get_datum(&list_val[3]).to_int_point()?;
+          let is_rect = top_left.1 == top_right.1 && top_right.0 == bottom_right.0 && bottom_right.1 == bottom_left.1 && bottom_left.0 == top_left.0;
+          let is_parallelogram = top_left.0 + bottom_right.0 == top_right.0 + bottom_left.0
+            && top_left.1 + bottom_right.1 == top_right.1 + bottom_left.1;
+          if is_rect {
+            (IntRect::from_quad(top_left, top_right, bottom_right, bottom_left), None)
+          } else if !is_parallelogram {
+            // Quads that are not parallelograms need a perspective mapping, which copyPixels does not do.
+            return Err(ScriptError::new("copyPixels only supports quads that are parallelograms".to_string()));
+          } else {
+            let rect = IntRect::from(0, 0, src_rect.2 - src_rect.0, src_rect.3 - src_rect.1);
+            let as_f64 = |point: (i32, i32)| (point.0 as f64, point.1 as f64);
+            let transform = Transform2D::from_rect_to_quad(&rect, as_f64(top_left), as_f64(top_right), as_f64(bottom_left));
+            (rect, transform)
+          }
         }
         _ => return Err(ScriptError::new("Invalid destRect for copyPixels".to_string())),
       };
-      let src_bitmap = player.bitmap_manager.get_bitmap(*src_bitmap_ref).unwrap().clone();
       let palettes = player.movie.cast_manager.palettes();
-      let dst_bitmap = player.bitmap_manager.get_bitmap_mut(*dst_bitmap_ref).unwrap();
-      dst_bitmap.copy_pixels(&palettes, &src_bitmap, dest_rect, IntRect::from_tuple(src_rect), &param_list_concrete);
+      let is_matte = param_list_concrete.get("ink").is_some_and(|x| x.int_value().is_ok_and(|ink| ink == 8));
+      if is_matte && !param_list_concrete.contains_key("maskImage") {
+        // Keep the matte on the source image, so copying it again does not build it again
+        let src_bitmap = player.bitmap_manager.get_bitmap_mut(src_bitmap_ref).unwrap();
+        if src_bitmap.matte.is_none() {
+          src_bitmap.create_matte(&palettes);
+        }
+      }
+      let src_bitmap = player.bitmap_manager.get_bitmap(src_bitmap_ref).unwrap().clone();
+      let dst_bitmap = player.bitmap_manager.get_bitmap_mut(dst_bitmap_ref).unwrap();
+      dst_bitmap.copy_pixels(&palettes, &src_bitmap, dest_rect, IntRect::from_tuple(src_rect), &param_list_concrete, transform)?;
       Ok(datum.clone())
     })
   }
@@ -271,3 +291,113 @@ impl BitmapDatumHandlers {
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use crate::{director::lingo::datum::DatumType, player::{bitmap::bitmap::{get_system_default_palette, Bitmap}, testing::with_test_player}};
+
+  use super::*;
+
+  /// Copies a black 4x4 bitmap onto a white 20x20 one through the given quad, and returns
+  /// whether the given destination pixels turned black.
+  fn copy_through_quad(quad: [(i32, i32); 4], pixels: &[(u16, u16)]) -> Result<Vec<bool>, ScriptError> {
+    let (dst, args) = reserve_player_mut(|player| {
+      let palettes = player.movie.cast_manager.palettes();
+      let mut src = Bitmap::new(4, 4, 32, PaletteRef::BuiltIn(get_system_default_palette()));
+      src.fill_rect(0, 0, 4, 4, (0, 0, 0), &palettes, 1.0);
+      let mut dst = Bitmap::new(20, 20, 32, PaletteRef::BuiltIn(get_system_default_palette()));
+      dst.fill_rect(0, 0, 20, 20, (255, 255, 255), &palettes, 1.0);
+      let src = player.bitmap_manager.add_bitmap(src);
+      let dst = player.bitmap_manager.add_bitmap(dst);
+      let corners = quad.iter().map(|point| player.alloc_datum(Datum::IntPoint(*point))).collect();
+      let args = vec![
+        player.alloc_datum(Datum::BitmapRef(src)),
+        player.alloc_datum(Datum::List(DatumType::List, corners, false)),
+        player.alloc_datum(Datum::IntRect((0, 0, 4, 4))),
+      ];
+      (player.alloc_datum(Datum::BitmapRef(dst)), args)
+    });
+    BitmapDatumHandlers::copy_pixels(&dst, &args)?;
+    Ok(reserve_player_mut(|player| {
+      let palettes = player.movie.cast_manager.palettes();
+      let bitmap_ref = player.get_datum(&dst).to_bitmap_ref().unwrap();
+      let bitmap = player.bitmap_manager.get_bitmap(*bitmap_ref).unwrap();
+      pixels.iter().map(|(x, y)| bitmap.get_pixel_color(&palettes, *x, *y) == (0, 0, 0)).collect()
+    }))
+  }
+
+  #[test]
+  fn copies_onto_parallelogram_quads() {
+    with_test_player(|_| {
+      // A rect given as a quad
+      assert_eq!(copy_through_quad([(2, 2), (10, 2), (10, 10), (2, 10)], &[(2, 2), (9, 9), (10, 10)]).unwrap(), vec![true, true, false]);
+      // Sheared to the right by one pixel per row
+      let sheared = copy_through_quad([(0, 0), (4, 0), (8, 4), (4, 4)], &[(0, 0), (6, 3), (1, 3)]).unwrap();
+      assert_eq!(sheared, vec![true, true, false]);
+    });
+  }
+
+  /// Copies a black ring around a white hole, with a white border, onto a red 5x5 bitmap with the
+  /// given param list, and returns the rows of the result.
+  fn copy_ring(params: Vec<(&str, Datum)>) -> Result<Vec<Vec<(u8, u8, u8)>>, ScriptError> {
+    let (dst, args) = reserve_player_mut(|player| {
+      let palettes = player.movie.cast_manager.palettes();
+      let mut src = Bitmap::new(5, 5, 32, PaletteRef::BuiltIn(get_system_default_palette()));
+      src.fill_rect(0, 0, 5, 5, (255, 255, 255), &palettes, 1.0);
+      src.fill_rect(1, 1, 4, 4, (0, 0, 0), &palettes, 1.0);
+      src.set_pixel(2, 2, (255, 255, 255), &palettes);
+      let mut dst = Bitmap::new(5, 5, 32, PaletteRef::BuiltIn(get_system_default_palette()));
+      dst.fill_rect(0, 0, 5, 5, (255, 0, 0), &palettes, 1.0);
+      let src = player.bitmap_manager.add_bitmap(src);
+      let dst = player.bitmap_manager.add_bitmap(dst);
+      let params = params.into_iter()
+        .map(|(key, value)| (player.alloc_datum(Datum::Symbol(key.to_string())), player.alloc_datum(value)))
+        .collect();
+      let args = vec![
+        player.alloc_datum(Datum::BitmapRef(src)),
+        player.alloc_datum(Datum::IntRect((0, 0, 5, 5))),
+        player.alloc_datum(Datum::IntRect((0, 0, 5, 5))),
+        player.alloc_datum(Datum::PropList(params, false)),
+      ];
+      (player.alloc_datum(Datum::BitmapRef(dst)), args)
+    });
+    BitmapDatumHandlers::copy_pixels(&dst, &args)?;
+    Ok(reserve_player_mut(|player| {
+      let palettes = player.movie.cast_manager.palettes();
+      let bitmap_ref = player.get_datum(&dst).to_bitmap_ref().unwrap();
+      let bitmap = player.bitmap_manager.get_bitmap(*bitmap_ref).unwrap();
+      (0..5).map(|y| (0..5).map(|x| bitmap.get_pixel_color(&palettes, x, y)).collect()).collect()
+    }))
+  }
+
+  #[test]
+  fn matte_ink_hides_the_white_border_of_the_source() {
+    with_test_player(|_| {
+      const RED: (u8, u8, u8) = (255, 0, 0);
+      const BLACK: (u8, u8, u8) = (0, 0, 0);
+      const WHITE: (u8, u8, u8) = (255, 255, 255);
+      let rows = copy_ring(vec![("ink", Datum::Int(8))]).unwrap();
+      assert_eq!(rows[0], vec![RED; 5]);
+      assert_eq!(rows[1], vec![RED, BLACK, BLACK, BLACK, RED]);
+      assert_eq!(rows[2], vec![RED, BLACK, WHITE, BLACK, RED]);
+      // Copy ink draws the border
+      let rows = copy_ring(vec![("ink", Datum::Int(0))]).unwrap();
+      assert_eq!(rows[0], vec![WHITE; 5]);
+    });
+  }
+
+  #[test]
+  fn rejects_mask_images_that_are_not_mattes() {
+    with_test_player(|_| {
+      let result = copy_ring(vec![("ink", Datum::Int(8)), ("maskImage", Datum::Int(1))]);
+      assert_eq!(result.unwrap_err().message, "Cannot convert datum to mask");
+    });
+  }
+
+  #[test]
+  fn rejects_quads_that_are_not_parallelograms() {
+    with_test_player(|_| {
+      assert!(copy_through_quad([(4, 0), (8, 0), (12, 8), (0, 8)], &[]).is_err());
+    });
+  }
+}
@@ -1,10 +1,12 @@
 use crate::{
-    director::lingo::datum::{Datum, StringChunkType},
+    director::lingo::datum::{datum_bool, Datum, StringChunkType},
     player::{
         cast_lib::CastMemberRef,
+        font::player_ensure_member_fonts,
         handlers::datum_handlers::{
             cast_member_ref::borrow_member_mut, string_chunk::StringChunkUtils,
         },
+        score::get_text_box_height,
         DatumRef, DirPlayer, ScriptError,
     },
 };
@@ -50,6 +52,7 @@ impl FieldMemberHandlers {
         cast_member_ref: &CastMemberRef,
         prop: &String,
     ) -> Result<Datum, ScriptError> {
+        player_ensure_member_fonts(player, cast_member_ref);
         let member = player
             .movie
             .cast_manager
@@ -58,6 +61,27 @@ impl FieldMemberHandlers {
         let field = member.member_type.as_field().unwrap();
         match prop.as_str() {
             "text" => Ok(Datum::String(field.text.to_owned())),
+            "alignment" => Ok(Datum::String(field.alignment.to_owned())),
+            "wordWrap" => Ok(datum_bool(field.word_wrap)),
+            "width" => Ok(Datum::Int(field.width as i32)),
+            "font" => Ok(Datum::String(field.font.to_owned())),
+            "fontSize" => Ok(Datum::Int(field.font_size as i32)),
+            "fontStyle" => Ok(Datum::String(field.font_style.to_owned())),
+            "fixedLineSpace" => Ok(Datum::Int(field.fixed_line_space as i32)),
+            "topSpacing" => Ok(Datum::Int(field.top_spacing as i32)),
+            "boxType" => Ok(Datum::Symbol(field.box_type.to_owned())),
+            "antialias" => Ok(datum_bool(field.anti_alias)),
+            "autoTab" => Ok(datum_bool(field.auto_tab)),
+            "editable" => Ok(datum_bool(field.editable)),
+            "border" => Ok(Datum::Int(field.border as i32)),
+            "height" => {
+                let height = get_text_box_height(player, &field.layout_cache, &field.styled_text(), &field.box_type, field.height);
+                Ok(Datum::Int(height))
+            }
+            "rect" => {
+                let height = get_text_box_height(player, &field.layout_cache, &field.styled_text(), &field.box_type, field.height);
+                Ok(Datum::IntRect((0, 0, field.width as i32, height)))
+            }
             _ => Err(ScriptError::new(format!(
                 "Cannot get castMember property {} for field",
                 prop
@@ -0,0 +1,71 @@
+use crate::{
+    director::lingo::datum::{datum_bool, Datum},
+    player::{
+        cast_lib::CastMemberRef,
+        handlers::datum_handlers::cast_member_ref::borrow_member_mut,
+        DirPlayer, ScriptError,
+    },
+};
+
+pub struct FilmLoopMemberHandlers {}
+
+impl FilmLoopMemberHandlers {
+    pub fn get_prop(
+        player: &mut DirPlayer,
+        cast_member_ref: &CastMemberRef,
+        prop: &String,
+    ) -> Result<Datum, ScriptError> {
+        let member = player
+            .movie
+            .cast_manager
+            .find_member_by_ref(cast_member_ref)
+            .unwrap();
+        let film_loop = member.member_type.as_film_loop().unwrap();
+        match prop.as_str() {
+            "loop" => Ok(datum_bool(film_loop.info.looping)),
+            "crop" => Ok(datum_bool(film_loop.info.crop)),
+            "center" => Ok(datum_bool(film_loop.info.center)),
+            "sound" => Ok(datum_bool(film_loop.info.enable_sound)),
+            "frameCount" => Ok(Datum::Int(film_loop.frame_count as i32)),
+            "width" => Ok(Datum::Int(film_loop.info.width())),
+            "height" => Ok(Datum::Int(film_loop.info.height())),
+            "rect" => Ok(Datum::IntRect((0, 0, film_loop.info.width(), film_loop.info.height()))),
+            "regPoint" => {
+                let (reg_x, reg_y) = film_loop.info.reg_point();
+                Ok(Datum::IntPoint((reg_x as i32, reg_y as i32)))
+            }
+            _ => Err(ScriptError::new(format!(
+                "Cannot get castMember prop {} for film loop",
+                prop
+            ))),
+        }
+    }
+
+    pub fn set_prop(
+        member_ref: &CastMemberRef,
+        prop: &String,
+        value: Datum,
+    ) -> Result<(), ScriptError> {
+        let value = value.to_bool()?;
+        borrow_member_mut(
+            member_ref,
+            |_| {},
+            |cast_member, _| {
+                let info = &mut cast_member.member_type.as_film_loop_mut().unwrap().info;
+                match prop.as_str() {
+                    "loop" => info.looping = value,
+                    "crop" => info.crop = value,
+                    "center" => info.center = value,
+                    "sound" => info.enable_sound = value,
+                    _ => {
+                        return Err(ScriptError::new(format!(
+                            "Cannot set castMember prop {} for film loop",
+                            prop
+                        )))
+                    }
+                }
+                Ok(())
+            },
+        )
+    }
+}
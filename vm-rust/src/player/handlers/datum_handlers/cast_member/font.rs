@@ -0,0 +1,28 @@
+use crate::{
+    director::lingo::datum::Datum,
+    player::{cast_lib::CastMemberRef, DirPlayer, ScriptError},
+};
+
+pub struct FontMemberHandlers {}
+
+impl FontMemberHandlers {
+    pub fn get_prop(
+        player: &mut DirPlayer,
+        cast_member_ref: &CastMemberRef,
+        prop: &String,
+    ) -> Result<Datum, ScriptError> {
+        let member = player
+            .movie
+            .cast_manager
+            .find_member_by_ref(cast_member_ref)
+            .unwrap();
+        let font = member.member_type.as_font().unwrap();
+        match prop.as_str() {
+            "font" | "originalFont" => Ok(Datum::String(font.font_name.to_owned())),
+            _ => Err(ScriptError::new(format!(
+                "Cannot get castMember prop {} for font",
+                prop
+            ))),
+        }
+    }
+}
@@ -1,3 +1,8 @@
 pub mod text;
 pub mod field;
 pub mod bitmap;
+pub mod sound;
+pub mod film_loop;
+pub mod font;
+pub mod shape;
+pub mod script;
@@ -0,0 +1,86 @@
+use crate::{
+    director::{
+        enums::ScriptType,
+        lingo::{
+            datum::Datum,
+            decompiler::{decompile_script, DecompilerContext},
+        },
+    },
+    player::{cast_lib::CastMemberRef, reserve_player_mut, DirPlayer, ScriptError},
+};
+
+pub struct ScriptMemberHandlers {}
+
+impl ScriptMemberHandlers {
+    pub fn get_prop(
+        player: &mut DirPlayer,
+        cast_member_ref: &CastMemberRef,
+        prop: &String,
+    ) -> Result<Datum, ScriptError> {
+        let cast = player
+            .movie
+            .cast_manager
+            .get_cast(cast_member_ref.cast_lib as u32)?;
+        let script = cast
+            .get_script_for_member(cast_member_ref.cast_member as u32)
+            .ok_or_else(|| ScriptError::new(format!(
+                "Script member {} has no compiled script",
+                cast_member_ref.cast_member
+            )))?;
+        match prop.as_str() {
+            "scriptText" => {
+                let lctx = cast.lctx.as_ref().ok_or_else(|| {
+                    ScriptError::new(format!("Cast {} has no script context", cast.name))
+                })?;
+                let ctx = DecompilerContext::new(lctx, &script.chunk, cast.dir_version, cast.capital_x);
+                Ok(Datum::String(decompile_script(&ctx)))
+            }
+            "scriptType" => {
+                let script_type = match script.script_type {
+                    ScriptType::Movie => "movie",
+                    ScriptType::Parent => "parent",
+                    _ => "score",
+                };
+                Ok(Datum::Symbol(script_type.to_string()))
+            }
+            _ => Err(ScriptError::new(format!(
+                "Cannot get castMember prop {} for script",
+                prop
+            ))),
+        }
+    }
+
+    pub fn set_prop(
+        member_ref: &CastMemberRef,
+        prop: &String,
+        value: Datum,
+    ) -> Result<(), ScriptError> {
+        reserve_player_mut(|player| {
+            let cast = player
+                .movie
+                .cast_manager
+                .get_cast_mut(member_ref.cast_lib as u32);
+            let number = member_ref.cast_member as u32;
+            match prop.as_str() {
+                "scriptText" => cast.set_script_text(number, &value.string_value()?)?,
+                "scriptType" => {
+                    let script_type = match value.string_value()?.as_str() {
+                        "movie" => ScriptType::Movie,
+                        "parent" => ScriptType::Parent,
+                        "score" => ScriptType::Score,
+                        other => return Err(ScriptError::new(format!("Invalid scriptType {}", other))),
+                    };
+                    cast.set_script_type(number, script_type)?;
+                }
+                _ => {
+                    return Err(ScriptError::new(format!(
+                        "Cannot set castMember prop {} for script",
+                        prop
+                    )))
+                }
+            }
+            player.movie.cast_manager.clear_movie_script_cache();
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,83 @@
+use log::warn;
+
+use crate::{
+    director::{
+        enums::ShapeType,
+        lingo::datum::{datum_bool, Datum},
+    },
+    player::{
+        bitmap::drawing::shape_pattern,
+        cast_lib::CastMemberRef,
+        handlers::datum_handlers::cast_member_ref::borrow_member_mut,
+        DirPlayer, ScriptError,
+    },
+};
+
+pub struct ShapeMemberHandlers {}
+
+impl ShapeMemberHandlers {
+    pub fn get_prop(
+        player: &mut DirPlayer,
+        cast_member_ref: &CastMemberRef,
+        prop: &String,
+    ) -> Result<Datum, ScriptError> {
+        let member = player
+            .movie
+            .cast_manager
+            .find_member_by_ref(cast_member_ref)
+            .unwrap();
+        let info = &member.member_type.as_shape().unwrap().shape_info;
+        match prop.as_str() {
+            "shapeType" => Ok(Datum::Symbol(info.shape_type.symbol().to_string())),
+            "filled" => Ok(datum_bool(info.filled)),
+            "lineSize" => Ok(Datum::Int(info.line_size as i32)),
+            "pattern" => Ok(Datum::Int(info.pattern as i32)),
+            "lineDirection" => Ok(Datum::Int(info.line_direction as i32)),
+            "width" => Ok(Datum::Int(info.width as i32)),
+            "height" => Ok(Datum::Int(info.height as i32)),
+            "rect" => Ok(Datum::IntRect((0, 0, info.width as i32, info.height as i32))),
+            "regPoint" => Ok(Datum::IntPoint((info.reg_point.0 as i32, info.reg_point.1 as i32))),
+            _ => Err(ScriptError::new(format!(
+                "Cannot get castMember prop {} for shape",
+                prop
+            ))),
+        }
+    }
+
+    pub fn set_prop(
+        member_ref: &CastMemberRef,
+        prop: &String,
+        value: Datum,
+    ) -> Result<(), ScriptError> {
+        borrow_member_mut(
+            member_ref,
+            |_| {},
+            |cast_member, _| {
+                let info = &mut cast_member.member_type.as_shape_mut().unwrap().shape_info;
+                match prop.as_str() {
+                    "shapeType" => {
+                        let name = value.string_value()?;
+                        info.shape_type = ShapeType::from_symbol(&name)
+                            .ok_or_else(|| ScriptError::new(format!("Invalid shapeType {}", name)))?;
+                    }
+                    "filled" => info.filled = value.to_bool()?,
+                    "lineSize" => info.line_size = value.int_value()?.clamp(0, 255) as u8,
+                    "pattern" => {
+                        info.pattern = value.int_value()?.clamp(0, u16::MAX as i32) as u16;
+                        if shape_pattern(info.pattern).is_none() {
+                            warn!("Pattern {} is not supported, the shape will be filled solid", info.pattern);
+                        }
+                    }
+                    "lineDirection" => info.line_direction = (value.int_value()? != 0) as u8,
+                    _ => {
+                        return Err(ScriptError::new(format!(
+                            "Cannot set castMember prop {} for shape",
+                            prop
+                        )))
+                    }
+                }
+                Ok(())
+            },
+        )
+    }
+}
@@ -0,0 +1,58 @@
+use crate::{
+    director::lingo::datum::{datum_bool, Datum},
+    player::{
+        cast_lib::CastMemberRef,
+        handlers::datum_handlers::cast_member_ref::borrow_member_mut,
+        DirPlayer, ScriptError,
+    },
+};
+
+pub struct SoundMemberHandlers {}
+
+impl SoundMemberHandlers {
+    pub fn get_prop(
+        player: &mut DirPlayer,
+        cast_member_ref: &CastMemberRef,
+        prop: &String,
+    ) -> Result<Datum, ScriptError> {
+        let member = player
+            .movie
+            .cast_manager
+            .find_member_by_ref(cast_member_ref)
+            .unwrap();
+        let sound_member = member.member_type.as_sound().unwrap();
+        match prop.as_str() {
+            "sampleRate" => Ok(Datum::Int(sound_member.sample_rate as i32)),
+            "sampleSize" => Ok(Datum::Int(sound_member.sample_size as i32)),
+            "channelCount" => Ok(Datum::Int(sound_member.channel_count as i32)),
+            "sampleCount" => Ok(Datum::Int(sound_member.frame_count() as i32)),
+            "duration" => Ok(Datum::Int(sound_member.duration_ms() as i32)),
+            "loop" => Ok(datum_bool(sound_member.looped)),
+            _ => Err(ScriptError::new(format!(
+                "Cannot get castMember prop {} for sound",
+                prop
+            ))),
+        }
+    }
+
+    pub fn set_prop(
+        member_ref: &CastMemberRef,
+        prop: &String,
+        value: Datum,
+    ) -> Result<(), ScriptError> {
+        match prop.as_str() {
+            "loop" => borrow_member_mut(
+                member_ref,
+                |_| {},
+                |cast_member, _| {
+                    cast_member.member_type.as_sound_mut().unwrap().looped = value.to_bool()?;
+                    Ok(())
+                },
+            ),
+            _ => Err(ScriptError::new(format!(
+                "Cannot set castMember prop {} for sound",
+                prop
+            ))),
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use crate::{
     director::lingo::datum::{datum_bool, Datum, DatumType, StringChunkExpr, StringChunkSource, StringChunkType},
     player::{
-        bitmap::bitmap::{Bitmap, BuiltInPalette, PaletteRef}, cast_lib::CastMemberRef, font::{get_text_index_at_pos, measure_text, DrawTextParams}, handlers::datum_handlers::{cast_member_ref::borrow_member_mut, string_chunk::StringChunkUtils}, DatumRef, DirPlayer, ScriptError
+        bitmap::bitmap::{Bitmap, BuiltInPalette, PaletteRef}, cast_lib::CastMemberRef, font::{layout::draw_styled_text, player_ensure_member_fonts}, handlers::datum_handlers::{cast_member_ref::borrow_member_mut, string_chunk::StringChunkUtils}, score::get_text_box_height, DatumRef, DirPlayer, ScriptError
     },
 };
 
@@ -10,6 +10,7 @@ pub struct TextMemberHandlers {}
 impl TextMemberHandlers {
     pub fn call(player: &mut DirPlayer, datum: &DatumRef, handler_name: &String, args: &Vec<DatumRef>) -> Result<DatumRef, ScriptError> {
         let member_ref = player.get_datum(datum).to_member_ref()?;
+        player_ensure_member_fonts(player, &member_ref);
         let member = player.movie.cast_manager.find_member_by_ref(&member_ref).unwrap();
         let text = member.member_type.as_text().unwrap();
         match handler_name.as_str() {
@@ -38,13 +39,9 @@ impl TextMemberHandlers {
             }
             "locToCharPos" => {
                 let (x, y) = player.get_datum(&args[0]).to_int_point()?;
-                let params = DrawTextParams {
-                    font: player.font_manager.get_system_font().unwrap(),
-                    line_height: None,
-                    line_spacing: text.fixed_line_space,
-                    top_spacing: text.top_spacing,
-                };
-                let index = get_text_index_at_pos(&text.text, &params, x, y);
+                let styled_text = text.styled_text();
+                let layout = text.layout(&player.font_manager);
+                let index = layout.char_index_at(&player.font_manager, &styled_text, x, y);
                 Ok(player.alloc_datum(Datum::Int((index + 1) as i32)))
             }
             _ => Err(ScriptError::new(format!("No handler {handler_name} for text member type")))
@@ -56,6 +53,7 @@ impl TextMemberHandlers {
         cast_member_ref: &CastMemberRef,
         prop: &String,
     ) -> Result<Datum, ScriptError> {
+        player_ensure_member_fonts(player, cast_member_ref);
         let member = player
             .movie
             .cast_manager
@@ -81,59 +79,39 @@ impl TextMemberHandlers {
             "boxType" => Ok(Datum::Symbol(text_data.box_type.to_owned())),
             "antialias" => Ok(datum_bool(text_data.anti_alias)),
             "rect" => {
-                let font = player.font_manager.get_system_font().unwrap();
-                let (width, height) = measure_text(
-                    &text_data.text,
-                    &font,
-                    None,
-                    text_data.fixed_line_space,
-                    text_data.top_spacing,
-                );
-                Ok(Datum::IntRect((0, 0, width as i32, height as i32)))
+                let height = get_text_box_height(player, &text_data.layout_cache, &text_data.styled_text(), &text_data.box_type, text_data.height);
+                Ok(Datum::IntRect((0, 0, text_data.width as i32, height)))
             }
             "height" => {
-                let font = player.font_manager.get_system_font().unwrap();
-                let (_, height) = measure_text(
-                    &text_data.text,
-                    &font,
-                    None,
-                    text_data.fixed_line_space,
-                    text_data.top_spacing,
-                );
-                Ok(Datum::Int(height as i32))
+                let height = get_text_box_height(player, &text_data.layout_cache, &text_data.styled_text(), &text_data.box_type, text_data.height);
+                Ok(Datum::Int(height))
             }
             "image" => {
-                // TODO: alignment
-                let font = player.font_manager.get_system_font().unwrap();
-                let (width, height) = measure_text(
-                    &text_data.text,
-                    &font,
-                    None,
-                    text_data.fixed_line_space,
-                    text_data.top_spacing,
-                );
+                let styled_text = text_data.styled_text();
+                let layout = text_data.layout(&player.font_manager);
+                let width = if text_data.width > 0 { text_data.width as i32 } else { layout.width };
+                let height = get_text_box_height(player, &text_data.layout_cache, &styled_text, &text_data.box_type, text_data.height);
                 // TODO use 32 bits
                 let mut bitmap = Bitmap::new(
-                    width,
-                    height,
+                    width.max(1) as u16,
+                    height.max(1) as u16,
                     8,
                     PaletteRef::BuiltIn(BuiltInPalette::GrayScale),
                 );
-                let font_bitmap = player.bitmap_manager.get_bitmap(font.bitmap_ref).unwrap();
                 let palettes = player.movie.cast_manager.palettes();
 
                 let ink = 36;
-                bitmap.draw_text(
-                    &text_data.text,
-                    font,
-                    font_bitmap,
-                    0,
-                    text_data.top_spacing as i32,
+                let bg_color = bitmap.get_bg_color_ref();
+                draw_styled_text(
+                    &mut bitmap,
+                    &player.font_manager,
+                    &player.bitmap_manager,
+                    &styled_text,
+                    &layout,
+                    (0, 0),
                     ink,
-                    bitmap.get_bg_color_ref(),
+                    bg_color,
                     &palettes,
-                    text_data.fixed_line_space,
-                    text_data.top_spacing,
                 );
 
                 let bitmap_ref = player.bitmap_manager.add_bitmap(bitmap);
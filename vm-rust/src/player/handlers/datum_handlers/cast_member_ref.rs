@@ -1,8 +1,8 @@
 use log::warn;
 
-use crate::{director::lingo::datum::Datum, js_api::JsApi, player::{cast_lib::CastMemberRef, cast_member::{CastMember, CastMemberType, CastMemberTypeId, TextMember}, handlers::types::TypeUtils, reserve_player_mut, reserve_player_ref, DatumRef, DirPlayer, ScriptError}};
+use crate::{director::lingo::datum::Datum, js_api::JsApi, player::{cast_lib::CastMemberRef, font::player_ensure_member_fonts, cast_member::{CastMember, CastMemberType, CastMemberTypeId, TextMember}, handlers::types::TypeUtils, reserve_player_mut, reserve_player_ref, DatumRef, DirPlayer, ScriptError}};
 
-use super::cast_member::{bitmap::BitmapMemberHandlers, field::FieldMemberHandlers, text::TextMemberHandlers};
+use super::cast_member::{bitmap::BitmapMemberHandlers, field::FieldMemberHandlers, film_loop::FilmLoopMemberHandlers, font::FontMemberHandlers, script::ScriptMemberHandlers, shape::ShapeMemberHandlers, sound::SoundMemberHandlers, text::TextMemberHandlers};
 
 pub struct CastMemberRefHandlers {}
 
@@ -44,19 +44,19 @@ impl CastMemberRefHandlers {
             Datum::CastMember(cast_member_ref) => cast_member_ref.to_owned(),
             _ => return Err(ScriptError::new("Cannot call charPosToLoc on non-cast-member".to_string())),
           };
+          player_ensure_member_fonts(player, &cast_member_ref);
           let cast_member = player.movie.cast_manager.find_member_by_ref(&cast_member_ref).unwrap();
           let text_data = cast_member.member_type.as_text().unwrap();
-          let char_pos = player.get_datum(&args[0]).int_value()? as u16;
-          let char_width: u16 = 7; // TODO: Implement char width
-          let line_height = get_text_member_line_height(&text_data);
-          let result = if text_data.text.is_empty() || char_pos <= 0 {
-            Datum::IntPoint((0, 0))
-          } else if char_pos > text_data.text.len() as u16 {
-            Datum::IntPoint(((char_width * (text_data.text.len() as u16)) as i32, line_height as i32))
-          } else {
-            Datum::IntPoint(((char_width * (char_pos - 1)) as i32, line_height as i32))
-          };
-          // TODO this is a stub!
+          let char_pos = player.get_datum(&args[0]).int_value()?;
+          let styled_text = text_data.styled_text();
+          let layout = text_data.layout(&player.font_manager);
+          let char_index = (char_pos.max(1) - 1) as usize;
+          let (x, y) = layout.char_pos(&player.font_manager, &styled_text, char_index);
+          // The point is at the bottom of the line the character is on
+          let line_height = layout.lines.iter()
+            .find(|line| line.y == y)
+            .map_or(get_text_member_line_height(text_data) as i32, |line| line.height);
+          let result = Datum::IntPoint((x, y + line_height));
           Ok(player.alloc_datum(result))
         })
       },
@@ -176,6 +176,21 @@ impl CastMemberRefHandlers {
       CastMemberTypeId::Text => {
         TextMemberHandlers::get_prop(player, cast_member_ref, prop)
       }
+      CastMemberTypeId::Sound => {
+        SoundMemberHandlers::get_prop(player, cast_member_ref, prop)
+      }
+      CastMemberTypeId::FilmLoop => {
+        FilmLoopMemberHandlers::get_prop(player, cast_member_ref, prop)
+      }
+      CastMemberTypeId::Font => {
+        FontMemberHandlers::get_prop(player, cast_member_ref, prop)
+      }
+      CastMemberTypeId::Shape => {
+        ShapeMemberHandlers::get_prop(player, cast_member_ref, prop)
+      }
+      CastMemberTypeId::Script => {
+        ScriptMemberHandlers::get_prop(player, cast_member_ref, prop)
+      }
       _ => {
         Err(ScriptError::new(format!("Cannot get castMember prop {} for member of type {:?}", prop, member_type)))
       }
@@ -205,6 +220,18 @@ impl CastMemberRefHandlers {
       CastMemberTypeId::Bitmap => {
         BitmapMemberHandlers::set_prop(member_ref, prop, value)
       }
+      CastMemberTypeId::Sound => {
+        SoundMemberHandlers::set_prop(member_ref, prop, value)
+      }
+      CastMemberTypeId::FilmLoop => {
+        FilmLoopMemberHandlers::set_prop(member_ref, prop, value)
+      }
+      CastMemberTypeId::Shape => {
+        ShapeMemberHandlers::set_prop(member_ref, prop, value)
+      }
+      CastMemberTypeId::Script => {
+        ScriptMemberHandlers::set_prop(member_ref, prop, value)
+      }
       _ => {
         Err(ScriptError::new(format!("Cannot set castMember prop {} for member of type {:?}", prop, member_type)))
       }
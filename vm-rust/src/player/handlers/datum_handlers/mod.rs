@@ -20,7 +20,7 @@ pub mod sound;
 
 use player::PlayerDatumHandlers;
 
-use crate::{director::lingo::datum::DatumType, player::{format_datum, reserve_player_ref, xtra::manager::{call_xtra_instance_async_handler, call_xtra_instance_handler, has_xtra_instance_async_handler}, DatumRef, ScriptError, ScriptErrorCode}};
+use crate::{director::lingo::datum::DatumType, player::{format_datum, reserve_player_ref, xtra::manager::{call_xtra_handler, call_xtra_instance_async_handler, call_xtra_instance_handler, has_xtra_instance_async_handler}, DatumRef, ScriptError, ScriptErrorCode}};
 
 use self::{bitmap::BitmapDatumHandlers, list_handlers::ListDatumHandlers, point::PointDatumHandlers, prop_list::PropListDatumHandlers, rect::RectDatumHandlers, script::ScriptDatumHandlers, sprite::SpriteDatumHandlers, string::StringDatumHandlers, string_chunk::StringChunkHandlers, timeout::TimeoutDatumHandlers};
 
@@ -76,8 +76,13 @@ pub async fn player_call_datum_handler(
         call_xtra_instance_handler(&xtra_name, instance_id, handler_name, args)
       }
     }
+    DatumType::Xtra => {
+      let xtra_name = reserve_player_ref(|player| player.get_datum(obj_ref).to_xtra_name().cloned())?;
+      call_xtra_handler(&xtra_name, handler_name, args)
+    }
     DatumType::ColorRef => color::ColorDatumHandlers::call(obj_ref, handler_name, args),
     DatumType::PlayerRef => PlayerDatumHandlers::call(handler_name, args),
+    DatumType::SoundRef => sound::SoundDatumHandlers::call(obj_ref, handler_name, args),
     _ => reserve_player_ref(|player| {
       let formatted_datum = format_datum(obj_ref, &player);
       Err(ScriptError::new_code(ScriptErrorCode::HandlerNotFound, format!("No handler {handler_name} for datum {}", formatted_datum)))
@@ -1,35 +1,196 @@
-use crate::{director::lingo::datum::Datum, player::{DatumRef, DirPlayer, ScriptError}};
+use crate::{director::lingo::datum::{datum_bool, Datum, DatumType}, player::{cast_lib::NULL_CAST_MEMBER_REF, reserve_player_mut, sound::{SoundChannel, SoundPlaylistEntry}, DatumRef, DirPlayer, ScriptError}};
 
 pub struct SoundDatumHandlers {}
 
 impl SoundDatumHandlers {
-  #[allow(dead_code, unused_variables)]
   pub fn call(datum: &DatumRef, handler_name: &String, args: &Vec<DatumRef>) -> Result<DatumRef, ScriptError> {
-    match handler_name.as_str() {
-      _ => Err(ScriptError::new(format!("No handler {handler_name} for sound")))
+    reserve_player_mut(|player| {
+      let channel_num = Self::get_channel_num(player, datum)?;
+      match handler_name.as_str() {
+        "play" => {
+          if let Some(arg) = args.get(0) {
+            let entry = Self::playlist_entry_from_datum(player, arg)?;
+            player.sound_manager.get_channel_mut(channel_num)?.play_now(entry);
+          } else {
+            player.sound_manager.get_channel_mut(channel_num)?.play();
+          }
+          Ok(DatumRef::Void)
+        }
+        "queue" => {
+          let entry = Self::playlist_entry_from_datum(player, &args[0])?;
+          player.sound_manager.get_channel_mut(channel_num)?.queue(entry);
+          Ok(DatumRef::Void)
+        }
+        "playNext" => {
+          let channel = player.sound_manager.get_channel_mut(channel_num)?;
+          channel.stop();
+          channel.play();
+          Ok(DatumRef::Void)
+        }
+        "stop" => {
+          player.sound_manager.get_channel_mut(channel_num)?.stop();
+          Ok(DatumRef::Void)
+        }
+        "pause" => {
+          player.sound_manager.get_channel_mut(channel_num)?.pause();
+          Ok(DatumRef::Void)
+        }
+        "rewind" => {
+          player.sound_manager.get_channel_mut(channel_num)?.rewind();
+          Ok(DatumRef::Void)
+        }
+        "breakLoop" => {
+          player.sound_manager.get_channel_mut(channel_num)?.break_loop();
+          Ok(DatumRef::Void)
+        }
+        "isBusy" => {
+          let is_busy = player.sound_manager.get_channel(channel_num)?.is_busy();
+          Ok(player.alloc_datum(datum_bool(is_busy)))
+        }
+        "setPlayList" => {
+          let list = player.get_datum(&args[0]).to_list()?.clone();
+          let entries = list.iter()
+            .map(|item| Self::playlist_entry_from_datum(player, item))
+            .collect::<Result<Vec<_>, _>>()?;
+          player.sound_manager.get_channel_mut(channel_num)?.set_playlist(entries);
+          Ok(DatumRef::Void)
+        }
+        "getPlayList" => {
+          let entries = player.sound_manager.get_channel(channel_num)?.playlist.clone();
+          let item_refs = entries.iter()
+            .map(|entry| Self::playlist_entry_to_datum(player, entry))
+            .collect();
+          Ok(player.alloc_datum(Datum::List(DatumType::List, item_refs, false)))
+        }
+        _ => Err(ScriptError::new(format!("No handler {handler_name} for sound")))
+      }
+    })
+  }
+
+  fn get_channel_num(player: &DirPlayer, datum: &DatumRef) -> Result<u16, ScriptError> {
+    match player.get_datum(datum) {
+      Datum::SoundRef(channel_num) => Ok(*channel_num),
+      _ => Err(ScriptError::new("Cannot get sound channel of non-sound datum".to_string())),
     }
   }
 
+  /// Resolves a member reference, name or number to a playlist entry for that sound member.
+  pub fn sound_entry_for_member(player: &DirPlayer, member: &Datum) -> Result<SoundPlaylistEntry, ScriptError> {
+    let member_ref = match member {
+      Datum::CastMember(member_ref) => Some(member_ref.to_owned()),
+      _ => player.movie.cast_manager.find_member_ref_by_identifiers(member, None, &player.allocator)?,
+    };
+    let member_ref = member_ref.ok_or_else(|| ScriptError::new("Sound member not found".to_string()))?;
+    let sound = player.movie.cast_manager
+      .find_member_by_ref(&member_ref)
+      .and_then(|x| x.member_type.as_sound())
+      .ok_or_else(|| ScriptError::new(format!("Member ({}, {}) is not a sound", member_ref.cast_lib, member_ref.cast_member)))?;
+    Ok(SoundPlaylistEntry::new(member_ref, sound.clone()))
+  }
 
-  pub fn get_prop(_player: &DirPlayer, _datum: &DatumRef, prop: &String) -> Result<Datum, ScriptError> {
+  /// Accepts either a sound member or a property list such as `[#member: member("x"), #loopCount: 2]`.
+  fn playlist_entry_from_datum(player: &DirPlayer, datum_ref: &DatumRef) -> Result<SoundPlaylistEntry, ScriptError> {
+    let datum = player.get_datum(datum_ref);
+    let props = match datum {
+      Datum::PropList(props, ..) => props,
+      _ => return Self::sound_entry_for_member(player, datum),
+    };
+    let get_prop = |name: &str| -> Result<Option<&Datum>, ScriptError> {
+      for (key, value) in props {
+        let key = player.get_datum(key);
+        if (key.is_symbol() || key.is_string()) && key.string_value()?.eq_ignore_ascii_case(name) {
+          return Ok(Some(player.get_datum(value)));
+        }
+      }
+      Ok(None)
+    };
+    let member = get_prop("member")?.ok_or_else(|| ScriptError::new("Sound playlist entry requires a #member".to_string()))?;
+    let mut entry = Self::sound_entry_for_member(player, member)?;
+    if let Some(loop_count) = get_prop("loopCount")? {
+      entry.loop_count = loop_count.int_value()?.max(0) as u32;
+    }
+    if let Some(start_time) = get_prop("startTime")? {
+      entry.start_time = start_time.int_value()?.max(0) as u32;
+    }
+    if let Some(end_time) = get_prop("endTime")? {
+      entry.end_time = Some(end_time.int_value()?.max(0) as u32);
+    }
+    if let Some(loop_start_time) = get_prop("loopStartTime")? {
+      entry.loop_start_time = Some(loop_start_time.int_value()?.max(0) as u32);
+    }
+    if let Some(loop_end_time) = get_prop("loopEndTime")? {
+      entry.loop_end_time = Some(loop_end_time.int_value()?.max(0) as u32);
+    }
+    Ok(entry)
+  }
+
+  fn playlist_entry_to_datum(player: &mut DirPlayer, entry: &SoundPlaylistEntry) -> DatumRef {
+    let mut props = vec![
+      ("member", Datum::CastMember(entry.member_ref.to_owned())),
+      ("loopCount", Datum::Int(entry.loop_count as i32)),
+      ("startTime", Datum::Int(entry.start_time as i32)),
+    ];
+    if let Some(end_time) = entry.end_time {
+      props.push(("endTime", Datum::Int(end_time as i32)));
+    }
+    if let Some(loop_start_time) = entry.loop_start_time {
+      props.push(("loopStartTime", Datum::Int(loop_start_time as i32)));
+    }
+    if let Some(loop_end_time) = entry.loop_end_time {
+      props.push(("loopEndTime", Datum::Int(loop_end_time as i32)));
+    }
+    let pairs = props.into_iter()
+      .map(|(key, value)| (player.alloc_datum(Datum::Symbol(key.to_string())), player.alloc_datum(value)))
+      .collect();
+    player.alloc_datum(Datum::PropList(pairs, false))
+  }
+
+  fn get_channel_prop(channel: &SoundChannel, prop: &String) -> Result<Datum, ScriptError> {
+    let entry = channel.current_entry();
     match prop.as_str() {
-      "volume" => {
-        Ok(Datum::Int(255)) // TODO
-      },
-      _ => {
-        Err(ScriptError::new(format!("Cannot get rect property {}", prop)))
-      },
+      "volume" => Ok(Datum::Int(channel.volume)),
+      "pan" => Ok(Datum::Int(channel.pan)),
+      "status" => Ok(Datum::Int(channel.status as i32)),
+      "member" => Ok(Datum::CastMember(entry.map_or(NULL_CAST_MEMBER_REF, |x| x.member_ref.to_owned()))),
+      "loopCount" => Ok(Datum::Int(entry.map_or(0, |x| x.loop_count as i32))),
+      "loopsRemaining" => Ok(Datum::Int(channel.loops_remaining() as i32)),
+      "elapsedTime" => Ok(Datum::Int(channel.elapsed_time() as i32)),
+      "startTime" => Ok(Datum::Int(entry.map_or(0, |x| x.start_time as i32))),
+      "endTime" => Ok(Datum::Int(entry.map_or(0, |x| x.end_time.unwrap_or(x.sound.duration_ms()) as i32))),
+      "sampleRate" => Ok(Datum::Int(entry.map_or(0, |x| x.sound.sample_rate as i32))),
+      "sampleCount" => Ok(Datum::Int(entry.map_or(0, |x| x.sound.frame_count() as i32))),
+      "channelCount" => Ok(Datum::Int(entry.map_or(0, |x| x.sound.channel_count as i32))),
+      _ => Err(ScriptError::new(format!("Cannot get sound property {}", prop))),
     }
   }
 
-  pub fn set_prop(_player: &mut DirPlayer, _datum: &DatumRef, prop: &String, _value_ref: &DatumRef) -> Result<(), ScriptError> {
+  pub fn get_prop(player: &DirPlayer, datum: &DatumRef, prop: &String) -> Result<Datum, ScriptError> {
+    let channel_num = Self::get_channel_num(player, datum)?;
+    let channel = player.sound_manager.get_channel(channel_num)?;
+    Self::get_channel_prop(channel, prop)
+  }
+
+  pub fn set_prop(player: &mut DirPlayer, datum: &DatumRef, prop: &String, value_ref: &DatumRef) -> Result<(), ScriptError> {
+    let channel_num = Self::get_channel_num(player, datum)?;
+    let value = player.get_datum(value_ref).int_value()?;
+    let channel = player.sound_manager.get_channel_mut(channel_num)?;
     match prop.as_str() {
       "volume" => {
-        // TODO
+        channel.volume = value.clamp(0, 255);
+        Ok(())
+      },
+      "pan" => {
+        channel.pan = value.clamp(-100, 100);
+        Ok(())
+      },
+      "loopCount" => {
+        if let Some(entry) = channel.current_entry_mut() {
+          entry.loop_count = value.max(0) as u32;
+        }
         Ok(())
       },
       _ => {
-        Err(ScriptError::new(format!("Cannot set rect property {}", prop)))
+        Err(ScriptError::new(format!("Cannot set sound property {}", prop)))
       },
     }
   }
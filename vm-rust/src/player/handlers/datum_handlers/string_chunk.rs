@@ -1,6 +1,6 @@
 use itertools::Itertools;
 
-use crate::{director::lingo::datum::{Datum, StringChunkExpr, StringChunkSource, StringChunkType}, player::{cast_member::CastMemberType, reserve_player_mut, DatumRef, DirPlayer, ScriptError}};
+use crate::{director::lingo::datum::{Datum, StringChunkExpr, StringChunkSource, StringChunkType}, js_api::JsApi, player::{cast_member::CastMemberType, reserve_player_mut, DatumRef, DirPlayer, ScriptError}};
 
 use super::string::{string_get_items, string_get_lines};
 
@@ -8,12 +8,23 @@ pub struct StringChunkHandlers { }
 pub struct StringChunkUtils { }
 
 impl StringChunkUtils {
+  fn get_source_string(player: &DirPlayer, original_str_src: &StringChunkSource) -> Result<String, ScriptError> {
+    match original_str_src {
+      StringChunkSource::Datum(original_str_ref) => player.get_datum(original_str_ref).string_value(),
+      StringChunkSource::Member(member_ref) => {
+        let member = player.movie.cast_manager.find_member_by_ref(member_ref);
+        match member.map(|x| &x.member_type) {
+          Some(CastMemberType::Field(field)) => Ok(field.text.clone()),
+          Some(CastMemberType::Text(text)) => Ok(text.text.clone()),
+          _ => Err(ScriptError::new("Cannot get contents of non-text member".to_string())),
+        }
+      }
+    }
+  }
+
   pub fn delete(player: &mut DirPlayer, original_str_src: &StringChunkSource, chunk_expr: &StringChunkExpr) -> Result<(), ScriptError> {
     let new_string = {
-      let original_str = match original_str_src {
-        StringChunkSource::Datum(original_str_ref) => player.get_datum(original_str_ref).string_value()?,
-        StringChunkSource::Member(member_ref) => player.movie.cast_manager.find_member_by_ref(&member_ref).unwrap().member_type.as_field().unwrap().text.clone()
-      };
+      let original_str = Self::get_source_string(player, original_str_src)?;
       Self::string_by_deleting_chunk(&original_str, &chunk_expr)
     }?;
     Self::set_value(player, original_str_src, chunk_expr, new_string)?;
@@ -22,17 +33,14 @@ impl StringChunkUtils {
 
   pub fn set_contents(player: &mut DirPlayer, original_str_src: &StringChunkSource, chunk_expr: &StringChunkExpr, new_string: String) -> Result<(), ScriptError> {
     let new_string = {
-      let original_str = match original_str_src {
-        StringChunkSource::Datum(original_str_ref) => player.get_datum(original_str_ref).string_value()?,
-        StringChunkSource::Member(member_ref) => player.movie.cast_manager.find_member_by_ref(&member_ref).unwrap().member_type.as_field().unwrap().text.clone()
-      };
+      let original_str = Self::get_source_string(player, original_str_src)?;
       Self::string_by_setting_chunk(&original_str, &chunk_expr, &new_string)
     }?;
     Self::set_value(player, original_str_src, chunk_expr, new_string)?;
     Ok(())
   }
 
-  pub fn set_value(player: &mut DirPlayer, original_str_src: &StringChunkSource, chunk_expr: &StringChunkExpr, new_string: String) -> Result<(), ScriptError> {
+  pub fn set_value(player: &mut DirPlayer, original_str_src: &StringChunkSource, _chunk_expr: &StringChunkExpr, new_string: String) -> Result<(), ScriptError> {
     match original_str_src {
       StringChunkSource::Datum(original_str_ref) => {
         let original_str_value = player.get_datum_mut(original_str_ref).to_string_mut()?;
@@ -45,37 +53,158 @@ impl StringChunkUtils {
           CastMemberType::Text(member) => member.text = new_string,
           _ => return Err(ScriptError::new("Cannot set contents for non-text member".to_string()))
         }
+        JsApi::dispatch_cast_member_changed(member_ref.to_owned());
       }
     }
     Ok(())
   }
 
-  pub fn string_by_deleting_chunk(string: &String, chunk_expr: &StringChunkExpr) -> Result<String, ScriptError> {
-    match chunk_expr.chunk_type {
-      StringChunkType::Char => {
-        let mut new_string = string.clone();
-        let (start, end) = Self::vm_range_to_host((chunk_expr.start, chunk_expr.end), string.len());
-        new_string.replace_range(start..end, "");
-        Ok(new_string)
-      },
-      _ => {
-        Err(ScriptError::new("Only char chunk type is supported for string by deleting chunk".to_string()))
+  fn get_line_break(string: &str) -> &'static str {
+    if string.contains("\r\n") { "\r\n" } else if string.contains("\n") { "\n" } else { "\r" }
+  }
+
+  /// Returns the delimiter between chunks of the given type, if chunks of that type have one.
+  fn get_chunk_delimiter(string: &str, chunk_type: &StringChunkType, item_delimiter: &str) -> Option<String> {
+    match chunk_type {
+      StringChunkType::Char | StringChunkType::Word => None,
+      StringChunkType::Line => Some(Self::get_line_break(string).to_string()),
+      StringChunkType::Item => {
+        if item_delimiter == "\r" || item_delimiter == "\n" {
+          Some(Self::get_line_break(string).to_string())
+        } else {
+          Some(item_delimiter.to_string())
+        }
       }
     }
   }
 
-  pub fn string_by_setting_chunk(string: &String, chunk_expr: &StringChunkExpr, replace_with: &String) -> Result<String, ScriptError> {
+  /// Returns the byte range of every chunk of the given type in the string.
+  fn get_chunk_byte_ranges(string: &str, chunk_type: &StringChunkType, item_delimiter: &str) -> Vec<(usize, usize)> {
+    match chunk_type {
+      StringChunkType::Char => string.char_indices().map(|(i, c)| (i, i + c.len_utf8())).collect_vec(),
+      StringChunkType::Word => {
+        let mut ranges = vec![];
+        let mut word_start = None;
+        for (i, c) in string.char_indices() {
+          match (c.is_whitespace(), word_start) {
+            (false, None) => word_start = Some(i),
+            (true, Some(start)) => {
+              ranges.push((start, i));
+              word_start = None;
+            }
+            _ => {}
+          }
+        }
+        if let Some(start) = word_start {
+          ranges.push((start, string.len()));
+        }
+        ranges
+      }
+      StringChunkType::Item | StringChunkType::Line => {
+        let delimiter = Self::get_chunk_delimiter(string, chunk_type, item_delimiter).unwrap();
+        let mut ranges = vec![];
+        let mut start = 0;
+        for (i, _) in string.match_indices(delimiter.as_str()) {
+          ranges.push((start, i));
+          start = i + delimiter.len();
+        }
+        ranges.push((start, string.len()));
+        ranges
+      }
+    }
+  }
+
+  /// Resolves a chunk expression to a byte range. Items and lines past the end are created by padding the
+  /// string with delimiters, so the returned string may be longer than the input.
+  fn resolve_chunk_byte_range(string: &String, chunk_expr: &StringChunkExpr, pad: bool) -> (String, Option<(usize, usize)>) {
+    let mut string = string.clone();
+    let mut ranges = Self::get_chunk_byte_ranges(&string, &chunk_expr.chunk_type, &chunk_expr.item_delimiter);
+    let start = if chunk_expr.start == -1 { ranges.len() as i32 } else { chunk_expr.start.max(1) };
+    let end = if chunk_expr.end == 0 { start } else if chunk_expr.end == -1 { ranges.len() as i32 } else { chunk_expr.end.max(start) };
+    // The last chunk of a string without any, such as the last word of ""
+    if start < 1 {
+      return (string, None);
+    }
+
+    if start as usize > ranges.len() {
+      let delimiter = Self::get_chunk_delimiter(&string, &chunk_expr.chunk_type, &chunk_expr.item_delimiter);
+      match delimiter {
+        Some(delimiter) if pad => {
+          for _ in ranges.len()..start as usize {
+            string.push_str(&delimiter);
+          }
+          ranges = Self::get_chunk_byte_ranges(&string, &chunk_expr.chunk_type, &chunk_expr.item_delimiter);
+        }
+        _ => return (string.to_owned(), None),
+      }
+    }
+    let end = (end as usize).min(ranges.len());
+    let range = (ranges[start as usize - 1].0, ranges[end - 1].1);
+    (string, Some(range))
+  }
+
+  /// Returns the character offsets of a chunk, as used by `selStart` and `selEnd`.
+  pub fn resolve_chunk_char_range(string: &String, chunk_expr: &StringChunkExpr) -> (usize, usize) {
+    let (_, range) = Self::resolve_chunk_byte_range(string, chunk_expr, false);
+    match range {
+      Some((start, end)) => (string[..start].chars().count(), string[..end].chars().count()),
+      None => {
+        let len = string.chars().count();
+        (len, len)
+      }
+    }
+  }
+
+  pub fn string_by_deleting_chunk(string: &String, chunk_expr: &StringChunkExpr) -> Result<String, ScriptError> {
+    let (string, range) = Self::resolve_chunk_byte_range(string, chunk_expr, false);
+    let (mut start, mut end) = match range {
+      Some(range) => range,
+      None => return Ok(string),
+    };
+    // Deleting a word, item or line also removes the delimiter that separated it from its neighbour
     match chunk_expr.chunk_type {
-      StringChunkType::Char => {
-        let mut new_string = string.clone();
-        let (start, end) = Self::vm_range_to_host((chunk_expr.start, chunk_expr.end), string.len());
-        new_string.replace_range(start..end, &replace_with);
-        Ok(new_string)
-      },
-      _ => {
-        Err(ScriptError::new("Only char chunk type is supported for string by setting chunk".to_string()))
+      StringChunkType::Char => {}
+      StringChunkType::Word => {
+        let trailing = string[end..].len() - string[end..].trim_start().len();
+        if trailing > 0 {
+          end += trailing;
+        } else {
+          start -= string[..start].len() - string[..start].trim_end().len();
+        }
+      }
+      StringChunkType::Item | StringChunkType::Line => {
+        let delimiter = Self::get_chunk_delimiter(&string, &chunk_expr.chunk_type, &chunk_expr.item_delimiter).unwrap();
+        if string[end..].starts_with(delimiter.as_str()) {
+          end += delimiter.len();
+        } else if string[..start].ends_with(delimiter.as_str()) {
+          start -= delimiter.len();
+        }
       }
     }
+    let mut new_string = string;
+    new_string.replace_range(start..end, "");
+    Ok(new_string)
+  }
+
+  pub fn string_by_setting_chunk(string: &String, chunk_expr: &StringChunkExpr, replace_with: &String) -> Result<String, ScriptError> {
+    let (mut new_string, range) = Self::resolve_chunk_byte_range(string, chunk_expr, true);
+    let (start, end) = range.unwrap_or((new_string.len(), new_string.len()));
+    new_string.replace_range(start..end, replace_with);
+    Ok(new_string)
+  }
+
+  pub fn string_by_putting_before_chunk(string: &String, chunk_expr: &StringChunkExpr, value: &String) -> Result<String, ScriptError> {
+    let (mut new_string, range) = Self::resolve_chunk_byte_range(string, chunk_expr, true);
+    let (start, _) = range.unwrap_or((new_string.len(), new_string.len()));
+    new_string.insert_str(start, value);
+    Ok(new_string)
+  }
+
+  pub fn string_by_putting_after_chunk(string: &String, chunk_expr: &StringChunkExpr, value: &String) -> Result<String, ScriptError> {
+    let (mut new_string, range) = Self::resolve_chunk_byte_range(string, chunk_expr, true);
+    let (_, end) = range.unwrap_or((new_string.len(), new_string.len()));
+    new_string.insert_str(end, value);
+    Ok(new_string)
   }
 
   fn vm_range_to_host(range: (i32, i32), max_length: usize) -> (usize, usize) {
@@ -281,3 +410,40 @@ impl StringChunkHandlers {
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use crate::director::lingo::datum::{StringChunkExpr, StringChunkType};
+
+  use super::StringChunkUtils;
+
+  fn last(chunk_type: StringChunkType) -> StringChunkExpr {
+    StringChunkExpr { chunk_type, start: -1, end: 0, item_delimiter: ",".to_string() }
+  }
+
+  #[test]
+  fn last_word_of_empty_string() {
+    for string in ["", "   "] {
+      let string = string.to_string();
+      let len = string.chars().count();
+      assert_eq!(StringChunkUtils::resolve_chunk_char_range(&string, &last(StringChunkType::Word)), (len, len));
+      assert_eq!(StringChunkUtils::string_by_deleting_chunk(&string, &last(StringChunkType::Word)).unwrap(), string);
+      assert_eq!(
+        StringChunkUtils::string_by_setting_chunk(&string, &last(StringChunkType::Word), &"x".to_string()).unwrap(),
+        format!("{}x", string)
+      );
+    }
+    assert_eq!(StringChunkUtils::string_by_deleting_chunk(&"".to_string(), &last(StringChunkType::Char)).unwrap(), "");
+  }
+
+  #[test]
+  fn put_into_chunks() {
+    let string = "one two,three".to_string();
+    let word = StringChunkExpr { chunk_type: StringChunkType::Word, start: 2, end: 0, item_delimiter: ",".to_string() };
+    assert_eq!(StringChunkUtils::string_by_setting_chunk(&string, &word, &"2".to_string()).unwrap(), "one 2");
+    assert_eq!(StringChunkUtils::string_by_setting_chunk(&string, &last(StringChunkType::Item), &"3".to_string()).unwrap(), "one two,3");
+    let item = StringChunkExpr { chunk_type: StringChunkType::Item, start: 4, end: 0, item_delimiter: ",".to_string() };
+    assert_eq!(StringChunkUtils::string_by_setting_chunk(&string, &item, &"4".to_string()).unwrap(), "one two,three,,4");
+    assert_eq!(StringChunkUtils::string_by_deleting_chunk(&string, &last(StringChunkType::Word)).unwrap(), "one");
+  }
+}
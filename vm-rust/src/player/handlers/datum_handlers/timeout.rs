@@ -1,4 +1,4 @@
-use crate::{console_warn, director::lingo::datum::Datum, player::{reserve_player_mut, timeout::Timeout, DatumRef, DirPlayer, ScriptError}};
+use crate::{console_warn, director::lingo::datum::{datum_bool, Datum}, player::{reserve_player_mut, timeout::Timeout, DatumRef, DirPlayer, ScriptError}};
 
 pub struct TimeoutDatumHandlers {}
 
@@ -23,14 +23,7 @@ impl TimeoutDatumHandlers {
         _ => return Err(ScriptError::new("Cannot create timeout from non-timeout".to_string())),
       };
 
-      let mut timeout = Timeout {
-        handler: timeout_handler,
-        name: timeout_name.to_owned(),
-        period: timeout_period as u32,
-        target_ref,
-        is_scheduled: false,
-      };
-      timeout.schedule();
+      let timeout = Timeout::new(timeout_name.to_owned(), timeout_period.max(0) as u32, timeout_handler, target_ref);
       player.timeout_manager.add_timeout(timeout);
       Ok(datum.clone())
     })
@@ -52,46 +45,45 @@ impl TimeoutDatumHandlers {
 
   pub fn get_prop(player: &mut DirPlayer, datum: &DatumRef, prop: &String) -> Result<DatumRef, ScriptError> {
     let timeout_ref = player.get_datum(datum);
-    let _timeout_name = match timeout_ref {
-      Datum::TimeoutRef(timeout_name) => Ok(timeout_name),
+    let timeout_name = match timeout_ref {
+      Datum::TimeoutRef(timeout_name) => Ok(timeout_name.to_owned()),
       _ => Err(ScriptError::new("Cannot get prop of non-timeout".to_string())),
     }?;
-    let timeout = player.timeout_manager.get_timeout(_timeout_name);
-    match prop.as_str() {
-      "name" => {
-        Ok(player.alloc_datum(Datum::String(_timeout_name.to_owned())))
-      },
-      "target" => {
-        Ok(timeout.map_or(DatumRef::Void, |x| x.target_ref.clone()))
-      }
-      _ => {
-        Err(ScriptError::new(format!("Cannot get timeout property {}", prop)))
-      },
+    if prop == "name" {
+      return Ok(player.alloc_datum(Datum::String(timeout_name)));
     }
+    let timeout = player.timeout_manager.get_timeout(&timeout_name);
+    let value = match prop.as_str() {
+      "target" => return Ok(timeout.map_or(DatumRef::Void, |x| x.target_ref.clone())),
+      "period" => Datum::Int(timeout.map_or(0, |x| x.period as i32)),
+      "persistent" => datum_bool(timeout.is_some_and(|x| x.persistent)),
+      "timeoutHandler" => timeout.map_or(Datum::Void, |x| Datum::Symbol(x.handler.to_owned())),
+      _ => return Err(ScriptError::new(format!("Cannot get timeout property {}", prop))),
+    };
+    Ok(player.alloc_datum(value))
   }
 
   pub fn set_prop(player: &mut DirPlayer, datum: &DatumRef, prop: &String, value: &DatumRef) -> Result<(), ScriptError> {
     let timeout_ref = player.get_datum(datum);
-    let _timeout_name = {
-      match timeout_ref {
-        Datum::TimeoutRef(timeout_name) => Ok(timeout_name.clone()),
-        _ => Err(ScriptError::new("Cannot set prop of non-timeout".to_string())),
-      }?
+    let timeout_name = match timeout_ref {
+      Datum::TimeoutRef(timeout_name) => Ok(timeout_name.clone()),
+      _ => Err(ScriptError::new("Cannot set prop of non-timeout".to_string())),
+    }?;
+    let value_datum = player.get_datum(value).clone();
+    let timeout = match player.timeout_manager.get_timeout_mut(&timeout_name) {
+      Some(timeout) => timeout,
+      None => return Err(ScriptError::new(format!("Cannot set {} of unscheduled timeout", prop))),
     };
-    let timeout = player.timeout_manager.get_timeout_mut(&_timeout_name);
     match prop.as_str() {
-      "target" => {
-        let new_target = value;
-        if let Some(timeout) = timeout {
-          timeout.target_ref = new_target.clone();
-        } else {
-          return Err(ScriptError::new("Cannot set target of unscheduled timeout".to_string()));
-        }
-        Ok(())
+      "target" => timeout.target_ref = value.clone(),
+      "period" => {
+        timeout.period = value_datum.int_value()?.max(0) as u32;
+        timeout.schedule();
       }
-      _ => {
-        Err(ScriptError::new(format!("Cannot set timeout property {}", prop)))
-      },
+      "persistent" => timeout.persistent = value_datum.bool_value()?,
+      "timeoutHandler" => timeout.handler = value_datum.string_value()?,
+      _ => return Err(ScriptError::new(format!("Cannot set timeout property {}", prop))),
     }
+    Ok(())
   }
 }
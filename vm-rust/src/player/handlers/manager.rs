@@ -1,6 +1,6 @@
 use log::warn;
 
-use crate::{director::lingo::datum::Datum, js_api::JsApi, player::{datum_formatting::format_concrete_datum, player_alloc_datum, player_call_script_handler, reserve_player_mut, reserve_player_ref, script_ref::ScriptInstanceRef, DatumRef, DirPlayer, ScriptError}};
+use crate::{director::lingo::datum::Datum, js_api::JsApi, utils, player::{datum_formatting::format_concrete_datum, player_alloc_datum, player_call_script_handler, reserve_player_mut, reserve_player_ref, script_ref::ScriptInstanceRef, DatumRef, DirPlayer, ScriptError}};
 
 use super::{cast::CastHandlers, datum_handlers::{player_call_datum_handler, script_instance::ScriptInstanceUtils}, movie::MovieHandlers, net::NetHandlers, string::StringHandlers, types::TypeHandlers};
 
@@ -74,7 +74,7 @@ impl BuiltInHandlerManager {
         return Err(ScriptError::new("random: max must be greater than or equal to 0".to_string()));
       }
       let max = max as f64;
-      let random = js_sys::Math::random() * max as f64;
+      let random = utils::random() * max;
       let random = random.floor() as i32;
       let random = random + min;
       Ok(player.alloc_datum(Datum::Int(random)))
@@ -158,6 +158,9 @@ impl BuiltInHandlerManager {
       "call" => true,
       "new" => true,
       "callAncestor" => true,
+      "updateStage" => true,
+      "value" => true,
+      "do" => true,
       _ => false,
     }
   }
@@ -167,6 +170,9 @@ impl BuiltInHandlerManager {
       "call" => Self::call(args).await,
       "new" => TypeHandlers::new(args).await,
       "callAncestor" => TypeHandlers::call_ancestor(args).await,
+      "updateStage" => MovieHandlers::update_stage(args).await,
+      "value" => TypeHandlers::value(args).await,
+      "do" => TypeHandlers::do_lingo(args).await,
       _ => {
         let msg = format!("No built-in async handler: {}", name);
         return Err(ScriptError::new(msg));
@@ -190,7 +196,6 @@ impl BuiltInHandlerManager {
       "floatp" => TypeHandlers::floatp(args),
       "offset" => StringHandlers::offset(args),
       "length" => StringHandlers::length(args),
-      "value" => TypeHandlers::value(args),
       "script" => MovieHandlers::script(args),
       "void" => TypeHandlers::void(args),
       "param" => Self::param(args),
@@ -211,9 +216,17 @@ impl BuiltInHandlerManager {
       "bitNot" => Self::bit_not(args),
       "symbol" => TypeHandlers::symbol(args),
       "go" => MovieHandlers::go(args),
+      "goLoop" | "goloop" => MovieHandlers::go_loop(args),
+      "goNext" | "gonext" => MovieHandlers::go_next(args),
+      "goPrevious" | "goprevious" => MovieHandlers::go_previous(args),
+      "play" => MovieHandlers::play(args),
+      "playDone" | "playdone" => MovieHandlers::play_done(args),
+      "label" => MovieHandlers::label(args),
+      "marker" => MovieHandlers::marker(args),
       "puppetSprite" => MovieHandlers::puppet_sprite(args),
       "clearGlobals" => Self::clear_globals(args),
       "sprite" => MovieHandlers::sprite(args),
+      "window" => MovieHandlers::window(args),
       "point" => TypeHandlers::point(args),
       "cursor" => TypeHandlers::cursor(args),
       "externalParamValue" => MovieHandlers::external_param_value(args),
@@ -221,6 +234,13 @@ impl BuiltInHandlerManager {
       "timeout" => TypeHandlers::timeout(args),
       "rect" => TypeHandlers::rect(args),
       "getStreamStatus" => NetHandlers::get_stream_status(args),
+      "postNetText" => NetHandlers::post_net_text(args),
+      "downloadNetThing" => NetHandlers::download_net_thing(args),
+      "netAbort" => NetHandlers::net_abort(args),
+      "netMIME" => NetHandlers::net_mime(args),
+      "netLastModDate" => NetHandlers::net_last_mod_date(args),
+      "getLatestNetID" => NetHandlers::get_latest_net_id(args),
+      "proxyServer" => NetHandlers::proxy_server(args),
       "netError" => NetHandlers::net_error(args),
       "netTextresult" => NetHandlers::net_text_result(args),
       "netTextResult" => NetHandlers::net_text_result(args),
@@ -241,7 +261,6 @@ impl BuiltInHandlerManager {
       "power" => TypeHandlers::power(args),
       "add" => TypeHandlers::add(args),
       "nothing" => TypeHandlers::nothing(args),
-      "updateStage" => MovieHandlers::update_stage(args),
       "getaProp" => TypeHandlers::get_a_prop(args),
       "min" => TypeHandlers::min(args),
       "max" => TypeHandlers::max(args),
@@ -249,7 +268,8 @@ impl BuiltInHandlerManager {
       "intersect" => TypeHandlers::intersect(args),
       "rollover" => MovieHandlers::rollover(args),
       "getPropAt" => TypeHandlers::get_prop_at(args),
-      "puppetSound" => Ok(DatumRef::Void), // TODO
+      "puppetSound" => MovieHandlers::puppet_sound(args),
+      "soundBusy" => MovieHandlers::sound_busy(args),
       "pi" => TypeHandlers::pi(args),
       "sin" => TypeHandlers::sin(args),
       "cos" => TypeHandlers::cos(args),
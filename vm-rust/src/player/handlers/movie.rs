@@ -1,4 +1,4 @@
-use crate::{director::lingo::datum::Datum, player::{cast_lib::INVALID_CAST_MEMBER_REF, datum_formatting::format_datum, reserve_player_mut, score::get_sprite_at, DatumRef, ScriptError}};
+use crate::{director::lingo::datum::{datum_bool, Datum}, player::{handlers::datum_handlers::sound::SoundDatumHandlers, cast_lib::INVALID_CAST_MEMBER_REF, datum_formatting::format_datum, events::player_prepare_frame, prefs, reserve_player_mut, score::get_sprite_at, DatumRef, DirPlayer, ScriptError}};
 
 pub struct MovieHandlers {}
 
@@ -10,6 +10,36 @@ impl MovieHandlers {
     })
   }
 
+  pub fn puppet_sound(args: &Vec<DatumRef>) -> Result<DatumRef, ScriptError> {
+    reserve_player_mut(|player| {
+      let (channel_num, member) = if args.len() > 1 {
+        (player.get_datum(&args[0]).int_value()? as u16, player.get_datum(&args[1]))
+      } else {
+        (1, player.get_datum(&args[0]))
+      };
+      let is_stop = match member {
+        Datum::Int(0) | Datum::Void => true,
+        Datum::CastMember(member_ref) => !member_ref.is_valid() || member_ref.cast_member == 0,
+        _ => false,
+      };
+      if is_stop {
+        player.sound_manager.get_channel_mut(channel_num)?.stop();
+      } else {
+        let entry = SoundDatumHandlers::sound_entry_for_member(player, member)?;
+        player.sound_manager.get_channel_mut(channel_num)?.play_now(entry);
+      }
+      Ok(DatumRef::Void)
+    })
+  }
+
+  pub fn sound_busy(args: &Vec<DatumRef>) -> Result<DatumRef, ScriptError> {
+    reserve_player_mut(|player| {
+      let channel_num = player.get_datum(&args[0]).int_value()? as u16;
+      let is_busy = player.sound_manager.get_channel(channel_num)?.is_busy();
+      Ok(player.alloc_datum(datum_bool(is_busy)))
+    })
+  }
+
   pub fn script(args: &Vec<DatumRef>) -> Result<DatumRef, ScriptError> {
     reserve_player_mut(|player| {
       let identifier = player.get_datum(&args[0]);
@@ -55,13 +85,100 @@ impl MovieHandlers {
     })
   }
 
+  fn resolve_frame(player: &DirPlayer, frame_ref: &DatumRef) -> Result<u32, ScriptError> {
+    match player.get_datum(frame_ref) {
+      Datum::String(label) => player.movie.score.get_label_frame(label)
+        .ok_or_else(|| ScriptError::new(format!("Frame label not found: {}", label))),
+      datum => Ok(datum.int_value()? as u32),
+    }
+  }
+
+  fn go_to_frame(player: &mut DirPlayer, frame: u32) {
+    if frame > 0 {
+      player.next_frame = Some(frame);
+    }
+  }
+
   pub fn go(args: &Vec<DatumRef>) -> Result<DatumRef, ScriptError> {
     reserve_player_mut(|player| {
-      player.next_frame = Some(player.get_datum(&args[0]).int_value()? as u32);
+      if args.len() > 1 {
+        return Err(ScriptError::new("go to movie is not supported".to_string()));
+      }
+      let frame = Self::resolve_frame(player, &args[0])?;
+      Self::go_to_frame(player, frame);
       Ok(DatumRef::Void)
     })
   }
 
+  pub fn go_loop(_: &Vec<DatumRef>) -> Result<DatumRef, ScriptError> {
+    reserve_player_mut(|player| {
+      let marker_frame = player.movie.score.get_marker_frame(player.movie.current_frame, 0);
+      Self::go_to_frame(player, marker_frame.max(1));
+      Ok(DatumRef::Void)
+    })
+  }
+
+  pub fn go_next(_: &Vec<DatumRef>) -> Result<DatumRef, ScriptError> {
+    reserve_player_mut(|player| {
+      let marker_frame = player.movie.score.get_marker_frame(player.movie.current_frame, 1);
+      Self::go_to_frame(player, marker_frame);
+      Ok(DatumRef::Void)
+    })
+  }
+
+  pub fn go_previous(_: &Vec<DatumRef>) -> Result<DatumRef, ScriptError> {
+    reserve_player_mut(|player| {
+      let marker_frame = player.movie.score.get_marker_frame(player.movie.current_frame, -1);
+      Self::go_to_frame(player, marker_frame);
+      Ok(DatumRef::Void)
+    })
+  }
+
+  pub fn play(args: &Vec<DatumRef>) -> Result<DatumRef, ScriptError> {
+    if args.is_empty() {
+      return Self::play_done(args);
+    }
+    reserve_player_mut(|player| {
+      if args.len() > 1 {
+        return Err(ScriptError::new("play movie is not supported".to_string()));
+      }
+      let frame = Self::resolve_frame(player, &args[0])?;
+      player.play_return_frames.push(player.movie.current_frame);
+      Self::go_to_frame(player, frame);
+      Ok(DatumRef::Void)
+    })
+  }
+
+  pub fn play_done(_: &Vec<DatumRef>) -> Result<DatumRef, ScriptError> {
+    reserve_player_mut(|player| {
+      if let Some(return_frame) = player.play_return_frames.pop() {
+        Self::go_to_frame(player, return_frame);
+      }
+      Ok(DatumRef::Void)
+    })
+  }
+
+  pub fn label(args: &Vec<DatumRef>) -> Result<DatumRef, ScriptError> {
+    reserve_player_mut(|player| {
+      let label = player.get_datum(&args[0]).string_value()?;
+      let frame = player.movie.score.get_label_frame(&label).unwrap_or(0);
+      Ok(player.alloc_datum(Datum::Int(frame as i32)))
+    })
+  }
+
+  pub fn marker(args: &Vec<DatumRef>) -> Result<DatumRef, ScriptError> {
+    reserve_player_mut(|player| {
+      let marker_frame = match player.get_datum(&args[0]) {
+        Datum::String(label) => player.movie.score.get_label_frame(label).unwrap_or(0),
+        datum => {
+          let offset = datum.int_value()?;
+          player.movie.score.get_marker_frame(player.movie.current_frame, offset)
+        }
+      };
+      Ok(player.alloc_datum(Datum::Int(marker_frame as i32)))
+    })
+  }
+
   pub fn puppet_sprite(args: &Vec<DatumRef>) -> Result<DatumRef, ScriptError> {
     reserve_player_mut(|player| {
       let sprite_number = player.get_datum(&args[0]).int_value()?;
@@ -79,6 +196,13 @@ impl MovieHandlers {
     })
   }
 
+  pub fn window(args: &[DatumRef]) -> Result<DatumRef, ScriptError> {
+    reserve_player_mut(|player| {
+      let name = player.get_datum(&args[0]).string_value()?;
+      Ok(player.alloc_datum(Datum::Window(name)))
+    })
+  }
+
   pub fn external_param_value(args: &Vec<DatumRef>) -> Result<DatumRef, ScriptError> {
     reserve_player_mut(|player| {
       let key = player.get_datum(&args[0]).string_value()?;
@@ -90,16 +214,35 @@ impl MovieHandlers {
   }
 
   pub fn stop_event(_: &Vec<DatumRef>) -> Result<DatumRef, ScriptError> {
-    // TODO stop event
-    Ok(DatumRef::Void)
+    reserve_player_mut(|player| {
+      player.is_event_stopped = true;
+      Ok(DatumRef::Void)
+    })
   }
 
-  pub fn get_pref(_: &Vec<DatumRef>) -> Result<DatumRef, ScriptError> {
-    Ok(DatumRef::Void)
+  pub fn get_pref(args: &Vec<DatumRef>) -> Result<DatumRef, ScriptError> {
+    reserve_player_mut(|player| {
+      if args.len() != 1 {
+        return Err(ScriptError::new("getPref requires 1 argument".to_string()));
+      }
+      let name = player.get_datum(&args[0]).string_value()?;
+      match prefs::get_pref(player, &name)? {
+        Some(value) => Ok(player.alloc_datum(Datum::String(value))),
+        None => Ok(DatumRef::Void),
+      }
+    })
   }
 
-  pub fn set_pref(_: &Vec<DatumRef>) -> Result<DatumRef, ScriptError> {
-    Ok(DatumRef::Void)
+  pub fn set_pref(args: &Vec<DatumRef>) -> Result<DatumRef, ScriptError> {
+    reserve_player_mut(|player| {
+      if args.len() != 2 {
+        return Err(ScriptError::new("setPref requires 2 arguments".to_string()));
+      }
+      let name = player.get_datum(&args[0]).string_value()?;
+      let value = player.get_datum(&args[1]).string_value()?;
+      prefs::set_pref(player, &name, &value)?;
+      Ok(DatumRef::Void)
+    })
   }
 
   pub fn go_to_net_page(_: &Vec<DatumRef>) -> Result<DatumRef, ScriptError> {
@@ -115,10 +258,11 @@ impl MovieHandlers {
     })
   }
 
-  pub fn update_stage(_: &Vec<DatumRef>) -> Result<DatumRef, ScriptError> {
-    // TODO: re-render
+  pub async fn update_stage(_: &Vec<DatumRef>) -> Result<DatumRef, ScriptError> {
     // The updateStage() method redraws sprites, performs transitions, plays sounds, sends a prepareFrame message
     // (affecting movie and behavior scripts), and sends a stepFrame message (which affects actorList)
+    // The stage itself is redrawn by the renderer on its next animation frame.
+    player_prepare_frame().await?;
     Ok(DatumRef::Void)
   }
 
@@ -129,3 +273,80 @@ impl MovieHandlers {
     })
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use crate::{director::chunks::frame_labels::FrameLabel, player::testing::with_test_player};
+
+  use super::*;
+
+  fn with_labeled_movie(f: impl FnOnce()) {
+    with_test_player(|_| {
+      reserve_player_mut(|player| {
+        player.movie.score.frame_labels = [(5, "intro"), (10, "menu"), (20, "game")]
+          .map(|(frame_num, label)| FrameLabel { frame_num, label: label.to_string() })
+          .to_vec();
+        player.movie.current_frame = 12;
+      });
+      f()
+    })
+  }
+
+  fn call(handler: fn(&Vec<DatumRef>) -> Result<DatumRef, ScriptError>, args: Vec<Datum>) -> Result<Datum, ScriptError> {
+    let args = reserve_player_mut(|player| args.into_iter().map(|x| player.alloc_datum(x)).collect());
+    let result = handler(&args)?;
+    Ok(reserve_player_mut(|player| player.get_datum(&result).clone()))
+  }
+
+  fn next_frame() -> Option<u32> {
+    reserve_player_mut(|player| player.next_frame.take())
+  }
+
+  #[test]
+  fn marker_counts_from_the_current_marker() {
+    with_labeled_movie(|| {
+      for (offset, frame) in [(0, 10), (1, 20), (2, 0), (-1, 5), (-2, 0)] {
+        assert!(matches!(call(MovieHandlers::marker, vec![Datum::Int(offset)]).unwrap(), Datum::Int(x) if x == frame));
+      }
+      assert!(matches!(call(MovieHandlers::marker, vec![Datum::String("GAME".to_string())]).unwrap(), Datum::Int(20)));
+      assert!(matches!(call(MovieHandlers::label, vec![Datum::String("menu".to_string())]).unwrap(), Datum::Int(10)));
+      assert!(matches!(call(MovieHandlers::label, vec![Datum::String("credits".to_string())]).unwrap(), Datum::Int(0)));
+    });
+  }
+
+  #[test]
+  fn go_reaches_frames_and_labels() {
+    with_labeled_movie(|| {
+      call(MovieHandlers::go, vec![Datum::Int(7)]).unwrap();
+      assert_eq!(next_frame(), Some(7));
+      call(MovieHandlers::go, vec![Datum::String("game".to_string())]).unwrap();
+      assert_eq!(next_frame(), Some(20));
+      assert!(call(MovieHandlers::go, vec![Datum::String("credits".to_string())]).is_err());
+      assert_eq!(next_frame(), None);
+
+      call(MovieHandlers::go_loop, vec![]).unwrap();
+      assert_eq!(next_frame(), Some(10));
+      call(MovieHandlers::go_next, vec![]).unwrap();
+      assert_eq!(next_frame(), Some(20));
+      call(MovieHandlers::go_previous, vec![]).unwrap();
+      assert_eq!(next_frame(), Some(5));
+    });
+  }
+
+  #[test]
+  fn window_refers_to_a_window_by_name() {
+    with_test_player(|_| {
+      let window = call(|args| MovieHandlers::window(args), vec![Datum::String("Help".to_string())]).unwrap();
+      assert!(matches!(window, Datum::Window(name) if name == "Help"));
+    });
+  }
+
+  #[test]
+  fn go_to_another_movie_fails() {
+    with_labeled_movie(|| {
+      let args = vec![Datum::Int(1), Datum::String("other.dir".to_string())];
+      assert!(call(MovieHandlers::go, args).is_err());
+      assert_eq!(next_frame(), None);
+    });
+  }
+}
@@ -1,8 +1,55 @@
-use crate::{director::lingo::datum::{datum_bool, Datum}, player::{reserve_player_mut, DatumRef, ScriptError}};
+use url::form_urlencoded::byte_serialize;
+
+use crate::{director::lingo::datum::{datum_bool, Datum}, player::{datum_formatting::format_concrete_datum, file_sandbox::resolve_sandbox_path, net_task::NetTaskState, reserve_player_mut, DatumRef, DirPlayer, ScriptError}};
 
 
 pub struct NetHandlers { }
 
+/// Reads the optional task argument of a net function, which can be a net id or the URL of a task.
+/// Returns `None` for the most recent task.
+fn get_task_id_arg(player: &DirPlayer, args: &[DatumRef]) -> Result<Option<u32>, ScriptError> {
+  match args.first().map(|x| player.get_datum(x)) {
+    Some(Datum::String(url)) => player.net_manager.find_task_id_by_url(url)
+      .map(Some)
+      .ok_or_else(|| ScriptError::new(format!("No net operation for URL {}", url))),
+    Some(datum) => Ok(Some(datum.int_value()? as u32)),
+    None => Ok(None),
+  }
+}
+
+/// Converts a Lingo string for the server, which expects its own line endings and character set.
+fn encode_post_text(text: &str, server_os: &str, character_set: &str) -> Vec<u8> {
+  let line_ending = match server_os.to_uppercase().as_str() {
+    "WIN" | "DOS" => "\r\n",
+    "MAC" => "\r",
+    _ => "\n",
+  };
+  let text = text.replace("\r\n", "\r").replace('\n', "\r").replace('\r', line_ending);
+  match character_set.to_uppercase().as_str() {
+    "ISO-8859-1" | "LATIN1" | "ASCII" => text.chars().map(|c| if (c as u32) < 256 { c as u8 } else { b'?' }).collect(),
+    _ => text.into_bytes(),
+  }
+}
+
+/// Form encodes the data of a `postNetText`. A property list becomes `name=value` pairs, a string is sent as it is.
+fn encode_post_data(player: &DirPlayer, data: &Datum, server_os: &str, character_set: &str) -> Result<Vec<u8>, ScriptError> {
+  let to_text = |datum: &Datum| datum.string_value().unwrap_or_else(|_| format_concrete_datum(datum, player));
+  match data {
+    Datum::PropList(pairs, ..) => {
+      let fields = pairs
+        .iter()
+        .map(|(key, value)| {
+          let key = encode_post_text(&to_text(player.get_datum(key)), server_os, character_set);
+          let value = encode_post_text(&to_text(player.get_datum(value)), server_os, character_set);
+          format!("{}={}", byte_serialize(&key).collect::<String>(), byte_serialize(&value).collect::<String>())
+        })
+        .collect::<Vec<_>>();
+      Ok(fields.join("&").into_bytes())
+    }
+    _ => Ok(encode_post_text(&data.string_value()?, server_os, character_set)),
+  }
+}
+
 impl NetHandlers {
   pub fn net_done(args: &Vec<DatumRef>) -> Result<DatumRef, ScriptError> {
     reserve_player_mut(|player| {
@@ -35,28 +82,113 @@ impl NetHandlers {
     })
   }
 
+  /// Starts a POST request: `postNetText(url, propListOrString, serverOSString, characterSet)`.
+  pub fn post_net_text(args: &Vec<DatumRef>) -> Result<DatumRef, ScriptError> {
+    reserve_player_mut(|player| {
+      let url = player.get_datum(&args[0]).string_value()?;
+      let server_os = args.get(2).map(|x| player.get_datum(x).string_value()).transpose()?.unwrap_or("UNIX".to_owned());
+      let character_set = args.get(3).map(|x| player.get_datum(x).string_value()).transpose()?.unwrap_or("UTF-8".to_owned());
+      let post_data = match args.get(1) {
+        Some(data) => encode_post_data(player, player.get_datum(data), &server_os, &character_set)?,
+        None => vec![],
+      };
+      let task_id = player.net_manager.post_net_text(url, post_data);
+      Ok(player.alloc_datum(Datum::Int(task_id as i32)))
+    })
+  }
+
+  pub fn download_net_thing(args: &Vec<DatumRef>) -> Result<DatumRef, ScriptError> {
+    reserve_player_mut(|player| {
+      let url = player.get_datum(&args[0]).string_value()?;
+      let local_path = player.get_datum(&args[1]).string_value()?;
+      let storage_path = resolve_sandbox_path(player, &local_path)
+        .ok_or_else(|| ScriptError::new(format!("Invalid download path {}", local_path)))?;
+      player.net_manager.download_net_thing(url, storage_path);
+      Ok(DatumRef::Void)
+    })
+  }
+
+  pub fn net_abort(args: &Vec<DatumRef>) -> Result<DatumRef, ScriptError> {
+    reserve_player_mut(|player| {
+      if let Some(task_id) = get_task_id_arg(player, args)? {
+        player.net_manager.abort_task(task_id);
+      }
+      Ok(DatumRef::Void)
+    })
+  }
+
+  pub fn get_latest_net_id(_: &Vec<DatumRef>) -> Result<DatumRef, ScriptError> {
+    reserve_player_mut(|player| {
+      let task_id = player.net_manager.get_latest_task_id();
+      Ok(player.alloc_datum(Datum::Int(task_id as i32)))
+    })
+  }
+
+  pub fn net_mime(args: &Vec<DatumRef>) -> Result<DatumRef, ScriptError> {
+    reserve_player_mut(|player| {
+      let task_id = get_task_id_arg(player, args)?;
+      let mime_type = player.net_manager.get_task_state(task_id).and_then(|x| x.mime_type);
+      Ok(player.alloc_datum(Datum::String(mime_type.unwrap_or_default())))
+    })
+  }
+
+  pub fn net_last_mod_date(args: &Vec<DatumRef>) -> Result<DatumRef, ScriptError> {
+    reserve_player_mut(|player| {
+      let task_id = get_task_id_arg(player, args)?;
+      let last_modified = player.net_manager.get_task_state(task_id).and_then(|x| x.last_modified);
+      Ok(player.alloc_datum(Datum::String(last_modified.unwrap_or_default())))
+    })
+  }
+
+  /// Sets the proxy with `proxyServer #http, "host", port` or clears it with `proxyServer #http, #stop`.
+  /// With only the server type, returns the current proxy as "host:port".
+  pub fn proxy_server(args: &Vec<DatumRef>) -> Result<DatumRef, ScriptError> {
+    reserve_player_mut(|player| {
+      let scheme = player.get_datum(&args[0]).string_value()?.to_lowercase();
+      match args.get(1).map(|x| player.get_datum(x)) {
+        None => {
+          let proxy = player.net_manager.proxies.get(&scheme).map(|(host, port)| format!("{}:{}", host, port));
+          Ok(proxy.map_or(DatumRef::Void, |proxy| player.alloc_datum(Datum::String(proxy))))
+        }
+        Some(Datum::Symbol(symbol)) if symbol.eq_ignore_ascii_case("stop") => {
+          player.net_manager.proxies.remove(&scheme);
+          Ok(DatumRef::Void)
+        }
+        Some(host) => {
+          let host = host.string_value()?;
+          let port = args.get(2).map(|x| player.get_datum(x).int_value()).transpose()?.unwrap_or(80);
+          if !(1..=65535).contains(&port) {
+            return Err(ScriptError::new(format!("Invalid proxy port {}", port)));
+          }
+          player.net_manager.proxies.insert(scheme, (host, port as u16));
+          Ok(DatumRef::Void)
+        }
+      }
+    })
+  }
+
   pub fn get_stream_status(args: &Vec<DatumRef>) -> Result<DatumRef, ScriptError> {
     reserve_player_mut(|player| {
-      let (state, error, url, is_ok) = {
-        let task_id = player.get_datum(&args[0]).int_value()? as u32;
-        let task = player.net_manager.get_task(task_id).unwrap();
-        let task_state = &player.net_manager.get_task_state(Some(task_id)).unwrap();
-        let (state, error) = if task_state.is_done() && task_state.result.as_ref().unwrap().is_ok() {
-          ("Complete", "OK")
-        } else if task_state.is_done() && task_state.result.as_ref().unwrap().is_err() {
-          ("Complete", "Task failed")
-        } else {
-          ("InProgress", "")
-        };
-        let is_ok = task_state.is_done() && task_state.result.as_ref().unwrap().is_ok();
-        (state.to_owned(), error.to_owned(), task.url.to_owned(), is_ok)
+      let task_id = get_task_id_arg(player, args)?;
+      let task = task_id.and_then(|task_id| player.net_manager.get_task(task_id));
+      let url = task.map_or(String::new(), |x| x.url.to_owned());
+      let task_state = task.and_then(|task| player.net_manager.get_task_state(Some(task.id)));
+      let (state, error) = match &task_state {
+        None => ("NoInformation", Datum::String("".to_owned())),
+        Some(NetTaskState { result: Some(Ok(_)), .. }) => ("Complete", Datum::String("OK".to_owned())),
+        Some(NetTaskState { result: Some(Err(code)), .. }) => ("Error", Datum::Int(*code)),
+        Some(state) if state.bytes_so_far > 0 => ("InProgress", Datum::String("".to_owned())),
+        Some(state) if state.bytes_total.is_some() => ("Started", Datum::String("".to_owned())),
+        Some(_) => ("Connecting", Datum::String("".to_owned())),
       };
+      let bytes_so_far = task_state.as_ref().map_or(0, |x| x.bytes_so_far);
+      let bytes_total = task_state.as_ref().and_then(|x| x.bytes_total).unwrap_or(0);
       let result_map = Datum::PropList(vec![
         (player.alloc_datum(Datum::String("URL".to_owned())), player.alloc_datum(Datum::String(url))),
-        (player.alloc_datum(Datum::String("state".to_owned())), player.alloc_datum(Datum::String(state))),
-        (player.alloc_datum(Datum::String("bytesSoFar".to_owned())), player.alloc_datum(Datum::Int(if is_ok { 100 } else { 0 }))),
-        (player.alloc_datum(Datum::String("bytesTotal".to_owned())), player.alloc_datum(Datum::Int(100))),
-        (player.alloc_datum(Datum::String("error".to_owned())), player.alloc_datum(Datum::String(error))),
+        (player.alloc_datum(Datum::String("state".to_owned())), player.alloc_datum(Datum::String(state.to_owned()))),
+        (player.alloc_datum(Datum::String("bytesSoFar".to_owned())), player.alloc_datum(Datum::Int(bytes_so_far as i32))),
+        (player.alloc_datum(Datum::String("bytesTotal".to_owned())), player.alloc_datum(Datum::Int(bytes_total as i32))),
+        (player.alloc_datum(Datum::String("error".to_owned())), player.alloc_datum(error)),
       ], false);
       Ok(player.alloc_datum(result_map))
     })
@@ -93,3 +225,247 @@ impl NetHandlers {
     })
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use async_std::task::{block_on, yield_now};
+
+  use crate::{
+    platform::{file_storage::{FileStorage, FileStorageError}, in_process_net::InProcessNetLoader, NetMethod, NetResponse},
+    player::{
+      net_task::{net_error_for_http_status, NET_ERROR_ABORTED, NET_ERROR_CONNECTION_FAILED, NET_ERROR_INVALID_URL, NET_ERROR_NOT_FOUND},
+      reserve_player_ref,
+      testing::with_test_player_net,
+    },
+  };
+
+  use super::*;
+
+  /// A fake server answering with the body "hello", or the error a browser fetch would give.
+  /// `/dated` answers as an HTML page with a modification date.
+  fn test_server() -> InProcessNetLoader {
+    InProcessNetLoader::new(Box::new(|request| {
+      if !["http", "https"].contains(&request.url.scheme()) {
+        return Err(NET_ERROR_INVALID_URL);
+      }
+      match (request.url.host_str(), request.url.path()) {
+        (Some("offline.test"), _) => Err(NET_ERROR_CONNECTION_FAILED),
+        (_, "/missing") => Err(net_error_for_http_status(404)),
+        (_, "/gone") => Err(net_error_for_http_status(410)),
+        (_, "/dated") => Ok(NetResponse {
+          data: b"<p>hello</p>".to_vec(),
+          mime_type: Some("text/html".to_owned()),
+          last_modified: Some("Tue, 15 Nov 1994 08:12:31 GMT".to_owned()),
+        }),
+        _ => Ok(NetResponse { data: b"hello".to_vec(), mime_type: Some("text/plain".to_owned()), last_modified: None }),
+      }
+    }))
+  }
+
+  fn call(handler: fn(&Vec<DatumRef>) -> Result<DatumRef, ScriptError>, args: Vec<Datum>) -> DatumRef {
+    let args = reserve_player_mut(|player| args.into_iter().map(|x| player.alloc_datum(x)).collect());
+    handler(&args).unwrap()
+  }
+
+  fn get(datum_ref: &DatumRef) -> Datum {
+    reserve_player_mut(|player| player.get_datum(datum_ref).clone())
+  }
+
+  fn string(value: &str) -> Datum {
+    Datum::String(value.to_owned())
+  }
+
+  /// Lets the spawned net tasks run.
+  fn run_net_tasks() {
+    block_on(async {
+      for _ in 0..10 {
+        yield_now().await;
+      }
+    });
+  }
+
+  fn net_error(task_id: &DatumRef) -> Datum {
+    get(&call(NetHandlers::net_error, vec![get(task_id)]))
+  }
+
+  fn stream_status(task_id: &DatumRef) -> Vec<(String, String)> {
+    let status = call(NetHandlers::get_stream_status, vec![get(task_id)]);
+    reserve_player_mut(|player| {
+      let Datum::PropList(pairs, ..) = player.get_datum(&status) else { panic!("getStreamStatus returned no list") };
+      pairs
+        .iter()
+        .map(|(key, value)| (player.get_datum(key).string_value().unwrap(), format_concrete_datum(player.get_datum(value), player)))
+        .collect()
+    })
+  }
+
+  fn status_prop(status: &[(String, String)], key: &str) -> String {
+    status.iter().find(|x| x.0 == key).unwrap().1.to_owned()
+  }
+
+  #[test]
+  fn post_net_text_form_encodes_prop_lists() {
+    let server = test_server();
+    with_test_player_net(server.clone(), |_| {
+      let form = reserve_player_mut(|player| {
+        let pairs = vec![
+          (player.alloc_datum(string("name")), player.alloc_datum(string("a b&c"))),
+          (player.alloc_datum(string("text")), player.alloc_datum(string("one\rtwo"))),
+          (player.alloc_datum(string("count")), player.alloc_datum(Datum::Int(3))),
+        ];
+        Datum::PropList(pairs, false)
+      });
+      let task_id = call(NetHandlers::post_net_text, vec![string("http://example.test/post"), form, string("WIN")]);
+      run_net_tasks();
+      assert!(matches!(get(&call(NetHandlers::net_text_result, vec![get(&task_id)])), Datum::String(text) if text == "hello"));
+
+      let requests = server.requests();
+      assert_eq!(requests.len(), 1);
+      assert!(matches!(requests[0].method, NetMethod::Post));
+      assert_eq!(requests[0].content_type.as_deref(), Some("application/x-www-form-urlencoded"));
+      assert_eq!(requests[0].body.as_deref(), Some(&b"name=a+b%26c&text=one%0D%0Atwo&count=3"[..]));
+    });
+  }
+
+  #[test]
+  fn post_net_text_converts_line_endings_and_character_set() {
+    let server = test_server();
+    with_test_player_net(server.clone(), |_| {
+      call(NetHandlers::post_net_text, vec![string("http://example.test/post"), string("a\rb\u{e9}"), string("UNIX"), string("ISO-8859-1")]);
+      call(NetHandlers::post_net_text, vec![string("http://example.test/post"), string("a\r\nb\u{e9}"), string("MAC")]);
+      call(NetHandlers::post_net_text, vec![string("http://example.test/post"), string("a\u{263a}"), string("UNIX"), string("ASCII")]);
+      run_net_tasks();
+
+      let bodies = server.requests().into_iter().map(|x| x.body.unwrap()).collect::<Vec<_>>();
+      assert_eq!(bodies, vec![b"a\nb\xe9".to_vec(), "a\rb\u{e9}".as_bytes().to_vec(), b"a?".to_vec()]);
+    });
+  }
+
+  #[test]
+  fn proxy_server_sets_and_clears_the_proxy() {
+    with_test_player_net(test_server(), |_| {
+      call(NetHandlers::proxy_server, vec![Datum::Symbol("http".to_owned()), string("proxy.test"), Datum::Int(8080)]);
+      assert!(matches!(get(&call(NetHandlers::proxy_server, vec![Datum::Symbol("http".to_owned())])), Datum::String(proxy) if proxy == "proxy.test:8080"));
+
+      for port in [0, -1, 65536, 73000] {
+        let args = reserve_player_mut(|player| {
+          vec![player.alloc_datum(Datum::Symbol("http".to_owned())), player.alloc_datum(string("other.test")), player.alloc_datum(Datum::Int(port))]
+        });
+        assert!(matches!(NetHandlers::proxy_server(&args), Err(err) if err.message == format!("Invalid proxy port {}", port)));
+      }
+      assert_eq!(reserve_player_mut(|player| player.net_manager.proxies.get("http").cloned()), Some(("proxy.test".to_owned(), 8080)));
+
+      call(NetHandlers::proxy_server, vec![Datum::Symbol("http".to_owned()), Datum::Symbol("stop".to_owned())]);
+      assert!(matches!(get(&call(NetHandlers::proxy_server, vec![Datum::Symbol("http".to_owned())])), Datum::Void));
+    });
+  }
+
+  #[test]
+  fn stream_status_follows_progress() {
+    with_test_player_net(test_server(), |_| {
+      let task_id = call(NetHandlers::get_net_text, vec![string("http://example.test/text")]);
+      let status = stream_status(&task_id);
+      assert_eq!(status_prop(&status, "URL"), "\"http://example.test/text\"");
+      assert_eq!(status_prop(&status, "state"), "\"Connecting\"");
+      assert_eq!(status_prop(&status, "bytesSoFar"), "0");
+
+      run_net_tasks();
+      let status = stream_status(&task_id);
+      assert_eq!(status_prop(&status, "state"), "\"Complete\"");
+      assert_eq!(status_prop(&status, "bytesSoFar"), "5");
+      assert_eq!(status_prop(&status, "bytesTotal"), "5");
+      assert_eq!(status_prop(&status, "error"), "\"OK\"");
+    });
+  }
+
+  #[test]
+  fn net_abort_finishes_with_4242() {
+    with_test_player_net(test_server(), |_| {
+      let task_id = call(NetHandlers::get_net_text, vec![string("http://example.test/text")]);
+      call(NetHandlers::net_abort, vec![get(&task_id)]);
+      assert!(matches!(get(&call(NetHandlers::net_done, vec![get(&task_id)])), Datum::Int(1)));
+
+      // The response that arrives afterwards is dropped
+      run_net_tasks();
+      assert!(matches!(net_error(&task_id), Datum::Int(NET_ERROR_ABORTED)));
+      assert_eq!(status_prop(&stream_status(&task_id), "state"), "\"Error\"");
+      assert!(matches!(get(&call(NetHandlers::net_text_result, vec![get(&task_id)])), Datum::String(text) if text.is_empty()));
+    });
+  }
+
+  #[test]
+  fn failed_requests_set_net_error() {
+    with_test_player_net(test_server(), |_| {
+      let tasks = [
+        ("http://offline.test/text", NET_ERROR_CONNECTION_FAILED),
+        ("ftp://example.test/text", NET_ERROR_INVALID_URL),
+        ("http://example.test/missing", NET_ERROR_NOT_FOUND),
+        ("http://example.test/gone", NET_ERROR_NOT_FOUND),
+      ]
+      .map(|(url, code)| (call(NetHandlers::get_net_text, vec![string(url)]), code));
+      run_net_tasks();
+      for (task_id, code) in &tasks {
+        assert!(matches!(net_error(task_id), Datum::Int(error) if error == *code));
+      }
+    });
+  }
+
+  #[test]
+  fn download_net_thing_stores_the_data_in_the_movie_sandbox() {
+    with_test_player_net(test_server(), |platform| {
+      reserve_player_mut(|player| player.movie.base_path = "http://example.test/games/".to_owned());
+      call(NetHandlers::download_net_thing, vec![string("http://example.test/text"), string("saves/hello.txt")]);
+      let task_id = call(NetHandlers::get_latest_net_id, vec![]);
+      call(NetHandlers::download_net_thing, vec![string("http://example.test/missing"), string("missing.txt")]);
+      let missing_task_id = call(NetHandlers::get_latest_net_id, vec![]);
+      run_net_tasks();
+
+      assert!(matches!(net_error(&task_id), Datum::String(text) if text == "OK"));
+      assert_eq!(platform.files.read("example.test/games/saves/hello.txt").unwrap(), b"hello");
+      assert!(matches!(net_error(&missing_task_id), Datum::Int(NET_ERROR_NOT_FOUND)));
+      assert_eq!(platform.files.read("example.test/games/missing.txt"), Err(FileStorageError::NotFound));
+
+      let args = reserve_player_mut(|player| vec![player.alloc_datum(string("http://example.test/text")), player.alloc_datum(string(".."))]);
+      assert!(matches!(NetHandlers::download_net_thing(&args), Err(err) if err.message == "Invalid download path .."));
+    });
+  }
+
+  #[test]
+  fn net_mime_and_last_mod_date_describe_the_response() {
+    with_test_player_net(test_server(), |_| {
+      let mime = |args: Vec<Datum>| get(&call(NetHandlers::net_mime, args)).string_value().unwrap();
+      let last_mod_date = |args: Vec<Datum>| get(&call(NetHandlers::net_last_mod_date, args)).string_value().unwrap();
+
+      let text_task_id = call(NetHandlers::get_net_text, vec![string("http://example.test/text")]);
+      let dated_task_id = call(NetHandlers::get_net_text, vec![string("http://example.test/dated")]);
+      // Nothing is known before the response arrives
+      assert_eq!(mime(vec![get(&dated_task_id)]), "");
+      assert_eq!(last_mod_date(vec![get(&dated_task_id)]), "");
+
+      run_net_tasks();
+      assert_eq!(mime(vec![get(&dated_task_id)]), "text/html");
+      assert_eq!(last_mod_date(vec![get(&dated_task_id)]), "Tue, 15 Nov 1994 08:12:31 GMT");
+      // Tasks can also be given by URL, and default to the latest one
+      assert_eq!(mime(vec![string("http://example.test/text")]), "text/plain");
+      assert_eq!(last_mod_date(vec![get(&text_task_id)]), "");
+      assert_eq!(mime(vec![]), "text/html");
+    });
+  }
+
+  #[test]
+  fn keeps_progress_reported_while_the_net_state_is_locked() {
+    with_test_player_net(test_server(), |_| {
+      let task_id = call(NetHandlers::get_net_text, vec![string("http://example.test/text")]);
+      let shared_state = reserve_player_ref(|player| player.net_manager.shared_state.clone());
+      let guard = shared_state.try_lock().unwrap();
+      run_net_tasks();
+      drop(guard);
+      run_net_tasks();
+
+      let status = stream_status(&task_id);
+      assert_eq!(status_prop(&status, "state"), "\"Complete\"");
+      assert_eq!(status_prop(&status, "bytesSoFar"), "5");
+      assert_eq!(status_prop(&status, "bytesTotal"), "5");
+    });
+  }
+}
@@ -1,6 +1,6 @@
 use itertools::Itertools;
 
-use crate::{director::lingo::datum::{datum_bool, Datum, DatumType}, player::{allocator::ScriptInstanceAllocatorTrait, bitmap::bitmap::{get_system_default_palette, Bitmap, BuiltInPalette, PaletteRef}, compare::sort_datums, datum_formatting::format_datum, eval::eval_lingo, geometry::IntRect, reserve_player_mut, reserve_player_ref, sprite::{ColorRef, CursorRef}, xtra::manager::{create_xtra_instance, is_xtra_registered}, DatumRef, DirPlayer, ScriptError}};
+use crate::{director::lingo::datum::{datum_bool, Datum, DatumType}, player::{allocator::ScriptInstanceAllocatorTrait, bitmap::bitmap::{get_system_default_palette, Bitmap, BuiltInPalette, PaletteRef}, compare::sort_datums, datum_formatting::format_datum, eval::{eval_lingo, eval_lingo_command}, geometry::IntRect, reserve_player_mut, reserve_player_ref, sprite::{ColorRef, CursorRef}, xtra::manager::{create_xtra_instance, get_registered_xtra_name, get_registered_xtra_names}, DatumRef, DirPlayer, ScriptError}};
 
 use super::datum_handlers::{list_handlers::ListDatumHandlers, player_call_datum_handler, prop_list::{PropListDatumHandlers, PropListUtils}, rect::RectUtils};
 
@@ -26,6 +26,7 @@ impl TypeUtils {
       Datum::IntRect(..) => Ok(vec!["rect"]),
       Datum::IntPoint(..) => Ok(vec!["point"]),
       Datum::SpriteRef(..) => Ok(vec!["sprite"]),
+      Datum::Window(..) => Ok(vec!["window"]),
       Datum::PaletteRef(..) => Ok(vec!["palette"]),
       _ => Err(ScriptError::new(format!("Getting ilk for unknown type: {}", datum.type_str())))?,
     }
@@ -183,14 +184,23 @@ impl TypeHandlers {
     })
   }
 
-  pub fn value(args: &Vec<DatumRef>) -> Result<DatumRef, ScriptError> {
-    reserve_player_mut(|player| {
-      let expr = player.get_datum(&args[0]);
-      match expr {
-        Datum::String(s) => eval_lingo(s.to_owned(), player),
-        _ => Ok(args[0].clone()),
+  pub async fn value(args: &Vec<DatumRef>) -> Result<DatumRef, ScriptError> {
+    let expr = reserve_player_ref(|player| {
+      match player.get_datum(&args[0]) {
+        Datum::String(s) => Some(s.to_owned()),
+        _ => None,
       }
-    })
+    });
+    match expr {
+      Some(expr) => eval_lingo(expr).await,
+      None => Ok(args[0].clone()),
+    }
+  }
+
+  pub async fn do_lingo(args: &Vec<DatumRef>) -> Result<DatumRef, ScriptError> {
+    let source = reserve_player_ref(|player| player.get_datum(&args[0]).string_value())?;
+    eval_lingo_command(source).await?;
+    Ok(DatumRef::Void)
   }
 
   pub fn void(_: &Vec<DatumRef>) -> Result<DatumRef, ScriptError> {
@@ -418,7 +428,7 @@ impl TypeHandlers {
         let xtra_name = reserve_player_ref(|player| {
           player.get_datum(&args[0]).to_xtra_name().unwrap().to_owned()
         });
-        let result_id = create_xtra_instance(&xtra_name, args)?;
+        let result_id = create_xtra_instance(&xtra_name, &args[1..].to_vec())?;
         reserve_player_mut(|player| {
           Ok(player.alloc_datum(Datum::XtraInstance(xtra_name, result_id)))
         })
@@ -506,13 +516,17 @@ impl TypeHandlers {
     })
   }
 
+  /// Looks up an Xtra by name, or by its position in `the xtraList`.
   pub fn xtra(args: &Vec<DatumRef>) -> Result<DatumRef, ScriptError> {
     reserve_player_mut(|player| {
-      let xtra_name = player.get_datum(&args[0]).string_value()?;
-      if is_xtra_registered(&xtra_name) {
-        Ok(player.alloc_datum(Datum::Xtra(xtra_name)))
-      } else {
-        Err(ScriptError::new(format!("Xtra {} is not registered", xtra_name)))
+      let registered_name = match player.get_datum(&args[0]) {
+        Datum::Int(number) if *number >= 1 => get_registered_xtra_names().get(*number as usize - 1).cloned(),
+        Datum::Int(_) => None,
+        datum => get_registered_xtra_name(&datum.string_value()?),
+      };
+      match registered_name {
+        Some(xtra_name) => Ok(player.alloc_datum(Datum::Xtra(xtra_name))),
+        None => Err(ScriptError::new(format!("Xtra {} is not registered", format_datum(&args[0], player)))),
       }
     })
   }
@@ -1,3 +1,5 @@
+use crate::platform::platform;
+
 use super::{cast_member::CastMemberType, events::player_dispatch_targeted_event, player_is_playing, reserve_player_mut, DatumRef, DirPlayer, ScriptError};
 
 fn get_next_focus_sprite_id(player: &DirPlayer, after: i16) -> i16 {
@@ -27,6 +29,7 @@ pub async fn player_key_down(key: String, code: u16) -> Result<DatumRef, ScriptE
     }
     let instance_ids = reserve_player_mut(|player| {
         player.keyboard_manager.key_down(key.clone(), code);
+        player.last_event_time = platform().clock.now_millis();
         if player.keyboard_focus_sprite != -1 {
             let sprite_id = player.keyboard_focus_sprite as usize;
             let sprite = player.movie.score.get_sprite(sprite_id as i16);
@@ -28,17 +28,23 @@ pub mod events;
 pub mod keyboard;
 pub mod keyboard_map;
 pub mod keyboard_events;
+pub mod mouse_events;
+pub mod file_sandbox;
+pub mod prefs;
 pub mod allocator;
 pub mod datum_ref;
 pub mod script_ref;
+pub mod sound;
+#[cfg(test)]
+pub mod testing;
 
-use std::{collections::HashMap, sync::Arc, time::Duration};
+use std::{collections::HashMap, rc::Rc, sync::Arc};
 
 use allocator::{DatumAllocator, DatumAllocatorTrait, ResetableAllocator};
 use datum_ref::DatumRef;
-use async_std::{channel::{self, Receiver, Sender}, future::{self, timeout}, sync::Mutex, task::spawn_local};
+use sound::{web_audio::WebAudioSoundBackend, NullSoundBackend, SoundBackend, SoundManager};
+use async_std::{channel::{self, Receiver, Sender}, sync::Mutex, task::spawn_local};
 use cast_manager::CastPreloadReason;
-use chrono::Local;
 use fxhash::FxHashMap;
 use log::warn;
 use manual_future::{ManualFutureCompleter, ManualFuture};
@@ -47,11 +53,11 @@ use lazy_static::lazy_static;
 use profiling::{end_profiling, start_profiling};
 use scope::ScopeResult;
 use script_ref::ScriptInstanceRef;
-use xtra::multiuser::{MultiuserXtraManager, MULTIUSER_XTRA_MANAGER_OPT};
+use xtra::{manager::{flush_xtras, get_registered_xtra_names}, multiuser::{MultiuserXtraManager, MULTIUSER_XTRA_MANAGER_OPT}};
 
-use crate::{console_warn, director::{chunks::handler::{Bytecode, HandlerDef}, enums::ScriptType, file::{read_director_file_bytes, DirectorFile}, lingo::{constants::{get_anim2_prop_name, get_anim_prop_name}, datum::{datum_bool, Datum, DatumType, VarRef}}}, js_api::JsApi, player::{bytecode::handler_manager::{player_execute_bytecode, BytecodeHandlerContext}, datum_formatting::format_datum, geometry::IntRect, profiling::get_profiler_report, scope::Scope}, utils::{get_base_url, get_basename_no_extension, get_elapsed_ticks, get_ticks}};
+use crate::{console_warn, director::{chunks::handler::{Bytecode, HandlerDef}, enums::ScriptType, file::{read_director_file_bytes, DirectorFile}, lingo::{constants::{get_anim2_prop_name, get_anim_prop_name}, datum::{datum_bool, Datum, DatumType, VarRef}}}, js_api::JsApi, platform::{platform, set_platform, web::web_platform, Platform}, player::{bytecode::handler_manager::{player_execute_bytecode, BytecodeHandlerContext}, datum_formatting::format_datum, geometry::IntRect, profiling::get_profiler_report, scope::Scope}, utils::{get_base_url, get_basename_no_extension, get_elapsed_ticks, get_local_time, get_ticks}};
 
-use self::{bytecode::handler_manager::StaticBytecodeHandlerManager, cast_lib::CastMemberRef, cast_manager::CastManager, commands::{run_command_loop, PlayerVMCommand}, debug::{Breakpoint, BreakpointContext, BreakpointManager}, events::{player_dispatch_global_event, player_invoke_global_event, player_unwrap_result, player_wait_available, run_event_loop, PlayerVMEvent}, font::{player_load_system_font, FontManager}, handlers::manager::BuiltInHandlerManager, keyboard::KeyboardManager, movie::Movie, net_manager::NetManagerSharedState, scope::ScopeRef, score::{get_sprite_at, Score}, script::{Script, ScriptHandlerRef, ScriptInstance, ScriptInstanceId}, sprite::{ColorRef, CursorRef}, timeout::TimeoutManager};
+use self::{bytecode::handler_manager::StaticBytecodeHandlerManager, cast_lib::CastMemberRef, cast_manager::CastManager, commands::{run_command_loop, PlayerVMCommand}, debug::{Breakpoint, BreakpointContext, BreakpointManager}, events::{player_dispatch_global_event, player_invoke_frame_event, player_invoke_global_event, player_invoke_static_event, player_prepare_frame, player_unwrap_result, player_wait_available, run_event_loop, PlayerVMEvent}, font::{player_load_system_font, FontManager}, handlers::manager::BuiltInHandlerManager, keyboard::KeyboardManager, mouse_events::{player_update_rollover, ticks_since}, movie::Movie, net_manager::NetManagerSharedState, scope::ScopeRef, score::{advance_film_loops, get_sprite_at, player_end_score_sprites, player_update_score_sprites, Score}, script::{Script, ScriptHandlerRef, ScriptInstance, ScriptInstanceId}, sprite::{ColorRef, CursorRef}, timeout::{player_sleep_until, TimeoutManager}};
 
 pub enum HandlerExecutionResult {
   Advance,
@@ -77,6 +83,7 @@ pub struct DirPlayer {
   pub is_playing: bool,
   pub is_script_paused: bool,
   pub next_frame: Option<u32>,
+  pub play_return_frames: Vec<u32>,
   pub queue_tx: Sender<PlayerVMExecutionItem>,
   pub globals: FxHashMap<String, DatumRef>,
   pub scopes: Vec<Scope>,
@@ -86,17 +93,29 @@ pub struct DirPlayer {
   pub stage_size: (u32, u32),
   pub bitmap_manager: bitmap::manager::BitmapManager,
   pub cursor: CursorRef,
-  pub start_time: chrono::DateTime<chrono::Local>,
+  /// Clock time the player was created at, in milliseconds.
+  pub start_time: i64,
   pub timeout_manager: TimeoutManager,
   pub title: String,
   pub bg_color: ColorRef,
   pub keyboard_focus_sprite: i16,
-  pub text_selection_start: u16,
-  pub text_selection_end: u16,
+  pub text_selection_start: u32,
+  pub text_selection_end: u32,
   pub mouse_loc: (i32, i32),
-  pub last_mouse_down_time: i64,
   pub is_double_click: bool,
   pub mouse_down_sprite: i16,
+  pub is_mouse_down: bool,
+  pub is_right_mouse_down: bool,
+  /// The sprite that got the last click, or 0 for the stage.
+  pub click_on: i16,
+  pub click_loc: (i32, i32),
+  /// Clock times of the last click, mouse move and mouse or keyboard event, in milliseconds.
+  /// There is no last click until the first one.
+  pub last_click_time: Option<i64>,
+  pub last_roll_time: i64,
+  pub last_event_time: i64,
+  /// The system cursor last sent to the host.
+  pub host_cursor_id: i32,
   pub subscribed_member_refs: Vec<CastMemberRef>, // TODO move to debug module
   pub is_subscribed_to_channel_names: bool, // TODO move to debug module
   pub font_manager: FontManager,
@@ -109,12 +128,17 @@ pub struct DirPlayer {
   pub dir_cache: HashMap<Box<str>, DirectorFile>,
   pub scope_count: u32,
   pub external_params: HashMap<String, String>,
+  pub sound_manager: SoundManager,
+  pub actor_list: DatumRef,
+  pub is_event_stopped: bool,
+  pub is_preparing_frame: bool,
 }
 
 impl DirPlayer {
   pub fn new<'a>(
     tx: Sender<PlayerVMExecutionItem>,
   ) -> DirPlayer {
+    let now = platform().clock.now_millis();
     let mut result = DirPlayer {
       movie: Movie { 
         rect: IntRect::from(0, 0, 0, 0),
@@ -134,11 +158,13 @@ impl DirPlayer {
         base_path: None,
         tasks: HashMap::new(),
         task_states: HashMap::new(),
-        shared_state: Arc::new(Mutex::new(NetManagerSharedState::new()))
+        shared_state: Arc::new(Mutex::new(NetManagerSharedState::new())),
+        proxies: HashMap::new(),
       },
       is_playing: false,
       is_script_paused: false,
       next_frame: None,
+      play_return_frames: vec![],
       queue_tx: tx,
       globals: FxHashMap::default(),
       scopes: Vec::with_capacity(MAX_STACK_SIZE),
@@ -148,15 +174,22 @@ impl DirPlayer {
       stage_size: (100, 100),
       bitmap_manager: bitmap::manager::BitmapManager::new(),
       cursor: CursorRef::System(0),
-      start_time: chrono::Local::now(),
+      start_time: now,
       timeout_manager: TimeoutManager::new(),
       title: "".to_string(),
       bg_color: ColorRef::Rgb(0, 0, 0),
       keyboard_focus_sprite: -1, // Setting keyboardFocusSprite to -1 returns keyboard focus control to the Score, and setting it to 0 disables keyboard entry into any editable sprite.
       mouse_loc: (0, 0),
-      last_mouse_down_time: 0,
       is_double_click: false,
       mouse_down_sprite: 0,
+      is_mouse_down: false,
+      is_right_mouse_down: false,
+      click_on: 0,
+      click_loc: (0, 0),
+      last_click_time: None,
+      last_roll_time: now,
+      last_event_time: now,
+      host_cursor_id: 0,
       subscribed_member_refs: vec![],
       is_subscribed_to_channel_names: false,
       font_manager: FontManager::new(),
@@ -171,6 +204,10 @@ impl DirPlayer {
       dir_cache: HashMap::new(),
       scope_count: 0,
       external_params: HashMap::new(),
+      sound_manager: SoundManager::new(Box::new(NullSoundBackend {})),
+      actor_list: DatumRef::Void,
+      is_event_stopped: false,
+      is_preparing_frame: false,
     };
     for i in 0..MAX_STACK_SIZE {
       result.scopes.push(Scope::default(i));
@@ -195,6 +232,7 @@ impl DirPlayer {
 
   async fn load_movie_from_dir(&mut self, dir: &DirectorFile) {
     self.movie.load_from_file(&dir, &mut self.net_manager, &mut self.bitmap_manager, &mut self.dir_cache).await;
+    self.font_manager.load_font_maps(dir);
     let (r, g, b) = self.movie.stage_color;
     self.bg_color = ColorRef::Rgb(r, g, b);
     JsApi::dispatch_movie_loaded(&dir);
@@ -208,11 +246,7 @@ impl DirPlayer {
     self.is_script_paused = false;
     // TODO runVM()
     async_std::task::spawn_local(async move {
-      if let Err(err) = player_invoke_global_event(&"prepareMovie".to_string(), &vec![]).await {
-        reserve_player_mut(|player| player.on_script_error(&err));
-        return;
-      }
-      run_frame_loop().await;
+      player_run_movie(None).await;
     });
   }
 
@@ -270,13 +304,14 @@ impl DirPlayer {
   }
 
   pub fn stop(&mut self) {
-    // TODO dispatch stop movie
     self.is_playing = false;
     self.next_frame = None;
+    self.play_return_frames.clear();
     //scopes.clear();
     // currentBreakpoint?.completer.completeError(CancelledException());
     // currentBreakpoint = null;
-    self.timeout_manager.clear();
+    self.timeout_manager.clear_non_persistent();
+    self.sound_manager.stop_all();
     //notifyListeners();
 
     warn!("Profiler report: {}", get_profiler_report());
@@ -286,6 +321,7 @@ impl DirPlayer {
     self.stop();
     self.scopes.clear();
     self.globals.clear();
+    self.actor_list = DatumRef::Void;
     self.allocator.reset();
     self.timeout_manager.clear();
     // netManager.clear();
@@ -308,8 +344,8 @@ impl DirPlayer {
   fn get_movie_prop(&self, prop: &String) -> Result<Datum, ScriptError> {
     match prop.as_str() {
       "stage" => Ok(Datum::Stage),
-      "time" => Ok(Datum::String(Local::now().format("%H:%M %p").to_string())),
-      "milliSeconds" => Ok(Datum::Int(chrono::Local::now().signed_duration_since(self.start_time).num_milliseconds() as i32)),
+      "time" => Ok(Datum::String(get_local_time().format("%H:%M %p").to_string())),
+      "milliSeconds" => Ok(Datum::Int((platform().clock.now_millis() - self.start_time) as i32)),
       "keyboardFocusSprite" => Ok(Datum::Int(self.keyboard_focus_sprite as i32)),
       "frameTempo" => Ok(Datum::Int(self.movie.puppet_tempo as i32)),
       "mouseLoc" => Ok(Datum::IntPoint(self.mouse_loc)),
@@ -328,11 +364,56 @@ impl DirPlayer {
       "key" => Ok(Datum::String(self.keyboard_manager.key())),
       "floatPrecision" => Ok(Datum::Int(self.float_precision as i32)),
       "doubleClick" => Ok(datum_bool(self.is_double_click)),
+      "mouseDown" | "stillDown" => Ok(datum_bool(self.is_mouse_down)),
+      "mouseUp" => Ok(datum_bool(!self.is_mouse_down)),
+      "rightMouseDown" => Ok(datum_bool(self.is_right_mouse_down)),
+      "rightMouseUp" => Ok(datum_bool(!self.is_right_mouse_down)),
+      "clickOn" => Ok(Datum::Int(self.click_on as i32)),
+      "clickLoc" => Ok(Datum::IntPoint(self.click_loc)),
+      "mouseMember" => {
+        let sprite = get_sprite_at(self, self.mouse_loc.0, self.mouse_loc.1, false);
+        let member_ref = sprite.and_then(|x| self.movie.score.get_sprite(x as i16)).and_then(|x| x.member.clone());
+        Ok(member_ref.map_or(Datum::Void, Datum::CastMember))
+      }
+      "lastClick" => Ok(Datum::Int(ticks_since(self.last_click_time.unwrap_or(self.start_time)))),
+      "lastRoll" => Ok(Datum::Int(ticks_since(self.last_roll_time))),
+      "lastEvent" => Ok(Datum::Int(ticks_since(self.last_event_time))),
       "ticks" => Ok(Datum::Int(get_elapsed_ticks(self.timer_tick_start))),
       _ => self.movie.get_prop(prop),
     }
   }
 
+  /// Like `get_movie_prop`, but returns the live list for props that scripts modify in place.
+  fn get_movie_prop_ref(&mut self, prop: &String) -> Result<DatumRef, ScriptError> {
+    match prop.as_str() {
+      "actorList" => Ok(self.get_actor_list()),
+      "timeoutList" => {
+        let timeout_names: Vec<_> = self.timeout_manager.timeouts.iter().map(|x| x.name.to_owned()).collect();
+        let timeout_refs = timeout_names.into_iter().map(|name| self.alloc_datum(Datum::TimeoutRef(name))).collect();
+        Ok(self.alloc_datum(Datum::List(DatumType::List, timeout_refs, false)))
+      }
+      "xtraList" => {
+        let name_key = self.alloc_datum(Datum::Symbol("name".to_string()));
+        let xtra_refs = get_registered_xtra_names().into_iter().map(|name| {
+          let name_ref = self.alloc_datum(Datum::String(name));
+          self.alloc_datum(Datum::PropList(vec![(name_key.clone(), name_ref)], false))
+        }).collect();
+        Ok(self.alloc_datum(Datum::List(DatumType::List, xtra_refs, false)))
+      }
+      _ => {
+        let value = self.get_movie_prop(prop)?;
+        Ok(self.alloc_datum(value))
+      }
+    }
+  }
+
+  pub fn get_actor_list(&mut self) -> DatumRef {
+    if let DatumRef::Void = self.actor_list {
+      self.actor_list = self.alloc_datum(Datum::List(DatumType::List, vec![], false));
+    }
+    self.actor_list.clone()
+  }
+
   fn get_player_prop(&mut self, prop: &String) -> Result<DatumRef, ScriptError> {
     match prop.as_str() {
       "traceScript" => Ok(self.alloc_datum(datum_bool(false))), // TODO
@@ -356,6 +437,7 @@ impl DirPlayer {
     match prop_name.as_str() {
       "colorDepth" => Ok(Datum::Int(32)),
       "timer" => Ok(Datum::Int(get_elapsed_ticks(self.timer_tick_start))),
+      "lastClick" | "lastRoll" | "lastEvent" => self.get_movie_prop(&prop_name),
       _ => Err(ScriptError::new(format!("Unknown anim prop {}", prop_name)))
     }
   }
@@ -376,11 +458,11 @@ impl DirPlayer {
         Ok(())
       },
       "selStart" => {
-        self.text_selection_start = value.int_value()? as u16;
+        self.text_selection_start = value.int_value()?.max(0) as u32;
         Ok(())
       },
       "selEnd" => {
-        self.text_selection_end = value.int_value()? as u16;
+        self.text_selection_end = value.int_value()?.max(0) as u32;
         Ok(())
       },
       "floatPrecision" => {
@@ -392,8 +474,13 @@ impl DirPlayer {
         Ok(())
       },
       "actorList" => {
-        // TODO
-        Ok(())
+        match value {
+          Datum::List(..) => {
+            self.actor_list = self.alloc_datum(value);
+            Ok(())
+          }
+          _ => Err(ScriptError::new("actorList must be a list".to_string())),
+        }
       },
       _ => {
         self.movie.set_prop(prop, value, &self.allocator)
@@ -552,9 +639,26 @@ pub async fn player_call_script_handler_raw_args(
   use_raw_arg_list: bool,
 ) -> Result<ScopeResult, ScriptError> {
   let (script_member_ref, handler_name) = &handler_ref;
+  let script_rc = reserve_player_ref(|player| {
+    player.movie.cast_manager.get_script_by_ref(&script_member_ref).unwrap().clone()
+  });
+  player_call_loaded_script_handler(receiver, script_rc, handler_name, arg_list, use_raw_arg_list).await
+}
+
+/// Calls a handler of a script that does not have to be stored in a cast, such as the scripts
+/// compiled for `do`. The script stays alive until the handler returns, even if its member is
+/// recompiled in the meantime.
+pub async fn player_call_loaded_script_handler(
+  receiver: Option<ScriptInstanceRef>, 
+  script_rc: Rc<Script>,
+  handler_name: &String,
+  arg_list: &Vec<DatumRef>,
+  use_raw_arg_list: bool,
+) -> Result<ScopeResult, ScriptError> {
+  let script_member_ref = &script_rc.member_ref;
+  let handler_ref: ScriptHandlerRef = (script_member_ref.clone(), handler_name.clone());
   let (scope_ref, handler_ptr, script_ptr) = reserve_player_mut(|player| {
     let (script_ptr, handler_ptr, handler_name_id, script_type) = {
-      let script_rc = player.movie.cast_manager.get_script_by_ref(&script_member_ref).unwrap();
       let script = script_rc.as_ref();
       let script_ptr = script as *const Script;
       let handler = script.get_own_handler(&handler_name);
@@ -663,7 +767,17 @@ pub async fn player_call_script_handler_raw_args(
   return Ok(scope);
 }
 
-pub async fn run_frame_loop() {
+/// Sends `prepareMovie` and runs the frame loop of a movie that was started with `play`.
+/// Returns after `frame_limit` frames when given, otherwise once the movie stops.
+pub async fn player_run_movie(frame_limit: Option<u32>) {
+  if let Err(err) = player_invoke_global_event(&"prepareMovie".to_string(), &vec![]).await {
+    reserve_player_mut(|player| player.on_script_error(&err));
+    return;
+  }
+  run_frame_loop(frame_limit).await;
+}
+
+pub async fn run_frame_loop(frame_limit: Option<u32>) {
   // let player_arc = &PLAYER_LOCK;
   let mut fps: u32;
   unsafe {
@@ -676,13 +790,22 @@ pub async fn run_frame_loop() {
 
   let mut is_playing = true;
   let mut is_script_paused = false;
-  while is_playing {
+  let mut is_first_frame = true;
+  let mut frame_count = 0;
+  while is_playing && frame_limit.is_none_or(|limit| frame_count < limit) {
     if !is_script_paused {
       player_wait_available().await;
-      player_unwrap_result(player_invoke_global_event(&"prepareFrame".to_string(), &vec![]).await);
-      player_unwrap_result(player_invoke_global_event(&"enterFrame".to_string(), &vec![]).await);
+      player_unwrap_result(player_update_score_sprites().await.map(|_| DatumRef::Void));
+      player_unwrap_result(player_prepare_frame().await.map(|_| DatumRef::Void));
+      if is_first_frame {
+        is_first_frame = false;
+        player_unwrap_result(player_invoke_global_event(&"startMovie".to_string(), &vec![]).await);
+      }
+      player_unwrap_result(player_invoke_frame_event(&"enterFrame".to_string(), &vec![]).await);
+      player_unwrap_result(player_invoke_static_event(&"idle".to_string(), &vec![]).await.map(|_| DatumRef::Void));
+      player_update_rollover(true);
     }
-    timeout(Duration::from_millis(1000 / fps as u64), future::pending::<()>()).await.unwrap_err();
+    player_sleep_until(platform().clock.now_millis() + 1000 / fps as i64).await;
     player_wait_available().await;
 
     let mut prev_frame = 0;
@@ -695,8 +818,11 @@ pub async fn run_frame_loop() {
         return;
       }
       prev_frame = player.movie.current_frame;
+      player.sound_manager.update(platform().clock.now_millis());
       if !player.is_script_paused {
         player.advance_frame();
+        advance_film_loops(player);
+        player.allocator.on_frame();
       }
       new_frame = player.movie.current_frame;
     });
@@ -720,15 +846,33 @@ pub async fn run_frame_loop() {
       });
       if !frame_skipped {
         // TODO only call this after timeout completes
-        player_unwrap_result(player_invoke_global_event(&"exitFrame".to_string(), &vec![]).await);
+        player_unwrap_result(player_invoke_frame_event(&"exitFrame".to_string(), &vec![]).await);
         (is_playing, is_script_paused) = reserve_player_mut(|player| {
           (player.is_playing, player.is_script_paused)
         });
       }
+      flush_xtras();
+      frame_count += 1;
     };   
   }
 }
 
+/// Stops the movie, sending `endSprite` to the active sprites and then `stopMovie`.
+pub async fn player_stop_movie() -> Result<(), ScriptError> {
+  let was_playing = reserve_player_mut(|player| {
+    let was_playing = player.is_playing;
+    player.stop();
+    was_playing
+  });
+  if !was_playing {
+    return Ok(());
+  }
+  player_end_score_sprites().await?;
+  player_invoke_global_event(&"stopMovie".to_string(), &vec![]).await?;
+  flush_xtras();
+  Ok(())
+}
+
 pub async fn player_trigger_breakpoint(breakpoint: Breakpoint, script_ref: CastMemberRef, handler_ref: ScriptHandlerRef, bytecode_index: usize) {
   let (future, completer) = ManualFuture::new();
   let breakpoint_ctx = BreakpointContext {
@@ -765,6 +909,12 @@ lazy_static! {
 
 pub fn init_player() {
   console_log::init_with_level(log::Level::Error).unwrap_or(());
+  init_player_with_platform(web_platform(), Box::new(WebAudioSoundBackend::new()));
+}
+
+/// Creates the player on top of the given platform services and starts its command and event loops.
+pub fn init_player_with_platform(platform: Platform, sound_backend: Box<dyn SoundBackend>) {
+  set_platform(platform);
   let (tx, rx) = channel::unbounded();
   let (event_tx, event_rx) = channel::unbounded();
   unsafe { 
@@ -776,6 +926,9 @@ pub fn init_player() {
   unsafe {
     PLAYER_OPT = Some(DirPlayer::new(tx));
   }
+  reserve_player_mut(|player| {
+    player.sound_manager.backend = sound_backend;
+  });
   // let mut player = //PLAYER_LOCK.try_write().unwrap();
   // *player = Some(DirPlayer::new(tx, allocator_rx, allocator_tx));
 
@@ -0,0 +1,413 @@
+use crate::{director::lingo::datum::Datum, js_api::JsApi, platform::platform};
+
+use super::{
+    cast_member::CastMemberType, events::player_dispatch_targeted_event, player_is_playing, reserve_player_mut,
+    score::{concrete_sprite_hit_test, get_sprite_at}, script_ref::ScriptInstanceRef, sprite::CursorRef, DatumRef,
+    DirPlayer, ScriptError,
+};
+
+/// The system cursor shown by the host while a cast member cursor is drawn on the stage.
+const BLANK_CURSOR_ID: i32 = 200;
+
+/// Milliseconds between two clicks for the second one to be a double click.
+const DOUBLE_CLICK_MILLIS: i64 = 500;
+
+/// Converts the time since `since` to ticks, as returned by `the lastClick`, `the lastRoll` and `the lastEvent`.
+pub fn ticks_since(since: i64) -> i32 {
+    ((platform().clock.now_millis() - since).max(0) * 60 / 1000).min(i32::MAX as i64) as i32
+}
+
+fn get_sprite_instances(player: &DirPlayer, sprite_num: i16) -> Option<Vec<ScriptInstanceRef>> {
+    player
+        .movie
+        .score
+        .get_sprite(sprite_num)
+        .map(|sprite| sprite.script_instance_list.clone())
+}
+
+/// Sends a rollover event, or `mouseUpOutside`, to the behaviors of a sprite. Unlike clicks, these never
+/// reach the frame or movie scripts.
+fn dispatch_sprite_rollover_event(handler_name: &str, instance_ids: Option<Vec<ScriptInstanceRef>>) {
+    if let Some(instance_ids) = instance_ids.filter(|x| !x.is_empty()) {
+        player_dispatch_targeted_event(&handler_name.to_string(), &vec![], Some(&instance_ids));
+    }
+}
+
+/// Returns the cursor of the sprite under the mouse, or the movie cursor if that sprite doesn't set one.
+pub fn get_active_cursor(player: &DirPlayer) -> &CursorRef {
+    player
+        .hovered_sprite
+        .and_then(|sprite_num| player.movie.score.get_sprite(sprite_num))
+        .and_then(|sprite| sprite.cursor_ref.as_ref())
+        .unwrap_or(&player.cursor)
+}
+
+fn get_host_cursor_id(cursor: &CursorRef) -> i32 {
+    match cursor {
+        CursorRef::System(cursor_id) => *cursor_id,
+        // Member cursors are drawn with the stage, so the host pointer is hidden.
+        CursorRef::Member(_) => BLANK_CURSOR_ID,
+    }
+}
+
+/// Finds the sprite under the mouse and sends `mouseLeave` and `mouseEnter` when it changed.
+/// On frame updates `mouseWithin` is also sent to the sprite the mouse stays over.
+/// Sprites can move under a still mouse, so this runs on every frame as well as on mouse moves.
+pub fn player_update_rollover(is_frame_update: bool) {
+    let events = reserve_player_mut(|player| {
+        let (x, y) = player.mouse_loc;
+        let prev_sprite = player.hovered_sprite;
+        let new_sprite = get_sprite_at(player, x, y, false).map(|x| x as i16);
+        player.hovered_sprite = new_sprite;
+
+        let host_cursor_id = get_host_cursor_id(get_active_cursor(player));
+        if host_cursor_id != player.host_cursor_id {
+            player.host_cursor_id = host_cursor_id;
+            JsApi::dispatch_cursor_changed(host_cursor_id);
+        }
+
+        let mut events = vec![];
+        if prev_sprite != new_sprite {
+            if let Some(prev_sprite) = prev_sprite {
+                events.push(("mouseLeave", get_sprite_instances(player, prev_sprite)));
+            }
+            if let Some(new_sprite) = new_sprite {
+                events.push(("mouseEnter", get_sprite_instances(player, new_sprite)));
+            }
+        } else if is_frame_update {
+            if let Some(new_sprite) = new_sprite {
+                events.push(("mouseWithin", get_sprite_instances(player, new_sprite)));
+            }
+        }
+        events
+    });
+    for (handler_name, instance_ids) in events {
+        dispatch_sprite_rollover_event(handler_name, instance_ids);
+    }
+}
+
+pub async fn player_mouse_move(x: i32, y: i32) -> Result<DatumRef, ScriptError> {
+    if !player_is_playing().await {
+        return Ok(DatumRef::Void);
+    }
+    reserve_player_mut(|player| {
+        let now = platform().clock.now_millis();
+        player.mouse_loc = (x, y);
+        player.last_roll_time = now;
+        player.last_event_time = now;
+    });
+    player_update_rollover(false);
+    Ok(DatumRef::Void)
+}
+
+pub async fn player_mouse_down(x: i32, y: i32) -> Result<DatumRef, ScriptError> {
+    if !player_is_playing().await {
+        return Ok(DatumRef::Void);
+    }
+    let instance_ids = reserve_player_mut(|player| {
+        let now = platform().clock.now_millis();
+        player.is_double_click = player.last_click_time.is_some_and(|last_click_time| now - last_click_time < DOUBLE_CLICK_MILLIS);
+        player.mouse_loc = (x, y);
+        player.click_loc = (x, y);
+        player.is_mouse_down = true;
+        player.last_click_time = Some(now);
+        player.last_event_time = now;
+
+        let sprite_num = get_sprite_at(player, x, y, true).map_or(0, |x| x as i16);
+        player.click_on = sprite_num;
+        player.mouse_down_sprite = sprite_num;
+        if sprite_num == 0 {
+            return None;
+        }
+        let sprite = player.movie.score.get_sprite(sprite_num);
+        let sprite_member = sprite
+            .and_then(|x| x.member.as_ref())
+            .and_then(|x| player.movie.cast_manager.find_member_by_ref(x));
+        if let Some(CastMemberType::Field(field_member)) = sprite_member.map(|x| &x.member_type) {
+            if field_member.editable {
+                player.keyboard_focus_sprite = sprite_num;
+            }
+        }
+        sprite.map(|x| x.script_instance_list.clone())
+    });
+    player_dispatch_targeted_event(&"mouseDown".to_string(), &vec![], instance_ids.as_ref());
+    Ok(DatumRef::Void)
+}
+
+/// Sends `mouseUp` to the sprite that got the `mouseDown`, or `mouseUpOutside` to its behaviors only
+/// if the mouse was released away from it.
+pub async fn player_mouse_up(x: i32, y: i32) -> Result<DatumRef, ScriptError> {
+    if !player_is_playing().await {
+        return Ok(DatumRef::Void);
+    }
+    let result = reserve_player_mut(|player| {
+        player.mouse_loc = (x, y);
+        player.is_mouse_down = false;
+        player.last_event_time = platform().clock.now_millis();
+        let sprite = if player.mouse_down_sprite > 0 {
+            player.movie.score.get_sprite(player.mouse_down_sprite)
+        } else {
+            None
+        };
+        player.mouse_down_sprite = 0;
+        sprite.map(|sprite| {
+            let is_inside = concrete_sprite_hit_test(player, sprite, x, y);
+            (sprite.script_instance_list.clone(), is_inside)
+        })
+    });
+    match result {
+        Some((instance_ids, false)) => dispatch_sprite_rollover_event("mouseUpOutside", Some(instance_ids)),
+        result => {
+            let instance_ids = result.map(|x| x.0);
+            player_dispatch_targeted_event(&"mouseUp".to_string(), &vec![], instance_ids.as_ref());
+        }
+    }
+    reserve_player_mut(|player| {
+        player.is_double_click = false;
+    });
+    Ok(DatumRef::Void)
+}
+
+pub async fn player_right_mouse_down(x: i32, y: i32) -> Result<DatumRef, ScriptError> {
+    if !player_is_playing().await {
+        return Ok(DatumRef::Void);
+    }
+    let instance_ids = reserve_player_mut(|player| {
+        player.mouse_loc = (x, y);
+        player.is_right_mouse_down = true;
+        player.last_event_time = platform().clock.now_millis();
+        get_sprite_at(player, x, y, true).and_then(|sprite_num| get_sprite_instances(player, sprite_num as i16))
+    });
+    player_dispatch_targeted_event(&"rightMouseDown".to_string(), &vec![], instance_ids.as_ref());
+    Ok(DatumRef::Void)
+}
+
+pub async fn player_right_mouse_up(x: i32, y: i32) -> Result<DatumRef, ScriptError> {
+    if !player_is_playing().await {
+        return Ok(DatumRef::Void);
+    }
+    let instance_ids = reserve_player_mut(|player| {
+        player.mouse_loc = (x, y);
+        player.is_right_mouse_down = false;
+        player.last_event_time = platform().clock.now_millis();
+        get_sprite_at(player, x, y, true).and_then(|sprite_num| get_sprite_instances(player, sprite_num as i16))
+    });
+    player_dispatch_targeted_event(&"rightMouseUp".to_string(), &vec![], instance_ids.as_ref());
+    Ok(DatumRef::Void)
+}
+
+/// Sends `mouseWheel` with the number of lines scrolled, positive when scrolling down, to the
+/// sprite under the mouse and then to the frame and movie scripts.
+pub async fn player_mouse_wheel(delta: i32) -> Result<DatumRef, ScriptError> {
+    if !player_is_playing().await {
+        return Ok(DatumRef::Void);
+    }
+    let (instance_ids, delta_ref) = reserve_player_mut(|player| {
+        player.last_event_time = platform().clock.now_millis();
+        let (x, y) = player.mouse_loc;
+        let instance_ids =
+            get_sprite_at(player, x, y, true).and_then(|sprite_num| get_sprite_instances(player, sprite_num as i16));
+        (instance_ids, player.alloc_datum(Datum::Int(delta)))
+    });
+    player_dispatch_targeted_event(&"mouseWheel".to_string(), &vec![delta_ref], instance_ids.as_ref());
+    Ok(DatumRef::Void)
+}
+
+#[cfg(test)]
+mod tests {
+    use async_std::task::block_on;
+
+    use crate::{
+        director::enums::{ScriptType, ShapeInfo},
+        player::{
+            cast_lib::cast_member_ref,
+            cast_member::{CastMemberType, ShapeMember},
+            events::{player_dispatch_global_event, player_invoke_global_event, player_unwrap_result},
+            player_run_movie,
+            reserve_player_ref,
+            score::ScoreSpriteSpan,
+            testing::{add_test_cast, add_test_scripts, logging_handler, start_test_log, take_test_log, with_test_player, with_test_player_at},
+        },
+    };
+
+    use super::*;
+
+    /// Returns handlers that append their name and first argument to the `gLog` global.
+    fn log_events(names: &[&str], receiver: Option<&str>) -> String {
+        let params = receiver.map_or("arg".to_string(), |receiver| format!("{receiver}, arg"));
+        names.iter().map(|name| logging_handler(name, &params, &format!("\"{name}\" & arg"), "")).collect()
+    }
+
+    /// Waits for the event loop to handle the events dispatched so far.
+    fn process_events() {
+        reserve_player_mut(|player| player.globals.remove("gProcessed"));
+        player_dispatch_global_event(&"eventsProcessed".to_string(), &vec![]);
+        block_on(async {
+            while !reserve_player_ref(|player| player.globals.contains_key("gProcessed")) {
+                async_std::task::yield_now().await;
+            }
+        });
+    }
+
+    /// Starts a movie with the behavior on sprite 1, a 20x20 square at (10, 10), and the movie script.
+    fn start_movie(behavior: &str, movie_script: &str) {
+        let main = movie_script.to_string()
+            + "on eventsProcessed\r  global gProcessed\r  gProcessed = 1\rend\r"
+            + &logging_handler("report", "", "\"clickOn\" & the clickOn & \" lastRoll\" & the lastRoll", "");
+        let cast_lib = add_test_scripts(vec![
+            (1, "main", ScriptType::Movie, &main),
+            (2, "behavior", ScriptType::Score, behavior),
+        ]);
+        let shape_info = ShapeInfo::from(&[0, 1, 0, 0, 0, 0, 0, 20, 0, 20][..]);
+        let shape_cast_lib = add_test_cast(vec![(1, "square", CastMemberType::Shape(ShapeMember { shape_info }))]);
+        reserve_player_mut(|player| {
+            player.movie.score.set_channel_count(1);
+            player.movie.score.sprite_spans.push(ScoreSpriteSpan {
+                sprite_number: 1,
+                start_frame: 1,
+                end_frame: 10,
+                behaviors: vec![cast_member_ref(cast_lib as i32, 2)],
+            });
+            player.is_playing = true;
+        });
+        block_on(player_run_movie(Some(1)));
+        reserve_player_mut(|player| {
+            let sprite = player.movie.score.get_sprite_mut(1);
+            sprite.member = Some(cast_member_ref(shape_cast_lib as i32, 1));
+            sprite.loc_h = 10;
+            sprite.loc_v = 10;
+            sprite.width = 20;
+            sprite.height = 20;
+        });
+        start_test_log();
+    }
+
+    /// Returns what the handlers logged once the events dispatched so far are handled.
+    fn take_log() -> String {
+        process_events();
+        take_test_log()
+    }
+
+    #[test]
+    fn sends_rollover_events_to_the_sprite_under_the_mouse() {
+        with_test_player(|_| {
+            start_movie(&log_events(&["mouseEnter", "mouseWithin", "mouseLeave"], Some("me")), "");
+            block_on(player_mouse_move(15, 15)).unwrap();
+            assert_eq!(take_log(), "mouseEnter ");
+            player_update_rollover(true);
+            player_update_rollover(true);
+            assert_eq!(take_log(), "mouseWithin mouseWithin ");
+            // Moving within the sprite is not a frame update
+            block_on(player_mouse_move(20, 20)).unwrap();
+            assert_eq!(take_log(), "");
+            block_on(player_mouse_move(40, 40)).unwrap();
+            assert_eq!(take_log(), "mouseLeave ");
+            player_update_rollover(true);
+            assert_eq!(take_log(), "");
+            assert_eq!(reserve_player_ref(|player| player.hovered_sprite), None);
+        });
+    }
+
+    #[test]
+    fn releasing_away_from_the_sprite_sends_mouse_up_outside_to_its_behaviors_only() {
+        with_test_player(|_| {
+            start_movie(&log_events(&["mouseDown", "mouseUp", "mouseUpOutside"], Some("me")), &log_events(&["mouseUp"], None));
+            block_on(player_mouse_down(15, 15)).unwrap();
+            block_on(player_mouse_up(40, 40)).unwrap();
+            assert_eq!(take_log(), "mouseDown mouseUpOutside ");
+
+            block_on(player_mouse_down(15, 15)).unwrap();
+            block_on(player_mouse_up(16, 16)).unwrap();
+            assert_eq!(take_log(), "mouseDown mouseUp ");
+        });
+    }
+
+    #[test]
+    fn sends_right_button_events_to_the_sprite_under_the_mouse() {
+        with_test_player(|_| {
+            start_movie(&log_events(&["rightMouseDown", "rightMouseUp"], Some("me")), "");
+            block_on(player_right_mouse_down(15, 15)).unwrap();
+            assert!(reserve_player_ref(|player| player.is_right_mouse_down));
+            block_on(player_right_mouse_up(15, 15)).unwrap();
+            assert!(!reserve_player_ref(|player| player.is_right_mouse_down));
+            block_on(player_right_mouse_down(40, 40)).unwrap();
+            block_on(player_right_mouse_up(40, 40)).unwrap();
+            assert_eq!(take_log(), "rightMouseDown rightMouseUp ");
+        });
+    }
+
+    #[test]
+    fn sends_mouse_wheel_with_its_delta_to_the_sprite_or_else_the_movie() {
+        with_test_player(|_| {
+            start_movie(&log_events(&["mouseWheel"], Some("me")), &log_events(&["mouseWheel"], None));
+            block_on(player_mouse_move(15, 15)).unwrap();
+            take_log();
+            block_on(player_mouse_wheel(3)).unwrap();
+            assert_eq!(take_log(), "mouseWheel3 ");
+
+            block_on(player_mouse_move(40, 40)).unwrap();
+            block_on(player_mouse_wheel(-1)).unwrap();
+            assert_eq!(take_log(), "mouseWheel-1 ");
+        });
+    }
+
+    #[test]
+    fn tracks_the_clicked_sprite_and_the_time_since_the_last_roll() {
+        with_test_player(|platform| {
+            start_movie(&log_events(&["mouseDown"], Some("me")), "");
+            block_on(player_mouse_move(15, 15)).unwrap();
+            platform.clock.advance(1000);
+            block_on(player_mouse_down(15, 15)).unwrap();
+            take_log();
+            player_unwrap_result(block_on(player_invoke_global_event(&"report".to_string(), &vec![])));
+            assert_eq!(take_log(), "clickOn1 lastRoll60 ");
+
+            block_on(player_mouse_move(40, 40)).unwrap();
+            block_on(player_mouse_down(40, 40)).unwrap();
+            player_unwrap_result(block_on(player_invoke_global_event(&"report".to_string(), &vec![])));
+            assert_eq!(take_log(), "clickOn0 lastRoll0 ");
+        });
+    }
+
+    #[test]
+    fn only_a_second_click_soon_after_the_first_is_a_double_click() {
+        with_test_player(|platform| {
+            start_movie("", "");
+            let click = |x, y| {
+                block_on(player_mouse_down(x, y)).unwrap();
+                let is_double_click = reserve_player_ref(|player| player.is_double_click);
+                block_on(player_mouse_up(x, y)).unwrap();
+                is_double_click
+            };
+            // The first click is at clock time 0, which must not look like a click right before it
+            assert!(!click(15, 15));
+            platform.clock.advance(200);
+            assert!(click(40, 40));
+            platform.clock.advance(600);
+            assert!(!click(15, 15));
+        });
+    }
+
+    #[test]
+    fn counts_the_last_click_roll_and_event_from_the_player_start() {
+        // The system clock counts milliseconds since 1970
+        with_test_player_at(1_760_000_000_000, |platform| {
+            let ticks = |prop: &str| match reserve_player_ref(|player| player.get_movie_prop(&prop.to_string())) {
+                Ok(Datum::Int(ticks)) => ticks,
+                other => panic!("{} is not a number: {:?}", prop, other.map(|x| x.type_str())),
+            };
+            assert_eq!([ticks("lastClick"), ticks("lastRoll"), ticks("lastEvent")], [0, 0, 0]);
+
+            platform.clock.advance(2000);
+            assert_eq!([ticks("lastClick"), ticks("lastRoll"), ticks("lastEvent")], [120, 120, 120]);
+
+            // Playing the first frames moves the clock on as well
+            start_movie("", "");
+            let last_roll = ticks("lastRoll");
+            assert!(last_roll >= 120);
+            block_on(player_mouse_down(40, 40)).unwrap();
+            platform.clock.advance(500);
+            assert_eq!([ticks("lastClick"), ticks("lastRoll"), ticks("lastEvent")], [30, last_roll + 30, 30]);
+        });
+    }
+}
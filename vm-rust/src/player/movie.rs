@@ -1,8 +1,7 @@
 use std::collections::HashMap;
 
-use chrono::Local;
 
-use crate::{director::{file::DirectorFile, lingo::datum::{datum_bool, Datum}}, utils::{PATH_SEPARATOR}};
+use crate::{director::{file::DirectorFile, lingo::datum::{datum_bool, Datum}}, utils::{get_local_time, PATH_SEPARATOR}};
 
 use super::{allocator::DatumAllocator, bitmap::manager::BitmapManager, cast_manager::CastManager, geometry::IntRect, net_manager::NetManager, score::Score, ScriptError, ScriptReceiver};
 
@@ -61,12 +60,12 @@ impl Movie {
       "runMode" => Ok(Datum::String("Plugin".to_string())), // Plugin / Author
       "date" => {
         // TODO localize formatting
-        let time = Local::now();
+        let time = get_local_time();
         let formatted = time.format("%m/%d/%Y").to_string();
         Ok(Datum::String(formatted))
       }
       "long time" => {
-        let time = Local::now();
+        let time = get_local_time();
         let formatted = time.format("%H:%M:%S %p").to_string();
         Ok(Datum::String(formatted))
       }
@@ -82,6 +81,18 @@ impl Movie {
       }
       "platform" => Ok(Datum::String("Windows,32".to_string())),
       "frame" => Ok(Datum::Int(self.current_frame as i32)),
+      "frameLabel" => match self.score.get_frame_label(self.current_frame) {
+        Some(label) => Ok(Datum::String(label.to_owned())),
+        None => Ok(Datum::Int(0)),
+      },
+      "labelList" => {
+        let mut result = String::new();
+        for label in &self.score.frame_labels {
+          result.push_str(&label.label);
+          result.push('\r');
+        }
+        Ok(Datum::String(result))
+      }
       "productVersion" => Ok(Datum::String("10.1".to_string())),
       "stageRight" => Ok(Datum::Int(self.rect.right as i32)),
       "stageLeft" => Ok(Datum::Int(self.rect.left as i32)),
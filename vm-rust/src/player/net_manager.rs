@@ -1,31 +1,51 @@
 use std::{path::Path, collections::HashMap, sync::Arc};
 
 use async_std::sync::Mutex;
+use log::warn;
 use manual_future::{ManualFuture, ManualFutureCompleter};
 use url::Url;
 
-use super::net_task::{NetTask, NetResult, fetch_net_task, NetTaskState};
+use crate::platform::platform;
+
+use super::net_task::{NetTask, NetResult, fetch_net_task, NetTaskState, NET_ERROR_ABORTED, NET_ERROR_INTERNAL};
 
 pub struct NetManager {
   pub base_path: Option<Url>,
   pub tasks: HashMap<u32, NetTask>,
   pub task_states: HashMap<u32, NetTaskState>,
-  pub shared_state: Arc<Mutex<NetManagerSharedState>>
+  pub shared_state: Arc<Mutex<NetManagerSharedState>>,
+  /// Proxy host and port by URL scheme, as set with `proxyServer`.
+  pub proxies: HashMap<String, (String, u16)>,
 }
 
+/// Progress reported by the net loader as (task id, bytes so far, bytes total). Loaders report
+/// progress synchronously, while the shared state may be locked, so it is queued here and
+/// applied the next time the state is read.
+type NetProgressQueue = Arc<std::sync::Mutex<Vec<(u32, u64, Option<u64>)>>>;
+
 pub struct NetManagerSharedState {
   pub task_states: HashMap<u32, NetTaskState>,
   pub task_completers: HashMap<u32, Vec<ManualFutureCompleter<()>>>,
+  progress_queue: NetProgressQueue,
 }
 
 impl NetManagerSharedState {
   pub fn new() -> NetManagerSharedState {
-    return NetManagerSharedState { task_states: HashMap::new(), task_completers: HashMap::new() }
+    return NetManagerSharedState {
+      task_states: HashMap::new(),
+      task_completers: HashMap::new(),
+      progress_queue: Arc::new(std::sync::Mutex::new(vec![])),
+    }
   }
 
   pub async fn fulfill_task(&mut self, id: u32, result: NetResult) {
-    let new_state = NetTaskState { result: Some(result) };
-    self.task_states.insert(id, new_state);
+    self.apply_queued_progress();
+    let state = self.task_states.entry(id).or_default();
+    if state.is_done() {
+      // The task was aborted, its response is dropped.
+      return;
+    }
+    state.result = Some(result);
 
     let completers_for_task = self.task_completers.get_mut(&id);
     if let Some(completers) = completers_for_task {
@@ -46,6 +66,16 @@ impl NetManagerSharedState {
   pub fn update_task_state(&mut self, task_id: u32, state: NetTaskState) {
     self.task_states.insert(task_id, state);
   }
+
+  fn apply_queued_progress(&mut self) {
+    let updates = std::mem::take(&mut *self.progress_queue.lock().unwrap());
+    for (task_id, bytes_so_far, bytes_total) in updates {
+      if let Some(state) = self.task_states.get_mut(&task_id) {
+        state.bytes_so_far = bytes_so_far;
+        state.bytes_total = bytes_total;
+      }
+    }
+  }
 }
 
 impl NetManager {
@@ -56,10 +86,18 @@ impl NetManager {
     self.base_path = Some(sanitized_path);
   }
 
-  // TODO findTask
+  pub fn find_task_id_by_url(&self, url: &String) -> Option<u32> {
+    find_task_with_url(&self.tasks, url).map(|x| x.id)
+  }
+
+  /// The id of the most recently started task, as returned by `getLatestNetID`.
+  pub fn get_latest_task_id(&self) -> u32 {
+    self.tasks.len() as u32
+  }
 
   pub fn get_task_state(&self, task_id: Option<u32>) -> Option<NetTaskState> {
-    let shared_state = self.shared_state.try_lock().unwrap();
+    let mut shared_state = self.shared_state.try_lock().unwrap();
+    shared_state.apply_queued_progress();
     let task_states = &shared_state.task_states;
     let task_id = task_id.unwrap_or(task_states.len() as u32);
     return task_states.get(&task_id).map(|x| x.clone());
@@ -112,26 +150,64 @@ impl NetManager {
     if let Some(existing_task) = find_task_with_url(&self.tasks, &url) {
       return existing_task.id;
     }
-    
-    // If not, construct the task outside of the borrowing scope
-    let net_task = {
-      let id = self.tasks.len() + 1;
-      NetTask::new(id as u32, &url, &normalize_task_url(&url, self.base_path.as_ref()))
+    let net_task = self.new_task(&url);
+    self.start_task(net_task)
+  }
+
+  /// Starts a POST request. Unlike GET requests, these are never shared with an earlier task for the same URL.
+  pub fn post_net_text(&mut self, url: String, post_data: Vec<u8>) -> u32 {
+    let mut net_task = self.new_task(&url);
+    net_task.post_data = Some(post_data);
+    self.start_task(net_task)
+  }
+
+  pub fn download_net_thing(&mut self, url: String, local_path: String) -> u32 {
+    let mut net_task = self.new_task(&url);
+    net_task.download_path = Some(local_path);
+    self.start_task(net_task)
+  }
+
+  /// Stops a task that is still running. It is done with error 4242 right away, and its response is dropped.
+  pub fn abort_task(&mut self, task_id: u32) {
+    if self.is_task_done(Some(task_id)) || !self.tasks.contains_key(&task_id) {
+      return;
+    }
+    let completers = {
+      let mut shared_state = self.shared_state.try_lock().unwrap();
+      shared_state.task_states.entry(task_id).or_default().result = Some(Err(NET_ERROR_ABORTED));
+      shared_state.task_completers.remove(&task_id).unwrap_or_default()
     };
+    async_std::task::spawn_local(async move {
+      for completer in completers {
+        completer.complete(()).await;
+      }
+    });
+  }
+
+  fn new_task(&self, url: &String) -> NetTask {
+    let id = self.tasks.len() + 1;
+    let resolved_url = normalize_task_url(url, self.base_path.as_ref());
+    let mut net_task = NetTask::new(id as u32, url, &resolved_url);
+    net_task.proxy = self.proxies.get(resolved_url.scheme()).cloned();
+    net_task
+  }
+
+  fn start_task(&mut self, net_task: NetTask) -> u32 {
     let task_id = net_task.id;
 
     // Set task initial state
-    {
+    let progress_queue = {
       let mut shared_shared = self.shared_state.try_lock().unwrap();
-      shared_shared.update_task_state(task_id, NetTaskState { result: None });
-    }
+      shared_shared.update_task_state(task_id, NetTaskState::default());
+      Arc::clone(&shared_shared.progress_queue)
+    };
 
     // Push the task and execute it
     self.tasks.insert(task_id, net_task.clone());
 
     let shared_state_arc = Arc::clone(&self.shared_state);
     async_std::task::spawn_local(async move { 
-      Self::execute_task(task_id.clone(), net_task, shared_state_arc).await; 
+      Self::execute_task(task_id.clone(), net_task, shared_state_arc, progress_queue).await; 
     });
 
     task_id
@@ -141,9 +217,31 @@ impl NetManager {
     id: u32, 
     task: NetTask, 
     shared_state_arc: Arc<Mutex<NetManagerSharedState>>,
+    progress_queue: NetProgressQueue,
   ) {
-    let result = fetch_net_task(&task).await;
+    let on_progress = Box::new(move |bytes_so_far, bytes_total| {
+      progress_queue.lock().unwrap().push((id, bytes_so_far, bytes_total));
+    });
+    let result = fetch_net_task(&task, on_progress).await.and_then(|response| {
+      match &task.download_path {
+        Some(download_path) => match platform().files.write(download_path, &response.data) {
+          Ok(()) => Ok(response),
+          Err(err) => {
+            warn!("Could not store the download of {}: {:?}", task.url, err);
+            Err(NET_ERROR_INTERNAL)
+          }
+        },
+        None => Ok(response),
+      }
+    });
     let mut shared_state = shared_state_arc.lock().await;
+    let result = result.map(|response| {
+      if let Some(state) = shared_state.task_states.get_mut(&id).filter(|x| !x.is_done()) {
+        state.mime_type = response.mime_type;
+        state.last_modified = response.last_modified;
+      }
+      response.data
+    });
     shared_state.fulfill_task(id, result).await;
   }
 
@@ -1,17 +1,39 @@
-use itertools::Itertools;
-use js_sys::Uint8Array;
 use url::Url;
-use wasm_bindgen::JsCast;
-use wasm_bindgen_futures::JsFuture;
-use web_sys::Response;
 
-use crate::utils::log_i;
+use crate::{platform::{platform, NetMethod, NetProgressCallback, NetRequest, NetResponse}, utils::log_i};
 
 pub type NetResult = Result<Vec<u8>, i32>;
 
-#[derive(Clone)]
+// Error codes returned by `netError`, as documented for Director.
+pub const NET_ERROR_INTERNAL: i32 = 20;
+pub const NET_ERROR_CONNECTION_FAILED: i32 = 4146;
+pub const NET_ERROR_UNEXPECTED_CLOSE: i32 = 4150;
+pub const NET_ERROR_TIMEOUT: i32 = 4154;
+pub const NET_ERROR_BAD_REPLY: i32 = 4156;
+pub const NET_ERROR_NOT_AUTHENTICATED: i32 = 4157;
+pub const NET_ERROR_INVALID_URL: i32 = 4159;
+pub const NET_ERROR_NOT_FOUND: i32 = 4165;
+pub const NET_ERROR_PROXY_FAILED: i32 = 4166;
+pub const NET_ERROR_ABORTED: i32 = 4242;
+
+/// Maps an unsuccessful HTTP status to a `netError` code.
+pub fn net_error_for_http_status(status: u16) -> i32 {
+  match status {
+    401 | 403 => NET_ERROR_NOT_AUTHENTICATED,
+    404 | 410 => NET_ERROR_NOT_FOUND,
+    407 => NET_ERROR_PROXY_FAILED,
+    408 | 504 => NET_ERROR_TIMEOUT,
+    _ => NET_ERROR_BAD_REPLY,
+  }
+}
+
+#[derive(Clone, Default)]
 pub struct NetTaskState {
-  pub result: Option<NetResult>
+  pub result: Option<NetResult>,
+  pub bytes_so_far: u64,
+  pub bytes_total: Option<u64>,
+  pub mime_type: Option<String>,
+  pub last_modified: Option<String>,
 }
 
 #[derive(Clone)]
@@ -19,6 +41,11 @@ pub struct NetTask {
   pub id: u32,
   pub url: String,
   pub resolved_url: Url,
+  /// The form encoded body of a `postNetText`.
+  pub post_data: Option<Vec<u8>>,
+  /// Where a `downloadNetThing` stores the data, as a path in file storage.
+  pub download_path: Option<String>,
+  pub proxy: Option<(String, u16)>,
 }
 
 impl NetTask {
@@ -27,6 +54,9 @@ impl NetTask {
       id: id.clone().to_owned(),
       url: url.clone().to_owned(),
       resolved_url: resolved_url.clone().to_owned(),
+      post_data: None,
+      download_path: None,
+      proxy: None,
     };
   }
 }
@@ -37,26 +67,16 @@ impl NetTaskState {
   }
 }
 
-pub async fn fetch_net_task(task: &NetTask) -> NetResult {
+pub async fn fetch_net_task(task: &NetTask, on_progress: NetProgressCallback) -> Result<NetResponse, i32> {
   log_i(format_args!("execute_task #{} url: {} resolved: {}", task.id, task.url, task.resolved_url.to_string()).to_string().as_str());
 
-  let task_result: NetResult;
-  let window = web_sys::window().unwrap();
-  let resp_result = JsFuture::from(window.fetch_with_str(&task.resolved_url.to_string())).await;
-  if let Ok(resp_value) = resp_result {
-    assert!(resp_value.is_instance_of::<Response>());
-    let resp: Response = resp_value.dyn_into().unwrap();
-    if resp.status() == 200 {
-      let blob = JsFuture::from(resp.array_buffer().unwrap()).await.unwrap();
-      let blob_buffer: Uint8Array = js_sys::Uint8Array::new(&blob);
-      
-      task_result = Ok(blob_buffer.to_vec().iter().map(|x| *x as u8).collect_vec());
-    } else {
-      task_result = Err(4); // TODO: Error code
-    }
-  } else {
-    task_result = Err(4); // TODO: Error code
-  }
-
-  return task_result;
+  let request = NetRequest {
+    url: task.resolved_url.clone(),
+    method: if task.post_data.is_some() { NetMethod::Post } else { NetMethod::Get },
+    body: task.post_data.clone(),
+    content_type: task.post_data.as_ref().map(|_| "application/x-www-form-urlencoded".to_owned()),
+    proxy: task.proxy.clone(),
+  };
+  let net_loader = platform().net_loader.clone();
+  return net_loader.fetch(request, on_progress).await;
 }
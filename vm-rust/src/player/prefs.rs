@@ -0,0 +1,172 @@
+use url::Url;
+
+use crate::platform::platform;
+
+use super::{DirPlayer, ScriptError};
+
+/// The longest pref name accepted, extension included.
+const MAX_PREF_NAME_LENGTH: usize = 64;
+/// The largest value a single pref can hold, in bytes.
+const MAX_PREF_SIZE: usize = 64 * 1024;
+/// Shockwave only writes prefs as text or HTML files.
+const PREF_EXTENSIONS: [&str; 3] = ["txt", "htm", "html"];
+const RESERVED_NAMES: [&str; 22] = [
+  "con", "prn", "aux", "nul", "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8", "com9", "lpt1", "lpt2",
+  "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9",
+];
+
+/// Returns the origin the prefs of the current movie belong to. Movies from the same
+/// host share their prefs, as they share the Prefs folder of the Shockwave plugin.
+fn get_pref_origin(player: &DirPlayer) -> String {
+  match Url::parse(&player.movie.base_path) {
+    Ok(url) => match (url.host_str(), url.port()) {
+      (Some(host), Some(port)) => format!("{}_{}", host, port),
+      (Some(host), None) => host.to_owned(),
+      (None, _) => "local".to_owned(),
+    },
+    Err(_) => "local".to_owned(),
+  }
+}
+
+/// Checks a pref name against the rules of Director and returns the file name it is stored
+/// under. Names are file names, not paths, and get a `.txt` extension when they have none.
+/// Lookups ignore case, as the Prefs folder does on Windows and Mac.
+fn get_pref_file_name(name: &str) -> Result<String, ScriptError> {
+  let invalid = |reason: &str| Err(ScriptError::new(format!("Invalid pref name \"{}\": {}", name, reason)));
+  if name.is_empty() {
+    return invalid("the name is empty");
+  }
+  if name.chars().any(|c| matches!(c, '\\' | '/' | ':' | '*' | '?' | '"' | '<' | '>' | '|') || c.is_control()) {
+    return invalid("it must be a file name, not a path");
+  }
+  if name.starts_with('.') || name.ends_with('.') || name.ends_with(' ') {
+    return invalid("it cannot start with a dot or end with a dot or space");
+  }
+
+  let file_name = match name.rsplit_once('.') {
+    Some((_, extension)) if PREF_EXTENSIONS.iter().any(|x| x.eq_ignore_ascii_case(extension)) => name.to_lowercase(),
+    Some(_) => return invalid("the extension must be .txt, .htm or .html"),
+    None => format!("{}.txt", name.to_lowercase()),
+  };
+  if file_name.chars().count() > MAX_PREF_NAME_LENGTH {
+    return invalid(&format!("it is longer than {} characters", MAX_PREF_NAME_LENGTH));
+  }
+  let stem = file_name.split('.').next().unwrap_or_default();
+  if RESERVED_NAMES.contains(&stem) {
+    return invalid("it is a reserved device name");
+  }
+  Ok(file_name)
+}
+
+fn get_pref_key(player: &DirPlayer, name: &str) -> Result<String, ScriptError> {
+  Ok(format!("{}/{}", get_pref_origin(player), get_pref_file_name(name)?))
+}
+
+/// Returns the value stored with `setPref`, or `None` when there is none.
+pub fn get_pref(player: &DirPlayer, name: &str) -> Result<Option<String>, ScriptError> {
+  let key = get_pref_key(player, name)?;
+  Ok(platform().prefs.get(&key))
+}
+
+pub fn set_pref(player: &DirPlayer, name: &str, value: &str) -> Result<(), ScriptError> {
+  let key = get_pref_key(player, name)?;
+  if value.len() > MAX_PREF_SIZE {
+    return Err(ScriptError::new(format!(
+      "Pref \"{}\" is {} bytes, over the limit of {} bytes",
+      name,
+      value.len(),
+      MAX_PREF_SIZE
+    )));
+  }
+  platform()
+    .prefs
+    .set(&key, value)
+    .map_err(|err| ScriptError::new(format!("Could not set pref \"{}\": {}", name, err)))
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    director::lingo::datum::Datum,
+    platform::pref_storage::PrefStorage,
+    player::{handlers::movie::MovieHandlers, reserve_player_mut, testing::with_test_player},
+  };
+
+  use super::*;
+
+  fn set_base_path(base_path: &str) {
+    reserve_player_mut(|player| player.movie.base_path = base_path.to_string());
+  }
+
+  fn get(name: &str) -> Result<Option<String>, ScriptError> {
+    reserve_player_mut(|player| get_pref(player, name))
+  }
+
+  fn set(name: &str, value: &str) -> Result<(), ScriptError> {
+    reserve_player_mut(|player| set_pref(player, name, value))
+  }
+
+  #[test]
+  fn round_trips_values() {
+    with_test_player(|_| {
+      set_base_path("http://example.test/games/");
+      assert_eq!(get("scores").unwrap(), None);
+      set("scores", "alice 10\rbob 7").unwrap();
+      assert_eq!(get("scores").unwrap().as_deref(), Some("alice 10\rbob 7"));
+      // The same file, as names without an extension get .txt and case is ignored
+      assert_eq!(get("SCORES.TXT").unwrap().as_deref(), Some("alice 10\rbob 7"));
+      set("scores", "").unwrap();
+      assert_eq!(get("scores").unwrap().as_deref(), Some(""));
+    });
+  }
+
+  #[test]
+  fn keys_prefs_by_the_origin_of_the_movie() {
+    with_test_player(|platform| {
+      set_base_path("http://example.test:8080/games/");
+      set("level", "3").unwrap();
+      assert_eq!(platform.prefs.get("example.test_8080/level.txt").as_deref(), Some("3"));
+
+      // Movies from another directory of the same host share the pref, other hosts don't
+      set_base_path("http://example.test:8080/other/");
+      assert_eq!(get("level").unwrap().as_deref(), Some("3"));
+      set_base_path("http://example.test/games/");
+      assert_eq!(get("level").unwrap(), None);
+      set_base_path("C:\\Games\\");
+      set("level", "5").unwrap();
+      assert_eq!(platform.prefs.get("local/level.txt").as_deref(), Some("5"));
+    });
+  }
+
+  #[test]
+  fn rejects_names_that_are_not_file_names() {
+    assert_eq!(get_pref_file_name("Save.HTM").unwrap(), "save.htm");
+    assert_eq!(get_pref_file_name("high scores").unwrap(), "high scores.txt");
+    let too_long = "a".repeat(MAX_PREF_NAME_LENGTH - 3);
+    for name in ["", "../save", "dir/save", "c:save", ".hidden", "save.", "save ", "save.exe", "con", "LPT1.txt", &too_long] {
+      assert!(get_pref_file_name(name).is_err(), "{}", name);
+    }
+    assert!(get_pref_file_name(&"a".repeat(MAX_PREF_NAME_LENGTH - 4)).is_ok());
+  }
+
+  #[test]
+  fn rejects_values_over_the_size_limit() {
+    with_test_player(|_| {
+      set_base_path("http://example.test/");
+      assert!(set("big", &"x".repeat(MAX_PREF_SIZE)).is_ok());
+      assert!(set("big", &"x".repeat(MAX_PREF_SIZE + 1)).is_err());
+      assert_eq!(get("big").unwrap().map(|x| x.len()), Some(MAX_PREF_SIZE));
+    });
+  }
+
+  #[test]
+  fn checks_the_arguments_of_the_handlers() {
+    with_test_player(|_| {
+      set_base_path("http://example.test/");
+      let name = reserve_player_mut(|player| player.alloc_datum(Datum::String("level".to_string())));
+      assert!(MovieHandlers::get_pref(&vec![]).is_err());
+      assert!(MovieHandlers::set_pref(&vec![name.clone()]).is_err());
+      assert!(MovieHandlers::get_pref(&vec![name]).is_ok());
+    });
+  }
+}
@@ -17,6 +17,8 @@ pub struct Scope {
   pub return_value: DatumRef,
   pub stack: Vec<DatumRef>,
   pub passed: bool,
+  /// Targets of the enclosing `tell` blocks, innermost last.
+  pub tell_targets: Vec<DatumRef>,
 }
 
 pub struct ScopeResult {
@@ -46,6 +48,7 @@ impl Scope {
       return_value: DatumRef::Void,
       stack: vec![],
       passed: false,
+      tell_targets: vec![],
     }
   }
 
@@ -60,5 +63,6 @@ impl Scope {
     self.return_value = DatumRef::Void;
     self.stack.clear();
     self.passed = false;
+    self.tell_targets.clear();
   }
 }
\ No newline at end of file
@@ -1,10 +1,11 @@
 use std::cmp::max;
 
 use itertools::Itertools;
+use log::warn;
 
-use crate::{director::{file::DirectorFile, lingo::datum::{datum_bool, Datum, DatumType}}, js_api::JsApi};
+use crate::{director::{chunks::{frame_labels::FrameLabel, score::{ScoreFrameChannelData, NUM_MAIN_CHANNELS}}, file::DirectorFile, lingo::datum::{datum_bool, Datum, DatumType}}, js_api::JsApi};
 
-use super::{cast_lib::{cast_member_ref, NULL_CAST_MEMBER_REF}, cast_member::CastMemberType, geometry::{IntRect, IntRectTuple}, handlers::datum_handlers::cast_member_ref::CastMemberRefHandlers, reserve_player_mut, script::script_set_prop, script_ref::ScriptInstanceRef, sprite::{ColorRef, CursorRef, Sprite}, DirPlayer, ScriptError};
+use super::{allocator::ScriptInstanceAllocatorTrait, bitmap::mask::BitmapMask, cast_lib::{cast_member_ref, CastMemberRef, NULL_CAST_MEMBER_REF}, cast_member::CastMemberType, events::{player_begin_event, player_end_event, player_invoke_event_to_each_instance, player_invoke_static_script_event}, font::layout::{StyledText, TextLayoutCache}, geometry::{IntRect, IntRectTuple, Transform2D}, handlers::datum_handlers::cast_member_ref::CastMemberRefHandlers, reserve_player_mut, script::{get_lctx_for_script, script_set_prop, ScriptInstance}, script_ref::ScriptInstanceRef, sprite::{ColorRef, CursorRef, LingoModifiedProps, Sprite}, DirPlayer, ScriptError};
 
 #[allow(dead_code)]
 pub struct SpriteChannel {
@@ -12,6 +13,8 @@ pub struct SpriteChannel {
   pub name: String,
   pub scripted: bool,
   pub sprite: Sprite,
+  /// The member placed by the score in the current frame, if the sprite is placed by the score.
+  pub score_member: Option<CastMemberRef>,
 }
 
 impl SpriteChannel {
@@ -21,6 +24,7 @@ impl SpriteChannel {
       name: "".to_owned(),
       scripted: false,
       sprite: Sprite::new(number),
+      score_member: None,
     }
   }
 }
@@ -33,9 +37,30 @@ pub struct ScoreFrameScriptReference {
   pub cast_member: u16,
 }
 
+#[derive(Clone)]
+pub struct ScoreSpriteSpan {
+  pub sprite_number: usize,
+  pub start_frame: u32,
+  pub end_frame: u32,
+  pub behaviors: Vec<CastMemberRef>,
+}
+
+impl ScoreSpriteSpan {
+  pub fn contains_frame(&self, frame: u32) -> bool {
+    frame >= self.start_frame && frame <= self.end_frame
+  }
+}
+
 pub struct Score {
   pub channels: Vec<SpriteChannel>,
   pub script_references: Vec<ScoreFrameScriptReference>,
+  pub sprite_spans: Vec<ScoreSpriteSpan>,
+  /// Non-empty sprite channels per frame as (frame index, channel index, data), sorted by frame.
+  pub channel_data: Vec<(u32, u16, ScoreFrameChannelData)>,
+  pub active_sprite_spans: Vec<usize>,
+  pub active_script_reference: Option<ScoreFrameScriptReference>,
+  /// Sorted by frame number.
+  pub frame_labels: Vec<FrameLabel>,
 }
 
 fn get_sprite_rect(player: &DirPlayer, sprite_id: i16) -> IntRectTuple {
@@ -53,7 +78,35 @@ impl Score {
     Score {
       channels: vec![],
       script_references: vec![],
+      sprite_spans: vec![],
+      channel_data: vec![],
+      active_sprite_spans: vec![],
+      active_script_reference: None,
+      frame_labels: vec![],
+    }
+  }
+
+  pub fn get_label_frame(&self, label: &str) -> Option<u32> {
+    self.frame_labels.iter()
+      .find(|x| x.label.eq_ignore_ascii_case(label))
+      .map(|x| x.frame_num)
+  }
+
+  pub fn get_frame_label(&self, frame: u32) -> Option<&str> {
+    self.frame_labels.iter()
+      .find(|x| x.frame_num == frame)
+      .map(|x| x.label.as_str())
+  }
+
+  /// Returns the frame of the marker `offset` markers away from the current one,
+  /// where the current marker is the last one at or before `frame`. Returns 0 if there is none.
+  pub fn get_marker_frame(&self, frame: u32, offset: i32) -> u32 {
+    let current_index = self.frame_labels.partition_point(|x| x.frame_num <= frame) as i32 - 1;
+    let index = current_index + offset;
+    if index < 0 {
+      return 0;
     }
+    self.frame_labels.get(index as usize).map_or(0, |x| x.frame_num)
   }
 
   pub fn get_script_in_frame(&self, frame: u32) -> Option<ScoreFrameScriptReference> {
@@ -102,39 +155,109 @@ impl Score {
 
   pub fn load_from_dir(&mut self, dir: &DirectorFile) {
     let score_chunk = dir.score.as_ref().unwrap();
-    self.set_channel_count(score_chunk.frame_data.header.num_channels as usize);
+    let num_channels = score_chunk.frame_data.header.num_channels;
+    self.set_channel_count(num_channels.saturating_sub(NUM_MAIN_CHANNELS) as usize);
 
-    for i in 0..score_chunk.frame_interval_primaries.len() {
-      let primary = &score_chunk.frame_interval_primaries[i];
-      let secondary = if i < score_chunk.frame_interval_secondaries.len() {
-        &score_chunk.frame_interval_secondaries[i]
-      } else {
-        continue;
-      };
-
-      self.script_references.push(
-        ScoreFrameScriptReference {
-          start_frame: primary.start_frame, 
-          end_frame: primary.end_frame, 
-          cast_lib: secondary.cast_lib, 
-          cast_member: secondary.cast_member,
-        }
-      );
+    for interval in &score_chunk.frame_intervals {
+      let primary = &interval.primary;
+      if primary.channel_index == 0 {
+        let secondary = match interval.secondaries.first() {
+          Some(secondary) => secondary,
+          None => continue,
+        };
+        self.script_references.push(
+          ScoreFrameScriptReference {
+            start_frame: primary.start_frame, 
+            end_frame: primary.end_frame, 
+            cast_lib: secondary.cast_lib, 
+            cast_member: secondary.cast_member,
+          }
+        );
+      } else if primary.channel_index >= NUM_MAIN_CHANNELS as u32 {
+        self.sprite_spans.push(
+          ScoreSpriteSpan {
+            sprite_number: (primary.channel_index - NUM_MAIN_CHANNELS as u32 + 1) as usize,
+            start_frame: primary.start_frame,
+            end_frame: primary.end_frame,
+            behaviors: interval.secondaries.iter()
+              .map(|x| cast_member_ref(x.cast_lib as i32, x.cast_member as i32))
+              .collect(),
+          }
+        );
+      }
     }
+    self.channel_data = score_chunk.frame_data.frame_channel_data.clone();
+    if let Some(frame_labels) = &dir.frame_labels {
+      self.frame_labels = frame_labels.labels.iter()
+        .cloned()
+        .sorted_by_key(|x| x.frame_num)
+        .collect();
+    }
+    self.apply_channel_data(1);
 
     JsApi::dispatch_score_changed();
   }
 
   pub fn reset(&mut self) {
     for channel in &mut self.channels {
-      if channel.sprite.puppet {
-        channel.sprite.reset();
-      }
+      channel.sprite.reset();
+      channel.score_member = None;
     }
+    self.active_sprite_spans.clear();
+    self.active_script_reference = None;
 
     JsApi::dispatch_score_changed();
   }
 
+  pub fn get_frame_channel_data(channel_data: &[(u32, u16, ScoreFrameChannelData)], frame: u32) -> &[(u32, u16, ScoreFrameChannelData)] {
+    let frame_index = frame.saturating_sub(1);
+    let start = channel_data.partition_point(|x| x.0 < frame_index);
+    let end = channel_data.partition_point(|x| x.0 <= frame_index);
+    &channel_data[start..end]
+  }
+
+  /// Places the non-puppet sprites as described by the score in the given frame.
+  pub fn apply_channel_data(&mut self, frame: u32) {
+    let mut frame_data: Vec<Option<&ScoreFrameChannelData>> = vec![None; self.channels.len()];
+    for (_, channel_index, data) in Self::get_frame_channel_data(&self.channel_data, frame) {
+      let sprite_index = (channel_index - NUM_MAIN_CHANNELS) as usize;
+      if sprite_index < frame_data.len() {
+        frame_data[sprite_index] = Some(data);
+      }
+    }
+
+    for (channel, data) in self.channels.iter_mut().zip(frame_data) {
+      if channel.sprite.puppet {
+        continue;
+      }
+      let sprite = &mut channel.sprite;
+      match data {
+        Some(data) => {
+          let member_ref = cast_member_ref(data.cast_lib as i32, data.cast_member as i32);
+          // Lingo changes to a score sprite persist until the end of its span.
+          if channel.score_member.as_ref() != Some(&member_ref) {
+            sprite.lingo_modified.clear();
+          }
+          let member_changed = !sprite.lingo_modified.contains(LingoModifiedProps::MEMBER)
+            && sprite.member.as_ref() != Some(&member_ref);
+          apply_sprite_channel_data(sprite, member_ref.clone(), data);
+          channel.score_member = Some(member_ref);
+          if member_changed {
+            sprite.film_loop_frame = 1;
+            JsApi::on_sprite_member_changed(channel.number as i16);
+          }
+        }
+        None => {
+          if channel.score_member.is_some() {
+            sprite.reset();
+            channel.score_member = None;
+            JsApi::on_sprite_member_changed(channel.number as i16);
+          }
+        }
+      }
+    }
+  }
+
   pub fn get_sorted_channels(&self) -> Vec<&SpriteChannel> {
     return self.channels
       .iter()
@@ -161,6 +284,128 @@ impl Score {
   }
 }
 
+/// Ends the sprite spans that do not include the given frame and returns the behaviors that should receive `endSprite`.
+fn end_sprite_spans(player: &mut DirPlayer, frame: u32) -> Vec<ScriptInstanceRef> {
+  let score = &mut player.movie.score;
+  let mut ended_instances = vec![];
+  let mut still_active = vec![];
+  for span_index in score.active_sprite_spans.drain(..) {
+    let span = &score.sprite_spans[span_index];
+    if span.contains_frame(frame) {
+      still_active.push(span_index);
+      continue;
+    }
+    if let Some(channel) = score.channels.get_mut(span.sprite_number - 1) {
+      ended_instances.append(&mut channel.sprite.script_instance_list);
+    }
+  }
+  score.active_sprite_spans = still_active;
+  ended_instances
+}
+
+/// Starts the sprite spans that include the given frame, instantiating their behaviors.
+fn begin_sprite_spans(player: &mut DirPlayer, frame: u32) -> Result<Vec<ScriptInstanceRef>, ScriptError> {
+  let new_spans = player.movie.score.sprite_spans.iter().enumerate()
+    .filter(|(index, span)| span.contains_frame(frame) && !player.movie.score.active_sprite_spans.contains(index))
+    .map(|(index, _)| index)
+    .collect_vec();
+
+  let mut began_instances = vec![];
+  for span_index in new_spans {
+    let span = player.movie.score.sprite_spans[span_index].clone();
+    let channel = player.movie.score.channels.get(span.sprite_number - 1);
+    if channel.is_none_or(|x| x.sprite.puppet) {
+      continue;
+    }
+    player.movie.score.active_sprite_spans.push(span_index);
+
+    let sprite_num_ref = player.alloc_datum(Datum::Int(span.sprite_number as i32));
+    let mut instance_refs = vec![];
+    for behavior_ref in &span.behaviors {
+      let instance_id = player.allocator.get_free_script_instance_id();
+      let instance = {
+        let script = match player.movie.cast_manager.get_script_by_ref(behavior_ref) {
+          Some(script) => script,
+          None => continue,
+        };
+        let lctx = match get_lctx_for_script(player, script) {
+          Some(lctx) => lctx,
+          None => {
+            warn!("Behavior {:?} of sprite {} has no Lingo context", behavior_ref, span.sprite_number);
+            continue;
+          }
+        };
+        ScriptInstance::new(instance_id, behavior_ref.to_owned(), script, lctx)
+      };
+      let instance_ref = player.allocator.alloc_script_instance(instance);
+      script_set_prop(player, &instance_ref, &"spriteNum".to_string(), &sprite_num_ref, false)?;
+      instance_refs.push(instance_ref);
+    }
+
+    let sprite = player.movie.score.get_sprite_mut(span.sprite_number as i16);
+    sprite.script_instance_list.extend(instance_refs.iter().cloned());
+    began_instances.extend(instance_refs);
+  }
+  Ok(began_instances)
+}
+
+/// Sends a sprite event to the given behaviors and then to the frame script, if any.
+async fn player_invoke_sprite_event(
+  handler_name: &String,
+  instances: &Vec<ScriptInstanceRef>,
+  frame_script: Option<ScoreFrameScriptReference>,
+) -> Result<(), ScriptError> {
+  let was_stopped = player_begin_event();
+  let result = async {
+    let stopped = player_invoke_event_to_each_instance(handler_name, &vec![], instances).await?;
+    if let (false, Some(frame_script)) = (stopped, frame_script) {
+      let script_ref = cast_member_ref(frame_script.cast_lib as i32, frame_script.cast_member as i32);
+      player_invoke_static_script_event(&script_ref, handler_name, &vec![]).await?;
+    }
+    Ok(())
+  }.await;
+  player_end_event(was_stopped);
+  result
+}
+
+/// Brings the score sprites up to date with the current frame: ends the spans that were left,
+/// applies the channel data of the frame and begins the spans that were entered.
+pub async fn player_update_score_sprites() -> Result<(), ScriptError> {
+  let (ended_instances, ended_frame_script) = reserve_player_mut(|player| {
+    let frame = player.movie.current_frame;
+    let ended_frame_script = match &player.movie.score.active_script_reference {
+      Some(script_ref) if frame < script_ref.start_frame || frame > script_ref.end_frame => {
+        player.movie.score.active_script_reference.take()
+      }
+      _ => None,
+    };
+    (end_sprite_spans(player, frame), ended_frame_script)
+  });
+  player_invoke_sprite_event(&"endSprite".to_string(), &ended_instances, ended_frame_script).await?;
+
+  let (began_instances, began_frame_script) = reserve_player_mut(|player| {
+    let frame = player.movie.current_frame;
+    player.movie.score.apply_channel_data(frame);
+    let began_frame_script = if player.movie.score.active_script_reference.is_none() {
+      player.movie.score.active_script_reference = player.movie.score.get_script_in_frame(frame);
+      player.movie.score.active_script_reference.clone()
+    } else {
+      None
+    };
+    Ok::<_, ScriptError>((begin_sprite_spans(player, frame)?, began_frame_script))
+  })?;
+  player_invoke_sprite_event(&"beginSprite".to_string(), &began_instances, began_frame_script).await
+}
+
+/// Ends every active sprite span and the frame script, as when the movie stops.
+pub async fn player_end_score_sprites() -> Result<(), ScriptError> {
+  let (ended_instances, ended_frame_script) = reserve_player_mut(|player| {
+    // Frames are numbered from 1, so no span contains frame 0.
+    (end_sprite_spans(player, 0), player.movie.score.active_script_reference.take())
+  });
+  player_invoke_sprite_event(&"endSprite".to_string(), &ended_instances, ended_frame_script).await
+}
+
 pub fn sprite_get_prop(
   player: &mut DirPlayer,
   sprite_id: i16,
@@ -195,6 +440,14 @@ pub fn sprite_get_prop(
       let rect = get_sprite_rect(player, sprite_id);
       Ok(Datum::IntRect(rect))
     },
+    "quad" => {
+      let quad = sprite.map_or([(0.0, 0.0); 4], |sprite| get_sprite_quad(player, sprite));
+      let points = quad
+        .iter()
+        .map(|(x, y)| player.alloc_datum(Datum::IntPoint((x.round() as i32, y.round() as i32))))
+        .collect_vec();
+      Ok(Datum::List(DatumType::List, points, false))
+    },
     "bgColor" => Ok(Datum::ColorRef(sprite.map_or(ColorRef::PaletteIndex(0), |sprite| sprite.bg_color.clone()))),
     "skew" => Ok(Datum::Float(sprite.map_or(0.0, |sprite| sprite.skew))),
     "locH" => Ok(Datum::Int(sprite.map_or(0, |sprite| sprite.loc_h) as i32)),
@@ -209,6 +462,15 @@ pub fn sprite_get_prop(
     "flipH" => Ok(datum_bool(sprite.map_or(false, |sprite| sprite.flip_h))),
     "flipV" => Ok(datum_bool(sprite.map_or(false, |sprite| sprite.flip_v))),
     "rotation" => Ok(Datum::Float(sprite.map_or(0.0, |sprite| sprite.rotation))),
+    "puppet" => Ok(datum_bool(sprite.map_or(false, |sprite| sprite.puppet))),
+    "cursor" => match sprite.and_then(|x| x.cursor_ref.clone()) {
+      Some(CursorRef::Member(member_ids)) => {
+        let member_ids = member_ids.into_iter().map(|x| player.alloc_datum(Datum::Int(x))).collect();
+        Ok(Datum::List(DatumType::List, member_ids, false))
+      }
+      Some(CursorRef::System(cursor_id)) => Ok(Datum::Int(cursor_id)),
+      None => Ok(Datum::Int(0)),
+    },
     "scriptInstanceList" => {
       let instance_ids = sprite.map_or(vec![], |x| x.script_instance_list.clone());
       let instance_ids = instance_ids.iter().map(|x| player.alloc_datum(Datum::ScriptInstanceRef(x.clone()))).collect();
@@ -251,6 +513,14 @@ pub fn sprite_set_prop(
   value: Datum,
 ) -> Result<(), ScriptError> {
   let result = match prop_name.as_str() {
+    "puppet" => borrow_sprite_mut(
+      sprite_id,
+      |_| {},
+      |sprite, _| {
+        sprite.puppet = value.to_bool()?;
+        Ok(())
+      }
+    ),
     "visible" => borrow_sprite_mut(
       sprite_id,
       |_| {}, 
@@ -428,6 +698,7 @@ pub fn sprite_set_prop(
                 (bitmap.width, bitmap.height)
               }
               CastMemberType::Shape(shape) => (shape.shape_info.width, shape.shape_info.height),
+              CastMemberType::FilmLoop(film_loop) => (film_loop.info.width() as u16, film_loop.info.height() as u16),
               _ => (0, 0),
             }
           }
@@ -441,6 +712,7 @@ pub fn sprite_set_prop(
           sprite.height = height as i32;
         }
         sprite.member = mem_ref;
+        sprite.film_loop_frame = 1;
         JsApi::on_sprite_member_changed(sprite_id);
         Ok(())
       }
@@ -514,19 +786,26 @@ pub fn sprite_set_prop(
         let reg_point = cast_member.map(
           |x| {
             match &x.member_type {
-              CastMemberType::Bitmap(bitmap) => bitmap.reg_point,
-              _ => (0, 0),
+              CastMemberType::Bitmap(bitmap) => {
+                let bitmap_size = player.bitmap_manager.get_bitmap(bitmap.image_ref).map(|x| (x.width, x.height));
+                (bitmap.reg_point, bitmap_size)
+              }
+              _ => ((0, 0), None),
             }
           }
-        ).unwrap_or((0, 0));
+        ).unwrap_or(((0, 0), None));
 
         reg_point
       },
-      |sprite, reg_point| {
+      |sprite, (reg_point, bitmap_size)| {
         match value {
           Datum::IntRect((left, top, right, bottom)) => {
-            sprite.loc_h = left + reg_point.0 as i32;
-            sprite.loc_v = top + reg_point.1 as i32;
+            let reg_point = match bitmap_size {
+              Some(bitmap_size) => get_stretched_reg_point(reg_point, bitmap_size, (right - left, bottom - top)),
+              None => (reg_point.0 as i32, reg_point.1 as i32),
+            };
+            sprite.loc_h = left + reg_point.0;
+            sprite.loc_v = top + reg_point.1;
             sprite.width = right - left;
             sprite.height = bottom - top;
             Ok(())
@@ -585,23 +864,193 @@ pub fn sprite_set_prop(
     _ => Err(ScriptError::new(format!("Cannot set prop {} of sprite", prop_name))),
   };
   if result.is_ok() {
+    reserve_player_mut(|player| {
+      let sprite = player.movie.score.get_sprite_mut(sprite_id);
+      sprite.lingo_modified.insert(LingoModifiedProps::for_sprite_prop(prop_name));
+    });
     JsApi::dispatch_channel_changed(sprite_id);
   }
   result
 }
 
+/// Copies the properties of a score channel to a sprite, except those changed by Lingo.
+fn apply_sprite_channel_data(sprite: &mut Sprite, member_ref: CastMemberRef, data: &ScoreFrameChannelData) {
+  let modified = sprite.lingo_modified;
+  let keep = |prop| modified.contains(prop);
+  if !keep(LingoModifiedProps::MEMBER) {
+    sprite.member = Some(member_ref);
+  }
+  if !keep(LingoModifiedProps::LOC_H) {
+    sprite.loc_h = data.pos_x as i32;
+  }
+  if !keep(LingoModifiedProps::LOC_V) {
+    sprite.loc_v = data.pos_y as i32;
+  }
+  if !keep(LingoModifiedProps::WIDTH) {
+    sprite.width = data.width as i32;
+  }
+  if !keep(LingoModifiedProps::HEIGHT) {
+    sprite.height = data.height as i32;
+  }
+  if !keep(LingoModifiedProps::INK) {
+    sprite.ink = data.ink_number() as i32;
+  }
+  if !keep(LingoModifiedProps::BLEND) {
+    sprite.blend = data.blend_percent() as i32;
+  }
+  if !keep(LingoModifiedProps::STRETCH) {
+    sprite.stretch = if data.stretch() { 1 } else { 0 };
+  }
+  if !keep(LingoModifiedProps::EDITABLE) {
+    sprite.editable = data.editable();
+  }
+  if !keep(LingoModifiedProps::ROTATION) {
+    sprite.rotation = data.rotation;
+  }
+  if !keep(LingoModifiedProps::SKEW) {
+    sprite.skew = data.skew;
+  }
+  if !keep(LingoModifiedProps::COLOR) {
+    sprite.color = if data.color_flag & 0x1 != 0 {
+      ColorRef::Rgb(data.fore_color, data.fore_color_g, data.fore_color_b)
+    } else {
+      ColorRef::PaletteIndex(data.fore_color)
+    };
+  }
+  if !keep(LingoModifiedProps::BG_COLOR) {
+    sprite.bg_color = if data.color_flag & 0x2 != 0 {
+      ColorRef::Rgb(data.back_color, data.back_color_g, data.back_color_b)
+    } else {
+      ColorRef::PaletteIndex(data.back_color)
+    };
+  }
+  if !keep(LingoModifiedProps::BACK_COLOR) {
+    sprite.back_color = data.back_color as i32;
+  }
+}
+
+/// How deep film loops may be nested inside each other before the inner ones are left out.
+const MAX_FILM_LOOP_DEPTH: usize = 8;
+
+/// Builds the sprites of the current frame of a film loop, positioned on the stage relative to the
+/// rect of the sprite showing it. Film loops nested inside the loop are expanded into their own sprites,
+/// so the result never contains film loop sprites. Returns an empty list if the sprite does not show a film loop.
+pub fn get_film_loop_child_sprites(player: &DirPlayer, sprite: &Sprite) -> Vec<Sprite> {
+  get_cropped_film_loop_child_sprites(player, sprite).into_iter().map(|(child, _)| child).collect()
+}
+
+/// Like `get_film_loop_child_sprites`, along with the stage rect each sprite is cropped to by the
+/// cropping loops showing it, if any.
+pub fn get_cropped_film_loop_child_sprites(player: &DirPlayer, sprite: &Sprite) -> Vec<(Sprite, Option<IntRect>)> {
+  let mut result = vec![];
+  let mut ancestors = vec![];
+  collect_film_loop_child_sprites(player, sprite, None, &mut ancestors, &mut result);
+  result
+}
+
+fn collect_film_loop_child_sprites(
+  player: &DirPlayer,
+  sprite: &Sprite,
+  clip_rect: Option<IntRect>,
+  ancestors: &mut Vec<CastMemberRef>,
+  result: &mut Vec<(Sprite, Option<IntRect>)>,
+) {
+  let member_ref = match &sprite.member {
+    Some(member_ref) => member_ref,
+    None => return,
+  };
+  let film_loop = match player.movie.cast_manager.find_member_by_ref(member_ref).and_then(|x| x.member_type.as_film_loop()) {
+    Some(film_loop) => film_loop,
+    None => return,
+  };
+  // A loop containing itself, directly or through other loops, would never finish expanding
+  if ancestors.contains(member_ref) || ancestors.len() >= MAX_FILM_LOOP_DEPTH {
+    warn!("Film loop member {} of castLib {} is nested too deeply", member_ref.cast_member, member_ref.cast_lib);
+    return;
+  }
+  let sprite_rect = get_untransformed_sprite_rect(player, sprite);
+  let (loop_left, loop_top, _, _) = film_loop.info.rect;
+  // Cropping loops keep their size and are cut off by the sprite rect, the others are scaled to fit it
+  let (scale_x, scale_y, origin_x, origin_y, clip_rect) = if film_loop.info.crop {
+    let (offset_x, offset_y) = if film_loop.info.center {
+      ((sprite_rect.width() - film_loop.info.width()) / 2, (sprite_rect.height() - film_loop.info.height()) / 2)
+    } else {
+      (0, 0)
+    };
+    let clip_rect = clip_rect.map_or(sprite_rect, |clip_rect| clip_rect.intersect(&sprite_rect));
+    (1.0, 1.0, sprite_rect.left + offset_x, sprite_rect.top + offset_y, Some(clip_rect))
+  } else {
+    let scale_x = sprite_rect.width() as f32 / film_loop.info.width().max(1) as f32;
+    let scale_y = sprite_rect.height() as f32 / film_loop.info.height().max(1) as f32;
+    (scale_x, scale_y, sprite_rect.left, sprite_rect.top, clip_rect)
+  };
+
+  ancestors.push(member_ref.clone());
+  for (_, channel_index, data) in Score::get_frame_channel_data(&film_loop.channel_data, sprite.film_loop_frame) {
+    // Loops recorded in older movies leave the cast lib empty, meaning the loop's own cast
+    let cast_lib = if data.cast_lib == 0 { member_ref.cast_lib } else { data.cast_lib as i32 };
+    let mut child = Sprite::new((channel_index - NUM_MAIN_CHANNELS) as usize + 1);
+    apply_sprite_channel_data(&mut child, cast_member_ref(cast_lib, data.cast_member as i32), data);
+    child.loc_h = origin_x + ((data.pos_x as i32 - loop_left as i32) as f32 * scale_x) as i32;
+    child.loc_v = origin_y + ((data.pos_y as i32 - loop_top as i32) as f32 * scale_y) as i32;
+    child.width = (data.width as f32 * scale_x) as i32;
+    child.height = (data.height as f32 * scale_y) as i32;
+    child.flip_h = sprite.flip_h;
+    child.flip_v = sprite.flip_v;
+
+    let child_loop = child.member.as_ref()
+      .and_then(|x| player.movie.cast_manager.find_member_by_ref(x))
+      .and_then(|x| x.member_type.as_film_loop());
+    match child_loop {
+      Some(child_loop) => {
+        // Nested loops have no sprite of their own to keep their frame, so they play along with the outer loop
+        child.film_loop_frame = get_nested_film_loop_frame(sprite.film_loop_frame, child_loop.frame_count, child_loop.info.looping);
+        collect_film_loop_child_sprites(player, &child, clip_rect, ancestors, result);
+      }
+      None => result.push((child, clip_rect)),
+    }
+  }
+  ancestors.pop();
+}
+
+/// Returns the frame of a film loop nested in another loop that is showing the given frame.
+fn get_nested_film_loop_frame(outer_frame: u32, frame_count: u32, looping: bool) -> u32 {
+  let elapsed = outer_frame.max(1) - 1;
+  if frame_count == 0 {
+    1
+  } else if looping {
+    elapsed % frame_count + 1
+  } else {
+    (elapsed + 1).min(frame_count)
+  }
+}
+
+/// Moves every sprite showing a film loop to the next frame of the loop.
+pub fn advance_film_loops(player: &mut DirPlayer) {
+  let cast_manager = &player.movie.cast_manager;
+  for channel in player.movie.score.channels.iter_mut() {
+    let sprite = &mut channel.sprite;
+    let film_loop = sprite.member.as_ref()
+      .and_then(|member_ref| cast_manager.find_member_by_ref(member_ref))
+      .and_then(|member| member.member_type.as_film_loop());
+    if let Some(film_loop) = film_loop {
+      if sprite.film_loop_frame < film_loop.frame_count {
+        sprite.film_loop_frame += 1;
+      } else if film_loop.info.looping {
+        sprite.film_loop_frame = 1;
+      }
+    }
+  }
+}
+
 pub fn concrete_sprite_hit_test(
   player: &DirPlayer,
   sprite: &Sprite,
   x: i32,
   y: i32,
 ) -> bool {
-  let rect = get_concrete_sprite_rect(player, sprite);
-  let left = rect.left;
-  let top = rect.top;
-  let right = rect.right;
-  let bottom = rect.bottom;
-  return x >= left && x < right && y >= top && y < bottom;
+  let rect = get_untransformed_sprite_rect(player, sprite);
+  sprite_contains_point(sprite, &rect, get_sprite_matte(player, sprite), x, y)
 }
 
 pub fn get_sprite_at(player: &DirPlayer, x: i32, y: i32, scripted: bool) -> Option<u32> {
@@ -614,7 +1063,35 @@ pub fn get_sprite_at(player: &DirPlayer, x: i32, y: i32, scripted: bool) -> Opti
   return None;
 }
 
+/// Returns the rotation and skew of the sprite around its loc, which is where its reg point sits on the stage.
+pub fn get_sprite_transform(sprite: &Sprite) -> Option<Transform2D> {
+  if sprite.rotation == 0.0 && sprite.skew == 0.0 {
+    return None;
+  }
+  Some(Transform2D::rotate_skew_around(
+    (sprite.loc_h as f64, sprite.loc_v as f64),
+    sprite.rotation as f64,
+    sprite.skew as f64,
+  ))
+}
+
+/// Returns the bounding rect of the sprite on the stage, including its rotation and skew.
 pub fn get_concrete_sprite_rect(player: &DirPlayer, sprite: &Sprite) -> IntRect {
+  let rect = get_untransformed_sprite_rect(player, sprite);
+  match get_sprite_transform(sprite) {
+    Some(transform) => transform.bounding_rect(&rect),
+    None => rect,
+  }
+}
+
+/// Returns the corners of the sprite on the stage, clockwise from the top left.
+pub fn get_sprite_quad(player: &DirPlayer, sprite: &Sprite) -> [(f64, f64); 4] {
+  let rect = get_untransformed_sprite_rect(player, sprite);
+  get_sprite_transform(sprite).unwrap_or(Transform2D::IDENTITY).quad(&rect)
+}
+
+/// Returns the rect of the sprite on the stage before its rotation and skew are applied.
+pub fn get_untransformed_sprite_rect(player: &DirPlayer, sprite: &Sprite) -> IntRect {
   let member = sprite.member.as_ref().and_then(|member_ref| 
     player
       .movie
@@ -630,19 +1107,14 @@ pub fn get_concrete_sprite_rect(player: &DirPlayer, sprite: &Sprite) -> IntRect
     CastMemberType::Bitmap(bitmap_member) => {
         let sprite_bitmap = player.bitmap_manager.get_bitmap(bitmap_member.image_ref);
         if sprite_bitmap.is_none() {
-          return IntRect::from(sprite.loc_h, sprite.loc_v, sprite.width, sprite.height);
+          return IntRect::from_size(sprite.loc_h, sprite.loc_v, sprite.width, sprite.height);
         }
         let src_bitmap = sprite_bitmap.unwrap();
         let reg_x = if sprite.flip_h { src_bitmap.width as i16 - bitmap_member.reg_point.0 } else { bitmap_member.reg_point.0 };
         let reg_y = if sprite.flip_v { src_bitmap.height as i16 - bitmap_member.reg_point.1 } else { bitmap_member.reg_point.1 };
+        let (reg_x, reg_y) = get_stretched_reg_point((reg_x, reg_y), (src_bitmap.width, src_bitmap.height), (sprite.width, sprite.height));
 
-        let dst_rect = IntRect::from(
-            sprite.loc_h - reg_x as i32,
-            sprite.loc_v - reg_y as i32, 
-            sprite.loc_h - reg_x as i32 + sprite.width, 
-            sprite.loc_v - reg_y as i32 + sprite.height
-        );
-        dst_rect
+        IntRect::from_size(sprite.loc_h - reg_x, sprite.loc_v - reg_y, sprite.width, sprite.height)
     }
     CastMemberType::Shape(shape_member) => {
         let reg_x = shape_member.shape_info.reg_point.0;
@@ -654,8 +1126,451 @@ pub fn get_concrete_sprite_rect(player: &DirPlayer, sprite: &Sprite) -> IntRect
           sprite.height + sprite.loc_v - reg_y as i32,
         )
     }
-    CastMemberType::Field(field_member) => IntRect::from_size(sprite.loc_h, sprite.loc_v, field_member.width as i32, 12), // TODO
-    CastMemberType::Text(text_member) => IntRect::from_size(sprite.loc_h, sprite.loc_v, text_member.width as i32, 12), // TODO
+    CastMemberType::FilmLoop(film_loop) => {
+        let (reg_x, reg_y) = film_loop.info.reg_point();
+        IntRect::from_size(sprite.loc_h - reg_x as i32, sprite.loc_v - reg_y as i32, sprite.width, sprite.height)
+    }
+    CastMemberType::Field(field_member) => {
+      let height = get_text_box_height(player, &field_member.layout_cache, &field_member.styled_text(), &field_member.box_type, field_member.height);
+      IntRect::from_size(sprite.loc_h, sprite.loc_v, field_member.width as i32, height)
+    }
+    CastMemberType::Text(text_member) => {
+      let height = get_text_box_height(player, &text_member.layout_cache, &text_member.styled_text(), &text_member.box_type, text_member.height);
+      IntRect::from_size(sprite.loc_h, sprite.loc_v, text_member.width as i32, height)
+    }
     _ => IntRect::from_size(sprite.loc_h, sprite.loc_v, sprite.width, sprite.height)
   }
 }
+
+/// Moves the reg point of a bitmap along with its pixels when a sprite stretches it to `sprite_size`.
+pub fn get_stretched_reg_point(reg_point: (i16, i16), bitmap_size: (u16, u16), sprite_size: (i32, i32)) -> (i32, i32) {
+  let stretch = |reg: i16, bitmap_size: u16, sprite_size: i32| {
+    if bitmap_size == 0 {
+      reg as i32
+    } else {
+      reg as i32 * sprite_size / bitmap_size as i32
+    }
+  };
+  (stretch(reg_point.0, bitmap_size.0, sprite_size.0), stretch(reg_point.1, bitmap_size.1, sprite_size.1))
+}
+
+/// "adjust" boxes grow to fit their text, other box types keep the height stored in the member.
+pub fn get_text_box_height(player: &DirPlayer, layout_cache: &TextLayoutCache, text: &StyledText, box_type: &str, member_height: u16) -> i32 {
+  if box_type == "adjust" || member_height == 0 {
+    layout_cache.layout(&player.font_manager, text).height
+  } else {
+    member_height as i32
+  }
+}
+
+/// Matte ink. Sprites using it are tested against their actual outline instead of their bounding rect.
+const INK_MATTE: i32 = 8;
+
+/// Creates the matte of a matte-ink bitmap sprite if it has not been created yet.
+fn ensure_sprite_matte(player: &mut DirPlayer, sprite_num: i16) {
+  let sprite = match player.movie.score.get_sprite(sprite_num) {
+    Some(sprite) if sprite.ink == INK_MATTE => sprite,
+    _ => return,
+  };
+  let image_ref = sprite.member.as_ref()
+    .and_then(|member_ref| player.movie.cast_manager.find_member_by_ref(member_ref))
+    .and_then(|member| match &member.member_type {
+      CastMemberType::Bitmap(bitmap_member) => Some(bitmap_member.image_ref),
+      _ => None,
+    });
+  let palettes = player.movie.cast_manager.palettes();
+  if let Some(bitmap) = image_ref.and_then(|image_ref| player.bitmap_manager.get_bitmap_mut(image_ref)) {
+    if bitmap.matte.is_none() {
+      bitmap.create_matte(&palettes);
+    }
+  }
+}
+
+fn get_sprite_matte<'a>(player: &'a DirPlayer, sprite: &Sprite) -> Option<&'a BitmapMask> {
+  if sprite.ink != INK_MATTE {
+    return None;
+  }
+  let member = sprite.member.as_ref().and_then(|member_ref| player.movie.cast_manager.find_member_by_ref(member_ref))?;
+  match &member.member_type {
+    CastMemberType::Bitmap(bitmap_member) => {
+      player.bitmap_manager.get_bitmap(bitmap_member.image_ref)?.matte.as_deref()
+    }
+    _ => None,
+  }
+}
+
+/// Tests a stage point against a sprite, undoing its rotation and skew to find the point in its
+/// untransformed rect. With a matte, the matte is scaled and flipped to fit the rect.
+fn sprite_contains_point(sprite: &Sprite, rect: &IntRect, matte: Option<&BitmapMask>, x: i32, y: i32) -> bool {
+  let (x, y) = match get_sprite_transform(sprite).and_then(|transform| transform.inverse()) {
+    Some(inverse) => inverse.apply(x as f64 + 0.5, y as f64 + 0.5),
+    None => (x as f64 + 0.5, y as f64 + 0.5),
+  };
+  if x < rect.left as f64 || x >= rect.right as f64 || y < rect.top as f64 || y >= rect.bottom as f64 {
+    return false;
+  }
+  let matte = match matte {
+    Some(matte) => matte,
+    None => return true,
+  };
+  let mut matte_x = ((x - rect.left as f64) * matte.width as f64 / rect.width().max(1) as f64) as i64;
+  let mut matte_y = ((y - rect.top as f64) * matte.height as f64 / rect.height().max(1) as f64) as i64;
+  if sprite.flip_h {
+    matte_x = matte.width as i64 - 1 - matte_x;
+  }
+  if sprite.flip_v {
+    matte_y = matte.height as i64 - 1 - matte_y;
+  }
+  matte.get_bit(matte_x as u16, matte_y as u16)
+}
+
+/// Implements `sprite a intersects b`. When both sprites use matte ink their outlines are compared
+/// instead of their bounding rects.
+pub fn sprite_intersects(player: &mut DirPlayer, sprite_a: i16, sprite_b: i16) -> bool {
+  ensure_sprite_matte(player, sprite_a);
+  ensure_sprite_matte(player, sprite_b);
+  let (sprite_a, sprite_b) = match (player.movie.score.get_sprite(sprite_a), player.movie.score.get_sprite(sprite_b)) {
+    (Some(a), Some(b)) => (a, b),
+    _ => return false,
+  };
+  let rect_a = get_untransformed_sprite_rect(player, sprite_a);
+  let rect_b = get_untransformed_sprite_rect(player, sprite_b);
+  let intersection = get_concrete_sprite_rect(player, sprite_a).intersect(&get_concrete_sprite_rect(player, sprite_b));
+  if intersection.width() <= 0 || intersection.height() <= 0 {
+    return false;
+  }
+  let (matte_a, matte_b) = match (get_sprite_matte(player, sprite_a), get_sprite_matte(player, sprite_b)) {
+    (Some(matte_a), Some(matte_b)) => (Some(matte_a), Some(matte_b)),
+    _ if get_sprite_transform(sprite_a).is_none() && get_sprite_transform(sprite_b).is_none() => return true,
+    _ => (None, None),
+  };
+  (intersection.top..intersection.bottom).any(|y| {
+    (intersection.left..intersection.right).any(|x| {
+      sprite_contains_point(sprite_a, &rect_a, matte_a, x, y) && sprite_contains_point(sprite_b, &rect_b, matte_b, x, y)
+    })
+  })
+}
+
+/// Implements `sprite a within b`. When both sprites use matte ink, every opaque pixel of the first
+/// sprite must be covered by an opaque pixel of the second.
+pub fn sprite_within(player: &mut DirPlayer, sprite_a: i16, sprite_b: i16) -> bool {
+  ensure_sprite_matte(player, sprite_a);
+  ensure_sprite_matte(player, sprite_b);
+  let (sprite_a, sprite_b) = match (player.movie.score.get_sprite(sprite_a), player.movie.score.get_sprite(sprite_b)) {
+    (Some(a), Some(b)) => (a, b),
+    _ => return false,
+  };
+  let rect_a = get_untransformed_sprite_rect(player, sprite_a);
+  let rect_b = get_untransformed_sprite_rect(player, sprite_b);
+  let (matte_a, matte_b) = match (get_sprite_matte(player, sprite_a), get_sprite_matte(player, sprite_b)) {
+    (Some(matte_a), Some(matte_b)) => (Some(matte_a), Some(matte_b)),
+    _ if get_sprite_transform(sprite_a).is_none() && get_sprite_transform(sprite_b).is_none() => {
+      return rect_a.left >= rect_b.left && rect_a.top >= rect_b.top && rect_a.right <= rect_b.right && rect_a.bottom <= rect_b.bottom;
+    }
+    _ => (None, None),
+  };
+  let bounds_a = get_concrete_sprite_rect(player, sprite_a);
+  (bounds_a.top..bounds_a.bottom).all(|y| {
+    (bounds_a.left..bounds_a.right).all(|x| {
+      !sprite_contains_point(sprite_a, &rect_a, matte_a, x, y) || sprite_contains_point(sprite_b, &rect_b, matte_b, x, y)
+    })
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use binary_reader::{BinaryReader, Endian};
+
+  use async_std::task::block_on;
+
+  use crate::{
+    director::{chunks::score::ScoreFrameData, enums::ScriptType},
+    player::{
+      bitmap::{bitmap::{get_system_default_palette, Bitmap, PaletteRef}, palette_map::PaletteMap},
+      cast_member::BitmapMember,
+      events::{player_invoke_global_event, player_unwrap_result},
+      reserve_player_mut,
+      reserve_player_ref,
+      testing::{add_test_cast, add_test_scripts, film_loop_score_data, test_film_loop, with_test_player, FilmLoopSprite},
+    },
+  };
+
+  use super::*;
+
+  /// Returns the member and loc of the sprites shown by a sprite of the given film loop at the given loop frame.
+  fn child_sprites(cast_lib: u32, member: u32, loc: (i32, i32), size: (i32, i32), frame: u32) -> Vec<(i32, (i32, i32))> {
+    let mut sprite = Sprite::new(1);
+    sprite.member = Some(cast_member_ref(cast_lib as i32, member as i32));
+    (sprite.loc_h, sprite.loc_v) = loc;
+    (sprite.width, sprite.height) = size;
+    sprite.film_loop_frame = frame;
+    reserve_player_ref(|player| {
+      get_film_loop_child_sprites(player, &sprite)
+        .iter()
+        .map(|x| (x.member.as_ref().unwrap().cast_member, (x.loc_h, x.loc_v)))
+        .collect_vec()
+    })
+  }
+
+  /// A 10x10 bitmap, black inside the given rect and white around it.
+  fn framed_bitmap(black: (i32, i32, i32, i32)) -> CastMemberType {
+    let palettes = PaletteMap::new();
+    let mut bitmap = Bitmap::new(10, 10, 32, PaletteRef::BuiltIn(get_system_default_palette()));
+    bitmap.fill_rect(0, 0, 10, 10, (255, 255, 255), &palettes, 1.0);
+    bitmap.fill_rect(black.0, black.1, black.2, black.3, (0, 0, 0), &palettes, 1.0);
+    let image_ref = reserve_player_mut(|player| player.bitmap_manager.add_bitmap(bitmap));
+    CastMemberType::Bitmap(BitmapMember { image_ref, reg_point: (0, 0) })
+  }
+
+  /// Places bitmap sprites as (member, loc, ink), along with a movie script that compares sprites 1 and 2.
+  fn add_bitmap_sprites(sprites: &[(u32, (i32, i32), i32)]) {
+    let cast_lib = add_test_cast(vec![
+      (1, "dot", framed_bitmap((3, 3, 7, 7))),
+      (2, "block", framed_bitmap((0, 0, 10, 10))),
+    ]);
+    add_test_scripts(vec![(1, "main", ScriptType::Movie, "on test\r  global gResult\r  gResult = [sprite 1 intersects 2, sprite 1 within 2]\rend\r")]);
+    reserve_player_mut(|player| {
+      player.movie.score.set_channel_count(sprites.len());
+      for (index, (member, loc, ink)) in sprites.iter().enumerate() {
+        let sprite = player.movie.score.get_sprite_mut(index as i16 + 1);
+        sprite.member = Some(cast_member_ref(cast_lib as i32, *member as i32));
+        (sprite.loc_h, sprite.loc_v) = *loc;
+        (sprite.width, sprite.height) = (10, 10);
+        sprite.ink = *ink;
+      }
+    });
+  }
+
+  /// Returns `sprite 1 intersects 2` and `sprite 1 within 2` as run by the OntoSpr and IntoSpr opcodes.
+  fn compare_sprites() -> (bool, bool) {
+    player_unwrap_result(block_on(player_invoke_global_event(&"test".to_string(), &vec![])));
+    reserve_player_ref(|player| {
+      let items = player.get_datum(&player.globals["gResult"]).to_list().unwrap().iter()
+        .map(|x| player.get_datum(x).int_value().unwrap() != 0)
+        .collect_vec();
+      (items[0], items[1])
+    })
+  }
+
+  #[test]
+  fn intersects_compares_bounding_rects_without_matte_ink() {
+    with_test_player(|_| {
+      add_bitmap_sprites(&[(1, (0, 0), 0), (1, (8, 8), 0)]);
+      assert_eq!(compare_sprites(), (true, false));
+      reserve_player_mut(|player| player.movie.score.get_sprite_mut(2).loc_h = 10);
+      assert_eq!(compare_sprites(), (false, false));
+    });
+  }
+
+  #[test]
+  fn intersects_compares_the_outlines_of_matte_sprites() {
+    with_test_player(|_| {
+      // Only the white borders overlap
+      add_bitmap_sprites(&[(1, (0, 0), 8), (1, (8, 8), 8)]);
+      assert_eq!(compare_sprites(), (false, false));
+      // The black squares overlap by two pixels
+      reserve_player_mut(|player| (player.movie.score.get_sprite_mut(2).loc_h, player.movie.score.get_sprite_mut(2).loc_v) = (2, 2));
+      assert_eq!(compare_sprites(), (true, false));
+      // With a single matte sprite the rects are compared
+      reserve_player_mut(|player| player.movie.score.get_sprite_mut(2).loc_h = 8);
+      reserve_player_mut(|player| player.movie.score.get_sprite_mut(1).ink = 0);
+      assert!(compare_sprites().0);
+    });
+  }
+
+  #[test]
+  fn within_compares_the_outlines_of_matte_sprites() {
+    with_test_player(|_| {
+      // The dot sticks out of the block's rect, but its black square does not
+      add_bitmap_sprites(&[(1, (3, 3), 8), (2, (0, 0), 8)]);
+      assert_eq!(compare_sprites(), (true, true));
+      reserve_player_mut(|player| player.movie.score.get_sprite_mut(1).loc_h = 5);
+      assert_eq!(compare_sprites(), (true, false));
+      // Without matte ink, only the rects count
+      reserve_player_mut(|player| player.movie.score.get_sprite_mut(1).loc_h = 3);
+      reserve_player_mut(|player| player.movie.score.get_sprite_mut(1).ink = 0);
+      assert_eq!(compare_sprites(), (true, false));
+    });
+  }
+
+  #[test]
+  fn hit_tests_rotated_and_skewed_sprites_through_their_transform() {
+    with_test_player(|_| {
+      // A 10x10 black block registered at its top left corner, at (20, 20)
+      add_bitmap_sprites(&[(2, (20, 20), 0)]);
+      let sprite_at = |x, y| reserve_player_ref(|player| get_sprite_at(player, x, y, false));
+      assert_eq!((sprite_at(25, 25), sprite_at(15, 25)), (Some(1), None));
+
+      // A quarter turn clockwise around the reg point swings the block to its left
+      reserve_player_mut(|player| player.movie.score.get_sprite_mut(1).rotation = 90.0);
+      assert_eq!((sprite_at(25, 25), sprite_at(15, 25)), (None, Some(1)));
+      assert_eq!(sprite_at(15, 31), None);
+
+      // Skewing by 45 degrees moves the rows below the reg point to the left, one pixel per row
+      reserve_player_mut(|player| {
+        let sprite = player.movie.score.get_sprite_mut(1);
+        (sprite.rotation, sprite.skew) = (0.0, 45.0);
+      });
+      assert_eq!((sprite_at(13, 28), sprite_at(27, 28)), (Some(1), None));
+      assert_eq!((sprite_at(25, 20), sprite_at(18, 20)), (Some(1), None));
+    });
+  }
+
+  #[test]
+  fn setting_the_rect_of_a_stretched_bitmap_keeps_its_reg_point_in_proportion() {
+    with_test_player(|_| {
+      let CastMemberType::Bitmap(member) = framed_bitmap((0, 0, 10, 10)) else { unreachable!() };
+      let cast_lib = add_test_cast(vec![(1, "block", CastMemberType::Bitmap(BitmapMember { reg_point: (5, 5), ..member }))]);
+      reserve_player_mut(|player| {
+        player.movie.score.set_channel_count(1);
+        player.movie.score.get_sprite_mut(1).member = Some(cast_member_ref(cast_lib as i32, 1));
+      });
+      sprite_set_prop(1, &"rect".to_string(), Datum::IntRect((10, 20, 40, 40))).unwrap();
+      reserve_player_mut(|player| {
+        let sprite = player.movie.score.get_sprite(1).unwrap();
+        assert_eq!((sprite.loc_h, sprite.loc_v, sprite.width, sprite.height), (25, 30, 30, 20));
+        let rect = get_untransformed_sprite_rect(player, sprite);
+        assert_eq!((rect.left, rect.top, rect.right, rect.bottom), (10, 20, 40, 40));
+      });
+    });
+  }
+
+  #[test]
+  fn reset_clears_sprites_placed_by_the_score() {
+    with_test_player(|_| {
+      let data = film_loop_score_data(&[&[(1, 2, 150, 120, 20, 10), (2, 3, 10, 10, 5, 5)]]);
+      let mut reader = BinaryReader::from_vec(&data);
+      reader.set_endian(Endian::Big);
+      let frame_data = ScoreFrameData::read_film_loop(&mut reader).unwrap();
+      reserve_player_mut(|player| {
+        let score = &mut player.movie.score;
+        score.set_channel_count(2);
+        score.channel_data = frame_data.frame_channel_data;
+        score.apply_channel_data(1);
+        score.get_sprite_mut(2).puppet = true;
+        score.active_sprite_spans.push(0);
+
+        score.reset();
+        for channel in &score.channels {
+          assert_eq!(channel.sprite.member, None);
+          assert!(!channel.sprite.puppet);
+          assert_eq!(channel.score_member, None);
+        }
+        assert!(score.active_sprite_spans.is_empty());
+
+        // Nothing is left over to keep the next playback from placing the sprites again
+        score.apply_channel_data(1);
+        assert_eq!(score.channels[0].sprite.member, Some(cast_member_ref(0, 2)));
+        assert_eq!(score.channels[1].sprite.member, Some(cast_member_ref(0, 3)));
+      });
+    });
+  }
+
+  #[test]
+  fn places_film_loop_sprites_in_the_sprite_rect() {
+    with_test_player(|_| {
+      let loop_member = test_film_loop((100, 100, 200, 150), &[&[(1, 2, 150, 120, 20, 10)], &[(1, 2, 160, 120, 20, 10)]]);
+      let cast_lib = add_test_cast(vec![(1, "loop", loop_member)]);
+      // The sprite is registered at the center of the loop, so its rect starts at the stage origin
+      assert_eq!(child_sprites(cast_lib, 1, (50, 25), (100, 50), 1), vec![(2, (50, 20))]);
+      assert_eq!(child_sprites(cast_lib, 1, (50, 25), (100, 50), 2), vec![(2, (60, 20))]);
+      // Stretched to twice the size
+      assert_eq!(child_sprites(cast_lib, 1, (100, 50), (200, 100), 2), vec![(2, (170, 65))]);
+    });
+  }
+
+  fn cropped_film_loop(rect: (i16, i16, i16, i16), center: bool, frames: &[&[FilmLoopSprite]]) -> CastMemberType {
+    let mut member = test_film_loop(rect, frames);
+    if let CastMemberType::FilmLoop(film_loop) = &mut member {
+      film_loop.info.crop = true;
+      film_loop.info.center = center;
+    }
+    member
+  }
+
+  /// Returns the member, rect and crop rect of the sprites shown by a sprite of the given film loop.
+  fn cropped_child_sprites(cast_lib: u32, member: u32, loc: (i32, i32), size: (i32, i32)) -> Vec<(i32, IntRectTuple, Option<IntRectTuple>)> {
+    let mut sprite = Sprite::new(1);
+    sprite.member = Some(cast_member_ref(cast_lib as i32, member as i32));
+    (sprite.loc_h, sprite.loc_v) = loc;
+    (sprite.width, sprite.height) = size;
+    sprite.film_loop_frame = 1;
+    reserve_player_ref(|player| {
+      get_cropped_film_loop_child_sprites(player, &sprite)
+        .iter()
+        .map(|(child, clip_rect)| {
+          let rect = (child.loc_h, child.loc_v, child.loc_h + child.width, child.loc_v + child.height);
+          let clip_rect = clip_rect.map(|x| (x.left, x.top, x.right, x.bottom));
+          (child.member.as_ref().unwrap().cast_member, rect, clip_rect)
+        })
+        .collect_vec()
+    })
+  }
+
+  #[test]
+  fn cropping_film_loops_keep_their_size() {
+    with_test_player(|_| {
+      let loop_member = cropped_film_loop((0, 0, 100, 50), false, &[&[(1, 2, 80, 10, 20, 10)]]);
+      let cast_lib = add_test_cast(vec![(1, "loop", loop_member)]);
+      // At its own size the whole loop shows
+      assert_eq!(cropped_child_sprites(cast_lib, 1, (50, 25), (100, 50)), vec![(2, (80, 10, 100, 20), Some((0, 0, 100, 50)))]);
+      // Shrunk to half its size, the loop is cut off instead of scaled
+      assert_eq!(cropped_child_sprites(cast_lib, 1, (50, 25), (50, 50)), vec![(2, (80, 10, 100, 20), Some((0, 0, 50, 50)))]);
+      // Scaling loops are stretched to the sprite and not cut off
+      let scaled_member = test_film_loop((0, 0, 100, 50), &[&[(1, 2, 80, 10, 20, 10)]]);
+      let cast_lib = add_test_cast(vec![(1, "scaled", scaled_member)]);
+      assert_eq!(cropped_child_sprites(cast_lib, 1, (50, 25), (50, 50)), vec![(2, (40, 10, 50, 20), None)]);
+    });
+  }
+
+  #[test]
+  fn centered_cropping_film_loops_stay_in_the_middle_of_the_sprite() {
+    with_test_player(|_| {
+      let loop_member = cropped_film_loop((0, 0, 100, 50), true, &[&[(1, 2, 80, 10, 20, 10)]]);
+      let cast_lib = add_test_cast(vec![(1, "loop", loop_member)]);
+      assert_eq!(cropped_child_sprites(cast_lib, 1, (50, 25), (50, 50)), vec![(2, (55, 10, 75, 20), Some((0, 0, 50, 50)))]);
+    });
+  }
+
+  #[test]
+  fn nested_film_loops_are_cropped_by_every_cropping_loop() {
+    with_test_player(|_| {
+      let inner = cropped_film_loop((0, 0, 40, 40), false, &[&[(1, 5, 0, 0, 40, 40)]]);
+      let outer = cropped_film_loop((0, 0, 100, 100), false, &[&[(1, 1, 80, 20, 20, 40)]]);
+      let cast_lib = add_test_cast(vec![(1, "inner", inner), (2, "outer", outer)]);
+      // The inner loop shows through a 20x40 sprite at (60, 0), of which the outer sprite keeps 10 pixels
+      assert_eq!(cropped_child_sprites(cast_lib, 2, (50, 50), (70, 100)), vec![(5, (60, 0, 100, 40), Some((60, 0, 70, 40)))]);
+    });
+  }
+
+  #[test]
+  fn plays_nested_film_loops_along_with_the_outer_loop() {
+    with_test_player(|_| {
+      let inner = test_film_loop((0, 0, 100, 100), &[&[(1, 5, 10, 0, 5, 5)], &[(1, 5, 20, 0, 5, 5)], &[(1, 5, 30, 0, 5, 5)]]);
+      // The inner loop sits at the center of the outer one, which is where its loc is
+      let outer = test_film_loop((0, 0, 100, 100), &[&[(1, 1, 50, 50, 100, 100)], &[], &[], &[]]);
+      let cast_lib = add_test_cast(vec![(1, "inner", inner), (2, "outer", outer)]);
+      let frames = (1..=4).map(|frame| child_sprites(cast_lib, 2, (50, 50), (100, 100), frame)).collect_vec();
+      assert_eq!(frames, vec![vec![(5, (10, 0))], vec![(5, (20, 0))], vec![(5, (30, 0))], vec![(5, (10, 0))]]);
+    });
+  }
+
+  #[test]
+  fn stops_expanding_film_loops_that_contain_themselves() {
+    with_test_player(|_| {
+      let looped = test_film_loop((0, 0, 100, 100), &[&[(1, 1, 50, 50, 100, 100), (2, 3, 10, 10, 5, 5)]]);
+      let cast_lib = add_test_cast(vec![(1, "self", looped)]);
+      // Each level adds its own sprite until the loop is found among its own ancestors
+      assert_eq!(child_sprites(cast_lib, 1, (50, 50), (100, 100), 1), vec![(3, (10, 10))]);
+    });
+  }
+
+  #[test]
+  fn places_sprites_of_wide_film_loops() {
+    with_test_player(|_| {
+      let loop_member = test_film_loop((-30000, 0, 30000, 100), &[&[(1, 2, 29000, 0, 10, 10)]]);
+      let cast_lib = add_test_cast(vec![(1, "wide", loop_member)]);
+      assert_eq!(child_sprites(cast_lib, 1, (30000, 50), (60000, 100), 1), vec![(2, (59000, 0))]);
+    });
+  }
+}
@@ -8,7 +8,7 @@ use crate::director::{
 };
 
 use super::{
-    allocator::{DatumAllocatorTrait, ScriptInstanceAllocatorTrait}, bytecode::handler_manager::BytecodeHandlerContext, cast_lib::{player_cast_lib_set_prop, CastMemberRef}, datum_formatting::{format_concrete_datum, format_datum}, handlers::{datum_handlers::{bitmap::BitmapDatumHandlers, cast_member_ref::CastMemberRefHandlers, color::ColorDatumHandlers, int::IntDatumHandlers, list_handlers::ListDatumUtils, point::PointDatumHandlers, prop_list::PropListUtils, rect::RectDatumHandlers, sound::SoundDatumHandlers, string::StringDatumUtils, string_chunk::StringChunkHandlers, symbol::SymbolDatumHandlers, timeout::TimeoutDatumHandlers, void::VoidDatumHandlers}, types::TypeUtils}, reserve_player_mut, reserve_player_ref, scope::Scope, score::{sprite_get_prop, sprite_set_prop}, script_ref::ScriptInstanceRef, stage::{get_stage_prop, set_stage_prop}, DatumRef, DirPlayer, ScriptError
+    allocator::{DatumAllocatorTrait, ScriptInstanceAllocatorTrait}, bytecode::handler_manager::BytecodeHandlerContext, cast_lib::{player_cast_lib_set_prop, CastMemberRef}, datum_formatting::{format_concrete_datum, format_datum}, handlers::{datum_handlers::{bitmap::BitmapDatumHandlers, cast_member_ref::CastMemberRefHandlers, color::ColorDatumHandlers, int::IntDatumHandlers, list_handlers::ListDatumUtils, point::PointDatumHandlers, prop_list::PropListUtils, rect::RectDatumHandlers, sound::SoundDatumHandlers, string::StringDatumUtils, string_chunk::StringChunkHandlers, symbol::SymbolDatumHandlers, timeout::TimeoutDatumHandlers, void::VoidDatumHandlers}, types::TypeUtils}, reserve_player_mut, reserve_player_ref, scope::Scope, score::{sprite_get_prop, sprite_set_prop}, script_ref::ScriptInstanceRef, stage::{get_stage_prop, set_stage_prop}, xtra::manager::{get_xtra_instance_prop, set_xtra_instance_prop}, DatumRef, DirPlayer, ScriptError
 };
 
 #[derive(Clone)]
@@ -48,6 +48,24 @@ impl ScriptInstance {
 }
 
 impl Script {
+    pub fn new(member_ref: CastMemberRef, name: String, chunk: ScriptChunk, script_type: ScriptType, names: &[String]) -> Script {
+        let mut handlers = FxHashMap::default();
+        let mut handler_names = Vec::new();
+        for handler in &chunk.handlers {
+            let handler_name = &names[handler.name_id as usize];
+            handlers.insert(handler_name.to_lowercase(), Rc::new(handler.clone()));
+            handler_names.push(handler_name.to_owned());
+        }
+        Script {
+            member_ref,
+            name,
+            chunk,
+            script_type,
+            handlers,
+            handler_names,
+        }
+    }
+
     pub fn get_own_handler_ref_at(&self, index: usize) -> Option<ScriptHandlerRef> {
         return self.handler_names.get(index).map(|x| (self.member_ref.clone(), x.clone()));
     }
@@ -320,6 +338,9 @@ pub async fn player_set_obj_prop(
         Datum::SoundRef(..) => reserve_player_mut(|player| {
             SoundDatumHandlers::set_prop(player, obj_ref, prop_name, value_ref)
         }),
+        Datum::XtraInstance(xtra_name, instance_id) => reserve_player_mut(|player| {
+            set_xtra_instance_prop(player, &xtra_name, instance_id, prop_name, value_ref)
+        }),
         _ => reserve_player_ref(|player| {
             Err(ScriptError::new(
                 format!(
@@ -377,7 +398,9 @@ pub fn get_obj_prop(
         Datum::Int(_) => IntDatumHandlers::get_prop(player, obj_ref, &prop_name),
         Datum::ColorRef(_) => ColorDatumHandlers::get_prop(player, obj_ref, &prop_name),
         Datum::PlayerRef => player.get_player_prop(prop_name),
+        Datum::MovieRef => player.get_movie_prop_ref(prop_name),
         Datum::SoundRef(_) => Ok(player.alloc_datum(SoundDatumHandlers::get_prop(player, obj_ref, &prop_name)?)),
+        Datum::XtraInstance(xtra_name, instance_id) => get_xtra_instance_prop(player, &xtra_name, instance_id, prop_name),
         _ => {
             if prop_name == "ilk" {
                 let ilk = TypeUtils::get_datum_ilk(&obj_clone)?;
@@ -0,0 +1,150 @@
+use std::io::Cursor;
+
+use symphonia::core::{audio::SampleBuffer, codecs::DecoderOptions, errors::Error as SymphoniaError, formats::FormatOptions, io::MediaSourceStream, meta::MetadataOptions, probe::Hint};
+
+use crate::director::chunks::sound::{SoundChunk, SoundCodec};
+
+/// Decoded PCM audio, as interleaved signed 16-bit samples.
+pub struct DecodedSound {
+  pub sample_rate: u32,
+  pub channel_count: u16,
+  /// Sample size of the source data in bits.
+  pub sample_size: u16,
+  /// Loop start and end, in sample frames.
+  pub loop_points: Option<(u32, u32)>,
+  pub samples: Vec<i16>,
+}
+
+const IMA_INDEX_TABLE: [i32; 16] = [
+  -1, -1, -1, -1, 2, 4, 6, 8,
+  -1, -1, -1, -1, 2, 4, 6, 8,
+];
+
+const IMA_STEP_TABLE: [i32; 89] = [
+  7, 8, 9, 10, 11, 12, 13, 14, 16, 17,
+  19, 21, 23, 25, 28, 31, 34, 37, 41, 45,
+  50, 55, 60, 66, 73, 80, 88, 97, 107, 118,
+  130, 143, 157, 173, 190, 209, 230, 253, 279, 307,
+  337, 371, 408, 449, 494, 544, 598, 658, 724, 796,
+  876, 963, 1060, 1166, 1282, 1411, 1552, 1707, 1878, 2066,
+  2272, 2499, 2749, 3024, 3327, 3660, 4026, 4428, 4871, 5358,
+  5894, 6484, 7132, 7845, 8630, 9493, 10442, 11487, 12635, 13899,
+  15289, 16818, 18500, 20350, 22385, 24623, 27086, 29794, 32767,
+];
+
+const IMA4_PACKET_SIZE: usize = 34;
+const IMA4_SAMPLES_PER_PACKET: usize = 64;
+
+pub fn decode_snd_chunk(chunk: &SoundChunk) -> Result<DecodedSound, String> {
+  let samples = match chunk.codec {
+    SoundCodec::Raw => decode_raw_pcm(chunk),
+    SoundCodec::ImaAdpcm => decode_ima4(&chunk.data, chunk.channel_count as usize, chunk.frame_count as usize),
+  };
+  Ok(DecodedSound {
+    sample_rate: chunk.sample_rate,
+    channel_count: chunk.channel_count,
+    sample_size: chunk.sample_size,
+    loop_points: if chunk.loop_end > chunk.loop_start { Some((chunk.loop_start, chunk.loop_end)) } else { None },
+    samples,
+  })
+}
+
+fn decode_raw_pcm(chunk: &SoundChunk) -> Vec<i16> {
+  let sample_count = chunk.frame_count as usize * chunk.channel_count as usize;
+  if chunk.sample_size == 16 {
+    chunk.data
+      .chunks_exact(2)
+      .take(sample_count)
+      .map(|x| i16::from_be_bytes([x[0], x[1]]))
+      .collect()
+  } else {
+    chunk.data
+      .iter()
+      .take(sample_count)
+      .map(|x| ((*x as i16) - 128) << 8)
+      .collect()
+  }
+}
+
+/// Decodes Apple IMA4. Packets for each channel are stored one after another, so they are interleaved on output.
+fn decode_ima4(data: &[u8], channel_count: usize, packet_count: usize) -> Vec<i16> {
+  let packet_count = packet_count.min(data.len() / (IMA4_PACKET_SIZE * channel_count));
+  let mut samples = vec![0i16; packet_count * IMA4_SAMPLES_PER_PACKET * channel_count];
+  for packet_index in 0..packet_count {
+    for channel in 0..channel_count {
+      let offset = (packet_index * channel_count + channel) * IMA4_PACKET_SIZE;
+      let packet = &data[offset..offset + IMA4_PACKET_SIZE];
+      let preamble = u16::from_be_bytes([packet[0], packet[1]]);
+      let mut predictor = (preamble & 0xFF80) as i16 as i32;
+      let mut step_index = ((preamble & 0x7F) as i32).min(88);
+
+      for (i, byte) in packet[2..].iter().enumerate() {
+        for (j, nibble) in [byte & 0x0F, byte >> 4].iter().enumerate() {
+          let step = IMA_STEP_TABLE[step_index as usize];
+          let mut diff = step >> 3;
+          if nibble & 4 != 0 { diff += step; }
+          if nibble & 2 != 0 { diff += step >> 1; }
+          if nibble & 1 != 0 { diff += step >> 2; }
+          if nibble & 8 != 0 {
+            predictor -= diff;
+          } else {
+            predictor += diff;
+          }
+          predictor = predictor.clamp(i16::MIN as i32, i16::MAX as i32);
+          step_index = (step_index + IMA_INDEX_TABLE[*nibble as usize]).clamp(0, 88);
+
+          let frame = packet_index * IMA4_SAMPLES_PER_PACKET + i * 2 + j;
+          samples[frame * channel_count + channel] = predictor as i16;
+        }
+      }
+    }
+  }
+  samples
+}
+
+pub fn decode_mpeg_audio(data: &[u8]) -> Result<DecodedSound, String> {
+  let source = MediaSourceStream::new(Box::new(Cursor::new(data.to_vec())), Default::default());
+  let mut hint = Hint::new();
+  hint.with_extension("mp3");
+  let probed = symphonia::default::get_probe()
+    .format(&hint, source, &FormatOptions::default(), &MetadataOptions::default())
+    .map_err(|e| format!("Could not probe MPEG audio: {}", e))?;
+  let mut format = probed.format;
+  let track = format.default_track().ok_or("MPEG audio stream has no track")?;
+  let track_id = track.id;
+  let mut decoder = symphonia::default::get_codecs()
+    .make(&track.codec_params, &DecoderOptions::default())
+    .map_err(|e| format!("Could not create MPEG audio decoder: {}", e))?;
+
+  let mut sample_rate = track.codec_params.sample_rate.unwrap_or(0);
+  let mut channel_count = track.codec_params.channels.map(|x| x.count() as u16).unwrap_or(0);
+  let mut samples = vec![];
+  while let Ok(packet) = format.next_packet() {
+    if packet.track_id() != track_id {
+      continue;
+    }
+    match decoder.decode(&packet) {
+      Ok(buffer) => {
+        let spec = *buffer.spec();
+        sample_rate = spec.rate;
+        channel_count = spec.channels.count() as u16;
+        let mut sample_buffer = SampleBuffer::<i16>::new(buffer.capacity() as u64, spec);
+        sample_buffer.copy_interleaved_ref(buffer);
+        samples.extend_from_slice(sample_buffer.samples());
+      }
+      Err(SymphoniaError::DecodeError(_)) => continue,
+      Err(_) => break,
+    }
+  }
+
+  if sample_rate == 0 || channel_count == 0 {
+    return Err("MPEG audio stream has no decodable frames".to_string());
+  }
+  Ok(DecodedSound {
+    sample_rate,
+    channel_count,
+    sample_size: 16,
+    loop_points: None,
+    samples,
+  })
+}
@@ -0,0 +1,526 @@
+pub mod decoder;
+pub mod web_audio;
+
+use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+
+use super::{cast_lib::CastMemberRef, cast_member::SoundMember, ScriptError};
+
+pub const NUM_SOUND_CHANNELS: u16 = 8;
+pub const DEFAULT_OUTPUT_SAMPLE_RATE: u32 = 44100;
+/// Audio due after a stalled frame is mixed and written in chunks of this length.
+const MAX_CHUNK_MS: i64 = 250;
+/// The most audio mixed to catch up after a stall. Longer gaps, such as a backgrounded tab,
+/// resync the mixer to the clock and the sound resumes where it was.
+const MAX_CATCH_UP_MS: i64 = 4 * MAX_CHUNK_MS;
+
+/// Receives the mixed output of all sound channels as interleaved stereo samples.
+pub trait SoundBackend {
+  fn sample_rate(&self) -> u32;
+  fn write(&mut self, samples: &[f32]);
+}
+
+/// Discards everything it is given. Used until a real output is attached.
+pub struct NullSoundBackend {}
+
+impl SoundBackend for NullSoundBackend {
+  fn sample_rate(&self) -> u32 {
+    DEFAULT_OUTPUT_SAMPLE_RATE
+  }
+
+  fn write(&mut self, _samples: &[f32]) {}
+}
+
+/// Collects the mixed output in memory, for running the mixer headless.
+/// Clones share the same samples, so a clone kept outside the player can read what was mixed.
+#[derive(Clone)]
+pub struct BufferSoundBackend {
+  pub sample_rate: u32,
+  pub samples: Rc<RefCell<Vec<f32>>>,
+}
+
+impl BufferSoundBackend {
+  #[allow(dead_code)]
+  pub fn new(sample_rate: u32) -> BufferSoundBackend {
+    BufferSoundBackend {
+      sample_rate,
+      samples: Rc::new(RefCell::new(vec![])),
+    }
+  }
+}
+
+impl SoundBackend for BufferSoundBackend {
+  fn sample_rate(&self) -> u32 {
+    self.sample_rate
+  }
+
+  fn write(&mut self, samples: &[f32]) {
+    self.samples.borrow_mut().extend_from_slice(samples);
+  }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum SoundChannelStatus {
+  Idle = 0,
+  Queued = 2,
+  Playing = 3,
+  Paused = 4,
+}
+
+#[derive(Clone)]
+pub struct SoundPlaylistEntry {
+  pub member_ref: CastMemberRef,
+  pub sound: SoundMember,
+  /// Number of times to play the loop region. 0 loops forever.
+  pub loop_count: u32,
+  pub start_time: u32,
+  pub end_time: Option<u32>,
+  pub loop_start_time: Option<u32>,
+  pub loop_end_time: Option<u32>,
+}
+
+impl SoundPlaylistEntry {
+  pub fn new(member_ref: CastMemberRef, sound: SoundMember) -> SoundPlaylistEntry {
+    let loop_count = if sound.looped { 0 } else { 1 };
+    SoundPlaylistEntry {
+      member_ref,
+      sound,
+      loop_count,
+      start_time: 0,
+      end_time: None,
+      loop_start_time: None,
+      loop_end_time: None,
+    }
+  }
+
+  fn ms_to_frame(&self, ms: u32) -> f64 {
+    (ms as f64 * self.sound.sample_rate as f64 / 1000.0).min(self.sound.frame_count() as f64)
+  }
+
+  fn start_frame(&self) -> f64 {
+    self.ms_to_frame(self.start_time)
+  }
+
+  fn end_frame(&self) -> f64 {
+    self.end_time.map_or(self.sound.frame_count() as f64, |x| self.ms_to_frame(x))
+  }
+
+  fn loop_start_frame(&self) -> f64 {
+    match (self.loop_start_time, self.sound.loop_points) {
+      (Some(time), _) => self.ms_to_frame(time),
+      (None, Some((start, _))) => start as f64,
+      (None, None) => self.start_frame(),
+    }
+  }
+
+  fn loop_end_frame(&self) -> f64 {
+    match (self.loop_end_time, self.sound.loop_points) {
+      (Some(time), _) => self.ms_to_frame(time),
+      (None, Some((_, end))) => (end as f64).min(self.sound.frame_count() as f64),
+      (None, None) => self.end_frame(),
+    }
+  }
+}
+
+struct PlayingSound {
+  entry: SoundPlaylistEntry,
+  position: f64,
+  loops_played: u32,
+  frames_played: f64,
+}
+
+pub struct SoundChannel {
+  pub volume: i32,
+  pub pan: i32,
+  pub status: SoundChannelStatus,
+  pub playlist: VecDeque<SoundPlaylistEntry>,
+  current: Option<PlayingSound>,
+}
+
+impl SoundChannel {
+  pub fn new() -> SoundChannel {
+    SoundChannel {
+      volume: 255,
+      pan: 0,
+      status: SoundChannelStatus::Idle,
+      playlist: VecDeque::new(),
+      current: None,
+    }
+  }
+
+  pub fn queue(&mut self, entry: SoundPlaylistEntry) {
+    self.playlist.push_back(entry);
+    if self.status == SoundChannelStatus::Idle {
+      self.status = SoundChannelStatus::Queued;
+    }
+  }
+
+  /// Starts playing the given entry immediately, interrupting the current sound.
+  pub fn play_now(&mut self, entry: SoundPlaylistEntry) {
+    self.current = None;
+    self.playlist.push_front(entry);
+    self.play();
+  }
+
+  /// Resumes a paused sound, or starts the next sound in the playlist.
+  pub fn play(&mut self) {
+    if self.current.is_none() {
+      self.start_next();
+    }
+    if self.current.is_some() {
+      self.status = SoundChannelStatus::Playing;
+    }
+  }
+
+  pub fn pause(&mut self) {
+    if self.status == SoundChannelStatus::Playing {
+      self.status = SoundChannelStatus::Paused;
+    }
+  }
+
+  pub fn stop(&mut self) {
+    self.current = None;
+    self.status = if self.playlist.is_empty() { SoundChannelStatus::Idle } else { SoundChannelStatus::Queued };
+  }
+
+  pub fn rewind(&mut self) {
+    if let Some(current) = &mut self.current {
+      current.position = current.entry.start_frame();
+      current.loops_played = 0;
+      current.frames_played = 0.0;
+    }
+  }
+
+  /// Lets the current loop finish and continues to the end of the sound.
+  pub fn break_loop(&mut self) {
+    if let Some(current) = &mut self.current {
+      current.entry.loop_count = current.loops_played + 1;
+    }
+  }
+
+  pub fn set_playlist(&mut self, entries: Vec<SoundPlaylistEntry>) {
+    self.playlist = entries.into();
+    if self.current.is_none() {
+      self.status = if self.playlist.is_empty() { SoundChannelStatus::Idle } else { SoundChannelStatus::Queued };
+    }
+  }
+
+  pub fn is_busy(&self) -> bool {
+    self.status == SoundChannelStatus::Playing
+  }
+
+  pub fn current_entry(&self) -> Option<&SoundPlaylistEntry> {
+    self.current.as_ref().map(|x| &x.entry)
+  }
+
+  pub fn current_entry_mut(&mut self) -> Option<&mut SoundPlaylistEntry> {
+    self.current.as_mut().map(|x| &mut x.entry)
+  }
+
+  pub fn elapsed_time(&self) -> u32 {
+    self.current.as_ref().map_or(0, |x| (x.frames_played * 1000.0 / x.entry.sound.sample_rate.max(1) as f64) as u32)
+  }
+
+  pub fn loops_remaining(&self) -> u32 {
+    self.current.as_ref().map_or(0, |x| x.entry.loop_count.saturating_sub(x.loops_played + 1))
+  }
+
+  fn start_next(&mut self) {
+    self.current = self.playlist.pop_front().map(|entry| PlayingSound {
+      position: entry.start_frame(),
+      entry,
+      loops_played: 0,
+      frames_played: 0.0,
+    });
+    if self.current.is_none() {
+      self.status = SoundChannelStatus::Idle;
+    }
+  }
+
+  fn gains(&self) -> (f32, f32) {
+    let volume = self.volume.clamp(0, 255) as f32 / 255.0;
+    let pan = self.pan.clamp(-100, 100) as f32 / 100.0;
+    let left = (1.0 - pan).min(1.0);
+    let right = (1.0 + pan).min(1.0);
+    (volume * left, volume * right)
+  }
+
+  /// Mixes this channel into `out`, an interleaved stereo buffer at `output_rate`.
+  fn render(&mut self, out: &mut [f32], output_rate: u32) {
+    if self.status != SoundChannelStatus::Playing {
+      return;
+    }
+    let (left_gain, right_gain) = self.gains();
+    let mut frame_index = 0;
+    let frame_count = out.len() / 2;
+    while frame_index < frame_count {
+      let current = match &mut self.current {
+        Some(current) => current,
+        None => {
+          self.start_next();
+          if self.current.is_none() {
+            return;
+          }
+          continue;
+        }
+      };
+      let entry = &current.entry;
+      let sound = &entry.sound;
+      let is_looping = entry.loop_count == 0 || current.loops_played + 1 < entry.loop_count;
+      let region_end = if is_looping { entry.loop_end_frame() } else { entry.end_frame() };
+      let region_start = entry.loop_start_frame();
+      if current.position >= region_end {
+        if is_looping && region_end > region_start {
+          current.position = region_start + (current.position - region_end);
+          current.loops_played += 1;
+        } else {
+          self.current = None;
+          continue;
+        }
+      }
+
+      let step = sound.sample_rate as f64 / output_rate as f64;
+      let (left, right) = sound.sample_at(current.position);
+      out[frame_index * 2] += left * left_gain;
+      out[frame_index * 2 + 1] += right * right_gain;
+      current.position += step;
+      current.frames_played += step;
+      frame_index += 1;
+    }
+  }
+}
+
+pub struct SoundManager {
+  pub channels: Vec<SoundChannel>,
+  pub backend: Box<dyn SoundBackend>,
+  /// The time of the first update, and the number of output frames rendered since.
+  start_time: Option<i64>,
+  rendered_frames: i64,
+}
+
+impl SoundManager {
+  pub fn new(backend: Box<dyn SoundBackend>) -> SoundManager {
+    SoundManager {
+      channels: (0..NUM_SOUND_CHANNELS).map(|_| SoundChannel::new()).collect(),
+      backend,
+      start_time: None,
+      rendered_frames: 0,
+    }
+  }
+
+  pub fn get_channel(&self, number: u16) -> Result<&SoundChannel, ScriptError> {
+    self.channels
+      .get((number as usize).wrapping_sub(1))
+      .ok_or_else(|| ScriptError::new(format!("Invalid sound channel {}", number)))
+  }
+
+  pub fn get_channel_mut(&mut self, number: u16) -> Result<&mut SoundChannel, ScriptError> {
+    self.channels
+      .get_mut((number as usize).wrapping_sub(1))
+      .ok_or_else(|| ScriptError::new(format!("Invalid sound channel {}", number)))
+  }
+
+  pub fn stop_all(&mut self) {
+    for channel in self.channels.iter_mut() {
+      channel.playlist.clear();
+      channel.stop();
+    }
+  }
+
+  /// Mixes all channels into `out`, an interleaved stereo buffer at the backend sample rate.
+  pub fn render(&mut self, out: &mut [f32]) {
+    let output_rate = self.backend.sample_rate();
+    out.fill(0.0);
+    for channel in self.channels.iter_mut() {
+      channel.render(out, output_rate);
+    }
+    for sample in out.iter_mut() {
+      *sample = sample.clamp(-1.0, 1.0);
+    }
+  }
+
+  /// Renders the audio due since the previous update and hands it to the backend. Slow frames are
+  /// caught up in chunks, so channels stay in step with the clock, up to `MAX_CATCH_UP_MS` of audio.
+  pub fn update(&mut self, time_ms: i64) {
+    let start_time = *self.start_time.get_or_insert(time_ms);
+    let sample_rate = self.backend.sample_rate() as i64;
+    // Counting frames from the start keeps partial frames from adding up between updates.
+    let due_frames = (time_ms - start_time).max(0) * sample_rate / 1000;
+    let frame_count = (due_frames - self.rendered_frames).min(MAX_CATCH_UP_MS * sample_rate / 1000);
+    self.rendered_frames = self.rendered_frames.max(due_frames);
+    let max_chunk_frames = (MAX_CHUNK_MS * sample_rate / 1000).max(1);
+    let mut remaining = frame_count;
+    while remaining > 0 {
+      let chunk_frames = remaining.min(max_chunk_frames) as usize;
+      let mut buffer = vec![0.0; chunk_frames * 2];
+      self.render(&mut buffer);
+      self.backend.write(&buffer);
+      remaining -= chunk_frames as i64;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use binary_reader::BinaryReader;
+
+  use crate::{
+    director::{chunks::sound::SoundChunk, lingo::datum::Datum},
+    player::{
+      cast_lib::cast_member_ref,
+      cast_member::CastMemberType,
+      handlers::{datum_handlers::sound::SoundDatumHandlers, movie::MovieHandlers},
+      reserve_player_mut,
+      testing::{add_test_cast, with_test_player},
+    },
+  };
+
+  use super::{decoder::{decode_mpeg_audio, decode_snd_chunk}, SoundMember};
+
+  /// A format 1 `snd ` resource with a standard header: 8-bit mono samples at 22050 Hz.
+  fn snd_fixture(samples: &[u8]) -> Vec<u8> {
+    let mut data = vec![];
+    data.extend_from_slice(&1u16.to_be_bytes()); // format
+    data.extend_from_slice(&1u16.to_be_bytes()); // data format count
+    data.extend_from_slice(&[0, 5, 0, 0, 0, 0x80]); // sampled synth
+    data.extend_from_slice(&1u16.to_be_bytes()); // command count
+    data.extend_from_slice(&0x8051u16.to_be_bytes()); // bufferCmd
+    data.extend_from_slice(&0u16.to_be_bytes());
+    data.extend_from_slice(&20u32.to_be_bytes()); // header offset
+    data.extend_from_slice(&0u32.to_be_bytes()); // sample pointer
+    data.extend_from_slice(&(samples.len() as u32).to_be_bytes());
+    data.extend_from_slice(&(22050u32 << 16).to_be_bytes());
+    data.extend_from_slice(&0u32.to_be_bytes()); // loop start
+    data.extend_from_slice(&0u32.to_be_bytes()); // loop end
+    data.extend_from_slice(&[0x00, 60]); // standard header, base frequency
+    data.extend_from_slice(samples);
+    data
+  }
+
+  fn sound_member(samples: &[u8]) -> SoundMember {
+    let chunk = SoundChunk::from_reader(&mut BinaryReader::from_u8(&snd_fixture(samples)), 0).unwrap();
+    let decoded = decode_snd_chunk(&chunk).unwrap();
+    let mut member = SoundMember::empty();
+    member.sample_rate = decoded.sample_rate;
+    member.channel_count = decoded.channel_count;
+    member.sample_size = decoded.sample_size;
+    member.samples = decoded.samples.into();
+    member.looped = false;
+    member
+  }
+
+  /// Plays 100 ms of a constant sample at full scale through sound channel 1.
+  fn puppet_test_sound() {
+    add_test_cast(vec![(1, "beep", CastMemberType::Sound(sound_member(&[0xFF; 2205])))]);
+    let (channel_ref, member_ref) = reserve_player_mut(|player| {
+      (player.alloc_datum(Datum::Int(1)), player.alloc_datum(Datum::CastMember(cast_member_ref(1, 1))))
+    });
+    MovieHandlers::puppet_sound(&vec![channel_ref, member_ref]).unwrap();
+  }
+
+  fn sound_busy(channel: i32) -> bool {
+    let channel_ref = reserve_player_mut(|player| player.alloc_datum(Datum::Int(channel)));
+    let result = MovieHandlers::sound_busy(&vec![channel_ref]).unwrap();
+    reserve_player_mut(|player| player.get_datum(&result).to_bool().unwrap())
+  }
+
+  fn update_sound(time_ms: i64) {
+    reserve_player_mut(|player| player.sound_manager.update(time_ms));
+  }
+
+  #[test]
+  fn decodes_snd_resources() {
+    let member = sound_member(&[0x80, 0xFF, 0x00]);
+    assert_eq!(member.sample_rate, 22050);
+    assert_eq!(member.channel_count, 1);
+    assert_eq!(*member.samples, vec![0, 127 << 8, -128 << 8]);
+  }
+
+  #[test]
+  fn decodes_mpeg_audio() {
+    // Silent MPEG-1 layer III frames: 128 kbit/s, 44.1 kHz, mono, empty side info.
+    let mut frame = vec![0u8; 417];
+    frame[..4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0xC0]);
+    let data = frame.repeat(8);
+    let decoded = decode_mpeg_audio(&data).unwrap();
+    assert_eq!(decoded.sample_rate, 44100);
+    assert_eq!(decoded.channel_count, 1);
+    assert!(!decoded.samples.is_empty());
+    assert!(decoded.samples.iter().all(|x| *x == 0));
+  }
+
+  #[test]
+  fn puppet_sound_plays_until_the_end_of_the_sound() {
+    with_test_player(|platform| {
+      puppet_test_sound();
+      assert!(sound_busy(1));
+      assert!(!sound_busy(2));
+
+      update_sound(0);
+      update_sound(50);
+      assert!(sound_busy(1));
+      {
+        let samples = platform.sound.samples.borrow();
+        assert_eq!(samples.len(), 1102 * 2);
+        assert!(samples.iter().all(|x| (*x - 127.0 / 128.0).abs() < 0.001));
+      }
+
+      update_sound(150);
+      assert!(!sound_busy(1));
+      let samples = platform.sound.samples.borrow();
+      assert_eq!(samples.len(), 3307 * 2);
+      assert!(samples[2205 * 2..].iter().all(|x| *x == 0.0));
+    });
+  }
+
+  #[test]
+  fn puppet_sound_zero_stops_the_channel() {
+    with_test_player(|_| {
+      puppet_test_sound();
+      let (channel_ref, zero_ref) = reserve_player_mut(|player| (player.alloc_datum(Datum::Int(1)), player.alloc_datum(Datum::Int(0))));
+      MovieHandlers::puppet_sound(&vec![channel_ref, zero_ref]).unwrap();
+      assert!(!sound_busy(1));
+    });
+  }
+
+  #[test]
+  fn channel_volume_scales_the_mix() {
+    with_test_player(|platform| {
+      puppet_test_sound();
+      reserve_player_mut(|player| {
+        let sound_ref = player.alloc_datum(Datum::SoundRef(1));
+        let volume_ref = player.alloc_datum(Datum::Int(51));
+        SoundDatumHandlers::set_prop(player, &sound_ref, &"volume".to_string(), &volume_ref).unwrap();
+        assert_eq!(SoundDatumHandlers::get_prop(player, &sound_ref, &"volume".to_string()).unwrap().int_value().unwrap(), 51);
+      });
+      update_sound(0);
+      update_sound(10);
+      let samples = platform.sound.samples.borrow();
+      assert!(!samples.is_empty());
+      assert!(samples.iter().all(|x| (*x - 0.2 * 127.0 / 128.0).abs() < 0.001));
+    });
+  }
+
+  #[test]
+  fn slow_frames_are_caught_up() {
+    with_test_player(|platform| {
+      puppet_test_sound();
+      update_sound(0);
+      update_sound(1000);
+      assert!(!sound_busy(1));
+      assert_eq!(platform.sound.samples.borrow().len(), 22050 * 2);
+    });
+  }
+
+  #[test]
+  fn long_stalls_resync_the_mixer() {
+    with_test_player(|platform| {
+      puppet_test_sound();
+      update_sound(0);
+      update_sound(60_000);
+      // Only a second is mixed, and the next update continues from the new time
+      assert_eq!(platform.sound.samples.borrow().len(), 22050 * 2);
+      update_sound(60_050);
+      assert_eq!(platform.sound.samples.borrow().len(), (22050 + 1102) * 2);
+    });
+  }
+}
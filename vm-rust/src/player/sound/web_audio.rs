@@ -0,0 +1,77 @@
+use log::warn;
+use web_sys::{AudioContext, AudioContextState};
+
+use super::{SoundBackend, DEFAULT_OUTPUT_SAMPLE_RATE};
+
+/// How far ahead of the audio clock mixed buffers are scheduled, in seconds.
+const SCHEDULE_LATENCY: f64 = 0.1;
+
+/// Plays mixed output through the Web Audio API by scheduling the buffers back to back.
+pub struct WebAudioSoundBackend {
+  context: Option<AudioContext>,
+  /// When the next buffer starts on the audio clock, once the first one is scheduled.
+  next_start_time: Option<f64>,
+}
+
+impl WebAudioSoundBackend {
+  pub fn new() -> WebAudioSoundBackend {
+    let context = AudioContext::new();
+    if let Err(err) = &context {
+      warn!("Could not create AudioContext: {:?}", err);
+    }
+    WebAudioSoundBackend {
+      context: context.ok(),
+      next_start_time: None,
+    }
+  }
+}
+
+impl SoundBackend for WebAudioSoundBackend {
+  fn sample_rate(&self) -> u32 {
+    self.context.as_ref().map_or(DEFAULT_OUTPUT_SAMPLE_RATE, |x| x.sample_rate() as u32)
+  }
+
+  fn write(&mut self, samples: &[f32]) {
+    let context = match &self.context {
+      Some(context) => context,
+      None => return,
+    };
+    if context.state() == AudioContextState::Suspended {
+      // Browsers only allow audio to start after a user gesture, so keep asking
+      let _ = context.resume();
+    }
+    let sample_rate = context.sample_rate();
+    let current_time = context.current_time();
+    let (start_time, samples) = match self.next_start_time {
+      Some(start_time) if start_time >= current_time => (start_time, samples),
+      // After a stall, drop the audio that was due while the player was busy, so that
+      // what is heard stays in step with the channels.
+      Some(start_time) => {
+        let late_frames = ((current_time + SCHEDULE_LATENCY - start_time) * sample_rate as f64) as usize;
+        let skipped = (late_frames * 2).min(samples.len());
+        (start_time + (skipped / 2) as f64 / sample_rate as f64, &samples[skipped..])
+      }
+      None => (current_time + SCHEDULE_LATENCY, samples),
+    };
+    let frame_count = samples.len() / 2;
+    self.next_start_time = Some(start_time + frame_count as f64 / sample_rate as f64);
+    if frame_count == 0 {
+      return;
+    }
+    let buffer = match context.create_buffer(2, frame_count as u32, sample_rate) {
+      Ok(buffer) => buffer,
+      Err(_) => return,
+    };
+    for channel in 0..2 {
+      let channel_data: Vec<f32> = samples.iter().skip(channel).step_by(2).copied().collect();
+      let _ = buffer.copy_to_channel(&channel_data, channel as i32);
+    }
+    let source = match context.create_buffer_source() {
+      Ok(source) => source,
+      Err(_) => return,
+    };
+    source.set_buffer(Some(&buffer));
+    let _ = source.connect_with_audio_node(&context.destination());
+    let _ = source.start_with_when(start_time);
+  }
+}
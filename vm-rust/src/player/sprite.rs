@@ -33,6 +33,63 @@ pub enum CursorRef {
   Member(Vec<i32>),
 }
 
+/// The score properties of a sprite that Lingo has changed during the current span.
+/// The score keeps applying the others on every frame.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct LingoModifiedProps(u16);
+
+impl LingoModifiedProps {
+  pub const MEMBER: u16 = 1 << 0;
+  pub const LOC_H: u16 = 1 << 1;
+  pub const LOC_V: u16 = 1 << 2;
+  pub const WIDTH: u16 = 1 << 3;
+  pub const HEIGHT: u16 = 1 << 4;
+  pub const INK: u16 = 1 << 5;
+  pub const BLEND: u16 = 1 << 6;
+  pub const STRETCH: u16 = 1 << 7;
+  pub const EDITABLE: u16 = 1 << 8;
+  pub const ROTATION: u16 = 1 << 9;
+  pub const SKEW: u16 = 1 << 10;
+  pub const COLOR: u16 = 1 << 11;
+  pub const BG_COLOR: u16 = 1 << 12;
+  pub const BACK_COLOR: u16 = 1 << 13;
+
+  /// Returns the score properties changed by setting the given sprite property.
+  pub fn for_sprite_prop(prop_name: &str) -> u16 {
+    match prop_name {
+      "member" | "memberNum" | "castNum" => Self::MEMBER | Self::WIDTH | Self::HEIGHT,
+      "locH" => Self::LOC_H,
+      "locV" => Self::LOC_V,
+      "loc" => Self::LOC_H | Self::LOC_V,
+      "rect" => Self::LOC_H | Self::LOC_V | Self::WIDTH | Self::HEIGHT,
+      "width" => Self::WIDTH,
+      "height" => Self::HEIGHT,
+      "ink" => Self::INK,
+      "blend" => Self::BLEND,
+      "stretch" => Self::STRETCH,
+      "editable" => Self::EDITABLE,
+      "rotation" => Self::ROTATION,
+      "skew" => Self::SKEW,
+      "color" => Self::COLOR,
+      "bgColor" => Self::BG_COLOR,
+      "backColor" => Self::BACK_COLOR,
+      _ => 0,
+    }
+  }
+
+  pub fn insert(&mut self, props: u16) {
+    self.0 |= props;
+  }
+
+  pub fn contains(&self, prop: u16) -> bool {
+    self.0 & prop != 0
+  }
+
+  pub fn clear(&mut self) {
+    self.0 = 0;
+  }
+}
+
 pub struct Sprite {
   pub number: usize,
   pub name: String,
@@ -57,6 +114,10 @@ pub struct Sprite {
   pub script_instance_list: Vec<ScriptInstanceRef>,
   pub cursor_ref: Option<CursorRef>,
   pub editable: bool,
+  /// The properties Lingo changed, so the score doesn't override them for the rest of the span.
+  pub lingo_modified: LingoModifiedProps,
+  /// Current frame of the film loop shown by this sprite, if any.
+  pub film_loop_frame: u32,
 }
 
 impl Sprite {
@@ -85,6 +146,8 @@ impl Sprite {
       script_instance_list: vec![],
       cursor_ref: None,
       editable: false,
+      lingo_modified: LingoModifiedProps::default(),
+      film_loop_frame: 1,
     }
   }
 
@@ -111,5 +174,7 @@ impl Sprite {
     self.script_instance_list.clear();
     self.cursor_ref = None;
     self.editable = false;
+    self.lingo_modified.clear();
+    self.film_loop_frame = 1;
   }
 }
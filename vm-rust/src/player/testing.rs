@@ -0,0 +1,198 @@
+use std::{rc::Rc, sync::Mutex};
+
+use binary_reader::{BinaryReader, Endian};
+
+use crate::{director::{chunks::score::{ScoreFrameData, NUM_MAIN_CHANNELS}, enums::{FilmLoopInfo, ScriptType}, lingo::datum::Datum}, platform::{
+  file_storage::MemoryFileStorage,
+  in_process_net::{EchoSocketConnector, InProcessNetLoader},
+  pref_storage::MemoryPrefStorage,
+  virtual_clock::VirtualClock,
+  Platform,
+}};
+
+use super::{
+  cast_lib::{CastLib, CastLibState},
+  cast_member::{CastMember, CastMemberType, FilmLoopMember, ScriptMember},
+  init_player_with_platform,
+  net_task::NET_ERROR_NOT_FOUND,
+  reserve_player_mut,
+  sound::BufferSoundBackend,
+  xtra::multiuser::MULTIUSER_XTRA_MANAGER_OPT,
+  PLAYER_EVENT_TX,
+  PLAYER_OPT,
+};
+
+/// The platform services of a test player, kept so tests can inspect and drive them.
+pub struct TestPlatform {
+  pub clock: VirtualClock,
+  pub net_loader: InProcessNetLoader,
+  pub files: MemoryFileStorage,
+  pub prefs: MemoryPrefStorage,
+  pub sound: BufferSoundBackend,
+}
+
+/// The player is a global, so tests using it take turns.
+static TEST_PLAYER_LOCK: Mutex<()> = Mutex::new(());
+
+/// Runs `f` on a fresh player with in-memory platform services. Net requests are answered
+/// as not found unless the test installs its own loader.
+pub fn with_test_player<T>(f: impl FnOnce(&TestPlatform) -> T) -> T {
+  with_test_player_net(InProcessNetLoader::new(Box::new(|_| Err(NET_ERROR_NOT_FOUND))), f)
+}
+
+pub fn with_test_player_net<T>(net_loader: InProcessNetLoader, f: impl FnOnce(&TestPlatform) -> T) -> T {
+  run_with_test_platform(VirtualClock::new(0), net_loader, f)
+}
+
+/// Like `with_test_player`, with the clock starting at `start_millis` when the player is created.
+pub fn with_test_player_at<T>(start_millis: i64, f: impl FnOnce(&TestPlatform) -> T) -> T {
+  run_with_test_platform(VirtualClock::new(start_millis), InProcessNetLoader::new(Box::new(|_| Err(NET_ERROR_NOT_FOUND))), f)
+}
+
+fn run_with_test_platform<T>(clock: VirtualClock, net_loader: InProcessNetLoader, f: impl FnOnce(&TestPlatform) -> T) -> T {
+  let _guard = TEST_PLAYER_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+  let test_platform = TestPlatform {
+    clock,
+    net_loader,
+    files: MemoryFileStorage::default(),
+    prefs: MemoryPrefStorage::default(),
+    sound: BufferSoundBackend::new(22050),
+  };
+  let platform = Platform {
+    net_loader: std::rc::Rc::new(test_platform.net_loader.clone()),
+    clock: std::rc::Rc::new(test_platform.clock.clone()),
+    sockets: std::rc::Rc::new(EchoSocketConnector {}),
+    files: std::rc::Rc::new(test_platform.files.clone()),
+    prefs: std::rc::Rc::new(test_platform.prefs.clone()),
+  };
+  // Dropping the previous test's player would drop its datum refs, which report back to the
+  // global player while it is being replaced, so it is leaked instead. So are the Multiuser
+  // instances and the events left unprocessed, which hold datum refs as well.
+  std::mem::forget(unsafe { std::ptr::replace(&raw mut PLAYER_OPT, None) });
+  std::mem::forget(unsafe { std::ptr::replace(&raw mut MULTIUSER_XTRA_MANAGER_OPT, None) });
+  std::mem::forget(unsafe { std::ptr::replace(&raw mut PLAYER_EVENT_TX, None) });
+  init_player_with_platform(platform, Box::new(test_platform.sound.clone()));
+  f(&test_platform)
+}
+
+/// Adds an internal cast holding the given members, and returns its number.
+pub fn add_test_cast(members: Vec<(u32, &str, CastMemberType)>) -> u32 {
+  reserve_player_mut(|player| {
+    let number = player.movie.cast_manager.casts.len() as u32 + 1;
+    let mut cast = CastLib {
+      name: format!("Cast {}", number),
+      file_name: "".to_string(),
+      number,
+      is_external: false,
+      state: CastLibState::Loaded,
+      lctx: None,
+      members: Default::default(),
+      scripts: Default::default(),
+      preload_mode: 0,
+      capital_x: false,
+      dir_version: 0,
+    };
+    for (member_number, name, member_type) in members {
+      let mut member = CastMember::new(member_number, member_type);
+      member.name = name.to_string();
+      cast.members.insert(member_number, member);
+    }
+    player.movie.cast_manager.casts.push(cast);
+    number
+  })
+}
+
+/// A sprite of a test film loop frame, as (sprite channel, cast member, loc h, loc v, width, height).
+pub type FilmLoopSprite = (u16, u16, i16, i16, i16, i16);
+
+/// Encodes a film loop score (SCVW chunk) from the sprites of each frame. Every frame only
+/// stores the listed channels, as a delta against the frame before it.
+pub fn film_loop_score_data(frames: &[&[FilmLoopSprite]]) -> Vec<u8> {
+  const RECORD_SIZE: usize = 48;
+  let mut frame_data = vec![];
+  for sprites in frames {
+    let mut deltas = vec![];
+    for &(channel, member, left, top, width, height) in sprites.iter() {
+      // Bitmap sprites in copy ink with a black foreground
+      let mut record = vec![1, 0, 255, 0, 0, 0];
+      for value in [member as i16, 0, 0, top, left, height, width] {
+        record.extend_from_slice(&value.to_be_bytes());
+      }
+      record.resize(RECORD_SIZE, 0);
+      let offset = (NUM_MAIN_CHANNELS + channel - 1) as usize * RECORD_SIZE;
+      deltas.extend_from_slice(&(RECORD_SIZE as u16).to_be_bytes());
+      deltas.extend_from_slice(&(offset as u16).to_be_bytes());
+      deltas.extend_from_slice(&record);
+    }
+    frame_data.extend_from_slice(&(deltas.len() as u16 + 2).to_be_bytes());
+    frame_data.extend_from_slice(&deltas);
+  }
+  let frames_offset = 20u32;
+  let mut data = vec![];
+  data.extend_from_slice(&(frames_offset + frame_data.len() as u32).to_be_bytes());
+  data.extend_from_slice(&frames_offset.to_be_bytes());
+  data.resize(14, 0);
+  data.extend_from_slice(&(RECORD_SIZE as u16).to_be_bytes());
+  data.resize(frames_offset as usize, 0);
+  data.extend_from_slice(&frame_data);
+  data
+}
+
+/// Builds a scaling, looping film loop member with the given rect and frames.
+pub fn test_film_loop(rect: (i16, i16, i16, i16), frames: &[&[FilmLoopSprite]]) -> CastMemberType {
+  let data = film_loop_score_data(frames);
+  let mut reader = BinaryReader::from_vec(&data);
+  reader.set_endian(Endian::Big);
+  let frame_data = ScoreFrameData::read_film_loop(&mut reader).unwrap();
+  CastMemberType::FilmLoop(FilmLoopMember {
+    info: FilmLoopInfo { rect, looping: true, crop: false, center: false, enable_sound: false },
+    frame_count: frame_data.header.frame_count,
+    channel_data: Rc::new(frame_data.frame_channel_data),
+  })
+}
+
+/// Adds a Director 11 cast of scripts compiled from Lingo source, given as (number, name, type, source),
+/// and returns its number.
+pub fn add_test_scripts(scripts: Vec<(u32, &str, ScriptType, &str)>) -> u32 {
+  let members = scripts.iter()
+    .map(|(number, name, script_type, _)| {
+      let script = ScriptMember { script_id: *number, script_type: *script_type, name: name.to_string() };
+      (*number, *name, CastMemberType::Script(script))
+    })
+    .collect();
+  let cast_lib = add_test_cast(members);
+  reserve_player_mut(|player| {
+    let cast = player.movie.cast_manager.get_cast_mut(cast_lib);
+    cast.dir_version = 1100;
+    for (number, _, _, source) in &scripts {
+      cast.set_script_text(*number, source).unwrap();
+    }
+  });
+  cast_lib
+}
+
+/// Starts an empty `gLog` global for the handlers of test scripts to append to.
+pub fn start_test_log() {
+  reserve_player_mut(|player| {
+    let log_ref = player.alloc_datum(Datum::String(String::new()));
+    player.globals.insert("gLog".to_string(), log_ref);
+  });
+}
+
+/// Returns what was appended to the `gLog` global since the last call, and empties it.
+pub fn take_test_log() -> String {
+  let log = reserve_player_mut(|player| player.get_datum(&player.globals["gLog"]).string_value().unwrap());
+  start_test_log();
+  log
+}
+
+/// Returns a handler that appends the Lingo expression `entry` and a space to the `gLog` global,
+/// then runs the statements in `rest`.
+pub fn logging_handler(name: &str, params: &str, entry: &str, rest: &str) -> String {
+  format!("on {name} {params}\r  global gLog\r  gLog = gLog & {entry} & \" \"\r{rest}end\r")
+}
+
+/// Returns handlers that append their name to the `gLog` global.
+pub fn log_events(names: &[&str], params: &str) -> String {
+  names.iter().map(|name| logging_handler(name, params, &format!("\"{name}\""), "")).collect()
+}
@@ -1,12 +1,17 @@
-use std::collections::HashMap;
+use log::warn;
 
+use crate::{director::lingo::datum::{Datum, TimeoutRef}, platform::platform};
 
-use crate::{director::lingo::datum::TimeoutRef, js_api::JsApi};
-
-use super::DatumRef;
+use super::{
+    events::{player_invoke_global_event, player_unwrap_result, player_wait_available},
+    handlers::datum_handlers::player_call_datum_handler,
+    reserve_player_mut, reserve_player_ref, DatumRef,
+};
 
+/// Keeps the timeout objects of the movie, in the order they were created, and decides when each is due
+/// based on the platform clock.
 pub struct TimeoutManager {
-    pub timeouts: HashMap<TimeoutRef, Timeout>,
+    pub timeouts: Vec<Timeout>,
 }
 
 pub struct Timeout {
@@ -14,58 +19,241 @@ pub struct Timeout {
     pub period: u32,
     pub handler: String,
     pub target_ref: DatumRef,
-    pub is_scheduled: bool,
+    /// Persistent timeouts survive the movie being stopped.
+    pub persistent: bool,
+    /// Clock time in milliseconds at which the timeout is triggered next.
+    pub next_trigger_time: i64,
 }
 
 impl TimeoutManager {
     pub fn new() -> TimeoutManager {
         TimeoutManager {
-            timeouts: HashMap::new(),
+            timeouts: vec![],
         }
     }
 
+    /// Adds a timeout, replacing an existing one with the same name.
     pub fn add_timeout(&mut self, timeout: Timeout) {
-        self.timeouts.insert(timeout.name.to_owned(), timeout);
+        match self.timeouts.iter_mut().find(|x| x.name == timeout.name) {
+            Some(existing) => *existing = timeout,
+            None => self.timeouts.push(timeout),
+        }
     }
 
-    #[allow(dead_code)]
     pub fn forget_timeout(&mut self, timeout_name: &TimeoutRef) {
-        let timeout = &mut self.timeouts.remove(timeout_name);
-        if let Some(timeout) = timeout {
-            timeout.cancel();
-        }
+        self.timeouts.retain(|x| &x.name != timeout_name);
     }
 
-    #[allow(dead_code)]
     pub fn get_timeout(&self, timeout_name: &TimeoutRef) -> Option<&Timeout> {
-        self.timeouts.get(timeout_name)
+        self.timeouts.iter().find(|x| &x.name == timeout_name)
     }
 
     pub fn get_timeout_mut(&mut self, timeout_name: &TimeoutRef) -> Option<&mut Timeout> {
-        self.timeouts.get_mut(timeout_name)
+        self.timeouts.iter_mut().find(|x| &x.name == timeout_name)
     }
 
-    pub fn clear(&mut self) {
-        for (_, timeout) in self.timeouts.iter_mut() {
-            timeout.cancel();
+    /// The earliest clock time at which a timeout is due, if there are any.
+    pub fn next_trigger_time(&self) -> Option<i64> {
+        self.timeouts.iter().map(|x| x.next_trigger_time).min()
+    }
+
+    /// Returns the names of the timeouts that are due at `now` and schedules their next trigger.
+    /// A timeout that fell behind by more than one period skips the missed triggers.
+    pub fn take_due_timeouts(&mut self, now: i64) -> Vec<TimeoutRef> {
+        let mut result = vec![];
+        for timeout in self.timeouts.iter_mut() {
+            if timeout.next_trigger_time > now {
+                continue;
+            }
+            let period = timeout.period.max(1) as i64;
+            timeout.next_trigger_time += period;
+            if timeout.next_trigger_time <= now {
+                timeout.next_trigger_time = now + period;
+            }
+            result.push(timeout.name.to_owned());
         }
+        result
+    }
+
+    /// Forgets the timeouts that are not persistent.
+    pub fn clear_non_persistent(&mut self) {
+        self.timeouts.retain(|x| x.persistent);
+    }
+
+    pub fn clear(&mut self) {
         self.timeouts.clear();
     }
 }
 
 impl Timeout {
-    pub fn cancel(&mut self) {
-        if self.is_scheduled {
-            JsApi::dispatch_clear_timeout(&self.name);
-            self.is_scheduled = false;
-        }
+    pub fn new(name: TimeoutRef, period: u32, handler: String, target_ref: DatumRef) -> Timeout {
+        let mut timeout = Timeout {
+            name,
+            period,
+            handler,
+            target_ref,
+            persistent: false,
+            next_trigger_time: 0,
+        };
+        timeout.schedule();
+        timeout
     }
 
+    /// Restarts the period of the timeout from the current clock time.
     pub fn schedule(&mut self) {
-        self.cancel();
+        self.next_trigger_time = platform().clock.now_millis() + self.period.max(1) as i64;
+    }
+}
+
+/// Calls the handler of a timeout, on its target if it has one and as a global event otherwise.
+pub async fn player_trigger_timeout(timeout_name: &TimeoutRef) {
+    let timeout = reserve_player_ref(|player| {
+        let timeout = player.timeout_manager.get_timeout(timeout_name)?;
+        Some((timeout.target_ref.clone(), timeout.handler.to_owned(), player.is_playing && !player.is_script_paused))
+    });
+    let (target_ref, handler_name, is_running) = match timeout {
+        Some(timeout) => timeout,
+        None => {
+            warn!("Timeout triggered but not found: {}", timeout_name);
+            return;
+        }
+    };
+    if !is_running {
+        // TODO how to handle is_script_paused?
+        return;
+    }
+    let args = vec![reserve_player_mut(|player| player.alloc_datum(Datum::TimeoutRef(timeout_name.to_owned())))];
+    let result = if target_ref != DatumRef::Void {
+        player_call_datum_handler(&target_ref, &handler_name, &args).await
+    } else {
+        player_invoke_global_event(&handler_name, &args).await
+    };
+    player_unwrap_result(result);
+}
+
+/// Triggers every timeout that is due at the current clock time.
+pub async fn player_trigger_due_timeouts() {
+    player_wait_available().await;
+    let now = platform().clock.now_millis();
+    let due_timeouts = reserve_player_mut(|player| player.timeout_manager.take_due_timeouts(now));
+    for timeout_name in due_timeouts {
+        player_trigger_timeout(&timeout_name).await;
+    }
+}
+
+/// Waits until the clock reaches `deadline`, triggering the timeouts that come due in the meantime.
+pub async fn player_sleep_until(deadline: i64) {
+    loop {
+        player_trigger_due_timeouts().await;
+        let now = platform().clock.now_millis();
+        if now >= deadline {
+            return;
+        }
+        let next_trigger_time = reserve_player_ref(|player| player.timeout_manager.next_trigger_time());
+        let wake_time = next_trigger_time.map_or(deadline, |time| time.min(deadline)).max(now);
+        platform().clock.sleep((wake_time - now) as u64).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_std::task::block_on;
+
+    use crate::{
+        director::enums::ScriptType,
+        player::{
+            player_run_movie,
+            player_stop_movie,
+            testing::{add_test_scripts, logging_handler, start_test_log, take_test_log, with_test_player},
+        },
+    };
+
+    use super::*;
+
+    fn log_tick() -> String {
+        logging_handler("tick", "", "\"movie\"", "")
+    }
+
+    /// Plays the movie, as timeouts only fire during playback, and starts the log.
+    fn start_playing() {
+        reserve_player_mut(|player| player.is_playing = true);
+        start_test_log();
+    }
+
+    fn add_timeout(name: &str, period: u32, target_ref: DatumRef) {
+        reserve_player_mut(|player| {
+            player.timeout_manager.add_timeout(Timeout::new(name.to_string(), period, "tick".to_string(), target_ref));
+        });
+    }
+
+    fn timeout_names() -> Vec<String> {
+        reserve_player_ref(|player| player.timeout_manager.timeouts.iter().map(|x| x.name.to_owned()).collect())
+    }
+
+    #[test]
+    fn fires_once_per_period() {
+        with_test_player(|_| {
+            add_test_scripts(vec![(1, "main", ScriptType::Movie, &log_tick())]);
+            start_playing();
+            add_timeout("ticker", 100, DatumRef::Void);
+
+            block_on(player_sleep_until(350));
+            assert_eq!(platform().clock.now_millis(), 350);
+            assert_eq!(take_test_log(), "movie movie movie ");
+
+            block_on(player_sleep_until(399));
+            assert_eq!(take_test_log(), "");
+            block_on(player_sleep_until(400));
+            assert_eq!(take_test_log(), "movie ");
+        });
+    }
+
+    #[test]
+    fn skips_the_periods_it_missed() {
+        with_test_player(|_| {
+            add_timeout("ticker", 100, DatumRef::Void);
+            let due = reserve_player_mut(|player| player.timeout_manager.take_due_timeouts(350));
+            assert_eq!(due, vec!["ticker".to_string()]);
+            reserve_player_ref(|player| {
+                assert_eq!(player.timeout_manager.next_trigger_time(), Some(450));
+            });
+            let due = reserve_player_mut(|player| player.timeout_manager.take_due_timeouts(400));
+            assert!(due.is_empty());
+        });
+    }
+
+    #[test]
+    fn calls_the_handler_on_its_target() {
+        with_test_player(|_| {
+            let target = logging_handler("tick", "me, timeoutObj", "\"target \" & timeoutObj.name", "");
+            let main = log_tick()
+                + "on setUp\r  global gTarget\r  gTarget = new(script \"target\")\r  timeout(\"poll\").new(100, #tick, gTarget)\rend\r";
+            add_test_scripts(vec![
+                (1, "main", ScriptType::Movie, &main),
+                (2, "target", ScriptType::Parent, &target),
+            ]);
+            start_playing();
+            player_unwrap_result(block_on(player_invoke_global_event(&"setUp".to_string(), &vec![])));
+
+            block_on(player_sleep_until(200));
+            assert_eq!(take_test_log(), "target poll target poll ");
+        });
+    }
 
-        let timeout_name = self.name.to_owned();
-        JsApi::dispatch_schedule_timeout(&timeout_name, self.period);
-        self.is_scheduled = true;
+    #[test]
+    fn stopping_the_movie_forgets_timeouts_that_are_not_persistent() {
+        with_test_player(|_| {
+            add_timeout("temporary", 100, DatumRef::Void);
+            add_timeout("kept", 100, DatumRef::Void);
+            reserve_player_mut(|player| {
+                player.timeout_manager.get_timeout_mut(&"kept".to_string()).unwrap().persistent = true;
+                player.is_playing = true;
+            });
+            block_on(async {
+                player_run_movie(Some(1)).await;
+                player_stop_movie().await.unwrap();
+            });
+            assert_eq!(timeout_names(), vec!["kept".to_string()]);
+        });
     }
 }
@@ -0,0 +1,569 @@
+use std::cell::{Cell, RefCell};
+
+use fxhash::FxHashMap;
+use log::warn;
+
+use crate::{
+    director::lingo::datum::{Datum, XtraInstanceId},
+    platform::{file_storage::FileStorageError, platform},
+    player::{file_sandbox::resolve_sandbox_path, reserve_player_mut, reserve_player_ref, DatumRef, ScriptError},
+};
+
+use super::manager::Xtra;
+
+// Status codes of the FileIO Xtra, as returned by `status()`.
+pub const FILEIO_OK: i32 = 0;
+pub const FILEIO_ERROR_MEMORY: i32 = 1;
+pub const FILEIO_ERROR_DIRECTORY_FULL: i32 = -33;
+pub const FILEIO_ERROR_VOLUME_FULL: i32 = -34;
+pub const FILEIO_ERROR_VOLUME_NOT_FOUND: i32 = -35;
+pub const FILEIO_ERROR_IO: i32 = -36;
+pub const FILEIO_ERROR_BAD_FILE_NAME: i32 = -37;
+pub const FILEIO_ERROR_FILE_NOT_OPEN: i32 = -38;
+pub const FILEIO_ERROR_TOO_MANY_FILES_OPEN: i32 = -42;
+pub const FILEIO_ERROR_FILE_NOT_FOUND: i32 = -43;
+pub const FILEIO_ERROR_DUPLICATE_FILE_NAME: i32 = -48;
+pub const FILEIO_ERROR_NO_SUCH_DRIVE: i32 = -56;
+pub const FILEIO_ERROR_NO_DISK: i32 = -65;
+pub const FILEIO_ERROR_DIRECTORY_NOT_FOUND: i32 = -120;
+
+const ERROR_STRINGS: [(i32, &str); 14] = [
+    (FILEIO_OK, "OK"),
+    (FILEIO_ERROR_MEMORY, "Memory allocation failure"),
+    (FILEIO_ERROR_DIRECTORY_FULL, "File directory full"),
+    (FILEIO_ERROR_VOLUME_FULL, "Volume full"),
+    (FILEIO_ERROR_VOLUME_NOT_FOUND, "Volume not found"),
+    (FILEIO_ERROR_IO, "I/O Error"),
+    (FILEIO_ERROR_BAD_FILE_NAME, "Bad file name"),
+    (FILEIO_ERROR_FILE_NOT_OPEN, "File not open"),
+    (FILEIO_ERROR_TOO_MANY_FILES_OPEN, "Too many files open"),
+    (FILEIO_ERROR_FILE_NOT_FOUND, "File not found"),
+    (FILEIO_ERROR_DUPLICATE_FILE_NAME, "Duplicate file name"),
+    (FILEIO_ERROR_NO_SUCH_DRIVE, "No such drive"),
+    (FILEIO_ERROR_NO_DISK, "No disk in drive"),
+    (FILEIO_ERROR_DIRECTORY_NOT_FOUND, "Directory not found"),
+];
+
+pub fn get_fileio_error_string(error_code: i32) -> &'static str {
+    ERROR_STRINGS
+        .iter()
+        .find(|(code, _)| *code == error_code)
+        .map_or("Unknown error", |(_, text)| text)
+}
+
+const INTERFACE: &str = "xtra fileio
+new object me -- create a new child instance
+-- FILEIO --
+fileName object me -- return fileName string of the open file
+status object me -- return the error code of the last method called
+error object me, int error -- return the error string of the error
+setFilterMask me, string mask -- set the filter mask for dialogs
+openFile object me, string fileName, int mode -- opens named file. valid modes: 0=r/w 1=r 2=w
+closeFile object me -- close the file
+displayOpen object me -- displays an open dialog and returns the selected fileName to lingo
+displaySave object me, string title, string defaultFileName -- displays save dialog and returns selected fileName to lingo
+createFile object me, string fileName -- creates a new file called fileName
+setPosition object me, int position -- set the file position
+getPosition object me -- get the file position
+getLength object me -- get the length of the open file
+writeChar object me, string theChar -- write a single character to the file
+writeString object me, string theString -- write a string to the file
+readChar object me -- read the next character of the file and return it as a string
+readLine object me -- read the next line (including the next RETURN) and return as a string
+readFile object me -- read from current position to EOF and return as a string
+readWord object me -- read the next word and return it as a string
+readToken object me, string skip, string break -- read the next token and return it as a string
+delete object me -- deletes the open file
+version object me -- return fileIO version information
+";
+
+#[derive(Clone, Copy, PartialEq)]
+enum FileMode {
+    ReadWrite,
+    Read,
+    Write,
+}
+
+impl FileMode {
+    fn from_code(code: i32) -> FileMode {
+        match code {
+            1 => FileMode::Read,
+            2 => FileMode::Write,
+            _ => FileMode::ReadWrite,
+        }
+    }
+
+    fn can_read(self) -> bool {
+        self != FileMode::Write
+    }
+
+    fn can_write(self) -> bool {
+        self != FileMode::Read
+    }
+}
+
+struct OpenFile {
+    /// The name the movie opened the file with.
+    file_name: String,
+    storage_path: String,
+    data: Vec<u8>,
+    position: usize,
+    mode: FileMode,
+    /// Whether `data` has changes that are not in storage yet.
+    is_dirty: bool,
+}
+
+impl OpenFile {
+    fn read_while(&mut self, mut predicate: impl FnMut(u8) -> bool) -> &[u8] {
+        let start = self.position;
+        while self.position < self.data.len() && predicate(self.data[self.position]) {
+            self.position += 1;
+        }
+        &self.data[start..self.position]
+    }
+
+    fn read_line(&mut self) -> Vec<u8> {
+        let mut line = self.read_while(|c| c != b'\r' && c != b'\n').to_vec();
+        if let Some(&terminator) = self.data.get(self.position) {
+            line.push(terminator);
+            self.position += 1;
+            if terminator == b'\r' && self.data.get(self.position) == Some(&b'\n') {
+                line.push(b'\n');
+                self.position += 1;
+            }
+        }
+        line
+    }
+
+    fn read_token(&mut self, skip_chars: &[u8], break_chars: &[u8]) -> Vec<u8> {
+        self.read_while(|c| skip_chars.contains(&c));
+        self.read_while(|c| !break_chars.contains(&c)).to_vec()
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        let end = self.position + bytes.len();
+        if end > self.data.len() {
+            self.data.resize(end, 0);
+        }
+        self.data[self.position..end].copy_from_slice(bytes);
+        self.position = end;
+        self.is_dirty = true;
+    }
+
+    /// Writes the changes to storage. Done on close and once per frame, as movies often quit
+    /// without closing their files.
+    fn flush(&mut self) -> Result<(), FileStorageError> {
+        if self.is_dirty {
+            platform().files.write(&self.storage_path, &self.data)?;
+            self.is_dirty = false;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct FileIOInstance {
+    file: Option<OpenFile>,
+    status: i32,
+}
+
+fn bytes_to_string(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).to_string()
+}
+
+fn get_storage_error_code(err: FileStorageError) -> i32 {
+    match err {
+        FileStorageError::NotFound => FILEIO_ERROR_FILE_NOT_FOUND,
+        FileStorageError::InvalidPath => FILEIO_ERROR_BAD_FILE_NAME,
+        FileStorageError::Io(message) => {
+            warn!("FileIO error: {}", message);
+            FILEIO_ERROR_IO
+        }
+    }
+}
+
+fn get_string_arg(args: &[DatumRef], index: usize) -> Result<String, ScriptError> {
+    reserve_player_ref(|player| match args.get(index) {
+        Some(arg) => player.get_datum(arg).string_value(),
+        None => Ok(String::new()),
+    })
+}
+
+fn get_int_arg(args: &[DatumRef], index: usize) -> Result<i32, ScriptError> {
+    reserve_player_ref(|player| match args.get(index).map(|x| player.get_datum(x)) {
+        Some(datum) if !datum.is_void() => datum.int_value(),
+        _ => Ok(0),
+    })
+}
+
+fn alloc_datum(datum: Datum) -> Result<DatumRef, ScriptError> {
+    Ok(reserve_player_mut(|player| player.alloc_datum(datum)))
+}
+
+/// The FileIO Xtra, reading and writing files in the sandbox of the movie.
+/// There are no file dialogs, so `displayOpen` and `displaySave` return EMPTY as if cancelled.
+#[derive(Default)]
+pub struct FileIOXtra {
+    instances: RefCell<FxHashMap<XtraInstanceId, FileIOInstance>>,
+    instance_counter: Cell<XtraInstanceId>,
+}
+
+impl FileIOXtra {
+    /// Runs `callback` on the open file of an instance, setting its status to the result.
+    fn with_open_file(
+        &self,
+        instance_id: XtraInstanceId,
+        callback: impl FnOnce(&mut OpenFile) -> Result<Datum, i32>,
+    ) -> Result<DatumRef, ScriptError> {
+        let result = {
+            let mut instances = self.instances.borrow_mut();
+            let instance = instances
+                .get_mut(&instance_id)
+                .ok_or_else(|| ScriptError::new(format!("FileIO instance #{} not found", instance_id)))?;
+            let result = match &mut instance.file {
+                Some(file) => callback(file),
+                None => Err(FILEIO_ERROR_FILE_NOT_OPEN),
+            };
+            instance.status = result.as_ref().err().copied().unwrap_or(FILEIO_OK);
+            result
+        };
+        alloc_datum(result.unwrap_or(Datum::Void))
+    }
+
+    fn with_instance<T>(
+        &self,
+        instance_id: XtraInstanceId,
+        callback: impl FnOnce(&mut FileIOInstance) -> T,
+    ) -> Result<T, ScriptError> {
+        let mut instances = self.instances.borrow_mut();
+        match instances.get_mut(&instance_id) {
+            Some(instance) => Ok(callback(instance)),
+            None => Err(ScriptError::new(format!("FileIO instance #{} not found", instance_id))),
+        }
+    }
+
+    fn resolve_path(file_name: &str) -> Result<String, i32> {
+        reserve_player_ref(|player| resolve_sandbox_path(player, file_name)).ok_or(FILEIO_ERROR_BAD_FILE_NAME)
+    }
+
+    fn open_file(file_name: String, mode: FileMode) -> Result<OpenFile, i32> {
+        let storage_path = Self::resolve_path(&file_name)?;
+        let data = platform().files.read(&storage_path).map_err(get_storage_error_code)?;
+        Ok(OpenFile { file_name, storage_path, data, position: 0, mode, is_dirty: false })
+    }
+
+    /// Closes the open file of an instance, writing its changes. Returns `None` if no file was open.
+    fn close_file(instance: &mut FileIOInstance) -> Option<Result<(), FileStorageError>> {
+        instance.file.take().map(|mut file| file.flush())
+    }
+
+    fn create_file(file_name: &str) -> Result<(), i32> {
+        let storage_path = Self::resolve_path(file_name)?;
+        if platform().files.exists(&storage_path) {
+            return Err(FILEIO_ERROR_DUPLICATE_FILE_NAME);
+        }
+        platform().files.write(&storage_path, &[]).map_err(get_storage_error_code)
+    }
+
+    fn read(
+        &self,
+        instance_id: XtraInstanceId,
+        callback: impl FnOnce(&mut OpenFile) -> Datum,
+    ) -> Result<DatumRef, ScriptError> {
+        self.with_open_file(instance_id, |file| {
+            if !file.mode.can_read() {
+                return Err(FILEIO_ERROR_FILE_NOT_OPEN);
+            }
+            if file.position >= file.data.len() {
+                return Ok(Datum::Void);
+            }
+            Ok(callback(file))
+        })
+    }
+
+    fn write(&self, instance_id: XtraInstanceId, bytes: Vec<u8>) -> Result<DatumRef, ScriptError> {
+        self.with_open_file(instance_id, |file| {
+            if !file.mode.can_write() {
+                return Err(FILEIO_ERROR_FILE_NOT_OPEN);
+            }
+            file.write(&bytes);
+            Ok(Datum::Void)
+        })
+    }
+}
+
+impl Xtra for FileIOXtra {
+    fn name(&self) -> &str {
+        "FileIO"
+    }
+
+    fn interface(&self) -> String {
+        INTERFACE.to_owned()
+    }
+
+    fn flush(&self) {
+        for instance in self.instances.borrow_mut().values_mut() {
+            if let Some(Err(err)) = instance.file.as_mut().map(|file| file.flush()) {
+                instance.status = get_storage_error_code(err);
+            }
+        }
+    }
+
+    fn create_instance(&self, _args: &Vec<DatumRef>) -> Result<XtraInstanceId, ScriptError> {
+        let instance_id = self.instance_counter.get() + 1;
+        self.instance_counter.set(instance_id);
+        self.instances.borrow_mut().insert(instance_id, FileIOInstance::default());
+        Ok(instance_id)
+    }
+
+    fn call_instance_handler(
+        &self,
+        handler_name: &String,
+        instance_id: XtraInstanceId,
+        args: &Vec<DatumRef>,
+    ) -> Result<DatumRef, ScriptError> {
+        match handler_name.to_lowercase().as_str() {
+            "filename" => {
+                let file_name = self.with_instance(instance_id, |instance| {
+                    instance.file.as_ref().map(|file| file.file_name.clone()).unwrap_or_default()
+                })?;
+                alloc_datum(Datum::String(file_name))
+            }
+            "status" => alloc_datum(Datum::Int(self.with_instance(instance_id, |instance| instance.status)?)),
+            "error" => {
+                let error_code = get_int_arg(args, 0)?;
+                alloc_datum(Datum::String(get_fileio_error_string(error_code).to_owned()))
+            }
+            "setfiltermask" => {
+                // Only used by the file dialogs.
+                self.with_instance(instance_id, |instance| instance.status = FILEIO_OK)?;
+                Ok(DatumRef::Void)
+            }
+            "openfile" => {
+                let file_name = get_string_arg(args, 0)?;
+                let mode = FileMode::from_code(get_int_arg(args, 1)?);
+                self.with_instance(instance_id, Self::close_file)?;
+                let result = Self::open_file(file_name, mode);
+                self.with_instance(instance_id, |instance| match result {
+                    Ok(file) => {
+                        instance.file = Some(file);
+                        instance.status = FILEIO_OK;
+                    }
+                    Err(error_code) => {
+                        instance.file = None;
+                        instance.status = error_code;
+                    }
+                })?;
+                Ok(DatumRef::Void)
+            }
+            "closefile" => {
+                self.with_instance(instance_id, |instance| {
+                    instance.status = match Self::close_file(instance) {
+                        Some(result) => result.err().map_or(FILEIO_OK, get_storage_error_code),
+                        None => FILEIO_ERROR_FILE_NOT_OPEN,
+                    };
+                })?;
+                Ok(DatumRef::Void)
+            }
+            "displayopen" | "displaysave" => {
+                warn!("FileIO {} is not supported, returning EMPTY", handler_name);
+                self.with_instance(instance_id, |instance| instance.status = FILEIO_OK)?;
+                alloc_datum(Datum::String(String::new()))
+            }
+            "createfile" => {
+                let file_name = get_string_arg(args, 0)?;
+                let status = Self::create_file(&file_name).err().unwrap_or(FILEIO_OK);
+                self.with_instance(instance_id, |instance| instance.status = status)?;
+                Ok(DatumRef::Void)
+            }
+            "setposition" => {
+                let position = get_int_arg(args, 0)?.max(0) as usize;
+                self.with_open_file(instance_id, |file| {
+                    file.position = position.min(file.data.len());
+                    Ok(Datum::Void)
+                })
+            }
+            "getposition" => self.with_open_file(instance_id, |file| Ok(Datum::Int(file.position as i32))),
+            "getlength" => self.with_open_file(instance_id, |file| Ok(Datum::Int(file.data.len() as i32))),
+            "writechar" => {
+                let string = get_string_arg(args, 0)?;
+                let bytes = string.chars().next().map(|c| c.to_string().into_bytes()).unwrap_or_default();
+                self.write(instance_id, bytes)
+            }
+            "writestring" => {
+                let string = get_string_arg(args, 0)?;
+                self.write(instance_id, string.into_bytes())
+            }
+            "readchar" => self.read(instance_id, |file| {
+                file.position += 1;
+                Datum::String(bytes_to_string(&file.data[file.position - 1..file.position]))
+            }),
+            "readline" => self.read(instance_id, |file| Datum::String(bytes_to_string(&file.read_line()))),
+            "readfile" => self.read(instance_id, |file| {
+                let rest = bytes_to_string(&file.data[file.position..]);
+                file.position = file.data.len();
+                Datum::String(rest)
+            }),
+            "readword" => self.read(instance_id, |file| {
+                Datum::String(bytes_to_string(&file.read_token(b" \t\r\n", b" \t\r\n")))
+            }),
+            "readtoken" => {
+                let skip_chars = get_string_arg(args, 0)?.into_bytes();
+                let break_chars = get_string_arg(args, 1)?.into_bytes();
+                self.read(instance_id, |file| {
+                    Datum::String(bytes_to_string(&file.read_token(&skip_chars, &break_chars)))
+                })
+            }
+            "delete" => {
+                let file = self.with_instance(instance_id, |instance| instance.file.take())?;
+                let status = match file {
+                    Some(file) => platform().files.delete(&file.storage_path).err().map_or(FILEIO_OK, get_storage_error_code),
+                    None => FILEIO_ERROR_FILE_NOT_OPEN,
+                };
+                self.with_instance(instance_id, |instance| instance.status = status)?;
+                Ok(DatumRef::Void)
+            }
+            "version" => alloc_datum(Datum::String("FileIO 10.1".to_owned())),
+            _ => Err(ScriptError::new(format!(
+                "No handler {} found for FileIO xtra instance #{}",
+                handler_name, instance_id
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        platform::file_storage::FileStorage,
+        player::testing::{with_test_player, TestPlatform},
+    };
+
+    use super::*;
+
+    struct FileIO {
+        xtra: FileIOXtra,
+        instance_id: XtraInstanceId,
+    }
+
+    impl FileIO {
+        fn new() -> FileIO {
+            let xtra = FileIOXtra::default();
+            let instance_id = xtra.create_instance(&vec![]).unwrap();
+            FileIO { xtra, instance_id }
+        }
+
+        fn call(&self, handler_name: &str, args: Vec<Datum>) -> Datum {
+            let args = reserve_player_mut(|player| args.into_iter().map(|x| player.alloc_datum(x)).collect());
+            let result = self.xtra.call_instance_handler(&handler_name.to_string(), self.instance_id, &args).unwrap();
+            reserve_player_ref(|player| player.get_datum(&result).clone())
+        }
+
+        fn status(&self) -> i32 {
+            self.call("status", vec![]).int_value().unwrap()
+        }
+    }
+
+    fn string(value: &str) -> Datum {
+        Datum::String(value.to_string())
+    }
+
+    fn with_fileio_player(f: impl FnOnce(&TestPlatform, FileIO)) {
+        with_test_player(|platform| {
+            reserve_player_mut(|player| player.movie.base_path = "http://example.test/games/".to_string());
+            f(platform, FileIO::new())
+        })
+    }
+
+    #[test]
+    fn writes_and_reads_lines() {
+        with_fileio_player(|platform, fileio| {
+            fileio.call("createFile", vec![string("scores.txt")]);
+            assert_eq!(fileio.status(), FILEIO_OK);
+            fileio.call("openFile", vec![string("scores.txt"), Datum::Int(0)]);
+            assert_eq!(fileio.status(), FILEIO_OK);
+            fileio.call("writeString", vec![string("alice 10\rbob 7\r\n")]);
+            assert_eq!(fileio.status(), FILEIO_OK);
+            assert!(matches!(fileio.call("getPosition", vec![]), Datum::Int(16)));
+            // Writes reach storage once per frame
+            assert_eq!(platform.files.read("example.test/games/scores.txt").unwrap(), b"");
+            fileio.xtra.flush();
+            assert_eq!(platform.files.read("example.test/games/scores.txt").unwrap(), b"alice 10\rbob 7\r\n");
+
+            fileio.call("setPosition", vec![Datum::Int(0)]);
+            assert!(matches!(fileio.call("readLine", vec![]), Datum::String(line) if line == "alice 10\r"));
+            assert!(matches!(fileio.call("getPosition", vec![]), Datum::Int(9)));
+            assert!(matches!(fileio.call("readLine", vec![]), Datum::String(line) if line == "bob 7\r\n"));
+            assert!(matches!(fileio.call("readLine", vec![]), Datum::Void));
+            assert_eq!(fileio.status(), FILEIO_OK);
+            fileio.call("closeFile", vec![]);
+
+            // Opened again, the file keeps what was written
+            fileio.call("openFile", vec![string("@/scores.txt"), Datum::Int(1)]);
+            assert!(matches!(fileio.call("readWord", vec![]), Datum::String(word) if word == "alice"));
+            assert!(matches!(fileio.call("getLength", vec![]), Datum::Int(16)));
+        });
+    }
+
+    #[test]
+    fn closing_a_file_writes_it() {
+        with_fileio_player(|platform, fileio| {
+            fileio.call("createFile", vec![string("save.txt")]);
+            fileio.call("openFile", vec![string("save.txt"), Datum::Int(2)]);
+            fileio.call("writeString", vec![string("level 3")]);
+            fileio.call("closeFile", vec![]);
+            assert_eq!(fileio.status(), FILEIO_OK);
+            assert_eq!(platform.files.read("example.test/games/save.txt").unwrap(), b"level 3");
+
+            // So does opening another file with the same instance
+            fileio.call("openFile", vec![string("save.txt"), Datum::Int(0)]);
+            fileio.call("writeString", vec![string("LEVEL")]);
+            fileio.call("openFile", vec![string("save.txt"), Datum::Int(1)]);
+            assert!(matches!(fileio.call("readFile", vec![]), Datum::String(x) if x == "LEVEL 3"));
+        });
+    }
+
+    #[test]
+    fn reports_missing_files() {
+        with_fileio_player(|_, fileio| {
+            fileio.call("openFile", vec![string("missing.txt"), Datum::Int(1)]);
+            assert_eq!(fileio.status(), FILEIO_ERROR_FILE_NOT_FOUND);
+            let error = fileio.call("error", vec![Datum::Int(FILEIO_ERROR_FILE_NOT_FOUND)]);
+            assert!(matches!(error, Datum::String(x) if x == "File not found"));
+        });
+    }
+
+    #[test]
+    fn reports_files_that_are_not_open() {
+        with_fileio_player(|_, fileio| {
+            assert!(matches!(fileio.call("getPosition", vec![]), Datum::Void));
+            assert_eq!(fileio.status(), FILEIO_ERROR_FILE_NOT_OPEN);
+            fileio.call("closeFile", vec![]);
+            assert_eq!(fileio.status(), FILEIO_ERROR_FILE_NOT_OPEN);
+
+            // Files opened for reading can't be written, and the other way around
+            fileio.call("createFile", vec![string("log.txt")]);
+            fileio.call("openFile", vec![string("log.txt"), Datum::Int(1)]);
+            fileio.call("writeString", vec![string("entry")]);
+            assert_eq!(fileio.status(), FILEIO_ERROR_FILE_NOT_OPEN);
+            fileio.call("openFile", vec![string("log.txt"), Datum::Int(2)]);
+            fileio.call("readLine", vec![]);
+            assert_eq!(fileio.status(), FILEIO_ERROR_FILE_NOT_OPEN);
+        });
+    }
+
+    #[test]
+    fn rejects_names_outside_the_sandbox() {
+        with_fileio_player(|platform, fileio| {
+            for file_name in ["", "..", "../other/file.txt", "data/../../file.txt"] {
+                fileio.call("createFile", vec![string(file_name)]);
+                assert_eq!(fileio.status(), FILEIO_ERROR_BAD_FILE_NAME);
+                fileio.call("openFile", vec![string(file_name), Datum::Int(0)]);
+                assert_eq!(fileio.status(), FILEIO_ERROR_BAD_FILE_NAME);
+            }
+            fileio.call("createFile", vec![string("data/../file.txt")]);
+            assert_eq!(fileio.status(), FILEIO_OK);
+            assert!(platform.files.exists("example.test/games/file.txt"));
+        });
+    }
+}
@@ -1,12 +1,174 @@
+use std::{cell::RefCell, rc::Rc};
+
+use futures::{future, future::LocalBoxFuture, FutureExt};
+
 use crate::{
-    director::lingo::datum::XtraInstanceId,
-    player::{DatumRef, ScriptError},
+    director::lingo::datum::{Datum, XtraInstanceId},
+    player::{reserve_player_mut, DatumRef, DirPlayer, ScriptError},
 };
 
-use super::multiuser::{borrow_multiuser_manager_mut, MultiuserXtraManager};
+use super::{fileio::FileIOXtra, multiuser::MultiuserXtra};
+
+/// A native Xtra, created by movies with `new(xtra "Name")`.
+///
+/// Handlers take `&self` because an Xtra can be called again while one of its handlers is
+/// still running, so implementations keep their instances behind their own interior mutability.
+pub trait Xtra {
+    /// The name movies use in `xtra "Name"`. Lookups ignore case.
+    fn name(&self) -> &str;
+
+    /// The text returned by `xtra("Name").interface()`, describing the handlers of the Xtra.
+    fn interface(&self) -> String;
+
+    /// Writes out anything the Xtra buffers. Called once per frame and when the movie stops.
+    fn flush(&self) {}
+
+    fn create_instance(&self, args: &Vec<DatumRef>) -> Result<XtraInstanceId, ScriptError>;
+
+    fn call_instance_handler(
+        &self,
+        handler_name: &String,
+        instance_id: XtraInstanceId,
+        args: &Vec<DatumRef>,
+    ) -> Result<DatumRef, ScriptError>;
+
+    fn has_instance_async_handler(&self, _handler_name: &String) -> bool {
+        false
+    }
+
+    fn call_instance_async_handler(
+        &self,
+        handler_name: &String,
+        instance_id: XtraInstanceId,
+        _args: &Vec<DatumRef>,
+    ) -> LocalBoxFuture<'static, Result<DatumRef, ScriptError>> {
+        future::ready(Err(ScriptError::new(format!(
+            "No async handler {} found for xtra {} instance #{}",
+            handler_name,
+            self.name(),
+            instance_id
+        ))))
+        .boxed_local()
+    }
+
+    fn get_instance_prop(
+        &self,
+        _player: &mut DirPlayer,
+        instance_id: XtraInstanceId,
+        prop_name: &String,
+    ) -> Result<DatumRef, ScriptError> {
+        Err(ScriptError::new(format!(
+            "Cannot get prop {} of xtra {} instance #{}",
+            prop_name,
+            self.name(),
+            instance_id
+        )))
+    }
 
-pub fn is_xtra_registered(name: &String) -> bool {
-    return name == "Multiuser";
+    fn set_instance_prop(
+        &self,
+        _player: &mut DirPlayer,
+        instance_id: XtraInstanceId,
+        prop_name: &String,
+        _value: &DatumRef,
+    ) -> Result<(), ScriptError> {
+        Err(ScriptError::new(format!(
+            "Cannot set prop {} of xtra {} instance #{}",
+            prop_name,
+            self.name(),
+            instance_id
+        )))
+    }
+
+    /// Whether `handler_name` can be called on the Xtra itself, without creating an instance.
+    fn has_static_handler(&self, _handler_name: &String) -> bool {
+        false
+    }
+
+    fn call_static_handler(&self, handler_name: &String, _args: &Vec<DatumRef>) -> Result<DatumRef, ScriptError> {
+        Err(ScriptError::new(format!(
+            "No handler {} found for xtra {}",
+            handler_name,
+            self.name()
+        )))
+    }
+}
+
+/// The Xtras available to movies, in the order of `the xtraList`.
+#[derive(Default)]
+pub struct XtraRegistry {
+    xtras: Vec<Rc<dyn Xtra>>,
+}
+
+impl XtraRegistry {
+    pub fn with_builtin_xtras() -> XtraRegistry {
+        let mut registry = XtraRegistry::default();
+        registry.register(Rc::new(MultiuserXtra {}));
+        registry.register(Rc::new(FileIOXtra::default()));
+        registry
+    }
+
+    /// Adds an Xtra, replacing any registered Xtra with the same name.
+    pub fn register(&mut self, xtra: Rc<dyn Xtra>) {
+        match self.xtras.iter().position(|x| x.name().eq_ignore_ascii_case(xtra.name())) {
+            Some(index) => self.xtras[index] = xtra,
+            None => self.xtras.push(xtra),
+        }
+    }
+
+    pub fn unregister(&mut self, name: &str) -> bool {
+        let count = self.xtras.len();
+        self.xtras.retain(|x| !x.name().eq_ignore_ascii_case(name));
+        self.xtras.len() != count
+    }
+
+    pub fn get(&self, name: &str) -> Option<Rc<dyn Xtra>> {
+        self.xtras.iter().find(|x| x.name().eq_ignore_ascii_case(name)).cloned()
+    }
+
+    pub fn xtras(&self) -> &Vec<Rc<dyn Xtra>> {
+        &self.xtras
+    }
+}
+
+thread_local! {
+    static XTRA_REGISTRY: RefCell<XtraRegistry> = RefCell::new(XtraRegistry::with_builtin_xtras());
+}
+
+/// Runs `f` on the registry, which starts with the built-in Xtras so that embedders can
+/// register their own before or after the player starts.
+pub fn with_xtra_registry<T>(f: impl FnOnce(&mut XtraRegistry) -> T) -> T {
+    XTRA_REGISTRY.with(|x| f(&mut x.borrow_mut()))
+}
+
+pub fn register_xtra(xtra: Rc<dyn Xtra>) {
+    with_xtra_registry(|registry| registry.register(xtra));
+}
+
+pub fn unregister_xtra(name: &str) -> bool {
+    with_xtra_registry(|registry| registry.unregister(name))
+}
+
+fn get_xtra(xtra_name: &String) -> Result<Rc<dyn Xtra>, ScriptError> {
+    with_xtra_registry(|registry| registry.get(xtra_name))
+        .ok_or_else(|| ScriptError::new(format!("Xtra {} not found", xtra_name)))
+}
+
+/// Returns the name an Xtra was registered with, for names given in any case.
+pub fn get_registered_xtra_name(name: &str) -> Option<String> {
+    with_xtra_registry(|registry| registry.get(name)).map(|x| x.name().to_owned())
+}
+
+pub fn get_registered_xtra_names() -> Vec<String> {
+    with_xtra_registry(|registry| registry.xtras().iter().map(|x| x.name().to_owned()).collect())
+}
+
+pub fn flush_xtras() {
+    // Collected first, so that Xtras can use the registry while they flush.
+    let xtras = with_xtra_registry(|registry| registry.xtras().clone());
+    for xtra in xtras {
+        xtra.flush();
+    }
 }
 
 pub fn call_xtra_instance_handler(
@@ -15,15 +177,7 @@ pub fn call_xtra_instance_handler(
     handler_name: &String,
     args: &Vec<DatumRef>,
 ) -> Result<DatumRef, ScriptError> {
-    match xtra_name.as_str() {
-        "Multiuser" => {
-            return MultiuserXtraManager::call_instance_handler(handler_name, instance_id, args)
-        }
-        _ => Err(ScriptError::new(format!(
-            "No handler {} found for xtra {} instance #{}",
-            handler_name, xtra_name, instance_id
-        ))),
-    }
+    get_xtra(xtra_name)?.call_instance_handler(handler_name, instance_id, args)
 }
 
 pub async fn call_xtra_instance_async_handler(
@@ -32,20 +186,8 @@ pub async fn call_xtra_instance_async_handler(
     handler_name: &String,
     args: &Vec<DatumRef>,
 ) -> Result<DatumRef, ScriptError> {
-    match xtra_name.as_str() {
-        "Multiuser" => {
-            return MultiuserXtraManager::call_instance_async_handler(
-                handler_name,
-                instance_id,
-                args,
-            )
-            .await
-        }
-        _ => Err(ScriptError::new(format!(
-            "No async handler {} found for xtra {} instance #{}",
-            handler_name, xtra_name, instance_id
-        ))),
-    }
+    let xtra = get_xtra(xtra_name)?;
+    xtra.call_instance_async_handler(handler_name, instance_id, args).await
 }
 
 pub fn has_xtra_instance_async_handler(
@@ -53,18 +195,158 @@ pub fn has_xtra_instance_async_handler(
     handler_name: &String,
     _instance_id: XtraInstanceId,
 ) -> bool {
-    match xtra_name.as_str() {
-        "Multiuser" => MultiuserXtraManager::has_instance_async_handler(handler_name),
-        _ => false,
-    }
+    with_xtra_registry(|registry| registry.get(xtra_name))
+        .is_some_and(|x| x.has_instance_async_handler(handler_name))
 }
 
 pub fn create_xtra_instance(
     xtra_name: &String,
     args: &Vec<DatumRef>,
 ) -> Result<XtraInstanceId, ScriptError> {
-    match xtra_name.as_str() {
-        "Multiuser" => Ok(borrow_multiuser_manager_mut(|x| x.create_instance(args))),
-        _ => Err(ScriptError::new(format!("Xtra {} not found", xtra_name))),
+    get_xtra(xtra_name)?.create_instance(args)
+}
+
+pub fn get_xtra_instance_prop(
+    player: &mut DirPlayer,
+    xtra_name: &String,
+    instance_id: XtraInstanceId,
+    prop_name: &String,
+) -> Result<DatumRef, ScriptError> {
+    get_xtra(xtra_name)?.get_instance_prop(player, instance_id, prop_name)
+}
+
+pub fn set_xtra_instance_prop(
+    player: &mut DirPlayer,
+    xtra_name: &String,
+    instance_id: XtraInstanceId,
+    prop_name: &String,
+    value: &DatumRef,
+) -> Result<(), ScriptError> {
+    get_xtra(xtra_name)?.set_instance_prop(player, instance_id, prop_name, value)
+}
+
+/// Calls a handler on the Xtra itself, as in `xtra("Name").interface()`.
+pub fn call_xtra_handler(
+    xtra_name: &String,
+    handler_name: &String,
+    args: &Vec<DatumRef>,
+) -> Result<DatumRef, ScriptError> {
+    let xtra = get_xtra(xtra_name)?;
+    match handler_name.as_str() {
+        "interface" => Ok(reserve_player_mut(|player| player.alloc_datum(Datum::String(xtra.interface())))),
+        "new" => {
+            let instance_id = xtra.create_instance(args)?;
+            let xtra_name = xtra.name().to_owned();
+            Ok(reserve_player_mut(|player| {
+                player.alloc_datum(Datum::XtraInstance(xtra_name, instance_id))
+            }))
+        }
+        _ if xtra.has_static_handler(handler_name) => xtra.call_static_handler(handler_name, args),
+        _ => Err(ScriptError::new(format!(
+            "No handler {} found for xtra {}",
+            handler_name, xtra_name
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::player::{handlers::types::TypeHandlers, reserve_player_ref, testing::with_test_player};
+
+    use super::*;
+
+    struct TestXtra {
+        name: &'static str,
+        interface: &'static str,
+    }
+
+    impl Xtra for TestXtra {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn interface(&self) -> String {
+            self.interface.to_owned()
+        }
+
+        fn create_instance(&self, _args: &Vec<DatumRef>) -> Result<XtraInstanceId, ScriptError> {
+            Ok(1)
+        }
+
+        fn call_instance_handler(
+            &self,
+            handler_name: &String,
+            _instance_id: XtraInstanceId,
+            _args: &Vec<DatumRef>,
+        ) -> Result<DatumRef, ScriptError> {
+            Err(ScriptError::new(format!("No handler {}", handler_name)))
+        }
+    }
+
+    fn test_xtra(name: &'static str, interface: &'static str) -> Rc<dyn Xtra> {
+        Rc::new(TestXtra { name, interface })
+    }
+
+    fn names(registry: &XtraRegistry) -> Vec<&str> {
+        registry.xtras().iter().map(|x| x.name()).collect()
+    }
+
+    #[test]
+    fn registering_a_name_again_replaces_the_xtra_in_place() {
+        let mut registry = XtraRegistry::default();
+        registry.register(test_xtra("First", "old"));
+        registry.register(test_xtra("Second", ""));
+        registry.register(test_xtra("FIRST", "new"));
+        assert_eq!(names(&registry), vec!["FIRST", "Second"]);
+        assert_eq!(registry.get("first").unwrap().interface(), "new");
+    }
+
+    #[test]
+    fn looks_up_xtras_in_any_case() {
+        let registry = XtraRegistry::with_builtin_xtras();
+        assert_eq!(registry.get("fileio").unwrap().name(), "FileIO");
+        assert_eq!(registry.get("MULTIUSER").unwrap().name(), "Multiuser");
+        assert!(registry.get("Missing").is_none());
+    }
+
+    #[test]
+    fn unregisters_xtras_by_name() {
+        let mut registry = XtraRegistry::with_builtin_xtras();
+        assert!(registry.unregister("fileIO"));
+        assert!(!registry.unregister("FileIO"));
+        assert_eq!(names(&registry), vec!["Multiuser"]);
+    }
+
+    #[test]
+    fn lists_the_registered_xtras_in_order() {
+        with_test_player(|_| {
+            register_xtra(test_xtra("Echo", ""));
+            unregister_xtra("Multiuser");
+            let xtra_names = reserve_player_mut(|player| {
+                let list_ref = player.get_movie_prop_ref(&"xtraList".to_string()).unwrap();
+                let entries = player.get_datum(&list_ref).to_list().unwrap().clone();
+                entries.iter().map(|entry| {
+                    // Each entry is a prop list like [#name: "FileIO"]
+                    let (_, name_ref) = &player.get_datum(entry).to_map().unwrap()[0];
+                    player.get_datum(name_ref).string_value().unwrap()
+                }).collect::<Vec<_>>()
+            });
+            assert_eq!(xtra_names, vec!["FileIO", "Echo"]);
+        });
+    }
+
+    #[test]
+    fn finds_xtras_by_their_position_in_the_xtra_list() {
+        with_test_player(|_| {
+            let xtra = |arg: Datum| {
+                let arg = reserve_player_mut(|player| player.alloc_datum(arg));
+                TypeHandlers::xtra(&vec![arg]).map(|result| reserve_player_ref(|player| player.get_datum(&result).to_xtra_name().unwrap().to_owned()))
+            };
+            assert_eq!(xtra(Datum::Int(2)).unwrap(), "FileIO");
+            assert_eq!(xtra(Datum::String("multiuser".to_string())).unwrap(), "Multiuser");
+            assert!(xtra(Datum::Int(0)).is_err());
+            assert!(xtra(Datum::Int(3)).is_err());
+            assert!(xtra(Datum::Int(-1)).is_err());
+        });
     }
 }
@@ -1,2 +1,3 @@
+pub mod fileio;
 pub mod manager;
 pub mod multiuser;
@@ -0,0 +1,115 @@
+use blowfish::{
+    cipher::{generic_array::GenericArray, BlockDecrypt, BlockEncrypt, KeyInit},
+    Blowfish,
+};
+
+use super::value::{MultiuserValue, ValueReader};
+
+/// The key Shockwave Multiuser Servers decrypt logons with, unless they are configured with their own.
+pub const DEFAULT_ENCRYPTION_KEY: &str = "IPAddress resolution";
+
+const BLOCK_SIZE: usize = 8;
+
+/// Encrypts the content of logon messages, so the password isn't sent in the clear.
+/// The encoded value is encrypted with Blowfish block by block, zero padded to a whole block,
+/// and sent as media.
+pub struct LogonCipher {
+    cipher: Blowfish,
+}
+
+impl LogonCipher {
+    /// Fails for keys Blowfish can't use, which are shorter than 4 or longer than 56 bytes.
+    pub fn new(key: &str) -> Result<LogonCipher, String> {
+        let cipher = Blowfish::new_from_slice(key.as_bytes())
+            .map_err(|_| format!("Invalid encryption key length {}", key.len()))?;
+        Ok(LogonCipher { cipher })
+    }
+
+    pub fn encrypt(&self, value: &MultiuserValue) -> MultiuserValue {
+        let mut data = vec![];
+        value.write(&mut data);
+        MultiuserValue::Media(self.encrypt_bytes(data))
+    }
+
+    pub fn decrypt(&self, content: &MultiuserValue) -> Result<MultiuserValue, String> {
+        let data = match content {
+            MultiuserValue::Media(data) if data.len().is_multiple_of(BLOCK_SIZE) => self.decrypt_bytes(data.clone()),
+            _ => return Err("Encrypted content is not a whole number of blocks".to_string()),
+        };
+        // The padding after the value is left unread.
+        ValueReader::new(&data).read_value()
+    }
+
+    fn encrypt_bytes(&self, mut data: Vec<u8>) -> Vec<u8> {
+        data.resize(data.len().next_multiple_of(BLOCK_SIZE), 0);
+        for block in data.chunks_exact_mut(BLOCK_SIZE) {
+            self.cipher.encrypt_block(GenericArray::from_mut_slice(block));
+        }
+        data
+    }
+
+    fn decrypt_bytes(&self, mut data: Vec<u8>) -> Vec<u8> {
+        for block in data.chunks_exact_mut(BLOCK_SIZE) {
+            self.cipher.decrypt_block(GenericArray::from_mut_slice(block));
+        }
+        data
+    }
+}
+
+impl Default for LogonCipher {
+    fn default() -> LogonCipher {
+        LogonCipher::new(DEFAULT_ENCRYPTION_KEY).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn logon_content() -> MultiuserValue {
+        MultiuserValue::List(vec![
+            MultiuserValue::String("movie".to_string()),
+            MultiuserValue::String("alice".to_string()),
+            MultiuserValue::String("secret".to_string()),
+        ])
+    }
+
+    /// Eric Young's test vectors, which read blocks and keys as big-endian words.
+    #[test]
+    fn encrypts_known_answers_block_by_block() {
+        let zero_cipher = LogonCipher::new("\0\0\0\0\0\0\0\0").unwrap();
+        let zero_block = [0x4e, 0xf9, 0x97, 0x45, 0x61, 0x98, 0xdd, 0x78];
+        assert_eq!(zero_cipher.encrypt_bytes(vec![0; 8]), zero_block);
+        // Each block is encrypted on its own, and the last one is padded with zeros.
+        assert_eq!(zero_cipher.encrypt_bytes(vec![0; 11]), [zero_block, zero_block].concat());
+        assert_eq!(zero_cipher.decrypt_bytes(zero_block.to_vec()), [0; 8]);
+
+        let cipher = LogonCipher::new(&"\u{11}".repeat(8)).unwrap();
+        assert_eq!(cipher.encrypt_bytes(vec![0x11; 8]), [0x24, 0x66, 0xdd, 0x87, 0x8b, 0x96, 0x3c, 0x9d]);
+    }
+
+    #[test]
+    fn round_trips_encrypted_values() {
+        let cipher = LogonCipher::default();
+        let encrypted = cipher.encrypt(&logon_content());
+        let MultiuserValue::Media(data) = &encrypted else { panic!("encrypted content is not media") };
+        assert_eq!(data.len() % BLOCK_SIZE, 0);
+        assert!(!data.windows(6).any(|x| x == b"secret"));
+        assert_eq!(cipher.decrypt(&encrypted).unwrap(), logon_content());
+    }
+
+    #[test]
+    fn needs_the_key_it_was_encrypted_with() {
+        let encrypted = LogonCipher::new("another key").unwrap().encrypt(&logon_content());
+        assert_ne!(LogonCipher::default().decrypt(&encrypted).ok(), Some(logon_content()));
+        assert!(LogonCipher::default().decrypt(&MultiuserValue::Media(vec![1, 2, 3])).is_err());
+        assert!(LogonCipher::default().decrypt(&logon_content()).is_err());
+    }
+
+    #[test]
+    fn rejects_keys_of_invalid_length() {
+        assert!(LogonCipher::new("abc").is_err());
+        assert!(LogonCipher::new(&"k".repeat(57)).is_err());
+        assert!(LogonCipher::new(&"k".repeat(56)).is_ok());
+    }
+}
@@ -0,0 +1,172 @@
+use super::value::{write_string, MultiuserValue, ValueReader};
+
+/// Every message starts with these bytes, followed by the length of the rest of the message.
+const MESSAGE_HEADER: [u8; 2] = [0x72, 0x00];
+const MAX_MESSAGE_LENGTH: usize = 16 * 1024 * 1024;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MultiuserMessage {
+    pub error_code: i32,
+    pub recipients: Vec<String>,
+    pub sender_id: String,
+    pub subject: String,
+    pub content: MultiuserValue,
+    pub time_stamp: i32,
+}
+
+impl MultiuserMessage {
+    pub fn new(sender_id: &str, recipients: Vec<String>, subject: &str, content: MultiuserValue) -> MultiuserMessage {
+        MultiuserMessage {
+            error_code: 0,
+            recipients,
+            sender_id: sender_id.to_owned(),
+            subject: subject.to_owned(),
+            content,
+            time_stamp: 0,
+        }
+    }
+
+    /// A message generated by the Xtra itself rather than received from the server.
+    pub fn system(subject: &str, error_code: i32, content: MultiuserValue) -> MultiuserMessage {
+        MultiuserMessage {
+            error_code,
+            recipients: vec![],
+            sender_id: "System".to_owned(),
+            subject: subject.to_owned(),
+            content,
+            time_stamp: 0,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut body = vec![];
+        body.extend_from_slice(&self.error_code.to_be_bytes());
+        body.extend_from_slice(&self.time_stamp.to_be_bytes());
+        write_string(&mut body, self.subject.as_bytes());
+        write_string(&mut body, self.sender_id.as_bytes());
+        body.extend_from_slice(&(self.recipients.len() as i32).to_be_bytes());
+        for recipient in &self.recipients {
+            write_string(&mut body, recipient.as_bytes());
+        }
+        self.content.write(&mut body);
+
+        let mut out = Vec::with_capacity(body.len() + 6);
+        out.extend_from_slice(&MESSAGE_HEADER);
+        out.extend_from_slice(&(body.len() as i32).to_be_bytes());
+        out.extend_from_slice(&body);
+        out
+    }
+
+    fn decode_body(body: &[u8]) -> Result<MultiuserMessage, String> {
+        let mut reader = ValueReader::new(body);
+        let error_code = reader.read_i32()?;
+        let time_stamp = reader.read_i32()?;
+        let subject = reader.read_string()?;
+        let sender_id = reader.read_string()?;
+        let recipient_count = reader.read_i32()?.max(0);
+        let mut recipients = vec![];
+        for _ in 0..recipient_count {
+            recipients.push(reader.read_string()?);
+        }
+        let content = if reader.is_at_end() { MultiuserValue::Void } else { reader.read_value()? };
+        Ok(MultiuserMessage { error_code, recipients, sender_id, subject, content, time_stamp })
+    }
+}
+
+/// Splits the bytes received on a connection into messages, keeping incomplete ones until the rest arrives.
+#[derive(Default)]
+pub struct MultiuserMessageDecoder {
+    buffer: Vec<u8>,
+}
+
+impl MultiuserMessageDecoder {
+    /// Returns the messages completed by `data`. After an error the buffered data is dropped,
+    /// as there is no way to find the start of the next message.
+    pub fn push(&mut self, data: &[u8]) -> Result<Vec<MultiuserMessage>, String> {
+        self.buffer.extend_from_slice(data);
+        let mut messages = vec![];
+        loop {
+            if self.buffer.len() < 6 {
+                break;
+            }
+            if self.buffer[0..2] != MESSAGE_HEADER {
+                self.buffer.clear();
+                return Err("Invalid message header".to_owned());
+            }
+            let length = i32::from_be_bytes([self.buffer[2], self.buffer[3], self.buffer[4], self.buffer[5]]);
+            if length < 0 || length as usize > MAX_MESSAGE_LENGTH {
+                self.buffer.clear();
+                return Err(format!("Invalid message length {}", length));
+            }
+            let end = 6 + length as usize;
+            if self.buffer.len() < end {
+                break;
+            }
+            let message = MultiuserMessage::decode_body(&self.buffer[6..end]);
+            self.buffer.drain(..end);
+            match message {
+                Ok(message) => messages.push(message),
+                Err(err) => {
+                    self.buffer.clear();
+                    return Err(err);
+                }
+            }
+        }
+        Ok(messages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_message(subject: &str, content: MultiuserValue) -> MultiuserMessage {
+        MultiuserMessage::new("alice", vec!["bob".to_string(), "@group".to_string()], subject, content)
+    }
+
+    #[test]
+    fn decodes_an_encoded_message() {
+        let mut message = test_message("Chat", MultiuserValue::List(vec![MultiuserValue::Integer(7)]));
+        message.error_code = -1;
+        message.time_stamp = 1234;
+        let mut decoder = MultiuserMessageDecoder::default();
+        assert_eq!(decoder.push(&message.encode()).unwrap(), vec![message]);
+    }
+
+    #[test]
+    fn waits_for_split_messages() {
+        let message = test_message("Chat", MultiuserValue::String("hello".to_string()));
+        let data = message.encode();
+        let mut decoder = MultiuserMessageDecoder::default();
+        // Split inside the header as well as inside the body
+        assert!(decoder.push(&data[..3]).unwrap().is_empty());
+        assert!(decoder.push(&data[3..10]).unwrap().is_empty());
+        assert!(decoder.push(&data[10..data.len() - 1]).unwrap().is_empty());
+        assert_eq!(decoder.push(&data[data.len() - 1..]).unwrap(), vec![message]);
+    }
+
+    #[test]
+    fn splits_concatenated_messages() {
+        let first = test_message("First", MultiuserValue::Integer(1));
+        let second = test_message("Second", MultiuserValue::Void);
+        let third = test_message("Third", MultiuserValue::Point(1, 2));
+        let mut data = [first.encode(), second.encode(), third.encode()].concat();
+        let rest = data.split_off(data.len() - 4);
+        let mut decoder = MultiuserMessageDecoder::default();
+        assert_eq!(decoder.push(&data).unwrap(), vec![first, second]);
+        assert_eq!(decoder.push(&rest).unwrap(), vec![third]);
+    }
+
+    #[test]
+    fn drops_invalid_data() {
+        let mut decoder = MultiuserMessageDecoder::default();
+        assert!(decoder.push(&[0x00, 0x00, 0, 0, 0, 0]).is_err());
+        assert!(decoder.push(&[0x72, 0x00, 0xff, 0xff, 0xff, 0xff]).is_err());
+
+        // A message whose body ends early, followed by a valid one that is dropped with it
+        let message = test_message("Chat", MultiuserValue::Void);
+        let data = [&[0x72, 0x00, 0, 0, 0, 2, 0, 0][..], &message.encode()].concat();
+        assert!(decoder.push(&data).is_err());
+        assert!(decoder.push(&[]).unwrap().is_empty());
+    }
+}
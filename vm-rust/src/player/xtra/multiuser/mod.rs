@@ -1,49 +1,641 @@
-use async_std::{channel::Sender, task::spawn_local};
+pub mod encryption;
+pub mod message;
+pub mod value;
+
+use std::collections::VecDeque;
+
+use async_std::{
+    channel::{Receiver, Sender},
+    task::spawn_local,
+};
 use fxhash::FxHashMap;
 use log::warn;
-use wasm_bindgen::{closure::Closure, JsCast};
-use web_sys::{ErrorEvent, Event, MessageEvent, WebSocket};
 
-use crate::{director::lingo::datum::{Datum, DatumType}, player::{events::player_dispatch_callback_event, reserve_player_mut, reserve_player_ref, DatumRef, ScriptError}};
+use crate::{
+    director::lingo::datum::{Datum, DatumType},
+    platform::{platform, SocketConnection, SocketEvent},
+    player::{
+        events::player_dispatch_callback_event, reserve_player_mut, reserve_player_ref, DatumRef, DirPlayer,
+        ScriptError,
+    },
+};
+
+use self::{
+    encryption::{LogonCipher, DEFAULT_ENCRYPTION_KEY},
+    message::{MultiuserMessage, MultiuserMessageDecoder},
+    value::MultiuserValue,
+};
+
+use super::manager::Xtra;
+
+// Error codes of the Multiuser Xtra, as returned by its handlers and in the errorCode of messages.
+pub const ERROR_UNKNOWN: i32 = -2147216184;
+pub const ERROR_INVALID_MOVIE_ID: i32 = -2147216183;
+pub const ERROR_INVALID_USER_ID: i32 = -2147216182;
+pub const ERROR_INVALID_PASSWORD: i32 = -2147216181;
+pub const ERROR_INCOMING_DATA_LOST: i32 = -2147216180;
+pub const ERROR_INVALID_SERVER_NAME: i32 = -2147216179;
+pub const ERROR_NO_CONNECTIONS_AVAILABLE: i32 = -2147216178;
+pub const ERROR_BAD_PARAMETER: i32 = -2147216177;
+pub const ERROR_NO_SOCKET_MANAGER: i32 = -2147216176;
+pub const ERROR_NO_CURRENT_CONNECTION: i32 = -2147216175;
+pub const ERROR_NO_WAITING_MESSAGE: i32 = -2147216174;
+pub const ERROR_BAD_CONNECTION_ID: i32 = -2147216173;
+pub const ERROR_WRONG_NUMBER_OF_PARAMS: i32 = -2147216172;
+pub const ERROR_UNKNOWN_INTERNAL_ERROR: i32 = -2147216171;
+pub const ERROR_CONNECTION_REFUSED: i32 = -2147216170;
+pub const ERROR_MESSAGE_TOO_LARGE: i32 = -2147216169;
+pub const ERROR_INVALID_MESSAGE_FORMAT: i32 = -2147216168;
+pub const ERROR_INVALID_MESSAGE_LENGTH: i32 = -2147216167;
+pub const ERROR_MESSAGE_MISSING: i32 = -2147216166;
+pub const ERROR_CONNECTION_DUPLICATE: i32 = -2147216162;
+pub const ERROR_INVALID_MESSAGE_RECIPIENT: i32 = -2147216160;
+pub const ERROR_NOT_PERMITTED: i32 = -2147216153;
+
+const ERROR_STRINGS: [(i32, &str); 23] = [
+    (0, "No error"),
+    (ERROR_UNKNOWN, "Unknown error"),
+    (ERROR_INVALID_MOVIE_ID, "Invalid movie ID"),
+    (ERROR_INVALID_USER_ID, "Invalid user ID"),
+    (ERROR_INVALID_PASSWORD, "Invalid password"),
+    (ERROR_INCOMING_DATA_LOST, "Incoming data has been lost"),
+    (ERROR_INVALID_SERVER_NAME, "Invalid server name"),
+    (ERROR_NO_CONNECTIONS_AVAILABLE, "No connections are available"),
+    (ERROR_BAD_PARAMETER, "Bad parameter"),
+    (ERROR_NO_SOCKET_MANAGER, "Networking is not available"),
+    (ERROR_NO_CURRENT_CONNECTION, "No current connection"),
+    (ERROR_NO_WAITING_MESSAGE, "No waiting message"),
+    (ERROR_BAD_CONNECTION_ID, "Bad connection ID"),
+    (ERROR_WRONG_NUMBER_OF_PARAMS, "Wrong number of parameters"),
+    (ERROR_UNKNOWN_INTERNAL_ERROR, "Unknown internal error"),
+    (ERROR_CONNECTION_REFUSED, "Connection refused"),
+    (ERROR_MESSAGE_TOO_LARGE, "Message too large"),
+    (ERROR_INVALID_MESSAGE_FORMAT, "Invalid message format"),
+    (ERROR_INVALID_MESSAGE_LENGTH, "Invalid message length"),
+    (ERROR_MESSAGE_MISSING, "Message missing"),
+    (ERROR_CONNECTION_DUPLICATE, "A connection with this user ID already exists"),
+    (ERROR_INVALID_MESSAGE_RECIPIENT, "Invalid message recipient"),
+    (ERROR_NOT_PERMITTED, "Not permitted"),
+];
+
+/// The port of Multiuser servers, also used when hosting peer-to-peer connections.
+const DEFAULT_PORT: u16 = 1626;
+
+pub fn get_net_error_string(error_code: i32) -> &'static str {
+    ERROR_STRINGS
+        .iter()
+        .find(|(code, _)| *code == error_code)
+        .map_or("Unknown error", |(_, text)| text)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ConnectionMode {
+    /// Binary messages as spoken by Shockwave Multiuser Servers.
+    Smus,
+    /// Plain text, with every chunk of received data delivered as one message.
+    Text,
+}
 
+impl ConnectionMode {
+    fn from_name(name: &str) -> Option<ConnectionMode> {
+        match name.to_lowercase().as_str() {
+            "smus" => Some(ConnectionMode::Smus),
+            "text" => Some(ConnectionMode::Text),
+            _ => None,
+        }
+    }
+}
 
-pub struct MultiuserMessage {
-    pub error_code: i32,
-    pub recipients: Vec<String>,
-    pub sender_id: String,
-    pub subject: String,
-    pub content: Datum,
-    pub time_stamp: i64,
+/// A handler set with `setNetMessageHandler`, only called for messages matching its filters.
+pub struct MultiuserMessageHandler {
+    pub handler_symbol: String,
+    pub receiver: DatumRef,
+    pub subject: Option<String>,
+    pub sender: Option<String>,
+    /// Passes the message to the handler instead of leaving it for `getNetMessage`.
+    pub pass_data: bool,
+}
+
+impl MultiuserMessageHandler {
+    fn matches(&self, message: &MultiuserMessage) -> bool {
+        self.subject.as_ref().is_none_or(|x| x.eq_ignore_ascii_case(&message.subject))
+            && self.sender.as_ref().is_none_or(|x| x.eq_ignore_ascii_case(&message.sender_id))
+    }
+
+    fn specificity(&self) -> usize {
+        self.subject.is_some() as usize + self.sender.is_some() as usize
+    }
+}
+
+pub struct ClientConnection {
+    id: u32,
+    outgoing: Sender<Vec<u8>>,
+    mode: ConnectionMode,
+    is_connected: bool,
+    is_logged_on: bool,
+}
+
+pub struct PeerConnection {
+    id: u32,
+    /// Set once the peer has logged on.
+    user_id: Option<String>,
+    outgoing: Sender<Vec<u8>>,
+}
+
+/// The state of a movie acting as the server of a peer-to-peer session, after `waitForNetConnection`.
+pub struct PeerHost {
+    id: u32,
+    max_connections: usize,
+    peers: Vec<PeerConnection>,
+    start_time: i64,
 }
 
 pub struct MultiuserXtraInstance {
-    pub net_message_handler: Option<(DatumRef, String)>,
-    pub message_queue: Vec<MultiuserMessage>,
-    pub socket_tx: Option<Sender<String>>,
+    pub handlers: Vec<MultiuserMessageHandler>,
+    pub message_queue: VecDeque<MultiuserMessage>,
+    pub user_id: String,
+    pub connection: Option<ClientConnection>,
+    pub peer_host: Option<PeerHost>,
+    /// Tells the events of a closed connection apart from those of the current one.
+    connection_counter: u32,
 }
 
 impl MultiuserXtraInstance {
-    pub fn dispatch_message_handler(&self) {
-        if let Some((handler_obj_ref, handler_symbol)) = &self.net_message_handler {
-            let handler_symbol = handler_symbol.clone();
-            let handler_obj_ref = handler_obj_ref.clone();
-            player_dispatch_callback_event(handler_obj_ref, &handler_symbol, &vec![]);
-        }
+    fn find_handler(&self, message: &MultiuserMessage) -> Option<&MultiuserMessageHandler> {
+        self.handlers
+            .iter()
+            .filter(|handler| handler.matches(message))
+            .max_by_key(|handler| handler.specificity())
+    }
+
+    /// Calls the handler of the message at `index` in the queue, if one is set.
+    fn dispatch_message_handler(&mut self, index: usize) {
+        let handler = match self.message_queue.get(index).and_then(|message| self.find_handler(message)) {
+            Some(handler) => (handler.receiver.clone(), handler.handler_symbol.clone(), handler.pass_data),
+            None => return,
+        };
+        let (receiver, handler_symbol, pass_data) = handler;
+        let args = if pass_data {
+            let message = self.message_queue.remove(index).unwrap();
+            vec![reserve_player_mut(|player| message_to_datum(player, message))]
+        } else {
+            vec![]
+        };
+        player_dispatch_callback_event(receiver, &handler_symbol, &args);
     }
 
     pub fn dispatch_message(&mut self, message: MultiuserMessage) {
-        self.message_queue.push(message);
-        self.dispatch_message_handler();
+        self.message_queue.push_back(message);
+        self.dispatch_message_handler(self.message_queue.len() - 1);
     }
 
     pub fn next_message(&mut self) -> Option<MultiuserMessage> {
-        if self.message_queue.is_empty() {
-            return None;
+        self.message_queue.pop_front()
+    }
+
+    fn next_connection_id(&mut self) -> u32 {
+        self.connection_counter += 1;
+        self.connection_counter
+    }
+
+    fn handle_client_event(
+        &mut self,
+        connection_id: u32,
+        event: SocketEvent,
+        decoder: &mut MultiuserMessageDecoder,
+        logon_content: &MultiuserValue,
+    ) -> bool {
+        let user_id = self.user_id.clone();
+        let connection = match &mut self.connection {
+            Some(connection) if connection.id == connection_id => connection,
+            _ => return false,
+        };
+        let mut messages = vec![];
+        let mut is_open = true;
+        match event {
+            SocketEvent::Connected => {
+                connection.is_connected = true;
+                match connection.mode {
+                    ConnectionMode::Smus => {
+                        let logon =
+                            MultiuserMessage::new(&user_id, vec!["System".to_owned()], "Logon", logon_content.clone());
+                        let _ = connection.outgoing.try_send(logon.encode());
+                    }
+                    ConnectionMode::Text => {
+                        connection.is_logged_on = true;
+                        messages.push(MultiuserMessage::system("ConnectToNetServer", 0, MultiuserValue::Void));
+                    }
+                }
+            }
+            SocketEvent::Data(data) => match connection.mode {
+                ConnectionMode::Text => {
+                    let text = String::from_utf8_lossy(&data).to_string();
+                    messages.push(MultiuserMessage::system("String", 0, MultiuserValue::String(text)));
+                }
+                ConnectionMode::Smus => match decoder.push(&data) {
+                    Ok(received) => {
+                        for mut message in received {
+                            // The server answers the logon, which movies know as the reply to connectToNetServer.
+                            if !connection.is_logged_on && message.subject == "Logon" {
+                                connection.is_logged_on = message.error_code == 0;
+                                message.subject = "ConnectToNetServer".to_owned();
+                            }
+                            messages.push(message);
+                        }
+                    }
+                    Err(err) => {
+                        warn!("Invalid Multiuser message: {}", err);
+                        messages.push(MultiuserMessage::system(
+                            "ConnectionProblem",
+                            ERROR_INVALID_MESSAGE_FORMAT,
+                            MultiuserValue::Void,
+                        ));
+                    }
+                },
+            },
+            SocketEvent::Error(err) => {
+                warn!("Multiuser connection error: {}", err);
+                if !connection.is_connected {
+                    messages.push(MultiuserMessage::system(
+                        "ConnectToNetServer",
+                        ERROR_CONNECTION_REFUSED,
+                        MultiuserValue::Void,
+                    ));
+                    is_open = false;
+                }
+            }
+            SocketEvent::Closed => {
+                messages.push(MultiuserMessage::system(
+                    "ConnectionProblem",
+                    ERROR_NO_CURRENT_CONNECTION,
+                    MultiuserValue::Void,
+                ));
+                is_open = false;
+            }
+        }
+        if !is_open {
+            self.connection = None;
+        }
+        for message in messages {
+            self.dispatch_message(message);
+        }
+        is_open
+    }
+
+    fn accept_peer(&mut self, host_id: u32, outgoing: Sender<Vec<u8>>) -> Option<u32> {
+        let peer_id = self.next_connection_id();
+        let host = self.peer_host.as_mut().filter(|host| host.id == host_id)?;
+        host.peers.push(PeerConnection { id: peer_id, user_id: None, outgoing });
+        Some(peer_id)
+    }
+
+    fn remove_peer(&mut self, peer_id: u32) {
+        if let Some(host) = &mut self.peer_host {
+            host.peers.retain(|peer| peer.id != peer_id);
+        }
+    }
+
+    fn handle_peer_message(&mut self, host_id: u32, peer_id: u32, mut message: MultiuserMessage) -> bool {
+        let host_user_id = self.user_id.clone();
+        let host = match &mut self.peer_host {
+            Some(host) if host.id == host_id => host,
+            _ => return false,
+        };
+        let logged_on_count = host.peers.iter().filter(|peer| peer.user_id.is_some()).count();
+        let max_connections = host.max_connections;
+        let is_duplicate = |user_id: &str| {
+            user_id.eq_ignore_ascii_case(&host_user_id)
+                || host.peers.iter().any(|peer| peer.user_id.as_ref().is_some_and(|x| x.eq_ignore_ascii_case(user_id)))
+        };
+        let peer = match host.peers.iter().find(|peer| peer.id == peer_id) {
+            Some(peer) => peer,
+            None => return false,
+        };
+
+        let user_id = match &peer.user_id {
+            Some(user_id) => user_id.clone(),
+            None => {
+                if message.subject != "Logon" {
+                    warn!("Ignoring message {} from a peer that has not logged on", message.subject);
+                    return true;
+                }
+                // The logon content is the movie ID, user ID and password of the peer, encrypted
+                // unless the peer logs on like a Text mode connection would.
+                let content = match &message.content {
+                    MultiuserValue::Media(_) => match LogonCipher::default().decrypt(&message.content) {
+                        Ok(content) => content,
+                        Err(err) => {
+                            warn!("Cannot decrypt the logon of a peer: {}", err);
+                            MultiuserValue::Void
+                        }
+                    },
+                    content => content.clone(),
+                };
+                let (movie_id, user_id) = match &content {
+                    MultiuserValue::List(items) => match (items.first(), items.get(1)) {
+                        (Some(MultiuserValue::String(movie_id)), Some(MultiuserValue::String(user_id))) => {
+                            (movie_id.clone(), user_id.clone())
+                        }
+                        _ => (String::new(), String::new()),
+                    },
+                    _ => (String::new(), String::new()),
+                };
+                let error_code = if user_id.is_empty() {
+                    ERROR_INVALID_USER_ID
+                } else if is_duplicate(&user_id) {
+                    ERROR_CONNECTION_DUPLICATE
+                } else if logged_on_count >= max_connections {
+                    ERROR_NO_CONNECTIONS_AVAILABLE
+                } else {
+                    0
+                };
+                let mut reply =
+                    MultiuserMessage::system("Logon", error_code, MultiuserValue::String(movie_id));
+                reply.recipients = vec![user_id.clone()];
+                let _ = peer.outgoing.try_send(reply.encode());
+                if error_code != 0 {
+                    self.remove_peer(peer_id);
+                    return false;
+                }
+                if let Some(peer) = host.peers.iter_mut().find(|peer| peer.id == peer_id) {
+                    peer.user_id = Some(user_id);
+                }
+                return true;
+            }
+        };
+        message.sender_id = user_id;
+        self.route_peer_message(message);
+        true
+    }
+
+    /// Delivers a message of the peer-to-peer session to its recipients, which may include the host itself.
+    fn route_peer_message(&mut self, mut message: MultiuserMessage) -> i32 {
+        let host = match &self.peer_host {
+            Some(host) => host,
+            None => return ERROR_NO_CURRENT_CONNECTION,
+        };
+        message.time_stamp = (platform().clock.now_millis() - host.start_time) as i32;
+        let is_from_host = message.sender_id.eq_ignore_ascii_case(&self.user_id);
+        let mut deliver_locally = false;
+        let mut targets: Vec<&PeerConnection> = vec![];
+        for recipient in &message.recipients {
+            if recipient.eq_ignore_ascii_case("@AllUsers") {
+                deliver_locally = !is_from_host;
+                targets.extend(host.peers.iter().filter(|peer| {
+                    peer.user_id.as_ref().is_some_and(|x| !x.eq_ignore_ascii_case(&message.sender_id))
+                }));
+            } else if recipient.eq_ignore_ascii_case(&self.user_id) {
+                deliver_locally = true;
+            } else if let Some(peer) = host
+                .peers
+                .iter()
+                .find(|peer| peer.user_id.as_ref().is_some_and(|x| x.eq_ignore_ascii_case(recipient)))
+            {
+                targets.push(peer);
+            } else {
+                return ERROR_INVALID_MESSAGE_RECIPIENT;
+            }
+        }
+        targets.sort_by_key(|peer| peer.id);
+        targets.dedup_by_key(|peer| peer.id);
+        if !targets.is_empty() {
+            let data = message.encode();
+            for peer in targets {
+                let _ = peer.outgoing.try_send(data.clone());
+            }
+        }
+        if deliver_locally {
+            self.dispatch_message(message);
+        }
+        0
+    }
+
+    fn send_message(&mut self, recipients: Vec<String>, subject: &str, content: MultiuserValue) -> i32 {
+        if let Some(connection) = &self.connection {
+            if !connection.is_connected {
+                return ERROR_NO_CURRENT_CONNECTION;
+            }
+            let data = match connection.mode {
+                ConnectionMode::Smus => MultiuserMessage::new(&self.user_id, recipients, subject, content).encode(),
+                ConnectionMode::Text => match content {
+                    MultiuserValue::String(text) | MultiuserValue::Symbol(text) => text.into_bytes(),
+                    MultiuserValue::Media(data) => data,
+                    _ => return ERROR_BAD_PARAMETER,
+                },
+            };
+            match connection.outgoing.try_send(data) {
+                Ok(_) => 0,
+                Err(_) => ERROR_NO_CURRENT_CONNECTION,
+            }
+        } else if self.peer_host.is_some() {
+            let message = MultiuserMessage::new(&self.user_id, recipients, subject, content);
+            self.route_peer_message(message)
+        } else {
+            ERROR_NO_CURRENT_CONNECTION
+        }
+    }
+}
+
+fn run_client_connection(
+    instance_id: u32,
+    connection_id: u32,
+    events: Receiver<SocketEvent>,
+    logon_content: MultiuserValue,
+) {
+    spawn_local(async move {
+        let mut decoder = MultiuserMessageDecoder::default();
+        while let Ok(event) = events.recv().await {
+            let is_open = borrow_multiuser_manager_mut(|manager| {
+                manager.instances.get_mut(&instance_id).is_some_and(|instance| {
+                    instance.handle_client_event(connection_id, event, &mut decoder, &logon_content)
+                })
+            });
+            if !is_open {
+                break;
+            }
+        }
+    });
+}
+
+fn run_peer_connection(instance_id: u32, host_id: u32, peer_id: u32, events: Receiver<SocketEvent>) {
+    spawn_local(async move {
+        let mut decoder = MultiuserMessageDecoder::default();
+        while let Ok(event) = events.recv().await {
+            let messages = match event {
+                SocketEvent::Data(data) => decoder.push(&data),
+                SocketEvent::Connected => continue,
+                SocketEvent::Error(err) => Err(err),
+                SocketEvent::Closed => break,
+            };
+            let is_open = borrow_multiuser_manager_mut(|manager| {
+                let instance = match manager.instances.get_mut(&instance_id) {
+                    Some(instance) => instance,
+                    None => return false,
+                };
+                match messages {
+                    Ok(messages) => messages
+                        .into_iter()
+                        .all(|message| instance.handle_peer_message(host_id, peer_id, message)),
+                    Err(err) => {
+                        warn!("Dropping peer connection: {}", err);
+                        false
+                    }
+                }
+            });
+            if !is_open {
+                break;
+            }
+        }
+        borrow_multiuser_manager_mut(|manager| {
+            if let Some(instance) = manager.instances.get_mut(&instance_id) {
+                instance.remove_peer(peer_id);
+            }
+        });
+    });
+}
+
+fn run_peer_host(instance_id: u32, host_id: u32, incoming: Receiver<SocketConnection>) {
+    spawn_local(async move {
+        while let Ok(SocketConnection { outgoing, events }) = incoming.recv().await {
+            let peer_id = borrow_multiuser_manager_mut(|manager| {
+                manager
+                    .instances
+                    .get_mut(&instance_id)
+                    .and_then(|instance| instance.accept_peer(host_id, outgoing))
+            });
+            match peer_id {
+                Some(peer_id) => run_peer_connection(instance_id, host_id, peer_id, events),
+                None => break,
+            }
+        }
+    });
+}
+
+fn message_to_datum(player: &mut DirPlayer, message: MultiuserMessage) -> DatumRef {
+    let recipient_refs = message
+        .recipients
+        .iter()
+        .map(|recipient| player.alloc_datum(Datum::String(recipient.clone())))
+        .collect();
+
+    let content = message.content.into_datum(player);
+    let error_code = player.alloc_datum(Datum::Int(message.error_code));
+    let recipients = player.alloc_datum(Datum::List(DatumType::List, recipient_refs, false));
+    let sender_id = player.alloc_datum(Datum::String(message.sender_id));
+    let subject = player.alloc_datum(Datum::String(message.subject));
+    let content = player.alloc_datum(content);
+    let time_stamp = player.alloc_datum(Datum::Int(message.time_stamp));
+
+    let error_code_key = player.alloc_datum(Datum::String("errorCode".to_string()));
+    let recipients_key = player.alloc_datum(Datum::String("recipients".to_string()));
+    let sender_id_key = player.alloc_datum(Datum::String("senderID".to_string()));
+    let subject_key = player.alloc_datum(Datum::String("subject".to_string()));
+    let content_key = player.alloc_datum(Datum::String("content".to_string()));
+    let time_stamp_key = player.alloc_datum(Datum::String("timeStamp".to_string()));
+
+    player.alloc_datum(Datum::PropList(
+        vec![
+            (error_code_key, error_code),
+            (recipients_key, recipients),
+            (sender_id_key, sender_id),
+            (subject_key, subject),
+            (content_key, content),
+            (time_stamp_key, time_stamp),
+        ],
+        false,
+    ))
+}
+
+/// Arguments given either by position or as a single property list, as most Multiuser handlers accept both.
+struct MultiuserArgs<'a> {
+    player: &'a DirPlayer,
+    args: &'a Vec<DatumRef>,
+    prop_list: Option<&'a Vec<(DatumRef, DatumRef)>>,
+}
+
+impl<'a> MultiuserArgs<'a> {
+    fn new(player: &'a DirPlayer, args: &'a Vec<DatumRef>) -> MultiuserArgs<'a> {
+        let prop_list = match args.as_slice() {
+            [single] => match player.get_datum(single) {
+                Datum::PropList(pairs, _) => Some(pairs),
+                _ => None,
+            },
+            _ => None,
+        };
+        MultiuserArgs { player, args, prop_list }
+    }
+
+    fn get(&self, index: usize, name: &str) -> Option<&'a Datum> {
+        let datum = match self.prop_list {
+            Some(pairs) => pairs
+                .iter()
+                .find(|(key, _)| match self.player.get_datum(key) {
+                    Datum::Symbol(key) | Datum::String(key) => key.eq_ignore_ascii_case(name),
+                    _ => false,
+                })
+                .map(|(_, value)| self.player.get_datum(value)),
+            None => self.args.get(index).map(|x| self.player.get_datum(x)),
+        };
+        datum.filter(|x| !x.is_void())
+    }
+
+    fn get_string(&self, index: usize, name: &str) -> Result<Option<String>, ScriptError> {
+        self.get(index, name).map(|x| x.string_value()).transpose()
+    }
+
+    fn get_recipients(&self, index: usize) -> Result<Vec<String>, ScriptError> {
+        match self.get(index, "recipients") {
+            Some(Datum::List(_, items, _)) => items.iter().map(|x| self.player.get_datum(x).string_value()).collect(),
+            Some(datum) => Ok(vec![datum.string_value()?]),
+            None => Ok(vec![]),
         }
-        Some(self.message_queue.remove(0))
     }
 }
 
+struct ConnectParams {
+    user_id: String,
+    password: String,
+    server: String,
+    port: u16,
+    movie_id: String,
+    mode: ConnectionMode,
+    encryption_key: String,
+}
+
+fn read_connect_params(args: &Vec<DatumRef>) -> Result<Option<ConnectParams>, ScriptError> {
+    reserve_player_ref(|player| {
+        let args = MultiuserArgs::new(player, args);
+        let server = match args.get_string(2, "serverID")? {
+            Some(server) => server,
+            None => return Ok(None),
+        };
+        let port = match args.get(3, "portNumber") {
+            Some(port) => port.int_value()? as u16,
+            None => DEFAULT_PORT,
+        };
+        let mode = match args.get(5, "mode") {
+            Some(Datum::Symbol(name)) | Some(Datum::String(name)) => match ConnectionMode::from_name(name) {
+                Some(mode) => mode,
+                None => return Ok(None),
+            },
+            _ => ConnectionMode::Smus,
+        };
+        Ok(Some(ConnectParams {
+            user_id: args.get_string(0, "userID")?.unwrap_or_default(),
+            password: args.get_string(1, "password")?.unwrap_or_default(),
+            server,
+            port,
+            movie_id: args.get_string(4, "movieID")?.unwrap_or_default(),
+            mode,
+            encryption_key: args.get_string(6, "encryptionKey")?.unwrap_or_else(|| DEFAULT_ENCRYPTION_KEY.to_owned()),
+        }))
+    })
+}
+
+fn alloc_int(value: i32) -> Result<DatumRef, ScriptError> {
+    Ok(reserve_player_mut(|player| player.alloc_datum(Datum::Int(value))))
+}
+
 pub struct MultiuserXtraManager {
     pub instances: FxHashMap<u32, MultiuserXtraInstance>,
     pub instance_counter: u32,
@@ -52,28 +644,28 @@ pub struct MultiuserXtraManager {
 impl MultiuserXtraManager {
     pub fn create_instance(&mut self, _: &Vec<DatumRef>) -> u32 {
         self.instance_counter += 1;
-        self.instances
-            .insert(self.instance_counter, MultiuserXtraInstance {
-                net_message_handler: None,
-                message_queue: vec![],
-                socket_tx: None,
-            });
+        self.instances.insert(
+            self.instance_counter,
+            MultiuserXtraInstance {
+                handlers: vec![],
+                message_queue: VecDeque::new(),
+                user_id: String::new(),
+                connection: None,
+                peer_host: None,
+                connection_counter: 0,
+            },
+        );
         self.instance_counter
     }
 
-    pub fn has_instance_async_handler(_name: &String) -> bool {
-        false
-    }
-
-    pub async fn call_instance_async_handler(
-        handler_name: &String,
+    fn with_instance<T>(
         instance_id: u32,
-        _args: &Vec<DatumRef>,
-    ) -> Result<DatumRef, ScriptError> {
-        Err(ScriptError::new(format!(
-            "No async handler {} found for Multiuser xtra instance #{}",
-            handler_name, instance_id
-        )))
+        callback: impl FnOnce(&mut MultiuserXtraInstance) -> T,
+    ) -> Result<T, ScriptError> {
+        borrow_multiuser_manager_mut(|manager| match manager.instances.get_mut(&instance_id) {
+            Some(instance) => Ok(callback(instance)),
+            None => Err(ScriptError::new(format!("Multiuser xtra instance #{} not found", instance_id))),
+        })
     }
 
     pub fn call_instance_handler(
@@ -84,148 +676,217 @@ impl MultiuserXtraManager {
         match handler_name.as_str() {
             "setNetBufferLimits" => Ok(DatumRef::Void),
             "setNetMessageHandler" => {
-                let mut multiusr_manager = unsafe { MULTIUSER_XTRA_MANAGER_OPT.as_mut().unwrap() };
-                let instance = multiusr_manager.instances.get_mut(&instance_id).unwrap();
-                reserve_player_mut(|player| {
-                    let handler_symbol = player.get_datum(args.get(0).unwrap());
-                    let handler_obj_ref = args.get(1).unwrap().clone();
-                    // TODO subject and sender
-                    if handler_symbol.is_void() {
-                        instance.net_message_handler = None;
-                    } else {
-                        let handler_symbol = handler_symbol.symbol_value()?;
-                        instance.net_message_handler = Some((handler_obj_ref, handler_symbol));
+                // #handler, object {, subject, sender, passData}
+                let (handler_symbol, receiver, subject, sender, pass_data) = reserve_player_ref(|player| {
+                    let handler_symbol = match args.first().map(|x| player.get_datum(x)) {
+                        Some(datum) if !datum.is_void() => Some(datum.symbol_value()?),
+                        _ => None,
+                    };
+                    let receiver = args.get(1).cloned().unwrap_or(DatumRef::Void);
+                    let get_filter = |index: usize| -> Result<Option<String>, ScriptError> {
+                        match args.get(index).map(|x| player.get_datum(x)) {
+                            Some(datum) if !datum.is_void() => Ok(Some(datum.string_value()?)),
+                            _ => Ok(None),
+                        }
+                    };
+                    let pass_data = match args.get(4).map(|x| player.get_datum(x)) {
+                        Some(datum) if !datum.is_void() => datum.int_value()? != 0,
+                        _ => false,
+                    };
+                    Ok((handler_symbol, receiver, get_filter(2)?, get_filter(3)?, pass_data))
+                })?;
+                Self::with_instance(instance_id, |instance| {
+                    instance
+                        .handlers
+                        .retain(|handler| handler.subject != subject || handler.sender != sender);
+                    if let Some(handler_symbol) = handler_symbol {
+                        instance.handlers.push(MultiuserMessageHandler {
+                            handler_symbol,
+                            receiver,
+                            subject,
+                            sender,
+                            pass_data,
+                        });
                     }
-
-                    // TODO return error code?
-                    Ok(player.alloc_datum(Datum::Int(0)))
-                })
+                })?;
+                alloc_int(0)
             }
             "connectToNetServer" => {
-                let mut multiusr_manager = unsafe { MULTIUSER_XTRA_MANAGER_OPT.as_mut().unwrap() };
-                let instance = multiusr_manager.instances.get_mut(&instance_id).unwrap();
-                if let Some((handler_obj_ref, handler_symbol)) = &instance.net_message_handler {
-                    let _handler_symbol = handler_symbol.clone();
-                    let _handler_obj_ref = handler_obj_ref.clone();
-                }
-                // userNameString, passwordString, serverIDString, portNumber, movieIDString {, mode, encryptionKey
-                let (host, port) = reserve_player_ref(|player| {
-                    let host = player.get_datum(args.get(2).unwrap()).string_value()?;
-                    let port = player.get_datum(args.get(3).unwrap()).int_value()?;
-
-                    Ok((host, port))
-                })?;
-                let ws_url = format!("ws://{}:{}", host, port);
-                let socket = WebSocket::new(&ws_url).unwrap();
-                socket.set_binary_type(web_sys::BinaryType::Arraybuffer);
-                
-                let socket_clone = socket.clone();
-                let onmessage_callback = Closure::<dyn FnMut(_)>::new(move |e: MessageEvent| {
-                    // e.data().dyn_into::<js_sys::JsString>()
-                    let data = e.data().dyn_into::<js_sys::ArrayBuffer>().unwrap();
-                    let array = js_sys::Uint8Array::new(&data);
-                    let vec = array.to_vec();
-                    let string = String::from_utf8_lossy(&vec);
-                    warn!("WebSocket message: {:?}", string);
-
-                    let mut multiusr_manager = unsafe { MULTIUSER_XTRA_MANAGER_OPT.as_mut().unwrap() };
-                    let instance = multiusr_manager.instances.get_mut(&instance_id).unwrap();
-                    instance.dispatch_message(MultiuserMessage {
-                        error_code: 0,
-                        recipients: vec!["*".to_string()],
-                        sender_id: "System".to_string(),
-                        subject: "String".to_string(),
-                        content: Datum::String(string.to_string()),
-                        time_stamp: 0, // TODO timestamp
+                let params = match read_connect_params(args)? {
+                    Some(params) => params,
+                    None => return alloc_int(ERROR_BAD_PARAMETER),
+                };
+                let ConnectParams { user_id, password, server, port, movie_id, mode, encryption_key } = params;
+                let cipher = match LogonCipher::new(&encryption_key) {
+                    Ok(cipher) => cipher,
+                    Err(err) => {
+                        warn!("connectToNetServer failed: {}", err);
+                        return alloc_int(ERROR_BAD_PARAMETER);
+                    }
+                };
+                let logon_content = cipher.encrypt(&MultiuserValue::List(vec![
+                    MultiuserValue::String(movie_id),
+                    MultiuserValue::String(user_id.clone()),
+                    MultiuserValue::String(password),
+                ]));
+                let SocketConnection { outgoing, events } = platform().sockets.connect(&server, port);
+                let connection_id = Self::with_instance(instance_id, |instance| {
+                    let connection_id = instance.next_connection_id();
+                    instance.user_id = user_id;
+                    instance.connection = Some(ClientConnection {
+                        id: connection_id,
+                        outgoing,
+                        mode,
+                        is_connected: false,
+                        is_logged_on: false,
                     });
-                });
-                let onerror_callback = Closure::<dyn FnMut(_)>::new(move |e: ErrorEvent| {
-                    // e.data().dyn_into::<js_sys::JsString>()
-                    warn!("WebSocket error: {:?}", e);
-                });
-                let onopen_callback = Closure::<dyn FnMut(_)>::new(move |e: Event| {
-                    warn!("WebSocket opened");
-                    let mut multiusr_manager = unsafe { MULTIUSER_XTRA_MANAGER_OPT.as_mut().unwrap() };
-                    let instance = multiusr_manager.instances.get_mut(&instance_id).unwrap();
-                    instance.dispatch_message(MultiuserMessage {
-                        error_code: 0,
-                        recipients: vec!["*".to_string()],
-                        sender_id: "System".to_string(),
-                        subject: "ConnectToNetServer".to_string(),
-                        content: Datum::Void,
-                        time_stamp: 0, // TODO timestamp
+                    connection_id
+                })?;
+                run_client_connection(instance_id, connection_id, events, logon_content);
+                alloc_int(0)
+            }
+            "waitForNetConnection" => {
+                // userID, maxConnections
+                let (user_id, max_connections) = reserve_player_ref(|player| {
+                    let args = MultiuserArgs::new(player, args);
+                    let max_connections = match args.get(1, "maxConnections") {
+                        Some(datum) => datum.int_value()?.max(0) as usize,
+                        None => 1,
+                    };
+                    Ok((args.get_string(0, "userID")?, max_connections))
+                })?;
+                let user_id = match user_id {
+                    Some(user_id) if !user_id.is_empty() => user_id,
+                    _ => return alloc_int(ERROR_INVALID_USER_ID),
+                };
+                let incoming = match platform().sockets.listen(DEFAULT_PORT) {
+                    Ok(incoming) => incoming,
+                    Err(err) => {
+                        warn!("waitForNetConnection failed: {}", err);
+                        return alloc_int(ERROR_NO_SOCKET_MANAGER);
+                    }
+                };
+                let host_id = Self::with_instance(instance_id, |instance| {
+                    let host_id = instance.next_connection_id();
+                    instance.user_id = user_id;
+                    instance.peer_host = Some(PeerHost {
+                        id: host_id,
+                        max_connections,
+                        peers: vec![],
+                        start_time: platform().clock.now_millis(),
                     });
-                });
-                socket.set_onmessage(Some(onmessage_callback.as_ref().unchecked_ref()));
-                socket.set_onerror(Some(onerror_callback.as_ref().unchecked_ref()));
-                socket.set_onopen(Some(onopen_callback.as_ref().unchecked_ref()));
-
-                let (tx, rx) = async_std::channel::unbounded();
-                instance.socket_tx = Some(tx);
-                spawn_local(async move {
-                    while let Ok(message) = rx.recv().await {
-                        warn!("Sending message: {:?}", message);
-                        socket_clone.send_with_u8_array(&message.as_bytes()).unwrap();
+                    host_id
+                })?;
+                run_peer_host(instance_id, host_id, incoming);
+                alloc_int(0)
+            }
+            "breakConnection" => {
+                let user_id = reserve_player_ref(|player| match args.first().map(|x| player.get_datum(x)) {
+                    Some(datum) if !datum.is_void() => Ok(Some(datum.string_value()?)),
+                    _ => Ok(None),
+                })?;
+                let error_code = Self::with_instance(instance_id, |instance| match user_id {
+                    Some(user_id) => {
+                        let peer_id = instance.peer_host.as_ref().and_then(|host| {
+                            host.peers
+                                .iter()
+                                .find(|peer| peer.user_id.as_ref().is_some_and(|x| x.eq_ignore_ascii_case(&user_id)))
+                                .map(|peer| peer.id)
+                        });
+                        match peer_id {
+                            Some(peer_id) => {
+                                instance.remove_peer(peer_id);
+                                0
+                            }
+                            None if instance.connection.is_some() => {
+                                instance.connection = None;
+                                0
+                            }
+                            None => ERROR_BAD_CONNECTION_ID,
+                        }
                     }
-                });
-
-                // Forget the callback to keep it alive
-                onmessage_callback.forget();
-                onerror_callback.forget();
-                onopen_callback.forget();
-
-                Ok(DatumRef::Void)
-            },
+                    None if instance.connection.is_some() || instance.peer_host.is_some() => {
+                        // Dropping the senders closes the sockets.
+                        instance.connection = None;
+                        instance.peer_host = None;
+                        0
+                    }
+                    None => ERROR_NO_CURRENT_CONNECTION,
+                })?;
+                alloc_int(error_code)
+            }
+            "getPeerConnectionList" => {
+                let user_ids = Self::with_instance(instance_id, |instance| {
+                    instance
+                        .peer_host
+                        .as_ref()
+                        .map(|host| host.peers.iter().filter_map(|peer| peer.user_id.clone()).collect::<Vec<_>>())
+                        .unwrap_or_default()
+                })?;
+                Ok(reserve_player_mut(|player| {
+                    let items = user_ids
+                        .into_iter()
+                        .map(|user_id| player.alloc_datum(Datum::String(user_id)))
+                        .collect();
+                    player.alloc_datum(Datum::List(DatumType::List, items, false))
+                }))
+            }
+            "getNetErrorString" => {
+                let error_code = reserve_player_ref(|player| match args.first() {
+                    Some(error_code) => player.get_datum(error_code).int_value(),
+                    None => Ok(0),
+                })?;
+                Ok(reserve_player_mut(|player| {
+                    player.alloc_datum(Datum::String(get_net_error_string(error_code).to_owned()))
+                }))
+            }
+            "getNumberWaitingNetMessages" => {
+                let count = Self::with_instance(instance_id, |instance| instance.message_queue.len())?;
+                alloc_int(count as i32)
+            }
+            "checkNetMessages" => {
+                // Calls the message handlers again for up to the given number of waiting messages.
+                let max_count = reserve_player_ref(|player| match args.first().map(|x| player.get_datum(x)) {
+                    Some(datum) if !datum.is_void() => datum.int_value(),
+                    _ => Ok(1),
+                })?;
+                Self::with_instance(instance_id, |instance| {
+                    let mut index = 0;
+                    for _ in 0..max_count.max(0) {
+                        if index >= instance.message_queue.len() {
+                            break;
+                        }
+                        let count = instance.message_queue.len();
+                        instance.dispatch_message_handler(index);
+                        // Messages passed to their handler have left the queue.
+                        if instance.message_queue.len() == count {
+                            index += 1;
+                        }
+                    }
+                })?;
+                alloc_int(0)
+            }
             "getNetMessage" => {
-                let mut multiusr_manager = unsafe { MULTIUSER_XTRA_MANAGER_OPT.as_mut().unwrap() };
-                let instance = multiusr_manager.instances.get_mut(&instance_id).unwrap();
-                if let Some(message) = instance.next_message() {
-                    reserve_player_mut(|player| {
-                        let recipient_refs = message.recipients.iter().map(|recipient| {
-                            player.alloc_datum(Datum::String(recipient.clone()))
-                        }).collect();
-
-                        let error_code = player.alloc_datum(Datum::Int(message.error_code));
-                        let recipients = player.alloc_datum(Datum::List(DatumType::List, recipient_refs, false));
-                        let sender_id = player.alloc_datum(Datum::String(message.sender_id));
-                        let subject = player.alloc_datum(Datum::String(message.subject));
-                        let content = player.alloc_datum(message.content);
-                        let time_stamp = player.alloc_datum(Datum::Int(message.time_stamp as i32)); // TODO: i64
-
-                        let error_code_key = player.alloc_datum(Datum::String("errorCode".to_string()));
-                        let recipients_key = player.alloc_datum(Datum::String("recipients".to_string()));
-                        let sender_id_key = player.alloc_datum(Datum::String("senderID".to_string()));
-                        let subject_key = player.alloc_datum(Datum::String("subject".to_string()));
-                        let content_key = player.alloc_datum(Datum::String("content".to_string()));
-                        let time_stamp_key = player.alloc_datum(Datum::String("timeStamp".to_string()));
-
-                        Ok(player.alloc_datum(Datum::PropList(vec![
-                            (error_code_key, error_code),
-                            (recipients_key, recipients),
-                            (sender_id_key, sender_id),
-                            (subject_key, subject),
-                            (content_key, content),
-                            (time_stamp_key, time_stamp),
-                        ], false)))
-                    })
-                } else {
-                    Ok(DatumRef::Void)
-                }
+                let message = Self::with_instance(instance_id, |instance| instance.next_message())?;
+                let message = message.unwrap_or_else(|| {
+                    MultiuserMessage::system("", ERROR_NO_WAITING_MESSAGE, MultiuserValue::Void)
+                });
+                Ok(reserve_player_mut(|player| message_to_datum(player, message)))
             }
             "sendNetMessage" => {
-                let mut multiusr_manager = unsafe { MULTIUSER_XTRA_MANAGER_OPT.as_mut().unwrap() };
-                let instance = multiusr_manager.instances.get_mut(&instance_id).unwrap();
-                reserve_player_ref(|player| {
-                    let msg_string = player.get_datum(args.get(2).unwrap()).string_value()?;
-                    warn!("sendNetMessage: {:?}", msg_string);
-                    if let Some(tx) = &instance.socket_tx {
-                        tx.try_send(msg_string).unwrap();
-                        Ok(DatumRef::Void)
-                    } else {
-                        Err(ScriptError::new("Socket not connected".to_string()))
-                    }
-                })
-            },
+                // recipients, subject, content, or a property list with the same names.
+                let (recipients, subject, content) = reserve_player_ref(|player| {
+                    let args = MultiuserArgs::new(player, args);
+                    let content = match args.get(2, "content") {
+                        Some(content) => MultiuserValue::from_datum(player, content)?,
+                        None => MultiuserValue::Void,
+                    };
+                    Ok((args.get_recipients(0)?, args.get_string(1, "subject")?.unwrap_or_default(), content))
+                })?;
+                let error_code =
+                    Self::with_instance(instance_id, |instance| instance.send_message(recipients, &subject, content))?;
+                alloc_int(error_code)
+            }
             _ => Err(ScriptError::new(format!(
                 "No handler {} found for Multiuser xtra instance #{}",
                 handler_name, instance_id
@@ -241,9 +902,52 @@ impl MultiuserXtraManager {
     }
 }
 
+const INTERFACE: &str = "xtra Multiuser
+new object me -- create a new Multiuser instance
+-- Connecting
+connectToNetServer object me, *userID, *password, *serverID, *portNumber, *movieID, *mode, *encryptionKey
+waitForNetConnection object me, string userID, integer maxConnections
+breakConnection object me, *userID
+getPeerConnectionList object me
+-- Messages
+setNetMessageHandler object me, symbol handler, object receiver, *subject, *sender, *passData
+sendNetMessage object me, *recipients, *subject, *content
+getNetMessage object me
+getNumberWaitingNetMessages object me
+checkNetMessages object me, *count
+setNetBufferLimits object me, *
+getNetErrorString object me, integer errorCode
+";
+
+/// The Multiuser Xtra, for messaging through Shockwave Multiuser Servers or between peers.
+pub struct MultiuserXtra {}
+
+impl Xtra for MultiuserXtra {
+    fn name(&self) -> &str {
+        "Multiuser"
+    }
+
+    fn interface(&self) -> String {
+        INTERFACE.to_owned()
+    }
+
+    fn create_instance(&self, args: &Vec<DatumRef>) -> Result<u32, ScriptError> {
+        Ok(borrow_multiuser_manager_mut(|manager| manager.create_instance(args)))
+    }
+
+    fn call_instance_handler(
+        &self,
+        handler_name: &String,
+        instance_id: u32,
+        args: &Vec<DatumRef>,
+    ) -> Result<DatumRef, ScriptError> {
+        MultiuserXtraManager::call_instance_handler(handler_name, instance_id, args)
+    }
+}
+
 pub fn borrow_multiuser_manager_mut<T>(callback: impl FnOnce(&mut MultiuserXtraManager) -> T) -> T {
-    let mut manager = unsafe { MULTIUSER_XTRA_MANAGER_OPT.as_mut().unwrap() };
-    callback(&mut *manager)
+    let manager = unsafe { MULTIUSER_XTRA_MANAGER_OPT.as_mut().unwrap() };
+    callback(manager)
 }
 
 // lazy_static! {
@@ -252,3 +956,375 @@ pub fn borrow_multiuser_manager_mut<T>(callback: impl FnOnce(&mut MultiuserXtraM
 // }
 
 pub static mut MULTIUSER_XTRA_MANAGER_OPT: Option<MultiuserXtraManager> = None;
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::{TcpListener, TcpStream},
+        rc::Rc,
+        time::Duration,
+    };
+
+    use async_std::task::{block_on, yield_now};
+
+    use crate::{
+        platform::{native::TcpSocketConnector, set_platform},
+        player::testing::with_test_player,
+    };
+
+    use super::*;
+
+    fn call(instance_id: u32, handler_name: &str, args: Vec<Datum>) -> DatumRef {
+        let args = reserve_player_mut(|player| args.into_iter().map(|x| player.alloc_datum(x)).collect());
+        MultiuserXtraManager::call_instance_handler(&handler_name.to_string(), instance_id, &args).unwrap()
+    }
+
+    fn call_int(instance_id: u32, handler_name: &str, args: Vec<Datum>) -> i32 {
+        let result = call(instance_id, handler_name, args);
+        reserve_player_ref(|player| player.get_datum(&result).int_value().unwrap())
+    }
+
+    fn call_string(instance_id: u32, handler_name: &str, args: Vec<Datum>) -> String {
+        let result = call(instance_id, handler_name, args);
+        reserve_player_ref(|player| player.get_datum(&result).string_value().unwrap())
+    }
+
+    fn string(value: &str) -> Datum {
+        Datum::String(value.to_string())
+    }
+
+    /// Lets the socket tasks run.
+    fn run_socket_tasks() {
+        block_on(async {
+            for _ in 0..10 {
+                yield_now().await;
+            }
+        });
+    }
+
+    /// Lets the socket tasks run until `done` holds, as real sockets deliver their data on other threads.
+    fn run_socket_tasks_until(mut done: impl FnMut() -> bool) {
+        for _ in 0..500 {
+            run_socket_tasks();
+            if done() {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        panic!("Timed out waiting for the sockets");
+    }
+
+    fn wait_for_net_messages(instance_id: u32, count: i32) {
+        run_socket_tasks_until(|| call_int(instance_id, "getNumberWaitingNetMessages", vec![]) >= count);
+    }
+
+    fn use_tcp_sockets() {
+        let mut platform = (*platform()).clone();
+        platform.sockets = Rc::new(TcpSocketConnector::default());
+        set_platform(platform);
+    }
+
+    /// Accepts a single connection on a free local port and sends back whatever it receives.
+    fn start_tcp_echo_server() -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buffer = [0u8; 4096];
+            while let Ok(length) = stream.read(&mut buffer) {
+                if length == 0 || stream.write_all(&buffer[..length]).is_err() {
+                    break;
+                }
+            }
+        });
+        port
+    }
+
+    /// Connects a raw peer, with reads that time out so the socket tasks can run in between.
+    fn connect_peer() -> TcpStream {
+        let stream = TcpStream::connect(("127.0.0.1", DEFAULT_PORT)).unwrap();
+        stream.set_read_timeout(Some(Duration::from_millis(10))).unwrap();
+        stream
+    }
+
+    /// Reads from a raw peer connection until a whole message has arrived.
+    fn read_message(stream: &mut TcpStream, decoder: &mut MultiuserMessageDecoder) -> MultiuserMessage {
+        let mut buffer = [0u8; 4096];
+        let mut received = vec![];
+        run_socket_tasks_until(|| {
+            match stream.read(&mut buffer) {
+                Ok(0) => panic!("The connection was closed"),
+                Ok(length) => received.extend(decoder.push(&buffer[..length]).unwrap()),
+                Err(_) => {}
+            }
+            !received.is_empty()
+        });
+        received.remove(0)
+    }
+
+    fn logon_content(movie_id: &str, user_id: &str, password: &str) -> MultiuserValue {
+        MultiuserValue::List(vec![
+            MultiuserValue::String(movie_id.to_string()),
+            MultiuserValue::String(user_id.to_string()),
+            MultiuserValue::String(password.to_string()),
+        ])
+    }
+
+    /// Takes the next message with `getNetMessage`, as its prop list converted back to a message.
+    fn get_net_message(instance_id: u32) -> MultiuserMessage {
+        let message = call(instance_id, "getNetMessage", vec![]);
+        reserve_player_ref(|player| {
+            let Datum::PropList(pairs, ..) = player.get_datum(&message) else {
+                panic!("getNetMessage returned no list")
+            };
+            let get = |key: &str| {
+                let (_, value) =
+                    pairs.iter().find(|(x, _)| player.get_datum(x).string_value().unwrap() == key).unwrap();
+                player.get_datum(value)
+            };
+            let Datum::List(_, recipients, _) = get("recipients") else { panic!("recipients is not a list") };
+            MultiuserMessage {
+                error_code: get("errorCode").int_value().unwrap(),
+                recipients: recipients.iter().map(|x| player.get_datum(x).string_value().unwrap()).collect(),
+                sender_id: get("senderID").string_value().unwrap(),
+                subject: get("subject").string_value().unwrap(),
+                content: MultiuserValue::from_datum(player, get("content")).unwrap(),
+                time_stamp: get("timeStamp").int_value().unwrap(),
+            }
+        })
+    }
+
+    #[test]
+    fn exchanges_messages_with_an_echo_server() {
+        with_test_player(|_| {
+            let instance_id = borrow_multiuser_manager_mut(|manager| manager.create_instance(&vec![]));
+            let connect_args =
+                vec![string("alice"), string("secret"), string("localhost"), Datum::Int(1626), string("movie")];
+            let error_code = call(instance_id, "connectToNetServer", connect_args);
+            assert!(matches!(reserve_player_ref(|player| player.get_datum(&error_code).clone()), Datum::Int(0)));
+
+            // The echo server sends the encrypted logon back, which is taken as the server accepting it
+            run_socket_tasks();
+            let logon = get_net_message(instance_id);
+            assert_eq!(logon.subject, "ConnectToNetServer");
+            assert_eq!(logon.error_code, 0);
+            assert_eq!(logon.sender_id, "alice");
+            assert!(matches!(logon.content, MultiuserValue::Media(_)));
+            assert_eq!(
+                LogonCipher::default().decrypt(&logon.content).unwrap(),
+                logon_content("movie", "alice", "secret")
+            );
+
+            let content = reserve_player_mut(|player| {
+                let items = vec![player.alloc_datum(Datum::Int(1)), player.alloc_datum(string("two"))];
+                Datum::List(DatumType::List, items, false)
+            });
+            let error_code = call(instance_id, "sendNetMessage", vec![string("bob"), string("Chat"), content]);
+            assert!(matches!(reserve_player_ref(|player| player.get_datum(&error_code).clone()), Datum::Int(0)));
+            run_socket_tasks();
+            assert_eq!(
+                get_net_message(instance_id),
+                MultiuserMessage::new(
+                    "alice",
+                    vec!["bob".to_string()],
+                    "Chat",
+                    MultiuserValue::List(vec![MultiuserValue::Integer(1), MultiuserValue::String("two".to_string())]),
+                )
+            );
+
+            assert_eq!(get_net_message(instance_id).error_code, ERROR_NO_WAITING_MESSAGE);
+        });
+    }
+
+    #[test]
+    fn encrypts_the_logon_with_the_given_key() {
+        with_test_player(|_| {
+            let instance_id = borrow_multiuser_manager_mut(|manager| manager.create_instance(&vec![]));
+            let connect_args = |key: &str| {
+                vec![
+                    string("alice"),
+                    string("secret"),
+                    string("localhost"),
+                    Datum::Int(1626),
+                    string("movie"),
+                    Datum::Symbol("smus".to_string()),
+                    string(key),
+                ]
+            };
+            assert_eq!(call_int(instance_id, "connectToNetServer", connect_args("abc")), ERROR_BAD_PARAMETER);
+
+            assert_eq!(call_int(instance_id, "connectToNetServer", connect_args("our own key")), 0);
+            run_socket_tasks();
+            let logon = get_net_message(instance_id);
+            assert_eq!(
+                LogonCipher::new("our own key").unwrap().decrypt(&logon.content).unwrap(),
+                logon_content("movie", "alice", "secret")
+            );
+        });
+    }
+
+    #[test]
+    fn exchanges_messages_with_a_tcp_server() {
+        with_test_player(|_| {
+            use_tcp_sockets();
+            let port = start_tcp_echo_server();
+            let instance_id = borrow_multiuser_manager_mut(|manager| manager.create_instance(&vec![]));
+            let connect_args =
+                vec![string("alice"), string("secret"), string("127.0.0.1"), Datum::Int(port as i32), string("movie")];
+            assert_eq!(call_int(instance_id, "connectToNetServer", connect_args), 0);
+
+            wait_for_net_messages(instance_id, 1);
+            let logon = get_net_message(instance_id);
+            assert_eq!(logon.subject, "ConnectToNetServer");
+            assert_eq!(logon.error_code, 0);
+            assert_eq!(
+                LogonCipher::default().decrypt(&logon.content).unwrap(),
+                logon_content("movie", "alice", "secret")
+            );
+
+            // Larger than a single read, so the message arrives in pieces
+            let text = "0123456789".repeat(1000);
+            assert_eq!(call_int(instance_id, "sendNetMessage", vec![string("bob"), string("Chat"), string(&text)]), 0);
+            assert_eq!(call_int(instance_id, "sendNetMessage", vec![string("bob"), string("Ping"), Datum::Int(7)]), 0);
+            wait_for_net_messages(instance_id, 2);
+            let message = get_net_message(instance_id);
+            assert_eq!(message.subject, "Chat");
+            assert_eq!(message.content, MultiuserValue::String(text));
+            let message = get_net_message(instance_id);
+            assert_eq!(message.subject, "Ping");
+            assert_eq!(message.content, MultiuserValue::Integer(7));
+
+            assert_eq!(call_int(instance_id, "breakConnection", vec![]), 0);
+            assert_eq!(call_int(instance_id, "breakConnection", vec![]), ERROR_NO_CURRENT_CONNECTION);
+            assert_eq!(
+                call_int(instance_id, "sendNetMessage", vec![string("bob"), string("Chat"), Datum::Void]),
+                ERROR_NO_CURRENT_CONNECTION
+            );
+        });
+    }
+
+    #[test]
+    fn hosts_peers_after_wait_for_net_connection() {
+        with_test_player(|_| {
+            use_tcp_sockets();
+            let instance_id = borrow_multiuser_manager_mut(|manager| manager.create_instance(&vec![]));
+            let wait_args = |user_id: &str| vec![string(user_id), Datum::Int(2)];
+            assert_eq!(call_int(instance_id, "waitForNetConnection", wait_args("")), ERROR_INVALID_USER_ID);
+            assert_eq!(call_int(instance_id, "waitForNetConnection", wait_args("host")), 0);
+            let peer_list = || {
+                let list = call(instance_id, "getPeerConnectionList", vec![]);
+                reserve_player_ref(|player| {
+                    let Datum::List(_, items, _) = player.get_datum(&list) else { panic!("not a list") };
+                    items.iter().map(|x| player.get_datum(x).string_value().unwrap()).collect::<Vec<_>>()
+                })
+            };
+            let log_on = |user_id: &str| {
+                let mut stream = connect_peer();
+                let content = LogonCipher::default().encrypt(&logon_content("movie", user_id, "pw"));
+                let logon = MultiuserMessage::new(user_id, vec!["System".to_owned()], "Logon", content);
+                stream.write_all(&logon.encode()).unwrap();
+                stream
+            };
+
+            let mut bob = log_on("bob");
+            let mut bob_decoder = MultiuserMessageDecoder::default();
+            run_socket_tasks_until(|| peer_list() == ["bob"]);
+            let reply = read_message(&mut bob, &mut bob_decoder);
+            assert_eq!((reply.subject.as_str(), reply.error_code), ("Logon", 0));
+            assert_eq!(reply.recipients, ["bob"]);
+            assert_eq!(reply.content, MultiuserValue::String("movie".to_string()));
+
+            // User IDs are unique within the session
+            let mut other_bob = log_on("BOB");
+            let reply = read_message(&mut other_bob, &mut MultiuserMessageDecoder::default());
+            assert_eq!(reply.error_code, ERROR_CONNECTION_DUPLICATE);
+            assert_eq!(peer_list(), ["bob"]);
+
+            // The host hears from its peers as if it were the server
+            let chat = MultiuserMessage::new("bob", vec!["host".to_owned()], "Chat", MultiuserValue::Integer(5));
+            bob.write_all(&chat.encode()).unwrap();
+            wait_for_net_messages(instance_id, 1);
+            let message = get_net_message(instance_id);
+            assert_eq!((message.sender_id.as_str(), message.subject.as_str()), ("bob", "Chat"));
+            assert_eq!(message.content, MultiuserValue::Integer(5));
+
+            assert_eq!(call_int(instance_id, "sendNetMessage", vec![string("bob"), string("Reply"), string("hi")]), 0);
+            let message = read_message(&mut bob, &mut bob_decoder);
+            assert_eq!((message.sender_id.as_str(), message.subject.as_str()), ("host", "Reply"));
+            assert_eq!(message.content, MultiuserValue::String("hi".to_string()));
+            assert_eq!(
+                call_int(instance_id, "sendNetMessage", vec![string("carol"), string("Reply"), string("hi")]),
+                ERROR_INVALID_MESSAGE_RECIPIENT
+            );
+
+            assert_eq!(call_int(instance_id, "breakConnection", vec![string("carol")]), ERROR_BAD_CONNECTION_ID);
+            assert_eq!(call_int(instance_id, "breakConnection", vec![string("Bob")]), 0);
+            assert!(peer_list().is_empty());
+            let mut buffer = [0u8; 16];
+            run_socket_tasks_until(|| matches!(bob.read(&mut buffer), Ok(0)));
+
+            assert_eq!(call_int(instance_id, "breakConnection", vec![]), 0);
+            assert_eq!(call_int(instance_id, "breakConnection", vec![]), ERROR_NO_CURRENT_CONNECTION);
+        });
+    }
+
+    #[test]
+    fn calls_the_most_specific_message_handler() {
+        with_test_player(|_| {
+            let instance_id = borrow_multiuser_manager_mut(|manager| manager.create_instance(&vec![]));
+            let symbol = |name: &str| Datum::Symbol(name.to_string());
+            let set_handler = |handler: Datum, subject: Datum, sender: Datum, pass_data: i32| {
+                let args = vec![handler, Datum::Void, subject, sender, Datum::Int(pass_data)];
+                assert_eq!(call_int(instance_id, "setNetMessageHandler", args), 0);
+            };
+            set_handler(symbol("anyMessage"), Datum::Void, Datum::Void, 0);
+            set_handler(symbol("chat"), string("Chat"), Datum::Void, 0);
+            set_handler(symbol("fromBob"), Datum::Void, string("bob"), 0);
+            set_handler(symbol("chatFromBob"), string("Chat"), string("bob"), 1);
+            let handler_for = |subject: &str, sender: &str| {
+                let message = MultiuserMessage::new(sender, vec![], subject, MultiuserValue::Void);
+                MultiuserXtraManager::with_instance(instance_id, |instance| {
+                    instance.find_handler(&message).map(|handler| handler.handler_symbol.clone())
+                })
+                .unwrap()
+            };
+            assert_eq!(handler_for("Chat", "BOB").as_deref(), Some("chatFromBob"));
+            assert_eq!(handler_for("chat", "carol").as_deref(), Some("chat"));
+            assert_eq!(handler_for("Move", "bob").as_deref(), Some("fromBob"));
+            assert_eq!(handler_for("Move", "carol").as_deref(), Some("anyMessage"));
+
+            // Handlers passed the data take their messages out of the queue
+            MultiuserXtraManager::with_instance(instance_id, |instance| {
+                instance.dispatch_message(MultiuserMessage::new("bob", vec![], "Chat", MultiuserValue::Void));
+                instance.dispatch_message(MultiuserMessage::new("carol", vec![], "Chat", MultiuserValue::Void));
+            })
+            .unwrap();
+            let message = get_net_message(instance_id);
+            assert_eq!(message.sender_id, "carol");
+            assert_eq!(get_net_message(instance_id).error_code, ERROR_NO_WAITING_MESSAGE);
+
+            // Setting no handler for the same filters removes the handler
+            set_handler(Datum::Void, string("Chat"), string("bob"), 0);
+            set_handler(Datum::Void, Datum::Void, Datum::Void, 0);
+            assert_eq!(handler_for("Chat", "carol").as_deref(), Some("chat"));
+            assert_eq!(handler_for("Move", "carol"), None);
+            assert_eq!(handler_for("Move", "bob").as_deref(), Some("fromBob"));
+        });
+    }
+
+    #[test]
+    fn describes_error_codes() {
+        with_test_player(|_| {
+            let instance_id = borrow_multiuser_manager_mut(|manager| manager.create_instance(&vec![]));
+            let error_string = |args: Vec<Datum>| call_string(instance_id, "getNetErrorString", args);
+            assert_eq!(error_string(vec![]), "No error");
+            assert_eq!(error_string(vec![Datum::Int(ERROR_INVALID_PASSWORD)]), "Invalid password");
+            assert_eq!(
+                error_string(vec![Datum::Int(ERROR_CONNECTION_DUPLICATE)]),
+                "A connection with this user ID already exists"
+            );
+            assert_eq!(error_string(vec![Datum::Int(12345)]), "Unknown error");
+        });
+    }
+}
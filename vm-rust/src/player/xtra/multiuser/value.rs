@@ -0,0 +1,386 @@
+use std::convert::TryInto;
+
+use crate::{
+    director::lingo::datum::{Datum, DatumType},
+    player::{sprite::ColorRef, DirPlayer, ScriptError},
+};
+
+// Type tags of the values in a Multiuser message.
+const VALUE_TYPE_VOID: u16 = 0;
+const VALUE_TYPE_INTEGER: u16 = 1;
+const VALUE_TYPE_SYMBOL: u16 = 2;
+const VALUE_TYPE_STRING: u16 = 3;
+const VALUE_TYPE_PICTURE: u16 = 5;
+const VALUE_TYPE_FLOAT: u16 = 6;
+const VALUE_TYPE_LIST: u16 = 7;
+const VALUE_TYPE_POINT: u16 = 8;
+const VALUE_TYPE_RECT: u16 = 9;
+const VALUE_TYPE_PROP_LIST: u16 = 10;
+const VALUE_TYPE_COLOR: u16 = 18;
+const VALUE_TYPE_MEDIA: u16 = 20;
+
+/// How deeply lists can be nested in a received value, so a hostile message can't overflow the stack.
+const MAX_VALUE_DEPTH: usize = 64;
+
+/// A Lingo value as it is sent in the content of a Multiuser message.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MultiuserValue {
+    Void,
+    Integer(i32),
+    Symbol(String),
+    String(String),
+    Float(f64),
+    List(Vec<MultiuserValue>),
+    PropList(Vec<(MultiuserValue, MultiuserValue)>),
+    Point(i32, i32),
+    Rect(i32, i32, i32, i32),
+    Color(u8, u8, u8),
+    Picture(Vec<u8>),
+    Media(Vec<u8>),
+}
+
+/// Writes a length prefixed string, padded to an even number of bytes.
+pub fn write_string(out: &mut Vec<u8>, value: &[u8]) {
+    out.extend_from_slice(&(value.len() as i32).to_be_bytes());
+    out.extend_from_slice(value);
+    if !value.len().is_multiple_of(2) {
+        out.push(0);
+    }
+}
+
+/// Reads the big-endian fields of a message. Running out of data is an error, as messages
+/// are only decoded once all of their bytes have arrived.
+pub struct ValueReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    depth: usize,
+}
+
+impl<'a> ValueReader<'a> {
+    pub fn new(data: &'a [u8]) -> ValueReader<'a> {
+        ValueReader { data, pos: 0, depth: 0 }
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.pos + len > self.data.len() {
+            return Err(format!("Unexpected end of message at {}", self.pos));
+        }
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, String> {
+        let bytes = self.read_bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn read_i32(&mut self) -> Result<i32, String> {
+        let bytes = self.read_bytes(4)?;
+        Ok(i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn read_len(&mut self) -> Result<usize, String> {
+        let len = self.read_i32()?;
+        if len < 0 {
+            return Err(format!("Invalid length {}", len));
+        }
+        Ok(len as usize)
+    }
+
+    pub fn read_raw_string(&mut self) -> Result<Vec<u8>, String> {
+        let len = self.read_len()?;
+        let bytes = self.read_bytes(len)?.to_vec();
+        if !len.is_multiple_of(2) {
+            self.read_bytes(1)?;
+        }
+        Ok(bytes)
+    }
+
+    pub fn read_string(&mut self) -> Result<String, String> {
+        Ok(String::from_utf8_lossy(&self.read_raw_string()?).to_string())
+    }
+
+    fn read_coordinate(&mut self) -> Result<i32, String> {
+        match self.read_value()? {
+            MultiuserValue::Integer(value) => Ok(value),
+            MultiuserValue::Float(value) => Ok(value.round() as i32),
+            other => Err(format!("Invalid coordinate {:?}", other)),
+        }
+    }
+
+    pub fn read_value(&mut self) -> Result<MultiuserValue, String> {
+        if self.depth >= MAX_VALUE_DEPTH {
+            return Err(format!("Value nested deeper than {} levels at {}", MAX_VALUE_DEPTH, self.pos));
+        }
+        self.depth += 1;
+        let value = self.read_value_contents();
+        self.depth -= 1;
+        value
+    }
+
+    fn read_value_contents(&mut self) -> Result<MultiuserValue, String> {
+        let value_type = self.read_u16()?;
+        let value = match value_type {
+            VALUE_TYPE_VOID => MultiuserValue::Void,
+            VALUE_TYPE_INTEGER => MultiuserValue::Integer(self.read_i32()?),
+            VALUE_TYPE_SYMBOL => MultiuserValue::Symbol(self.read_string()?),
+            VALUE_TYPE_STRING => MultiuserValue::String(self.read_string()?),
+            VALUE_TYPE_PICTURE => MultiuserValue::Picture(self.read_raw_string()?),
+            VALUE_TYPE_FLOAT => {
+                let bytes = self.read_bytes(8)?;
+                MultiuserValue::Float(f64::from_be_bytes(bytes.try_into().unwrap()))
+            }
+            VALUE_TYPE_LIST => {
+                let count = self.read_len()?;
+                let mut items = Vec::with_capacity(count.min(1024));
+                for _ in 0..count {
+                    items.push(self.read_value()?);
+                }
+                MultiuserValue::List(items)
+            }
+            VALUE_TYPE_POINT => MultiuserValue::Point(self.read_coordinate()?, self.read_coordinate()?),
+            VALUE_TYPE_RECT => MultiuserValue::Rect(
+                self.read_coordinate()?,
+                self.read_coordinate()?,
+                self.read_coordinate()?,
+                self.read_coordinate()?,
+            ),
+            VALUE_TYPE_PROP_LIST => {
+                let count = self.read_len()?;
+                let mut pairs = Vec::with_capacity(count.min(1024));
+                for _ in 0..count {
+                    pairs.push((self.read_value()?, self.read_value()?));
+                }
+                MultiuserValue::PropList(pairs)
+            }
+            VALUE_TYPE_COLOR => {
+                let bytes = self.read_bytes(4)?;
+                MultiuserValue::Color(bytes[0], bytes[1], bytes[2])
+            }
+            VALUE_TYPE_MEDIA => MultiuserValue::Media(self.read_raw_string()?),
+            _ => return Err(format!("Unsupported value type {}", value_type)),
+        };
+        Ok(value)
+    }
+
+    pub fn is_at_end(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+}
+
+impl MultiuserValue {
+    pub fn write(&self, out: &mut Vec<u8>) {
+        match self {
+            MultiuserValue::Void => out.extend_from_slice(&VALUE_TYPE_VOID.to_be_bytes()),
+            MultiuserValue::Integer(value) => {
+                out.extend_from_slice(&VALUE_TYPE_INTEGER.to_be_bytes());
+                out.extend_from_slice(&value.to_be_bytes());
+            }
+            MultiuserValue::Symbol(value) => {
+                out.extend_from_slice(&VALUE_TYPE_SYMBOL.to_be_bytes());
+                write_string(out, value.as_bytes());
+            }
+            MultiuserValue::String(value) => {
+                out.extend_from_slice(&VALUE_TYPE_STRING.to_be_bytes());
+                write_string(out, value.as_bytes());
+            }
+            MultiuserValue::Float(value) => {
+                out.extend_from_slice(&VALUE_TYPE_FLOAT.to_be_bytes());
+                out.extend_from_slice(&value.to_be_bytes());
+            }
+            MultiuserValue::List(items) => {
+                out.extend_from_slice(&VALUE_TYPE_LIST.to_be_bytes());
+                out.extend_from_slice(&(items.len() as i32).to_be_bytes());
+                for item in items {
+                    item.write(out);
+                }
+            }
+            MultiuserValue::PropList(pairs) => {
+                out.extend_from_slice(&VALUE_TYPE_PROP_LIST.to_be_bytes());
+                out.extend_from_slice(&(pairs.len() as i32).to_be_bytes());
+                for (key, value) in pairs {
+                    key.write(out);
+                    value.write(out);
+                }
+            }
+            MultiuserValue::Point(x, y) => {
+                out.extend_from_slice(&VALUE_TYPE_POINT.to_be_bytes());
+                MultiuserValue::Integer(*x).write(out);
+                MultiuserValue::Integer(*y).write(out);
+            }
+            MultiuserValue::Rect(left, top, right, bottom) => {
+                out.extend_from_slice(&VALUE_TYPE_RECT.to_be_bytes());
+                for value in [left, top, right, bottom] {
+                    MultiuserValue::Integer(*value).write(out);
+                }
+            }
+            MultiuserValue::Color(r, g, b) => {
+                out.extend_from_slice(&VALUE_TYPE_COLOR.to_be_bytes());
+                out.extend_from_slice(&[*r, *g, *b, 0]);
+            }
+            MultiuserValue::Picture(data) => {
+                out.extend_from_slice(&VALUE_TYPE_PICTURE.to_be_bytes());
+                write_string(out, data);
+            }
+            MultiuserValue::Media(data) => {
+                out.extend_from_slice(&VALUE_TYPE_MEDIA.to_be_bytes());
+                write_string(out, data);
+            }
+        }
+    }
+
+    /// Converts a Lingo value for sending. Values that can't be sent, such as script instances, are an error.
+    pub fn from_datum(player: &DirPlayer, datum: &Datum) -> Result<MultiuserValue, ScriptError> {
+        let value = match datum {
+            Datum::Void | Datum::Null => MultiuserValue::Void,
+            Datum::Int(value) => MultiuserValue::Integer(*value),
+            Datum::Float(value) => MultiuserValue::Float(*value as f64),
+            Datum::String(value) => MultiuserValue::String(value.clone()),
+            Datum::StringChunk(.., value) => MultiuserValue::String(value.clone()),
+            Datum::Symbol(value) => MultiuserValue::Symbol(value.clone()),
+            Datum::List(_, items, _) => MultiuserValue::List(
+                items
+                    .iter()
+                    .map(|item| MultiuserValue::from_datum(player, player.get_datum(item)))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Datum::PropList(pairs, _) => MultiuserValue::PropList(
+                pairs
+                    .iter()
+                    .map(|(key, value)| {
+                        Ok((
+                            MultiuserValue::from_datum(player, player.get_datum(key))?,
+                            MultiuserValue::from_datum(player, player.get_datum(value))?,
+                        ))
+                    })
+                    .collect::<Result<_, ScriptError>>()?,
+            ),
+            Datum::IntPoint((x, y)) => MultiuserValue::Point(*x, *y),
+            Datum::IntRect((left, top, right, bottom)) => MultiuserValue::Rect(*left, *top, *right, *bottom),
+            Datum::ColorRef(ColorRef::Rgb(r, g, b)) => MultiuserValue::Color(*r, *g, *b),
+            Datum::Media(data) => MultiuserValue::Media(data.clone()),
+            _ => {
+                return Err(ScriptError::new(format!(
+                    "Cannot send a {} in a Multiuser message",
+                    datum.type_str()
+                )))
+            }
+        };
+        Ok(value)
+    }
+
+    pub fn into_datum(self, player: &mut DirPlayer) -> Datum {
+        match self {
+            MultiuserValue::Void => Datum::Void,
+            MultiuserValue::Integer(value) => Datum::Int(value),
+            MultiuserValue::Symbol(value) => Datum::Symbol(value),
+            MultiuserValue::String(value) => Datum::String(value),
+            MultiuserValue::Float(value) => Datum::Float(value as f32),
+            MultiuserValue::List(items) => {
+                let items = items
+                    .into_iter()
+                    .map(|item| {
+                        let item = item.into_datum(player);
+                        player.alloc_datum(item)
+                    })
+                    .collect();
+                Datum::List(DatumType::List, items, false)
+            }
+            MultiuserValue::PropList(pairs) => {
+                let pairs = pairs
+                    .into_iter()
+                    .map(|(key, value)| {
+                        let key = key.into_datum(player);
+                        let value = value.into_datum(player);
+                        (player.alloc_datum(key), player.alloc_datum(value))
+                    })
+                    .collect();
+                Datum::PropList(pairs, false)
+            }
+            MultiuserValue::Point(x, y) => Datum::IntPoint((x, y)),
+            MultiuserValue::Rect(left, top, right, bottom) => Datum::IntRect((left, top, right, bottom)),
+            MultiuserValue::Color(r, g, b) => Datum::ColorRef(ColorRef::Rgb(r, g, b)),
+            MultiuserValue::Picture(data) | MultiuserValue::Media(data) => Datum::Media(data),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(value: &MultiuserValue) -> MultiuserValue {
+        let mut out = vec![];
+        value.write(&mut out);
+        let mut reader = ValueReader::new(&out);
+        let result = reader.read_value().unwrap();
+        assert!(reader.is_at_end());
+        result
+    }
+
+    #[test]
+    fn round_trips_every_value_type() {
+        let values = [
+            MultiuserValue::Void,
+            MultiuserValue::Integer(-123456),
+            MultiuserValue::Symbol("odd".to_string()),
+            MultiuserValue::String("even".to_string()),
+            MultiuserValue::String("".to_string()),
+            MultiuserValue::Float(-2.5),
+            MultiuserValue::List(vec![MultiuserValue::Integer(1), MultiuserValue::String("two".to_string())]),
+            MultiuserValue::PropList(vec![(
+                MultiuserValue::Symbol("a".to_string()),
+                MultiuserValue::List(vec![MultiuserValue::Void]),
+            )]),
+            MultiuserValue::Point(-10, 20),
+            MultiuserValue::Rect(1, 2, 3, 4),
+            MultiuserValue::Color(255, 128, 0),
+            MultiuserValue::Picture(vec![1, 2, 3]),
+            MultiuserValue::Media(vec![4, 5, 6, 7]),
+        ];
+        for value in values {
+            assert_eq!(round_trip(&value), value);
+        }
+    }
+
+    #[test]
+    fn pads_strings_to_an_even_length() {
+        let mut out = vec![];
+        MultiuserValue::String("abc".to_string()).write(&mut out);
+        assert_eq!(out, [0, 3, 0, 0, 0, 3, b'a', b'b', b'c', 0]);
+    }
+
+    #[test]
+    fn reads_float_coordinates() {
+        let mut out = VALUE_TYPE_POINT.to_be_bytes().to_vec();
+        MultiuserValue::Float(1.6).write(&mut out);
+        MultiuserValue::Integer(2).write(&mut out);
+        assert_eq!(ValueReader::new(&out).read_value().unwrap(), MultiuserValue::Point(2, 2));
+    }
+
+    fn nested_lists(depth: usize) -> Vec<u8> {
+        let mut out = vec![];
+        for _ in 0..depth - 1 {
+            out.extend_from_slice(&VALUE_TYPE_LIST.to_be_bytes());
+            out.extend_from_slice(&1i32.to_be_bytes());
+        }
+        MultiuserValue::Void.write(&mut out);
+        out
+    }
+
+    #[test]
+    fn limits_nesting_depth() {
+        assert!(ValueReader::new(&nested_lists(MAX_VALUE_DEPTH)).read_value().is_ok());
+        assert!(ValueReader::new(&nested_lists(MAX_VALUE_DEPTH + 1)).read_value().is_err());
+        assert!(ValueReader::new(&nested_lists(100_000)).read_value().is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_and_unknown_values() {
+        let mut out = vec![];
+        MultiuserValue::String("text".to_string()).write(&mut out);
+        assert!(ValueReader::new(&out[..out.len() - 1]).read_value().is_err());
+        assert!(ValueReader::new(&[0, 99]).read_value().is_err());
+    }
+}
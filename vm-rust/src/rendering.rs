@@ -1,11 +1,11 @@
-use std::{borrow::{Borrow, BorrowMut}, cell::RefCell, collections::HashMap, rc::Rc};
+use std::{borrow::{Borrow, BorrowMut}, cell::RefCell, rc::Rc};
 
 use async_std::task::spawn_local;
 use chrono::Local;
 use wasm_bindgen::{prelude::*, Clamped};
 
-use crate::{js_api::JsApi, player::{
-    bitmap::{bitmap::{get_system_default_palette, resolve_color_ref, Bitmap, PaletteRef}, drawing::{should_matte_sprite, CopyPixelsParams}, mask::BitmapMask, palette_map::PaletteMap}, cast_lib::CastMemberRef, cast_member::CastMemberType, geometry::IntRect, score::{get_concrete_sprite_rect, get_sprite_at}, sprite::CursorRef, DirPlayer, PLAYER_OPT
+use crate::{js_api::JsApi, platform::FramePresenter, player::{
+    bitmap::{bitmap::{get_system_default_palette, resolve_color_ref, Bitmap, PaletteRef}, drawing::{should_mask_sprite, should_matte_sprite, CopyPixelsParams}, manager::BitmapRef, mask::BitmapMask, palette_map::PaletteMap}, font::{layout::draw_styled_text, player_ensure_member_fonts}, cast_lib::CastMemberRef, cast_member::CastMemberType, geometry::IntRect, mouse_events::get_active_cursor, score::{get_concrete_sprite_rect, get_cropped_film_loop_child_sprites, get_film_loop_child_sprites, get_sprite_at, get_sprite_transform, get_untransformed_sprite_rect}, sprite::{ColorRef, CursorRef, Sprite}, DirPlayer, PLAYER_OPT
 }};
 
 pub struct PlayerCanvasRenderer {
@@ -37,95 +37,12 @@ pub fn render_stage_to_bitmap(player: &mut DirPlayer, bitmap: &mut Bitmap, debug
         &palettes,
     );
 
-    let sorted_sprites = player
-        .movie
-        .score
-        .get_sorted_channels();
+    prepare_sprite_mattes(player, &palettes);
+    prepare_sprite_fonts(player);
 
-    for channel in sorted_sprites {
-        let sprite = &channel.sprite;
-        let sprite_rect = get_concrete_sprite_rect(player, sprite);
-        let member_ref = sprite.member.as_ref().unwrap();
-        let member = player
-            .movie
-            .cast_manager
-            .find_member_by_ref(member_ref);
-        if member.is_none() {
-            continue;
-        }
-        let member = member.unwrap();
-        match &member.member_type {
-            CastMemberType::Bitmap(bitmap_member) => {
-                let sprite_bitmap = player.bitmap_manager.get_bitmap_mut(bitmap_member.image_ref);
-                if sprite_bitmap.is_none() {
-                    continue;
-                }
-                let src_bitmap = sprite_bitmap.unwrap();
-                let mask = if should_matte_sprite(sprite.ink as u32) {
-                    if src_bitmap.matte.is_none() {
-                        src_bitmap.create_matte(&palettes);
-                    }
-                    Some(src_bitmap.matte.as_ref().unwrap())
-                } else {
-                    None
-                };
-                let src_rect = IntRect::from(0, 0, sprite.width as i32, sprite.height as i32);
-                let dst_rect = sprite_rect;
-                let dst_rect = IntRect::from(
-                    if sprite.flip_h { dst_rect.right } else { dst_rect.left },
-                    if sprite.flip_v { dst_rect.bottom } else { dst_rect.top },
-                    if sprite.flip_h { dst_rect.left } else { dst_rect.right },
-                    if sprite.flip_v { dst_rect.top } else { dst_rect.bottom },
-                );
-
-                let mut params = CopyPixelsParams {
-                    blend: sprite.blend as i32,
-                    ink: sprite.ink as u32,
-                    color: sprite.color.clone(),
-                    bg_color: sprite.bg_color.clone(),
-                    mask_image: None,
-                };
-                if let Some(mask) = mask {
-                    let mask_bitmap: &BitmapMask = mask.borrow();
-                    params.mask_image = Some(mask_bitmap);
-                }
-                bitmap.copy_pixels_with_params(
-                    &palettes, 
-                    &src_bitmap, 
-                    dst_rect, 
-                    src_rect,
-                    &params,
-                );
-            }
-            CastMemberType::Shape(_) => {
-                let dst_rect = sprite_rect;
-                bitmap.fill_rect(
-                    dst_rect.left, 
-                    dst_rect.top, 
-                    dst_rect.right, 
-                    dst_rect.bottom, 
-                    resolve_color_ref(&palettes, &sprite.color, &PaletteRef::BuiltIn(get_system_default_palette())), 
-                    &palettes, 
-                    sprite.blend as f32 / 100.0,
-                );
-            }
-            CastMemberType::Field(field_member) => {
-                let font = player.font_manager.get_system_font().unwrap(); // TODO
-                let font_bitmap = player.bitmap_manager.get_bitmap(font.bitmap_ref).unwrap();
-
-                bitmap.draw_text(&field_member.text, font, font_bitmap, sprite.loc_h, sprite.loc_v, sprite.ink as u32, sprite.bg_color.clone(), &palettes, field_member.fixed_line_space, field_member.top_spacing);
-
-                if player.keyboard_focus_sprite == sprite.number as i16 {
-                    let cursor_x = sprite.loc_h + (sprite.width / 2);
-                    let cursor_y = sprite.loc_v;
-                    let cursor_width = 1;
-                    let cursor_height = field_member.font_size as i16;
-                    
-                    bitmap.fill_rect(cursor_x, cursor_y, cursor_x + cursor_width, cursor_y + cursor_height as i32, (0, 0, 0), &palettes, 1.0)
-                }
-            }
-            _ => {}
-        }
+    let player: &DirPlayer = player;
+    for channel in player.movie.score.get_sorted_channels() {
+        draw_sprite(player, bitmap, &channel.sprite, &palettes);
     }
 
     // Draw debug rect
@@ -164,22 +81,237 @@ pub fn render_stage_to_bitmap(player: &mut DirPlayer, bitmap: &mut Bitmap, debug
     draw_cursor(player, bitmap, &palettes);
 }
 
-fn draw_cursor(player: &DirPlayer, bitmap: &mut Bitmap, palettes: &PaletteMap) {
-    let hovered_sprite = get_sprite_at(player, player.mouse_loc.0, player.mouse_loc.1, false);
-    let cursor_ref = if let Some(hovered_sprite) = hovered_sprite {
-        let hovered_sprite = player.movie.score.get_sprite(hovered_sprite as i16).unwrap();
-        hovered_sprite.cursor_ref.as_ref()
-    } else {
-        None
+/// Creates the mattes needed to draw the current sprites, including the sprites inside film loops.
+fn prepare_sprite_mattes(player: &mut DirPlayer, palettes: &PaletteMap) {
+    fn collect_matte_bitmaps(player: &DirPlayer, sprite: &Sprite, result: &mut Vec<BitmapRef>) {
+        let member = sprite.member.as_ref().and_then(|x| player.movie.cast_manager.find_member_by_ref(x));
+        match member.map(|x| &x.member_type) {
+            Some(CastMemberType::Bitmap(bitmap_member)) if should_matte_sprite(sprite.ink as u32) => {
+                result.push(bitmap_member.image_ref);
+            }
+            Some(CastMemberType::FilmLoop(_)) => {
+                for child in get_film_loop_child_sprites(player, sprite) {
+                    collect_matte_bitmaps(player, &child, result);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut bitmap_refs = vec![];
+    for channel in player.movie.score.get_sorted_channels() {
+        collect_matte_bitmaps(player, &channel.sprite, &mut bitmap_refs);
+    }
+    for bitmap_ref in bitmap_refs {
+        if let Some(src_bitmap) = player.bitmap_manager.get_bitmap_mut(bitmap_ref) {
+            if src_bitmap.matte.is_none() {
+                src_bitmap.create_matte(palettes);
+            }
+        }
+    }
+}
+
+/// Loads the fonts needed to draw the current text and field sprites, including the sprites inside film loops.
+fn prepare_sprite_fonts(player: &mut DirPlayer) {
+    fn collect_text_members(player: &DirPlayer, sprite: &Sprite, result: &mut Vec<CastMemberRef>) {
+        let member = sprite.member.as_ref().and_then(|x| player.movie.cast_manager.find_member_by_ref(x));
+        match member.map(|x| &x.member_type) {
+            Some(CastMemberType::Field(_)) | Some(CastMemberType::Text(_)) => {
+                result.push(sprite.member.clone().unwrap());
+            }
+            Some(CastMemberType::FilmLoop(_)) => {
+                for child in get_film_loop_child_sprites(player, sprite) {
+                    collect_text_members(player, &child, result);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut member_refs = vec![];
+    for channel in player.movie.score.get_sorted_channels() {
+        collect_text_members(player, &channel.sprite, &mut member_refs);
+    }
+    for member_ref in member_refs {
+        player_ensure_member_fonts(player, &member_ref);
+    }
+}
+
+fn draw_sprite(player: &DirPlayer, bitmap: &mut Bitmap, sprite: &Sprite, palettes: &PaletteMap) {
+    let member = sprite.member.as_ref().and_then(|x| player.movie.cast_manager.find_member_by_ref(x));
+    let member = match member {
+        Some(member) => member,
+        None => return,
     };
-    let cursor_ref = cursor_ref.or(Some(&player.cursor));
-    let cursor_list = cursor_ref
-        .and_then(|x| {
-            match x {
-                CursorRef::Member(x) => Some(x),
-                _ => None,
+    let sprite_rect = get_untransformed_sprite_rect(player, sprite);
+    match &member.member_type {
+        CastMemberType::Bitmap(bitmap_member) => {
+            let src_bitmap = match player.bitmap_manager.get_bitmap(bitmap_member.image_ref) {
+                Some(src_bitmap) => src_bitmap,
+                None => return,
+            };
+            let mask = if should_matte_sprite(sprite.ink as u32) {
+                src_bitmap.matte.as_ref()
+            } else {
+                None
+            };
+            let src_rect = IntRect::from_size(0, 0, src_bitmap.width as i32, src_bitmap.height as i32);
+            let dst_rect = sprite_rect;
+            let dst_rect = IntRect::from(
+                if sprite.flip_h { dst_rect.right } else { dst_rect.left },
+                if sprite.flip_v { dst_rect.bottom } else { dst_rect.top },
+                if sprite.flip_h { dst_rect.left } else { dst_rect.right },
+                if sprite.flip_v { dst_rect.top } else { dst_rect.bottom },
+            );
+
+            // Mask ink uses the cast member following the sprite's member as its mask.
+            let mask_member_image = if should_mask_sprite(sprite.ink as u32) {
+                sprite.member.as_ref()
+                    .map(|x| CastMemberRef { cast_lib: x.cast_lib, cast_member: x.cast_member + 1 })
+                    .and_then(|x| player.movie.cast_manager.find_member_by_ref(&x))
+                    .and_then(|x| x.member_type.as_bitmap())
+                    .and_then(|x| player.bitmap_manager.get_bitmap(x.image_ref))
+                    .map(|x| x.to_mask())
+            } else {
+                None
+            };
+
+            let mut params = CopyPixelsParams {
+                blend: sprite.blend as i32,
+                ink: sprite.ink as u32,
+                color: sprite.color.clone(),
+                bg_color: sprite.bg_color.clone(),
+                mask_image: mask_member_image.as_ref(),
+                transform: get_sprite_transform(sprite),
+                bilinear: false,
+            };
+            if let Some(mask) = mask {
+                let mask_bitmap: &BitmapMask = mask.borrow();
+                params.mask_image = Some(mask_bitmap);
             }
-        });
+            // Filtering would blend the edges of masked and transparent inks with the pixels they hide.
+            params.bilinear = params.transform.is_some() && params.mask_image.is_none() && (params.ink == 0 || params.ink == 32);
+            bitmap.copy_pixels_with_params(
+                palettes, 
+                src_bitmap, 
+                dst_rect, 
+                src_rect,
+                &params,
+            );
+        }
+        CastMemberType::Shape(shape_member) => {
+            let params = CopyPixelsParams {
+                blend: sprite.blend,
+                ink: sprite.ink as u32,
+                color: sprite.color.clone(),
+                bg_color: sprite.bg_color.clone(),
+                mask_image: None,
+                transform: get_sprite_transform(sprite),
+                bilinear: false,
+            };
+            bitmap.draw_shape(palettes, &shape_member.shape_info, sprite_rect, &params);
+        }
+        CastMemberType::Field(field_member) => {
+            let styled_text = field_member.styled_text();
+            let layout = field_member.layout(&player.font_manager);
+            draw_sprite_text(bitmap, sprite, &sprite_rect, palettes, |bitmap, (left, top)| {
+                draw_styled_text(bitmap, &player.font_manager, &player.bitmap_manager, &styled_text, &layout, (left, top), sprite.ink as u32, sprite.bg_color.clone(), palettes);
+
+                if player.keyboard_focus_sprite == sprite.number as i16 {
+                    let (cursor_x, cursor_y) = layout.char_pos(&player.font_manager, &styled_text, field_member.text.chars().count());
+                    let cursor_x = left + cursor_x;
+                    let cursor_y = top + cursor_y;
+                    let cursor_width = 1;
+                    let cursor_height = layout.lines.last().map_or(field_member.font_size as i32, |line| line.height);
+
+                    bitmap.fill_rect(cursor_x, cursor_y, cursor_x + cursor_width, cursor_y + cursor_height, (0, 0, 0), palettes, 1.0)
+                }
+            });
+        }
+        CastMemberType::Text(text_member) => {
+            let styled_text = text_member.styled_text();
+            let layout = text_member.layout(&player.font_manager);
+            draw_sprite_text(bitmap, sprite, &sprite_rect, palettes, |bitmap, (left, top)| {
+                draw_styled_text(bitmap, &player.font_manager, &player.bitmap_manager, &styled_text, &layout, (left, top), sprite.ink as u32, sprite.bg_color.clone(), palettes);
+            });
+        }
+        CastMemberType::FilmLoop(_) => {
+            for (child, clip_rect) in get_cropped_film_loop_child_sprites(player, sprite) {
+                match clip_rect {
+                    Some(clip_rect) => draw_clipped_sprite(player, bitmap, child, &clip_rect, palettes),
+                    None => draw_sprite(player, bitmap, &child, palettes),
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Draws a sprite, leaving the pixels outside `clip_rect` unchanged. The sprite is drawn over a copy
+/// of the pixels it covers, so that inks blending with what is below work as usual.
+fn draw_clipped_sprite(player: &DirPlayer, bitmap: &mut Bitmap, mut sprite: Sprite, clip_rect: &IntRect, palettes: &PaletteMap) {
+    let clip_rect = clip_rect.intersect(&IntRect::from(0, 0, bitmap.width as i32, bitmap.height as i32));
+    if clip_rect.width() <= 0 || clip_rect.height() <= 0 {
+        return;
+    }
+    let local_rect = IntRect::from(0, 0, clip_rect.width(), clip_rect.height());
+    let params = CopyPixelsParams {
+        blend: 100,
+        ink: 0,
+        color: ColorRef::Rgb(0, 0, 0),
+        bg_color: ColorRef::Rgb(255, 255, 255),
+        mask_image: None,
+        transform: None,
+        bilinear: false,
+    };
+    let mut clip_bitmap = Bitmap::new(clip_rect.width() as u16, clip_rect.height() as u16, 32, bitmap.palette_ref.clone());
+    clip_bitmap.copy_pixels_with_params(palettes, bitmap, local_rect, clip_rect, &params);
+
+    sprite.loc_h -= clip_rect.left;
+    sprite.loc_v -= clip_rect.top;
+    draw_sprite(player, &mut clip_bitmap, &sprite, palettes);
+    bitmap.copy_pixels_with_params(palettes, &clip_bitmap, clip_rect, local_rect, &params);
+}
+
+/// Draws the text of a field or text sprite with `draw`, which is given the bitmap to draw on and
+/// the top left of the text box in it. Rotated or skewed sprites are drawn upright into a bitmap of
+/// their own first, then copied through the sprite's transform.
+fn draw_sprite_text(bitmap: &mut Bitmap, sprite: &Sprite, sprite_rect: &IntRect, palettes: &PaletteMap, draw: impl FnOnce(&mut Bitmap, (i32, i32))) {
+    let transform = match get_sprite_transform(sprite) {
+        Some(transform) => transform,
+        None => return draw(bitmap, (sprite_rect.left, sprite_rect.top)),
+    };
+    let width = sprite_rect.width().clamp(0, u16::MAX as i32);
+    let height = sprite_rect.height().clamp(0, u16::MAX as i32);
+    if width == 0 || height == 0 {
+        return;
+    }
+    // Copy ink draws the background of the text box, the other inks leave it out, so it is kept
+    // white to be dropped by background transparent ink when copied.
+    let ink = sprite.ink as u32;
+    let bg_color = if ink == 0 { resolve_color_ref(palettes, &sprite.bg_color, &bitmap.palette_ref) } else { (255, 255, 255) };
+    let mut text_bitmap = Bitmap::new(width as u16, height as u16, 32, bitmap.palette_ref.clone());
+    text_bitmap.fill_rect(0, 0, width, height, bg_color, palettes, 1.0);
+    draw(&mut text_bitmap, (0, 0));
+
+    let params = CopyPixelsParams {
+        blend: sprite.blend,
+        ink: if ink == 0 { 0 } else { 36 },
+        color: ColorRef::Rgb(0, 0, 0),
+        bg_color: ColorRef::Rgb(255, 255, 255),
+        mask_image: None,
+        transform: Some(transform),
+        bilinear: false,
+    };
+    let dst_rect = IntRect::from(sprite_rect.left, sprite_rect.top, sprite_rect.left + width, sprite_rect.top + height);
+    bitmap.copy_pixels_with_params(palettes, &text_bitmap, dst_rect, IntRect::from(0, 0, width, height), &params);
+}
+
+fn draw_cursor(player: &DirPlayer, bitmap: &mut Bitmap, palettes: &PaletteMap) {
+    let cursor_list = match get_active_cursor(player) {
+        CursorRef::Member(x) => Some(x),
+        _ => None,
+    };
     let cursor_bitmap_member = cursor_list
         .and_then(|x| x.first().map(|x| *x)) // TODO: what to do with other values? maybe animate?
         .and_then(|x| player.movie.cast_manager.find_member_by_slot_number(x as u32))
@@ -220,6 +352,8 @@ fn draw_cursor(player: &DirPlayer, bitmap: &mut Bitmap, palettes: &PaletteMap) {
                 bg_color: bitmap.get_bg_color_ref(),
                 color: bitmap.get_fg_color_ref(),
                 mask_image: mask.as_ref(),
+                transform: None,
+                bilinear: false,
             }
         );
     }
@@ -300,12 +434,13 @@ impl PlayerCanvasRenderer {
                     palettes,
                     1.0
                 );
-                bitmap.copy_pixels(
+                let params = CopyPixelsParams::default(&bitmap);
+                bitmap.copy_pixels_with_params(
                     &palettes,
                     sprite_bitmap,
                     IntRect::from(0, 0, sprite_bitmap.width as i32, sprite_bitmap.height as i32),
                     IntRect::from(0, 0, sprite_bitmap.width as i32, sprite_bitmap.height as i32),
-                    &HashMap::new(),
+                    &params,
                 );
                 bitmap.set_pixel(sprite_member.reg_point.0 as i32, sprite_member.reg_point.1 as i32, (255, 0, 255), palettes);
 
@@ -370,7 +505,14 @@ impl PlayerCanvasRenderer {
 
         if let Some(font) = player.font_manager.get_system_font() {
             let font_bitmap = player.bitmap_manager.get_bitmap(font.bitmap_ref).unwrap();
-            let txt = format!("Datum count: {}\nScript count: {}", player.allocator.datum_count(), player.allocator.script_instance_count());
+            let stats = &player.allocator.cycle_collector_stats;
+            let txt = format!(
+                "Datum count: {}\nScript count: {}\nCycles collected: {} datums, {} scripts",
+                player.allocator.datum_count(),
+                player.allocator.script_instance_count(),
+                stats.datums_collected,
+                stats.script_instances_collected,
+            );
             bitmap.draw_text(
                 txt.as_str(),
                 font, 
@@ -384,16 +526,22 @@ impl PlayerCanvasRenderer {
                 0
             );
         }
-        let slice_data = Clamped(bitmap.data.as_slice());
+        self.ctx2d.present_frame(bitmap);
+    }
+}
+
+impl FramePresenter for web_sys::CanvasRenderingContext2d {
+    fn present_frame(&mut self, stage: &Bitmap) {
+        let slice_data = Clamped(stage.data.as_slice());
         let image_data = web_sys::ImageData::new_with_u8_clamped_array_and_sh(
             slice_data,
-            bitmap.width.into(),
-            bitmap.height.into(),
+            stage.width.into(),
+            stage.height.into(),
         );
-        self.ctx2d.set_fill_style(&JsValue::from_str("white"));
+        self.set_fill_style(&JsValue::from_str("white"));
         match image_data {
             Ok(image_data) => {
-                self.ctx2d.put_image_data(&image_data, 0.0, 0.0).unwrap();
+                self.put_image_data(&image_data, 0.0, 0.0).unwrap();
             }
             _ => {}
         }
@@ -597,3 +745,88 @@ async fn run_draw_loop() {
     let cb = cb.as_ref().unwrap();
     request_animation_frame(&cb);
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        director::enums::ShapeInfo,
+        player::{
+            cast_lib::cast_member_ref,
+            cast_member::{BitmapMember, FilmLoopMember, ShapeMember},
+            reserve_player_mut,
+            testing::{add_test_cast, test_film_loop, with_test_player},
+        },
+    };
+
+    use super::*;
+
+    /// Draws a 100x50 film loop with a 20x10 black square at (40, 10), shown by a 50x50 sprite at
+    /// the top left of a white bitmap. Returns the colors of the row of pixels across the square.
+    fn draw_film_loop(crop: bool) -> Vec<(u8, u8, u8)> {
+        with_test_player(|_| {
+            let mut loop_member = test_film_loop((0, 0, 100, 50), &[&[(1, 2, 40, 10, 20, 10)]]);
+            if let CastMemberType::FilmLoop(FilmLoopMember { info, .. }) = &mut loop_member {
+                info.crop = crop;
+            }
+            let shape_info = ShapeInfo::from(&[0, 1, 0, 0, 0, 0, 0, 10, 0, 20][..]);
+            let cast_lib = add_test_cast(vec![(1, "loop", loop_member), (2, "square", CastMemberType::Shape(ShapeMember { shape_info }))]);
+            reserve_player_mut(|player| {
+                let palettes = PaletteMap::new();
+                let mut bitmap = Bitmap::new(100, 50, 32, PaletteRef::BuiltIn(get_system_default_palette()));
+                bitmap.fill_rect(0, 0, 100, 50, (255, 255, 255), &palettes, 1.0);
+                let mut sprite = Sprite::new(1);
+                sprite.member = Some(cast_member_ref(cast_lib as i32, 1));
+                (sprite.loc_h, sprite.loc_v) = (50, 25);
+                (sprite.width, sprite.height) = (50, 50);
+                sprite.film_loop_frame = 1;
+                draw_sprite(player, &mut bitmap, &sprite, &palettes);
+                (0..100).map(|x| bitmap.get_pixel_color(&palettes, x, 15)).collect()
+            })
+        })
+    }
+
+    fn black_columns(row: &[(u8, u8, u8)]) -> Vec<usize> {
+        row.iter().enumerate().filter(|(_, color)| **color == (0, 0, 0)).map(|(x, _)| x).collect()
+    }
+
+    #[test]
+    fn cropping_film_loops_are_cut_off_at_the_sprite_rect() {
+        assert_eq!(black_columns(&draw_film_loop(true)), (40..50).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn scaling_film_loops_are_stretched_to_the_sprite_rect() {
+        assert_eq!(black_columns(&draw_film_loop(false)), (20..30).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn stretched_bitmaps_fill_the_sprite_rect_around_their_reg_point() {
+        with_test_player(|_| {
+            let palettes = PaletteMap::new();
+            let mut src_bitmap = Bitmap::new(10, 10, 32, PaletteRef::BuiltIn(get_system_default_palette()));
+            src_bitmap.fill_rect(0, 0, 10, 10, (255, 255, 255), &palettes, 1.0);
+            src_bitmap.fill_rect(0, 0, 5, 10, (0, 0, 0), &palettes, 1.0);
+            let image_ref = reserve_player_mut(|player| player.bitmap_manager.add_bitmap(src_bitmap));
+            let cast_lib = add_test_cast(vec![(1, "half", CastMemberType::Bitmap(BitmapMember { image_ref, reg_point: (5, 5) }))]);
+            reserve_player_mut(|player| {
+                let mut bitmap = Bitmap::new(40, 40, 32, PaletteRef::BuiltIn(get_system_default_palette()));
+                bitmap.fill_rect(0, 0, 40, 40, (255, 255, 255), &palettes, 1.0);
+                let mut sprite = Sprite::new(1);
+                sprite.member = Some(cast_member_ref(cast_lib as i32, 1));
+                (sprite.loc_h, sprite.loc_v) = (20, 20);
+                (sprite.width, sprite.height) = (20, 20);
+
+                // The reg point stays at the center of the bitmap, at the loc of the sprite
+                let rect = get_untransformed_sprite_rect(player, &sprite);
+                assert_eq!((rect.left, rect.top, rect.right, rect.bottom), (10, 10, 30, 30));
+                draw_sprite(player, &mut bitmap, &sprite, &palettes);
+                for y in [10, 20, 29] {
+                    let row = (0..40).map(|x| bitmap.get_pixel_color(&palettes, x, y)).collect::<Vec<_>>();
+                    assert_eq!(black_columns(&row), (10..20).collect::<Vec<_>>());
+                }
+                assert_eq!(black_columns(&(0..40).map(|x| bitmap.get_pixel_color(&palettes, x, 9)).collect::<Vec<_>>()), vec![]);
+                assert_eq!(black_columns(&(0..40).map(|x| bitmap.get_pixel_color(&palettes, x, 30)).collect::<Vec<_>>()), vec![]);
+            });
+        });
+    }
+}
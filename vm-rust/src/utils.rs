@@ -1,8 +1,10 @@
-use chrono::Local;
 use itertools::Itertools;
+use chrono::{DateTime, FixedOffset, Local, TimeZone, Utc};
 use url::Url;
 use wasm_bindgen::JsValue;
 
+use crate::platform::{has_js_host, platform};
+
 pub fn set_panic_hook() {
     // When the `console_error_panic_hook` feature is enabled, we can call the
     // `set_panic_hook` function at least once during initialization, and then
@@ -15,20 +17,32 @@ pub fn set_panic_hook() {
 }
 
 pub fn log_i(value: &str) {
-    web_sys::console::log_1(&JsValue::from_str(value))
+    if has_js_host() {
+        web_sys::console::log_1(&JsValue::from_str(value))
+    } else {
+        log::info!("{}", value)
+    }
 }
 
 #[macro_export]
 macro_rules! console_warn {
   ($($arg:tt)*) => (
-    web_sys::console::warn_1(&wasm_bindgen::JsValue::from_str(&format_args!($($arg)*).to_string().as_str()))
+    if $crate::platform::has_js_host() {
+      web_sys::console::warn_1(&wasm_bindgen::JsValue::from_str(&format_args!($($arg)*).to_string().as_str()))
+    } else {
+      log::warn!($($arg)*)
+    }
   )
 }
 
 #[macro_export]
 macro_rules! console_error {
   ($($arg:tt)*) => (
-    web_sys::console::error_1(&wasm_bindgen::JsValue::from_str(&format_args!($($arg)*).to_string().as_str()))
+    if $crate::platform::has_js_host() {
+      web_sys::console::error_1(&wasm_bindgen::JsValue::from_str(&format_args!($($arg)*).to_string().as_str()))
+    } else {
+      log::error!($($arg)*)
+    }
   )
 }
 
@@ -61,11 +75,39 @@ impl ToHexString for Vec<u8> {
     }
 }
 
+/// The current clock time in the local timezone, for `the time` and `the date`.
+/// Virtual clocks use UTC so that deterministic runs don't depend on the host timezone.
+pub fn get_local_time() -> DateTime<FixedOffset> {
+  let clock = platform().clock.clone();
+  let millis = clock.now_millis();
+  if clock.is_virtual() {
+    return Utc.timestamp_millis_opt(millis).single().unwrap_or_default().fixed_offset();
+  }
+  Local.timestamp_millis_opt(millis).single().unwrap_or_else(Local::now).fixed_offset()
+}
+
 pub fn get_ticks() -> u32 {
-  let time: chrono::DateTime<Local> = Local::now();
   // 60 ticks per second
-  let millis = time.timestamp_millis();
-  (millis as f32 / (1000.0 / 60.0)) as u32
+  let millis = platform().clock.now_millis();
+  (millis as f64 / (1000.0 / 60.0)) as u32
+}
+
+/// Returns a random number in `[0, 1)`.
+pub fn random() -> f64 {
+  if has_js_host() {
+    return js_sys::Math::random();
+  }
+  // xorshift64*, seeded from the clock
+  static mut RANDOM_STATE: u64 = 0;
+  unsafe {
+    if RANDOM_STATE == 0 {
+      RANDOM_STATE = platform().clock.now_millis() as u64 | 1;
+    }
+    RANDOM_STATE ^= RANDOM_STATE >> 12;
+    RANDOM_STATE ^= RANDOM_STATE << 25;
+    RANDOM_STATE ^= RANDOM_STATE >> 27;
+    (RANDOM_STATE.wrapping_mul(0x2545F4914F6CDD1D) >> 11) as f64 / (1u64 << 53) as f64
+  }
 }
 
 pub fn get_elapsed_ticks(tick_start: u32) -> i32 {
@@ -0,0 +1,126 @@
+//! Runs the headless CLI on a minimal movie and checks the stage it writes.
+
+use std::{fs, process::Command};
+
+use vm_rust::platform::png::decode_png;
+
+const STAGE_COLOR: (u8, u8, u8) = (10, 200, 30);
+const STAGE_WIDTH: u16 = 32;
+const STAGE_HEIGHT: u16 = 24;
+
+fn chunk(fourcc: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut bytes = fourcc.to_vec();
+    bytes.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(payload);
+    bytes
+}
+
+fn u16s(values: &[u16]) -> Vec<u8> {
+    values.iter().flat_map(|x| x.to_be_bytes()).collect()
+}
+
+fn u32s(values: &[u32]) -> Vec<u8> {
+    values.iter().flat_map(|x| x.to_be_bytes()).collect()
+}
+
+/// A Director 7 movie config with a stage of `STAGE_WIDTH` by `STAGE_HEIGHT` in `STAGE_COLOR`.
+fn config() -> Vec<u8> {
+    let mut config = vec![0u8; 68];
+    config[0..2].copy_from_slice(&68u16.to_be_bytes());
+    config[4..12].copy_from_slice(&u16s(&[0, 0, STAGE_HEIGHT, STAGE_WIDTH]));
+    config[18] = STAGE_COLOR.1;
+    config[19] = STAGE_COLOR.2;
+    config[26] = 1;
+    config[27] = STAGE_COLOR.0;
+    config[28..30].copy_from_slice(&32u16.to_be_bytes());
+    config[36..38].copy_from_slice(&1224u16.to_be_bytes());
+    config[54..56].copy_from_slice(&30u16.to_be_bytes());
+    config
+}
+
+/// A score with a single empty frame and no sprites.
+fn score() -> Vec<u8> {
+    let mut frame_data = u32s(&[22, 0, 1]);
+    frame_data.extend(u16s(&[13, 48, 8, 0]));
+    // One frame without any channel changes
+    frame_data.extend(u16s(&[2]));
+
+    let entries = [frame_data, vec![], vec![]];
+    let mut score = u32s(&[0, 0, 0, entries.len() as u32, entries.len() as u32 + 1, 0]);
+    let mut offset = 0;
+    score.extend(u32s(&[0]));
+    for entry in &entries {
+        offset += entry.len() as u32;
+        score.extend(u32s(&[offset]));
+    }
+    for entry in &entries {
+        score.extend_from_slice(entry);
+    }
+    score
+}
+
+/// An uncompressed RIFX movie with just a memory map, an empty key table, a config and a score.
+fn movie() -> Vec<u8> {
+    let key_table = [u16s(&[12, 12]), u32s(&[0, 0])].concat();
+    let chunks: Vec<([u8; 4], Vec<u8>)> = vec![
+        (*b"KEY*", key_table),
+        (*b"VWCF", config()),
+        (*b"VWSC", score()),
+    ];
+
+    let imap_offset = 12;
+    let mmap_offset = imap_offset + 8 + 24;
+    let entry_count = chunks.len() as u32 + 3;
+    let mmap_length = 24 + 20 * entry_count;
+    let mut offset = mmap_offset + 8 + mmap_length;
+    let mut entries = vec![
+        (*b"RIFX", 0, 0),
+        (*b"imap", 24, imap_offset),
+        (*b"mmap", mmap_length, mmap_offset),
+    ];
+    for (fourcc, payload) in &chunks {
+        entries.push((*fourcc, payload.len() as u32, offset));
+        offset += 8 + payload.len() as u32;
+    }
+
+    let mut mmap = [u16s(&[24, 20]), u32s(&[entry_count, entry_count, 0, 0, 0])].concat();
+    for (fourcc, length, offset) in &entries {
+        mmap.extend_from_slice(fourcc);
+        mmap.extend(u32s(&[*length, *offset]));
+        mmap.extend(u16s(&[0, 0]));
+        mmap.extend(u32s(&[0]));
+    }
+
+    let mut body = b"MV93".to_vec();
+    body.extend(chunk(b"imap", &u32s(&[1, mmap_offset, 1224, 0, 0, 0])));
+    body.extend(chunk(b"mmap", &mmap));
+    for (fourcc, payload) in &chunks {
+        body.extend(chunk(fourcc, payload));
+    }
+    chunk(b"RIFX", &body)
+}
+
+#[test]
+fn writes_the_stage_of_a_movie_to_a_png() {
+    let dir = std::env::temp_dir().join(format!("dirplayer-cli-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let movie_path = dir.join("movie.dir");
+    let output_path = dir.join("stage.png");
+    fs::write(&movie_path, movie()).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_dirplayer-cli"))
+        .arg(&movie_path)
+        .args(["--frames", "1", "--deterministic", "--out"])
+        .arg(&output_path)
+        .arg("--data-dir")
+        .arg(dir.join("data"))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let image = decode_png(&fs::read(&output_path).unwrap()).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!((image.width, image.height), (STAGE_WIDTH as u32, STAGE_HEIGHT as u32));
+    let (r, g, b) = STAGE_COLOR;
+    assert!(image.data.chunks(4).all(|pixel| pixel[0..3] == [r, g, b]));
+}